@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::Category;
+
+// Codes Splitwise categories have in an external system: YNAB categories, ledger accounts,
+// company expense codes. They come from CATEGORY_MAPPING_FILE, a JSON object of category ->
+// code where a category is named by its ID, its name or its "Parent > Name" path (Splitwise has
+// an "Other" in every parent), e.g. {"Groceries": "Food:Groceries", "Utilities > Other": "6290"}.
+// A subcategory without an entry of its own takes its parent's code, and "*" is the code of
+// everything else. Every export writes the code beside the category.
+
+// Entry for every category left without a code
+const FALLBACK: &str = "*";
+
+pub struct CategoryMapping {
+    // Normalized ID, name or path -> code
+    codes: HashMap<String, String>,
+    // The entries as written, for reporting the ones that name no category
+    entries: BTreeMap<String, String>,
+}
+
+/// How a mapping covers the category list.
+pub struct Coverage {
+    // (category ID, path, code, entry the code came from)
+    pub mapped: Vec<(i64, String, String, String)>,
+    // (category ID, path)
+    pub unmapped: Vec<(i64, String)>,
+    // Entries that match no category, probably misspelled
+    pub unknown_entries: Vec<String>,
+}
+
+impl CategoryMapping {
+    pub fn new(entries: BTreeMap<String, String>) -> Self {
+        let codes = entries.iter().map(|(key, code)| (normalize(key), code.clone())).collect();
+        Self { codes, entries }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read CATEGORY_MAPPING_FILE {}", path))?;
+        let entries: BTreeMap<String, String> = serde_json::from_str(&text)
+            .with_context(|| format!("CATEGORY_MAPPING_FILE {} must be a JSON object of category -> code", path))?;
+        Ok(Self::new(entries))
+    }
+
+    /// The code of category `id` (with `name` for categories missing from the list), and the entry it came from.
+    pub fn code(&self, categories: &[Category], id: i64, name: &str) -> Option<(&str, String)> {
+        let found = categories.iter().find_map(|parent| {
+            if parent.id == id {
+                return Some((None, parent));
+            }
+            let sub = parent.subcategories.as_deref()?.iter().find(|sub| sub.id == id)?;
+            Some((Some(sub), parent))
+        });
+        let own = |id: i64, name: &str, path: Option<String>| {
+            let keys = [Some(id.to_string()), path, Some(name.to_string())];
+            keys.into_iter().flatten().find_map(|key| Some((self.codes.get(&normalize(&key))?.as_str(), key)))
+        };
+        let mapped = match found {
+            Some((Some(sub), parent)) => own(sub.id, &sub.name, Some(path(&parent.name, &sub.name)))
+                .or_else(|| own(parent.id, &parent.name, None)),
+            Some((None, parent)) => own(parent.id, &parent.name, None),
+            None => own(id, name, None),
+        };
+        mapped.or_else(|| Some((self.codes.get(FALLBACK)?.as_str(), FALLBACK.to_string())))
+    }
+
+    /// Which categories an expense can have get a code, and which entries name no category.
+    pub fn coverage(&self, categories: &[Category]) -> Coverage {
+        let mut coverage = Coverage { mapped: Vec::new(), unmapped: Vec::new(), unknown_entries: Vec::new() };
+        let mut known: Vec<String> = vec![FALLBACK.to_string()];
+        for parent in categories {
+            known.extend([parent.id.to_string(), normalize(&parent.name)]);
+            let subs = parent.subcategories.as_deref().unwrap_or(&[]);
+            // Expenses are filed under subcategories; a parent without any is used directly
+            let assignable: Vec<(i64, &str, String)> = if subs.is_empty() {
+                vec![(parent.id, parent.name.as_str(), parent.name.clone())]
+            } else {
+                subs.iter().map(|sub| (sub.id, sub.name.as_str(), path(&parent.name, &sub.name))).collect()
+            };
+            for (id, name, path) in assignable {
+                known.extend([id.to_string(), normalize(name), normalize(&path)]);
+                match self.code(categories, id, name) {
+                    Some((code, entry)) => coverage.mapped.push((id, path, code.to_string(), entry)),
+                    None => coverage.unmapped.push((id, path)),
+                }
+            }
+        }
+        coverage.unknown_entries = self.entries.keys().filter(|key| !known.contains(&normalize(key))).cloned().collect();
+        coverage
+    }
+}
+
+fn path(parent: &str, name: &str) -> String {
+    format!("{} > {}", parent, name)
+}
+
+fn normalize(key: &str) -> String {
+    key.split('>').map(|part| part.trim().to_lowercase()).collect::<Vec<_>>().join(" > ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Subcategory;
+
+    fn category(id: i64, name: &str, subs: &[(i64, &str)]) -> Category {
+        Category {
+            id,
+            name: name.to_string(),
+            subcategories: Some(
+                subs.iter()
+                    .map(|&(id, name)| Subcategory { id, name: name.to_string(), ..Default::default() })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn subcategories_fall_back_to_their_parent_then_the_default() {
+        let categories = [
+            category(1, "Utilities", &[(11, "Electricity"), (12, "Other")]),
+            category(2, "Food and drink", &[(21, "Groceries"), (22, "Other")]),
+            category(3, "Entertainment", &[(31, "Movies")]),
+        ];
+        let entries = [("groceries", "Food:Groceries"), ("Utilities", "6200"), ("food and drink>other", "5190"), ("Movis", "7100")];
+        let mapping = CategoryMapping::new(entries.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect());
+        assert_eq!(mapping.code(&categories, 21, "Groceries").unwrap().0, "Food:Groceries");
+        assert_eq!(mapping.code(&categories, 12, "Other").unwrap(), ("6200", "Utilities".to_string()));
+        assert_eq!(mapping.code(&categories, 22, "Other").unwrap().0, "5190");
+        assert_eq!(mapping.code(&categories, 31, "Movies"), None);
+
+        let coverage = mapping.coverage(&categories);
+        assert_eq!(coverage.unmapped, vec![(31, "Entertainment > Movies".to_string())]);
+        assert_eq!(coverage.unknown_entries, vec!["Movis".to_string()]);
+
+        let mut entries: BTreeMap<String, String> = BTreeMap::new();
+        entries.insert("*".to_string(), "9999".to_string());
+        let mapping = CategoryMapping::new(entries);
+        assert_eq!(mapping.code(&categories, 31, "Movies").unwrap(), ("9999", "*".to_string()));
+        assert!(mapping.coverage(&categories).unmapped.is_empty());
+    }
+}
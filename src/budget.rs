@@ -0,0 +1,224 @@
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Limits on what one tool call may cost, so a broad question on a large account gets a partial
+// answer instead of paging through the whole history. The batching loops ask `next_page` before
+// each batch and stop once the call's Splitwise requests or pages are spent; the result then
+// carries a `_budget` notice saying so. Results over the response size limit lose list items
+// from the end. A deadline (timeout_ms) stops the loops the same way, so a slow scan returns
+// what it gathered, with next_offset to continue from, before the MCP client gives up on it and
+// discards the work. Outside of a budgeted scope (background jobs) nothing is limited.
+
+/// Per-call limits; 0 means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub splitwise_calls: usize,
+    pub pages: usize,
+    pub response_bytes: usize,
+    pub timeout_ms: u64,
+}
+
+struct Usage {
+    limits: Limits,
+    deadline: Option<Instant>,
+    splitwise_calls: usize,
+    pages: usize,
+    // Which limit stopped a loop, if any
+    exceeded: Option<&'static str>,
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<Mutex<Usage>>;
+}
+
+/// Run `future` under `limits`, returning its output and, when a limit cut it short, the notice.
+pub async fn scoped<F: Future>(limits: Limits, future: F) -> (F::Output, Option<Value>) {
+    let usage = Arc::new(Mutex::new(Usage {
+        limits,
+        deadline: (limits.timeout_ms > 0).then(|| Instant::now() + Duration::from_millis(limits.timeout_ms)),
+        splitwise_calls: 0,
+        pages: 0,
+        exceeded: None,
+    }));
+    let output = CURRENT.scope(usage.clone(), future).await;
+    let usage = usage.lock().unwrap();
+    let notice = usage.exceeded.map(|exceeded| {
+        let (limit, reason) = match exceeded {
+            "splitwise_calls" => (usage.limits.splitwise_calls as u64, "Budget exceeded (Splitwise calls limit of"),
+            "pages" => (usage.limits.pages as u64, "Budget exceeded (pages limit of"),
+            _ => (usage.limits.timeout_ms, "Deadline reached (timeout_ms of"),
+        };
+        json!({
+            "exceeded": exceeded,
+            "limit": limit,
+            "splitwise_calls": usage.splitwise_calls,
+            "pages": usage.pages,
+            "notice": format!(
+                "{} {}): the results are partial. Continue from next_offset where given, refine \
+                 your filter (a date range, a group or a friend), or run the tool with start_job, \
+                 which has no budget",
+                reason, limit
+            ),
+        })
+    });
+    (output, notice)
+}
+
+/// Count a Splitwise request against the current call's budget.
+pub fn record_call() {
+    let _ = CURRENT.try_with(|usage| usage.lock().unwrap().splitwise_calls += 1);
+}
+
+/// Count a page about to be fetched by a batching loop; false once the budget is spent, in
+/// which case the loop should return what it has.
+pub fn next_page() -> bool {
+    CURRENT
+        .try_with(|usage| {
+            let mut usage = usage.lock().unwrap();
+            let limits = usage.limits;
+            let exceeded = if usage.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Some("deadline")
+            } else if limits.splitwise_calls > 0 && usage.splitwise_calls >= limits.splitwise_calls {
+                Some("splitwise_calls")
+            } else if limits.pages > 0 && usage.pages >= limits.pages {
+                Some("pages")
+            } else {
+                None
+            };
+            match exceeded {
+                Some(limit) => {
+                    usage.exceeded.get_or_insert(limit);
+                    false
+                }
+                None => {
+                    usage.pages += 1;
+                    true
+                }
+            }
+        })
+        .unwrap_or(true)
+}
+
+/// Whether a limit has cut the current call short so far.
+pub fn exceeded() -> bool {
+    CURRENT.try_with(|usage| usage.lock().unwrap().exceeded.is_some()).unwrap_or(false)
+}
+
+/// Key under which a paged result lists the offset each of its items was found at, so that
+/// trimming can move next_offset back to the first dropped item. Removed before the result
+/// leaves the server.
+pub const POSITIONS: &str = "_positions";
+
+/// `result` cut down to `max_bytes` of JSON by dropping `items` from the end, with the notice
+/// to attach when anything was dropped. Results without items are returned whole. A paged
+/// result (one with POSITIONS) continues from the first dropped item.
+pub fn fit(mut result: Value, max_bytes: usize) -> (Value, Option<Value>) {
+    let positions = result.as_object_mut().and_then(|obj| obj.remove(POSITIONS));
+    if max_bytes == 0 {
+        return (result, None);
+    }
+    let size = result.to_string().len();
+    if size <= max_bytes {
+        return (result, None);
+    }
+    let Some(items) = result.get_mut("items").and_then(Value::as_array_mut) else {
+        return (result, None);
+    };
+    let total = items.len();
+    // Room for the truncated flag added below, and for a next_offset that was null
+    let mut excess = size - max_bytes + r#","truncated":true"#.len() + 20;
+    while excess > 0 {
+        let Some(item) = items.pop() else { break };
+        // The item and the comma before it
+        excess = excess.saturating_sub(item.to_string().len() + 1);
+    }
+    let kept = items.len();
+    result["returned"] = json!(kept);
+    result["truncated"] = json!(true);
+    if let Some(next) = positions.as_ref().and_then(|p| p.get(kept)).filter(|_| kept < total) {
+        result["next_offset"] = next.clone();
+        result["has_more"] = json!(true);
+    }
+    let notice = json!({
+        "exceeded": "response_bytes",
+        "limit": max_bytes,
+        "dropped_items": total - kept,
+        "notice": format!(
+            "Budget exceeded (response size limit of {} bytes): {} of {} items were dropped. Ask for \
+             fewer fields, a smaller limit or a narrower filter",
+            max_bytes,
+            total - kept,
+            total
+        ),
+    });
+    (result, Some(notice))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn loops_stop_once_the_budget_is_spent() {
+        let limits = Limits { splitwise_calls: 3, pages: 10, response_bytes: 0, timeout_ms: 0 };
+        let (pages, notice) = scoped(limits, async {
+            let mut pages = 0;
+            while next_page() {
+                record_call();
+                pages += 1;
+            }
+            pages
+        })
+        .await;
+        assert_eq!(pages, 3);
+        let notice = notice.unwrap();
+        assert_eq!(notice["exceeded"], "splitwise_calls");
+        assert!(notice["notice"].as_str().unwrap().contains("refine your filter"));
+
+        let limits = Limits { splitwise_calls: 0, pages: 2, response_bytes: 0, timeout_ms: 0 };
+        let (_, notice) = scoped(limits, async { while next_page() {} }).await;
+        assert_eq!(notice.unwrap()["pages"], 2);
+
+        let limits = Limits { splitwise_calls: 0, pages: 0, response_bytes: 0, timeout_ms: 50 };
+        let (pages, notice) = scoped(limits, async {
+            let mut pages = 0;
+            while next_page() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                pages += 1;
+            }
+            pages
+        })
+        .await;
+        assert!((2..=3).contains(&pages));
+        let notice = notice.unwrap();
+        assert_eq!(notice["exceeded"], "deadline");
+        assert!(notice["notice"].as_str().unwrap().starts_with("Deadline reached (timeout_ms of 50)"));
+        // Unbudgeted work is never stopped
+        assert!(next_page());
+    }
+
+    #[test]
+    fn oversized_results_lose_items_from_the_end() {
+        let items: Vec<Value> = (0..100).map(|i| json!({ "id": i, "description": "x".repeat(50) })).collect();
+        let result = json!({ "returned": 100, "items": items, "has_more": false });
+        let (fitted, notice) = fit(result.clone(), 2000);
+        assert!(fitted.to_string().len() <= 2000);
+        assert_eq!(fitted["items"][0]["id"], 0);
+        assert_eq!(fitted["returned"], fitted["items"].as_array().unwrap().len());
+        assert_eq!(fitted["truncated"], true);
+        assert_eq!(notice.unwrap()["exceeded"], "response_bytes");
+        assert!(fit(result, 0).1.is_none());
+
+        // A paged result continues from the first item dropped
+        let positions: Vec<usize> = (0..100).map(|i| 10 + 2 * i).collect();
+        let result = json!({ "returned": 100, "items": items, "has_more": false, "next_offset": null, POSITIONS: positions });
+        let (fitted, _) = fit(result, 2000);
+        let kept = fitted["returned"].as_u64().unwrap();
+        assert!(fitted.to_string().len() <= 2000);
+        assert_eq!(fitted["next_offset"], 10 + 2 * kept);
+        assert_eq!(fitted["has_more"], true);
+        assert!(fitted.get(POSITIONS).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::trace;
+use crate::types::{Category, Friend, Group, User};
+
+// Short-lived copies of slow-changing account data, so resolving a group, user or
+// category name doesn't cost a Splitwise round trip for every argument.
+
+pub struct Cached<T> {
+    name: &'static str,
+    ttl: Duration,
+    slot: Mutex<Option<(Instant, Arc<T>)>>,
+}
+
+impl<T> Cached<T> {
+    fn new(name: &'static str, ttl: Duration) -> Self {
+        Self {
+            name,
+            ttl,
+            slot: Mutex::new(None),
+        }
+    }
+
+    pub async fn get_or_fetch<F, Fut>(&self, fetch: F) -> Result<Arc<T>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some((fetched_at, value)) = self.slot.lock().unwrap().as_ref() {
+            if fetched_at.elapsed() <= self.ttl {
+                trace::record_cache_hit(&format!("lookup:{}", self.name));
+                return Ok(value.clone());
+            }
+        }
+
+        // The lock is not held across the fetch; concurrent misses just fetch twice
+        let value = Arc::new(fetch().await?);
+        *self.slot.lock().unwrap() = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    pub fn invalidate(&self) {
+        *self.slot.lock().unwrap() = None;
+    }
+
+    // The last value fetched, even if expired, without fetching
+    pub fn peek(&self) -> Option<Arc<T>> {
+        self.slot.lock().unwrap().as_ref().map(|(_, value)| value.clone())
+    }
+
+    fn status(&self) -> Value {
+        match self.slot.lock().unwrap().as_ref() {
+            Some((fetched_at, _)) => json!({
+                "cached": true,
+                "age_secs": fetched_at.elapsed().as_secs(),
+                "expired": fetched_at.elapsed() > self.ttl,
+            }),
+            None => json!({ "cached": false }),
+        }
+    }
+}
+
+pub struct LookupCache {
+    pub me: Cached<User>,
+    pub groups: Cached<Vec<Group>>,
+    pub friends: Cached<Vec<Friend>>,
+    pub categories: Cached<Vec<Category>>,
+    // The get_dashboard snapshot served as the splitwise://context resource
+    pub dashboard: Cached<Value>,
+}
+
+impl LookupCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            me: Cached::new("me", ttl),
+            groups: Cached::new("groups", ttl),
+            friends: Cached::new("friends", ttl),
+            categories: Cached::new("categories", ttl),
+            dashboard: Cached::new("dashboard", ttl),
+        }
+    }
+
+    // Call after anything that adds or removes groups, members or friends
+    pub fn invalidate_people(&self) {
+        self.groups.invalidate();
+        self.friends.invalidate();
+        self.dashboard.invalidate();
+    }
+
+    pub fn status(&self) -> Value {
+        json!({
+            "me": self.me.status(),
+            "groups": self.groups.status(),
+            "friends": self.friends.status(),
+            "categories": self.categories.status(),
+            "dashboard": self.dashboard.status(),
+        })
+    }
+}
@@ -0,0 +1,427 @@
+use anyhow::{Context, Result};
+use flate2::{write::ZlibEncoder, Compression, Crc};
+use std::f64::consts::PI;
+use std::io::Write;
+
+// PNG charts for clients that can show images but can't draw charts themselves: a pie of
+// shares, bars per period and a line over time. Charts are drawn on a fixed canvas with a
+// built-in 5x8 pixel font doubled in size; accented letters lose their accents and anything
+// outside ASCII prints as "?". The image is a plain RGB PNG, deflated, usually 10-20 KB.
+
+const WIDTH: usize = 720;
+const HEIGHT: usize = 420;
+
+type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [255, 255, 255];
+const INK: Rgb = [40, 40, 40];
+const GRID: Rgb = [225, 225, 225];
+// Colors of slices and bars, in order
+const PALETTE: [Rgb; 8] = [
+    [78, 121, 167],
+    [242, 142, 43],
+    [225, 87, 89],
+    [118, 183, 178],
+    [89, 161, 79],
+    [237, 201, 72],
+    [176, 122, 161],
+    [156, 117, 95],
+];
+
+// Screen pixels per font pixel, and the width a character takes with its spacing
+const SCALE: i64 = 2;
+const ADVANCE: i64 = 6 * SCALE;
+
+// Plot area of bar and line charts; the left margin holds the value labels
+const LEFT: i64 = 90;
+const RIGHT: i64 = WIDTH as i64 - 20;
+const TOP: i64 = 60;
+const BOTTOM: i64 = HEIGHT as i64 - 50;
+
+/// A pie of (label, value) slices, with a legend of each slice's share. Only positive values count.
+pub fn pie(title: &str, slices: &[(String, f64)]) -> Result<Vec<u8>> {
+    let slices: Vec<&(String, f64)> = slices.iter().filter(|(_, value)| *value > 0.0).collect();
+    let total: f64 = slices.iter().map(|(_, value)| value).sum();
+    anyhow::ensure!(total > 0.0, "Nothing to chart: there are no positive amounts");
+    let mut canvas = Canvas::new(title);
+
+    let (cx, cy, radius) = (200_i64, 235_i64, 150_i64);
+    // Where each slice ends, as a share of the way round from the top, clockwise
+    let ends: Vec<f64> = slices
+        .iter()
+        .scan(0.0, |sum, (_, value)| {
+            *sum += value / total;
+            Some(*sum)
+        })
+        .collect();
+    for y in cy - radius..=cy + radius {
+        for x in cx - radius..=cx + radius {
+            let (dx, dy) = ((x - cx) as f64, (y - cy) as f64);
+            if dx * dx + dy * dy > (radius * radius) as f64 {
+                continue;
+            }
+            let turn = (dx.atan2(-dy) / (2.0 * PI)).rem_euclid(1.0);
+            let slice = ends.iter().position(|end| turn < *end).unwrap_or(ends.len() - 1);
+            canvas.set(x, y, PALETTE[slice % PALETTE.len()]);
+        }
+    }
+
+    let mut y = 80;
+    for (i, (label, value)) in slices.iter().enumerate() {
+        canvas.fill_rect(400, y, 16, 16, PALETTE[i % PALETTE.len()]);
+        let share = format!("{:.0}%", value / total * 100.0);
+        canvas.text(424, y, &cut(label, 17), INK);
+        canvas.text(RIGHT - text_width(&share), y, &share, INK);
+        y += 28;
+    }
+    canvas.png()
+}
+
+/// One bar per (label, value), such as months; negative values go below the zero line.
+pub fn bars(title: &str, bars: &[(String, f64)]) -> Result<Vec<u8>> {
+    anyhow::ensure!(!bars.is_empty(), "Nothing to chart");
+    let mut canvas = Canvas::new(title);
+    let y_of = canvas.value_axis(bars.iter().map(|(_, value)| *value));
+    let slot = (RIGHT - LEFT) as f64 / bars.len() as f64;
+    for (i, (_, value)) in bars.iter().enumerate() {
+        let x = LEFT + (slot * (i as f64 + 0.15)) as i64;
+        let width = ((slot * 0.7) as i64).max(1);
+        let (zero, top) = (y_of(0.0), y_of(*value));
+        canvas.fill_rect(x, zero.min(top), width, (zero - top).abs().max(1), PALETTE[0]);
+    }
+    canvas.label_axis(&labels(bars), LEFT, |i| LEFT + (slot * (i as f64 + 0.5)) as i64);
+    canvas.png()
+}
+
+/// A line through (label, value) points, evenly spaced, such as a balance at the end of each month.
+pub fn line(title: &str, points: &[(String, f64)]) -> Result<Vec<u8>> {
+    anyhow::ensure!(!points.is_empty(), "Nothing to chart");
+    let mut canvas = Canvas::new(title);
+    let y_of = canvas.value_axis(points.iter().map(|(_, value)| *value));
+    // Inset from the ends so the first and last labels fit
+    let inset = 40;
+    let x_of = |i: usize| match points.len() {
+        1 => (LEFT + RIGHT) / 2,
+        n => LEFT + inset + (RIGHT - LEFT - 2 * inset) * i as i64 / (n as i64 - 1),
+    };
+    for (i, pair) in points.windows(2).enumerate() {
+        canvas.line((x_of(i), y_of(pair[0].1)), (x_of(i + 1), y_of(pair[1].1)), PALETTE[0]);
+    }
+    for (i, (_, value)) in points.iter().enumerate() {
+        canvas.fill_rect(x_of(i) - 3, y_of(*value) - 3, 7, 7, PALETTE[0]);
+    }
+    canvas.label_axis(&labels(points), LEFT, x_of);
+    canvas.png()
+}
+
+/// A grid of `cells[row][column]`, each shaded by its value: the largest is the darkest, zero stays gray.
+pub fn heatmap(title: &str, rows: &[&str], columns: &[&str], cells: &[Vec<f64>]) -> Result<Vec<u8>> {
+    anyhow::ensure!(!rows.is_empty() && !columns.is_empty(), "Nothing to chart");
+    let max = cells.iter().flatten().fold(0.0_f64, |max, v| max.max(*v));
+    let mut canvas = Canvas::new(title);
+    canvas.text(20, 36, &format!("Darkest: {}", compact(max, 1.0)), INK);
+
+    let left = 20 + rows.iter().map(|row| text_width(row)).max().unwrap_or(0) + ADVANCE;
+    let (width, height) = ((RIGHT - left) as f64 / columns.len() as f64, (BOTTOM - TOP) as f64 / rows.len() as f64);
+    for (r, row) in rows.iter().enumerate() {
+        let y = TOP + (height * r as f64) as i64;
+        canvas.text(20, y + (height as i64 - 16) / 2, row, INK);
+        for c in 0..columns.len() {
+            let value = cells.get(r).and_then(|row| row.get(c)).copied().unwrap_or(0.0);
+            let x = left + (width * c as f64) as i64;
+            let color = if value > 0.0 && max > 0.0 { shade(value / max) } else { GRID };
+            // One pixel of background between cells
+            let next_x = left + (width * (c + 1) as f64) as i64;
+            let next_y = TOP + (height * (r + 1) as f64) as i64;
+            canvas.fill_rect(x, y, next_x - x - 1, next_y - y - 1, color);
+        }
+    }
+    canvas.label_axis(columns, left, |c| left + (width * (c as f64 + 0.5)) as i64);
+    canvas.png()
+}
+
+struct Canvas {
+    // RGB, row by row
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(title: &str) -> Self {
+        let mut canvas = Self { pixels: BACKGROUND.repeat(WIDTH * HEIGHT) };
+        canvas.text(20, 18, &cut(title, (WIDTH as i64 - 40) as usize / ADVANCE as usize), INK);
+        canvas
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: Rgb) {
+        if (0..WIDTH as i64).contains(&x) && (0..HEIGHT as i64).contains(&y) {
+            let at = (y as usize * WIDTH + x as usize) * 3;
+            self.pixels[at..at + 3].copy_from_slice(&color);
+        }
+    }
+
+    fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: Rgb) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.set(px, py, color);
+            }
+        }
+    }
+
+    // Two pixels thick
+    fn line(&mut self, from: (i64, i64), to: (i64, i64), color: Rgb) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).max(1);
+        for step in 0..=steps {
+            let x = from.0 + (to.0 - from.0) * step / steps;
+            let y = from.1 + (to.1 - from.1) * step / steps;
+            self.fill_rect(x, y, 2, 2, color);
+        }
+    }
+
+    fn text(&mut self, x: i64, y: i64, text: &str, color: Rgb) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as i64 * ADVANCE;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) != 0 {
+                        self.fill_rect(left + column * SCALE, y + row as i64 * SCALE, SCALE, SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+
+    // Grid lines and labels for the values, returning where a value goes on the y axis
+    fn value_axis(&mut self, values: impl Iterator<Item = f64>) -> impl Fn(f64) -> i64 {
+        let (min, max) = values.fold((0.0_f64, 0.0_f64), |(min, max), v| (min.min(v), max.max(v)));
+        let step = nice_step(max - min);
+        let low = (min / step).floor() * step;
+        let high = ((max / step).ceil() * step).max(low + step);
+        let y_of = move |value: f64| BOTTOM - ((value - low) / (high - low) * (BOTTOM - TOP) as f64).round() as i64;
+        let mut tick = low;
+        while tick <= high + step / 2.0 {
+            let y = y_of(tick);
+            let color = if tick.abs() < step / 2.0 { INK } else { GRID };
+            self.fill_rect(LEFT, y, RIGHT - LEFT, 1, color);
+            let label = compact(tick, step);
+            self.text(LEFT - 8 - text_width(&label), y - 8, &label, INK);
+            tick += step;
+        }
+        y_of
+    }
+
+    // Labels under the points of a plot starting at `left`, skipping some when they would overlap
+    fn label_axis(&mut self, labels: &[&str], left: i64, x_of: impl Fn(usize) -> i64) {
+        let widest = labels.iter().map(|label| text_width(label)).max().unwrap_or(0) + ADVANCE;
+        let every = ((widest * labels.len() as i64) / (RIGHT - left) + 1) as usize;
+        for (i, label) in labels.iter().enumerate().step_by(every) {
+            self.text(x_of(i) - text_width(label) / 2, BOTTOM + 12, label, INK);
+        }
+    }
+
+    fn png(&self) -> Result<Vec<u8>> {
+        // Each row starts with its filter type, 0 for none
+        let mut raw = Vec::with_capacity((WIDTH * 3 + 1) * HEIGHT);
+        for row in self.pixels.chunks(WIDTH * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&raw).context("Failed to compress the chart")?;
+        let data = encoder.finish().context("Failed to compress the chart")?;
+
+        let mut header = Vec::new();
+        header.extend((WIDTH as u32).to_be_bytes());
+        header.extend((HEIGHT as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, no filtering method or interlacing beyond the defaults
+        header.extend([8, 2, 0, 0, 0]);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, body) in [(b"IHDR", header), (b"IDAT", data), (b"IEND", Vec::new())] {
+            png.extend((body.len() as u32).to_be_bytes());
+            let mut crc = Crc::new();
+            crc.update(kind);
+            crc.update(&body);
+            png.extend(kind);
+            png.extend(body);
+            png.extend(crc.sum().to_be_bytes());
+        }
+        Ok(png)
+    }
+}
+
+fn labels(points: &[(String, f64)]) -> Vec<&str> {
+    points.iter().map(|(label, _)| label.as_str()).collect()
+}
+
+// From a pale blue for the smallest share of the maximum to a deep blue for all of it
+fn shade(share: f64) -> Rgb {
+    let (pale, deep): (Rgb, Rgb) = ([222, 235, 247], [8, 48, 107]);
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * share.clamp(0.0, 1.0)).round() as u8;
+    [mix(pale[0], deep[0]), mix(pale[1], deep[1]), mix(pale[2], deep[2])]
+}
+
+fn text_width(text: &str) -> i64 {
+    text.chars().count() as i64 * ADVANCE
+}
+
+// `text` in at most `chars` characters
+fn cut(text: &str, chars: usize) -> String {
+    if text.chars().count() <= chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(chars.saturating_sub(2)).collect();
+    format!("{}..", kept.trim_end())
+}
+
+// 1, 2 or 5 times a power of ten, giving about five grid lines over `range`
+fn nice_step(range: f64) -> f64 {
+    if range <= 0.0 {
+        return 1.0;
+    }
+    let rough = range / 5.0;
+    let magnitude = 10_f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * magnitude).find(|step| *step >= rough).unwrap_or(10.0 * magnitude)
+}
+
+// Axis label: 1.2M, 15k, 250 or 0.5
+fn compact(value: f64, step: f64) -> String {
+    let size = value.abs();
+    if size >= 1_000_000.0 {
+        format!("{}M", trim_zeros(value / 1_000_000.0))
+    } else if size >= 10_000.0 {
+        format!("{}k", trim_zeros(value / 1000.0))
+    } else if step < 1.0 {
+        format!("{:.1}", value)
+    } else {
+        format!("{:.0}", value)
+    }
+}
+
+fn trim_zeros(value: f64) -> String {
+    let text = format!("{:.1}", value);
+    text.strip_suffix(".0").unwrap_or(&text).to_string()
+}
+
+fn glyph(c: char) -> &'static [u8; 8] {
+    let c = match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' => 'a',
+        'Á' | 'À' | 'Â' | 'Ä' | 'Ã' => 'A',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'É' | 'È' | 'Ê' | 'Ë' => 'E',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+        'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' => 'O',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ç' => 'c',
+        'Ç' => 'C',
+        ' '..='~' => c,
+        _ => '?',
+    };
+    &FONT[c as usize - ' ' as usize]
+}
+
+// 5x8 bitmaps of the printable ASCII characters, one byte per row with the leftmost pixel in
+// bit 4; capitals take the top seven rows and the last row holds descenders
+const FONT: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04, 0x00],
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A, 0x00],
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04, 0x00], [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03, 0x00],
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D, 0x00], [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02, 0x00], [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08, 0x00],
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00, 0x00], [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00, 0x00],
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E, 0x00], [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F, 0x00], [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E, 0x00],
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02, 0x00], [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E, 0x00],
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E, 0x00], [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08, 0x00],
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E, 0x00], [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C, 0x00],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00, 0x00], [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02, 0x00], [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00, 0x00],
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08, 0x00], [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04, 0x00],
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E, 0x00], [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00],
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E, 0x00], [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E, 0x00],
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C, 0x00], [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F, 0x00],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10, 0x00], [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F, 0x00],
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00], [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00],
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C, 0x00], [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11, 0x00],
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F, 0x00], [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11, 0x00],
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x00], [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00],
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10, 0x00], [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D, 0x00],
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11, 0x00], [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E, 0x00],
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00], [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00], [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A, 0x00],
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11, 0x00], [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x00],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F, 0x00], [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E, 0x00],
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00, 0x00], [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E, 0x00],
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x00],
+    [0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F, 0x00],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E, 0x00], [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E, 0x00],
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F, 0x00], [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E, 0x00],
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08, 0x00], [0x00, 0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00], [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E, 0x00],
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x02, 0x12, 0x0C], [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12, 0x00],
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00], [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11, 0x00],
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00], [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E, 0x00],
+    [0x00, 0x00, 0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10], [0x00, 0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x01],
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10, 0x00], [0x00, 0x00, 0x0F, 0x10, 0x0E, 0x01, 0x1E, 0x00],
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06, 0x00], [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D, 0x00],
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00], [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A, 0x00],
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x00], [0x00, 0x00, 0x11, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F, 0x00], [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02, 0x00],
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00], [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08, 0x00],
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00, 0x00],
+
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // Width, height and pixels of a PNG written by Canvas::png
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        let length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(&png[41..41 + length]).read_to_end(&mut raw).unwrap();
+        assert_eq!(raw.len(), (width as usize * 3 + 1) * height as usize);
+        (width, height, raw)
+    }
+
+    fn pixel(raw: &[u8], x: usize, y: usize) -> Rgb {
+        let at = y * (WIDTH * 3 + 1) + 1 + x * 3;
+        [raw[at], raw[at + 1], raw[at + 2]]
+    }
+
+    #[test]
+    fn pie_slices_go_clockwise_from_the_top() {
+        let slices = [("Rent".to_string(), 750.0), ("Groceries".to_string(), 250.0), ("Refund".to_string(), -20.0)];
+        let (width, height, raw) = decode(&pie("Spending", &slices).unwrap());
+        assert_eq!((width, height), (720, 420));
+        // Right of the center is a quarter of the way round, in the first three quarters
+        assert_eq!(pixel(&raw, 300, 235), PALETTE[0]);
+        assert_eq!(pixel(&raw, 100, 200), PALETTE[1]);
+        assert_eq!(pixel(&raw, 5, 5), BACKGROUND);
+        assert!(pie("Nothing", &[("Refund".to_string(), -5.0)]).is_err());
+    }
+
+    #[test]
+    fn bars_stand_on_the_zero_line() {
+        let months = [("2024-01".to_string(), 100.0), ("2024-02".to_string(), 0.0), ("2024-03".to_string(), 40.0)];
+        let (_, _, raw) = decode(&bars("Monthly", &months).unwrap());
+        let slot = (RIGHT - LEFT) as usize / 3;
+        let middle = |i: usize| LEFT as usize + slot * i + slot / 2;
+        assert_eq!(pixel(&raw, middle(0), (BOTTOM - 5) as usize), PALETTE[0]);
+        assert_eq!(pixel(&raw, middle(0), (TOP + 5) as usize), PALETTE[0]);
+        assert_ne!(pixel(&raw, middle(2), (TOP + 5) as usize), PALETTE[0]);
+        assert_eq!((nice_step(100.0), nice_step(730.0), compact(15000.0, 5000.0)), (20.0, 200.0, "15k".to_string()));
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context as _, Result};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, Ordering};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+// MCP logging: selected tracing events (tool calls finishing or failing, Splitwise API errors
+// and rate limits) are also sent to the client as notifications/message, at or above
+// the level it asked for with logging/setLevel. Events reach the transport handling the request
+// through a task-local sender; anything logged elsewhere (startup, background jobs) only goes
+// to the console.
+
+// MCP's syslog-style levels, least severe first
+const LEVELS: [&str; 8] = ["debug", "info", "notice", "warning", "error", "critical", "alert", "emergency"];
+
+// Targets (the last part of the module path) whose events are forwarded
+const FORWARDED: [&str; 2] = ["tools", "splitwise"];
+
+// Until the client chooses a level, only problems are sent ("warning")
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(3);
+
+tokio::task_local! {
+    static SINK: UnboundedSender<Value>;
+}
+
+/// Apply a logging/setLevel request.
+pub fn set_level(level: &str) -> Result<()> {
+    let index = LEVELS
+        .iter()
+        .position(|l| l.eq_ignore_ascii_case(level.trim()))
+        .with_context(|| format!("Unknown log level '{}'; use one of {}", level, LEVELS.join(", ")))?;
+    MIN_LEVEL.store(index as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Run `future`, sending the events it logs to `sink` as notifications/message.
+pub async fn forward<F: Future>(sink: UnboundedSender<Value>, future: F) -> F::Output {
+    SINK.scope(sink, future).await
+}
+
+/// The layer that forwards events, to install next to the console output.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    ClientLog.with_filter(filter_fn(forwarded))
+}
+
+fn forwarded(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event() && FORWARDED.contains(&logger(metadata))
+}
+
+fn logger<'a>(metadata: &Metadata<'a>) -> &'a str {
+    metadata.target().rsplit("::").next().unwrap_or_default()
+}
+
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 4,
+        Level::WARN => 3,
+        Level::INFO => 1,
+        _ => 0,
+    }
+}
+
+struct ClientLog;
+
+impl<S: Subscriber> Layer<S> for ClientLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let severity = severity(event.metadata().level());
+        if severity < MIN_LEVEL.load(Ordering::Relaxed) {
+            return;
+        }
+        // Outside a forwarded request there is nobody to tell
+        let _ = SINK.try_with(|sink| {
+            let mut fields = Fields(Map::new());
+            event.record(&mut fields);
+            let _ = sink.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": LEVELS[severity as usize],
+                    "logger": logger(event.metadata()),
+                    "data": fields.0,
+                }
+            }));
+        });
+    }
+}
+
+// An event's fields as JSON; the formatted text is under "message"
+struct Fields(Map<String, Value>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[tokio::test]
+    async fn forwards_selected_events_at_the_chosen_level() {
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer()));
+        let (sink, mut received) = tokio::sync::mpsc::unbounded_channel();
+
+        set_level("info").unwrap();
+        forward(sink, async {
+            tracing::info!(target: "app::tools", elapsed_ms = 12u64, "Tool list_groups finished");
+            tracing::debug!(target: "app::tools", "Below the level");
+            tracing::warn!(target: "hyper::client", "Not a forwarded target");
+        })
+        .await;
+        // Nobody is listening outside forward()
+        tracing::warn!(target: "app::splitwise", "Not sent");
+
+        let message = received.try_recv().unwrap();
+        assert_eq!(message["method"], "notifications/message");
+        assert_eq!(message["params"]["level"], "info");
+        assert_eq!(message["params"]["logger"], "tools");
+        assert_eq!(message["params"]["data"], json!({ "message": "Tool list_groups finished", "elapsed_ms": 12 }));
+        assert!(received.try_recv().is_err());
+
+        assert!(set_level("loud").is_err());
+        set_level("warning").unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::splitwise::DEFAULT_USER_AGENT;
+
+// Runtime configuration shared by all transports, read once from the environment at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Transport serving this process ("stdio" or "http"), set by each binary
+    pub transport: &'static str,
+    // Serve in-memory fixture data instead of a real Splitwise account
+    pub sandbox: bool,
+    // Exchange rate provider: "ecb" (default, no key needed) or "exchangerate_host"
+    pub exchange_rate_provider: String,
+    pub exchange_rate_api_key: Option<String>,
+    // How long fetched exchange rates are reused before refreshing
+    pub exchange_rate_ttl_secs: u64,
+    // How long group, friend and category lists are reused when resolving names
+    pub lookup_cache_ttl_secs: u64,
+    // How long pages of expenses are reused by later scans (EXPENSE_CACHE_TTL_SECS); 0 disables
+    pub expense_cache_ttl_secs: u64,
+    // JSON file of extra category aliases, e.g. {"nafta": "Gas/fuel"}
+    pub category_aliases_file: Option<String>,
+    // JSON file mapping description words to merchant names, e.g. {"mcdonalds": "McDonald's"}
+    pub merchant_aliases_file: Option<String>,
+    // JSON file mapping categories to codes in an external system, e.g. {"Groceries": "5100"}
+    pub category_mapping_file: Option<String>,
+    // Where learned preferences are kept between runs; None in sandbox mode
+    pub data_dir: Option<PathBuf>,
+    // Base64 AES-256 key the data directory is encrypted with (SPLITWISE_MCP_DATA_KEY), or a
+    // file holding it, e.g. a mounted secret (SPLITWISE_MCP_DATA_KEY_FILE)
+    pub data_key: Option<String>,
+    pub data_key_file: Option<String>,
+    // Send mutations form-encoded instead of as JSON (SPLITWISE_REQUEST_ENCODING=form)
+    pub form_encoded_bodies: bool,
+    // User-Agent sent to Splitwise: SPLITWISE_USER_AGENT, or the crate name and version with
+    // SPLITWISE_MCP_CONTACT appended so heavy usage can be traced back to its operator
+    pub user_agent: String,
+    // Send an X-Request-Id header with every Splitwise call (SPLITWISE_REQUEST_IDS=true)
+    pub request_ids: bool,
+    // Outbound connection pool: idle connections kept per host and for how long, and whether
+    // HTTP/2 may be negotiated (SPLITWISE_HTTP2=false forces HTTP/1.1)
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout_secs: u64,
+    pub http2: bool,
+    // Tools per tools/list page (TOOLS_PAGE_SIZE); 0 lists them all at once
+    pub tools_page_size: usize,
+    // JSON file of which tools are offered (read-only mode, an allowlist), read again when it
+    // changes (TOOL_POLICY_FILE; see tool_policy.rs)
+    pub tool_policy_file: Option<String>,
+    // Redaction of personal data in tool results (PII_REDACTION): "off", "first_names" or
+    // "initials"; emails are pseudonymized or, with PII_REDACTION_EMAILS=strip, removed
+    pub pii_redaction: String,
+    pub pii_redaction_emails: String,
+    // Budget of one tool call (see budget.rs); 0 is unlimited. Splitwise requests and pages of
+    // 100 expenses a scan may use (TOOL_MAX_SPLITWISE_CALLS, TOOL_MAX_PAGES), and the size of
+    // the result in bytes (TOOL_MAX_RESPONSE_BYTES)
+    pub tool_max_splitwise_calls: usize,
+    pub tool_max_pages: usize,
+    pub tool_max_response_bytes: usize,
+    // How long a scan may run before returning what it has (TOOL_TIMEOUT_MS), unless the call
+    // passes timeout_ms; 0 waits for the whole scan
+    pub tool_timeout_ms: u64,
+    // Language of tool descriptions (TOOL_LOCALE, e.g. "es"), over the client's and the account's
+    pub tool_locale: Option<String>,
+    // Keep expense writes that can't reach Splitwise and send them later (QUEUE_OFFLINE_WRITES)
+    pub queue_offline_writes: bool,
+    // The only directories file tools may use over HTTP (HTTP_FILE_DIRS, separated like PATH);
+    // client roots aren't trusted there. Empty turns file access off on that transport
+    pub http_file_dirs: Vec<PathBuf>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let sandbox = env_parse("SANDBOX", false);
+        let data_dir = env::var("SPLITWISE_MCP_DATA_DIR")
+            .map(PathBuf::from)
+            .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".splitwise-mcp")))
+            .unwrap_or_else(|_| PathBuf::from(".splitwise-mcp"));
+
+        Self {
+            transport: "stdio",
+            sandbox,
+            exchange_rate_provider: env::var("EXCHANGE_RATE_PROVIDER")
+                .unwrap_or_else(|_| "ecb".to_string()),
+            exchange_rate_api_key: env::var("EXCHANGE_RATE_API_KEY").ok(),
+            exchange_rate_ttl_secs: env_parse("EXCHANGE_RATE_CACHE_TTL_SECS", 3600),
+            lookup_cache_ttl_secs: env_parse("LOOKUP_CACHE_TTL_SECS", 300),
+            expense_cache_ttl_secs: env_parse("EXPENSE_CACHE_TTL_SECS", 120),
+            category_aliases_file: env::var("CATEGORY_ALIASES_FILE").ok(),
+            merchant_aliases_file: env::var("MERCHANT_ALIASES_FILE").ok(),
+            category_mapping_file: env::var("CATEGORY_MAPPING_FILE").ok(),
+            // Sandbox sessions must not leak fixture data into the real store
+            data_dir: (!sandbox).then_some(data_dir),
+            data_key: env::var("SPLITWISE_MCP_DATA_KEY").ok().filter(|key| !key.trim().is_empty()),
+            data_key_file: env::var("SPLITWISE_MCP_DATA_KEY_FILE").ok(),
+            form_encoded_bodies: env::var("SPLITWISE_REQUEST_ENCODING")
+                .is_ok_and(|v| v.trim().eq_ignore_ascii_case("form")),
+            user_agent: env::var("SPLITWISE_USER_AGENT").unwrap_or_else(|_| {
+                match env::var("SPLITWISE_MCP_CONTACT") {
+                    Ok(contact) if !contact.trim().is_empty() => {
+                        format!("{} (+{})", DEFAULT_USER_AGENT, contact.trim())
+                    }
+                    _ => DEFAULT_USER_AGENT.to_string(),
+                }
+            }),
+            request_ids: env_parse("SPLITWISE_REQUEST_IDS", false),
+            pool_max_idle_per_host: env_parse("HTTP_POOL_MAX_IDLE_PER_HOST", 8),
+            pool_idle_timeout_secs: env_parse("HTTP_POOL_IDLE_TIMEOUT_SECS", 90),
+            http2: env_parse("SPLITWISE_HTTP2", true),
+            tools_page_size: env_parse("TOOLS_PAGE_SIZE", 0),
+            tool_policy_file: env::var("TOOL_POLICY_FILE").ok().filter(|f| !f.trim().is_empty()),
+            pii_redaction: env::var("PII_REDACTION").unwrap_or_else(|_| "off".to_string()).trim().to_lowercase(),
+            pii_redaction_emails: env::var("PII_REDACTION_EMAILS")
+                .unwrap_or_else(|_| "hash".to_string())
+                .trim()
+                .to_lowercase(),
+            tool_max_splitwise_calls: env_parse("TOOL_MAX_SPLITWISE_CALLS", 100),
+            tool_max_pages: env_parse("TOOL_MAX_PAGES", 50),
+            tool_max_response_bytes: env_parse("TOOL_MAX_RESPONSE_BYTES", 1_000_000),
+            tool_timeout_ms: env_parse("TOOL_TIMEOUT_MS", 45_000),
+            tool_locale: env::var("TOOL_LOCALE").ok().filter(|l| !l.trim().is_empty()),
+            queue_offline_writes: env_parse("QUEUE_OFFLINE_WRITES", false),
+            http_file_dirs: env::var_os("HTTP_FILE_DIRS")
+                .map(|dirs| env::split_paths(&dirs).filter(|dir| !dir.as_os_str().is_empty()).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// HTTP client with the configured pool, shared by all outbound calls so multi-page scans
+    /// keep reusing warm connections.
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_secs))
+            .tcp_keepalive(Duration::from_secs(60))
+            // Large expense pages compress well; responses are decompressed transparently
+            .gzip(true)
+            .brotli(true);
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+        builder.build().context("Failed to build the HTTP client")
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,106 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+// Encryption of what the server keeps under the data directory (learned preferences, tags,
+// the history export), so a stolen disk or backup doesn't give away the expense history.
+// With SPLITWISE_MCP_DATA_KEY set, every file is written with AES-256-GCM under that key and a
+// fresh random nonce; the file or export line it belongs to is bound in as associated data, so
+// sealed contents can't be swapped between files. Files written before a key was set are still
+// read, and are encrypted the next time they are written.
+
+// Prefix of a sealed document, followed by the nonce and the ciphertext
+const MAGIC: &[u8] = b"SWMCP-AES256GCM1";
+// Prefix of a sealed line in a JSON Lines file, followed by the sealed bytes in base64
+const LINE_PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+pub struct Cipher {
+    aead: Aes256Gcm,
+}
+
+impl Cipher {
+    /// A cipher for a 32-byte key given in base64 (e.g. from `openssl rand -base64 32`).
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let key = STANDARD
+            .decode(key.trim())
+            .context("SPLITWISE_MCP_DATA_KEY is not valid base64")?;
+        if key.len() != 32 {
+            bail!("SPLITWISE_MCP_DATA_KEY must be 32 bytes, got {}", key.len());
+        }
+        let aead = Aes256Gcm::new_from_slice(&key).map_err(|_| anyhow!("Invalid data key"))?;
+        Ok(Self { aead })
+    }
+
+    /// Encrypt `plain`, binding it to `context` (the file it is written to).
+    pub fn seal(&self, context: &str, plain: &[u8]) -> Result<Vec<u8>> {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let sealed = self
+            .aead
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plain, aad: context.as_bytes() })
+            .map_err(|_| anyhow!("Failed to encrypt {}", context))?;
+        Ok([MAGIC, &nonce[..], &sealed].concat())
+    }
+
+    /// Decrypt what `seal` wrote for the same `context`.
+    pub fn open(&self, context: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+        let body = sealed
+            .strip_prefix(MAGIC)
+            .filter(|body| body.len() > NONCE_LEN)
+            .with_context(|| format!("{} is not encrypted", context))?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: context.as_bytes() })
+            .map_err(|_| anyhow!("Failed to decrypt {}: wrong SPLITWISE_MCP_DATA_KEY or damaged file", context))
+    }
+
+    /// One line of a JSON Lines file, sealed.
+    pub fn seal_line(&self, context: &str, line: &str) -> Result<String> {
+        Ok(format!("{}{}", LINE_PREFIX, STANDARD.encode(self.seal(context, line.as_bytes())?)))
+    }
+}
+
+/// Whether a file's contents were written by `Cipher::seal`.
+pub fn is_sealed(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+/// A line as written to a JSON Lines file: decrypted when sealed, as is when written in the clear.
+pub fn open_line(cipher: Option<&Cipher>, context: &str, line: &str) -> Result<String> {
+    let Some(encoded) = line.strip_prefix(LINE_PREFIX) else {
+        return Ok(line.to_string());
+    };
+    let Some(cipher) = cipher else {
+        bail!("{} is encrypted; set SPLITWISE_MCP_DATA_KEY to read it", context);
+    };
+    let sealed = STANDARD.decode(encoded.trim()).with_context(|| format!("Damaged line in {}", context))?;
+    String::from_utf8(cipher.open(context, &sealed)?).with_context(|| format!("Damaged line in {}", context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    #[test]
+    fn sealed_data_opens_only_with_its_key_and_file() {
+        let cipher = Cipher::from_base64(KEY).unwrap();
+        let sealed = cipher.seal("tags", b"{\"1\": [\"trip\"]}").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(4).any(|w| w == b"trip"));
+        assert_eq!(cipher.open("tags", &sealed).unwrap(), b"{\"1\": [\"trip\"]}");
+        assert!(cipher.open("learned_categories", &sealed).is_err());
+        assert_ne!(cipher.seal("tags", b"same").unwrap(), cipher.seal("tags", b"same").unwrap());
+
+        let other = Cipher::from_base64(&STANDARD.encode([7u8; 32])).unwrap();
+        assert!(other.open("tags", &sealed).is_err());
+        assert!(Cipher::from_base64("c2hvcnQ=").is_err());
+
+        let line = cipher.seal_line("history_export.jsonl", "{\"id\":1}").unwrap();
+        assert_eq!(open_line(Some(&cipher), "history_export.jsonl", &line).unwrap(), "{\"id\":1}");
+        assert_eq!(open_line(None, "history_export.jsonl", "{\"id\":2}").unwrap(), "{\"id\":2}");
+        assert!(open_line(None, "history_export.jsonl", &line).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use serde_json::Value;
+use std::fmt::Write;
+
+// The /dashboard page: the get_dashboard snapshot (balances per group and friend, recent
+// expenses) and the server_status figures (Splitwise calls, errors, latency, sync), rendered as
+// plain HTML on the server. It reloads itself every minute; the snapshot comes from the lookup
+// cache, so reloading doesn't cost a Splitwise call each time.
+
+const REFRESH_SECS: u32 = 60;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222;max-width:60rem}\
+h1{font-size:1.4rem}h2{font-size:1.1rem;margin-top:2rem}\
+table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:.3rem .6rem;border-bottom:1px solid #ddd}\
+td.num{text-align:right;font-variant-numeric:tabular-nums}.owed{color:#1a7f37}.owing{color:#c62828}\
+.muted{color:#777}";
+
+/// The page for a dashboard snapshot and a server_status result.
+pub fn render(snapshot: &Value, status: &Value) -> String {
+    let mut page = String::new();
+    let _ = write!(
+        page,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width\">\
+         <meta http-equiv=\"refresh\" content=\"{}\"><title>Splitwise</title><style>{}</style></head><body>",
+        REFRESH_SECS, STYLE
+    );
+    let _ = write!(
+        page,
+        "<h1>{}</h1><p class=\"muted\">As of {}{}</p>",
+        escape(snapshot["me"]["name"].as_str().unwrap_or("Splitwise")),
+        escape(snapshot["as_of"].as_str().unwrap_or_default()),
+        if status["sandbox"] == true { " · sandbox data" } else { "" }
+    );
+
+    page.push_str("<h2>Groups</h2><table><tr><th>Group</th><th>Your balance</th></tr>");
+    for group in snapshot["groups"].as_array().into_iter().flatten() {
+        let _ = write!(page, "<tr><td>{}</td>{}</tr>", escape(&text(&group["name"])), balance_cell(&group["balance"]));
+    }
+    page.push_str("</table>");
+
+    page.push_str("<h2>Friends</h2><table><tr><th>Friend</th><th>Balance</th></tr>");
+    let friends = snapshot["friends_with_balance"].as_array().map(Vec::as_slice).unwrap_or_default();
+    if friends.is_empty() {
+        page.push_str("<tr><td colspan=\"2\" class=\"muted\">All settled up</td></tr>");
+    }
+    for friend in friends {
+        let _ = write!(page, "<tr><td>{}</td>{}</tr>", escape(&text(&friend["name"])), balance_cell(&friend["balance"]));
+    }
+    page.push_str("</table>");
+
+    page.push_str(
+        "<h2>Recent expenses</h2><table><tr><th>Date</th><th>Description</th><th>Cost</th><th>Your net</th></tr>",
+    );
+    for expense in snapshot["recent_expenses"].as_array().into_iter().flatten() {
+        let currency = text(&expense["currency_code"]);
+        let _ = write!(
+            page,
+            "<tr><td>{}</td><td>{}</td><td class=\"num\">{} {}</td>{}</tr>",
+            escape(text(&expense["date"]).get(..10).unwrap_or_default()),
+            escape(&text(&expense["description"])),
+            escape(&text(&expense["cost"])),
+            escape(&currency),
+            amount_cell(&text(&expense["my_net_balance"]), &currency)
+        );
+    }
+    page.push_str("</table>");
+
+    let api = &status["splitwise_api"];
+    page.push_str("<h2>Server</h2><table>");
+    let rows = [
+        ("Version", text(&status["version"])),
+        ("Uptime", uptime(status["uptime_secs"].as_u64().unwrap_or_default())),
+        ("Splitwise calls", format!("{} ({} in the last hour)", text(&api["calls"]), text(&api["calls_last_hour"]))),
+        ("Splitwise errors", text(&api["errors"])),
+        ("Rate limit remaining", text(&api["rate_limit_remaining"])),
+        (
+            "Last error",
+            match api["last_error"].as_object() {
+                Some(error) => format!("{} at {}", text(&error["message"]), text(&error["at"])),
+                None => "none".to_string(),
+            },
+        ),
+        (
+            "Sync",
+            match status["sync"]["checkpoints"].as_array().map_or(0, Vec::len) {
+                0 => "not run yet".to_string(),
+                1 => "1 group synced".to_string(),
+                groups => format!("{} groups synced", groups),
+            },
+        ),
+    ];
+    for (label, value) in rows {
+        let _ = write!(page, "<tr><th>{}</th><td>{}</td></tr>", label, escape(&value));
+    }
+    page.push_str("</table>");
+
+    if let Some(endpoints) = api["endpoints"].as_object().filter(|e| !e.is_empty()) {
+        page.push_str(
+            "<h2>Splitwise endpoints</h2><table><tr><th>Endpoint</th><th>Calls</th><th>Errors</th>\
+             <th>Avg ms</th><th>p95 ms</th></tr>",
+        );
+        for (endpoint, stats) in endpoints {
+            let _ = write!(
+                page,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape(endpoint),
+                text(&stats["calls"]),
+                text(&stats["errors"]),
+                text(&stats["avg_ms"]),
+                text(&stats["p95_ms"])
+            );
+        }
+        page.push_str("</table>");
+    }
+    page.push_str("</body></html>");
+    page
+}
+
+// A JSON value as plain text; strings without quotes, null as "-"
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Balances per currency, green when owed to the user and red when the user owes
+fn balance_cell(balances: &Value) -> String {
+    let balances = balances.as_array().map(Vec::as_slice).unwrap_or_default();
+    if balances.is_empty() {
+        return "<td class=\"muted\">settled up</td>".to_string();
+    }
+    let amounts: Vec<String> = balances
+        .iter()
+        .map(|b| amount_span(&text(&b["amount"]), &text(&b["currency_code"])))
+        .collect();
+    format!("<td>{}</td>", amounts.join(", "))
+}
+
+fn amount_cell(amount: &str, currency: &str) -> String {
+    format!("<td class=\"num\">{}</td>", amount_span(amount, currency))
+}
+
+fn amount_span(amount: &str, currency: &str) -> String {
+    let class = match amount.parse::<f64>() {
+        Ok(a) if a > 0.0 => "owed",
+        Ok(a) if a < 0.0 => "owing",
+        _ => "muted",
+    };
+    format!("<span class=\"{}\">{} {}</span>", class, escape(amount), escape(currency))
+}
+
+fn uptime(secs: u64) -> String {
+    match secs {
+        s if s < 3600 => format!("{} min", s / 60),
+        s if s < 86400 => format!("{} h {} min", s / 3600, s % 3600 / 60),
+        s => format!("{} days {} h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_balances_expenses_and_status() {
+        let snapshot = json!({
+            "me": { "name": "Alex <Admin>" },
+            "as_of": "2025-03-01T10:00:00Z",
+            "groups": [
+                { "name": "Casa", "balance": [{ "amount": "25.50", "currency_code": "USD" }] },
+                { "name": "Trip", "balance": [] },
+            ],
+            "friends_with_balance": [],
+            "recent_expenses": [{
+                "date": "2025-02-28T12:00:00Z", "description": "Pizza & beer", "cost": "30.00",
+                "currency_code": "USD", "my_net_balance": "-10.00",
+            }],
+        });
+        let status = json!({
+            "version": "0.1.0",
+            "sandbox": true,
+            "uptime_secs": 7260,
+            "splitwise_api": {
+                "calls": 12, "errors": 1, "calls_last_hour": 4, "rate_limit_remaining": null,
+                "last_error": { "at": "2025-03-01T09:00:00Z", "message": "API error (429)" },
+                "endpoints": { "GET /get_groups": { "calls": 3, "errors": 0, "avg_ms": 120, "p95_ms": 250 } },
+            },
+            "sync": { "checkpoints": [{ "group_id": 2001, "updated_after": null, "synced_at": null }] },
+        });
+
+        let page = render(&snapshot, &status);
+        assert!(page.contains("<h1>Alex &lt;Admin&gt;</h1>"));
+        assert!(page.contains("sandbox data"));
+        assert!(page.contains("<span class=\"owed\">25.50 USD</span>"));
+        assert!(page.contains("<td class=\"muted\">settled up</td>"));
+        assert!(page.contains("All settled up"));
+        assert!(page.contains("<td>2025-02-28</td><td>Pizza &amp; beer</td>"));
+        assert!(page.contains("<span class=\"owing\">-10.00 USD</span>"));
+        assert!(page.contains("<tr><th>Uptime</th><td>2 h 1 min</td></tr>"));
+        assert!(page.contains("API error (429) at 2025-03-01T09:00:00Z"));
+        assert!(page.contains("<td>GET /get_groups</td>"));
+        assert!(page.contains("<tr><th>Sync</th><td>1 group synced</td></tr>"));
+    }
+}
@@ -0,0 +1,139 @@
+use serde_json::{json, Value};
+use std::fmt::Display;
+
+use crate::splitwise::UpstreamError;
+
+// JSON-RPC errors, built the same way by every transport. Malformed requests and bad tool
+// arguments get the standard codes; Splitwise failures get codes of their own from the range
+// the spec leaves to servers, so a client can tell a revoked API key or a rate limit from a bug.
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+// MCP's code for a resources/read of a URI the server doesn't have
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+// Splitwise refused the API key (401, 403)
+pub const UPSTREAM_UNAUTHORIZED: i64 = -32011;
+// Splitwise rate limit (429)
+pub const UPSTREAM_RATE_LIMITED: i64 = -32012;
+// The expense, group or user doesn't exist (404)
+pub const UPSTREAM_NOT_FOUND: i64 = -32013;
+// Any other error Splitwise answered with
+pub const UPSTREAM_ERROR: i64 = -32014;
+
+/// Arguments that are missing or wrong, as opposed to a failure while acting on them.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidParams(pub String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown resource: {0}")]
+pub struct UnknownResource(pub String);
+
+/// An error whose message had personal data redacted. It stands in for the original, whose
+/// causes would still carry the data, so it keeps the code and upstream status they gave.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct Redacted {
+    pub message: String,
+    pub code: i64,
+    pub upstream_status: Option<u16>,
+}
+
+impl Redacted {
+    /// `error` with its message (and its causes') replaced by `redact` applied to it.
+    pub fn new(error: &anyhow::Error, redact: impl FnOnce(String) -> String) -> Self {
+        Self {
+            message: redact(format!("{:#}", error)),
+            code: code(error),
+            upstream_status: upstream_status(error),
+        }
+    }
+}
+
+/// The code for an error raised while handling a request.
+pub fn code(error: &anyhow::Error) -> i64 {
+    for cause in error.chain() {
+        if let Some(redacted) = cause.downcast_ref::<Redacted>() {
+            return redacted.code;
+        }
+        if cause.is::<InvalidParams>() {
+            return INVALID_PARAMS;
+        }
+        if cause.is::<UnknownResource>() {
+            return RESOURCE_NOT_FOUND;
+        }
+        if let Some(upstream) = cause.downcast_ref::<UpstreamError>() {
+            return match upstream.status.as_u16() {
+                401 | 403 => UPSTREAM_UNAUTHORIZED,
+                429 => UPSTREAM_RATE_LIMITED,
+                404 => UPSTREAM_NOT_FOUND,
+                _ => UPSTREAM_ERROR,
+            };
+        }
+    }
+    INTERNAL_ERROR
+}
+
+/// An error response with the given code.
+pub fn response(id: Option<&Value>, code: i64, message: impl Display) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message.to_string()
+        }
+    })
+}
+
+/// The error response for a failed request; Splitwise errors carry the HTTP status as data.
+pub fn from_error(id: Option<&Value>, error: &anyhow::Error) -> Value {
+    let mut response = response(id, code(error), error);
+    if let Some(status) = upstream_status(error) {
+        response["error"]["data"] = json!({ "upstream_status": status });
+    }
+    response
+}
+
+// The HTTP status Splitwise answered with, when it caused `error`
+fn upstream_status(error: &anyhow::Error) -> Option<u16> {
+    error.chain().find_map(|cause| {
+        cause
+            .downcast_ref::<Redacted>()
+            .and_then(|redacted| redacted.upstream_status)
+            .or_else(|| cause.downcast_ref::<UpstreamError>().map(|upstream| upstream.status.as_u16()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn codes_follow_the_cause() {
+        let upstream = |status| anyhow::Error::new(UpstreamError { status, errors: "no".to_string() });
+        let rejected = upstream(StatusCode::UNAUTHORIZED).context("Failed to load groups");
+        assert_eq!(code(&rejected), UPSTREAM_UNAUTHORIZED);
+        assert_eq!(code(&upstream(StatusCode::TOO_MANY_REQUESTS)), UPSTREAM_RATE_LIMITED);
+        assert_eq!(code(&upstream(StatusCode::NOT_FOUND)), UPSTREAM_NOT_FOUND);
+        assert_eq!(code(&upstream(StatusCode::BAD_REQUEST)), UPSTREAM_ERROR);
+        assert_eq!(code(&InvalidParams("missing field `cost`".to_string()).into()), INVALID_PARAMS);
+        assert_eq!(code(&anyhow::anyhow!("disk full")), INTERNAL_ERROR);
+
+        let response = from_error(Some(&json!(7)), &rejected);
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["message"], "Failed to load groups");
+        assert_eq!(response["error"]["data"], json!({ "upstream_status": 401 }));
+
+        // A redacted stand-in answers with the code and status of the error it replaces
+        let redacted = anyhow::Error::new(Redacted::new(&rejected, |m| m.replace("groups", "[hidden]")));
+        let response = from_error(Some(&json!(8)), &redacted);
+        assert_eq!(response["error"]["code"], UPSTREAM_UNAUTHORIZED);
+        assert_eq!(response["error"]["message"], "Failed to load [hidden]: API error (401 Unauthorized): no");
+        assert_eq!(response["error"]["data"], json!({ "upstream_status": 401 }));
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::trace;
+
+const ECB_URL: &str = "https://api.frankfurter.app";
+const EXCHANGERATE_HOST_URL: &str = "https://api.exchangerate.host";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateProvider {
+    // European Central Bank reference rates (via frankfurter.app), no API key required
+    Ecb,
+    // exchangerate.host, requires EXCHANGE_RATE_API_KEY but covers far more currencies (e.g. ARS)
+    ExchangerateHost,
+}
+
+impl RateProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RateProvider::Ecb => "ecb",
+            RateProvider::ExchangerateHost => "exchangerate_host",
+        }
+    }
+}
+
+struct CachedRates {
+    fetched_at: Instant,
+    // Date the provider published these rates (YYYY-MM-DD)
+    date: String,
+    // Units of each currency per one unit of the base currency
+    rates: HashMap<String, f64>,
+}
+
+pub struct Conversion {
+    pub rate: f64,
+    pub converted: f64,
+    pub date: String,
+}
+
+pub struct ExchangeRates {
+    client: Client,
+    provider: RateProvider,
+    api_key: Option<String>,
+    ttl: Duration,
+    // Latest rates keyed by base currency
+    cache: Mutex<HashMap<String, CachedRates>>,
+    // Historical rates never change, so they are kept for the lifetime of the process
+    historical: Mutex<HashMap<(String, String), HashMap<String, f64>>>,
+}
+
+impl ExchangeRates {
+    pub fn new(config: &Config, client: Client) -> Result<Self> {
+        let provider = match config.exchange_rate_provider.as_str() {
+            "ecb" | "frankfurter" => RateProvider::Ecb,
+            "exchangerate_host" | "exchangerate.host" => RateProvider::ExchangerateHost,
+            other => anyhow::bail!("Unknown EXCHANGE_RATE_PROVIDER: {}", other),
+        };
+
+        Ok(Self {
+            client,
+            provider,
+            api_key: config.exchange_rate_api_key.clone(),
+            ttl: Duration::from_secs(config.exchange_rate_ttl_secs),
+            cache: Mutex::new(HashMap::new()),
+            historical: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn provider(&self) -> RateProvider {
+        self.provider
+    }
+
+    /// Answer with `rates` (units per one `base`) instead of asking the provider: as the latest
+    /// rates when `date` is None, otherwise as those published on `date`. Tests run offline.
+    #[cfg(test)]
+    pub fn seed(&self, base: &str, date: Option<&str>, rates: &[(&str, f64)]) {
+        let rates: HashMap<String, f64> = rates.iter().map(|(code, rate)| (code.to_string(), *rate)).collect();
+        match date {
+            Some(date) => {
+                self.historical.lock().unwrap().insert((base.to_string(), date.to_string()), rates);
+            }
+            None => {
+                let cached = CachedRates {
+                    fetched_at: Instant::now(),
+                    date: chrono::Utc::now().date_naive().to_string(),
+                    rates,
+                };
+                self.cache.lock().unwrap().insert(base.to_string(), cached);
+            }
+        }
+    }
+
+    // Summary of cached rate tables for diagnostics
+    pub fn cache_status(&self) -> Value {
+        let latest: Vec<Value> = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(base, cached)| json!({
+                "base": base,
+                "rates_date": cached.date,
+                "currencies": cached.rates.len(),
+                "age_secs": cached.fetched_at.elapsed().as_secs(),
+                "expired": cached.fetched_at.elapsed() > self.ttl,
+            }))
+            .collect();
+
+        json!({
+            "provider": self.provider.name(),
+            "ttl_secs": self.ttl.as_secs(),
+            "latest": latest,
+            "historical_dates": self.historical.lock().unwrap().len(),
+        })
+    }
+
+    /// Convert an amount using the latest available rate.
+    pub async fn convert(&self, amount: f64, from: &str, to: &str) -> Result<Conversion> {
+        let from = from.to_uppercase();
+        let to = to.to_uppercase();
+
+        if from == to {
+            return Ok(Conversion {
+                rate: 1.0,
+                converted: amount,
+                date: chrono::Utc::now().date_naive().to_string(),
+            });
+        }
+
+        let (date, rate) = self.latest_rate(&from, &to).await?;
+        Ok(Conversion {
+            rate,
+            converted: amount * rate,
+            date,
+        })
+    }
+
+    /// Convert an amount using the rate published on a given date (YYYY-MM-DD).
+    /// Falls back to the closest earlier publication for weekends and holidays.
+    pub async fn convert_on(
+        &self,
+        amount: f64,
+        from: &str,
+        to: &str,
+        date: &str,
+    ) -> Result<Conversion> {
+        let from = from.to_uppercase();
+        let to = to.to_uppercase();
+        // Accept full timestamps such as Splitwise expense dates
+        let date = date.get(..10).unwrap_or(date);
+
+        if from == to {
+            return Ok(Conversion {
+                rate: 1.0,
+                converted: amount,
+                date: date.to_string(),
+            });
+        }
+
+        // A day's rates are fetched once; a currency missing from them won't appear later
+        let key = (from.clone(), date.to_string());
+        let cached = self.historical.lock().unwrap().get(&key).map(|rates| rates.get(&to).copied());
+        let rate = match cached {
+            Some(rate) => {
+                trace::record_cache_hit(&format!("exchange_rates:{}->{}@{}", from, to, date));
+                rate
+            }
+            None => {
+                let rates = self.fetch_historical(&from, date).await?;
+                let rate = rates.get(&to).copied();
+                self.historical.lock().unwrap().insert(key, rates);
+                rate
+            }
+        };
+        let rate = rate.with_context(|| {
+            format!(
+                "Exchange rate {} -> {} on {} not available from the '{}' provider",
+                from,
+                to,
+                date,
+                self.provider.name()
+            )
+        })?;
+
+        Ok(Conversion {
+            rate,
+            converted: amount * rate,
+            date: date.to_string(),
+        })
+    }
+
+    async fn latest_rate(&self, base: &str, target: &str) -> Result<(String, f64)> {
+        if let Some(found) = self.cached_rate(base, target) {
+            trace::record_cache_hit(&format!("exchange_rates:{}->{}", base, target));
+            return Ok(found);
+        }
+
+        let (date, rates) = self.fetch_latest(base).await?;
+        let rate = rates.get(target).copied().with_context(|| {
+            format!(
+                "Exchange rate {} -> {} not available from the '{}' provider",
+                base,
+                target,
+                self.provider.name()
+            )
+        })?;
+
+        self.cache.lock().unwrap().insert(
+            base.to_string(),
+            CachedRates {
+                fetched_at: Instant::now(),
+                date: date.clone(),
+                rates,
+            },
+        );
+
+        Ok((date, rate))
+    }
+
+    fn cached_rate(&self, base: &str, target: &str) -> Option<(String, f64)> {
+        let cache = self.cache.lock().unwrap();
+        let cached = cache.get(base)?;
+        if cached.fetched_at.elapsed() > self.ttl {
+            return None;
+        }
+        cached.rates.get(target).map(|r| (cached.date.clone(), *r))
+    }
+
+    async fn fetch_historical(&self, base: &str, date: &str) -> Result<HashMap<String, f64>> {
+        match self.provider {
+            RateProvider::Ecb => {
+                #[derive(Deserialize)]
+                struct Response {
+                    rates: HashMap<String, f64>,
+                }
+                let url = format!("{}/{}", ECB_URL, date);
+                let response: Response = self
+                    .client
+                    .get(&url)
+                    .query(&[("from", base)])
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .with_context(|| format!("ECB rates not available for {} on {}", base, date))?
+                    .json()
+                    .await?;
+                Ok(response.rates)
+            }
+            RateProvider::ExchangerateHost => {
+                #[derive(Deserialize)]
+                struct Response {
+                    success: bool,
+                    quotes: Option<HashMap<String, f64>>,
+                    error: Option<Value>,
+                }
+                let api_key = self
+                    .api_key
+                    .as_deref()
+                    .context("EXCHANGE_RATE_API_KEY is required for the exchangerate_host provider")?;
+                let url = format!("{}/historical", EXCHANGERATE_HOST_URL);
+                let response: Response = self
+                    .client
+                    .get(&url)
+                    .query(&[("access_key", api_key), ("source", base), ("date", date)])
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+
+                if !response.success {
+                    anyhow::bail!("exchangerate.host error: {}", response.error.unwrap_or(json!(null)))
+                }
+
+                Ok(response
+                    .quotes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(pair, rate)| (pair.strip_prefix(base).unwrap_or(&pair).to_string(), rate))
+                    .collect())
+            }
+        }
+    }
+
+    async fn fetch_latest(&self, base: &str) -> Result<(String, HashMap<String, f64>)> {
+        match self.provider {
+            RateProvider::Ecb => {
+                #[derive(Deserialize)]
+                struct Response {
+                    date: String,
+                    rates: HashMap<String, f64>,
+                }
+                let url = format!("{}/latest", ECB_URL);
+                let response: Response = self
+                    .client
+                    .get(&url)
+                    .query(&[("from", base)])
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .with_context(|| format!("ECB rates not available for base currency {}", base))?
+                    .json()
+                    .await?;
+                Ok((response.date, response.rates))
+            }
+            RateProvider::ExchangerateHost => {
+                #[derive(Deserialize)]
+                struct Response {
+                    success: bool,
+                    timestamp: Option<i64>,
+                    quotes: Option<HashMap<String, f64>>,
+                    error: Option<Value>,
+                }
+                let api_key = self
+                    .api_key
+                    .as_deref()
+                    .context("EXCHANGE_RATE_API_KEY is required for the exchangerate_host provider")?;
+                let url = format!("{}/live", EXCHANGERATE_HOST_URL);
+                let response: Response = self
+                    .client
+                    .get(&url)
+                    .query(&[("access_key", api_key), ("source", base)])
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+
+                if !response.success {
+                    anyhow::bail!("exchangerate.host error: {}", response.error.unwrap_or(json!(null)))
+                }
+
+                let date = response
+                    .timestamp
+                    .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                    .map(|dt| dt.date_naive().to_string())
+                    .unwrap_or_else(|| chrono::Utc::now().date_naive().to_string());
+                // Quotes are keyed as "USDEUR"; strip the source prefix
+                let rates = response
+                    .quotes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(pair, rate)| (pair.strip_prefix(base).unwrap_or(&pair).to_string(), rate))
+                    .collect();
+                Ok((date, rates))
+            }
+        }
+    }
+}
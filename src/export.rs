@@ -0,0 +1,361 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::crypto::{self, Cipher};
+use crate::splitwise::SplitwiseClient;
+use crate::store::LocalStore;
+use crate::types::{Expense, ListExpensesParams};
+
+// Export of the whole expense history as a background job, for accounts too large to list in
+// one tool call. It fetches one batch per request and checkpoints after each: the expenses are
+// appended to a JSON Lines file and the offset reached is saved beside it, so after a restart
+// the export picks up where it stopped. Every page is bounded by the time the export started,
+// so expenses added meanwhile don't shift the offsets, and the result is deduplicated by ID
+// (a batch fetched again after a crash) and ordered by date, then ID.
+// Without a data directory (sandbox mode) the expenses are kept in memory. With a data key each
+// line is encrypted on its own, so appending and skipping a partial last line work the same.
+
+const STATE: &str = "history_export";
+const DATA_FILE: &str = "history_export.jsonl";
+const BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportState {
+    pub status: ExportStatus,
+    pub started_at: String,
+    // Upper date bound of every page
+    pub snapshot: String,
+    pub next_offset: usize,
+    // Expenses fetched, counting any batch fetched twice
+    pub fetched: usize,
+    pub batches: usize,
+    pub updated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct HistoryExport {
+    client: Arc<SplitwiseClient>,
+    store: Arc<LocalStore>,
+    state: Arc<Mutex<Option<ExportState>>>,
+    // Whether a task in this process is working on the export
+    active: Arc<AtomicBool>,
+    memory: Arc<Mutex<Vec<Expense>>>,
+}
+
+impl HistoryExport {
+    pub fn new(client: Arc<SplitwiseClient>, dir: Option<PathBuf>, cipher: Option<Arc<Cipher>>) -> Self {
+        let store = LocalStore::new(dir, cipher);
+        let state = store.load::<Option<ExportState>>(STATE);
+        Self {
+            client,
+            store: Arc::new(store),
+            state: Arc::new(Mutex::new(state)),
+            active: Arc::new(AtomicBool::new(false)),
+            memory: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Start an export in the background, or resume an unfinished one from its checkpoint.
+    /// A finished export is kept unless `restart` is set.
+    pub fn start(&self, restart: bool) -> Result<()> {
+        if self.active.swap(true, Ordering::SeqCst) {
+            anyhow::ensure!(!restart, "An export is running; restart it once it has finished");
+            return Ok(());
+        }
+        let prepared = self.prepare(restart);
+        if !matches!(prepared, Ok(true)) {
+            self.active.store(false, Ordering::SeqCst);
+            return prepared.map(|_| ());
+        }
+        let export = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = export.run().await {
+                export.fail(&e);
+            }
+            export.active.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    // Set up the state for a run; false when a finished export is kept as it is
+    fn prepare(&self, restart: bool) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.as_mut() {
+            Some(current) if !restart && current.status == ExportStatus::Done => return Ok(false),
+            Some(current) if !restart => {
+                info!("Resuming history export at offset {}", current.next_offset);
+                current.status = ExportStatus::Running;
+                current.error = None;
+                // Memory-only exports don't outlive the process, so start those over
+                if self.data_file().is_none() && self.memory.lock().unwrap().is_empty() {
+                    current.next_offset = 0;
+                }
+            }
+            _ => {
+                self.clear_data()?;
+                let now = chrono::Utc::now().to_rfc3339();
+                *state = Some(ExportState {
+                    status: ExportStatus::Running,
+                    started_at: now.clone(),
+                    snapshot: now.clone(),
+                    next_offset: 0,
+                    fetched: 0,
+                    batches: 0,
+                    updated_at: now,
+                    finished_at: None,
+                    error: None,
+                });
+            }
+        }
+        self.store.save(STATE, &*state)?;
+        Ok(true)
+    }
+
+    async fn run(&self) -> Result<()> {
+        loop {
+            let (offset, snapshot) = {
+                let state = self.state.lock().unwrap();
+                let state = state.as_ref().context("Export state missing")?;
+                (state.next_offset, state.snapshot.clone())
+            };
+            let params = ListExpensesParams {
+                dated_before: Some(snapshot),
+                limit: Some(BATCH_SIZE as i32),
+                offset: Some(offset as i32),
+                ..Default::default()
+            };
+            let page = self
+                .client
+                .get_expense_page(params)
+                .await
+                .with_context(|| format!("Failed to fetch expenses at offset {}", offset))?;
+            // Unreadable items are skipped but still move the offset past them
+            let returned = page.len();
+            let batch: Vec<Expense> = page.into_iter().flatten().collect();
+            self.append(&batch)?;
+
+            let finished = returned < BATCH_SIZE;
+            let mut state = self.state.lock().unwrap();
+            let current = state.as_mut().context("Export state missing")?;
+            let now = chrono::Utc::now().to_rfc3339();
+            current.next_offset += returned;
+            current.fetched += batch.len();
+            current.batches += 1;
+            current.updated_at = now.clone();
+            if finished {
+                current.status = ExportStatus::Done;
+                current.finished_at = Some(now);
+                info!("History export finished: {} expenses in {} batches", current.fetched, current.batches);
+            }
+            self.store.save(STATE, &*state)?;
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+
+    fn fail(&self, error: &anyhow::Error) {
+        warn!("History export stopped: {:#}", error);
+        let mut state = self.state.lock().unwrap();
+        if let Some(current) = state.as_mut() {
+            current.status = ExportStatus::Failed;
+            current.error = Some(format!("{:#}", error));
+            current.updated_at = chrono::Utc::now().to_rfc3339();
+        }
+        if let Err(e) = self.store.save(STATE, &*state) {
+            warn!("Failed to save export state: {}", e);
+        }
+    }
+
+    /// Progress of the latest export. A running export with no task behind it was cut off by a
+    /// restart and is reported as "interrupted".
+    pub fn status(&self) -> Option<Value> {
+        let state = self.state.lock().unwrap().clone()?;
+        let mut status = serde_json::to_value(&state).ok()?;
+        if state.status == ExportStatus::Running && !self.active.load(Ordering::SeqCst) {
+            status["status"] = json!("interrupted");
+        }
+        status["data_file"] = json!(self.data_file());
+        Some(status)
+    }
+
+    /// Everything exported so far, oldest first, with the state it was read at.
+    pub fn expenses(&self) -> Result<(Vec<Expense>, ExportState)> {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .clone()
+            .context("No export has been started; use export_all_history")?;
+        let fetched = match self.data_file() {
+            Some(path) => read_lines(&path, self.store.cipher())?,
+            None => self.memory.lock().unwrap().clone(),
+        };
+        let by_id: BTreeMap<i64, Expense> = fetched.into_iter().map(|e| (e.id, e)).collect();
+        let mut expenses: Vec<Expense> = by_id.into_values().collect();
+        expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+        Ok((expenses, state))
+    }
+
+    fn append(&self, batch: &[Expense]) -> Result<()> {
+        let Some(path) = self.data_file() else {
+            self.memory.lock().unwrap().extend(batch.iter().cloned());
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut lines = String::new();
+        for expense in batch {
+            let line = serde_json::to_string(expense)?;
+            match self.store.cipher() {
+                Some(cipher) => lines.push_str(&cipher.seal_line(DATA_FILE, &line)?),
+                None => lines.push_str(&line),
+            }
+            lines.push('\n');
+        }
+        file.write_all(lines.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn clear_data(&self) -> Result<()> {
+        self.memory.lock().unwrap().clear();
+        if let Some(path) = self.data_file() {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the exported expenses are written, one JSON object per line.
+    pub fn data_file(&self) -> Option<PathBuf> {
+        self.store.dir().map(|dir| dir.join(DATA_FILE))
+    }
+}
+
+fn read_lines(path: &PathBuf, cipher: Option<&Cipher>) -> Result<Vec<Expense>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let lines = BufReader::new(file).lines().collect::<std::io::Result<Vec<String>>>()?;
+    let mut expenses = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        // A crash mid-write can leave a partial last line; its batch is fetched again anyway
+        let line = match crypto::open_line(cipher, DATA_FILE, line) {
+            Ok(line) => line,
+            Err(e) if cipher.is_some() && index + 1 == lines.len() => {
+                warn!("Skipping unreadable export line: {:#}", e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        match serde_json::from_str(&line) {
+            Ok(expense) => expenses.push(expense),
+            Err(e) if !line.trim().is_empty() => warn!("Skipping unreadable export line: {}", e),
+            Err(_) => {}
+        }
+    }
+    Ok(expenses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn finish(export: &HistoryExport) {
+        while export.active.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_after_a_restart_without_duplicates() {
+        let dir = std::env::temp_dir().join(format!("splitwise-mcp-export-{}", std::process::id()));
+        let client = Arc::new(SplitwiseClient::sandbox());
+
+        let export = HistoryExport::new(client.clone(), Some(dir.clone()), None);
+        export.start(false).unwrap();
+        finish(&export).await;
+        let (first, state) = export.expenses().unwrap();
+        assert_eq!(state.status, ExportStatus::Done);
+        assert!(!first.is_empty());
+        assert!(first.windows(2).all(|w| (&w[0].date, w[0].id) <= (&w[1].date, w[1].id)));
+
+        // Pretend the process died after writing the batch but before saving its checkpoint
+        let mut interrupted = state.clone();
+        interrupted.status = ExportStatus::Running;
+        interrupted.next_offset = 0;
+        export.store.save(STATE, &Some(interrupted)).unwrap();
+
+        let restarted = HistoryExport::new(client, Some(dir.clone()), None);
+        assert_eq!(restarted.status().unwrap()["status"], "interrupted");
+        restarted.start(false).unwrap();
+        finish(&restarted).await;
+        let (second, state) = restarted.expenses().unwrap();
+        assert_eq!(state.status, ExportStatus::Done);
+        assert_eq!(state.fetched, first.len() * 2);
+        let ids = |expenses: &[Expense]| expenses.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(&second), ids(&first));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn encrypts_the_export_with_a_data_key() {
+        let dir = std::env::temp_dir().join(format!("splitwise-mcp-export-enc-{}", std::process::id()));
+        let client = Arc::new(SplitwiseClient::sandbox());
+        let cipher = Arc::new(Cipher::from_base64("AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=").unwrap());
+
+        let export = HistoryExport::new(client.clone(), Some(dir.clone()), Some(cipher.clone()));
+        export.start(false).unwrap();
+        finish(&export).await;
+        let (expenses, _) = export.expenses().unwrap();
+        let description = expenses[0].description.clone();
+        let data = fs::read_to_string(export.data_file().unwrap()).unwrap();
+        assert!(data.lines().all(|line| line.starts_with("enc:")));
+        assert!(!data.contains(&description));
+        assert!(crypto::is_sealed(&fs::read(dir.join("history_export.json")).unwrap()));
+
+        let restarted = HistoryExport::new(client.clone(), Some(dir.clone()), Some(cipher));
+        assert_eq!(restarted.expenses().unwrap().0.len(), expenses.len());
+        // Without the key the state reads as missing and the data can't be read
+        let keyless = HistoryExport::new(client, Some(dir.clone()), None);
+        assert!(keyless.status().is_none());
+        assert!(read_lines(&keyless.data_file().unwrap(), None).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Translated tool and parameter descriptions. Descriptions are prompts for the model, which
+// follows hints in the user's language better. A catalog maps each English description to its
+// translation; descriptions it doesn't have stay in English.
+
+const SPANISH: &str = include_str!("locales/es.json");
+
+/// The catalog for `locale` ("es", "es-AR", "es_ES"); None for English or an unknown language.
+fn catalog(locale: &str) -> Option<&'static HashMap<String, String>> {
+    static ES: OnceLock<HashMap<String, String>> = OnceLock::new();
+    let language = locale.trim().split(['-', '_']).next().unwrap_or_default();
+    if language.eq_ignore_ascii_case("es") {
+        Some(ES.get_or_init(|| serde_json::from_str(SPANISH).expect("locales/es.json is a JSON object of strings")))
+    } else {
+        None
+    }
+}
+
+/// Translate the descriptions of `tools` and of their parameters, nested ones included.
+pub fn localize(tools: &mut [Value], locale: &str) {
+    let Some(catalog) = catalog(locale) else {
+        return;
+    };
+    for tool in tools {
+        translate(tool, catalog);
+    }
+}
+
+// Every "description" string in a tool or schema; a parameter named description is an object
+fn translate(value: &mut Value, catalog: &HashMap<String, String>) {
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj.iter_mut() {
+                match value {
+                    Value::String(text) if key == "description" => {
+                        if let Some(translated) = catalog.get(text.as_str()) {
+                            *text = translated.clone();
+                        }
+                    }
+                    _ => translate(value, catalog),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| translate(item, catalog)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn descriptions_follow_the_locale() {
+        let original = vec![json!({
+            "name": "delete_expense",
+            "description": "Delete an expense",
+            "inputSchema": { "properties": {
+                "expense_id": { "type": "integer", "description": "The ID of the expense to delete" },
+                "description": { "type": "string", "description": "Not in the catalog" },
+            } },
+        })];
+
+        let mut tools = original.clone();
+        localize(&mut tools, "es-AR");
+        assert_eq!(tools[0]["description"], "Borra un gasto");
+        assert_eq!(tools[0]["inputSchema"]["properties"]["expense_id"]["description"], "ID del gasto a borrar");
+        assert_eq!(tools[0]["inputSchema"]["properties"]["description"]["description"], "Not in the catalog");
+
+        let mut tools = original.clone();
+        localize(&mut tools, "en");
+        assert_eq!(tools, original);
+    }
+}
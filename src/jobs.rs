@@ -0,0 +1,199 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::AbortHandle;
+
+// Background jobs for tool calls that take longer than a client will wait for one request
+// (full-history analytics, bulk changes). A job runs a tool call on its own task; callers poll
+// its status and collect the result later. Jobs live in memory only, and the oldest finished
+// ones are dropped once there are more than MAX_FINISHED.
+
+const MAX_FINISHED: usize = 50;
+
+enum JobState {
+    Running(AbortHandle),
+    Done(Value),
+    Failed(String),
+    Cancelled,
+}
+
+struct Job {
+    tool: String,
+    started_at: String,
+    started: Instant,
+    finished_at: Option<String>,
+    elapsed_ms: Option<u128>,
+    state: JobState,
+}
+
+impl Job {
+    fn status(&self, id: &str) -> Value {
+        let status = match self.state {
+            JobState::Running(_) => "running",
+            JobState::Done(_) => "done",
+            JobState::Failed(_) => "failed",
+            JobState::Cancelled => "cancelled",
+        };
+        let mut value = json!({
+            "job_id": id,
+            "tool": self.tool,
+            "status": status,
+            "started_at": self.started_at,
+            "finished_at": self.finished_at,
+            "elapsed_ms": self.elapsed_ms.unwrap_or_else(|| self.started.elapsed().as_millis()),
+        });
+        if let JobState::Failed(ref error) = self.state {
+            value["error"] = json!(error);
+        }
+        value
+    }
+
+    fn finish(&mut self, state: JobState) {
+        self.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        self.elapsed_ms = Some(self.started.elapsed().as_millis());
+        self.state = state;
+    }
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Arc<Mutex<BTreeMap<u64, Job>>>,
+    next_id: AtomicU64,
+}
+
+impl Jobs {
+    /// Run `work` as a job for `tool` and return the job's ID.
+    pub fn start<F>(&self, tool: &str, work: F) -> String
+    where
+        F: Future<Output = Result<Value>> + Send + 'static,
+    {
+        let number = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let jobs = Arc::clone(&self.jobs);
+        // Hold the lock until the job is registered, so the task can't finish before it is
+        let mut registry = self.jobs.lock().unwrap();
+        let handle = tokio::spawn(async move {
+            let outcome = work.await;
+            if let Some(job) = jobs.lock().unwrap().get_mut(&number) {
+                job.finish(match outcome {
+                    Ok(value) => JobState::Done(value),
+                    Err(e) => JobState::Failed(format!("{:#}", e)),
+                });
+            }
+        });
+        registry.insert(
+            number,
+            Job {
+                tool: tool.to_string(),
+                started_at: chrono::Utc::now().to_rfc3339(),
+                started: Instant::now(),
+                finished_at: None,
+                elapsed_ms: None,
+                state: JobState::Running(handle.abort_handle()),
+            },
+        );
+        prune(&mut registry);
+        job_id(number)
+    }
+
+    pub fn status(&self, id: &str) -> Result<Value> {
+        let number = parse_id(id)?;
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&number).with_context(|| format!("Unknown job {}", id))?;
+        Ok(job.status(id))
+    }
+
+    /// Status of every job still kept, newest first.
+    pub fn list(&self) -> Vec<Value> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().rev().map(|(number, job)| job.status(&job_id(*number))).collect()
+    }
+
+    pub fn result(&self, id: &str) -> Result<Value> {
+        let number = parse_id(id)?;
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&number).with_context(|| format!("Unknown job {}", id))?;
+        match job.state {
+            JobState::Running(_) => bail!("Job {} is still running; check job_status", id),
+            JobState::Done(ref value) => {
+                let mut status = job.status(id);
+                status["result"] = value.clone();
+                Ok(status)
+            }
+            JobState::Failed(_) | JobState::Cancelled => Ok(job.status(id)),
+        }
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<Value> {
+        let number = parse_id(id)?;
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(&number).with_context(|| format!("Unknown job {}", id))?;
+        let JobState::Running(ref handle) = job.state else {
+            bail!("Job {} has already finished", id);
+        };
+        handle.abort();
+        job.finish(JobState::Cancelled);
+        Ok(job.status(id))
+    }
+}
+
+fn job_id(number: u64) -> String {
+    format!("job-{}", number)
+}
+
+fn parse_id(id: &str) -> Result<u64> {
+    id.trim()
+        .strip_prefix("job-")
+        .and_then(|n| n.parse().ok())
+        .with_context(|| format!("Not a job ID: '{}' (expected e.g. job-3)", id))
+}
+
+// Drop the oldest finished jobs beyond MAX_FINISHED; running ones are always kept
+fn prune(jobs: &mut BTreeMap<u64, Job>) {
+    let finished: Vec<u64> = jobs
+        .iter()
+        .filter(|(_, job)| !matches!(job.state, JobState::Running(_)))
+        .map(|(number, _)| *number)
+        .collect();
+    for number in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED)) {
+        jobs.remove(number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cancelled_jobs_stop_and_report_it() {
+        let jobs = Jobs::default();
+        let id = jobs.start("slow", async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(json!("never"))
+        });
+        assert_eq!(jobs.status(&id).unwrap()["status"], "running");
+        assert!(jobs.result(&id).is_err());
+
+        assert_eq!(jobs.cancel(&id).unwrap()["status"], "cancelled");
+        assert!(jobs.cancel(&id).is_err());
+        assert!(jobs.result(&id).unwrap().get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn failures_are_kept_with_their_error() {
+        let jobs = Jobs::default();
+        let id = jobs.start("broken", async { bail!("upstream said no") });
+        while jobs.status(&id).unwrap()["status"] == "running" {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let status = jobs.result(&id).unwrap();
+        assert_eq!(status["status"], "failed");
+        assert_eq!(status["error"], "upstream said no");
+        assert!(jobs.status("job-999").is_err());
+        assert!(jobs.status("nonsense").is_err());
+    }
+}
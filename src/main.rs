@@ -6,13 +6,47 @@ use rmcp::transport::stdio;
 use serde_json::json;
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
 use tracing_subscriber;
 
+mod accounts;
+mod budget;
+mod cache;
+mod chart;
+mod client_log;
+mod config;
+mod crypto;
+mod errors;
+mod exchange;
+mod export;
+mod i18n;
+mod jobs;
+mod matching;
+mod merchants;
+mod metadata;
+mod nl_query;
+mod pdf;
+mod peer;
+mod projection;
+mod receipt;
+mod redact;
+mod render;
+mod resolve;
+mod roots;
+mod sampling;
+mod sandbox;
 mod splitwise;
+mod store;
+mod sync;
+mod tool_policy;
 mod tools;
+mod trace;
 mod types;
+mod warnings;
+mod write_queue;
 
+use config::Config;
 use splitwise::SplitwiseClient;
 use tools::SplitwiseTools;
 
@@ -29,48 +63,90 @@ async fn main() -> Result<()> {
     // Load environment variables
     dotenv().ok();
 
-    // Get API key from environment
-    let api_key = env::var("SPLITWISE_API_KEY")
-        .context("SPLITWISE_API_KEY environment variable not set")?;
+    let config = Config::from_env();
 
     info!("Starting Splitwise MCP server...");
 
-    // Create Splitwise client
-    let client = Arc::new(
+    // Create Splitwise client (fixture-backed in sandbox mode)
+    let client = if config.sandbox {
+        info!("SANDBOX mode: serving fixture data, no Splitwise account is used");
+        SplitwiseClient::sandbox()
+    } else {
+        let api_key = env::var("SPLITWISE_API_KEY")
+            .context("SPLITWISE_API_KEY environment variable not set")?;
         SplitwiseClient::new(api_key)
-            .context("Failed to create Splitwise client")?,
-    );
+            .context("Failed to create Splitwise client")?
+            .with_http_client(config.http_client()?)
+            .with_form_bodies(config.form_encoded_bodies)
+            .with_user_agent(config.user_agent.clone())
+            .with_request_ids(config.request_ids)
+            .with_expense_cache(Duration::from_secs(config.expense_cache_ttl_secs))
+    };
+    let client = Arc::new(client);
 
     // Create tools handler
-    let tools = Arc::new(SplitwiseTools::new(client));
+    let tools = Arc::new(
+        SplitwiseTools::new(client, config)
+            .context("Failed to create tools handler")?,
+    );
+
+    // Load who "me" is and their groups in the background, so neither the first tool call nor
+    // initialize's account summary waits for them
+    tokio::spawn({
+        let tools = tools.clone();
+        async move { tools.warm_up().await }
+    });
 
     // Create MCP server
     let server = ServerBuilder::new()
-        .on_initialize(|params| {
-            info!("Client connected: {:?}", params.client_info);
+        .on_initialize({
+            let tools = tools.clone();
+            move |params| {
+                info!("Client connected: {:?}", params.client_info);
             
-            Ok(InitializeResult {
-                server_info: json!({
-                    "name": "splitwise-mcp-server",
-                    "version": "0.1.0",
-                    "description": "MCP server for Splitwise expense tracking"
-                }),
-                capabilities: ServerCapabilities {
-                    tools: Some(json!({
-                        "available": true
-                    })),
+                Ok(InitializeResult {
+                    server_info: json!({
+                        "name": "splitwise-mcp-server",
+                        "version": "0.1.0",
+                        "description": "MCP server for Splitwise expense tracking",
+                        "account": tools.account_summary(),
+                        "instructions": tools.instructions()
+                    }),
+                    capabilities: ServerCapabilities {
+                        tools: Some(json!({
+                            "available": true
+                        })),
+                        resources: Some(json!({
+                            "available": true
+                        })),
+                        ..Default::default()
+                    },
                     ..Default::default()
-                },
-                ..Default::default()
-            })
+                })
+            }
         })
         .on_list_tools(move |_| {
             let tools = tools.clone();
-            let tool_list = tools.get_tools();
+            let tool_list = tools.localized_tools();
             Ok(json!({
                 "tools": tool_list
             }))
         })
+        .on_list_resources({
+            let tools = tools.clone();
+            move |_| {
+                Ok(json!({
+                    "resources": tools.get_resources()
+                }))
+            }
+        })
+        .on_read_resource({
+            let tools = tools.clone();
+            move |params| {
+                let tools = tools.clone();
+                Box::pin(async move { tools.read_resource(&params.uri).await })
+            }
+        })
         .on_call_tool({
             let tools = tools.clone();
             move |params| {
@@ -78,10 +154,7 @@ async fn main() -> Result<()> {
                 Box::pin(async move {
                     match tools.handle_tool_call(&params.name, params.arguments).await {
                         Ok(result) => Ok(CallToolResult {
-                            content: vec![json!({
-                                "type": "text",
-                                "text": result.to_string(),
-                            })],
+                            content: render::content(&result),
                             ..Default::default()
                         }),
                         Err(e) => {
@@ -90,7 +163,8 @@ async fn main() -> Result<()> {
                                 content: vec![json!({
                                     "type": "text",
                                     "text": json!({
-                                        "error": e.to_string()
+                                        "error": e.to_string(),
+                                        "code": errors::code(&e)
                                     }).to_string(),
                                 })],
                                 is_error: Some(true),
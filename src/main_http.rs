@@ -1,34 +1,91 @@
 use anyhow::{Context, Result};
 use axum::{
     extract::{Query, State},
-    http::{header, HeaderMap, Method, StatusCode},
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use base64::{engine::general_purpose::STANDARD, Engine};
 use dotenv::dotenv;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::convert::Infallible;
+use std::time::Duration;
 use std::{env, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender;
 use tower::ServiceBuilder;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{info, warn};
-use tracing_subscriber;
+use tracing::{debug, info, warn};
+use tracing_subscriber::prelude::*;
 
+mod accounts;
+mod budget;
+mod cache;
+mod chart;
+mod client_log;
+mod config;
+mod crypto;
+mod dashboard;
+mod errors;
+mod exchange;
+mod export;
+mod i18n;
+mod jobs;
+mod matching;
+mod merchants;
+mod metadata;
+mod nl_query;
+mod pdf;
+mod peer;
+mod projection;
+mod receipt;
+mod redact;
+mod render;
+mod resolve;
+mod rest;
+mod roots;
+mod sampling;
+mod sandbox;
+mod sessions;
 mod splitwise;
+mod store;
+mod sync;
+mod tenants;
+mod tool_policy;
 mod tools;
+mod trace;
 mod types;
+mod warnings;
+mod write_queue;
 
+use config::Config;
+use peer::Peer;
+use sessions::Sessions;
 use splitwise::SplitwiseClient;
+use tenants::Tenant;
 use tools::SplitwiseTools;
 
+// Responses smaller than this are sent uncompressed
+const COMPRESS_MIN_BYTES: u16 = 1024;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
 #[derive(Clone)]
 struct AppState {
     tools: Arc<SplitwiseTools>,
+    // Further accounts, each behind its own bearer token (TENANTS_FILE)
+    tenants: Arc<Vec<Tenant>>,
     auth_token: String,
     client_id: String,
     client_secret: String,
+    sessions: Arc<Sessions>,
+    // How often an idle event stream gets a comment, so proxies don't close it
+    keep_alive: Duration,
 }
 
 #[derive(Deserialize)]
@@ -45,15 +102,22 @@ struct TokenResponse {
     expires_in: i32,
 }
 
-// Authentication middleware - supports both Bearer token and Basic auth
-async fn check_auth(headers: &HeaderMap, state: &AppState) -> Result<(), StatusCode> {
+// Authentication middleware - supports both Bearer token and Basic auth. Returns the tools of the
+// account the credentials belong to: a tenant's, or the default one
+async fn check_auth(headers: &HeaderMap, state: &AppState) -> Result<Arc<SplitwiseTools>, StatusCode> {
     // First try Bearer token
     if let Some(auth_header) = headers.get(header::AUTHORIZATION) {
         if let Ok(auth_str) = auth_header.to_str() {
             // Check Bearer token
             if let Some(token) = auth_str.strip_prefix("Bearer ") {
                 if token == state.auth_token {
-                    return Ok(());
+                    return Ok(state.tools.clone());
+                }
+                if let Some(tenant) = state.tenants.iter().find(|t| t.token == token) {
+                    if !tenant.admit() {
+                        return Err(StatusCode::TOO_MANY_REQUESTS);
+                    }
+                    return Ok(tenant.tools.clone());
                 }
             }
             
@@ -65,7 +129,7 @@ async fn check_auth(headers: &HeaderMap, state: &AppState) -> Result<(), StatusC
                         if parts.len() == 2 && 
                            parts[0] == state.client_id && 
                            parts[1] == state.client_secret {
-                            return Ok(());
+                            return Ok(state.tools.clone());
                         }
                     }
                 }
@@ -99,96 +163,257 @@ async fn oauth_token_handler(
 }
 
 
-// HTTP POST endpoint for MCP requests
+// HTTP POST endpoint for MCP requests. Clients that accept text/event-stream get the response
+// as a stream of events: log notifications and sampling requests as they are made, then the
+// response itself. Their answers to sampling requests are POSTed here too.
 async fn mcp_handler(
     headers: HeaderMap,
     State(state): State<AppState>,
-    Json(request): Json<serde_json::Value>,
-) -> Result<impl IntoResponse, StatusCode> {
+    body: String,
+) -> Result<Response, StatusCode> {
     // Check authentication
-    check_auth(&headers, &state).await?;
+    let tools = check_auth(&headers, &state).await?;
+
+    // Requests to the client and what it declared belong to this account's session only
+    let account = account_of(&state, &tools);
 
+    // A session that expired or was ended is gone, and another account's was never there; the
+    // client has to initialize again
+    let session = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string);
+    if let Some(ref session) = session {
+        if !state.sessions.touch(session, account.as_deref()) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    let request: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => return Ok(Json(errors::response(None, errors::PARSE_ERROR, format!("Parse error: {}", e))).into_response()),
+    };
     info!("HTTP request received: {:?}", request);
 
-    // Parse the JSON-RPC request
-    let method = request
-        .get("method")
-        .and_then(|m| m.as_str())
+    if peer::resolve(&Peer::new(account.clone(), session.clone()), &request) {
+        return Ok(StatusCode::ACCEPTED.into_response());
+    }
+    if request.get("method").and_then(|m| m.as_str()).is_none() {
+        return Ok(Json(errors::response(request.get("id"), errors::INVALID_REQUEST, "Invalid request")).into_response());
+    }
+    // Notifications (initialized, roots/list_changed) get no response
+    if request.get("id").is_none() {
+        return Ok(StatusCode::ACCEPTED.into_response());
+    }
+
+    let started = (request["method"] == "initialize").then(|| state.sessions.start(account.clone()));
+    let peer = Peer::new(account, started.clone().or(session));
+    let mut response = respond(state, tools, headers, request, peer).await;
+    if let Some(session) = started {
+        if let Ok(value) = HeaderValue::from_str(&session) {
+            response.headers_mut().insert(SESSION_HEADER, value);
+        }
+    }
+    Ok(response)
+}
+
+// The response to a request, as JSON or as an event stream
+async fn respond(
+    state: AppState,
+    tools: Arc<SplitwiseTools>,
+    headers: HeaderMap,
+    request: serde_json::Value,
+    peer: Peer,
+) -> Response {
+    let (sink, outgoing) = tokio::sync::mpsc::unbounded_channel();
+    let streams = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"));
+    if !streams {
+        let response = handle_request(&tools, &headers, &request, sink, peer).await;
+        return Json(response).into_response();
+    }
+
+    // No stream stays open between requests, so a change to the tools offered is announced on
+    // the peer's next streamed one
+    let changed = peer::connect(peer.clone(), sink.clone(), async { tools.tools_changed_notice() }).await;
+    if let Some(notice) = changed {
+        let _ = sink.send(notice);
+    }
+
+    let id = request.get("id").cloned();
+    let keep_alive = state.keep_alive;
+    let handling = tokio::spawn(async move { handle_request(&tools, &headers, &request, sink, peer).await });
+    // The channel closes once the request is handled; its response is the last event
+    let events = futures::stream::unfold((outgoing, Some(handling)), move |(mut outgoing, handling)| {
+        let id = id.clone();
+        async move {
+            if let Some(message) = outgoing.recv().await {
+                return Some((message, (outgoing, handling)));
+            }
+            let response = match handling?.await {
+                Ok(response) => response,
+                Err(e) => errors::response(id.as_ref(), errors::INTERNAL_ERROR, format!("Request handling failed: {}", e)),
+            };
+            Some((response, (outgoing, None)))
+        }
+    })
+    .map(|event| Ok::<_, Infallible>(Event::default().event("message").data(event.to_string())));
+    Sse::new(events).keep_alive(KeepAlive::new().interval(keep_alive)).into_response()
+}
+
+// The tenant `tools` serve; None for the default account
+fn account_of(state: &AppState, tools: &Arc<SplitwiseTools>) -> Option<String> {
+    state.tenants.iter().find(|t| Arc::ptr_eq(&t.tools, tools)).map(|t| t.name.clone())
+}
+
+// Ends the session named in the Mcp-Session-Id header, if the caller's account started it
+async fn end_session(headers: HeaderMap, State(state): State<AppState>) -> Result<StatusCode, StatusCode> {
+    let tools = check_auth(&headers, &state).await?;
+    let account = account_of(&state, &tools);
+    let session = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
         .ok_or(StatusCode::BAD_REQUEST)?;
+    if state.sessions.end(session, account.as_deref()) {
+        peer::forget(session);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+// One JSON-RPC request from `peer`; notifications and sampling requests made meanwhile go to `sink`
+async fn handle_request(
+    tools: &Arc<SplitwiseTools>,
+    headers: &HeaderMap,
+    request: &serde_json::Value,
+    sink: UnboundedSender<serde_json::Value>,
+    peer: Peer,
+) -> serde_json::Value {
+    peer::connect(peer, sink.clone(), handle_method(tools, headers, request, sink)).await
+}
 
-    let response = match method {
+async fn handle_method(
+    tools: &Arc<SplitwiseTools>,
+    headers: &HeaderMap,
+    request: &serde_json::Value,
+    sink: UnboundedSender<serde_json::Value>,
+) -> serde_json::Value {
+    let id = request.get("id");
+    let method = request.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+
+    match method {
         "initialize" => {
+            peer::initialize(request.get("params"));
             json!({
                 "jsonrpc": "2.0",
                 "id": request.get("id"),
                 "result": {
                     "protocolVersion": "2024-11-05",
                     "capabilities": {
-                        "tools": {}
+                        "tools": { "listChanged": tools.tool_list_may_change() },
+                        "resources": {},
+                        "logging": {}
                     },
                     "serverInfo": {
                         "name": "splitwise-mcp-server",
-                        "version": "0.1.0"
-                    }
+                        "version": "0.1.0",
+                        "account": tools.account_summary()
+                    },
+                    "instructions": tools.instructions()
                 }
             })
         }
         "tools/list" => {
-            let tools = state.tools.get_tools();
+            let cursor = request
+                .get("params")
+                .and_then(|p| p.get("cursor"))
+                .and_then(|c| c.as_str());
+            match tools.list_tools(cursor) {
+                Ok(result) => json!({
+                    "jsonrpc": "2.0",
+                    "id": request.get("id"),
+                    "result": result
+                }),
+                Err(e) => errors::from_error(id, &e),
+            }
+        }
+        "resources/list" => {
             json!({
                 "jsonrpc": "2.0",
                 "id": request.get("id"),
                 "result": {
-                    "tools": tools
+                    "resources": tools.get_resources()
                 }
             })
         }
+        "resources/read" => {
+            let Some(uri) = request.get("params").and_then(|p| p.get("uri")).and_then(|u| u.as_str()) else {
+                return errors::response(id, errors::INVALID_PARAMS, "resources/read needs a uri");
+            };
+            match client_log::forward(sink.clone(), tools.read_resource(uri)).await {
+                Ok(result) => json!({
+                    "jsonrpc": "2.0",
+                    "id": request.get("id"),
+                    "result": result
+                }),
+                Err(e) => errors::from_error(id, &e),
+            }
+        }
+        "ping" => {
+            json!({
+                "jsonrpc": "2.0",
+                "id": request.get("id"),
+                "result": {}
+            })
+        }
+        "logging/setLevel" => {
+            let Some(level) = request.get("params").and_then(|p| p.get("level")).and_then(|l| l.as_str()) else {
+                return errors::response(id, errors::INVALID_PARAMS, "logging/setLevel needs a level");
+            };
+            match client_log::set_level(level) {
+                Ok(()) => json!({
+                    "jsonrpc": "2.0",
+                    "id": request.get("id"),
+                    "result": {}
+                }),
+                Err(e) => errors::response(id, errors::INVALID_PARAMS, e),
+            }
+        }
         "tools/call" => {
-            let params = request.get("params").ok_or(StatusCode::BAD_REQUEST)?;
-            let tool_name = params
-                .get("name")
-                .and_then(|n| n.as_str())
-                .ok_or(StatusCode::BAD_REQUEST)?;
-            let arguments = params.get("arguments").cloned();
-
-            match state.tools.handle_tool_call(tool_name, arguments).await {
+            let params = request.get("params");
+            let Some(tool_name) = params.and_then(|p| p.get("name")).and_then(|n| n.as_str()) else {
+                return errors::response(id, errors::INVALID_PARAMS, "tools/call needs the tool's name");
+            };
+            let mut arguments = params.and_then(|p| p.get("arguments")).cloned();
+
+            // Clients that can't add tool arguments may request tracing with a header instead
+            let trace_header = headers
+                .get("x-mcp-trace")
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1");
+            if trace_header {
+                let args = arguments.get_or_insert_with(|| json!({}));
+                if let Some(obj) = args.as_object_mut() {
+                    obj.insert("trace".to_string(), json!(true));
+                }
+            }
+
+            let call = tools.handle_tool_call(tool_name, arguments);
+            match client_log::forward(sink.clone(), call).await {
                 Ok(result) => {
                     json!({
                         "jsonrpc": "2.0",
                         "id": request.get("id"),
                         "result": {
-                            "content": [{
-                                "type": "text",
-                                "text": result.to_string()
-                            }]
-                        }
-                    })
-                }
-                Err(e) => {
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": request.get("id"),
-                        "error": {
-                            "code": -32603,
-                            "message": e.to_string()
+                            "content": render::content(&result)
                         }
                     })
                 }
+                Err(e) => errors::from_error(id, &e),
             }
         }
-        _ => {
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "error": {
-                    "code": -32601,
-                    "message": format!("Method not found: {}", method)
-                }
-            })
-        }
-    };
-
-    Ok(Json(response))
+        _ => errors::response(id, errors::METHOD_NOT_FOUND, format!("Method not found: {}", method)),
+    }
 }
 
 // Health check endpoint
@@ -200,6 +425,55 @@ async fn health_check() -> impl IntoResponse {
     }))
 }
 
+// Prometheus metrics: Splitwise API calls, errors and latency per endpoint
+async fn metrics_handler(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let tools = check_auth(&headers, &state).await?;
+    // The default account sees every tenant's figures, each labelled; a tenant sees only its own
+    let metrics = if Arc::ptr_eq(&tools, &state.tools) {
+        let mut all = vec![state.tools.as_ref()];
+        all.extend(state.tenants.iter().map(|t| t.tools.as_ref()));
+        SplitwiseTools::metrics_of(&all)
+    } else {
+        tools.metrics()
+    };
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics))
+}
+
+// HTML status page for a browser, which asks for the OAuth client ID and secret (Basic auth)
+async fn dashboard_handler(headers: HeaderMap, State(state): State<AppState>) -> Response {
+    let tools = match check_auth(&headers, &state).await {
+        Ok(tools) => tools,
+        Err(StatusCode::UNAUTHORIZED) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Basic realm=\"splitwise-mcp\"")],
+            )
+                .into_response()
+        }
+        Err(status) => return status.into_response(),
+    };
+    let (snapshot, status) = tokio::join!(tools.dashboard_snapshot(), tools.handle_tool_call("server_status", None));
+    match (snapshot, status) {
+        (Ok(snapshot), Ok(status)) => Html(dashboard::render(&snapshot, &status)).into_response(),
+        (Err(e), _) | (_, Err(e)) => {
+            (StatusCode::BAD_GATEWAY, format!("Could not load the dashboard: {:#}", e)).into_response()
+        }
+    }
+}
+
+// A Splitwise client for `api_key`, set up as the configuration says
+fn splitwise_client(config: &Config, api_key: String) -> Result<SplitwiseClient> {
+    Ok(SplitwiseClient::new(api_key)?
+        .with_http_client(config.http_client()?)
+        .with_form_bodies(config.form_encoded_bodies)
+        .with_user_agent(config.user_agent.clone())
+        .with_request_ids(config.request_ids)
+        .with_expense_cache(Duration::from_secs(config.expense_cache_ttl_secs)))
+}
+
 // Server info endpoint
 async fn server_info() -> impl IntoResponse {
     Json(json!({
@@ -209,12 +483,15 @@ async fn server_info() -> impl IntoResponse {
         "transport": "http",
         "capabilities": {
             "tools": true,
-            "resources": false,
+            "resources": true,
+            "logging": true,
             "prompts": false
         },
         "endpoints": {
             "mcp": "/mcp",
             "health": "/health",
+            "metrics": "/metrics",
+            "openapi": "/api/openapi.json",
             "info": "/"
         }
     }))
@@ -223,11 +500,15 @@ async fn server_info() -> impl IntoResponse {
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
+    // Console output, plus the events forwarded to MCP clients that asked for them
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer().with_filter(
+                tracing_subscriber::EnvFilter::from_default_env()
+                    .add_directive(tracing::Level::INFO.into()),
+            ),
         )
+        .with(client_log::layer())
         .init();
 
     // Load environment variables
@@ -236,14 +517,27 @@ async fn main() -> Result<()> {
     info!("Starting Splitwise MCP HTTP/SSE server...");
 
     // Get configuration from environment
-    let api_key = env::var("SPLITWISE_API_KEY")
-        .context("SPLITWISE_API_KEY environment variable not set")?;
-    
+    let config = Config {
+        transport: "http",
+        ..Config::from_env()
+    };
+    let client = if config.sandbox {
+        info!("SANDBOX mode: serving fixture data, no Splitwise account is used");
+        SplitwiseClient::sandbox()
+    } else {
+        let api_key = env::var("SPLITWISE_API_KEY")
+            .context("SPLITWISE_API_KEY environment variable not set")?;
+        splitwise_client(&config, api_key)?
+    };
     let auth_token = env::var("MCP_AUTH_TOKEN")
         .unwrap_or_else(|_| {
             warn!("MCP_AUTH_TOKEN not set, using default token (INSECURE!)");
             "default-token".to_string()
         });
+    let tenant_configs = match env::var("TENANTS_FILE") {
+        Ok(path) => tenants::load(&path, &auth_token)?,
+        Err(_) => Vec::new(),
+    };
     
     let client_id = env::var("OAUTH_CLIENT_ID")
         .unwrap_or_else(|_| {
@@ -263,42 +557,125 @@ async fn main() -> Result<()> {
         .parse::<u16>()
         .context("Invalid PORT")?;
 
+    let keep_alive = env::var("SSE_KEEPALIVE_SECS")
+        .unwrap_or_else(|_| "15".to_string())
+        .parse::<u64>()
+        .context("Invalid SSE_KEEPALIVE_SECS")?;
+    let idle_timeout = env::var("SESSION_IDLE_TIMEOUT_SECS")
+        .unwrap_or_else(|_| "1800".to_string())
+        .parse::<u64>()
+        .context("Invalid SESSION_IDLE_TIMEOUT_SECS")?;
+    let dashboard = env::var("DASHBOARD").is_ok_and(|v| v.eq_ignore_ascii_case("true") || v == "1");
+
     // Initialize Splitwise client and tools
-    let client = Arc::new(SplitwiseClient::new(api_key)?);
-    let tools = Arc::new(SplitwiseTools::new(client));
+    let client = Arc::new(client);
+    let tools = Arc::new(SplitwiseTools::new(client, config.clone())?);
+
+    // Each tenant gets its own client, caches and local store (under DATA_DIR/tenants/<name>), and
+    // files only under tenants/<name> of each HTTP_FILE_DIRS directory
+    let mut tenants = Vec::new();
+    for tenant in &tenant_configs {
+        let client = if config.sandbox {
+            SplitwiseClient::sandbox()
+        } else {
+            splitwise_client(&config, tenant.splitwise_api_key.clone())?
+        };
+        let tenant_config = Config {
+            data_dir: config.data_dir.as_ref().map(|dir| dir.join("tenants").join(&tenant.name)),
+            http_file_dirs: config.http_file_dirs.iter().map(|dir| dir.join("tenants").join(&tenant.name)).collect(),
+            ..config.clone()
+        };
+        let tools = SplitwiseTools::new(Arc::new(client.with_tenant(tenant.name.clone())), tenant_config)
+            .with_context(|| format!("Failed to set up tenant '{}'", tenant.name))?;
+        tenants.push(Tenant::new(tenant, Arc::new(tools)));
+    }
+    if !tenants.is_empty() {
+        info!("Tenants: {}", tenants.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", "));
+    }
+
+    // Load who "me" is and their groups in the background, so neither the first tool call nor
+    // initialize's account summary waits for them
+    for tools in std::iter::once(&tools).chain(tenants.iter().map(|t| &t.tools)) {
+        let tools = tools.clone();
+        tokio::spawn(async move { tools.warm_up().await });
+    }
+
+    // Drop idle sessions every so often; a request in one that timed out is refused anyway
+    let sessions = Arc::new(Sessions::new(Duration::from_secs(idle_timeout)));
+    tokio::spawn({
+        let sessions = sessions.clone();
+        async move {
+            let mut sweep = tokio::time::interval(Duration::from_secs((idle_timeout / 2).clamp(1, 60)));
+            loop {
+                sweep.tick().await;
+                let expired = sessions.expire();
+                for session in &expired {
+                    peer::forget(session);
+                }
+                debug!("{} idle MCP sessions dropped", expired.len());
+            }
+        }
+    });
 
     // Create application state
     let state = AppState {
         tools,
+        tenants: Arc::new(tenants),
         auth_token: auth_token.clone(),
         client_id: client_id.clone(),
         client_secret: client_secret.clone(),
+        sessions,
+        keep_alive: Duration::from_secs(keep_alive.max(1)),
     };
 
     // Configure CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static("x-mcp-trace"),
+            header::HeaderName::from_static(SESSION_HEADER),
+        ])
+        .expose_headers([header::HeaderName::from_static(SESSION_HEADER)]);
 
     // Build the router
-    let app = Router::new()
+    let mut routes = Router::new()
         // MCP endpoint
-        .route("/mcp", post(mcp_handler))
+        .route("/mcp", post(mcp_handler).delete(end_session))
         // OAuth2 token endpoint
         .route("/oauth/token", post(oauth_token_handler))
         // Utility endpoints
         .route("/health", get(health_check))
+        .route("/metrics", get(metrics_handler))
         .route("/", get(server_info))
+        // REST endpoints over the same tools, and their OpenAPI document
+        .merge(rest::routes());
+    if dashboard {
+        routes = routes.route("/dashboard", get(dashboard_handler));
+    }
+    let app = routes
         // Add state and middleware
         .with_state(state)
-        .layer(ServiceBuilder::new().layer(cors));
+        .layer(
+            ServiceBuilder::new()
+                .layer(cors)
+                // gzip or brotli, as the client's Accept-Encoding allows; small replies aren't worth
+                // it, and event streams would be held back until the compressor flushed
+                .layer(CompressionLayer::new().compress_when(
+                    SizeAbove::new(COMPRESS_MIN_BYTES).and(NotForContentType::const_new("text/event-stream")),
+                )),
+        );
 
     // Bind to address
     let addr = format!("0.0.0.0:{}", port);
     info!("HTTP server listening on {}", addr);
     info!("MCP endpoint: http://{}:{}/mcp", "localhost", port);
     info!("OAuth2 token endpoint: http://{}:{}/oauth/token", "localhost", port);
+    if dashboard {
+        info!("Dashboard: http://{}:{}/dashboard", "localhost", port);
+    }
     info!("Client ID: {}", client_id);
     info!("Client Secret: {} (keep this secret!)", if client_secret.len() > 10 { 
         format!("{}...", &client_secret[..10]) 
@@ -3,37 +3,106 @@ use dotenv::dotenv;
 use serde_json::json;
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
-use tracing_subscriber;
+use tracing_subscriber::prelude::*;
 
+mod accounts;
+mod budget;
+mod cache;
+mod chart;
+mod client_log;
+mod config;
+mod crypto;
+mod errors;
+mod exchange;
+mod export;
+mod i18n;
+mod jobs;
+mod matching;
+mod merchants;
+mod metadata;
+mod nl_query;
+mod pdf;
+mod peer;
+mod projection;
+mod receipt;
+mod redact;
+mod render;
+mod resolve;
+mod roots;
+mod sampling;
+mod sandbox;
 mod splitwise;
+mod store;
+mod sync;
+mod tool_policy;
 mod tools;
+mod trace;
 mod types;
+mod warnings;
+mod write_queue;
 
+use config::Config;
+use peer::Peer;
 use splitwise::SplitwiseClient;
 use tools::SplitwiseTools;
 
+// How often TOOL_POLICY_FILE is checked for changes to announce
+const TOOL_POLICY_POLL: Duration = Duration::from_secs(2);
+
 // Simple stdio server that responds to JSON-RPC requests
 async fn run_server() -> Result<()> {
     use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
     
-    let api_key = env::var("SPLITWISE_API_KEY")
-        .context("SPLITWISE_API_KEY environment variable not set")?;
+    let config = Config::from_env();
+    let client = if config.sandbox {
+        info!("SANDBOX mode: serving fixture data, no Splitwise account is used");
+        SplitwiseClient::sandbox()
+    } else {
+        let api_key = env::var("SPLITWISE_API_KEY")
+            .context("SPLITWISE_API_KEY environment variable not set")?;
+        SplitwiseClient::new(api_key)?
+            .with_http_client(config.http_client()?)
+            .with_form_bodies(config.form_encoded_bodies)
+            .with_user_agent(config.user_agent.clone())
+            .with_request_ids(config.request_ids)
+            .with_expense_cache(Duration::from_secs(config.expense_cache_ttl_secs))
+    };
+
+    let client = Arc::new(client);
+    let tools = Arc::new(SplitwiseTools::new(client, config)?);
 
-    let client = Arc::new(SplitwiseClient::new(api_key)?);
-    let tools = Arc::new(SplitwiseTools::new(client));
+    // Load who "me" is and their groups in the background, so neither the first tool call nor
+    // initialize's account summary waits for them
+    tokio::spawn({
+        let tools = tools.clone();
+        async move { tools.warm_up().await }
+    });
     
     let stdin = tokio::io::stdin();
-    let mut stdout = tokio::io::stdout();
     let reader = BufReader::new(stdin);
     let mut lines = reader.lines();
+
+    // Responses and log notifications share stdout, so one task writes both
+    let (out, mut outgoing) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = outgoing.recv().await {
+            stdout.write_all(format!("{}\n", message).as_bytes()).await?;
+            stdout.flush().await?;
+        }
+        Ok::<(), std::io::Error>(())
+    });
     
     // Send initialization response
     let _init_response = json!({
         "jsonrpc": "2.0",
         "result": {
             "capabilities": {
-                "tools": {}
+                "tools": {},
+                "resources": {},
+                "logging": {}
             },
             "serverInfo": {
                 "name": "splitwise-mcp-server",
@@ -42,43 +111,140 @@ async fn run_server() -> Result<()> {
         }
     });
     
+    // Read on a task of its own, so a tool waiting for the client's answer to a sampling
+    // request gets it while the loop below is busy with that tool call
+    let (incoming, mut requests) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+    let reader = tokio::spawn({
+        let out = out.clone();
+        async move {
+            while let Some(line) = lines.next_line().await? {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let message: serde_json::Value = match serde_json::from_str(&line) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        out.send(errors::response(None, errors::PARSE_ERROR, format!("Parse error: {}", e)))?;
+                        continue;
+                    }
+                };
+                if !peer::resolve(&Peer::default(), &message) && incoming.send(message).is_err() {
+                    break;
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+    });
+
+    // Tell the client when TOOL_POLICY_FILE changes what it is offered
+    if tools.tool_list_may_change() {
+        tokio::spawn({
+            let (tools, out) = (tools.clone(), out.clone());
+            async move {
+                let mut ticks = tokio::time::interval(TOOL_POLICY_POLL);
+                loop {
+                    ticks.tick().await;
+                    if let Some(notice) = tools.tools_changed_notice() {
+                        if out.send(notice).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
     info!("MCP Server ready. Waiting for requests...");
     
-    while let Some(line) = lines.next_line().await? {
-        if line.trim().is_empty() {
+    while let Some(request) = requests.recv().await {
+        // Notifications (initialized, roots/list_changed) get no response
+        if request.get("id").is_none() && request.get("method").is_some() {
             continue;
         }
-        
-        let request: serde_json::Value = serde_json::from_str(&line)?;
-        
+
         let response = if let Some(method) = request.get("method").and_then(|m| m.as_str()) {
             match method {
                 "initialize" => {
+                    peer::initialize(request.get("params"));
                     json!({
                         "jsonrpc": "2.0",
                         "id": request.get("id"),
                         "result": {
                             "protocolVersion": "2024-11-05",
                             "capabilities": {
-                                "tools": {}
+                                "tools": { "listChanged": tools.tool_list_may_change() },
+                                "resources": {},
+                                "logging": {}
                             },
                             "serverInfo": {
                                 "name": "splitwise-mcp-server",
-                                "version": "0.1.0"
-                            }
+                                "version": "0.1.0",
+                                "account": tools.account_summary()
+                            },
+                            "instructions": tools.instructions()
                         }
                     })
                 }
                 "tools/list" => {
-                    let tool_list = tools.get_tools();
+                    let cursor = request
+                        .get("params")
+                        .and_then(|p| p.get("cursor"))
+                        .and_then(|c| c.as_str());
+                    match tools.list_tools(cursor) {
+                        Ok(result) => json!({
+                            "jsonrpc": "2.0",
+                            "id": request.get("id"),
+                            "result": result
+                        }),
+                        Err(e) => errors::from_error(request.get("id"), &e),
+                    }
+                }
+                "resources/list" => {
                     json!({
                         "jsonrpc": "2.0",
                         "id": request.get("id"),
                         "result": {
-                            "tools": tool_list
+                            "resources": tools.get_resources()
                         }
                     })
                 }
+                "resources/read" => {
+                    let uri = request
+                        .get("params")
+                        .and_then(|p| p.get("uri"))
+                        .and_then(|u| u.as_str())
+                        .unwrap_or("");
+                    match client_log::forward(out.clone(), tools.read_resource(uri)).await {
+                        Ok(result) => json!({
+                            "jsonrpc": "2.0",
+                            "id": request.get("id"),
+                            "result": result
+                        }),
+                        Err(e) => errors::from_error(request.get("id"), &e),
+                    }
+                }
+                "ping" => {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": request.get("id"),
+                        "result": {}
+                    })
+                }
+                "logging/setLevel" => {
+                    let level = request
+                        .get("params")
+                        .and_then(|p| p.get("level"))
+                        .and_then(|l| l.as_str())
+                        .unwrap_or("");
+                    match client_log::set_level(level) {
+                        Ok(()) => json!({
+                            "jsonrpc": "2.0",
+                            "id": request.get("id"),
+                            "result": {}
+                        }),
+                        Err(e) => errors::response(request.get("id"), errors::INVALID_PARAMS, e),
+                    }
+                }
                 "tools/call" => {
                     let empty_params = json!({});
                     let params = request.get("params").unwrap_or(&empty_params);
@@ -87,69 +253,47 @@ async fn run_server() -> Result<()> {
                         .unwrap_or("");
                     let arguments = params.get("arguments").cloned();
                     
-                    match tools.handle_tool_call(tool_name, arguments).await {
+                    let call = peer::connect(Peer::default(), out.clone(), tools.handle_tool_call(tool_name, arguments));
+                    match client_log::forward(out.clone(), call).await {
                         Ok(result) => {
                             json!({
                                 "jsonrpc": "2.0",
                                 "id": request.get("id"),
                                 "result": {
-                                    "content": [{
-                                        "type": "text",
-                                        "text": result.to_string()
-                                    }]
-                                }
-                            })
-                        }
-                        Err(e) => {
-                            json!({
-                                "jsonrpc": "2.0",
-                                "id": request.get("id"),
-                                "error": {
-                                    "code": -32603,
-                                    "message": e.to_string()
+                                    "content": render::content(&result)
                                 }
                             })
                         }
+                        Err(e) => errors::from_error(request.get("id"), &e),
                     }
                 }
-                _ => {
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": request.get("id"),
-                        "error": {
-                            "code": -32601,
-                            "message": format!("Method not found: {}", method)
-                        }
-                    })
-                }
+                _ => errors::response(request.get("id"), errors::METHOD_NOT_FOUND, format!("Method not found: {}", method)),
             }
         } else {
-            json!({
-                "jsonrpc": "2.0",
-                "id": request.get("id"),
-                "error": {
-                    "code": -32600,
-                    "message": "Invalid request"
-                }
-            })
+            errors::response(request.get("id"), errors::INVALID_REQUEST, "Invalid request")
         };
         
-        let response_str = format!("{}\n", response);
-        stdout.write_all(response_str.as_bytes()).await?;
-        stdout.flush().await?;
+        out.send(response)?;
     }
-    
+
+    drop(out);
+    reader.await??;
+    writer.await??;
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
+    // Console output, plus the events forwarded to MCP clients that asked for them
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer().with_filter(
+                tracing_subscriber::EnvFilter::from_default_env()
+                    .add_directive(tracing::Level::INFO.into()),
+            ),
         )
+        .with(client_log::layer())
         .init();
 
     // Load environment variables
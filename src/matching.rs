@@ -0,0 +1,178 @@
+use crate::merchants;
+
+// Bank statement lines paired with Splitwise expenses for reconciliation. A line and an expense
+// can be the same payment when their amounts differ by at most the amount tolerance, their
+// dates by at most the date window (banks book card payments a few days late) and, when a
+// minimum is set, their descriptions are at least that alike. Among those pairs the closest
+// amounts are taken first, then the closest dates, then the more alike descriptions, and each
+// line and expense is used once, so two equal charges on consecutive days pair up with the two
+// expenses of those days.
+
+/// A bank line or an expense, reduced to what matching looks at.
+pub struct Entry {
+    pub date: chrono::NaiveDate,
+    pub amount: f64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    pub date_window_days: i64,
+    pub amount_tolerance: f64,
+    // 0 to 1, see merchants::similarity; 0 lets descriptions only break ties
+    pub min_similarity: f64,
+}
+
+impl Rules {
+    fn allows_amount_and_date(&self, amount_difference: f64, days_apart: i64) -> bool {
+        // Half a cent of slack for amounts that went through f64
+        amount_difference <= self.amount_tolerance + 0.005 && days_apart <= self.date_window_days
+    }
+
+    /// Why a line and an expense compared as in `m` can't be the same payment; empty when they can.
+    pub fn mismatches(&self, m: &Match) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if m.amount_difference > self.amount_tolerance + 0.005 {
+            reasons.push(format!(
+                "the amounts differ by {:.2}, more than the tolerance of {:.2}",
+                m.amount_difference, self.amount_tolerance
+            ));
+        }
+        if m.days_apart > self.date_window_days {
+            reasons.push(format!(
+                "{} days apart, more than the window of {}",
+                m.days_apart, self.date_window_days
+            ));
+        }
+        if m.similarity < self.min_similarity {
+            reasons.push(format!(
+                "descriptions {:.2} alike, less than the minimum of {:.2}",
+                m.similarity, self.min_similarity
+            ));
+        }
+        reasons
+    }
+}
+
+/// A line paired with an expense, by their positions in the lists given to `pair`.
+pub struct Match {
+    pub line: usize,
+    pub expense: usize,
+    pub amount_difference: f64,
+    pub days_apart: i64,
+    pub similarity: f64,
+}
+
+/// How line `line` and expense `expense` compare, whatever the rules.
+pub fn compare(lines: &[Entry], expenses: &[Entry], line: usize, expense: usize) -> Match {
+    let (l, e) = (&lines[line], &expenses[expense]);
+    Match {
+        line,
+        expense,
+        amount_difference: (l.amount - e.amount).abs(),
+        days_apart: (l.date - e.date).num_days().abs(),
+        similarity: merchants::similarity(&l.description, &e.description),
+    }
+}
+
+/// Which of two pairs `pair` takes first.
+pub fn closer(a: &Match, b: &Match) -> std::cmp::Ordering {
+    let cents = |amount: f64| (amount * 100.0).round() as i64;
+    cents(a.amount_difference)
+        .cmp(&cents(b.amount_difference))
+        .then(a.days_apart.cmp(&b.days_apart))
+        .then(b.similarity.total_cmp(&a.similarity))
+        .then(a.line.cmp(&b.line))
+        .then(a.expense.cmp(&b.expense))
+}
+
+/// The pairing of statement lines with expenses under `rules`, in line order.
+pub fn pair(lines: &[Entry], expenses: &[Entry], rules: Rules) -> Vec<Match> {
+    let mut candidates = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        for (j, expense) in expenses.iter().enumerate() {
+            // Descriptions are only compared for pairs the amounts and dates allow
+            let amount_difference = (line.amount - expense.amount).abs();
+            let days_apart = (line.date - expense.date).num_days().abs();
+            if rules.allows_amount_and_date(amount_difference, days_apart) {
+                let m = compare(lines, expenses, i, j);
+                if m.similarity >= rules.min_similarity {
+                    candidates.push(m);
+                }
+            }
+        }
+    }
+    candidates.sort_by(closer);
+    let mut line_used = vec![false; lines.len()];
+    let mut expense_used = vec![false; expenses.len()];
+    let mut matches: Vec<Match> = candidates
+        .into_iter()
+        .filter(|m| {
+            let free = !line_used[m.line] && !expense_used[m.expense];
+            if free {
+                line_used[m.line] = true;
+                expense_used[m.expense] = true;
+            }
+            free
+        })
+        .collect();
+    matches.sort_by_key(|m| m.line);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, amount: f64, description: &str) -> Entry {
+        Entry {
+            date: chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount,
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn each_line_takes_the_closest_free_expense() {
+        let lines = [
+            entry("2024-03-04", 42.10, "CARREFOUR 22 MADRID"),
+            entry("2024-03-05", 12.00, "NETFLIX.COM"),
+            entry("2024-03-06", 12.00, "SPOTIFY"),
+            entry("2024-03-20", 99.99, "AMAZON MKTPLACE"),
+        ];
+        let expenses = [
+            entry("2024-03-02", 42.10, "Groceries Carrefour"),
+            entry("2024-03-06", 12.00, "Spotify"),
+            entry("2024-03-05", 12.00, "Netflix"),
+            entry("2024-03-01", 60.00, "Dinner"),
+        ];
+        let rules = Rules { date_window_days: 3, amount_tolerance: 0.0, min_similarity: 0.0 };
+        let matches = pair(&lines, &expenses, rules);
+        let pairs: Vec<(usize, usize)> = matches.iter().map(|m| (m.line, m.expense)).collect();
+        assert_eq!(pairs, vec![(0, 0), (1, 2), (2, 1)]);
+        assert_eq!(matches[0].days_apart, 2);
+
+        // Out of the window, and a cent off without a tolerance
+        let late = [entry("2024-03-09", 42.10, "Carrefour"), entry("2024-03-02", 42.11, "Carrefour")];
+        assert!(pair(&late[..1], &expenses, rules).is_empty());
+        assert!(pair(&late[1..], &expenses, rules).is_empty());
+        let loose = Rules { date_window_days: 3, amount_tolerance: 0.01, min_similarity: 0.0 };
+        assert_eq!(pair(&late[1..], &expenses, loose)[0].expense, 0);
+    }
+
+    #[test]
+    fn a_similarity_minimum_keeps_unrelated_charges_apart() {
+        let lines = [entry("2024-03-05", 12.00, "NETFLIX.COM"), entry("2024-03-05", 30.00, "SHELL 0042")];
+        let expenses = [entry("2024-03-05", 12.00, "Spotify"), entry("2024-03-04", 30.00, "Fuel at Shell")];
+        let strict = Rules { date_window_days: 3, amount_tolerance: 0.0, min_similarity: 0.5 };
+        let matches = pair(&lines, &expenses, strict);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].line, matches[0].expense), (1, 1));
+
+        let reasons = strict.mismatches(&compare(&lines, &expenses, 0, 1));
+        assert_eq!(reasons.len(), 2);
+        assert!(reasons[0].starts_with("the amounts differ by 18.00"));
+        assert!(reasons[1].starts_with("descriptions 0.00 alike"));
+        assert!(strict.mismatches(&matches[0]).is_empty());
+    }
+}
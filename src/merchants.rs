@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::nl_query;
+
+// Merchant names from free-text expense descriptions. A description is reduced to a key
+// (lowercase, no accents, cut at the "*" card processors add, without digits and legal
+// suffixes), so "UBER *TRIP", "Uber BV" and "uber" all become "uber". Keys that are close
+// enough (one is the start of the other, or only a typo apart) are then merged into the more
+// frequent one. A merchant is named by a description that was already clean ("Supermercado
+// Día"), or else by its key in title case. Aliases from MERCHANT_ALIASES_FILE win over all of
+// that: any description containing an alias's words is that merchant.
+
+// Words that say nothing about who was paid
+const NOISE: [&str; 18] = [
+    "bv", "inc", "llc", "ltd", "sa", "srl", "sas", "gmbh", "co", "corp", "com", "www", "the", "pos",
+    "payment", "purchase", "compra", "pago",
+];
+
+// How similar two keys must be to count as the same merchant
+const SIMILARITY: f64 = 0.93;
+
+pub struct Merchants {
+    // Normalized alias words -> merchant name
+    aliases: Vec<(String, String)>,
+}
+
+impl Merchants {
+    pub fn new(aliases: HashMap<String, String>) -> Self {
+        let mut aliases: Vec<(String, String)> = aliases
+            .into_iter()
+            .map(|(alias, merchant)| (key(&alias), merchant))
+            .filter(|(alias, _)| !alias.is_empty())
+            .collect();
+        // Longer aliases first, so "uber eats" wins over "uber"
+        aliases.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+        Self { aliases }
+    }
+
+    /// Load a JSON object of alias -> merchant name, e.g. {"mcdonalds": "McDonald's"}.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read MERCHANT_ALIASES_FILE {}", path))?;
+        let aliases: HashMap<String, String> = serde_json::from_str(&text)
+            .with_context(|| format!("MERCHANT_ALIASES_FILE {} must be a JSON object of alias -> merchant name", path))?;
+        Ok(Self::new(aliases))
+    }
+
+    /// The merchant of each description, in the same order.
+    pub fn group(&self, descriptions: &[&str]) -> Vec<String> {
+        let keys: Vec<String> = descriptions.iter().map(|d| key(d)).collect();
+
+        // Most frequent keys become the names the others merge into
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for k in keys.iter().filter(|k| self.alias(k).is_none()) {
+            *counts.entry(k).or_default() += 1;
+        }
+        let mut ranked: Vec<(&str, usize)> = counts.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.len().cmp(&b.0.len())).then(a.0.cmp(b.0)));
+        let mut canonical: HashMap<&str, &str> = HashMap::new();
+        let mut names: Vec<&str> = Vec::new();
+        for (k, _) in ranked {
+            let target = names.iter().copied().find(|name| same_merchant(name, k)).unwrap_or(k);
+            if target == k {
+                names.push(k);
+            }
+            canonical.insert(k, target);
+        }
+
+        // Name each merchant after a description written the way a person would write it
+        let mut display: HashMap<&str, String> = HashMap::new();
+        for (k, description) in keys.iter().zip(descriptions) {
+            let description = description.trim();
+            let cased = description != description.to_lowercase() && description != description.to_uppercase();
+            if canonical.get(k.as_str()) == Some(&k.as_str()) && cased && words(description) == *k {
+                display.entry(k).or_insert_with(|| description.to_string());
+            }
+        }
+
+        keys.iter()
+            .zip(descriptions)
+            .map(|(k, description)| match self.alias(k) {
+                Some(merchant) => merchant.to_string(),
+                None if k.is_empty() => description.trim().to_string(),
+                None => {
+                    let name = canonical.get(k.as_str()).copied().unwrap_or(k);
+                    display.get(name).cloned().unwrap_or_else(|| title_case(name))
+                }
+            })
+            .collect()
+    }
+
+    fn alias(&self, key: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| nl_query::contains_word(key, alias))
+            .map(|(_, merchant)| merchant.as_str())
+    }
+}
+
+// "UBER *TRIP 1234" -> "uber"; "Supermercado Día S.A." -> "supermercado dia"
+fn key(description: &str) -> String {
+    let text = nl_query::normalize(description);
+    let text = text.split('*').next().unwrap_or("");
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !w.chars().any(|c| c.is_ascii_digit()) && !NOISE.contains(w))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// All the words of a description, normalized; equal to its key when nothing was dropped
+fn words(description: &str) -> String {
+    nl_query::normalize(description)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn same_merchant(a: &str, b: &str) -> bool {
+    let starts = |long: &str, short: &str| long.starts_with(short) && long[short.len()..].starts_with(' ');
+    starts(a, b) || starts(b, a) || strsim::jaro_winkler(a, b) >= SIMILARITY
+}
+
+/// How alike two descriptions are as names of who was paid, from 0 to 1: 1 for the same
+/// merchant, else the share of the shorter key's words found (up to a typo) in the other, so
+/// "CARREFOUR EXPRESS MADRID" and "Cena Carrefour" get 0.5.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (key(a), key(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if same_merchant(&a, &b) {
+        return 1.0;
+    }
+    let (short, long) = if a.split(' ').count() <= b.split(' ').count() { (&a, &b) } else { (&b, &a) };
+    let shared = short
+        .split(' ')
+        .filter(|word| long.split(' ').any(|other| strsim::jaro_winkler(word, other) >= SIMILARITY))
+        .count();
+    shared as f64 / short.split(' ').count() as f64
+}
+
+fn title_case(key: &str) -> String {
+    key.split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_noise_and_spellings_collapse_into_one_merchant() {
+        let merchants = Merchants::new(HashMap::new());
+        let grouped = merchants.group(&["UBER *TRIP 8812", "Uber BV", "uber", "Uber trip home", "Carrefour", "CARREFOUR 22", "Carrefur", "Rent"]);
+        assert_eq!(grouped, vec!["Uber", "Uber", "Uber", "Uber", "Carrefour", "Carrefour", "Carrefour", "Rent"]);
+    }
+
+    #[test]
+    fn aliases_win_over_keys() {
+        let aliases = HashMap::from([
+            ("dia".to_string(), "Supermercado Día".to_string()),
+            ("uber eats".to_string(), "Uber Eats".to_string()),
+            ("uber".to_string(), "Uber".to_string()),
+        ]);
+        let merchants = Merchants::new(aliases);
+        assert_eq!(
+            merchants.group(&["Supermercado DIA 041", "UBER EATS *PENDING", "Uber *Trip", "###"]),
+            vec!["Supermercado Día", "Uber Eats", "Uber", "###"]
+        );
+    }
+
+    #[test]
+    fn similarity_ignores_card_noise() {
+        assert_eq!(similarity("UBER *TRIP 8812", "Uber BV"), 1.0);
+        assert_eq!(similarity("CARREFOUR EXPRESS 22 MADRID", "Cena Carrefour"), 0.5);
+        assert_eq!(similarity("Netflix", "Rent"), 0.0);
+        assert_eq!(similarity("1234", "Rent"), 0.0);
+    }
+
+    #[test]
+    fn clean_descriptions_name_their_merchant() {
+        let merchants = Merchants::new(HashMap::new());
+        assert_eq!(
+            merchants.group(&["SUPERMERCADO DIA 12", "Supermercado Día", "Cena en El Boliche"]),
+            vec!["Supermercado Día", "Supermercado Día", "Cena en El Boliche"]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// Structured metadata kept in an expense's `details` (notes), since Splitwise has no tags or
+// custom fields. It is a single line at the end of the notes:
+//
+//   [mcp] {"tags":["wedding"],"external_id":"INV-204","source":"bank-import"}
+//
+// Everything before that line is the user's own text and is never rewritten.
+
+const MARKER: &str = "[mcp] ";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Reference in another system (invoice number, bank transaction ID)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    // Where the expense came from (an importer name, "receipt", ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reimbursement: Option<Reimbursement>,
+    // Key of the create_expense call that made the expense, so a retry finds it (see write_queue.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    // Keys this version doesn't know about are carried along untouched
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Money the user expects back from outside Splitwise, e.g. from an employer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reimbursement {
+    pub payer: String,
+    pub amount: String,
+    pub currency_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reimbursed_on: Option<String>,
+}
+
+// Tags that track a reimbursement's state
+pub const REIMBURSABLE: &str = "reimbursable";
+pub const REIMBURSED: &str = "reimbursed";
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.external_id.is_none()
+            && self.source.is_none()
+            && self.reimbursement.is_none()
+            && self.idempotency_key.is_none()
+            && self.extra.is_empty()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        self.tags.contains(&tag)
+    }
+
+    pub fn add_tags<'a>(&mut self, tags: impl IntoIterator<Item = &'a String>) {
+        for tag in tags {
+            let tag = normalize_tag(tag);
+            if !tag.is_empty() && !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    pub fn remove_tags(&mut self, tags: &[String]) {
+        let removed: Vec<String> = tags.iter().map(|t| normalize_tag(t)).collect();
+        self.tags.retain(|t| !removed.contains(t));
+    }
+
+    pub fn set_tags(&mut self, tags: &[String]) {
+        self.tags.clear();
+        self.add_tags(tags);
+    }
+}
+
+/// Separate the user's notes from the metadata line. Notes without one yield empty metadata.
+pub fn split(details: Option<&str>) -> (String, Metadata) {
+    let details = details.unwrap_or("");
+    let Some(start) = details.rfind(MARKER).filter(|i| *i == 0 || details[..*i].ends_with('\n')) else {
+        return (details.to_string(), Metadata::default());
+    };
+    match serde_json::from_str(details[start + MARKER.len()..].trim()) {
+        Ok(metadata) => (details[..start].trim_end().to_string(), metadata),
+        // Something that merely looks like our marker; leave it as notes
+        Err(_) => (details.to_string(), Metadata::default()),
+    }
+}
+
+/// Put notes and metadata back together into a `details` value.
+pub fn join(notes: &str, metadata: &Metadata) -> String {
+    let notes = notes.trim_end();
+    if metadata.is_empty() {
+        return notes.to_string();
+    }
+    let line = format!("{}{}", MARKER, serde_json::to_string(metadata).unwrap_or_default());
+    if notes.is_empty() {
+        line
+    } else {
+        format!("{}\n\n{}", notes, line)
+    }
+}
+
+// Tags compare case-insensitively and are stored lowercase
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_and_metadata_round_trip() {
+        let mut metadata = Metadata::default();
+        metadata.add_tags(&["Wedding".to_string(), "wedding ".to_string(), "gifts".to_string()]);
+        metadata.external_id = Some("INV-204".to_string());
+        metadata.extra.insert("future".to_string(), Value::from(7));
+
+        let details = join("Paid at the venue\n", &metadata);
+        assert_eq!(
+            details,
+            "Paid at the venue\n\n[mcp] {\"tags\":[\"wedding\",\"gifts\"],\"external_id\":\"INV-204\",\"future\":7}"
+        );
+        let (notes, back) = split(Some(&details));
+        assert_eq!(notes, "Paid at the venue");
+        assert_eq!(back.tags, vec!["wedding", "gifts"]);
+        assert_eq!(back.external_id.as_deref(), Some("INV-204"));
+        assert_eq!(back.extra["future"], Value::from(7));
+        assert_eq!(join(&notes, &back), details);
+        assert!(back.has_tag("GIFTS"));
+    }
+
+    #[test]
+    fn notes_without_metadata_are_left_alone() {
+        assert_eq!(join("Just notes", &Metadata::default()), "Just notes");
+        let (notes, metadata) = split(None);
+        assert!(notes.is_empty() && metadata.is_empty());
+        // Only a whole line starting with the marker counts, and only when it parses
+        for details in ["see [mcp] {\"tags\":[\"x\"]}", "[mcp] not json"] {
+            let (notes, metadata) = split(Some(details));
+            assert_eq!(notes, details);
+            assert!(metadata.is_empty());
+        }
+        let (notes, metadata) = split(Some("[mcp] {\"tags\":[\"x\"]}"));
+        assert_eq!((notes.as_str(), metadata.tags), ("", vec!["x".to_string()]));
+    }
+}
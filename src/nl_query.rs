@@ -0,0 +1,322 @@
+use chrono::{Datelike, Days, Duration, Months, NaiveDate};
+use regex::Regex;
+use serde::Serialize;
+
+// Parser for constrained natural-language expense questions such as
+// "food spending in March in the Casa group". It only extracts filters from the text;
+// resolving group, friend and category names against the account is up to the caller.
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExpenseQuery {
+    // Inclusive start date (YYYY-MM-DD)
+    pub dated_after: Option<String>,
+    // Exclusive end date (YYYY-MM-DD)
+    pub dated_before: Option<String>,
+    // Human-readable description of the period, e.g. "March 2025"
+    pub period: Option<String>,
+    // Splitwise category names (parent or subcategory) matched through aliases
+    pub categories: Vec<String>,
+    // Text that names a group ("Casa" in "in the Casa group")
+    pub group_phrase: Option<String>,
+    // Text that names a friend ("Jamie" in "dinners with Jamie")
+    pub friend_phrase: Option<String>,
+    // Quoted text to look for in descriptions
+    pub search_text: Option<String>,
+}
+
+const MONTHS: [(&str, u32); 36] = [
+    ("january", 1), ("february", 2), ("march", 3), ("april", 4), ("may", 5), ("june", 6),
+    ("july", 7), ("august", 8), ("september", 9), ("october", 10), ("november", 11), ("december", 12),
+    ("jan", 1), ("feb", 2), ("mar", 3), ("apr", 4), ("jun", 6), ("jul", 7),
+    ("aug", 8), ("sep", 9), ("sept", 9), ("oct", 10), ("nov", 11), ("dec", 12),
+    ("enero", 1), ("febrero", 2), ("marzo", 3), ("abril", 4), ("mayo", 5), ("junio", 6),
+    ("julio", 7), ("agosto", 8), ("septiembre", 9), ("octubre", 10), ("noviembre", 11), ("diciembre", 12),
+];
+
+// Everyday words mapped to the Splitwise category they most likely mean
+const CATEGORY_ALIASES: [(&str, &str); 30] = [
+    ("food", "Food and drink"),
+    ("comida", "Food and drink"),
+    ("alimentos", "Food and drink"),
+    ("groceries", "Groceries"),
+    ("grocery", "Groceries"),
+    ("supermarket", "Groceries"),
+    ("supermercado", "Groceries"),
+    ("restaurant", "Dining out"),
+    ("restaurants", "Dining out"),
+    ("dining", "Dining out"),
+    ("dinners", "Dining out"),
+    ("eating out", "Dining out"),
+    ("drinks", "Liquor"),
+    ("alcohol", "Liquor"),
+    ("rent", "Rent"),
+    ("alquiler", "Rent"),
+    ("utilities", "Utilities"),
+    ("servicios", "Utilities"),
+    ("electricity", "Electricity"),
+    ("luz", "Electricity"),
+    ("internet", "TV/Phone/Internet"),
+    ("phone", "TV/Phone/Internet"),
+    ("transport", "Transportation"),
+    ("transportation", "Transportation"),
+    ("travel", "Transportation"),
+    ("fuel", "Gas/fuel"),
+    ("nafta", "Gas/fuel"),
+    ("hotel", "Hotel"),
+    ("hotels", "Hotel"),
+    ("entertainment", "Entertainment"),
+];
+
+// Largest N taken in "last N days"; anything longer than that isn't a period
+const MAX_RELATIVE_COUNT: u32 = 10_000;
+
+// Words that end a group or friend name when scanning around "group" / "with"
+const STOPWORDS: [&str; 24] = [
+    "in", "the", "for", "from", "of", "our", "my", "on", "at", "with", "and", "since", "during",
+    "between", "last", "this", "past", "before", "after", "until", "to", "group", "how", "much",
+];
+
+pub fn parse(question: &str, today: NaiveDate) -> ExpenseQuery {
+    let text = normalize(question);
+    let mut query = ExpenseQuery::default();
+
+    parse_period(&text, today, &mut query);
+
+    for (alias, category) in CATEGORY_ALIASES {
+        if contains_word(&text, alias) && !query.categories.iter().any(|c| c == category) {
+            query.categories.push(category.to_string());
+        }
+    }
+
+    let quoted = Regex::new(r#""([^"]+)""#).unwrap();
+    query.search_text = quoted.captures(question).map(|c| c[1].to_string());
+
+    let words: Vec<&str> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '-'))
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if let Some(pos) = words.iter().position(|w| *w == "group") {
+        // Prefer "the Casa group"; fall back to "group Casa"
+        let before = name_words(words[..pos].iter().rev());
+        query.group_phrase = if before.is_empty() {
+            Some(name_words(words[pos + 1..].iter()).join(" ")).filter(|s| !s.is_empty())
+        } else {
+            Some(before.into_iter().rev().collect::<Vec<_>>().join(" "))
+        };
+    }
+
+    if let Some(pos) = words.iter().position(|w| *w == "with") {
+        let name = name_words(words[pos + 1..].iter().take(2));
+        if !name.is_empty() {
+            query.friend_phrase = Some(name.join(" "));
+        }
+    }
+
+    query
+}
+
+// The Splitwise category an everyday word stands for ("restaurants" -> "Dining out")
+pub fn category_alias(word: &str) -> Option<&'static str> {
+    let word = normalize(word);
+    CATEGORY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == word.trim())
+        .map(|(_, category)| *category)
+}
+
+// Lowercase and strip common accents so "García" and "garcia" compare equal
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            other => other,
+        })
+        .collect()
+}
+
+pub fn contains_word(text: &str, word: &str) -> bool {
+    Regex::new(&format!(r"\b{}\b", regex::escape(word)))
+        .map(|re| re.is_match(text))
+        .unwrap_or(false)
+}
+
+// Collect consecutive non-stopwords, stopping at the first stopword or period word
+fn name_words<'a>(words: impl Iterator<Item = &'a &'a str>) -> Vec<&'a str> {
+    words
+        .take_while(|w| !STOPWORDS.contains(w) && month_number(w).is_none() && w.parse::<u32>().is_err())
+        .copied()
+        .collect()
+}
+
+/// The month a lowercase English or Spanish month name or abbreviation stands for.
+pub fn month_number(word: &str) -> Option<u32> {
+    MONTHS.iter().find(|(name, _)| *name == word).map(|(_, n)| *n)
+}
+
+fn parse_period(text: &str, today: NaiveDate, query: &mut ExpenseQuery) {
+    // Explicit ISO dates take precedence over everything else
+    let iso = Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap();
+    let dates: Vec<NaiveDate> = iso
+        .find_iter(text)
+        .filter_map(|m| NaiveDate::parse_from_str(m.as_str(), "%Y-%m-%d").ok())
+        .collect();
+    match dates.as_slice() {
+        [start, end, ..] => {
+            set_range(query, *start, *end + Duration::days(1), format!("{} to {}", start, end));
+            return;
+        }
+        [date] => {
+            if Regex::new(r"\b(before|until)\s+\d{4}").unwrap().is_match(text) {
+                query.dated_before = Some(date.to_string());
+                query.period = Some(format!("before {}", date));
+            } else {
+                query.dated_after = Some(date.to_string());
+                query.period = Some(format!("since {}", date));
+            }
+            return;
+        }
+        [] => {}
+    }
+
+    let relative = Regex::new(r"\b(?:last|past)\s+(\d+)\s+(day|week|month|year)s?\b").unwrap();
+    if let Some(c) = relative.captures(text) {
+        let n = c[1].parse::<u32>().ok().filter(|n| *n <= MAX_RELATIVE_COUNT);
+        let start = n.and_then(|n| match &c[2] {
+            "day" => today.checked_sub_days(Days::new(n as u64)),
+            "week" => today.checked_sub_days(Days::new(7 * n as u64)),
+            "month" => today.checked_sub_months(Months::new(n)),
+            _ => today.checked_sub_months(Months::new(12 * n)),
+        });
+        if let (Some(n), Some(start)) = (n, start) {
+            set_range(query, start, today + Duration::days(1), format!("last {} {}s", n, &c[2]));
+        }
+        return;
+    }
+
+    let month_start = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+    let year_start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let named = [
+        ("today", today, today + Duration::days(1)),
+        ("yesterday", today - Duration::days(1), today),
+        ("this week", week_start, today + Duration::days(1)),
+        ("last week", week_start - Duration::weeks(1), week_start),
+        ("this month", month_start, today + Duration::days(1)),
+        ("last month", month_start - Months::new(1), month_start),
+        ("this year", year_start, today + Duration::days(1)),
+        ("last year", year_start - Months::new(12), year_start),
+    ];
+    for (phrase, start, end) in named {
+        if contains_word(text, phrase) {
+            set_range(query, start, end, phrase.to_string());
+            return;
+        }
+    }
+
+    // "2025-03" is that month
+    let year_month = Regex::new(r"\b(20\d{2})-(\d{2})\b").unwrap();
+    if let Some(c) = year_month.captures(text) {
+        if let Some(start) = NaiveDate::from_ymd_opt(c[1].parse().unwrap(), c[2].parse().unwrap(), 1) {
+            set_range(query, start, start + Months::new(1), start.format("%B %Y").to_string());
+            return;
+        }
+    }
+
+    let month_names: Vec<&str> = MONTHS.iter().map(|(name, _)| *name).collect();
+    let month = Regex::new(&format!(r"\b({})\b(?:\s+(?:of\s+|de\s+)?(\d{{4}}))?", month_names.join("|"))).unwrap();
+    for c in month.captures_iter(text) {
+        // "may" is too common a word to count unless a year follows or it reads "in may"
+        if &c[1] == "may" && c.get(2).is_none() && !text.contains("in may") {
+            continue;
+        }
+        let number = month_number(&c[1]).unwrap();
+        // Without a year, a month means its most recent occurrence
+        let year = match c.get(2) {
+            Some(y) => y.as_str().parse().unwrap_or(today.year()),
+            None if number > today.month() => today.year() - 1,
+            None => today.year(),
+        };
+        if let Some(start) = NaiveDate::from_ymd_opt(year, number, 1) {
+            set_range(query, start, start + Months::new(1), start.format("%B %Y").to_string());
+            return;
+        }
+    }
+
+    let year = Regex::new(r"\b(20\d{2})\b").unwrap();
+    if let Some(c) = year.captures(text) {
+        let y: i32 = c[1].parse().unwrap();
+        if let (Some(start), Some(end)) = (NaiveDate::from_ymd_opt(y, 1, 1), NaiveDate::from_ymd_opt(y + 1, 1, 1)) {
+            set_range(query, start, end, y.to_string());
+        }
+    }
+}
+
+fn set_range(query: &mut ExpenseQuery, start: NaiveDate, end: NaiveDate, label: String) {
+    query.dated_after = Some(start.to_string());
+    query.dated_before = Some(end.to_string());
+    query.period = Some(label);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 5, 14).unwrap()
+    }
+
+    #[test]
+    fn relative_periods_end_today() {
+        let query = parse("groceries in the last 3 months", today());
+        assert_eq!(query.dated_after.as_deref(), Some("2025-02-14"));
+        assert_eq!(query.dated_before.as_deref(), Some("2025-05-15"));
+        assert_eq!(query.period.as_deref(), Some("last 3 months"));
+        assert_eq!(query.categories, vec!["Groceries"]);
+
+        let query = parse("past 2 weeks", today());
+        assert_eq!(query.dated_after.as_deref(), Some("2025-04-30"));
+    }
+
+    #[test]
+    fn oversized_relative_periods_are_no_period() {
+        for question in ["last 999999999 days", "last 400000 years", "last 99999999999 months"] {
+            let query = parse(question, today());
+            assert_eq!(query.period, None, "{}", question);
+            assert_eq!(query.dated_after, None, "{}", question);
+        }
+    }
+
+    #[test]
+    fn named_months_mean_their_latest_occurrence() {
+        let query = parse("food spending in March in the Casa group", today());
+        assert_eq!(query.dated_after.as_deref(), Some("2025-03-01"));
+        assert_eq!(query.dated_before.as_deref(), Some("2025-04-01"));
+        assert_eq!(query.group_phrase.as_deref(), Some("casa"));
+        assert_eq!(query.categories, vec!["Food and drink"]);
+
+        let query = parse("hotels in september", today());
+        assert_eq!(query.dated_after.as_deref(), Some("2024-09-01"));
+        // "may" alone is a verb, not a month
+        assert_eq!(parse("what may I owe", today()).period, None);
+    }
+
+    #[test]
+    fn iso_dates_and_names_are_picked_out() {
+        let query = parse("dinners with Jamie from 2025-01-10 to 2025-01-20 \"pizza\"", today());
+        assert_eq!(query.dated_after.as_deref(), Some("2025-01-10"));
+        assert_eq!(query.dated_before.as_deref(), Some("2025-01-21"));
+        assert_eq!(query.friend_phrase.as_deref(), Some("jamie"));
+        assert_eq!(query.search_text.as_deref(), Some("pizza"));
+
+        let query = parse("rent before 2025-02-01", today());
+        assert_eq!((query.dated_after, query.dated_before.as_deref()), (None, Some("2025-02-01")));
+    }
+}
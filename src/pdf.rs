@@ -0,0 +1,274 @@
+// A small PDF writer for reports that are shared outside Splitwise, such as group statements.
+// Documents are A4 pages of text lines and table rows in the standard Helvetica fonts, which
+// every PDF reader has, so nothing is embedded and the output stays a few kilobytes. Text is
+// encoded as WinAnsi (Latin-1 plus a few typographic marks), which covers Spanish and the other
+// western languages; anything else prints as "?". Rows flow onto a new page when one is full,
+// and every page gets a "Page n of m" footer.
+
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+// Line height as a multiple of the font size
+const LEADING: f64 = 1.35;
+
+/// Width of the area between the margins, for laying out columns.
+pub const CONTENT_WIDTH: f64 = PAGE_WIDTH - 2.0 * MARGIN;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table column: where it starts, counted from the left margin, how wide it is and how its cells line up.
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub x: f64,
+    pub width: f64,
+    pub align: Align,
+}
+
+pub struct Document {
+    // Content stream of each page
+    pages: Vec<Vec<u8>>,
+    // Baseline of the next line on the current page
+    y: f64,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self { pages: vec![Vec::new()], y: PAGE_HEIGHT - MARGIN }
+    }
+
+    pub fn title(&mut self, text: &str) {
+        self.line(text, 16.0, true);
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.space(8.0);
+        self.line(text, 12.0, true);
+        self.rule();
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.line(text, 10.0, false);
+    }
+
+    pub fn space(&mut self, points: f64) {
+        self.y -= points;
+    }
+
+    /// One table row, each cell cut with "…" to fit its column.
+    pub fn row(&mut self, cells: &[&str], columns: &[Column], bold: bool) {
+        let size = 9.0;
+        self.make_room(size);
+        let y = self.y - size;
+        for (cell, column) in cells.iter().zip(columns) {
+            let fitted = fit(cell, column.width - 4.0, size);
+            let x = match column.align {
+                Align::Left => MARGIN + column.x,
+                Align::Right => MARGIN + column.x + column.width - text_width(&fitted, size),
+            };
+            self.show(&fitted, x, y, size, bold);
+        }
+        self.y -= size * LEADING;
+    }
+
+    /// A thin line across the page.
+    pub fn rule(&mut self) {
+        let y = self.y - 2.0;
+        let page = self.pages.last_mut().expect("a document has a page");
+        page.extend(format!("0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n", MARGIN, y, PAGE_WIDTH - MARGIN, y).bytes());
+        self.y -= 6.0;
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The finished file.
+    pub fn finish(mut self) -> Vec<u8> {
+        let count = self.pages.len();
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            let footer = format!("Page {} of {}", i + 1, count);
+            let x = PAGE_WIDTH - MARGIN - text_width(&footer, 8.0);
+            page.extend(text_op(&footer, x, MARGIN / 2.0, 8.0, false));
+        }
+
+        // Catalog, page tree and the two fonts, then each page and its content
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..count).map(|i| format!("{} 0 R", 5 + 2 * i)).collect::<Vec<_>>().join(" "),
+                count
+            )
+            .into_bytes(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec(),
+        ];
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    6 + 2 * i
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n", i + 1).bytes());
+            out.extend(object);
+            out.extend(b"\nendobj\n");
+        }
+        let xref = out.len();
+        out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            out.extend(format!("{:010} 00000 n \n", offset).bytes());
+        }
+        out.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).bytes());
+        out
+    }
+
+    fn line(&mut self, text: &str, size: f64, bold: bool) {
+        self.make_room(size);
+        let fitted = fit(text, CONTENT_WIDTH, size);
+        self.show(&fitted, MARGIN, self.y - size, size, bold);
+        self.y -= size * LEADING;
+    }
+
+    fn show(&mut self, text: &str, x: f64, y: f64, size: f64, bold: bool) {
+        let page = self.pages.last_mut().expect("a document has a page");
+        page.extend(text_op(text, x, y, size, bold));
+    }
+
+    // Start a new page when a line of `size` doesn't fit above the bottom margin
+    fn make_room(&mut self, size: f64) {
+        if self.y - size * LEADING < MARGIN {
+            self.pages.push(Vec::new());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+}
+
+fn text_op(text: &str, x: f64, y: f64, size: f64, bold: bool) -> Vec<u8> {
+    let mut op = format!("BT /{} {} Tf {:.1} {:.1} Td (", if bold { "F2" } else { "F1" }, size, x, y).into_bytes();
+    for byte in encode(text) {
+        if matches!(byte, b'(' | b')' | b'\\') {
+            op.push(b'\\');
+        }
+        op.push(byte);
+    }
+    op.extend(b") Tj ET\n");
+    op
+}
+
+// WinAnsi bytes of `text`
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{A0}'..='\u{FF}' => c as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            _ => b'?',
+        })
+        .collect()
+}
+
+// Helvetica advance widths of the printable ASCII characters, in thousandths of the font size
+const ASCII_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667,
+    556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556,
+    556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722,
+    500, 500, 500, 334, 260, 334, 584,
+];
+
+// Bold letters run a little wider; measuring with the regular widths keeps columns close enough
+fn text_width(text: &str, size: f64) -> f64 {
+    let thousandths: u32 = encode(text)
+        .into_iter()
+        .map(|byte| match byte {
+            b' '..=b'~' => ASCII_WIDTHS[(byte - b' ') as usize] as u32,
+            0x85 => 1000,
+            _ => 556,
+        })
+        .sum();
+    thousandths as f64 * size / 1000.0
+}
+
+// `text`, cut with "…" where it would run past `width`
+fn fit(text: &str, width: f64, size: f64) -> String {
+    if text_width(text, size) <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.to_string();
+    while !cut.is_empty() && text_width(&format!("{}…", cut), size) > width {
+        cut.pop();
+    }
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_are_well_formed_and_flow_onto_new_pages() {
+        let mut document = Document::new();
+        document.title("Casa (statement)");
+        let columns = [
+            Column { x: 0.0, width: 200.0, align: Align::Left },
+            Column { x: 200.0, width: 80.0, align: Align::Right },
+        ];
+        for i in 0..100 {
+            document.row(&[&format!("Supermercado Día {}", i), "1,234.50"], &columns, false);
+        }
+        assert_eq!(document.page_count(), 2);
+        let bytes = document.finish();
+        assert!(bytes.starts_with(b"%PDF-1.4"));
+        assert!(bytes.ends_with(b"%%EOF\n"));
+
+        // Parentheses are escaped, and "í" is one WinAnsi byte
+        let find = |needle: &[u8]| bytes.windows(needle.len()).position(|w| w == needle);
+        assert!(find(b"(Casa \\(statement\\)) Tj").is_some());
+        assert!(find(b"(Supermercado D\xEDa 0) Tj").is_some());
+        assert!(find(b"(Page 2 of 2) Tj").is_some());
+
+        // Every object sits where the cross-reference table says
+        let text = String::from_utf8_lossy(&bytes);
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let table = String::from_utf8_lossy(&bytes[xref..]);
+        let entries: Vec<&str> = table.lines().skip(3).take_while(|l| l.ends_with(" n ")).collect();
+        assert_eq!(entries.len(), 4 + 2 * 2);
+        for (i, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(bytes[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn long_cells_are_cut_to_their_column() {
+        let cut = fit("A very long description of a dinner with everyone", 100.0, 9.0);
+        assert!(cut.ends_with('…'));
+        assert!(text_width(&cut, 9.0) <= 100.0);
+        assert_eq!(fit("Rent", 100.0, 9.0), "Rent");
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+
+// Requests this server makes to the client (sampling/createMessage, roots/list). They go out
+// through the transport handling the tool call, like log notifications, and the client's
+// answers come back as JSON-RPC responses that the transport hands to resolve().
+//
+// Everything is kept per peer: the stdio client, or on HTTP one session of one account. A
+// peer's initialize doesn't change what another declared, and a response only settles a
+// request made to the peer that sends it.
+
+/// One client connection: the account it authenticated as (None for the default one) and its
+/// HTTP session, if it has one. The stdio client is `Peer::default()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Peer {
+    account: Option<String>,
+    session: Option<String>,
+}
+
+impl Peer {
+    // Only the HTTP transport has accounts and sessions
+    #[allow(dead_code)]
+    pub fn new(account: Option<String>, session: Option<String>) -> Self {
+        Self { account, session }
+    }
+}
+
+// What each peer said about itself in initialize
+static CLIENTS: Mutex<BTreeMap<Peer, Value>> = Mutex::new(BTreeMap::new());
+// The tool policy revision each peer last heard of
+static TOOLS_SEEN: Mutex<BTreeMap<Peer, u64>> = Mutex::new(BTreeMap::new());
+// Requests waiting for an answer, by ID, with the peer they were sent to
+type Waiting = (Peer, oneshot::Sender<Value>);
+static PENDING: Mutex<BTreeMap<String, Waiting>> = Mutex::new(BTreeMap::new());
+
+tokio::task_local! {
+    static CONNECTION: (Peer, UnboundedSender<Value>);
+}
+
+// The peer being served; outside connect() that is the stdio client, the only one there is
+fn current() -> Peer {
+    CONNECTION.try_with(|(peer, _)| peer.clone()).unwrap_or_default()
+}
+
+/// Keep the client capabilities and info from initialize's params.
+pub fn initialize(params: Option<&Value>) {
+    CLIENTS.lock().unwrap().insert(current(), params.cloned().unwrap_or(Value::Null));
+}
+
+/// Drop what the peers of an ended HTTP session declared.
+#[allow(dead_code)]
+pub fn forget(session: &str) {
+    CLIENTS.lock().unwrap().retain(|peer, _| peer.session.as_deref() != Some(session));
+    TOOLS_SEEN.lock().unwrap().retain(|peer, _| peer.session.as_deref() != Some(session));
+}
+
+/// Whether the peer being served has yet to hear that the tool list changed to `revision`.
+/// Each revision is reported once; a peer first seen now lists the tools as they are anyway.
+pub fn tools_changed(revision: u64) -> bool {
+    TOOLS_SEEN.lock().unwrap().insert(current(), revision).is_some_and(|seen| seen < revision)
+}
+
+/// Whether the client declared `capability` ("sampling", "roots").
+pub fn supports(capability: &str) -> bool {
+    CLIENTS.lock().unwrap().get(&current()).is_some_and(|client| client["capabilities"].get(capability).is_some())
+}
+
+/// The user's language, if the client gave one as `locale` in its clientInfo (not every client does).
+pub fn locale() -> Option<String> {
+    CLIENTS.lock().unwrap().get(&current())?["clientInfo"]["locale"].as_str().map(str::to_string)
+}
+
+/// Run `future` on behalf of `peer`, sending the requests it makes to the client to `sink`.
+pub async fn connect<F: Future>(peer: Peer, sink: UnboundedSender<Value>, future: F) -> F::Output {
+    CONNECTION.scope((peer, sink), future).await
+}
+
+/// Hand a response from `peer` to the request waiting for it; false if it answers none of the
+/// requests made to that peer.
+pub fn resolve(peer: &Peer, message: &Value) -> bool {
+    if message.get("method").is_some() {
+        return false;
+    }
+    let Some(id) = message.get("id").and_then(|id| id.as_str()) else {
+        return false;
+    };
+    let waiting = {
+        let mut pending = PENDING.lock().unwrap();
+        match pending.get(id) {
+            Some((asked, _)) if asked == peer => pending.remove(id),
+            _ => None,
+        }
+    };
+    match waiting {
+        Some((_, waiting)) => {
+            let _ = waiting.send(message.clone());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Send a request to the client and wait up to `timeout` for its result.
+pub async fn request(method: &str, params: Value, timeout: Duration) -> Result<Value> {
+    let Ok((peer, sink)) = CONNECTION.try_with(|(peer, sink)| (peer.clone(), sink.clone())) else {
+        bail!("{} needs the stdio transport, or an HTTP request that accepts text/event-stream", method);
+    };
+
+    // Unguessable, so one client can't answer for another even if it could reach our requests
+    let id = format!("server-{:032x}", rand::random::<u128>());
+    let (answer, answered) = oneshot::channel();
+    PENDING.lock().unwrap().insert(id.clone(), (peer, answer));
+    if sink.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).is_err() {
+        PENDING.lock().unwrap().remove(&id);
+        bail!("The client disconnected before {} was sent", method);
+    }
+    let response = tokio::time::timeout(timeout, answered).await;
+    PENDING.lock().unwrap().remove(&id);
+
+    let mut response = match response {
+        Ok(Ok(response)) => response,
+        Ok(Err(_)) => bail!("The client disconnected before answering {}", method),
+        Err(_) => bail!("The client didn't answer {} within {} s", method, timeout.as_secs()),
+    };
+    if let Some(error) = response.get("error") {
+        bail!("The client refused {}: {}", method, error["message"].as_str().unwrap_or("no reason given"));
+    }
+    Ok(response["result"].take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answers_come_back_to_the_request_that_asked() {
+        let (sink, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        let client = tokio::spawn(async move {
+            let request: Value = requests.recv().await.unwrap();
+            assert_eq!(request["method"], "roots/list");
+            assert!(!resolve(&Peer::default(), &json!({ "jsonrpc": "2.0", "id": "server-0", "result": {} })));
+            assert!(resolve(&Peer::default(), &json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "roots": [] } })));
+        });
+
+        let result = connect(Peer::default(), sink, request("roots/list", json!({}), Duration::from_secs(5))).await.unwrap();
+        assert_eq!(result, json!({ "roots": [] }));
+        client.await.unwrap();
+
+        // Outside connect() there is no way to reach the client
+        assert!(request("roots/list", json!({}), Duration::from_secs(5)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn peers_are_kept_apart() {
+        let ana = Peer::new(Some("ana".to_string()), Some("s1".to_string()));
+        let marcos = Peer::new(Some("marcos".to_string()), Some("s2".to_string()));
+        let (sink, _) = tokio::sync::mpsc::unbounded_channel();
+        let declare = |capabilities: Value| async move { initialize(Some(&json!({ "capabilities": capabilities }))) };
+        connect(ana.clone(), sink.clone(), declare(json!({ "roots": {} }))).await;
+        connect(marcos.clone(), sink.clone(), declare(json!({ "sampling": {} }))).await;
+        assert!(connect(ana.clone(), sink.clone(), async { supports("roots") && !supports("sampling") }).await);
+        assert!(connect(marcos.clone(), sink.clone(), async { supports("sampling") && !supports("roots") }).await);
+
+        // Marcos can't answer what was asked of Ana
+        let (sink, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        let asked = tokio::spawn(connect(ana.clone(), sink, request("roots/list", json!({}), Duration::from_secs(5))));
+        let sent: Value = requests.recv().await.unwrap();
+        let answer = json!({ "jsonrpc": "2.0", "id": sent["id"], "result": { "roots": [] } });
+        assert!(!resolve(&marcos, &answer));
+        assert!(resolve(&ana, &answer));
+        assert_eq!(asked.await.unwrap().unwrap(), json!({ "roots": [] }));
+
+        forget("s1");
+        assert!(!connect(ana, tokio::sync::mpsc::unbounded_channel().0, async { supports("roots") }).await);
+    }
+}
@@ -0,0 +1,193 @@
+use serde_json::{Map, Value};
+
+// Field selection for tool results: serialize once, then keep only the requested keys, so a
+// tool can answer with just what a question needs. A field may be a dotted path into nested
+// objects ("category.name"), and the result keeps the nesting. Arrays along a path are
+// projected element by element ("members.first_name"), and braces pick several sub-fields at
+// once ("users{user_id,owed_share}" is "users.user_id" plus "users.owed_share"; braces nest).
+// Unknown fields are skipped.
+
+/// Keep only `fields` of an object, or of every object in an array.
+pub fn project(value: &Value, fields: &[String]) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(|item| project(item, fields)).collect()),
+        Value::Object(_) => {
+            let mut projected = Value::Object(Map::new());
+            for field in fields.iter().flat_map(|f| expand(f.as_str())) {
+                let path: Vec<&str> = field.split('.').collect();
+                copy_path(value, &path, &mut projected);
+            }
+            projected
+        }
+        other => other.clone(),
+    }
+}
+
+/// Project when the caller asked for specific fields; otherwise return the value unchanged.
+pub fn project_if(value: Value, fields: Option<&[String]>) -> Value {
+    match fields {
+        Some(fields) => project(&value, fields),
+        None => value,
+    }
+}
+
+// "users{user_id,user{first_name}}" -> ["users.user_id", "users.user.first_name"]
+fn expand(field: &str) -> Vec<String> {
+    let field = field.trim();
+    let Some(open) = field.find('{') else {
+        return vec![field.to_string()];
+    };
+    let Some(inner) = field[open + 1..].strip_suffix('}') else {
+        // Unbalanced braces: take the text literally, which simply won't match
+        return vec![field.to_string()];
+    };
+    let prefix = field[..open].trim_end_matches('.');
+    split_top_level(inner)
+        .into_iter()
+        .flat_map(expand)
+        .map(|sub| if prefix.is_empty() { sub } else { format!("{}.{}", prefix, sub) })
+        .collect()
+}
+
+// Split on commas that are not inside braces
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts.into_iter().map(str::trim).filter(|p| !p.is_empty()).collect()
+}
+
+fn copy_path(source: &Value, path: &[&str], target: &mut Value) {
+    let (Some((key, rest)), Value::Object(target)) = (path.split_first(), target) else {
+        return;
+    };
+    let Some(found) = source.get(key) else {
+        return;
+    };
+    if rest.is_empty() {
+        target.insert(key.to_string(), found.clone());
+        return;
+    }
+    match found {
+        Value::Object(_) => {
+            let entry = target
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            copy_path(found, rest, entry);
+        }
+        Value::Array(items) => {
+            let entry = target
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Array(items.iter().map(|_| Value::Object(Map::new())).collect()));
+            if let Value::Array(targets) = entry {
+                for (item, target) in items.iter().zip(targets) {
+                    copy_path(item, rest, target);
+                }
+            }
+        }
+        // A scalar (or null) has no sub-fields; keep it so the caller sees it is empty
+        _ => {
+            target.insert(key.to_string(), found.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_only_requested_top_level_fields() {
+        let value = json!({ "id": 1, "name": "Casa", "group_type": "home" });
+        assert_eq!(project(&value, &fields(&["id", "name"])), json!({ "id": 1, "name": "Casa" }));
+    }
+
+    #[test]
+    fn skips_unknown_fields() {
+        let value = json!({ "id": 1 });
+        assert_eq!(project(&value, &fields(&["id", "nope", "nope.deeper"])), json!({ "id": 1 }));
+    }
+
+    #[test]
+    fn follows_dotted_paths_into_objects() {
+        let value = json!({ "category": { "id": 15, "name": "Rent", "icon": "x" } });
+        assert_eq!(
+            project(&value, &fields(&["category.name"])),
+            json!({ "category": { "name": "Rent" } })
+        );
+    }
+
+    #[test]
+    fn maps_paths_over_arrays() {
+        let value = json!({
+            "members": [
+                { "first_name": "Ana", "balance": [{ "amount": "1.00", "currency_code": "USD" }] },
+                { "first_name": "Marcos", "balance": [] },
+            ]
+        });
+        assert_eq!(
+            project(&value, &fields(&["members.first_name", "members.balance.amount"])),
+            json!({
+                "members": [
+                    { "first_name": "Ana", "balance": [{ "amount": "1.00" }] },
+                    { "first_name": "Marcos", "balance": [] },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn projects_every_element_of_a_list() {
+        let value = json!([{ "id": 1, "x": true }, { "id": 2, "x": false }]);
+        assert_eq!(project(&value, &fields(&["id"])), json!([{ "id": 1 }, { "id": 2 }]));
+    }
+
+    #[test]
+    fn whole_and_nested_requests_for_the_same_key_combine() {
+        let value = json!({ "category": { "id": 15, "name": "Rent" } });
+        let expected = json!({ "category": { "id": 15, "name": "Rent" } });
+        assert_eq!(project(&value, &fields(&["category", "category.name"])), expected);
+        assert_eq!(project(&value, &fields(&["category.name", "category"])), expected);
+    }
+
+    #[test]
+    fn braces_select_several_sub_fields() {
+        let value = json!({
+            "users": [
+                { "user_id": 1, "owed_share": "5.00", "paid_share": "10.00", "user": { "first_name": "Ana", "last_name": "G" } },
+            ]
+        });
+        assert_eq!(
+            project(&value, &fields(&["users{user_id, owed_share, user{first_name}}"])),
+            json!({ "users": [{ "user_id": 1, "owed_share": "5.00", "user": { "first_name": "Ana" } }] })
+        );
+    }
+
+    #[test]
+    fn unbalanced_braces_match_nothing() {
+        let value = json!({ "users": [{ "user_id": 1 }] });
+        assert_eq!(project(&value, &fields(&["users{user_id"])), json!({}));
+    }
+
+    #[test]
+    fn leaves_values_alone_without_fields() {
+        let value = json!({ "id": 1, "name": "Casa" });
+        assert_eq!(project_if(value.clone(), None), value);
+    }
+}
@@ -0,0 +1,396 @@
+use chrono::NaiveDate;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::nl_query;
+
+// Deterministic reading of a pasted receipt or forwarded order email: the total, its currency,
+// the date and the merchant, each with a confidence and the line it was read from. Unlike
+// parse_statement it needs no model (MCP sampling), so it gives the same answer every time and
+// works with any client; it only knows a receipt's usual shape, so low-confidence fields are
+// meant to be checked with the user before anything is created.
+
+// Lines naming the amount charged, strongest first; the confidence a match on each gives
+const TOTAL_WORDS: [(&str, f64); 14] = [
+    ("grand total", 0.95),
+    ("amount due", 0.95),
+    ("amount paid", 0.95),
+    ("total paid", 0.95),
+    ("total charged", 0.95),
+    ("order total", 0.95),
+    ("total a pagar", 0.95),
+    ("importe total", 0.95),
+    ("total due", 0.9),
+    ("balance due", 0.9),
+    ("you paid", 0.9),
+    ("charged", 0.8),
+    ("total", 0.85),
+    ("importe", 0.7),
+];
+// Lines with "total" that aren't the amount charged
+const NOT_TOTAL: [&str; 8] = ["subtotal", "sub-total", "sub total", "total tax", "tax total", "total savings", "total items", "total discount"];
+
+// Symbols that name one currency, and the ones shared by several (resolved with the default)
+const SYMBOLS: [(&str, &str); 6] = [("€", "EUR"), ("£", "GBP"), ("₹", "INR"), ("R$", "BRL"), ("US$", "USD"), ("AR$", "ARS")];
+const DOLLAR_CURRENCIES: [&str; 9] = ["USD", "ARS", "CAD", "AUD", "MXN", "NZD", "CLP", "COP", "UYU"];
+const YEN_CURRENCIES: [&str; 2] = ["JPY", "CNY"];
+// ISO codes trusted when they appear on their own in the text
+const CODES: [&str; 20] = [
+    "USD", "EUR", "GBP", "ARS", "BRL", "CAD", "AUD", "MXN", "CLP", "COP", "UYU", "JPY", "CNY", "INR", "CHF", "SEK", "NOK",
+    "DKK", "NZD", "PEN",
+];
+
+// Words in a sender's name that aren't part of the merchant's
+const SENDER_NOISE: [&str; 8] = ["receipts", "receipt", "no-reply", "noreply", "orders", "billing", "notifications", "team"];
+// Email headers and boilerplate that are never the merchant
+const NOT_MERCHANT: [&str; 12] = [
+    "to:", "date:", "subject:", "cc:", "sent:", "fecha:", "asunto:", "para:", "receipt", "invoice", "factura", "thank",
+];
+
+/// One value read from the text, with how sure the extractor is of it and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field<T> {
+    pub value: T,
+    pub confidence: f64,
+    pub line: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Receipt {
+    pub total: Option<Field<f64>>,
+    pub currency: Option<Field<String>>,
+    pub date: Option<Field<NaiveDate>>,
+    pub merchant: Option<Field<String>>,
+}
+
+impl Receipt {
+    /// The fields as JSON: the values, plus per-field confidence and evidence.
+    pub fn to_json(&self) -> Value {
+        fn evidence<T>(field: &Option<Field<T>>) -> (Value, Value) {
+            match field {
+                Some(field) => (json!((field.confidence * 100.0).round() / 100.0), json!(field.line)),
+                None => (json!(0.0), Value::Null),
+            }
+        }
+        let fields = [
+            ("cost", evidence(&self.total)),
+            ("currency_code", evidence(&self.currency)),
+            ("date", evidence(&self.date)),
+            ("description", evidence(&self.merchant)),
+        ];
+        json!({
+            "cost": self.total.as_ref().map(|f| format!("{:.2}", f.value)),
+            "currency_code": self.currency.as_ref().map(|f| &f.value),
+            "date": self.date.as_ref().map(|f| f.value.to_string()),
+            "description": self.merchant.as_ref().map(|f| &f.value),
+            "confidence": fields.iter().map(|(name, (confidence, _))| (name.to_string(), confidence.clone())).collect::<serde_json::Map<_, _>>(),
+            "evidence": fields.iter().map(|(name, (_, line))| (name.to_string(), line.clone())).collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+/// Read a receipt out of `text`. `default_currency` settles "$" and stands in when no currency
+/// is shown; dates after `today` are ignored.
+pub fn extract(text: &str, default_currency: &str, today: NaiveDate) -> Receipt {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let (total, marker) = match find_total(&lines) {
+        Some((field, marker)) => (Some(field), marker),
+        None => (None, None),
+    };
+    Receipt {
+        currency: find_currency(&lines, marker.as_deref(), total.as_ref(), default_currency),
+        total,
+        date: find_date(&lines, today),
+        merchant: find_merchant(&lines),
+    }
+}
+
+/// The currency a short text such as an expense description names on its own ("€40 taxi",
+/// "ARS 5000"), with the rule that found it and the text it was read from. A code or symbol next
+/// to an amount comes first. "$" and "¥" stand for several currencies, so they name none here.
+pub fn currency_in_description(description: &str) -> Option<(String, &'static str, String)> {
+    let named = |marker: &str| {
+        if let Some((_, code)) = SYMBOLS.iter().find(|(symbol, _)| *symbol == marker) {
+            return Some((code.to_string(), "symbol"));
+        }
+        CODES.contains(&marker).then(|| (marker.to_string(), "iso_code"))
+    };
+    let beside_amount = amount_pattern().captures_iter(description).find_map(|c| {
+        let whole = c.get(0)?.as_str().trim().to_string();
+        [c.name("pre"), c.name("post")].into_iter().flatten().find_map(|m| named(m.as_str())).map(|(code, rule)| (code, rule, whole))
+    });
+    beside_amount.or_else(|| {
+        let code = Regex::new(r"\b[A-Z]{3}\b").unwrap();
+        let found = code
+            .find_iter(description)
+            .find_map(|m| named(m.as_str()).map(|(code, rule)| (code, rule, m.as_str().to_string())));
+        found.or_else(|| SYMBOLS.iter().find(|(symbol, _)| description.contains(symbol)).map(|(symbol, code)| (code.to_string(), "symbol", symbol.to_string())))
+    })
+}
+
+fn amount_pattern() -> Regex {
+    Regex::new(
+        r"(?P<pre>US\$|AR\$|R\$|[$€£¥₹]|\b[A-Z]{3}\b)?\s?(?P<num>\d{1,3}(?:[.,\s]\d{3})+(?:[.,]\d{1,2})?|\d+(?:[.,]\d{1,2})?)\b\s?(?P<post>US\$|[$€£¥₹]|\b[A-Z]{3}\b)?",
+    )
+    .unwrap()
+}
+
+// An amount as written on a receipt: "1.234,56" and "1,234.56" are both 1234.56, "12,50" is 12.5
+fn parse_number(number: &str) -> Option<f64> {
+    let number: String = number.chars().filter(|c| !c.is_whitespace()).collect();
+    let decimal = number
+        .rfind(['.', ','])
+        .filter(|i| number.len() - i - 1 <= 2);
+    let (whole, fraction) = match decimal {
+        Some(i) => (&number[..i], &number[i + 1..]),
+        None => (number.as_str(), ""),
+    };
+    let whole: String = whole.chars().filter(char::is_ascii_digit).collect();
+    format!("{}.{}", if whole.is_empty() { "0" } else { &whole }, if fraction.is_empty() { "0" } else { fraction })
+        .parse()
+        .ok()
+}
+
+// The amount charged and the currency marker written next to it
+fn find_total(lines: &[&str]) -> Option<(Field<f64>, Option<String>)> {
+    let pattern = amount_pattern();
+    let last_amount = |line: &str| {
+        pattern.captures_iter(line).filter_map(|c| {
+            let value = parse_number(c.name("num")?.as_str())?;
+            let marker = c.name("pre").or(c.name("post")).map(|m| m.as_str().to_string());
+            Some((value, marker))
+        })
+        .filter(|(value, _)| *value > 0.0)
+        .last()
+    };
+
+    let mut best: Option<(Field<f64>, Option<String>)> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let lower = line.to_lowercase();
+        if NOT_TOTAL.iter().any(|word| lower.contains(word)) {
+            continue;
+        }
+        let Some((_, confidence)) = TOTAL_WORDS.iter().find(|(word, _)| nl_query::contains_word(&lower, word)) else {
+            continue;
+        };
+        // The amount is on the line, or alone on the next one
+        let found = last_amount(line).or_else(|| lines.get(i + 1).and_then(|next| last_amount(next)));
+        let Some((value, marker)) = found else { continue };
+        // The last of equally strong lines wins: receipts list the total after what it adds up
+        if best.as_ref().is_none_or(|(b, _)| *confidence >= b.confidence) {
+            best = Some((Field { value, confidence: *confidence, line: line.to_string() }, marker));
+        }
+    }
+    if best.is_some() {
+        return best;
+    }
+    // Nothing is labelled: the largest amount with a currency next to it, as a guess
+    lines
+        .iter()
+        .flat_map(|line| {
+            pattern.captures_iter(line).filter_map(move |c| {
+                let marker = c.name("pre").or(c.name("post"))?.as_str().to_string();
+                let value = parse_number(c.name("num")?.as_str())?;
+                Some((Field { value, confidence: 0.4, line: line.to_string() }, Some(marker)))
+            })
+        })
+        .filter(|(field, marker)| field.value > 0.0 && marker.as_deref().is_some_and(is_currency_marker))
+        .max_by(|a, b| a.0.value.total_cmp(&b.0.value))
+}
+
+fn is_currency_marker(marker: &str) -> bool {
+    currency_of(marker, "USD").is_some()
+}
+
+// The currency a symbol or code stands for, and how sure that is
+fn currency_of(marker: &str, default: &str) -> Option<(String, f64)> {
+    if let Some((_, code)) = SYMBOLS.iter().find(|(symbol, _)| *symbol == marker) {
+        return Some((code.to_string(), 0.9));
+    }
+    match marker {
+        "$" if DOLLAR_CURRENCIES.contains(&default) => Some((default.to_string(), 0.7)),
+        "$" => Some(("USD".to_string(), 0.5)),
+        "¥" if YEN_CURRENCIES.contains(&default) => Some((default.to_string(), 0.7)),
+        "¥" => Some(("JPY".to_string(), 0.5)),
+        code if CODES.contains(&code) => Some((code.to_string(), 0.95)),
+        _ => None,
+    }
+}
+
+fn find_currency(lines: &[&str], marker: Option<&str>, total: Option<&Field<f64>>, default: &str) -> Option<Field<String>> {
+    if let (Some(marker), Some(total)) = (marker, total) {
+        if let Some((value, confidence)) = currency_of(marker, default) {
+            return Some(Field { value, confidence, line: total.line.clone() });
+        }
+    }
+    // A code or an unambiguous symbol anywhere in the text
+    let code = Regex::new(r"\b[A-Z]{3}\b").unwrap();
+    for line in lines {
+        let found = code
+            .find_iter(line)
+            .find_map(|m| CODES.contains(&m.as_str()).then(|| m.as_str().to_string()))
+            .or_else(|| SYMBOLS.iter().find(|(symbol, _)| line.contains(symbol)).map(|(_, code)| code.to_string()));
+        if let Some(value) = found {
+            return Some(Field { value, confidence: 0.7, line: line.to_string() });
+        }
+    }
+    Some(Field { value: default.to_string(), confidence: 0.3, line: String::new() })
+}
+
+// The first date in the text that isn't in the future; lines labelled as the date come first
+fn find_date(lines: &[&str], today: NaiveDate) -> Option<Field<NaiveDate>> {
+    let iso = Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").unwrap();
+    let numeric = Regex::new(r"\b(\d{1,2})[/.-](\d{1,2})[/.-](\d{4}|\d{2})\b").unwrap();
+    let month_first = Regex::new(r"(?i)\b([a-z]{3,10})\.?\s+(\d{1,2})(?:st|nd|rd|th)?,?\s+(\d{4})\b").unwrap();
+    let day_first = Regex::new(r"(?i)\b(\d{1,2})(?:\s+de)?\s+([a-z]{3,10})\.?,?(?:\s+de)?\s+(\d{4})\b").unwrap();
+
+    let mut found: Vec<(bool, Field<NaiveDate>)> = Vec::new();
+    for line in lines {
+        let lower = line.to_lowercase();
+        let labelled = ["date", "fecha", "sent", "enviado"].iter().any(|word| lower.contains(word));
+        let mut add = |date: Option<NaiveDate>, confidence: f64| {
+            if let Some(date) = date.filter(|d| *d <= today) {
+                found.push((labelled, Field { value: date, confidence, line: line.to_string() }));
+            }
+        };
+        for c in iso.captures_iter(line) {
+            add(ymd(&c[1], &c[2], &c[3]), 0.95);
+        }
+        for c in month_first.captures_iter(&lower) {
+            add(nl_query::month_number(&c[1]).and_then(|m| ymd(&c[3], &m.to_string(), &c[2])), 0.9);
+        }
+        for c in day_first.captures_iter(&lower) {
+            add(nl_query::month_number(&c[2]).and_then(|m| ymd(&c[3], &m.to_string(), &c[1])), 0.9);
+        }
+        for c in numeric.captures_iter(line) {
+            let year = if c[3].len() == 2 { format!("20{}", &c[3]) } else { c[3].to_string() };
+            let (first, second): (u32, u32) = (c[1].parse().unwrap_or(0), c[2].parse().unwrap_or(0));
+            // Day first unless that can't be a date; both readings possible means a guess
+            let date = ymd(&year, &c[2], &c[1]).or_else(|| ymd(&year, &c[1], &c[2]));
+            add(date, if first <= 12 && second <= 12 && first != second { 0.6 } else { 0.85 });
+        }
+    }
+    let labelled = found.iter().position(|(labelled, _)| *labelled).unwrap_or(0);
+    (!found.is_empty()).then(|| found.swap_remove(labelled).1)
+}
+
+fn ymd(year: &str, month: &str, day: &str) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+}
+
+// Who was paid: named by the email ("Your receipt from Uber"), its sender, or the receipt's
+// first line of text
+fn find_merchant(lines: &[&str]) -> Option<Field<String>> {
+    let named = Regex::new(r"(?i)\b(?:receipt|order|purchase|payment|invoice|compra|pedido|recibo)\s+(?:from|at|to|with|en|de)\s+([^\n.,!|]+)").unwrap();
+    for line in lines {
+        if let Some(c) = named.captures(line) {
+            if let Some(name) = clean_merchant(&c[1]) {
+                return Some(Field { value: name, confidence: 0.8, line: line.to_string() });
+            }
+        }
+    }
+    let sender = Regex::new(r#"(?i)^(?:from|de):\s*"?([^"<]*?)"?\s*(?:<([^>]+)>)?$"#).unwrap();
+    for line in lines {
+        let Some(c) = sender.captures(line) else { continue };
+        // "Uber Receipts <noreply@uber.com>" is Uber; a bare address goes by its domain
+        let display: Vec<&str> = c[1]
+            .split_whitespace()
+            .filter(|word| !SENDER_NOISE.contains(&word.to_lowercase().as_str()) && !word.contains('@'))
+            .collect();
+        let address = c.get(2).map(|m| m.as_str()).or_else(|| c[1].contains('@').then_some(&c[1]));
+        let name = match display.is_empty() {
+            false => display.join(" "),
+            true => match address.and_then(|a| a.split('@').nth(1)).and_then(|domain| domain.split('.').next()) {
+                Some(domain) => domain.chars().take(1).flat_map(char::to_uppercase).chain(domain.chars().skip(1)).collect(),
+                None => continue,
+            },
+        };
+        if let Some(name) = clean_merchant(&name) {
+            return Some(Field { value: name, confidence: 0.7, line: line.to_string() });
+        }
+    }
+    lines
+        .iter()
+        .find(|line| {
+            let lower = line.to_lowercase();
+            line.chars().filter(|c| c.is_alphabetic()).count() >= 3
+                && !line.starts_with(|c: char| c.is_ascii_digit())
+                && !NOT_MERCHANT.iter().any(|word| lower.starts_with(word) || lower.contains(&format!(" {}", word)))
+                && !lower.starts_with("from:")
+                && !TOTAL_WORDS.iter().any(|(word, _)| nl_query::contains_word(&lower, word))
+        })
+        .and_then(|line| Some(Field { value: clean_merchant(line)?, confidence: 0.5, line: line.to_string() }))
+}
+
+fn clean_merchant(name: &str) -> Option<String> {
+    let name = name.trim().trim_matches(|c: char| !c.is_alphanumeric());
+    let name: String = name.chars().take(60).collect();
+    (name.chars().filter(|c| c.is_alphabetic()).count() >= 2).then(|| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 30).unwrap()
+    }
+
+    #[test]
+    fn descriptions_name_a_currency_only_when_it_is_unambiguous() {
+        let found = currency_in_description;
+        assert_eq!(found("€40 taxi"), Some(("EUR".to_string(), "symbol", "€40".to_string())));
+        assert_eq!(found("Hotel ARS 5000"), Some(("ARS".to_string(), "iso_code", "ARS 5000".to_string())));
+        assert_eq!(found("Dinner 30 GBP with Ana"), Some(("GBP".to_string(), "iso_code", "30 GBP".to_string())));
+        assert_eq!(found("Souvenirs (paid in BRL)").map(|f| f.1), Some("iso_code"));
+        assert_eq!(found("Lunch R$ 45").map(|f| f.0), Some("BRL".to_string()));
+        // "$" could be any dollar or peso, and "TAXI" isn't a currency
+        assert_eq!(found("$40 TAXI"), None);
+        assert_eq!(found("Groceries"), None);
+    }
+
+    #[test]
+    fn reads_a_store_receipt() {
+        let text = "SUPERMERCADO DIA\nAv. Corrientes 1234\nFecha: 14/06/2025 18:32\nLeche 1.250,00\nPan 980,50\nSUBTOTAL 2.230,50\nTOTAL $ 2.230,50\nGracias por su compra";
+        let receipt = extract(text, "ARS", today());
+        let total = receipt.total.unwrap();
+        assert_eq!(total.value, 2230.5);
+        assert_eq!(total.line, "TOTAL $ 2.230,50");
+        assert_eq!(receipt.currency.unwrap().value, "ARS");
+        let date = receipt.date.unwrap();
+        assert_eq!(date.value, NaiveDate::from_ymd_opt(2025, 6, 14).unwrap());
+        assert!(date.confidence > 0.8);
+        assert_eq!(receipt.merchant.unwrap().value, "SUPERMERCADO DIA");
+    }
+
+    #[test]
+    fn reads_a_forwarded_order_email() {
+        let text = "---------- Forwarded message ---------\nFrom: Uber Receipts <noreply@uber.com>\nDate: Sat, May 3, 2025 at 11:02 PM\nSubject: Your Saturday evening trip with Uber\n\nTrip fare  €18.40\nBooking fee €2.10\nTotal €20.50\n";
+        let receipt = extract(text, "USD", today());
+        assert_eq!(receipt.total.as_ref().unwrap().value, 20.5);
+        let currency = receipt.currency.unwrap();
+        assert_eq!(currency.value, "EUR");
+        assert!(currency.confidence >= 0.9);
+        assert_eq!(receipt.date.unwrap().value, NaiveDate::from_ymd_opt(2025, 5, 3).unwrap());
+        assert_eq!(receipt.merchant.unwrap().value, "Uber");
+
+        let json = extract(text, "USD", today()).to_json();
+        assert_eq!(json["cost"], "20.50");
+        assert_eq!(json["evidence"]["cost"], "Total €20.50");
+    }
+
+    #[test]
+    fn unlabelled_or_missing_fields_get_low_confidence() {
+        let receipt = extract("Coffee corner\n2 x flat white 7.00 USD\nthanks!", "EUR", today());
+        assert_eq!(receipt.total.as_ref().unwrap().value, 7.0);
+        assert!(receipt.total.unwrap().confidence < 0.5);
+        assert!(receipt.date.is_none());
+        assert_eq!(receipt.currency.unwrap().value, "USD");
+
+        // A date in the future is not the purchase date
+        assert!(extract("Total 10.00\nValid until 2030-01-01", "EUR", today()).date.is_none());
+        assert_eq!(parse_number("1,234.56"), Some(1234.56));
+        assert_eq!(parse_number("12,5"), Some(12.5));
+        assert_eq!(parse_number("1 234"), Some(1234.0));
+    }
+}
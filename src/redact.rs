@@ -0,0 +1,327 @@
+use anyhow::Result;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::config::Config;
+
+// Redaction of personal data in what tools return, for people who want the assistant to work
+// out amounts without sending their contacts' details to the model provider. With PII_REDACTION
+// set, every result and resource has its email addresses replaced by pseudonyms (or removed,
+// with PII_REDACTION_EMAILS=strip), avatar pictures dropped, and people's names shortened:
+// "first_names" keeps the first name and the last name's initial, "initials" keeps only
+// initials. Names are shortened in name fields and wherever a known person's full name appears
+// in text. Amounts, dates, IDs and group names are left alone. Pseudonyms are salted per
+// process, so they tell people apart within a session but can't be looked up.
+// "amounts_only" goes further for spending analytics without revealing what was bought: on top
+// of initials, each expense's description becomes its category name and its notes, receipt,
+// comments and merchant names are hidden. Amounts, dates and categories are kept.
+
+const PSEUDONYM_DOMAIN: &str = "@redacted.invalid";
+
+// What was bought, hidden by amounts_only besides the description itself
+const PURCHASE_DETAILS: [&str; 7] =
+    ["details", "notes", "receipt", "content", "merchant", "descriptions", "evidence"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Names {
+    FirstNames,
+    Initials,
+}
+
+pub struct Redactor {
+    names: Names,
+    // amounts_only: descriptions become categories and purchase details are dropped
+    hide_purchases: bool,
+    strip_emails: bool,
+    salt: u64,
+    email: Regex,
+}
+
+impl Redactor {
+    /// The redactor PII_REDACTION asks for; None when redaction is off.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let (names, hide_purchases) = match config.pii_redaction.as_str() {
+            "off" | "" => return Ok(None),
+            "first_names" => (Names::FirstNames, false),
+            "initials" => (Names::Initials, false),
+            "amounts_only" => (Names::Initials, true),
+            other => anyhow::bail!(
+                "Unknown PII_REDACTION: {} (use off, first_names, initials or amounts_only)",
+                other
+            ),
+        };
+        let strip_emails = match config.pii_redaction_emails.as_str() {
+            "hash" => false,
+            "strip" => true,
+            other => anyhow::bail!("Unknown PII_REDACTION_EMAILS: {} (use hash or strip)", other),
+        };
+        Ok(Some(Self {
+            names,
+            hide_purchases,
+            strip_emails,
+            salt: rand::random(),
+            email: Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+        }))
+    }
+
+    /// `value` with its personal data redacted. `people` are (first name, last name) pairs of
+    /// everyone whose full name should be shortened in text, besides those found in `value`.
+    pub fn redact(&self, mut value: Value, people: &[(String, Option<String>)]) -> Value {
+        let mut known = people.to_vec();
+        collect_people(&value, &mut known);
+        let mut replacements = HashMap::new();
+        if self.hide_purchases {
+            // Descriptions quoted elsewhere in the result (a summary, a warning) go too, except
+            // those a category name contains, which would otherwise be replaced inside it
+            collect_descriptions(&value, &mut replacements);
+            let categories: Vec<String> = replacements.values().cloned().collect();
+            replacements.retain(|description, _| !categories.iter().any(|c| c.contains(description.as_str())));
+        }
+        let names = self.name_replacements(&known, replacements);
+        self.walk(&mut value, &names);
+        value
+    }
+
+    // Each known full name (and, with initials, each first name alone) and what replaces it,
+    // matched as whole words, longest first, together with `replacements` already gathered
+    fn name_replacements(
+        &self,
+        people: &[(String, Option<String>)],
+        mut replacements: HashMap<String, String>,
+    ) -> Option<(Regex, HashMap<String, String>)> {
+        for (first, last) in people {
+            let first = first.trim();
+            let last = last.as_deref().map(str::trim).filter(|l| !l.is_empty());
+            if first.chars().count() < 2 {
+                continue;
+            }
+            if let Some(last) = last {
+                replacements.insert(format!("{} {}", first, last), self.short_name(first, Some(last)));
+            }
+            if self.names == Names::Initials {
+                replacements.insert(first.to_string(), initial(first));
+            }
+        }
+        if replacements.is_empty() {
+            return None;
+        }
+        let mut keys: Vec<&String> = replacements.keys().collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.len()));
+        let pattern = keys.iter().map(|k| regex::escape(k)).collect::<Vec<_>>().join("|");
+        let regex = Regex::new(&format!(r"\b(?:{})\b", pattern)).ok()?;
+        Some((regex, replacements))
+    }
+
+    fn short_name(&self, first: &str, last: Option<&str>) -> String {
+        let first = match self.names {
+            Names::FirstNames => first.to_string(),
+            Names::Initials => initial(first),
+        };
+        match last {
+            Some(last) => format!("{} {}", first, initial(last)),
+            None => first,
+        }
+    }
+
+    fn walk(&self, value: &mut Value, names: &Option<(Regex, HashMap<String, String>)>) {
+        match value {
+            Value::Object(object) => {
+                self.redact_person(object);
+                if self.hide_purchases {
+                    hide_purchase(object);
+                }
+                let entries = std::mem::take(object);
+                for (key, mut child) in entries {
+                    self.walk(&mut child, names);
+                    object.insert(self.redact_text(&key, names), child);
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.walk(item, names)),
+            Value::String(text) => *text = self.redact_text(text, names),
+            _ => {}
+        }
+    }
+
+    // The fields of a user, friend or member object
+    fn redact_person(&self, object: &mut Map<String, Value>) {
+        object.remove("picture");
+        if let Some(Value::String(email)) = object.get("email") {
+            if self.strip_emails {
+                object.remove("email");
+            } else {
+                let pseudonym = self.pseudonym(email);
+                object.insert("email".to_string(), Value::String(pseudonym));
+            }
+        }
+        if let Some(Value::String(last)) = object.get_mut("last_name") {
+            *last = initial(last);
+        }
+        if self.names == Names::Initials {
+            if let Some(Value::String(first)) = object.get_mut("first_name") {
+                *first = initial(first);
+            }
+        }
+    }
+
+    fn redact_text(&self, text: &str, names: &Option<(Regex, HashMap<String, String>)>) -> String {
+        let text = self.email.replace_all(text, |found: &regex::Captures| {
+            if found[0].ends_with(PSEUDONYM_DOMAIN) {
+                found[0].to_string()
+            } else if self.strip_emails {
+                "[email]".to_string()
+            } else {
+                self.pseudonym(&found[0])
+            }
+        });
+        match names {
+            Some((regex, replacements)) => regex
+                .replace_all(&text, |found: &regex::Captures| replacements[&found[0]].clone())
+                .into_owned(),
+            None => text.into_owned(),
+        }
+    }
+
+    fn pseudonym(&self, email: &str) -> String {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.salt.hash(&mut hasher);
+        email.trim().to_lowercase().hash(&mut hasher);
+        format!("user-{:08x}{}", hasher.finish() as u32, PSEUDONYM_DOMAIN)
+    }
+}
+
+// An expense's description replaced by its category, and what else tells what was bought removed
+fn hide_purchase(object: &mut Map<String, Value>) {
+    if object.get("description").is_some_and(Value::is_string) {
+        let category = category_of(object).unwrap_or("Expense").to_string();
+        object.insert("description".to_string(), Value::String(category));
+    }
+    for key in PURCHASE_DETAILS {
+        object.remove(key);
+    }
+}
+
+// The category name an expense carries, whichever shape the result gives it
+fn category_of(object: &Map<String, Value>) -> Option<&str> {
+    object
+        .get("category")
+        .and_then(|c| c.get("name").or(Some(c)))
+        .and_then(Value::as_str)
+        .or_else(|| object.get("category_name").and_then(Value::as_str))
+}
+
+// Each expense description in `value` and the category it becomes
+fn collect_descriptions(value: &Value, replacements: &mut HashMap<String, String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(description) = object.get("description").and_then(Value::as_str) {
+                let description = description.trim();
+                // Very short descriptions would match inside unrelated words
+                if description.chars().count() >= 3 {
+                    let category = category_of(object).unwrap_or("Expense").to_string();
+                    replacements.insert(description.to_string(), category);
+                }
+            }
+            object.values().for_each(|child| collect_descriptions(child, replacements));
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_descriptions(item, replacements)),
+        _ => {}
+    }
+}
+
+// People whose names appear as first_name/last_name fields anywhere in `value`
+fn collect_people(value: &Value, people: &mut Vec<(String, Option<String>)>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(first) = object.get("first_name").and_then(Value::as_str) {
+                let last = object.get("last_name").and_then(Value::as_str).map(str::to_string);
+                people.push((first.to_string(), last));
+            }
+            object.values().for_each(|child| collect_people(child, people));
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_people(item, people)),
+        _ => {}
+    }
+}
+
+fn initial(name: &str) -> String {
+    match name.trim().chars().next() {
+        Some(c) => format!("{}.", c.to_uppercase()),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn redactor(names: &str, emails: &str) -> Redactor {
+        let mut config = Config::from_env();
+        config.pii_redaction = names.to_string();
+        config.pii_redaction_emails = emails.to_string();
+        Redactor::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn shortens_names_and_hides_emails() {
+        let result = json!({
+            "members": [{
+                "id": 7, "first_name": "Ana", "last_name": "Gómez", "email": "ana@example.com",
+                "picture": { "medium": "https://example.com/ana.png" }, "balance": [{ "amount": "12.50" }],
+            }],
+            "note": "Ana Gómez paid; ask ana@example.com or Bruno Díaz",
+            "by_person": { "Ana Gómez": "12.50" },
+            "group": "Casa Anabel",
+        });
+        let people = vec![("Bruno".to_string(), Some("Díaz".to_string()))];
+
+        let first_names = redactor("first_names", "hash").redact(result.clone(), &people);
+        let member = &first_names["members"][0];
+        assert_eq!(member["first_name"], "Ana");
+        assert_eq!(member["last_name"], "G.");
+        assert!(member.get("picture").is_none());
+        let pseudonym = member["email"].as_str().unwrap();
+        assert!(pseudonym.ends_with("@redacted.invalid"));
+        assert_eq!(member["balance"][0]["amount"], "12.50");
+        assert_eq!(first_names["note"], format!("Ana G. paid; ask {} or Bruno D.", pseudonym));
+        assert_eq!(first_names["by_person"]["Ana G."], "12.50");
+        assert_eq!(first_names["group"], "Casa Anabel");
+
+        let initials = redactor("initials", "strip").redact(result, &people);
+        assert_eq!(initials["members"][0]["first_name"], "A.");
+        assert!(initials["members"][0].get("email").is_none());
+        assert_eq!(initials["note"], "A. G. paid; ask [email] or B. D.");
+        assert_eq!(initials["group"], "Casa Anabel");
+
+        let expenses = json!({
+            "items": [
+                { "description": "Farmacia Vida", "details": "antidepressants", "cost": "42.00",
+                  "date": "2025-03-01", "category": { "id": 43, "name": "Medical expenses" },
+                  "receipt": { "original": "https://example.com/r.jpg" } },
+                { "description": "Uber", "cost": "9.10", "category_name": "Taxi" },
+            ],
+            "merchants": [{ "merchant": "Farmacia Vida", "descriptions": ["Farmacia Vida"], "total_cost": "42.00" }],
+            "note": "Farmacia Vida was entered twice",
+            "internet": { "description": "Internet", "category": { "name": "TV/Phone/Internet" } },
+        });
+        let amounts = redactor("amounts_only", "hash").redact(expenses, &[]);
+        let first = &amounts["items"][0];
+        assert_eq!(first["description"], "Medical expenses");
+        assert_eq!(first["cost"], "42.00");
+        assert_eq!(first["date"], "2025-03-01");
+        assert_eq!(first["category"]["name"], "Medical expenses");
+        assert!(first.get("details").is_none() && first.get("receipt").is_none());
+        assert_eq!(amounts["items"][1]["description"], "Taxi");
+        assert_eq!(amounts["merchants"][0], json!({ "total_cost": "42.00" }));
+        assert_eq!(amounts["note"], "Medical expenses was entered twice");
+        assert_eq!(amounts["internet"]["category"]["name"], "TV/Phone/Internet");
+
+        let mut config = Config::from_env();
+        config.pii_redaction = "off".to_string();
+        assert!(Redactor::from_config(&config).unwrap().is_none());
+        config.pii_redaction = "everything".to_string();
+        assert!(Redactor::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+// Text renderings of tool results for chat clients that show raw JSON poorly. Rows are the
+// objects of a list; nested objects become dotted columns ("category.name"), lists of scalars
+// are joined with "; " and lists of objects are kept as compact JSON. An object result renders
+// its scalar fields as "key: value" lines followed by one table per list of objects in it.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Table,
+    Csv,
+}
+
+// Longest cell shown in a table before it is cut; CSV cells are never cut
+const MAX_CELL: usize = 48;
+
+/// Render a tool result in the requested format. JSON leaves it untouched.
+pub fn render(value: Value, format: OutputFormat) -> Value {
+    match format {
+        OutputFormat::Json => value,
+        OutputFormat::Table => Value::String(table(&value)),
+        OutputFormat::Csv => Value::String(csv(&value)),
+    }
+}
+
+/// Text for an MCP content block: rendered output as is, anything else as JSON.
+pub fn text_content(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// MCP content blocks for a tool result. An `image` field ({data, mimeType}) becomes an image
+/// block of its own, followed by the rest of the result as text.
+pub fn content(result: &Value) -> Vec<Value> {
+    let image = result.get("image").filter(|image| image["data"].is_string() && image["mimeType"].is_string());
+    let Some(image) = image else {
+        return vec![json!({ "type": "text", "text": text_content(result) })];
+    };
+    let mut rest = result.clone();
+    if let Some(obj) = rest.as_object_mut() {
+        obj.remove("image");
+    }
+    vec![
+        json!({ "type": "image", "data": image["data"], "mimeType": image["mimeType"] }),
+        json!({ "type": "text", "text": text_content(&rest) }),
+    ]
+}
+
+/// CSV with the columns in the given order, for formats that other programs import.
+pub fn csv_with_columns(columns: &[&str], rows: &[Vec<String>]) -> String {
+    let header = columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",");
+    std::iter::once(header)
+        .chain(rows.iter().map(|row| row.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",")))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn table(value: &Value) -> String {
+    match value {
+        Value::Array(items) => text_table(items),
+        Value::Object(obj) => {
+            let (scalars, lists) = sections(obj);
+            let mut out: Vec<String> = scalars
+                .iter()
+                .filter(|(_, v)| !v.is_empty())
+                .map(|(k, v)| format!("{}: {}", k, v))
+                .collect();
+            for (key, items) in lists {
+                if !out.is_empty() {
+                    out.push(String::new());
+                }
+                out.push(format!("{} ({})", key, items.len()));
+                out.push(text_table(items));
+            }
+            out.join("\n")
+        }
+        other => cell(other),
+    }
+}
+
+fn csv(value: &Value) -> String {
+    match value {
+        Value::Array(items) => csv_table(items),
+        Value::Object(obj) => {
+            let (_, lists) = sections(obj);
+            match lists.as_slice() {
+                // Nothing list-like: the object itself is the only row
+                [] => csv_table(std::slice::from_ref(value)),
+                [(_, items)] => csv_table(items),
+                _ => lists
+                    .iter()
+                    .map(|(key, items)| format!("# {}\n{}", key, csv_table(items)))
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+            }
+        }
+        other => csv_field(&cell(other)),
+    }
+}
+
+// Flattened (column, text) pairs of one object
+type Cells = Vec<(String, String)>;
+
+// Split an object into its flattened scalar fields and its lists of objects
+fn sections(obj: &Map<String, Value>) -> (Cells, Vec<(&String, &Vec<Value>)>) {
+    let mut scalars = Vec::new();
+    let mut lists = Vec::new();
+    for (key, value) in obj {
+        match value {
+            Value::Array(items) if items.iter().any(Value::is_object) => lists.push((key, items)),
+            _ => flatten(key, value, &mut scalars),
+        }
+    }
+    (scalars, lists)
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Cells) {
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (key, inner) in obj {
+                flatten(&format!("{}.{}", prefix, key), inner, out);
+            }
+        }
+        other => out.push((prefix.to_string(), cell(other))),
+    }
+}
+
+// Columns in order of first appearance, so rows with optional fields still line up
+fn rows(items: &[Value]) -> (Vec<String>, Vec<Vec<String>>) {
+    let flat: Vec<Cells> = items
+        .iter()
+        .map(|item| {
+            let mut fields = Vec::new();
+            match item {
+                Value::Object(obj) => {
+                    for (key, value) in obj {
+                        flatten(key, value, &mut fields);
+                    }
+                }
+                other => fields.push(("value".to_string(), cell(other))),
+            }
+            fields
+        })
+        .collect();
+    let mut columns: Vec<String> = Vec::new();
+    for (key, _) in flat.iter().flatten() {
+        if !columns.contains(key) {
+            columns.push(key.clone());
+        }
+    }
+    let rows = flat
+        .iter()
+        .map(|fields| {
+            columns
+                .iter()
+                .map(|c| fields.iter().find(|(k, _)| k == c).map(|(_, v)| v.clone()).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    (columns, rows)
+}
+
+fn text_table(items: &[Value]) -> String {
+    if items.is_empty() {
+        return "(none)".to_string();
+    }
+    let (columns, rows) = rows(items);
+    let rows: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|row| row.into_iter().map(|c| truncate(&c)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| rows.iter().map(|r| r[i].chars().count()).chain([c.chars().count()]).max().unwrap_or(0))
+        .collect();
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<width$}", c, width = *w))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let mut out = vec![
+        line(&columns),
+        widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"),
+    ];
+    out.extend(rows.iter().map(|r| line(r)));
+    out.join("\n")
+}
+
+fn csv_table(items: &[Value]) -> String {
+    let (columns, rows) = rows(items);
+    std::iter::once(columns)
+        .chain(rows)
+        .map(|row| row.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.iter().all(|v| !v.is_object() && !v.is_array()) => {
+            items.iter().map(cell).collect::<Vec<_>>().join("; ")
+        }
+        other => other.to_string(),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    // Line breaks would split a row
+    let text = text.replace('\n', " ");
+    if text.chars().count() <= MAX_CELL {
+        return text;
+    }
+    let cut: String = text.chars().take(MAX_CELL - 1).collect();
+    format!("{}…", cut)
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_is_left_alone() {
+        let value = json!([{ "id": 1 }]);
+        assert_eq!(render(value.clone(), OutputFormat::Json), value);
+    }
+
+    #[test]
+    fn table_aligns_columns_and_flattens_nested_objects() {
+        let value = json!([
+            { "id": 1, "category": { "name": "Rent" }, "cost": "1200.00" },
+            { "id": 22, "category": { "name": "Groceries" }, "cost": "8.50" },
+        ]);
+        assert_eq!(
+            render(value, OutputFormat::Table),
+            json!(concat!(
+                "category.name | cost    | id\n",
+                "--------------+---------+---\n",
+                "Rent          | 1200.00 | 1\n",
+                "Groceries     | 8.50    | 22",
+            ))
+        );
+    }
+
+    #[test]
+    fn table_of_an_object_lists_scalars_then_each_list() {
+        let value = json!({ "count": 1, "note": null, "expenses": [{ "id": 7 }] });
+        assert_eq!(
+            render(value, OutputFormat::Table),
+            json!("count: 1\n\nexpenses (1)\nid\n--\n7")
+        );
+    }
+
+    #[test]
+    fn csv_quotes_when_needed_and_fills_missing_columns() {
+        let value = json!({
+            "count": 2,
+            "groups": [
+                { "name": "Casa, Palermo", "tags": ["a", "b"] },
+                { "name": "Say \"hi\"", "extra": 1 },
+            ]
+        });
+        assert_eq!(
+            render(value, OutputFormat::Csv),
+            json!("name,tags,extra\n\"Casa, Palermo\",a; b,\n\"Say \"\"hi\"\"\",,1")
+        );
+    }
+
+    #[test]
+    fn csv_of_a_plain_object_is_one_row() {
+        let value = json!({ "total": "10.00", "currency": "USD" });
+        assert_eq!(render(value, OutputFormat::Csv), json!("currency,total\nUSD,10.00"));
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+
+use crate::nl_query::normalize;
+
+// Fuzzy matching of free-form names ("la casa", "Marcos N.") against groups and people.
+// Scores are in 0..=1; callers decide what counts as confident enough.
+
+// A match at or above this score is accepted without asking
+pub const CONFIDENT: f64 = 0.8;
+// The best match must lead the runner-up by this much to be unambiguous
+const MARGIN: f64 = 0.1;
+
+// Articles and fillers that people put in front of names
+const FILLER: [&str; 9] = ["the", "la", "el", "los", "las", "our", "my", "group", "grupo"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub id: i64,
+    pub name: String,
+    pub confidence: f64,
+}
+
+/// Score every candidate against `query`, best first. Candidates scoring below 0.3 are dropped.
+pub fn rank(query: &str, candidates: impl IntoIterator<Item = (i64, String)>) -> Vec<Candidate> {
+    let query_tokens = tokens(query);
+    let mut ranked: Vec<Candidate> = candidates
+        .into_iter()
+        .map(|(id, name)| {
+            let confidence = score(&query_tokens, &tokens(&name));
+            Candidate {
+                id,
+                name,
+                confidence: (confidence * 100.0).round() / 100.0,
+            }
+        })
+        .filter(|c| c.confidence >= 0.3)
+        .collect();
+    ranked.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    ranked
+}
+
+/// The single confident, unambiguous match, if there is one.
+pub fn best(ranked: &[Candidate]) -> Option<&Candidate> {
+    let first = ranked.first()?;
+    let runner_up = ranked.get(1).map_or(0.0, |c| c.confidence);
+    (first.confidence >= CONFIDENT && first.confidence - runner_up >= MARGIN).then_some(first)
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty() && !FILLER.contains(t))
+        .map(|t| t.to_string())
+        .collect()
+}
+
+// Average over query tokens of their best match in the name, so "Marcos N." fits
+// "Marcos Núñez" and "casa" fits "Casa" but "ana" does not fit "Bariloche Trip"
+fn score(query: &[String], name: &[String]) -> f64 {
+    if query.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+    if query == name {
+        return 1.0;
+    }
+
+    let total: f64 = query
+        .iter()
+        .map(|q| {
+            name.iter()
+                .map(|n| {
+                    if q == n {
+                        1.0
+                    } else if n.starts_with(q.as_str()) {
+                        // Initials and prefixes ("n" for "nunez", "bari" for "bariloche")
+                        0.9
+                    } else {
+                        strsim::jaro_winkler(q, n) * 0.9
+                    }
+                })
+                .fold(0.0, f64::max)
+        })
+        .sum();
+
+    // Names with many words the query never mentions are slightly less likely
+    let coverage = query.len().min(name.len()) as f64 / name.len() as f64;
+    (total / query.len() as f64) * (0.85 + 0.15 * coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Vec<(i64, String)> {
+        vec![(101, "Ana García".to_string()), (102, "Marcos Núñez".to_string()), (103, "Ana López".to_string())]
+    }
+
+    #[test]
+    fn initials_and_accents_still_match() {
+        let ranked = rank("Marcos N.", people());
+        assert_eq!(ranked[0].id, 102);
+        assert_eq!(ranked[0].confidence, 0.95);
+        assert_eq!(best(&ranked).map(|c| c.id), Some(102));
+        assert_eq!(best(&rank("ana garcia", people())).map(|c| c.id), Some(101));
+    }
+
+    #[test]
+    fn fillers_are_ignored() {
+        let groups = vec![(2001, "Casa".to_string()), (2002, "Bariloche Trip".to_string())];
+        let ranked = rank("la casa", groups.clone());
+        assert_eq!((ranked[0].id, ranked[0].confidence), (2001, 1.0));
+        assert_eq!(best(&rank("the bari group", groups)).map(|c| c.id), Some(2002));
+    }
+
+    #[test]
+    fn close_runners_up_leave_it_ambiguous() {
+        let ranked = rank("ana", people());
+        assert_eq!(ranked[0].confidence, ranked[1].confidence);
+        assert!(ranked[2].confidence < 0.5);
+        assert!(best(&ranked).is_none());
+        assert!(rank("", people()).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::errors;
+use crate::tools::SplitwiseTools;
+use crate::{check_auth, AppState};
+
+// A small REST API next to /mcp for dashboards and scripts that don't speak JSON-RPC. Each
+// endpoint is a tool call (list_expenses, create_expense, get_group), so it takes the same
+// arguments, resolves the same names and fails the same way: errors come back as {"error": ...}
+// holding the JSON-RPC error object /mcp would have answered with, under a matching HTTP status.
+
+// What GET /api/expenses returns for each expense unless `fields` says otherwise
+const EXPENSE_FIELDS: [&str; 8] =
+    ["id", "date", "description", "cost", "currency_code", "category.name", "group_id", "my_net_balance"];
+
+// list_expenses arguments GET /api/expenses takes as query parameters, besides fields
+const EXPENSE_FILTERS: [&str; 9] =
+    ["group_id", "group_name", "friend_id", "user_name", "dated_after", "dated_before", "search_text", "limit", "offset"];
+
+const BALANCE_FIELDS: [&str; 4] = ["id", "name", "members{id,first_name,last_name,balance}", "simplified_debts"];
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/expenses", get(list_expenses).post(create_expense))
+        .route("/api/groups/:id/balances", get(group_balances))
+        .route("/api/openapi.json", get(openapi_document))
+}
+
+// GET /api/expenses: list_expenses's filters as query parameters; fields is comma-separated
+#[derive(Deserialize)]
+struct ExpenseQuery {
+    group_id: Option<i64>,
+    group_name: Option<String>,
+    friend_id: Option<i64>,
+    user_name: Option<String>,
+    dated_after: Option<String>,
+    dated_before: Option<String>,
+    search_text: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    fields: Option<String>,
+}
+
+async fn list_expenses(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<ExpenseQuery>,
+) -> Result<Response, StatusCode> {
+    let tools = check_auth(&headers, &state).await?;
+    let fields: Vec<String> = match &query.fields {
+        Some(fields) => fields.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect(),
+        None => EXPENSE_FIELDS.iter().map(|f| f.to_string()).collect(),
+    };
+    let mut arguments = json!({
+        "group_id": query.group_id,
+        "group_name": query.group_name,
+        "friend_id": query.friend_id,
+        "user_name": query.user_name,
+        "dated_after": query.dated_after,
+        "dated_before": query.dated_before,
+        "search_text": query.search_text,
+        "limit": query.limit,
+        "offset": query.offset,
+        "fields": fields,
+    });
+    if let Some(arguments) = arguments.as_object_mut() {
+        arguments.retain(|_, value| !value.is_null());
+    }
+    Ok(call(&tools, "list_expenses", arguments, StatusCode::OK).await)
+}
+
+// POST /api/expenses: the body is create_expense's arguments
+async fn create_expense(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<Response, StatusCode> {
+    let tools = check_auth(&headers, &state).await?;
+    Ok(call(&tools, "create_expense", body, StatusCode::CREATED).await)
+}
+
+// GET /api/groups/{id}/balances: each member's balance per currency and who pays whom
+async fn group_balances(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(group_id): Path<i64>,
+) -> Result<Response, StatusCode> {
+    let tools = check_auth(&headers, &state).await?;
+    let arguments = json!({ "group_id": group_id, "fields": BALANCE_FIELDS });
+    Ok(call(&tools, "get_group", arguments, StatusCode::OK).await)
+}
+
+async fn openapi_document(State(state): State<AppState>) -> Json<Value> {
+    Json(openapi(&state.tools))
+}
+
+async fn call(tools: &Arc<SplitwiseTools>, tool: &str, arguments: Value, success: StatusCode) -> Response {
+    match tools.handle_tool_call(tool, Some(arguments)).await {
+        Ok(result) => (success, Json(result)).into_response(),
+        Err(e) => {
+            let mut response = errors::from_error(None, &e);
+            let error = response["error"].take();
+            (status_for(&error), Json(json!({ "error": error }))).into_response()
+        }
+    }
+}
+
+fn status_for(error: &Value) -> StatusCode {
+    // Splitwise answers arguments it won't accept with 200 or 400 and a list of errors
+    if matches!(error["data"]["upstream_status"].as_u64(), Some(200 | 400)) {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    match error["code"].as_i64().unwrap_or(errors::INTERNAL_ERROR) {
+        errors::INVALID_PARAMS => StatusCode::BAD_REQUEST,
+        errors::RESOURCE_NOT_FOUND | errors::UPSTREAM_NOT_FOUND => StatusCode::NOT_FOUND,
+        errors::UPSTREAM_RATE_LIMITED => StatusCode::TOO_MANY_REQUESTS,
+        errors::UPSTREAM_UNAUTHORIZED | errors::UPSTREAM_ERROR => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// The OpenAPI 3 description of these endpoints. Parameter and body schemas are taken from the
+// tools they call, so they change with them
+fn openapi(tools: &SplitwiseTools) -> Value {
+    let registry = tools.get_tools();
+    let schema = |tool: &str| {
+        registry
+            .iter()
+            .find(|t| t["name"] == tool)
+            .map(|t| t["inputSchema"].clone())
+            .unwrap_or_else(|| json!({ "type": "object" }))
+    };
+    let list = schema("list_expenses");
+    let query: Vec<Value> = EXPENSE_FILTERS
+        .iter()
+        .map(|name| {
+            let property = &list["properties"][name];
+            json!({
+                "name": name,
+                "in": "query",
+                "description": property["description"],
+                "schema": { "type": property["type"] },
+            })
+        })
+        .chain([json!({
+            "name": "fields",
+            "in": "query",
+            "description": format!(
+                "Comma-separated fields to return for each expense, as in list_expenses. Default: {}",
+                EXPENSE_FIELDS.join(",")
+            ),
+            "schema": { "type": "string" },
+        })])
+        .collect();
+    let failure = json!({
+        "description": "The tool failed: the JSON-RPC error /mcp would have answered with",
+        "content": { "application/json": { "schema": {
+            "type": "object",
+            "properties": { "error": {
+                "type": "object",
+                "properties": {
+                    "code": { "type": "integer" },
+                    "message": { "type": "string" },
+                    "data": { "type": "object", "properties": { "upstream_status": { "type": "integer" } } },
+                },
+            } },
+        } } },
+    });
+    let result = |description: &str| json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "type": "object" } } },
+    });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Splitwise MCP server REST API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Convenience endpoints over the same tools the MCP endpoint serves",
+        },
+        "components": {
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+        },
+        "security": [{ "bearer": [] }],
+        "paths": {
+            "/api/expenses": {
+                "get": {
+                    "summary": "List expenses (list_expenses)",
+                    "parameters": query,
+                    "responses": {
+                        "200": result("{items, returned, scanned, has_more, next_offset}"),
+                        "default": failure,
+                    },
+                },
+                "post": {
+                    "summary": "Create an expense (create_expense)",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": schema("create_expense") } },
+                    },
+                    "responses": {
+                        "201": result("The created expense"),
+                        "default": failure,
+                    },
+                },
+            },
+            "/api/groups/{id}/balances": {
+                "get": {
+                    "summary": "A group's balances: each member's balance per currency and the simplified debts",
+                    "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" } }],
+                    "responses": {
+                        "200": result("{id, name, members, simplified_debts}"),
+                        "default": failure,
+                    },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::splitwise::SplitwiseClient;
+
+    #[test]
+    fn openapi_takes_its_schemas_from_the_tools() {
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        let tools = SplitwiseTools::new(Arc::new(SplitwiseClient::sandbox()), config).unwrap();
+        let document = openapi(&tools);
+        let create = &document["paths"]["/api/expenses"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert!(create["properties"]["cost"]["description"].is_string());
+        let parameters = document["paths"]["/api/expenses"]["get"]["parameters"].as_array().unwrap();
+        let group_id = parameters.iter().find(|p| p["name"] == "group_id").unwrap();
+        assert_eq!(group_id["schema"]["type"], "integer");
+        assert!(group_id["description"].is_string());
+
+        let error = |code: i64, upstream: Option<u16>| {
+            json!({ "code": code, "message": "no", "data": { "upstream_status": upstream } })
+        };
+        assert_eq!(status_for(&error(errors::INVALID_PARAMS, None)), StatusCode::BAD_REQUEST);
+        assert_eq!(status_for(&error(errors::UPSTREAM_NOT_FOUND, Some(404))), StatusCode::NOT_FOUND);
+        assert_eq!(status_for(&error(errors::UPSTREAM_ERROR, Some(200))), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(status_for(&error(errors::UPSTREAM_ERROR, Some(503))), StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::peer;
+
+// MCP roots: the directories the client lets this server work in. Tools that take a file path
+// only read and write inside them. The client is asked for them (roots/list) each time a file
+// is opened, so a root the user adds or removes applies right away. Clients without the roots
+// capability get no file access at all.
+//
+// A client's roots are paths on its own machine, which is only this server's on stdio. Over
+// HTTP they are never trusted: files are confined to the directories the operator configured
+// (HTTP_FILE_DIRS), and without any there is no file access.
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Largest file a tool reads
+const MAX_READ_BYTES: u64 = 1024 * 1024;
+
+/// Read a text file inside the client's roots, or inside `server_dirs` when given.
+pub async fn read_to_string(path: &str, server_dirs: Option<&[PathBuf]>) -> Result<String> {
+    let resolved = allowed(path, false, server_dirs).await?;
+    let size = std::fs::metadata(&resolved)
+        .with_context(|| format!("Failed to read {}", resolved.display()))?
+        .len();
+    if size > MAX_READ_BYTES {
+        bail!("{} is {} KB; files over {} KB aren't read", resolved.display(), size / 1024, MAX_READ_BYTES / 1024);
+    }
+    std::fs::read_to_string(&resolved).with_context(|| format!("Failed to read {} as text", resolved.display()))
+}
+
+/// Write a file inside the client's roots, or inside `server_dirs` when given, replacing it if
+/// it exists, and return where it went.
+pub async fn write(path: &str, contents: impl AsRef<[u8]>, server_dirs: Option<&[PathBuf]>) -> Result<PathBuf> {
+    let resolved = allowed(path, true, server_dirs).await?;
+    std::fs::write(&resolved, contents).with_context(|| format!("Failed to write {}", resolved.display()))?;
+    Ok(resolved)
+}
+
+async fn allowed(path: &str, write: bool, server_dirs: Option<&[PathBuf]>) -> Result<PathBuf> {
+    if let Some(dirs) = server_dirs {
+        if dirs.is_empty() {
+            bail!("File access over HTTP is off; the server's operator can allow directories with HTTP_FILE_DIRS");
+        }
+        let dirs: Vec<PathBuf> = dirs.iter().filter_map(|dir| dir.canonicalize().ok()).collect();
+        return resolve_in(path, &dirs, write);
+    }
+    if !peer::supports("roots") {
+        bail!("File access needs a client that shares directories with the server (MCP roots)");
+    }
+    let listed = peer::request("roots/list", json!({}), TIMEOUT).await?;
+    resolve_in(path, &roots_from(&listed), write)
+}
+
+// The shared directories that exist here, as canonical paths
+fn roots_from(listed: &Value) -> Vec<PathBuf> {
+    listed["roots"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|root| url::Url::parse(root["uri"].as_str()?).ok()?.to_file_path().ok())
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect()
+}
+
+// Where `path` points once symlinks and ".." are followed, if that is inside one of the roots.
+// Relative paths start at the first root; a file to write may not exist yet, but its directory must
+fn resolve_in(path: &str, roots: &[PathBuf], write: bool) -> Result<PathBuf> {
+    let Some(first) = roots.first() else {
+        bail!("No directory is shared with the server");
+    };
+    let requested = Path::new(path.trim());
+    let requested = if requested.is_relative() { first.join(requested) } else { requested.to_path_buf() };
+    let resolved = match requested.canonicalize() {
+        Ok(existing) => existing,
+        Err(_) if write => {
+            let name = requested.file_name().with_context(|| format!("{} isn't a file name", path))?;
+            let dir = requested.parent().unwrap_or(first);
+            dir.canonicalize()
+                .with_context(|| format!("No directory {}", dir.display()))?
+                .join(name)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Can't open {}", requested.display())),
+    };
+    if !roots.iter().any(|root| resolved.starts_with(root)) {
+        let shared: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+        bail!("{} is outside the shared directories ({})", resolved.display(), shared.join(", "));
+    }
+    if resolved.is_dir() {
+        bail!("{} is a directory", resolved.display());
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_must_stay_inside_the_roots() {
+        let base = std::env::temp_dir().join(format!("splitwise-mcp-roots-{}", std::process::id()));
+        let root = base.join("shared");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("statement.txt"), "UBER 12.40").unwrap();
+        std::fs::write(base.join("secret.txt"), "no").unwrap();
+        let listed = json!({ "roots": [
+            { "uri": url::Url::from_directory_path(&root).unwrap().to_string(), "name": "Shared" },
+            { "uri": "file:///no/such/directory" },
+        ] });
+        let roots = roots_from(&listed);
+        assert_eq!(roots, vec![root.canonicalize().unwrap()]);
+
+        assert!(resolve_in("statement.txt", &roots, false).unwrap().ends_with("shared/statement.txt"));
+        assert!(resolve_in("new.csv", &roots, true).unwrap().ends_with("shared/new.csv"));
+        assert!(resolve_in("new.csv", &roots, false).is_err());
+        assert!(resolve_in("../secret.txt", &roots, false).is_err());
+        assert!(resolve_in(base.join("secret.txt").to_str().unwrap(), &roots, true).is_err());
+        assert!(resolve_in("missing/new.csv", &roots, true).is_err());
+        assert!(resolve_in("statement.txt", &[], false).is_err());
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn http_uses_the_operator_directories_only() {
+        let base = std::env::temp_dir().join(format!("splitwise-mcp-server-dirs-{}", std::process::id()));
+        let allowed_dir = base.join("exports");
+        std::fs::create_dir_all(&allowed_dir).unwrap();
+        let dirs = [allowed_dir.clone()];
+
+        let written = write("statement.pdf", "%PDF", Some(&dirs)).await.unwrap();
+        assert_eq!(written, allowed_dir.canonicalize().unwrap().join("statement.pdf"));
+        assert_eq!(read_to_string("statement.pdf", Some(&dirs)).await.unwrap(), "%PDF");
+        assert!(write("/etc/passwd", "x", Some(&dirs)).await.is_err());
+        assert!(write(base.join("outside.txt").to_str().unwrap(), "x", Some(&dirs)).await.is_err());
+        // Nothing configured means no files at all, whatever the client claims
+        let off = read_to_string(written.to_str().unwrap(), Some(&[])).await.unwrap_err();
+        assert!(off.to_string().contains("HTTP_FILE_DIRS"));
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,31 @@
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::time::Duration;
+
+use crate::peer;
+
+// MCP sampling: a tool can ask the client's model for a completion (sampling/createMessage).
+// Only clients that declared the sampling capability in initialize are asked.
+
+// Clients usually let the user review a sampling request before running it
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Ask the client's model to answer `prompt`, and return the text it wrote.
+pub async fn create_message(system: &str, prompt: &str, max_tokens: u32) -> Result<String> {
+    if !peer::supports("sampling") {
+        bail!("This MCP client doesn't support sampling, which this tool needs");
+    }
+    let params = json!({
+        "messages": [{ "role": "user", "content": { "type": "text", "text": prompt } }],
+        "systemPrompt": system,
+        "includeContext": "none",
+        "maxTokens": max_tokens,
+        "modelPreferences": { "intelligencePriority": 0.8, "speedPriority": 0.3 },
+    });
+    let result = peer::request("sampling/createMessage", params, TIMEOUT).await?;
+    result
+        .pointer("/content/text")
+        .and_then(|t| t.as_str())
+        .map(str::to_string)
+        .context("The client's sampling answer has no text")
+}
@@ -0,0 +1,1171 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::types::*;
+
+// In-memory stand-in for the Splitwise API used when SANDBOX=true.
+// It answers the same endpoints with the same JSON shapes, so every tool works unchanged,
+// and mutations are applied to the fixture state for the lifetime of the process.
+pub struct Sandbox {
+    state: Mutex<SandboxState>,
+    // Answer everything with 503, as Splitwise does during an outage
+    pub unavailable: AtomicBool,
+    // Milliseconds to take over each answer, as over a slow connection
+    pub latency_ms: AtomicU64,
+}
+
+struct SandboxGroup {
+    id: i64,
+    name: String,
+    group_type: Option<String>,
+    simplify_by_default: bool,
+    member_ids: Vec<i64>,
+    updated_at: String,
+}
+
+struct SandboxState {
+    me: i64,
+    users: Vec<User>,
+    friend_ids: Vec<i64>,
+    groups: Vec<SandboxGroup>,
+    expenses: Vec<Expense>,
+    comments: Vec<Comment>,
+    next_id: i64,
+}
+
+type SandboxResponse = (StatusCode, String);
+
+// (days ago, description, cost, currency, category, group, payer, participants)
+type FixtureExpense<'a> = (i64, &'a str, f64, &'a str, i64, Option<i64>, i64, Vec<i64>);
+type FixtureCategory<'a> = (i64, &'a str, &'a [(i64, &'a str)]);
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SandboxState::fixtures(Utc::now())),
+            unavailable: AtomicBool::new(false),
+            latency_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn handle(
+        &self,
+        method: Method,
+        endpoint: &str,
+        params: &[(&str, String)],
+        body: Option<&Value>,
+    ) -> SandboxResponse {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable".to_string());
+        }
+        let mut state = self.state.lock().unwrap();
+        let empty = json!({});
+        let body = body.unwrap_or(&empty);
+
+        let path = endpoint.trim_start_matches('/');
+        let (name, id) = match path.rsplit_once('/') {
+            Some((name, id)) => (name, id.parse::<i64>().ok()),
+            None => (path, None),
+        };
+
+        let result = match (method, name, id) {
+            (Method::GET, "get_current_user", _) => {
+                Ok(json!({ "user": state.user(state.me) }))
+            }
+            (Method::GET, "get_user", Some(id)) => state
+                .users
+                .iter()
+                .find(|u| u.id == id)
+                .map(|u| json!({ "user": u }))
+                .ok_or_else(|| not_found("user")),
+            (Method::POST, "update_user", Some(id)) => state.update_user(id, body),
+            (Method::GET, "get_groups", _) => {
+                let groups: Vec<Group> = state.groups.iter().map(|g| state.render_group(g)).collect();
+                Ok(json!({ "groups": groups }))
+            }
+            (Method::GET, "get_group", Some(id)) => state
+                .groups
+                .iter()
+                .find(|g| g.id == id)
+                .map(|g| json!({ "group": state.render_group(g) }))
+                .ok_or_else(|| not_found("group")),
+            (Method::POST, "create_group", _) => Ok(state.create_group(body)),
+            (Method::POST, "delete_group", Some(id)) => {
+                let before = state.groups.len();
+                state.groups.retain(|g| g.id != id);
+                Ok(json!({ "success": state.groups.len() < before, "errors": {} }))
+            }
+            (Method::POST, "add_user_to_group", _) => Ok(state.add_user_to_group(body)),
+            (Method::POST, "remove_user_from_group", _) => {
+                let group_id = body["group_id"].as_i64().unwrap_or_default();
+                let user_id = body["user_id"].as_i64().unwrap_or_default();
+                let removed = match state.groups.iter_mut().find(|g| g.id == group_id) {
+                    Some(group) => {
+                        let before = group.member_ids.len();
+                        group.member_ids.retain(|id| *id != user_id);
+                        group.member_ids.len() < before
+                    }
+                    None => false,
+                };
+                Ok(json!({ "success": removed, "errors": {} }))
+            }
+            (Method::GET, "get_expenses", _) => Ok(json!({ "expenses": state.list_expenses(params) })),
+            (Method::GET, "get_expense", Some(id)) => state
+                .expenses
+                .iter()
+                .find(|e| e.id == id)
+                .map(|e| json!({ "expense": e }))
+                .ok_or_else(|| not_found("expense")),
+            (Method::POST, "create_expense", _) => Ok(state.create_expense(body)),
+            (Method::POST, "update_expense", Some(id)) => state.update_expense(id, body),
+            (Method::POST, "delete_expense", Some(id)) => {
+                let me = state.me_reference();
+                match state.expenses.iter_mut().find(|e| e.id == id) {
+                    Some(expense) => {
+                        expense.deleted_at = Some(Utc::now().to_rfc3339());
+                        expense.deleted_by = Some(me);
+                        // Like Splitwise, deleting counts as an update
+                        expense.updated_at = Utc::now().to_rfc3339();
+                        let me = state.me;
+                        let content = format!("{} deleted this expense.", state.short_name(me));
+                        state.add_system_comment(id, me, content, Utc::now());
+                        Ok(json!({ "success": true, "errors": {} }))
+                    }
+                    None => Err(not_found("expense")),
+                }
+            }
+            (Method::GET, "get_comments", _) => {
+                let expense_id = params
+                    .iter()
+                    .find(|(k, _)| *k == "expense_id")
+                    .and_then(|(_, v)| v.parse::<i64>().ok());
+                let comments: Vec<&Comment> = state
+                    .comments
+                    .iter()
+                    .filter(|c| c.relation_id == expense_id)
+                    .collect();
+                Ok(json!({ "comments": comments }))
+            }
+            (Method::POST, "parse_sentence", _) => Ok(state.parse_sentence(body)),
+            (Method::GET, "get_friends", _) => {
+                let friends: Vec<Friend> = state
+                    .friend_ids
+                    .iter()
+                    .map(|id| state.render_friend(*id))
+                    .collect();
+                Ok(json!({ "friends": friends }))
+            }
+            (Method::GET, "get_friend", Some(id)) if state.friend_ids.contains(&id) => {
+                Ok(json!({ "friend": state.render_friend(id) }))
+            }
+            (Method::POST, "create_friend", _) => {
+                let email = body["user_email"].as_str().unwrap_or_default().to_string();
+                let id = state.find_or_invite(&email, None, None);
+                if !state.friend_ids.contains(&id) {
+                    state.friend_ids.push(id);
+                }
+                Ok(json!({ "friends": [state.render_friend(id)] }))
+            }
+            (Method::POST, "delete_friend", Some(id)) => {
+                let before = state.friend_ids.len();
+                state.friend_ids.retain(|f| *f != id);
+                Ok(json!({ "success": state.friend_ids.len() < before, "errors": {} }))
+            }
+            (Method::GET, "get_currencies", _) => Ok(json!({ "currencies": fixture_currencies() })),
+            (Method::GET, "get_categories", _) => Ok(json!({ "categories": fixture_categories() })),
+            _ => Err(not_found("endpoint")),
+        };
+
+        match result {
+            Ok(value) => (StatusCode::OK, value.to_string()),
+            Err((status, message)) => (
+                status,
+                json!({ "errors": { "base": [message] } }).to_string(),
+            ),
+        }
+    }
+}
+
+// parse_sentence's answer for a sentence it can't make an expense of
+fn sentence_error(error: &str) -> Value {
+    json!({ "expense": null, "valid": false, "confidence": 0.0, "error": error })
+}
+
+// The currency a word of a sentence names: a code ("eur") or a symbol before the amount ("€12")
+fn sentence_currency(word: &str) -> Option<&'static str> {
+    const CODES: [&str; 5] = ["ARS", "BRL", "EUR", "GBP", "USD"];
+    match word.chars().next()? {
+        '€' => return Some("EUR"),
+        '£' => return Some("GBP"),
+        _ => {}
+    }
+    let word = word.trim_matches(|c: char| !c.is_alphabetic()).to_uppercase();
+    CODES.into_iter().find(|code| *code == word)
+}
+
+fn not_found(what: &str) -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, format!("{} not found (sandbox)", what))
+}
+
+fn amount(value: f64) -> String {
+    format!("{:.2}", value)
+}
+
+fn parse(value: &str) -> f64 {
+    value.parse().unwrap_or(0.0)
+}
+
+// Body values may arrive as JSON strings or numbers
+fn body_str(body: &Value, key: &str) -> Option<String> {
+    match &body[key] {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn body_i64(body: &Value, key: &str) -> Option<i64> {
+    match &body[key] {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn body_bool(body: &Value, key: &str) -> bool {
+    match &body[key] {
+        Value::Bool(b) => *b,
+        Value::String(s) => s == "true" || s == "1",
+        Value::Number(n) => n.as_i64() == Some(1),
+        _ => false,
+    }
+}
+
+// Derive who pays whom from each user's net balance, matching debtors to creditors greedily
+fn repayments_for(users: &[ExpenseUser]) -> Vec<Repayment> {
+    let mut creditors: Vec<(i64, f64)> = users
+        .iter()
+        .map(|u| (u.user_id, parse(&u.net_balance)))
+        .filter(|(_, net)| *net > 0.005)
+        .collect();
+    let mut debtors: Vec<(i64, f64)> = users
+        .iter()
+        .map(|u| (u.user_id, -parse(&u.net_balance)))
+        .filter(|(_, owed)| *owed > 0.005)
+        .collect();
+
+    let mut repayments = Vec::new();
+    let (mut c, mut d) = (0, 0);
+    while c < creditors.len() && d < debtors.len() {
+        let settled = creditors[c].1.min(debtors[d].1);
+        repayments.push(Repayment {
+            from: debtors[d].0,
+            to: creditors[c].0,
+            amount: amount(settled),
+        });
+        creditors[c].1 -= settled;
+        debtors[d].1 -= settled;
+        if creditors[c].1 < 0.005 {
+            c += 1;
+        }
+        if debtors[d].1 < 0.005 {
+            d += 1;
+        }
+    }
+    repayments
+}
+
+impl SandboxState {
+    fn fixtures(now: DateTime<Utc>) -> Self {
+        let user = |id: i64, first: &str, last: &str, email: &str, status: &str, currency: &str| User {
+            id,
+            first_name: first.to_string(),
+            last_name: Some(last.to_string()),
+            email: email.to_string(),
+            registration_status: Some(status.to_string()),
+            picture: None,
+            default_currency: Some(currency.to_string()),
+            locale: Some("en".to_string()),
+        };
+
+        let users = vec![
+            user(100, "Alex", "Sandbox", "alex@example.com", "confirmed", "USD"),
+            user(101, "Ana", "García", "ana@example.com", "confirmed", "USD"),
+            user(102, "Marcos", "Núñez", "marcos@example.com", "confirmed", "ARS"),
+            user(103, "Jamie", "Lee", "jamie@example.com", "confirmed", "USD"),
+            user(104, "Pat", "Rivera", "pat@example.com", "invited", "USD"),
+            user(105, "Sam", "Oldfriend", "sam@example.com", "confirmed", "EUR"),
+        ];
+
+        let groups = vec![
+            SandboxGroup {
+                id: 2001,
+                name: "Casa".to_string(),
+                group_type: Some("home".to_string()),
+                simplify_by_default: true,
+                member_ids: vec![100, 101, 102],
+                updated_at: now.to_rfc3339(),
+            },
+            SandboxGroup {
+                id: 2002,
+                name: "Bariloche Trip".to_string(),
+                group_type: Some("trip".to_string()),
+                simplify_by_default: false,
+                member_ids: vec![100, 102, 103],
+                updated_at: (now - Duration::days(110)).to_rfc3339(),
+            },
+        ];
+
+        let mut state = Self {
+            me: 100,
+            users,
+            friend_ids: vec![101, 102, 103, 104, 105],
+            groups,
+            expenses: Vec::new(),
+            comments: Vec::new(),
+            next_id: 30001,
+        };
+
+        let casa = [100, 101, 102];
+        let trip = [100, 102, 103];
+        let fixtures: Vec<FixtureExpense> = vec![
+            (95, "Rent", 1500.0, "USD", 3, Some(2001), 100, casa.to_vec()),
+            (65, "Rent", 1500.0, "USD", 3, Some(2001), 100, casa.to_vec()),
+            (35, "Rent", 1500.0, "USD", 3, Some(2001), 100, casa.to_vec()),
+            (5, "Rent", 1500.0, "USD", 3, Some(2001), 100, casa.to_vec()),
+            (72, "Electricity bill", 84.2, "USD", 5, Some(2001), 101, casa.to_vec()),
+            (42, "Electricity bill", 91.75, "USD", 5, Some(2001), 101, casa.to_vec()),
+            (12, "Electricity bill", 90.5, "USD", 5, Some(2001), 101, casa.to_vec()),
+            (63, "Internet", 45.0, "USD", 8, Some(2001), 102, casa.to_vec()),
+            (33, "Internet", 45.0, "USD", 8, Some(2001), 102, casa.to_vec()),
+            (3, "Internet", 45.0, "USD", 8, Some(2001), 102, casa.to_vec()),
+            (100, "Netflix", 15.49, "USD", 21, Some(2001), 100, casa.to_vec()),
+            (70, "Netflix", 15.49, "USD", 21, Some(2001), 100, casa.to_vec()),
+            (40, "NETFLIX.COM", 15.49, "USD", 21, Some(2001), 100, casa.to_vec()),
+            (10, "Netflix", 15.49, "USD", 21, Some(2001), 100, casa.to_vec()),
+            (77, "Supermercado Día", 71.2, "USD", 12, Some(2001), 100, casa.to_vec()),
+            (47, "Groceries", 58.0, "USD", 12, Some(2001), 102, casa.to_vec()),
+            (20, "Carrefour", 112.75, "USD", 12, Some(2001), 101, casa.to_vec()),
+            (8, "Supermercado Día", 64.3, "USD", 12, Some(2001), 100, casa.to_vec()),
+            (15, "Asado", 96.0, "USD", 13, Some(2001), 102, casa.to_vec()),
+            (9, "Cleaning supplies", 23.4, "USD", 14, Some(2001), 101, vec![100, 101]),
+            (121, "Uber to the airport", 35.0, "USD", 36, Some(2002), 100, trip.to_vec()),
+            (120, "Hotel Llao Llao", 450000.0, "ARS", 30, Some(2002), 100, trip.to_vec()),
+            (119, "Nafta", 60000.0, "ARS", 33, Some(2002), 103, trip.to_vec()),
+            (118, "Cena en El Boliche", 120000.0, "ARS", 13, Some(2002), 102, trip.to_vec()),
+            (30, "Concert tickets", 180.0, "USD", 22, None, 100, vec![100, 103]),
+            (200, "Pizza night", 42.0, "USD", 13, None, 100, vec![100, 104]),
+            (520, "Museum tickets", 30.0, "EUR", 23, None, 105, vec![100, 105]),
+        ];
+
+        for (days_ago, description, cost, currency, category, group_id, payer, participants) in fixtures {
+            let share = cost / participants.len() as f64;
+            let shares: Vec<(i64, f64, f64)> = participants
+                .iter()
+                .map(|id| (*id, if *id == payer { cost } else { 0.0 }, share))
+                .collect();
+            let date = now - Duration::days(days_ago);
+            state.insert_expense(description, cost, currency, category, group_id, &shares, false, date, payer);
+        }
+
+        // Settlements: Ana paid part of what she owed, the old museum debt with Sam is settled
+        let ana_payment = now - Duration::days(25);
+        state.insert_expense("Payment", 300.0, "USD", 18, Some(2001), &[(101, 300.0, 0.0), (100, 0.0, 300.0)], true, ana_payment, 101);
+        let sam_payment = now - Duration::days(500);
+        state.insert_expense("Payment", 15.0, "EUR", 18, None, &[(100, 15.0, 0.0), (105, 0.0, 15.0)], true, sam_payment, 100);
+
+        // Recurring series: the latest rent and internet entries repeat monthly
+        for expense in state.expenses.iter_mut() {
+            if (expense.description == "Rent" || expense.description == "Internet")
+                && parse_days_ago(&expense.date, now) < 10
+            {
+                expense.repeats = true;
+                expense.repeat_interval = Some("monthly".to_string());
+                expense.next_repeat = DateTime::parse_from_rfc3339(&expense.date)
+                    .ok()
+                    .map(|d| (d + chrono::Months::new(1)).with_timezone(&Utc).to_rfc3339());
+            }
+        }
+
+        // Ana corrected the latest rent after it was entered
+        let rent = state
+            .expenses
+            .iter_mut()
+            .filter(|e| e.description == "Rent")
+            .max_by(|a, b| a.date.cmp(&b.date));
+        if let Some(rent) = rent {
+            let edited = DateTime::parse_from_rfc3339(&rent.date).unwrap().with_timezone(&Utc) + Duration::hours(5);
+            rent.updated_at = edited.to_rfc3339();
+            rent.updated_by = Some(UserReference {
+                id: 101,
+                first_name: "Ana".to_string(),
+                last_name: Some("García".to_string()),
+                picture: None,
+            });
+            let id = rent.id;
+            let content = "Ana G. updated this transaction:\n- cost changed from “1450.00” to “1500.00”".to_string();
+            state.add_system_comment(id, 101, content, edited);
+        }
+
+        // A question about the latest electricity bill, and Ana's answer
+        let electricity = state
+            .expenses
+            .iter()
+            .filter(|e| e.description == "Electricity bill")
+            .max_by(|a, b| a.date.cmp(&b.date))
+            .map(|e| (e.id, DateTime::parse_from_rfc3339(&e.date).unwrap().with_timezone(&Utc)));
+        if let Some((id, date)) = electricity {
+            state.add_comment(id, 100, "User", "Why is this one so much higher than last month?".to_string(), date + Duration::days(1));
+            state.add_comment(id, 101, "User", "The heater was on most of the month".to_string(), date + Duration::days(2));
+        }
+
+        // A duplicate that somebody already deleted
+        let duplicate = now - Duration::days(8);
+        state.insert_expense("Supermercado Día", 64.3, "USD", 12, Some(2001), &[(100, 64.3, 21.43), (101, 0.0, 21.43), (102, 0.0, 21.44)], false, duplicate, 100);
+        if let Some(last) = state.expenses.last_mut() {
+            last.deleted_at = Some((duplicate + Duration::hours(1)).to_rfc3339());
+            last.deleted_by = Some(last.created_by.clone());
+            let id = last.id;
+            state.add_system_comment(id, 100, "Alex S. deleted this expense.".to_string(), duplicate + Duration::hours(1));
+        }
+
+        state
+    }
+
+    fn user(&self, id: i64) -> Option<&User> {
+        self.users.iter().find(|u| u.id == id)
+    }
+
+    fn reference(&self, id: i64) -> UserReference {
+        let user = self.user(id);
+        UserReference {
+            id,
+            first_name: user.map(|u| u.first_name.clone()).unwrap_or_default(),
+            last_name: user.and_then(|u| u.last_name.clone()),
+            picture: None,
+        }
+    }
+
+    fn me_reference(&self) -> UserReference {
+        self.reference(self.me)
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn category(&self, id: i64) -> Category {
+        let categories = fixture_categories();
+        let found = categories
+            .iter()
+            .flat_map(|c| {
+                std::iter::once((c.id, c.name.clone()))
+                    .chain(c.subcategories.iter().flatten().map(|s| (s.id, s.name.clone())))
+            })
+            .find(|(cid, _)| *cid == id);
+        let (id, name) = found.unwrap_or((18, "General".to_string()));
+        Category {
+            id,
+            name,
+            icon: None,
+            subcategories: None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert_expense(
+        &mut self,
+        description: &str,
+        cost: f64,
+        currency: &str,
+        category_id: i64,
+        group_id: Option<i64>,
+        shares: &[(i64, f64, f64)],
+        payment: bool,
+        date: DateTime<Utc>,
+        created_by: i64,
+    ) -> Expense {
+        let users: Vec<ExpenseUser> = shares
+            .iter()
+            .map(|(user_id, paid, owed)| ExpenseUser {
+                user_id: *user_id,
+                user: Some(self.reference(*user_id)),
+                paid_share: amount(*paid),
+                owed_share: amount(*owed),
+                net_balance: amount(paid - owed),
+            })
+            .collect();
+        let id = self.next_id();
+        let expense = Expense {
+            id,
+            group_id,
+            friendship_id: if group_id.is_none() { Some(id + 500000) } else { None },
+            expense_bundle_id: None,
+            description: description.to_string(),
+            repeats: false,
+            repeat_interval: Some("never".to_string()),
+            email_reminder: Some(false),
+            email_reminder_in_advance: Some(-1),
+            next_repeat: None,
+            details: None,
+            comments_count: 0,
+            payment,
+            creation_method: Some(if payment { "payment" } else { "equal" }.to_string()),
+            transaction_method: Some("offline".to_string()),
+            transaction_confirmed: false,
+            transaction_id: None,
+            transaction_status: None,
+            cost: amount(cost),
+            currency_code: currency.to_string(),
+            repayments: repayments_for(&users),
+            date: date.to_rfc3339(),
+            created_at: date.to_rfc3339(),
+            created_by: self.reference(created_by),
+            updated_at: date.to_rfc3339(),
+            updated_by: None,
+            deleted_at: None,
+            deleted_by: None,
+            category: self.category(category_id),
+            receipt: Receipt {
+                original: None,
+                large: None,
+            },
+            users,
+        };
+        self.expenses.push(expense.clone());
+        expense
+    }
+
+    fn find_or_invite(&mut self, email: &str, first_name: Option<String>, last_name: Option<String>) -> i64 {
+        if let Some(user) = self.users.iter().find(|u| u.email.eq_ignore_ascii_case(email)) {
+            return user.id;
+        }
+        let id = self.next_id();
+        self.users.push(User {
+            id,
+            first_name: first_name.unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string()),
+            last_name,
+            email: email.to_string(),
+            registration_status: Some("invited".to_string()),
+            picture: None,
+            default_currency: Some("USD".to_string()),
+            locale: None,
+        });
+        id
+    }
+
+    // Per-currency totals of a user's net balance over the given expenses
+    fn balances_of(&self, user_id: i64, expenses: &[&Expense]) -> Vec<Balance> {
+        let mut totals: HashMap<String, f64> = HashMap::new();
+        for expense in expenses {
+            for user in expense.users.iter().filter(|u| u.user_id == user_id) {
+                *totals.entry(expense.currency_code.clone()).or_default() += parse(&user.net_balance);
+            }
+        }
+        to_balances(totals)
+    }
+
+    fn live_expenses(&self) -> impl Iterator<Item = &Expense> {
+        self.expenses.iter().filter(|e| e.deleted_at.is_none())
+    }
+
+    fn render_group(&self, group: &SandboxGroup) -> Group {
+        let expenses: Vec<&Expense> = self
+            .live_expenses()
+            .filter(|e| e.group_id == Some(group.id))
+            .collect();
+
+        let members = group
+            .member_ids
+            .iter()
+            .filter_map(|id| self.user(*id))
+            .map(|u| GroupMember {
+                id: u.id,
+                first_name: u.first_name.clone(),
+                last_name: u.last_name.clone(),
+                email: Some(u.email.clone()),
+                registration_status: u.registration_status.clone(),
+                picture: None,
+                balance: self.balances_of(u.id, &expenses),
+            })
+            .collect::<Vec<_>>();
+
+        // Pairwise debts per currency, then a simplified version computed from net balances
+        let mut pairwise: HashMap<(i64, i64, String), f64> = HashMap::new();
+        for expense in &expenses {
+            for repayment in &expense.repayments {
+                *pairwise
+                    .entry((repayment.from, repayment.to, expense.currency_code.clone()))
+                    .or_default() += parse(&repayment.amount);
+            }
+        }
+        let mut original_debts = Vec::new();
+        for ((from, to, currency), value) in &pairwise {
+            let reverse = pairwise.get(&(*to, *from, currency.clone())).copied().unwrap_or(0.0);
+            if value - reverse > 0.005 {
+                original_debts.push(Debt {
+                    from: *from,
+                    to: *to,
+                    amount: amount(value - reverse),
+                    currency_code: currency.clone(),
+                });
+            }
+        }
+
+        let mut simplified_debts = Vec::new();
+        let currencies: Vec<String> = members
+            .iter()
+            .flat_map(|m| m.balance.iter().map(|b| b.currency_code.clone()))
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        for currency in currencies {
+            let users: Vec<ExpenseUser> = members
+                .iter()
+                .map(|m| ExpenseUser {
+                    user_id: m.id,
+                    user: None,
+                    paid_share: String::new(),
+                    owed_share: String::new(),
+                    net_balance: m
+                        .balance
+                        .iter()
+                        .find(|b| b.currency_code == currency)
+                        .map(|b| b.amount.clone())
+                        .unwrap_or_default(),
+                })
+                .collect();
+            simplified_debts.extend(repayments_for(&users).into_iter().map(|r| Debt {
+                from: r.from,
+                to: r.to,
+                amount: r.amount,
+                currency_code: currency.clone(),
+            }));
+        }
+
+        Group {
+            id: group.id,
+            name: group.name.clone(),
+            group_type: group.group_type.clone(),
+            updated_at: group.updated_at.clone(),
+            simplify_by_default: group.simplify_by_default,
+            members,
+            original_debts,
+            simplified_debts,
+            whiteboard: None,
+            group_reminders: None,
+            invite_link: Some(format!("https://www.splitwise.com/join/sandbox{}+{}", group.id, self.me)),
+        }
+    }
+
+    fn render_friend(&self, id: i64) -> Friend {
+        let user = self.user(id).cloned();
+        // Positive balance means the friend owes the current user
+        let mut by_group: HashMap<i64, HashMap<String, f64>> = HashMap::new();
+        let mut updated_at = String::new();
+        for expense in self.live_expenses() {
+            let involved = expense.users.iter().any(|u| u.user_id == id)
+                && expense.users.iter().any(|u| u.user_id == self.me);
+            if !involved {
+                continue;
+            }
+            if expense.updated_at > updated_at {
+                updated_at = expense.updated_at.clone();
+            }
+            for repayment in &expense.repayments {
+                let delta = if repayment.from == id && repayment.to == self.me {
+                    parse(&repayment.amount)
+                } else if repayment.from == self.me && repayment.to == id {
+                    -parse(&repayment.amount)
+                } else {
+                    continue;
+                };
+                *by_group
+                    .entry(expense.group_id.unwrap_or(0))
+                    .or_default()
+                    .entry(expense.currency_code.clone())
+                    .or_default() += delta;
+            }
+        }
+
+        let mut totals: HashMap<String, f64> = HashMap::new();
+        for balances in by_group.values() {
+            for (currency, value) in balances {
+                *totals.entry(currency.clone()).or_default() += value;
+            }
+        }
+
+        Friend {
+            id,
+            first_name: user.as_ref().map(|u| u.first_name.clone()).unwrap_or_default(),
+            last_name: user.as_ref().and_then(|u| u.last_name.clone()),
+            email: user.as_ref().map(|u| u.email.clone()),
+            registration_status: user.as_ref().and_then(|u| u.registration_status.clone()),
+            picture: None,
+            balance: to_balances(totals),
+            groups: by_group
+                .into_iter()
+                .map(|(group_id, balances)| FriendGroup {
+                    group_id,
+                    balance: to_balances(balances),
+                })
+                .collect(),
+            updated_at,
+        }
+    }
+
+    fn list_expenses(&self, params: &[(&str, String)]) -> Vec<Expense> {
+        let param = |name: &str| params.iter().find(|(k, _)| *k == name).map(|(_, v)| v.clone());
+        let group_id = param("group_id").and_then(|v| v.parse::<i64>().ok());
+        let friend_id = param("friend_id").and_then(|v| v.parse::<i64>().ok());
+        let limit = param("limit").and_then(|v| v.parse::<usize>().ok()).unwrap_or(20);
+        let offset = param("offset").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+
+        let mut expenses: Vec<&Expense> = self
+            .expenses
+            .iter()
+            .filter(|e| group_id.is_none_or(|g| e.group_id == Some(g)))
+            .filter(|e| friend_id.is_none_or(|f| e.users.iter().any(|u| u.user_id == f)))
+            .filter(|e| param("dated_after").is_none_or(|d| e.date.as_str() >= d.as_str()))
+            .filter(|e| param("dated_before").is_none_or(|d| e.date.as_str() <= d.as_str()))
+            .filter(|e| param("updated_after").is_none_or(|d| e.updated_at.as_str() >= d.as_str()))
+            .filter(|e| param("updated_before").is_none_or(|d| e.updated_at.as_str() <= d.as_str()))
+            .collect();
+        expenses.sort_by(|a, b| b.date.cmp(&a.date).then(b.id.cmp(&a.id)));
+
+        // Like Splitwise, limit=0 means no limit
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        expenses.into_iter().skip(offset).take(limit).cloned().collect()
+    }
+
+    // Read users__N__field entries from a flattened request body
+    fn flattened_shares(&mut self, body: &Value) -> Option<Vec<(i64, f64, f64)>> {
+        let mut shares = Vec::new();
+        for i in 0.. {
+            let key = |field: &str| format!("users__{}__{}", i, field);
+            if body[key("paid_share")].is_null() && body[key("owed_share")].is_null() {
+                break;
+            }
+            let user_id = match body_i64(body, &key("user_id")) {
+                Some(id) => id,
+                None => {
+                    let email = body_str(body, &key("email"))?;
+                    let first = body_str(body, &key("first_name"));
+                    let last = body_str(body, &key("last_name"));
+                    let id = self.find_or_invite(&email, first, last);
+                    // Like Splitwise, sharing an expense with someone makes them a friend
+                    if id != self.me && !self.friend_ids.contains(&id) {
+                        self.friend_ids.push(id);
+                    }
+                    id
+                }
+            };
+            let paid = body_str(body, &key("paid_share")).map(|v| parse(&v)).unwrap_or(0.0);
+            let owed = body_str(body, &key("owed_share")).map(|v| parse(&v)).unwrap_or(0.0);
+            shares.push((user_id, paid, owed));
+        }
+        (!shares.is_empty()).then_some(shares)
+    }
+
+    fn create_expense(&mut self, body: &Value) -> Value {
+        let cost = body_str(body, "cost").map(|c| parse(&c)).unwrap_or(0.0);
+        let group_id = body_i64(body, "group_id").filter(|id| *id != 0);
+        let shares = match self.flattened_shares(body) {
+            Some(shares) => shares,
+            None => {
+                let members = match group_id.and_then(|id| self.groups.iter().find(|g| g.id == id)) {
+                    Some(group) => group.member_ids.clone(),
+                    None => return expense_error("Either group_id with split_equally or users__N__ shares are required"),
+                };
+                let share = cost / members.len() as f64;
+                members
+                    .iter()
+                    .map(|id| (*id, if *id == self.me { cost } else { 0.0 }, share))
+                    .collect()
+            }
+        };
+
+        let paid: f64 = shares.iter().map(|s| s.1).sum();
+        let owed: f64 = shares.iter().map(|s| s.2).sum();
+        if (paid - cost).abs() > 0.01 || (owed - cost).abs() > 0.01 {
+            return expense_error(&format!(
+                "The total of paid shares ({:.2}) and owed shares ({:.2}) must equal the cost ({:.2})",
+                paid, owed, cost
+            ));
+        }
+
+        let date = body_str(body, "date")
+            .and_then(|d| {
+                DateTime::parse_from_rfc3339(&d).ok().map(|d| d.with_timezone(&Utc)).or_else(|| {
+                    chrono::NaiveDate::parse_from_str(d.get(..10)?, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|d| d.and_hms_opt(12, 0, 0))
+                        .map(|d| d.and_utc())
+                })
+            })
+            .unwrap_or_else(Utc::now);
+        let description = body_str(body, "description").unwrap_or_default();
+        let currency = body_str(body, "currency_code").unwrap_or_else(|| "USD".to_string());
+        let category = body_i64(body, "category_id").unwrap_or(18);
+        let me = self.me;
+
+        let mut expense = self.insert_expense(&description, cost, &currency, category, group_id, &shares, body_bool(body, "payment"), date, me);
+        expense.details = body_str(body, "details");
+        expense.created_at = Utc::now().to_rfc3339();
+        expense.updated_at = expense.created_at.clone();
+        self.replace(expense.clone());
+
+        json!({ "expenses": [expense], "errors": {} })
+    }
+
+    fn update_expense(&mut self, id: i64, body: &Value) -> Result<Value, (StatusCode, String)> {
+        let mut expense = self
+            .expenses
+            .iter()
+            .find(|e| e.id == id)
+            .cloned()
+            .ok_or_else(|| not_found("expense"))?;
+
+        if let Some(description) = body_str(body, "description") {
+            expense.description = description;
+        }
+        if let Some(currency) = body_str(body, "currency_code") {
+            expense.currency_code = currency;
+        }
+        if let Some(details) = body_str(body, "details") {
+            expense.details = Some(details);
+        }
+        if let Some(date) = body_str(body, "date") {
+            expense.date = date;
+        }
+        if let Some(category) = body_i64(body, "category_id") {
+            expense.category = self.category(category);
+        }
+        if let Some(cost) = body_str(body, "cost") {
+            let cost = parse(&cost);
+            // Without new shares, rescale the existing split to the new cost
+            let old = parse(&expense.cost);
+            if old > 0.0 && body["users__0__owed_share"].is_null() {
+                let factor = cost / old;
+                for user in expense.users.iter_mut() {
+                    let paid = parse(&user.paid_share) * factor;
+                    let owed = parse(&user.owed_share) * factor;
+                    user.paid_share = amount(paid);
+                    user.owed_share = amount(owed);
+                    user.net_balance = amount(paid - owed);
+                }
+            }
+            expense.cost = amount(cost);
+        }
+        if let Some(shares) = self.flattened_shares(body) {
+            let cost = parse(&expense.cost);
+            let paid: f64 = shares.iter().map(|s| s.1).sum();
+            let owed: f64 = shares.iter().map(|s| s.2).sum();
+            if (paid - cost).abs() > 0.01 || (owed - cost).abs() > 0.01 {
+                return Ok(expense_error(&format!(
+                    "The total of paid shares ({:.2}) and owed shares ({:.2}) must equal the cost ({:.2})",
+                    paid, owed, cost
+                )));
+            }
+            expense.users = shares
+                .iter()
+                .map(|(user_id, paid, owed)| ExpenseUser {
+                    user_id: *user_id,
+                    user: Some(self.reference(*user_id)),
+                    paid_share: amount(*paid),
+                    owed_share: amount(*owed),
+                    net_balance: amount(paid - owed),
+                })
+                .collect();
+        }
+
+        expense.repayments = repayments_for(&expense.users);
+        expense.updated_at = Utc::now().to_rfc3339();
+        expense.updated_by = Some(self.me_reference());
+
+        // Splitwise logs every edit as a system comment on the expense
+        let previous = self.expenses.iter().find(|e| e.id == id).cloned().unwrap();
+        let shares = |e: &Expense| {
+            e.users
+                .iter()
+                .map(|u| format!("{} {}/{}", self.short_name(u.user_id), u.paid_share, u.owed_share))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let changes: Vec<String> = [
+            ("description", previous.description.clone(), expense.description.clone()),
+            ("cost", previous.cost.clone(), expense.cost.clone()),
+            ("currency", previous.currency_code.clone(), expense.currency_code.clone()),
+            ("date", previous.date.clone(), expense.date.clone()),
+            ("category", previous.category.name.clone(), expense.category.name.clone()),
+            ("details", previous.details.clone().unwrap_or_default(), expense.details.clone().unwrap_or_default()),
+            ("shares", shares(&previous), shares(&expense)),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| format!("- {} changed from “{}” to “{}”", field, old, new))
+        .collect();
+        if !changes.is_empty() {
+            let content = format!("{} updated this transaction:\n{}", self.short_name(self.me), changes.join("\n"));
+            self.add_system_comment(id, self.me, content, Utc::now());
+        }
+
+        self.replace(expense.clone());
+
+        Ok(json!({ "expenses": [expense], "errors": {} }))
+    }
+
+    // "Ana G.", the way Splitwise names people in system comments
+    fn short_name(&self, id: i64) -> String {
+        let reference = self.reference(id);
+        match reference.last_name.as_deref().and_then(|l| l.chars().next()) {
+            Some(initial) => format!("{} {}.", reference.first_name, initial),
+            None => reference.first_name,
+        }
+    }
+
+    fn add_system_comment(&mut self, expense_id: i64, author: i64, content: String, at: DateTime<Utc>) {
+        self.add_comment(expense_id, author, "System", content, at);
+    }
+
+    fn add_comment(&mut self, expense_id: i64, author: i64, comment_type: &str, content: String, at: DateTime<Utc>) {
+        if let Some(expense) = self.expenses.iter_mut().find(|e| e.id == expense_id) {
+            expense.comments_count += 1;
+        }
+        let comment = Comment {
+            id: 90001 + self.comments.len() as i64,
+            content,
+            comment_type: comment_type.to_string(),
+            relation_type: Some("ExpenseComment".to_string()),
+            relation_id: Some(expense_id),
+            created_at: at.to_rfc3339(),
+            deleted_at: None,
+            user: Some(self.reference(author)),
+        };
+        self.comments.push(comment);
+    }
+
+    fn replace(&mut self, expense: Expense) {
+        if let Some(existing) = self.expenses.iter_mut().find(|e| e.id == expense.id) {
+            *existing = expense;
+        }
+    }
+
+    fn parse_sentence(&mut self, body: &Value) -> Value {
+        let input = body_str(body, "input").unwrap_or_default();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        // Very small parser: the first number is the cost, a currency code or symbol the
+        // currency, "<name> paid" the payer, and the remaining words the description
+        let cost = words
+            .iter()
+            .find_map(|w| w.trim_matches(|c: char| !c.is_ascii_digit() && c != '.').parse::<f64>().ok());
+        let Some(cost) = cost else {
+            return sentence_error("Could not find an amount in the sentence (sandbox parser)");
+        };
+        let currency = words.iter().find_map(|w| sentence_currency(w));
+        let paid_at = words.iter().position(|w| w.eq_ignore_ascii_case("paid")).filter(|i| *i > 0);
+        let payer = match paid_at.map(|i| words[i - 1]) {
+            None => self.me,
+            Some(name) if name.eq_ignore_ascii_case("I") => self.me,
+            Some(name) => match self.users.iter().find(|u| u.first_name.eq_ignore_ascii_case(name)) {
+                Some(user) => user.id,
+                None => return sentence_error(&format!("Could not tell who '{}' is (sandbox parser)", name)),
+            },
+        };
+        let description = words
+            .iter()
+            .enumerate()
+            .filter(|(i, w)| {
+                !w.chars().any(|c| c.is_ascii_digit())
+                    && sentence_currency(w).is_none()
+                    && paid_at.is_none_or(|at| *i + 1 != at && *i != at)
+            })
+            .map(|(_, w)| *w)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut request = json!({
+            "cost": amount(cost),
+            "description": description,
+        });
+        if let Some(currency) = currency {
+            request["currency_code"] = json!(currency);
+        }
+        // Split equally among the group, or with the friend, the payer included
+        let group_id = body_i64(body, "group_id");
+        let mut participants = match group_id.and_then(|id| self.groups.iter().find(|g| g.id == id)) {
+            Some(group) => group.member_ids.clone(),
+            None => [Some(self.me), body_i64(body, "friend_id")].into_iter().flatten().collect(),
+        };
+        if !participants.contains(&payer) {
+            participants.push(payer);
+        }
+        if let Some(group_id) = group_id {
+            request["group_id"] = json!(group_id);
+        }
+        let cents = (cost * 100.0).round() as i64;
+        let count = participants.len() as i64;
+        for (n, id) in participants.iter().enumerate() {
+            let owed = cents / count + i64::from((n as i64) < cents % count);
+            let paid = if *id == payer { cost } else { 0.0 };
+            request[format!("users__{}__user_id", n)] = json!(id);
+            request[format!("users__{}__paid_share", n)] = json!(amount(paid));
+            request[format!("users__{}__owed_share", n)] = json!(amount(owed as f64 / 100.0));
+        }
+
+        let created = self.create_expense(&request);
+        let expense = created["expenses"][0].clone();
+        if !body_bool(body, "autosave") {
+            // Preview only: drop the expense again but keep its rendered form
+            let id = expense["id"].as_i64();
+            self.expenses.retain(|e| Some(e.id) != id);
+        }
+
+        json!({
+            "expense": expense,
+            "valid": !expense.is_null(),
+            "confidence": 0.6,
+            "error": created["errors"]["base"][0],
+        })
+    }
+
+    fn update_user(&mut self, id: i64, body: &Value) -> Result<Value, (StatusCode, String)> {
+        let user = self
+            .users
+            .iter_mut()
+            .find(|u| u.id == id)
+            .ok_or_else(|| not_found("user"))?;
+        if let Some(first_name) = body_str(body, "first_name") {
+            user.first_name = first_name;
+        }
+        if let Some(last_name) = body_str(body, "last_name") {
+            user.last_name = Some(last_name);
+        }
+        if let Some(email) = body_str(body, "email") {
+            user.email = email;
+        }
+        if let Some(locale) = body_str(body, "locale") {
+            user.locale = Some(locale);
+        }
+        if let Some(currency) = body_str(body, "default_currency") {
+            user.default_currency = Some(currency);
+        }
+        Ok(serde_json::to_value(&*user).unwrap_or_default())
+    }
+
+    fn create_group(&mut self, body: &Value) -> Value {
+        let id = self.next_id();
+        let mut member_ids = vec![self.me];
+        for i in 0.. {
+            let key = |field: &str| format!("users__{}__{}", i, field);
+            let user_id = match (body_i64(body, &key("user_id")), body_str(body, &key("email"))) {
+                (Some(id), _) => id,
+                (None, Some(email)) => {
+                    self.find_or_invite(&email, body_str(body, &key("first_name")), body_str(body, &key("last_name")))
+                }
+                (None, None) => break,
+            };
+            if !member_ids.contains(&user_id) {
+                member_ids.push(user_id);
+            }
+        }
+        let group = SandboxGroup {
+            id,
+            name: body_str(body, "name").unwrap_or_else(|| "New group".to_string()),
+            group_type: body_str(body, "group_type"),
+            simplify_by_default: body_bool(body, "simplify_by_default"),
+            member_ids,
+            updated_at: Utc::now().to_rfc3339(),
+        };
+        let rendered = self.render_group(&group);
+        self.groups.push(group);
+        json!({ "group": rendered })
+    }
+
+    fn add_user_to_group(&mut self, body: &Value) -> Value {
+        let group_id = body_i64(body, "group_id").unwrap_or_default();
+        let user_id = match body_i64(body, "user_id") {
+            Some(id) => id,
+            None => match body_str(body, "email") {
+                Some(email) => self.find_or_invite(&email, body_str(body, "first_name"), body_str(body, "last_name")),
+                None => return json!({ "success": false, "user": null, "errors": { "base": ["user_id or email is required"] } }),
+            },
+        };
+        let Some(group) = self.groups.iter_mut().find(|g| g.id == group_id) else {
+            return json!({ "success": false, "user": null, "errors": { "base": ["Group not found (sandbox)"] } });
+        };
+        if !group.member_ids.contains(&user_id) {
+            group.member_ids.push(user_id);
+        }
+        json!({ "success": true, "user": self.user(user_id), "errors": {} })
+    }
+}
+
+fn expense_error(message: &str) -> Value {
+    json!({ "expenses": [], "errors": { "base": [message] } })
+}
+
+fn to_balances(totals: HashMap<String, f64>) -> Vec<Balance> {
+    let mut balances: Vec<Balance> = totals
+        .into_iter()
+        .map(|(currency_code, value)| Balance {
+            currency_code,
+            amount: amount(if value.abs() < 0.005 { 0.0 } else { value }),
+        })
+        .collect();
+    balances.sort_by(|a, b| a.currency_code.cmp(&b.currency_code));
+    balances
+}
+
+fn parse_days_ago(date: &str, now: DateTime<Utc>) -> i64 {
+    DateTime::parse_from_rfc3339(date)
+        .map(|d| (now - d.with_timezone(&Utc)).num_days())
+        .unwrap_or(i64::MAX)
+}
+
+fn fixture_currencies() -> Vec<Currency> {
+    [("USD", "$"), ("EUR", "€"), ("ARS", "$"), ("GBP", "£"), ("BRL", "R$"), ("CLP", "$"), ("MXN", "$"), ("JPY", "¥")]
+        .iter()
+        .map(|(code, unit)| Currency {
+            currency_code: code.to_string(),
+            unit: unit.to_string(),
+        })
+        .collect()
+}
+
+// Subset of Splitwise's real category tree, with the same IDs
+fn fixture_categories() -> Vec<Category> {
+    let tree: [FixtureCategory; 7] = [
+        (1, "Utilities", &[(5, "Electricity"), (6, "Heat/gas"), (7, "Water"), (8, "TV/Phone/Internet"), (37, "Trash"), (48, "Cleaning"), (11, "Other")]),
+        (2, "Uncategorized", &[(18, "General")]),
+        (19, "Entertainment", &[(20, "Games"), (21, "Movies"), (22, "Music"), (24, "Sports"), (23, "Other")]),
+        (25, "Food and drink", &[(13, "Dining out"), (12, "Groceries"), (38, "Liquor"), (26, "Other")]),
+        (27, "Home", &[(3, "Rent"), (4, "Mortgage"), (14, "Household supplies"), (16, "Furniture"), (17, "Maintenance"), (42, "Pets"), (39, "Services"), (15, "Electronics"), (28, "Other")]),
+        (40, "Life", &[(43, "Insurance"), (41, "Clothing"), (44, "Gifts"), (45, "Medical expenses"), (50, "Childcare"), (49, "Education"), (47, "Taxes"), (29, "Other")]),
+        (31, "Transportation", &[(32, "Bus/train"), (46, "Car"), (33, "Gas/fuel"), (30, "Hotel"), (9, "Parking"), (34, "Plane"), (36, "Taxi"), (35, "Bicycle"), (10, "Other")]),
+    ];
+
+    tree.iter()
+        .map(|(id, name, subcategories)| Category {
+            id: *id,
+            name: name.to_string(),
+            icon: None,
+            subcategories: Some(
+                subcategories
+                    .iter()
+                    .map(|(id, name)| Subcategory {
+                        id: *id,
+                        name: name.to_string(),
+                        icon: None,
+                    })
+                    .collect(),
+            ),
+        })
+        .collect()
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// MCP sessions on the HTTP transport. initialize starts one and its ID goes back in the
+// Mcp-Session-Id header, which the client sends with every later request. A session unused for
+// longer than the idle timeout is dropped, and requests still carrying its ID get 404 so the
+// client knows to initialize again. Requests without the header are served as before. A
+// session belongs to the account (tenant) that started it; another account's requests can
+// neither use nor end it, and are told it doesn't exist.
+
+pub struct Sessions {
+    idle: Duration,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    // The tenant's name; None for the default account
+    account: Option<String>,
+    last_seen: Instant,
+}
+
+impl Sessions {
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a session for `account` and return its ID.
+    pub fn start(&self, account: Option<String>) -> String {
+        let id = format!("{:032x}", rand::random::<u128>());
+        let session = Session { account, last_seen: Instant::now() };
+        self.sessions.lock().unwrap().insert(id.clone(), session);
+        id
+    }
+
+    /// Record a request of `account` in session `id`; false if there is no such session (any
+    /// more) or it is another account's. An idle one is left for expire() to drop, so it is
+    /// reported there.
+    pub fn touch(&self, id: &str, account: Option<&str>) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(id) {
+            Some(session) if session.account.as_deref() == account => {
+                if session.last_seen.elapsed() > self.idle {
+                    return false;
+                }
+                session.last_seen = Instant::now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// End session `id` at `account`'s request; false if there was no such session, or it is
+    /// another account's.
+    pub fn end(&self, id: &str, account: Option<&str>) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.get(id).is_none_or(|session| session.account.as_deref() != account) {
+            return false;
+        }
+        sessions.remove(id).is_some()
+    }
+
+    /// Drop the sessions idle for longer than the timeout and return their IDs.
+    pub fn expire(&self) -> Vec<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let idle: Vec<String> = sessions
+            .iter()
+            .filter(|(_, session)| session.last_seen.elapsed() > self.idle)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &idle {
+            sessions.remove(id);
+        }
+        idle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_sessions_expire() {
+        let sessions = Sessions::new(Duration::from_millis(200));
+        let kept = sessions.start(None);
+        let idle = sessions.start(None);
+        assert_ne!(kept, idle);
+        assert!(!sessions.touch("unknown", None));
+
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(sessions.touch(&kept, None));
+        std::thread::sleep(Duration::from_millis(120));
+        assert_eq!(sessions.expire(), vec![idle.clone()]);
+        assert!(!sessions.touch(&idle, None));
+
+        assert!(sessions.end(&kept, None));
+        assert!(!sessions.touch(&kept, None));
+    }
+
+    #[test]
+    fn sessions_belong_to_the_account_that_started_them() {
+        let sessions = Sessions::new(Duration::from_secs(60));
+        let ana = sessions.start(Some("ana".to_string()));
+        assert!(!sessions.touch(&ana, Some("marcos")));
+        assert!(!sessions.touch(&ana, None));
+        assert!(!sessions.end(&ana, Some("marcos")));
+        assert!(sessions.touch(&ana, Some("ana")));
+
+        let default = sessions.start(None);
+        assert!(!sessions.end(&default, Some("ana")));
+        assert!(sessions.end(&default, None));
+        assert!(sessions.end(&ana, Some("ana")));
+    }
+}
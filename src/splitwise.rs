@@ -1,41 +1,440 @@
 use anyhow::{Context, Result};
-use reqwest::{Client, Response};
-use serde_json::json;
-use std::collections::HashMap;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
 
+use crate::budget;
+use crate::sandbox::Sandbox;
+use crate::trace;
 use crate::types::*;
+use crate::warnings;
 
 const BASE_URL: &str = "https://secure.splitwise.com/api/v3.0";
+// Identifies this server to Splitwise unless a User-Agent is configured
+pub const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+// Rate-limit headers Splitwise may send, logged with every response that has them
+const RATE_LIMIT_HEADERS: [&str; 4] = ["x-ratelimit-limit", "x-ratelimit-remaining", "x-ratelimit-reset", "retry-after"];
+
+/// An error Splitwise answered with: an error status, or errors in a successful response.
+#[derive(Debug, thiserror::Error)]
+#[error("API error ({status}): {errors}")]
+pub struct UpstreamError {
+    pub status: StatusCode,
+    pub errors: String,
+}
 
 pub struct SplitwiseClient {
     client: Client,
     api_key: String,
+    stats: ClientStats,
+    // When set, requests are answered from in-memory fixtures instead of Splitwise
+    sandbox: Option<Sandbox>,
+    // Send POST bodies as application/x-www-form-urlencoded, the encoding Splitwise documents
+    // for its flattened users__N__field parameters
+    form_bodies: bool,
+    user_agent: String,
+    // Send a fresh X-Request-Id with every request, so a call can be quoted to Splitwise
+    request_ids: bool,
+    // Whose account this is, when one server serves several; labels its metrics. Only the HTTP
+    // transport has tenants
+    #[allow(dead_code)]
+    tenant: Option<String>,
+    inflight: InFlight,
+    pages: PageCache,
+}
+
+// An upstream answer as shared with coalesced callers; transport errors travel as their message
+type Shared = std::result::Result<(StatusCode, String), String>;
+// The leading request's ID and whoever is waiting on it
+type Waiters = (u64, Vec<oneshot::Sender<Shared>>);
+
+// Identical GETs made while one is already in flight (two summaries scanning the same group's
+// expenses at once) wait for its answer instead of calling Splitwise again. Keyed by endpoint
+// and query. A write detaches the requests in flight, so nothing that starts after it can be
+// answered with what was read before it.
+#[derive(Default)]
+struct InFlight {
+    waiting: Mutex<HashMap<String, Waiters>>,
+    next_id: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl InFlight {
+    async fn run<F>(&self, key: String, fetch: F) -> Result<(StatusCode, String)>
+    where
+        F: std::future::Future<Output = Result<(StatusCode, String)>>,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let waiter = {
+            let mut waiting = self.waiting.lock().unwrap();
+            match waiting.get_mut(&key) {
+                Some((_, waiters)) => {
+                    let (sender, receiver) = oneshot::channel();
+                    waiters.push(sender);
+                    Some(receiver)
+                }
+                None => {
+                    waiting.insert(key.clone(), (id, Vec::new()));
+                    None
+                }
+            }
+        };
+        if let Some(receiver) = waiter {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+            trace::record_cache_hit(&format!("in-flight:{}", key));
+            match receiver.await {
+                Ok(shared) => return shared.map_err(anyhow::Error::msg),
+                // The leading request was cancelled before it got an answer
+                Err(_) => return fetch.await,
+            }
+        }
+
+        // Dropped when the request finishes or is cancelled, releasing the key either way
+        struct Lead<'a> {
+            inflight: &'a InFlight,
+            key: String,
+            id: u64,
+        }
+        impl Lead<'_> {
+            fn waiters(&self) -> Vec<oneshot::Sender<Shared>> {
+                let mut waiting = self.inflight.waiting.lock().unwrap();
+                match waiting.get(&self.key) {
+                    Some((id, _)) if *id == self.id => waiting.remove(&self.key).map(|(_, w)| w).unwrap_or_default(),
+                    _ => Vec::new(),
+                }
+            }
+        }
+        impl Drop for Lead<'_> {
+            fn drop(&mut self) {
+                self.waiters();
+            }
+        }
+
+        let lead = Lead { inflight: self, key, id };
+        let result = fetch.await;
+        for waiter in lead.waiters() {
+            let _ = waiter.send(result.as_ref().map(Clone::clone).map_err(|e| format!("{:#}", e)));
+        }
+        result
+    }
+
+    fn detach_all(&self) {
+        self.waiting.lock().unwrap().clear();
+    }
+}
+
+// Pages of get_expenses kept for a short while (EXPENSE_CACHE_TTL_SECS), so a conversation that
+// runs several summaries over the same month downloads its pages once. Keyed by the query with
+// its parameters sorted. Before a cached page is served, one updated_after query (at most every
+// PAGE_CHECK_INTERVAL) asks Splitwise whether any expense changed since the pages were fetched,
+// e.g. in the Splitwise app; if one did, the cache is emptied. Writes through this server empty
+// it as well.
+const PAGE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Allowance for our clock running ahead of Splitwise's in the updated_after stamp
+const PAGE_CLOCK_SKEW_SECS: i64 = 10;
+const MAX_CACHED_PAGES: usize = 100;
+
+#[derive(Default)]
+struct PageCache {
+    // Zero disables the cache
+    ttl: Duration,
+    pages: Mutex<HashMap<String, (Instant, String)>>,
+    // When the cached pages were last known to be current, and as of which upstream time
+    checked: Mutex<Option<(Instant, chrono::DateTime<chrono::Utc>)>>,
+    // Bumped by every clear, so a page read before a write isn't stored after it
+    generation: AtomicU64,
+    hits: AtomicU64,
+}
+
+impl PageCache {
+    fn get(&self, key: &str) -> Option<String> {
+        match self.pages.lock().unwrap().get(key) {
+            Some((fetched_at, text)) if fetched_at.elapsed() <= self.ttl => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    fn put(&self, key: String, text: String, generation: u64, started: chrono::DateTime<chrono::Utc>) {
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let mut pages = self.pages.lock().unwrap();
+        pages.retain(|_, (fetched_at, _)| fetched_at.elapsed() <= self.ttl);
+        if pages.len() >= MAX_CACHED_PAGES {
+            let oldest = pages.iter().min_by_key(|(_, (fetched_at, _))| *fetched_at).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                pages.remove(&oldest);
+            }
+        }
+        pages.insert(key, (Instant::now(), text));
+        // The first page fetched sets the stamp later checks ask about
+        self.checked.lock().unwrap().get_or_insert((Instant::now(), started));
+    }
+
+    fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.pages.lock().unwrap().clear();
+        *self.checked.lock().unwrap() = None;
+    }
+}
+
+// Upstream usage counters reported by the server_status tool
+#[derive(Default)]
+struct ClientStats {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    // Timestamps of calls made in the last hour, for the rate-limit estimate
+    recent_calls: Mutex<VecDeque<Instant>>,
+    last_error: Mutex<Option<(chrono::DateTime<chrono::Utc>, String)>>,
+    // Last value of a rate-limit header, if Splitwise sent one
+    rate_limit_remaining: Mutex<Option<String>>,
+    // Per "METHOD /endpoint" counters, with numeric IDs in the path folded into ":id"
+    endpoints: Mutex<BTreeMap<String, EndpointStats>>,
+}
+
+// Upper bounds (ms) of the latency histogram buckets; slower calls only count in +Inf
+const LATENCY_BUCKETS_MS: [u64; 8] = [50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Default)]
+struct EndpointStats {
+    calls: u64,
+    errors: u64,
+    total_ms: u64,
+    max_ms: u64,
+    // Calls per bucket of LATENCY_BUCKETS_MS, not cumulative
+    buckets: [u64; LATENCY_BUCKETS_MS.len()],
+}
+
+// Read by server_status and /metrics, which the test-api binary never calls
+#[allow(dead_code)]
+impl EndpointStats {
+    fn summary(&self) -> Value {
+        json!({
+            "calls": self.calls,
+            "errors": self.errors,
+            "avg_ms": self.total_ms.checked_div(self.calls).unwrap_or(0),
+            "max_ms": self.max_ms,
+            "p95_ms": self.quantile_ms(0.95),
+        })
+    }
+
+    // Upper bound of the bucket holding the given quantile; None when it is beyond the last one
+    fn quantile_ms(&self, q: f64) -> Option<u64> {
+        let target = (self.calls as f64 * q).ceil() as u64;
+        let mut seen = 0;
+        for (bound, count) in LATENCY_BUCKETS_MS.iter().zip(self.buckets) {
+            seen += count;
+            if seen >= target.max(1) {
+                return Some(*bound);
+            }
+        }
+        None
+    }
 }
 
 impl SplitwiseClient {
     pub fn new(api_key: String) -> Result<Self> {
-        let client = Client::builder()
-            .default_headers({
-                let mut headers = reqwest::header::HeaderMap::new();
-                headers.insert(
-                    reqwest::header::AUTHORIZATION,
-                    format!("Bearer {}", api_key).parse()?,
-                );
-                headers.insert(
-                    reqwest::header::CONTENT_TYPE,
-                    "application/json".parse()?,
-                );
-                headers
-            })
-            .build()?;
+        // Credentials go on each request rather than into the client, so its connection pool
+        // can be shared with other outbound calls (exchange rates)
+        Ok(Self {
+            client: Client::builder().build()?,
+            api_key,
+            stats: ClientStats::default(),
+            sandbox: None,
+            form_bodies: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_ids: false,
+            tenant: None,
+            inflight: InFlight::default(),
+            pages: PageCache::default(),
+        })
+    }
+
+    /// Use `client` (and its connection pool) for every request.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The underlying HTTP client, for other outbound calls that should share its pool.
+    pub fn http_client(&self) -> Client {
+        self.client.clone()
+    }
+
+    pub fn with_form_bodies(mut self, enabled: bool) -> Self {
+        self.form_bodies = enabled;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: String) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
 
-        Ok(Self { client, api_key })
+    pub fn with_request_ids(mut self, enabled: bool) -> Self {
+        self.request_ids = enabled;
+        self
+    }
+
+    /// Reuse get_expenses pages for up to `ttl`; zero turns the cache off.
+    pub fn with_expense_cache(mut self, ttl: Duration) -> Self {
+        self.pages.ttl = ttl;
+        self
+    }
+
+    /// Add a tenant="name" label to this client's metrics.
+    #[allow(dead_code)]
+    pub fn with_tenant(mut self, name: String) -> Self {
+        self.tenant = Some(name);
+        self
+    }
+
+    // Client backed by fixture data; no API key or network access needed
+    pub fn sandbox() -> Self {
+        Self {
+            client: Client::new(),
+            api_key: String::new(),
+            stats: ClientStats::default(),
+            sandbox: Some(Sandbox::new()),
+            form_bodies: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_ids: false,
+            tenant: None,
+            inflight: InFlight::default(),
+            pages: PageCache::default(),
+        }
+    }
+
+    pub fn is_sandbox(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    #[cfg(test)]
+    pub fn sandbox_state(&self) -> Option<&Sandbox> {
+        self.sandbox.as_ref()
+    }
+
+    pub fn stats(&self) -> Value {
+        let calls_last_hour = {
+            let mut recent = self.stats.recent_calls.lock().unwrap();
+            while recent
+                .front()
+                .is_some_and(|t| t.elapsed() > Duration::from_secs(3600))
+            {
+                recent.pop_front();
+            }
+            recent.len()
+        };
+        let last_error = self.stats.last_error.lock().unwrap().clone();
+        let endpoints: serde_json::Map<String, Value> = self
+            .stats
+            .endpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, stats)| (key.clone(), stats.summary()))
+            .collect();
+
+        json!({
+            "calls": self.stats.calls.load(Ordering::Relaxed),
+            "errors": self.stats.errors.load(Ordering::Relaxed),
+            "calls_last_hour": calls_last_hour,
+            // Requests answered by an identical one already in flight, without a call
+            "coalesced": self.inflight.coalesced.load(Ordering::Relaxed),
+            "expense_cache": {
+                "enabled": !self.pages.ttl.is_zero(),
+                "pages": self.pages.pages.lock().unwrap().len(),
+                "hits": self.pages.hits.load(Ordering::Relaxed),
+            },
+            "rate_limit_remaining": *self.stats.rate_limit_remaining.lock().unwrap(),
+            "endpoints": endpoints,
+            "last_error": last_error.map(|(at, message)| json!({
+                "at": at.to_rfc3339(),
+                "message": message,
+            })),
+        })
+    }
+
+    fn record_call(&self) {
+        self.stats.calls.fetch_add(1, Ordering::Relaxed);
+        self.stats.recent_calls.lock().unwrap().push_back(Instant::now());
+    }
+
+    // One attempt against an endpoint; status 0 means no response arrived
+    fn record_endpoint(&self, method: &Method, endpoint: &str, status: u16, latency: Duration) {
+        let key = format!("{} {}", method, endpoint_pattern(endpoint));
+        let ms = latency.as_millis() as u64;
+        let mut endpoints = self.stats.endpoints.lock().unwrap();
+        let stats = endpoints.entry(key).or_default();
+        stats.calls += 1;
+        if status == 0 || status >= 400 {
+            stats.errors += 1;
+        }
+        stats.total_ms += ms;
+        stats.max_ms = stats.max_ms.max(ms);
+        if let Some(bucket) = LATENCY_BUCKETS_MS.iter().position(|bound| ms <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+    }
+
+    /// Per-endpoint counters and latency histograms in the Prometheus text format.
+    pub fn metrics(&self) -> String {
+        Self::metrics_of(&[self])
+    }
+
+    /// Metrics of several clients in one exposition, each metric family listed once.
+    pub fn metrics_of(clients: &[&SplitwiseClient]) -> String {
+        let endpoints: Vec<_> = clients.iter().map(|c| (c.tenant.as_deref(), c.stats.endpoints.lock().unwrap())).collect();
+        let series = || {
+            endpoints
+                .iter()
+                .flat_map(|(tenant, endpoints)| endpoints.iter().map(move |(key, stats)| (labels(*tenant, key), stats)))
+        };
+        let mut out = vec![
+            "# HELP splitwise_api_requests_total Splitwise API requests".to_string(),
+            "# TYPE splitwise_api_requests_total counter".to_string(),
+        ];
+        for (labels, stats) in series() {
+            out.push(format!("splitwise_api_requests_total{{{}}} {}", labels, stats.calls));
+        }
+        out.push("# HELP splitwise_api_errors_total Splitwise API requests that failed or returned an error status".to_string());
+        out.push("# TYPE splitwise_api_errors_total counter".to_string());
+        for (labels, stats) in series() {
+            out.push(format!("splitwise_api_errors_total{{{}}} {}", labels, stats.errors));
+        }
+        out.push("# HELP splitwise_api_request_duration_seconds Splitwise API request latency".to_string());
+        out.push("# TYPE splitwise_api_request_duration_seconds histogram".to_string());
+        for (labels, stats) in series() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_MS.iter().zip(stats.buckets) {
+                cumulative += count;
+                out.push(format!(
+                    "splitwise_api_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels,
+                    *bound as f64 / 1000.0,
+                    cumulative
+                ));
+            }
+            out.push(format!("splitwise_api_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, stats.calls));
+            out.push(format!("splitwise_api_request_duration_seconds_sum{{{}}} {}", labels, stats.total_ms as f64 / 1000.0));
+            out.push(format!("splitwise_api_request_duration_seconds_count{{{}}} {}", labels, stats.calls));
+        }
+        out.join("\n") + "\n"
+    }
+
+    fn record_error(&self, message: String) {
+        warn!("Splitwise request failed: {}", message);
+        self.stats.errors.fetch_add(1, Ordering::Relaxed);
+        *self.stats.last_error.lock().unwrap() = Some((chrono::Utc::now(), message));
     }
 
     async fn get<T: for<'de> serde::Deserialize<'de>>(&self, endpoint: &str) -> Result<T> {
-        let url = format!("{}{}", BASE_URL, endpoint);
-        let response = self.client.get(&url).send().await?;
-        self.handle_response(response).await
+        self.request(Method::GET, endpoint, &[], None).await
     }
 
     async fn get_with_params<T: for<'de> serde::Deserialize<'de>>(
@@ -43,9 +442,7 @@ impl SplitwiseClient {
         endpoint: &str,
         params: &[(&str, String)],
     ) -> Result<T> {
-        let url = format!("{}{}", BASE_URL, endpoint);
-        let response = self.client.get(&url).query(params).send().await?;
-        self.handle_response(response).await
+        self.request(Method::GET, endpoint, params, None).await
     }
 
     async fn post<T: for<'de> serde::Deserialize<'de>>(
@@ -53,31 +450,172 @@ impl SplitwiseClient {
         endpoint: &str,
         body: serde_json::Value,
     ) -> Result<T> {
-        let url = format!("{}{}", BASE_URL, endpoint);
-        let response = self.client.post(&url).json(&body).send().await?;
-        self.handle_response(response).await
+        self.request(Method::POST, endpoint, &[], Some(body)).await
     }
 
     async fn delete<T: for<'de> serde::Deserialize<'de>>(&self, endpoint: &str) -> Result<T> {
-        let url = format!("{}{}", BASE_URL, endpoint);
-        let response = self.client.delete(&url).send().await?;
-        self.handle_response(response).await
+        self.request(Method::DELETE, endpoint, &[], None).await
     }
 
-    async fn handle_response<T: for<'de> serde::Deserialize<'de>>(
+    async fn request<T: for<'de> serde::Deserialize<'de>>(
         &self,
-        response: Response,
+        method: Method,
+        endpoint: &str,
+        params: &[(&str, String)],
+        body: Option<Value>,
     ) -> Result<T> {
-        let status = response.status();
-        let text = response.text().await?;
+        let (status, text) = self.fetch(method, endpoint, params, body.as_ref()).await?;
+        self.parse_body(status, text)
+    }
+
+    // Performs one logical call, sharing a GET with an identical one already in flight
+    async fn fetch(
+        &self,
+        method: Method,
+        endpoint: &str,
+        params: &[(&str, String)],
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, String)> {
+        // Query parameters are part of the traced label so paging shows up in the breakdown
+        let label = if params.is_empty() {
+            endpoint.to_string()
+        } else {
+            let query: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            format!("{}?{}", endpoint, query.join("&"))
+        };
+        let is_get = method == Method::GET;
+        // Boxed so the request state isn't copied into every caller's future
+        let upstream = Box::pin(self.fetch_upstream(method, endpoint, &label, params, body));
+        if !is_get {
+            self.inflight.detach_all();
+            self.pages.clear();
+            return upstream.await;
+        }
+        self.inflight.run(label.clone(), upstream).await
+    }
+
+    async fn fetch_upstream(
+        &self,
+        method: Method,
+        endpoint: &str,
+        label: &str,
+        params: &[(&str, String)],
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, String)> {
+        if let Some(ref sandbox) = self.sandbox {
+            let started = Instant::now();
+            self.record_call();
+            let latency = sandbox.latency_ms.load(std::sync::atomic::Ordering::SeqCst);
+            if latency > 0 {
+                tokio::time::sleep(Duration::from_millis(latency)).await;
+            }
+            let (status, text) = sandbox.handle(method.clone(), endpoint, params, body);
+            trace::record_call(method.as_str(), label, status.as_u16(), started.elapsed());
+            budget::record_call();
+            self.record_endpoint(&method, endpoint, status.as_u16(), started.elapsed());
+            return Ok((status, text));
+        }
+
+        let url = format!("{}{}", BASE_URL, endpoint);
+        let mut request: RequestBuilder = self
+            .client
+            .request(method.clone(), &url)
+            .bearer_auth(&self.api_key)
+            .header(reqwest::header::USER_AGENT, &self.user_agent);
+        let request_id = self.request_ids.then(|| format!("{:016x}", rand::random::<u64>()));
+        if let Some(ref id) = request_id {
+            request = request.header("X-Request-Id", id);
+        }
+        if !params.is_empty() {
+            request = request.query(params);
+        }
+        if let Some(body) = body {
+            request = if self.form_bodies {
+                request.form(&form_fields(body))
+            } else {
+                request.json(body)
+            };
+        }
+
+        let started = Instant::now();
+        self.record_call();
+        match request.send().await {
+            Ok(response) => {
+                let status = response.status();
+                trace::record_call(method.as_str(), label, status.as_u16(), started.elapsed());
+                budget::record_call();
+                self.record_endpoint(&method, endpoint, status.as_u16(), started.elapsed());
+                debug!(
+                    "Splitwise {} {} -> {} in {:?}{}",
+                    method,
+                    label,
+                    status,
+                    started.elapsed(),
+                    request_id.as_ref().map(|id| format!(" (request id {})", id)).unwrap_or_default()
+                );
+                self.record_rate_limit(&response);
+                let text = response.text().await?;
+                Ok((status, text))
+            }
+            Err(e) => {
+                trace::record_call(method.as_str(), label, 0, started.elapsed());
+                budget::record_call();
+                self.record_endpoint(&method, endpoint, 0, started.elapsed());
+                self.record_error(e.to_string());
+                match request_id {
+                    Some(id) => Err(anyhow::Error::new(e).context(format!("request id {}", id))),
+                    None => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    fn record_rate_limit(&self, response: &Response) {
+        let limits = rate_limit_headers(response.headers());
+        if limits.is_empty() {
+            return;
+        }
+        let summary: Vec<String> = limits.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            warn!("Splitwise rate limit reached: {}", summary.join(", "));
+        } else {
+            debug!("Splitwise rate limit: {}", summary.join(", "));
+        }
+        if let Some((_, remaining)) = limits.iter().find(|(name, _)| *name == "x-ratelimit-remaining") {
+            *self.stats.rate_limit_remaining.lock().unwrap() = Some(remaining.clone());
+        }
+    }
 
+    fn parse_body<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        status: StatusCode,
+        text: String,
+    ) -> Result<T> {
         if status.is_success() {
-            serde_json::from_str(&text).with_context(|| {
+            let parsed = serde_json::from_str::<Value>(&text).with_context(|| {
                 format!("Failed to parse response. Status: {}, Length: {}, First 500 chars: {}", 
                     status, 
                     text.len(),
                     &text.chars().take(500).collect::<String>())
-            })
+            });
+            let value = match parsed {
+                Ok(value) => value,
+                Err(e) => {
+                    self.record_error(e.to_string());
+                    return Err(e);
+                }
+            };
+            // Many endpoints answer a rejected request with 200 OK and a populated `errors`
+            if let Some(errors) = envelope_errors(&value) {
+                let error = UpstreamError { status, errors };
+                self.record_error(error.to_string());
+                return Err(error.into());
+            }
+            let parsed = serde_json::from_value(value).context("Unexpected response shape");
+            if let Err(ref e) = parsed {
+                self.record_error(e.to_string());
+            }
+            parsed
         } else {
             let error: ApiError = serde_json::from_str(&text).unwrap_or_else(|_| ApiError {
                 errors: {
@@ -86,7 +624,9 @@ impl SplitwiseClient {
                     map
                 },
             });
-            anyhow::bail!("API error ({}): {:?}", status, error.errors)
+            let error = UpstreamError { status, errors: format!("{:?}", error.errors) };
+            self.record_error(error.to_string());
+            Err(error.into())
         }
     }
 
@@ -120,10 +660,10 @@ impl SplitwiseClient {
     pub async fn get_groups(&self) -> Result<Vec<Group>> {
         #[derive(serde::Deserialize)]
         struct Response {
-            groups: Vec<Group>,
+            groups: Vec<Value>,
         }
         let response: Response = self.get("/get_groups").await?;
-        Ok(response.groups)
+        Ok(readable_items("/get_groups", response.groups))
     }
 
     pub async fn get_group(&self, id: i64) -> Result<Group> {
@@ -200,14 +740,13 @@ impl SplitwiseClient {
         struct Response {
             success: bool,
             user: Option<User>,
-            errors: Option<serde_json::Value>,
         }
         let response: Response = self.post("/add_user_to_group", body).await?;
         
         if response.success {
             response.user.context("User not returned despite success")
         } else {
-            anyhow::bail!("Failed to add user to group: {:?}", response.errors)
+            anyhow::bail!("Failed to add user to group")
         }
     }
 
@@ -223,6 +762,12 @@ impl SplitwiseClient {
 
     // Expense endpoints
     pub async fn get_expenses(&self, params: ListExpensesParams) -> Result<Vec<Expense>> {
+        Ok(self.get_expense_page(params).await?.into_iter().flatten().collect())
+    }
+
+    /// Every item of an expense page in Splitwise's order, None where an item couldn't be read
+    /// (recorded as a warning), so callers paging by offset count what Splitwise returned.
+    pub async fn get_expense_page(&self, params: ListExpensesParams) -> Result<Vec<Option<Expense>>> {
         let mut query_params = vec![];
         
         if let Some(group_id) = params.group_id {
@@ -252,16 +797,59 @@ impl SplitwiseClient {
 
         #[derive(serde::Deserialize)]
         struct Response {
-            expenses: Vec<Expense>,
+            expenses: Vec<Value>,
         }
-        
-        let response: Response = if query_params.is_empty() {
-            self.get("/get_expenses").await?
-        } else {
-            self.get_with_params("/get_expenses", &query_params).await?
+
+        if self.pages.ttl.is_zero() {
+            let response: Response = self.get_with_params("/get_expenses", &query_params).await?;
+            return Ok(item_slots("/get_expenses", response.expenses));
+        }
+        let mut sorted: Vec<String> = query_params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        sorted.sort();
+        let key = sorted.join("&");
+        if let Some(text) = self.cached_page(&key).await? {
+            let response: Response = self.parse_body(StatusCode::OK, text)?;
+            return Ok(item_slots("/get_expenses", response.expenses));
+        }
+        let generation = self.pages.generation.load(Ordering::SeqCst);
+        let started = chrono::Utc::now();
+        let (status, text) = self.fetch(Method::GET, "/get_expenses", &query_params, None).await?;
+        let response: Response = self.parse_body(status, text.clone())?;
+        self.pages.put(key, text, generation, started);
+        Ok(item_slots("/get_expenses", response.expenses))
+    }
+
+    // A cached page for `key`, once Splitwise confirms no expense changed since it was fetched
+    async fn cached_page(&self, key: &str) -> Result<Option<String>> {
+        let Some(text) = self.pages.get(key) else {
+            return Ok(None);
         };
-        
-        Ok(response.expenses)
+        let since = match *self.pages.checked.lock().unwrap() {
+            Some((checked_at, _)) if checked_at.elapsed() < PAGE_CHECK_INTERVAL => None,
+            Some((_, since)) => Some(since),
+            None => return Ok(None),
+        };
+        if let Some(since) = since {
+            let generation = self.pages.generation.load(Ordering::SeqCst);
+            let started = chrono::Utc::now();
+            let stamp = (since - chrono::Duration::seconds(PAGE_CLOCK_SKEW_SECS))
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let changed: Value = self
+                .get_with_params("/get_expenses", &[("updated_after", stamp), ("limit", "1".to_string())])
+                .await?;
+            if changed["expenses"].as_array().is_some_and(|expenses| !expenses.is_empty()) {
+                debug!("Expenses changed upstream; dropping cached pages");
+                self.pages.clear();
+                return Ok(None);
+            }
+            if self.pages.generation.load(Ordering::SeqCst) != generation {
+                return Ok(None);
+            }
+            *self.pages.checked.lock().unwrap() = Some((Instant::now(), started));
+        }
+        self.pages.hits.fetch_add(1, Ordering::Relaxed);
+        trace::record_cache_hit(&format!("expenses:{}", key));
+        Ok(Some(text))
     }
 
     pub async fn get_expense(&self, id: i64) -> Result<Expense> {
@@ -273,6 +861,17 @@ impl SplitwiseClient {
         Ok(response.expense)
     }
 
+    pub async fn get_comments(&self, expense_id: i64) -> Result<Vec<Comment>> {
+        #[derive(serde::Deserialize)]
+        struct Response {
+            comments: Vec<Comment>,
+        }
+        let response: Response = self
+            .get_with_params("/get_comments", &[("expense_id", expense_id.to_string())])
+            .await?;
+        Ok(response.comments)
+    }
+
     pub async fn create_expense(&self, request: CreateExpenseRequest) -> Result<Vec<Expense>> {
         let mut body = json!({
             "cost": request.cost,
@@ -327,16 +926,8 @@ impl SplitwiseClient {
         #[derive(serde::Deserialize)]
         struct Response {
             expenses: Vec<Expense>,
-            errors: Option<serde_json::Value>,
         }
         let response: Response = self.post("/create_expense", body).await?;
-        
-        if let Some(errors) = response.errors {
-            if !errors.is_null() && errors.as_object().map_or(false, |o| !o.is_empty()) {
-                anyhow::bail!("Failed to create expense: {:?}", errors)
-            }
-        }
-        
         Ok(response.expenses)
     }
 
@@ -364,6 +955,12 @@ impl SplitwiseClient {
         if let Some(date) = request.date {
             body["date"] = json!(date);
         }
+        if let Some(details) = request.details {
+            body["details"] = json!(details);
+        }
+        if let Some(group_id) = request.group_id {
+            body["group_id"] = json!(group_id);
+        }
         
         // Handle split information - only send users array, not split_equally flag
         // The split_equally parameter is not supported by update_expense endpoint
@@ -389,26 +986,39 @@ impl SplitwiseClient {
         #[derive(serde::Deserialize)]
         struct Response {
             expenses: Vec<Expense>,
-            errors: Option<serde_json::Value>,
         }
         let response: Response = self
             .post(&format!("/update_expense/{}", id), body)
             .await?;
         
-        if let Some(errors) = response.errors {
-            if !errors.is_null() && errors.as_object().map_or(false, |o| !o.is_empty()) {
-                anyhow::bail!("Failed to update expense: {:?}", errors)
-            }
-        }
-        
         Ok(response.expenses)
     }
 
+    pub async fn parse_sentence(
+        &self,
+        input: &str,
+        group_id: Option<i64>,
+        friend_id: Option<i64>,
+        autosave: bool,
+    ) -> Result<ParsedSentence> {
+        let mut body = json!({
+            "input": input,
+            "autosave": autosave,
+        });
+        if let Some(group_id) = group_id {
+            body["group_id"] = json!(group_id);
+        }
+        if let Some(friend_id) = friend_id {
+            body["friend_id"] = json!(friend_id);
+        }
+
+        self.post("/parse_sentence", body).await
+    }
+
     pub async fn delete_expense(&self, id: i64) -> Result<bool> {
         #[derive(serde::Deserialize)]
         struct DeleteResponse {
             success: bool,
-            errors: serde_json::Value, // Can be {} or object with actual errors
         }
         let response: DeleteResponse = self
             .post(&format!("/delete_expense/{}", id), json!({}))
@@ -420,10 +1030,10 @@ impl SplitwiseClient {
     pub async fn get_friends(&self) -> Result<Vec<Friend>> {
         #[derive(serde::Deserialize)]
         struct Response {
-            friends: Vec<Friend>,
+            friends: Vec<Value>,
         }
         let response: Response = self.get("/get_friends").await?;
-        Ok(response.friends)
+        Ok(readable_items("/get_friends", response.friends))
     }
 
     pub async fn get_friend(&self, id: i64) -> Result<Friend> {
@@ -448,6 +1058,21 @@ impl SplitwiseClient {
         Ok(response.friends)
     }
 
+    pub async fn delete_friend(&self, id: i64) -> Result<bool> {
+        #[derive(serde::Deserialize)]
+        struct DeleteResponse {
+            success: bool,
+        }
+        let response: DeleteResponse = self
+            .post(&format!("/delete_friend/{}", id), json!({}))
+            .await?;
+
+        if !response.success {
+            anyhow::bail!("Failed to remove friend")
+        }
+        Ok(response.success)
+    }
+
     // Utility endpoints
     pub async fn get_currencies(&self) -> Result<Vec<Currency>> {
         #[derive(serde::Deserialize)]
@@ -466,4 +1091,267 @@ impl SplitwiseClient {
         let response: Response = self.get("/get_categories").await?;
         Ok(response.categories)
     }
-}
\ No newline at end of file
+}
+// Messages from a response's `errors`, which Splitwise sends as {"base": ["..."]}, a
+// per-field object, or a plain list; None when it is missing or empty
+fn envelope_errors(value: &Value) -> Option<String> {
+    fn messages(value: &Value) -> Vec<String> {
+        match value {
+            Value::Null => Vec::new(),
+            Value::String(s) if s.trim().is_empty() => Vec::new(),
+            Value::String(s) => vec![s.clone()],
+            Value::Array(items) => items.iter().flat_map(messages).collect(),
+            Value::Object(fields) => fields
+                .iter()
+                .flat_map(|(field, inner)| {
+                    messages(inner).into_iter().map(move |m| match field.as_str() {
+                        "base" => m,
+                        _ => format!("{}: {}", field, m),
+                    })
+                })
+                .collect(),
+            other => vec![other.to_string()],
+        }
+    }
+    let found = messages(value.get("errors")?);
+    (!found.is_empty()).then(|| found.join("; "))
+}
+
+// A flat JSON body as form fields. Nulls are left out, and anything nested (which the
+// endpoints here never send) is passed as JSON text
+fn form_fields(body: &Value) -> Vec<(String, String)> {
+    let Some(fields) = body.as_object() else {
+        return Vec::new();
+    };
+    fields
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (key.clone(), text)
+        })
+        .collect()
+}
+
+// "/get_expense/123" -> "/get_expense/:id", so each resource isn't counted apart
+fn endpoint_pattern(endpoint: &str) -> String {
+    endpoint
+        .split('/')
+        .map(|part| if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) { ":id" } else { part })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// "GET /get_expense/:id" -> method="GET",endpoint="/get_expense/:id"
+#[allow(dead_code)]
+fn labels(tenant: Option<&str>, key: &str) -> String {
+    let (method, endpoint) = key.split_once(' ').unwrap_or(("", key));
+    match tenant {
+        Some(tenant) => format!("tenant=\"{}\",method=\"{}\",endpoint=\"{}\"", tenant, method, endpoint),
+        None => format!("method=\"{}\",endpoint=\"{}\"", method, endpoint),
+    }
+}
+
+fn rate_limit_headers(headers: &reqwest::header::HeaderMap) -> Vec<(&'static str, String)> {
+    RATE_LIMIT_HEADERS
+        .iter()
+        .filter_map(|name| Some((*name, headers.get(*name)?.to_str().ok()?.to_string())))
+        .collect()
+}
+
+// Parse list items one by one, so a single item in an unexpected shape is skipped with a
+// warning (carrying its raw JSON) rather than failing the whole list
+fn readable_items<T: serde::de::DeserializeOwned>(endpoint: &str, items: Vec<Value>) -> Vec<T> {
+    item_slots(endpoint, items).into_iter().flatten().collect()
+}
+
+// Each item parsed in place, None (and a warning) for the ones that don't fit
+fn item_slots<T: serde::de::DeserializeOwned>(endpoint: &str, items: Vec<Value>) -> Vec<Option<T>> {
+    items
+        .into_iter()
+        .map(|item| match T::deserialize(&item) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                warnings::record(endpoint, &e.to_string(), &item);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_errors_are_found_in_every_shape() {
+        assert_eq!(envelope_errors(&json!({ "expenses": [], "errors": {} })), None);
+        assert_eq!(envelope_errors(&json!({ "success": true, "errors": [] })), None);
+        assert_eq!(envelope_errors(&json!({ "friends": [] })), None);
+        assert_eq!(
+            envelope_errors(&json!({ "errors": { "base": ["Invalid user"], "cost": ["must be positive"] } })),
+            Some("Invalid user; cost: must be positive".to_string())
+        );
+        assert_eq!(
+            envelope_errors(&json!({ "success": false, "errors": ["Not allowed"] })),
+            Some("Not allowed".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn unreadable_list_items_become_warnings() {
+        let items = vec![
+            json!({ "id": 1, "cost": 12.5, "description": null, "comments_count": null, "users": null }),
+            json!({ "id": 2, "description": ["not", "text"] }),
+            // Without an id it isn't read as expense 0
+            json!({ "description": "Lost id", "cost": "5.00" }),
+        ];
+        let (expenses, skipped) =
+            warnings::collect(async { readable_items::<Expense>("/get_expenses", items.clone()) }).await;
+        assert_eq!(expenses.len(), 1);
+        assert_eq!(expenses[0].cost, "12.5");
+        assert_eq!(expenses[0].comments_count, 0);
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0]["id"], 2);
+        assert_eq!(skipped[0]["raw"]["description"], json!(["not", "text"]));
+        assert_eq!(skipped[1]["raw"]["description"], "Lost id");
+
+        // Paging needs every item's place, readable or not
+        let (slots, _) = warnings::collect(async { item_slots::<Expense>("/get_expenses", items) }).await;
+        assert_eq!(slots.iter().map(Option::is_some).collect::<Vec<_>>(), vec![true, false, false]);
+    }
+
+    #[tokio::test]
+    async fn rejected_mutations_surface_their_errors() {
+        let client = SplitwiseClient::sandbox();
+        let error = client
+            .add_user_to_group(999_999, GroupUserInput { user_id: Some(101), first_name: None, last_name: None, email: None })
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Group not found"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn metrics_count_calls_per_endpoint() {
+        let client = SplitwiseClient::sandbox();
+        client.get_expense(1).await.ok();
+        client.get_expense(2).await.ok();
+        client.get_groups().await.unwrap();
+
+        let endpoints = &client.stats()["endpoints"];
+        assert_eq!(endpoints["GET /get_expense/:id"]["calls"], 2);
+        assert_eq!(endpoints["GET /get_groups"]["calls"], 1);
+        let metrics = client.metrics();
+        assert!(metrics.contains("splitwise_api_requests_total{method=\"GET\",endpoint=\"/get_expense/:id\"} 2"));
+        assert!(metrics.contains("splitwise_api_request_duration_seconds_count{method=\"GET\",endpoint=\"/get_groups\"} 1"));
+
+        let ana = SplitwiseClient::sandbox().with_tenant("ana".to_string());
+        ana.get_groups().await.unwrap();
+        let metrics = SplitwiseClient::metrics_of(&[&client, &ana]);
+        assert_eq!(metrics.matches("# TYPE splitwise_api_requests_total").count(), 1);
+        assert!(metrics.contains("splitwise_api_requests_total{method=\"GET\",endpoint=\"/get_groups\"} 1"));
+        assert!(metrics.contains("splitwise_api_requests_total{tenant=\"ana\",method=\"GET\",endpoint=\"/get_groups\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn identical_requests_in_flight_share_one_call() {
+        let inflight = InFlight::default();
+        let calls = AtomicU64::new(0);
+        let fetch = |answer: &'static str| {
+            let calls = &calls;
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok((StatusCode::OK, answer.to_string()))
+            }
+        };
+        let (a, b, c) = tokio::join!(
+            inflight.run("/get_expenses?group_id=1".to_string(), fetch("first")),
+            inflight.run("/get_expenses?group_id=1".to_string(), fetch("second")),
+            inflight.run("/get_expenses?group_id=2".to_string(), fetch("other")),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(a.unwrap().1, "first");
+        assert_eq!(b.unwrap().1, "first");
+        assert_eq!(c.unwrap().1, "other");
+        assert_eq!(inflight.coalesced.load(Ordering::Relaxed), 1);
+        assert!(inflight.waiting.lock().unwrap().is_empty());
+
+        // A cancelled leader leaves its followers to fetch for themselves
+        let leader = inflight.run("/get_groups".to_string(), fetch("cancelled"));
+        let follower = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            inflight.run("/get_groups".to_string(), fetch("own")).await
+        };
+        let (_, answer) = tokio::join!(tokio::time::timeout(Duration::from_millis(20), leader), follower);
+        assert_eq!(answer.unwrap().1, "own");
+    }
+
+    #[tokio::test]
+    async fn expense_pages_are_reused_until_something_changes() {
+        let client = SplitwiseClient::sandbox().with_expense_cache(Duration::from_secs(60));
+        let calls = || client.stats()["endpoints"]["GET /get_expenses"]["calls"].as_u64().unwrap();
+        let month = || ListExpensesParams { dated_after: Some("2020-01-01".to_string()), limit: Some(100), ..Default::default() };
+        let first = client.get_expenses(month()).await.unwrap();
+        let again = client.get_expenses(month()).await.unwrap();
+        assert_eq!(first.len(), again.len());
+        assert_eq!(calls(), 1);
+        assert_eq!(client.stats()["expense_cache"]["hits"], 1);
+
+        // Past the check interval, one updated_after query confirms the pages are current
+        client.pages.checked.lock().unwrap().as_mut().unwrap().0 -= PAGE_CHECK_INTERVAL;
+        client.get_expenses(month()).await.unwrap();
+        assert_eq!(calls(), 2);
+
+        // A change made elsewhere is noticed by the next check
+        let body = json!({ "cost": "12.00", "description": "Made in the app", "group_id": 2001, "split_equally": true });
+        client.sandbox.as_ref().unwrap().handle(Method::POST, "/create_expense", &[], Some(&body));
+        client.pages.checked.lock().unwrap().as_mut().unwrap().0 -= PAGE_CHECK_INTERVAL;
+        let refreshed = client.get_expenses(month()).await.unwrap();
+        assert_eq!(refreshed.len(), first.len() + 1);
+        assert_eq!(calls(), 4);
+
+        // And writes through this client empty the cache at once
+        client.delete_expense(refreshed[0].id).await.unwrap();
+        let after_delete = client.get_expenses(month()).await.unwrap();
+        assert_eq!(calls(), 5);
+        assert!(after_delete.iter().any(|e| e.id == refreshed[0].id && e.deleted_at.is_some()));
+    }
+
+    #[test]
+    fn rate_limit_headers_are_picked_out() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "41".parse().unwrap());
+        headers.insert("retry-after", "30".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+        assert_eq!(
+            rate_limit_headers(&headers),
+            vec![("x-ratelimit-remaining", "41".to_string()), ("retry-after", "30".to_string())]
+        );
+    }
+
+    #[test]
+    fn form_fields_flatten_scalars_and_drop_nulls() {
+        let body = json!({
+            "name": "Casa",
+            "group_type": "home",
+            "simplify_by_default": true,
+            "users__0__user_id": 101,
+            "users__0__email": null,
+        });
+        let mut fields = form_fields(&body);
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec![
+                ("group_type".to_string(), "home".to_string()),
+                ("name".to_string(), "Casa".to_string()),
+                ("simplify_by_default".to_string(), "true".to_string()),
+                ("users__0__user_id".to_string(), "101".to_string()),
+            ]
+        );
+    }
+}
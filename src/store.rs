@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::crypto::{self, Cipher};
+
+// Small JSON documents the server keeps between runs (learned preferences and the like),
+// one file per document under the data directory. Without a directory (sandbox mode)
+// nothing is persisted. With a cipher (SPLITWISE_MCP_DATA_KEY) documents are encrypted.
+pub struct LocalStore {
+    dir: Option<PathBuf>,
+    cipher: Option<Arc<Cipher>>,
+}
+
+impl LocalStore {
+    pub fn new(dir: Option<PathBuf>, cipher: Option<Arc<Cipher>>) -> Self {
+        Self { dir, cipher }
+    }
+
+    pub fn dir(&self) -> Option<&PathBuf> {
+        self.dir.as_ref()
+    }
+
+    pub fn cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_deref()
+    }
+
+    /// Load a document, falling back to its default when it is missing or unreadable.
+    pub fn load<T: DeserializeOwned + Default>(&self, name: &str) -> T {
+        let Some(path) = self.path(name) else {
+            return T::default();
+        };
+        let Ok(contents) = fs::read(&path) else {
+            return T::default();
+        };
+        let document = self.open(name, contents).and_then(|plain| Ok(serde_json::from_slice(&plain)?));
+        document.unwrap_or_else(|e| {
+            warn!("Ignoring unreadable {}: {:#}", path.display(), e);
+            T::default()
+        })
+    }
+
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        let Some(path) = self.path(name) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        // An encrypted document this store can't read is kept rather than replaced by what was
+        // built from its default
+        if let Ok(existing) = fs::read(&path) {
+            if crypto::is_sealed(&existing) {
+                self.open(name, existing)
+                    .with_context(|| format!("Not overwriting {}", path.display()))?;
+            }
+        }
+        let mut contents = serde_json::to_vec_pretty(value)?;
+        if let Some(cipher) = &self.cipher {
+            contents = cipher.seal(name, &contents)?;
+        }
+        // Write then rename so a crash never leaves a half-written document
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, contents)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    // A document's contents as JSON, decrypted when it was written encrypted
+    fn open(&self, name: &str, contents: Vec<u8>) -> Result<Vec<u8>> {
+        if !crypto::is_sealed(&contents) {
+            return Ok(contents);
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.open(name, &contents),
+            None => anyhow::bail!("{} is encrypted; set SPLITWISE_MCP_DATA_KEY to read it", name),
+        }
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.json", name)))
+    }
+}
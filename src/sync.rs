@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::types::Expense;
+
+// How far the local view of each group's expenses has caught up with Splitwise. A group's
+// checkpoint is the latest `updated_at` seen in it, so the next sync asks only for expenses
+// updated after that and costs one request for a quiet group. Deleting an expense updates it,
+// so deletions come back from that query with `deleted_at` set; they are kept as tombstones,
+// which lets anything reading the checkpoints (a mirror, change notifications, deltas) drop
+// deleted expenses instead of only ever learning about new ones. The checkpoints are saved
+// in the local store, keyed by group ID.
+
+// Each query reaches this far back past the checkpoint, for an edit whose stamp was taken a
+// moment before one already seen but committed after it; expenses seen before are skipped
+const OVERLAP_SECS: i64 = 10;
+// Tombstones kept per group; the oldest go first
+const MAX_TOMBSTONES: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub group_name: String,
+    // Latest updated_at seen; None until a first sync went through the whole group
+    pub updated_after: Option<String>,
+    pub synced_at: Option<String>,
+    // Deleted expense ID -> when it was deleted
+    #[serde(default)]
+    pub tombstones: BTreeMap<i64, String>,
+}
+
+/// Expense IDs one sync found changed in a group since its checkpoint.
+#[derive(Debug, Default)]
+pub struct Delta {
+    pub created: Vec<i64>,
+    pub updated: Vec<i64>,
+    pub deleted: Vec<i64>,
+    pub restored: Vec<i64>,
+}
+
+impl Checkpoint {
+    /// The `updated_after` to fetch the group's changes with; None to fetch all of it.
+    pub fn query_stamp(&self) -> Option<String> {
+        let since = parse(self.updated_after.as_deref()?)?;
+        Some((since - chrono::Duration::seconds(OVERLAP_SECS)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+    }
+
+    /// Fold in the expenses fetched with `query_stamp` (all of them), moving the checkpoint past them.
+    pub fn apply(&mut self, expenses: &[Expense], now: chrono::DateTime<chrono::Utc>) -> Delta {
+        let since = self.updated_after.as_deref().and_then(parse);
+        let mut latest = since;
+        let mut delta = Delta::default();
+        for expense in expenses {
+            let Some(updated) = parse(&expense.updated_at) else {
+                continue;
+            };
+            // Already seen, fetched again for the overlap
+            if since.is_some_and(|since| updated <= since) {
+                continue;
+            }
+            latest = latest.max(Some(updated));
+            match &expense.deleted_at {
+                Some(deleted_at) => {
+                    if self.tombstones.insert(expense.id, deleted_at.clone()).is_none() {
+                        delta.deleted.push(expense.id);
+                    }
+                }
+                None if self.tombstones.remove(&expense.id).is_some() => delta.restored.push(expense.id),
+                None if parse(&expense.created_at).is_some_and(|created| since.is_some_and(|since| created <= since)) => {
+                    delta.updated.push(expense.id)
+                }
+                None => delta.created.push(expense.id),
+            }
+        }
+        if self.tombstones.len() > MAX_TOMBSTONES {
+            let mut oldest: Vec<(String, i64)> = self.tombstones.iter().map(|(id, at)| (at.clone(), *id)).collect();
+            oldest.sort();
+            for (_, id) in oldest.iter().take(self.tombstones.len() - MAX_TOMBSTONES) {
+                self.tombstones.remove(id);
+            }
+        }
+        // A group with nothing in it yet starts from now
+        let latest = latest.unwrap_or(now);
+        self.updated_after = Some(latest.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true));
+        self.synced_at = Some(now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+        delta
+    }
+}
+
+fn parse(stamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(stamp).ok().map(|stamp| stamp.with_timezone(&chrono::Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expense(id: i64, created_at: &str, updated_at: &str, deleted_at: Option<&str>) -> Expense {
+        Expense {
+            id,
+            created_at: created_at.to_string(),
+            updated_at: updated_at.to_string(),
+            deleted_at: deleted_at.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changes_since_the_checkpoint_are_sorted_out_once() {
+        let now = chrono::Utc::now();
+        let mut checkpoint = Checkpoint::default();
+        assert_eq!(checkpoint.query_stamp(), None);
+        let first = checkpoint.apply(
+            &[
+                expense(1, "2024-03-01T10:00:00Z", "2024-03-01T10:00:00Z", None),
+                expense(2, "2024-03-02T10:00:00Z", "2024-03-03T09:00:00Z", Some("2024-03-03T09:00:00Z")),
+            ],
+            now,
+        );
+        assert_eq!((first.created, first.deleted), (vec![1], vec![2]));
+        assert_eq!(checkpoint.updated_after.as_deref(), Some("2024-03-03T09:00:00Z"));
+        assert_eq!(checkpoint.query_stamp().as_deref(), Some("2024-03-03T08:59:50Z"));
+
+        // The overlap brings back expense 2, which was already counted
+        let second = checkpoint.apply(
+            &[
+                expense(2, "2024-03-02T10:00:00Z", "2024-03-03T09:00:00Z", Some("2024-03-03T09:00:00Z")),
+                expense(1, "2024-03-01T10:00:00Z", "2024-03-04T12:00:00+01:00", None),
+                expense(3, "2024-03-04T12:30:00Z", "2024-03-04T12:30:00Z", None),
+            ],
+            now,
+        );
+        assert_eq!((second.created, second.updated, second.deleted), (vec![3], vec![1], vec![]));
+        assert_eq!(checkpoint.updated_after.as_deref(), Some("2024-03-04T12:30:00Z"));
+
+        let third = checkpoint.apply(&[expense(2, "2024-03-02T10:00:00Z", "2024-03-05T08:00:00Z", None)], now);
+        assert_eq!(third.restored, vec![2]);
+        assert!(checkpoint.tombstones.is_empty());
+        // Nothing new leaves the checkpoint where it was
+        checkpoint.apply(&[], now);
+        assert_eq!(checkpoint.updated_after.as_deref(), Some("2024-03-05T08:00:00Z"));
+
+        let mut empty = Checkpoint::default();
+        empty.apply(&[], now);
+        assert!(empty.query_stamp().is_some());
+    }
+}
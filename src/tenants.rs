@@ -0,0 +1,144 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::tools::SplitwiseTools;
+
+// Several Splitwise accounts served by one HTTP server, e.g. one per person in a household, each
+// with their own assistant. TENANTS_FILE lists them; each tenant has its own bearer token,
+// Splitwise client, caches and local store, may be held to a number of requests per minute, and
+// its Splitwise metrics carry a tenant label. The account set up with SPLITWISE_API_KEY and
+// MCP_AUTH_TOKEN is served as before.
+
+/// One entry of TENANTS_FILE.
+#[derive(Debug, Deserialize)]
+pub struct TenantConfig {
+    pub name: String,
+    pub token: String,
+    pub splitwise_api_key: String,
+    // Requests to this server per minute; unlimited when missing
+    pub requests_per_minute: Option<u32>,
+}
+
+pub struct Tenant {
+    pub name: String,
+    pub token: String,
+    pub tools: Arc<SplitwiseTools>,
+    limit: Option<RateLimit>,
+}
+
+impl Tenant {
+    pub fn new(config: &TenantConfig, tools: Arc<SplitwiseTools>) -> Self {
+        Self {
+            name: config.name.clone(),
+            token: config.token.clone(),
+            tools,
+            limit: config.requests_per_minute.map(RateLimit::new),
+        }
+    }
+
+    /// Count a request against the tenant's limit; false once this minute's allowance is spent.
+    pub fn admit(&self) -> bool {
+        self.limit.as_ref().is_none_or(RateLimit::admit)
+    }
+}
+
+/// Read TENANTS_FILE: a JSON list of {name, token, splitwise_api_key, requests_per_minute}.
+/// `auth_token` is MCP_AUTH_TOKEN, which belongs to the default account.
+pub fn load(path: &str, auth_token: &str) -> Result<Vec<TenantConfig>> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read TENANTS_FILE {}", path))?;
+    let tenants: Vec<TenantConfig> =
+        serde_json::from_str(&content).with_context(|| format!("TENANTS_FILE {} is not a list of tenants", path))?;
+    check(&tenants, auth_token)?;
+    Ok(tenants)
+}
+
+// Names become directory names and metric labels; tokens must tell tenants apart, and from the
+// default account
+fn check(tenants: &[TenantConfig], auth_token: &str) -> Result<()> {
+    let mut names = HashSet::new();
+    let mut tokens = HashSet::new();
+    for tenant in tenants {
+        let name = tenant.name.as_str();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            bail!("Tenant name '{}' may only use letters, digits, '-' and '_'", name);
+        }
+        if !names.insert(name) {
+            bail!("Tenant '{}' is listed twice", name);
+        }
+        if tenant.token.trim().len() < 16 {
+            bail!("Tenant '{}' needs a token of at least 16 characters", name);
+        }
+        if tenant.token == auth_token {
+            bail!("Tenant '{}' has MCP_AUTH_TOKEN as its token, which is the default account's", name);
+        }
+        if !tokens.insert(tenant.token.as_str()) {
+            bail!("Tenant '{}' shares its token with another tenant", name);
+        }
+    }
+    Ok(())
+}
+
+// Requests allowed per minute, counted in fixed one-minute windows
+struct RateLimit {
+    per_minute: u32,
+    window: Mutex<(Instant, u32)>,
+}
+
+impl RateLimit {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            window: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    fn admit(&self) -> bool {
+        let mut window = self.window.lock().unwrap();
+        if window.0.elapsed() >= Duration::from_secs(60) {
+            *window = (Instant::now(), 0);
+        }
+        if window.1 >= self.per_minute {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(name: &str, token: &str) -> TenantConfig {
+        TenantConfig {
+            name: name.to_string(),
+            token: token.to_string(),
+            splitwise_api_key: "key".to_string(),
+            requests_per_minute: None,
+        }
+    }
+
+    #[test]
+    fn tenants_need_distinct_names_and_tokens() {
+        let admin = "admin-token-0123456789";
+        assert!(check(&[tenant("ana", "ana-token-0123456789"), tenant("marcos", "marcos-token-0123456")], admin).is_ok());
+        assert!(check(&[tenant("ana", "ana-token-0123456789"), tenant("ana", "other-token-0123456")], admin).is_err());
+        assert!(check(&[tenant("ana", "same-token-0123456789"), tenant("jo", "same-token-0123456789")], admin).is_err());
+        assert!(check(&[tenant("../etc", "ana-token-0123456789")], admin).is_err());
+        assert!(check(&[tenant("ana", "short")], admin).is_err());
+        assert!(check(&[tenant("ana", admin)], admin).is_err());
+    }
+
+    #[test]
+    fn rate_limit_allows_so_many_per_minute() {
+        let limit = RateLimit::new(2);
+        assert!(limit.admit());
+        assert!(limit.admit());
+        assert!(!limit.admit());
+        limit.window.lock().unwrap().0 -= Duration::from_secs(61);
+        assert!(limit.admit());
+    }
+}
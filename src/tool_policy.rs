@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+// Which tools clients are offered. TOOL_POLICY_FILE holds a JSON object such as
+// {"read_only": true, "allow": ["list_expenses", "get_group"]}: read-only mode hides every tool
+// that can change Splitwise data, and an allowlist hides every tool not on it. The file is read
+// again whenever it changes, so an operator can narrow or widen what a running server offers;
+// each change that alters the policy bumps a revision, and the transports then send clients
+// notifications/tools/list_changed so they list the tools again. A file that stops parsing
+// keeps the last policy that did. Without TOOL_POLICY_FILE every tool is offered and the list
+// never changes.
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolPolicy {
+    #[serde(default)]
+    pub read_only: bool,
+    // Names of the only tools offered; None offers all of them
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+}
+
+impl ToolPolicy {
+    /// Load a policy file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read TOOL_POLICY_FILE {}", path))?;
+        serde_json::from_str(&text).with_context(|| {
+            format!("TOOL_POLICY_FILE {} must be a JSON object like {{\"read_only\": true, \"allow\": [\"list_expenses\"]}}", path)
+        })
+    }
+
+    /// Whether `tool` is offered; `writes` says whether it can change Splitwise data.
+    pub fn permits(&self, tool: &str, writes: bool) -> bool {
+        !(self.read_only && writes) && self.allow.as_ref().is_none_or(|allow| allow.iter().any(|t| t == tool))
+    }
+
+    /// Why `tool` is not offered, for the error a call to it gets.
+    pub fn refusal(&self, tool: &str, writes: bool) -> String {
+        if self.read_only && writes {
+            format!("{} is not available: this server is in read-only mode", tool)
+        } else {
+            format!("{} is not available: it is not on this server's tool allowlist", tool)
+        }
+    }
+}
+
+/// The policy in force, kept in step with TOOL_POLICY_FILE.
+pub struct ToolPolicies {
+    path: Option<String>,
+    state: Mutex<State>,
+}
+
+struct State {
+    policy: ToolPolicy,
+    // When the file was last read, to skip reading it again while it is unchanged
+    modified: Option<SystemTime>,
+    revision: u64,
+}
+
+impl ToolPolicies {
+    /// The policy in `path`, if given; a file that can't be read at startup is an error.
+    pub fn new(path: Option<String>) -> Result<Self> {
+        let policy = match path {
+            Some(ref path) => ToolPolicy::from_file(path)?,
+            None => ToolPolicy::default(),
+        };
+        let modified = path.as_deref().and_then(modified);
+        Ok(Self {
+            path,
+            state: Mutex::new(State { policy, modified, revision: 0 }),
+        })
+    }
+
+    /// Whether the tool list can change while the server runs.
+    pub fn is_dynamic(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// The policy in force and its revision, after reading the file again if it changed.
+    pub fn current(&self) -> (ToolPolicy, u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(path) = &self.path {
+            let now = modified(path);
+            if now != state.modified {
+                state.modified = now;
+                match ToolPolicy::from_file(path) {
+                    Ok(policy) if policy != state.policy => {
+                        info!("Tool policy changed: {:?}", policy);
+                        state.policy = policy;
+                        state.revision += 1;
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Keeping the current tool policy: {:#}", e),
+                }
+            }
+        }
+        (state.policy.clone(), state.revision)
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_hides_writes_and_the_allowlist_everything_else() {
+        let open = ToolPolicy::default();
+        assert!(open.permits("create_expense", true) && open.permits("list_expenses", false));
+
+        let read_only = ToolPolicy { read_only: true, allow: None };
+        assert!(!read_only.permits("create_expense", true) && read_only.permits("list_expenses", false));
+        assert!(read_only.refusal("create_expense", true).contains("read-only"));
+
+        let allow = ToolPolicy { read_only: false, allow: Some(vec!["create_expense".to_string()]) };
+        assert!(allow.permits("create_expense", true) && !allow.permits("list_expenses", false));
+        assert!(allow.refusal("list_expenses", false).contains("allowlist"));
+    }
+
+    #[test]
+    fn a_changed_file_is_a_new_revision_and_a_broken_one_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("splitwise-mcp-tool-policy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("policy.json");
+        let write = |text: &str, seconds: u64| {
+            std::fs::write(&path, text).unwrap();
+            // Set the time explicitly, since two writes can land within the clock's resolution
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(seconds)).unwrap();
+        };
+        write(r#"{"read_only": false}"#, 1);
+        let policies = ToolPolicies::new(Some(path.to_string_lossy().into_owned())).unwrap();
+        assert!(policies.is_dynamic());
+        assert_eq!(policies.current(), (ToolPolicy::default(), 0));
+
+        write(r#"{"read_only": true}"#, 2);
+        assert_eq!(policies.current().1, 1);
+        assert!(policies.current().0.read_only);
+
+        // Rewritten with the same policy, or broken, the revision stays
+        write(r#"{"read_only": true, "allow": null}"#, 3);
+        assert_eq!(policies.current().1, 1);
+        write(r#"{"read_only": tru"#, 4);
+        assert_eq!(policies.current(), (ToolPolicy { read_only: true, allow: None }, 1));
+
+        assert!(ToolPolicies::new(Some(dir.join("missing.json").to_string_lossy().into_owned())).is_err());
+        assert!(!ToolPolicies::new(None).unwrap().is_dynamic());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
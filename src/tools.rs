@@ -1,22 +1,153 @@
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{Datelike, Timelike};
+use futures::StreamExt;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
+use crate::accounts::CategoryMapping;
+use crate::budget;
+use crate::cache::LookupCache;
+use crate::chart;
+use crate::config::Config;
+use crate::crypto::Cipher;
+use crate::errors::{self, InvalidParams, UnknownResource};
+use crate::exchange::ExchangeRates;
+use crate::export::{ExportStatus, HistoryExport};
+use crate::i18n;
+use crate::jobs::Jobs;
+use crate::matching;
+use crate::merchants::Merchants;
+use crate::metadata;
+use crate::nl_query;
+use crate::pdf;
+use crate::peer;
+use crate::projection;
+use crate::receipt;
+use crate::redact::Redactor;
+use crate::render::{self, OutputFormat};
+use crate::resolve::{self, Candidate};
+use crate::roots;
+use crate::sampling;
 use crate::splitwise::SplitwiseClient;
+use crate::store::LocalStore;
+use crate::sync;
+use crate::tool_policy::ToolPolicies;
+use crate::trace;
 use crate::types::*;
+use crate::warnings;
+use crate::write_queue::{self, PendingWrite};
 
 pub struct SplitwiseTools {
     client: Arc<SplitwiseClient>,
+    rates: ExchangeRates,
+    config: Config,
+    lookups: LookupCache,
+    // User-configured category aliases (normalized word -> category name)
+    category_aliases: HashMap<String, String>,
+    merchants: Merchants,
+    // Codes of categories in an external system (CATEGORY_MAPPING_FILE), used by exports
+    category_mapping: Option<CategoryMapping>,
+    store: LocalStore,
+    // Description -> category the user chose when correcting an expense
+    learned_categories: Mutex<BTreeMap<String, LearnedCategory>>,
+    // Group ID -> how create_expense splits that group's expenses when not told otherwise
+    split_profiles: Mutex<BTreeMap<i64, SplitProfile>>,
+    // Group ID -> how far sync_now has caught up with that group's changes
+    sync_checkpoints: Mutex<BTreeMap<i64, sync::Checkpoint>>,
+    // Expense writes waiting for Splitwise to be reachable (QUEUE_OFFLINE_WRITES), oldest first
+    pending_writes: Mutex<Vec<PendingWrite>>,
+    // "friend ID:currency" -> a debt the user means to pay, added from payables_list
+    settlement_plan: Mutex<BTreeMap<String, PlannedPayment>>,
+    // Alias user ID -> the account it stands for, so one person's history isn't split in reports
+    member_aliases: Mutex<BTreeMap<i64, MemberAlias>>,
+    // Held while queued writes are sent, so two flushes don't send the same write
+    flushing: tokio::sync::Mutex<()>,
+    export: HistoryExport,
+    jobs: Jobs,
+    started_at: Instant,
+    // Shortens names and hides emails in results (PII_REDACTION); None when off
+    redactor: Option<Redactor>,
+    // Which tools are offered (TOOL_POLICY_FILE)
+    policies: ToolPolicies,
 }
 
 impl SplitwiseTools {
-    pub fn new(client: Arc<SplitwiseClient>) -> Self {
-        Self { client }
+    pub fn new(client: Arc<SplitwiseClient>, config: Config) -> Result<Self> {
+        let rates = ExchangeRates::new(&config, client.http_client())?;
+        let lookups = LookupCache::new(Duration::from_secs(config.lookup_cache_ttl_secs));
+        let category_aliases = match config.category_aliases_file {
+            Some(ref path) => load_category_aliases(path)?,
+            None => HashMap::new(),
+        };
+        let merchants = match config.merchant_aliases_file {
+            Some(ref path) => Merchants::from_file(path)?,
+            None => Merchants::new(HashMap::new()),
+        };
+        let category_mapping = match config.category_mapping_file {
+            Some(ref path) => Some(CategoryMapping::from_file(path)?),
+            None => None,
+        };
+        let cipher = match (&config.data_key, &config.data_key_file) {
+            (Some(key), _) => Some(Arc::new(Cipher::from_base64(key)?)),
+            (None, Some(path)) => {
+                let key = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read SPLITWISE_MCP_DATA_KEY_FILE {}", path))?;
+                Some(Arc::new(Cipher::from_base64(&key)?))
+            }
+            (None, None) => None,
+        };
+        let store = LocalStore::new(config.data_dir.clone(), cipher.clone());
+        let learned_categories = Mutex::new(store.load(LEARNED_CATEGORIES));
+        let split_profiles = Mutex::new(store.load(SPLIT_PROFILES));
+        let sync_checkpoints = Mutex::new(store.load(SYNC_CHECKPOINTS));
+        let pending_writes = Mutex::new(store.load(PENDING_WRITES));
+        let settlement_plan = Mutex::new(store.load(SETTLEMENT_PLAN));
+        let member_aliases = Mutex::new(store.load(MEMBER_ALIASES));
+        let export = HistoryExport::new(client.clone(), config.data_dir.clone(), cipher);
+        let redactor = Redactor::from_config(&config)?;
+        let policies = ToolPolicies::new(config.tool_policy_file.clone())?;
+        Ok(Self {
+            client,
+            rates,
+            config,
+            lookups,
+            category_aliases,
+            merchants,
+            category_mapping,
+            store,
+            learned_categories,
+            split_profiles,
+            sync_checkpoints,
+            pending_writes,
+            settlement_plan,
+            member_aliases,
+            flushing: tokio::sync::Mutex::new(()),
+            export,
+            jobs: Jobs::default(),
+            started_at: Instant::now(),
+            redactor,
+            policies,
+        })
     }
 
     pub fn get_tools(&self) -> Vec<Value> {
-        vec![
+        let mut tools = vec![
+            json!({
+                "name": "get_dashboard",
+                "description": "Snapshot to start a conversation with, in one call: you (name, default currency), your balance in each group, friends you have a balance with, and the last 10 expenses. Fetched concurrently; use the other tools for details",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
             // User tools
             json!({
                 "name": "get_current_user",
@@ -36,18 +167,28 @@ impl SplitwiseTools {
                         "user_id": {
                             "type": "integer",
                             "description": "The ID of the user to retrieve"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to user_id; matched loosely (e.g. 'Marcos N.')"
                         }
                     },
-                    "required": ["user_id"]
+                    "required": []
                 }
             }),
             // Group tools
             json!({
                 "name": "list_groups",
-                "description": "List all groups the current user belongs to",
+                "description": "List all groups the current user belongs to. Returns {items, returned, scanned, has_more, next_offset}",
                 "inputSchema": {
                     "type": "object",
-                    "properties": {},
+                    "properties": {
+                        "fields": {
+                            "type": "array",
+                            "description": "Only return these fields, e.g. [\"id\", \"name\", \"members.first_name\", \"members.balance\"]. Dotted paths reach into nested objects and lists; braces pick several sub-fields (\"members{id,first_name}\"). Available: id, name, group_type, updated_at, simplify_by_default, members (id, first_name, last_name, email, registration_status, picture, balance), original_debts, simplified_debts, whiteboard, group_reminders, invite_link. Default: everything",
+                            "items": {"type": "string"}
+                        }
+                    },
                     "required": []
                 }
             }),
@@ -60,9 +201,18 @@ impl SplitwiseTools {
                         "group_id": {
                             "type": "integer",
                             "description": "The ID of the group to retrieve"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "fields": {
+                            "type": "array",
+                            "description": "Only return these fields, e.g. [\"id\", \"name\", \"members.first_name\", \"members.balance\"]. Dotted paths reach into nested objects and lists; braces pick several sub-fields (\"members{id,first_name}\"). Available: id, name, group_type, updated_at, simplify_by_default, members (id, first_name, last_name, email, registration_status, picture, balance), original_debts, simplified_debts, whiteboard, group_reminders, invite_link. Default: everything",
+                            "items": {"type": "string"}
                         }
                     },
-                    "required": ["group_id"]
+                    "required": []
                 }
             }),
             json!({
@@ -91,7 +241,7 @@ impl SplitwiseTools {
             // Expense tools
             json!({
                 "name": "list_expenses",
-                "description": "List expenses with optional filters",
+                "description": "List expenses with optional filters. Returns {items, returned, scanned, has_more, next_offset}: scanned is how many expenses were examined to find the items, and when has_more is true, call again with offset set to next_offset and the same filters for the next page",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
@@ -99,10 +249,18 @@ impl SplitwiseTools {
                             "type": "integer",
                             "description": "Filter by group ID"
                         },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
                         "friend_id": {
                             "type": "integer",
                             "description": "Filter by friend ID"
                         },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        },
                         "dated_after": {
                             "type": "string",
                             "description": "Filter expenses after this date (YYYY-MM-DD)"
@@ -113,22 +271,31 @@ impl SplitwiseTools {
                         },
                         "limit": {
                             "type": "integer",
-                            "description": "Maximum number of expenses to return"
+                            "description": "Maximum number of expenses to return (Splitwise returns 20 without one, unless filtering)"
                         },
                         "offset": {
                             "type": "integer",
-                            "description": "Number of expenses to skip"
+                            "description": "Where to start: the next_offset of the previous page. Counts expenses in Splitwise's order (matches, with group_ids or include_personal)"
                         },
                         "fields": {
                             "type": "array",
-                            "description": "Fields to include (REQUIRED). Common: id, description, cost, currency_code, date, category, payment, group_id. All available: id, description, cost, currency_code, date, category (id & name), payment (true if payment/settlement), group_id (null if personal), friendship_id (for non-group expenses), details (notes), users (array with paid_share, owed_share, net_balance per user), repayments (simplified debt flows), created_at, created_by, updated_at, updated_by, deleted_at (when deleted), deleted_by, receipt (image URLs), comments_count, transaction_confirmed (for integrated payments), transaction_id, transaction_method, transaction_status, repeats, repeat_interval (weekly/monthly/yearly), next_repeat, email_reminder, email_reminder_in_advance, expense_bundle_id",
+                            "description": "Fields to include (REQUIRED). Common: id, description, cost, currency_code, date, category, payment, group_id. All available: id, description, cost, currency_code, date, category (id & name), payment (true if payment/settlement), group_id (null if personal), friendship_id (for non-group expenses), details (notes), users (array with paid_share, owed_share, net_balance per user), repayments (simplified debt flows), created_at, created_by, updated_at, updated_by, deleted_at (when deleted), deleted_by, receipt (image URLs), comments_count, transaction_confirmed (for integrated payments), transaction_id, transaction_method, transaction_status, repeats, repeat_interval (weekly/monthly/yearly), next_repeat, email_reminder, email_reminder_in_advance, expense_bundle_id, tags (from the notes metadata), metadata, relative_to_me (your own paid_share, owed_share and net_balance, and whether you are involved), my_paid_share, my_owed_share, my_net_balance (the same, as plain values), participants (who paid and owes what, by name: \"Marcos paid 30.00, owes 10.00; Ana owes 10.00\"). Dotted paths pick nested values, e.g. category.name or users.owed_share, and braces pick several from the same list: users{user_id,paid_share,owed_share}",
                             "items": {
                                 "type": "string"
                             }
                         },
                         "search_text": {
                             "type": "string",
-                            "description": "Text to search for (case-insensitive substring match)"
+                            "description": "Text to search for (case-insensitive; how it matches depends on search_mode)"
+                        },
+                        "search_mode": {
+                            "type": "string",
+                            "description": "How search_text matches: 'substring' (default), 'regex' (e.g. '^Uber', case-insensitive) or 'fuzzy' (tolerates typos such as 'azado' for 'asado')",
+                            "enum": ["substring", "regex", "fuzzy"]
+                        },
+                        "fuzzy_threshold": {
+                            "type": "number",
+                            "description": "Minimum similarity (0-1) for search_mode 'fuzzy'. Default: 0.75; lower it to tolerate more typos"
                         },
                         "search_fields": {
                             "type": "array",
@@ -144,15 +311,63 @@ impl SplitwiseTools {
                                 "type": "integer"
                             }
                         },
+                        "category_names": {
+                            "type": "array",
+                            "description": "Filter by category names instead of IDs; matched loosely. A parent category such as 'Food and drink' includes its subcategories",
+                            "items": {
+                                "type": "string"
+                            }
+                        },
+                        "tags": {
+                            "type": "array",
+                            "description": "Only expenses carrying all of these tags (from the metadata line in their notes)",
+                            "items": {
+                                "type": "string"
+                            }
+                        },
+                        "group_ids": {
+                            "type": "array",
+                            "description": "Search several groups in one call (fetched concurrently, merged newest first). Combines with group_id/group_name",
+                            "items": {
+                                "type": "integer"
+                            }
+                        },
+                        "include_personal": {
+                            "type": "boolean",
+                            "description": "Also include non-group expenses; with group_ids this covers e.g. 'all my transport spending anywhere'"
+                        },
                         "include_deleted": {
                             "type": "string",
                             "description": "Control deleted expense filtering: 'exclude' (default), 'include' (show all), or 'only' (show only deleted)",
                             "enum": ["exclude", "include", "only"]
+                        },
+                        "convert_to": {
+                            "type": "string",
+                            "description": "Currency code to convert each expense cost into. Adds a converted_cost field (amount, currency, rate, rate_date)"
+                        },
+                        "rate_date": {
+                            "type": "string",
+                            "description": "Which exchange rate convert_to uses: 'expense_date' (default, the rate on each expense's own date, best for reports spanning months) or 'latest' (today's rate)",
+                            "enum": ["expense_date", "latest"]
                         }
                     },
                     "required": ["fields"]
                 }
             }),
+            json!({
+                "name": "query_expenses_nl",
+                "description": "Answer a spending question written in plain language, e.g. 'food spending in March in the Casa group' or 'dinners with Jamie last month'. Understands periods (month names, 'last month', 'this year', 'last 30 days', YYYY-MM-DD dates), category words (food, groceries, rent, utilities, transport...), group names and 'with <friend>'. Returns the structured filter it applied together with per-currency totals, so check the filter before relying on the answer",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "question": {
+                            "type": "string",
+                            "description": "The question, e.g. 'how much did we spend on groceries last month in the Casa group'. Quote text to search descriptions for it"
+                        }
+                    },
+                    "required": ["question"]
+                }
+            }),
             json!({
                 "name": "get_expense",
                 "description": "Get detailed information about a specific expense",
@@ -168,784 +383,11762 @@ impl SplitwiseTools {
                             "items": {
                                 "type": "string"
                             },
-                            "description": "Fields to include (REQUIRED). Available: id, description, cost, currency_code, date, category, payment, group_id, friendship_id, details, users, repayments, created_at, created_by, updated_at, updated_by, deleted_at, deleted_by, receipt, comments_count, transaction_confirmed, transaction_id, transaction_method, transaction_status, repeats, repeat_interval, next_repeat, email_reminder, email_reminder_in_advance, expense_bundle_id"
+                            "description": "Fields to include (REQUIRED). Available: id, description, cost, currency_code, date, category, payment, group_id, friendship_id, details, users, repayments, created_at, created_by, updated_at, updated_by, deleted_at, deleted_by, receipt, comments_count, transaction_confirmed, transaction_id, transaction_method, transaction_status, repeats, repeat_interval, next_repeat, email_reminder, email_reminder_in_advance, expense_bundle_id, tags, metadata, relative_to_me (your paid, owed and net share), my_paid_share, my_owed_share, my_net_balance, participants (who paid and owes what, by name). Dotted paths pick nested values, e.g. category.name or users.owed_share, and braces pick several from the same list: users{user_id,paid_share,owed_share}"
                         }
                     },
                     "required": ["expense_id", "fields"]
                 }
             }),
             json!({
-                "name": "create_expense",
-                "description": "Create a new expense. IMPORTANT: Always call get_categories first to choose the most appropriate category/subcategory ID for the expense type. Categories determine the icon shown in Splitwise.",
+                "name": "explain_expense",
+                "description": "Explain one expense in plain terms: who paid what, who owes what, the net effect on each member and on you, and who pays whom. Use this instead of interpreting the raw users/repayments arrays",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "cost": {
-                            "type": "string",
-                            "description": "Total cost of the expense (e.g., '25.00')"
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The ID of the expense"
+                        }
+                    },
+                    "required": ["expense_id"]
+                }
+            }),
+            json!({
+                "name": "tag_expense",
+                "description": "Add tags to an expense, e.g. 'wedding' or 'reimbursable', to track concepts that cut across categories. Tags live in a metadata line at the end of the expense notes",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The expense to tag"
                         },
-                        "description": {
-                            "type": "string",
-                            "description": "Description of the expense"
+                        "tags": {
+                            "type": "array",
+                            "items": {"type": "string"}
+                        }
+                    },
+                    "required": ["expense_id", "tags"]
+                }
+            }),
+            json!({
+                "name": "untag_expense",
+                "description": "Remove tags from an expense",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The expense to untag"
                         },
-                        "currency_code": {
+                        "tags": {
+                            "type": "array",
+                            "items": {"type": "string"}
+                        }
+                    },
+                    "required": ["expense_id", "tags"]
+                }
+            }),
+            json!({
+                "name": "list_expenses_by_tag",
+                "description": "List the expenses carrying a tag, with totals per currency for the full cost and the user's share",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tag": {
                             "type": "string",
-                            "description": "Currency code (e.g., 'USD', 'EUR')"
+                            "description": "The tag to look for"
                         },
                         "group_id": {
                             "type": "integer",
-                            "description": "Group ID to add expense to"
-                        },
-                        "split_equally": {
-                            "type": "boolean",
-                            "description": "Whether to split equally among all group members. Default: true. Set to false when using split_by_shares."
-                        },
-                        "split_by_shares": {
-                            "type": "array",
-                            "description": "Custom split amounts. Each entry specifies a user and their paid/owed amounts. Use this for unequal splits or when multiple people pay.",
-                            "items": {
-                                "type": "object",
-                                "properties": {
-                                    "user_id": {
-                                        "type": "integer",
-                                        "description": "User ID (get from list_friends or get_group)"
-                                    },
-                                    "email": {
-                                        "type": "string",
-                                        "description": "User email (alternative to user_id)"
-                                    },
-                                    "paid_share": {
-                                        "type": "string",
-                                        "description": "Amount this user paid (e.g., '50.00')"
-                                    },
-                                    "owed_share": {
-                                        "type": "string",
-                                        "description": "Amount this user owes (e.g., '25.00')"
-                                    }
-                                },
-                                "required": ["paid_share", "owed_share"]
-                            }
+                            "description": "Only this group's expenses"
                         },
-                        "date": {
+                        "group_name": {
                             "type": "string",
-                            "description": "Date of the expense (YYYY-MM-DD)"
+                            "description": "Group name as an alternative to group_id"
                         },
-                        "category_id": {
-                            "type": "integer",
-                            "description": "Category or subcategory ID from get_categories. Use the most specific subcategory when possible (e.g., 13 for Restaurants instead of 25 for Food). Required for proper icon display."
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
                         },
-                        "details": {
+                        "dated_before": {
                             "type": "string",
-                            "description": "Additional details about the expense"
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
                         }
                     },
-                    "required": ["cost", "description"]
+                    "required": ["tag"]
                 }
             }),
             json!({
-                "name": "update_expense",
-                "description": "Update an existing expense including its split/division",
+                "name": "summarize_expenses",
+                "description": "Totals of non-payment expenses grouped by category, month or tag, per currency, with the user's share (my_share) and relative_to_me: what the user paid, owes and their net. With group_by tag an expense counts once under each of its tags",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "expense_id": {
-                            "type": "integer",
-                            "description": "The ID of the expense to update"
-                        },
-                        "cost": {
-                            "type": "string",
-                            "description": "New total cost of the expense"
-                        },
-                        "description": {
+                        "group_by": {
                             "type": "string",
-                            "description": "New description of the expense"
+                            "enum": ["category", "month", "tag"],
+                            "description": "How to group the totals (default: category)"
                         },
-                        "currency_code": {
-                            "type": "string",
-                            "description": "New currency code"
+                        "tags": {
+                            "type": "array",
+                            "description": "Only expenses carrying all of these tags",
+                            "items": {"type": "string"}
                         },
-                        "category_id": {
+                        "group_id": {
                             "type": "integer",
-                            "description": "Category or subcategory ID from get_categories"
+                            "description": "Only this group's expenses"
                         },
-                        "date": {
+                        "group_name": {
                             "type": "string",
-                            "description": "New date (YYYY-MM-DD)"
+                            "description": "Group name as an alternative to group_id"
                         },
-                        "split_equally": {
-                            "type": "boolean",
-                            "description": "Whether to split equally among all group members. Set to false when using split_by_shares."
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
                         },
-                        "split_by_shares": {
-                            "type": "array",
-                            "description": "Custom split amounts. Each entry specifies a user and their paid/owed amounts. Use this for unequal splits or when changing who pays.",
-                            "items": {
-                                "type": "object",
-                                "properties": {
-                                    "user_id": {
-                                        "type": "integer",
-                                        "description": "User ID (get from list_friends or get_group)"
-                                    },
-                                    "email": {
-                                        "type": "string",
-                                        "description": "User email (alternative to user_id)"
-                                    },
-                                    "paid_share": {
-                                        "type": "string",
-                                        "description": "Amount this user paid (e.g., '50.00')"
-                                    },
-                                    "owed_share": {
-                                        "type": "string",
-                                        "description": "Amount this user owes (e.g., '25.00')"
-                                    }
-                                },
-                                "required": ["paid_share", "owed_share"]
-                            }
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
                         }
                     },
-                    "required": ["expense_id"]
+                    "required": []
                 }
             }),
             json!({
-                "name": "delete_expense",
-                "description": "Delete an expense",
+                "name": "compare_periods",
+                "description": "Compare non-payment spending between two periods, e.g. March vs April or this year vs last year: totals per currency and per category in each period, with the change and percent change from the first to the second, for both the total cost and the user's share. Categories are ordered by the size of the change",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "expense_id": {
+                        "first": {
+                            "description": "The earlier (baseline) period: text such as 'March', 'March 2025', '2025-03', 'last month', 'last year', '2024' or two YYYY-MM-DD dates, or an object {dated_after, dated_before} (YYYY-MM-DD, end exclusive)"
+                        },
+                        "second": {
+                            "description": "The period compared against the first, in the same forms"
+                        },
+                        "group_id": {
                             "type": "integer",
-                            "description": "The ID of the expense to delete"
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
                         }
                     },
-                    "required": ["expense_id"]
+                    "required": ["first", "second"]
                 }
             }),
-            // Friend tools
             json!({
-                "name": "list_friends",
-                "description": "List all friends and their balances",
+                "name": "contribution_report",
+                "description": "Who actually pays for things in a group: per member and currency, the total they paid, the total they consumed (their owed shares) and their net contribution (paid minus consumed), with their percentage of everything paid. Optionally broken down by month or year. Payments (settle-ups) are left out unless include_payments is set",
                 "inputSchema": {
                     "type": "object",
-                    "properties": {},
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group to report on"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "period_by": {
+                            "type": "string",
+                            "enum": ["none", "month", "year"],
+                            "description": "Also break the figures down per month or year (default: none)"
+                        },
+                        "include_payments": {
+                            "type": "boolean",
+                            "description": "Count payments and settlements as contributions too (default: false)"
+                        }
+                    },
                     "required": []
                 }
             }),
             json!({
-                "name": "get_friend",
-                "description": "Get detailed information about a specific friend",
+                "name": "category_drilldown",
+                "description": "Everything about one category's spending: totals per currency with the user's share, a monthly trend, a breakdown by subcategory when given a parent category (which covers all of its subcategories), the top merchants by total (similar descriptions such as 'UBER *TRIP' and 'Uber BV' count as one merchant), and the largest individual expenses. Payments are left out",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "friend_id": {
+                        "category_id": {
                             "type": "integer",
-                            "description": "The user ID of the friend"
+                            "description": "Category or parent category ID"
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name as an alternative to category_id, e.g. 'Food and drink' or 'groceries'"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "top": {
+                            "type": "integer",
+                            "description": "How many merchants and largest expenses to list (default 5, max 50)"
                         }
                     },
-                    "required": ["friend_id"]
+                    "required": []
                 }
             }),
             json!({
-                "name": "add_friend",
-                "description": "Add a new friend by email",
+                "name": "top_merchants",
+                "description": "Where the money goes by merchant: non-payment expenses grouped by merchant, with similar descriptions ('UBER *TRIP', 'Uber BV', 'uber') counted as one and MERCHANT_ALIASES_FILE naming the rest. Per merchant and currency: count, total, the user's share, the latest date and a few of the descriptions that were grouped. Largest totals first",
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "email": {
+                        "dated_after": {
                             "type": "string",
-                            "description": "Email address of the friend to add"
-                        }
-                    },
-                    "required": ["email"]
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "category_names": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Only these categories; a parent category includes its subcategories"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "How many merchants to list (default 10, max 100)"
+                        }
+                    },
+                    "required": []
                 }
             }),
-            // Utility tools
             json!({
-                "name": "get_currencies",
-                "description": "Get list of supported currencies",
+                "name": "project_cashflow",
+                "description": "Project the user's shared-expense outflow for the coming months. Each month is the user's share of the recurring expenses falling due in it, plus an estimate for everything else: the user's average monthly share per category over the recent history, less what recurring expenses already cover in that category. Per currency, without conversion; payments are left out",
                 "inputSchema": {
                     "type": "object",
-                    "properties": {},
+                    "properties": {
+                        "months": {
+                            "type": "integer",
+                            "description": "How many calendar months to project, starting next month (default 3, max 24)"
+                        },
+                        "history_months": {
+                            "type": "integer",
+                            "description": "How many past full months the category averages are taken over (default 6, max 36)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        }
+                    },
                     "required": []
                 }
             }),
             json!({
-                "name": "get_categories",
-                "description": "Get list of expense categories with their IDs. Each category has an associated icon in Splitwise (e.g., 25=Food has a restaurant icon, 31=Transportation has a car icon)",
+                "name": "debt_aging",
+                "description": "How long each outstanding balance with a friend has existed: the date the running balance last crossed zero, rebuilt from the shared expense history, and its age in days. Debts are bucketed into 0-30, 30-60 and 60+ days, per currency and direction (owed to the user or owed by the user). Oldest first",
                 "inputSchema": {
                     "type": "object",
-                    "properties": {},
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "Only this friend"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        }
+                    },
                     "required": []
                 }
             }),
-        ]
-    }
-
-    pub async fn handle_tool_call(&self, name: &str, arguments: Option<Value>) -> Result<Value> {
-        let arguments = arguments.unwrap_or_else(|| json!({}));
-        
-        match name {
-            // User tools
-            "get_current_user" => {
-                let user = self.client.get_current_user().await?;
-                Ok(serde_json::to_value(user)?)
-            }
-            "get_user" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    user_id: i64,
-                }
-                let args: Args = serde_json::from_value(arguments)?;
-                let user = self.client.get_user(args.user_id).await?;
-                Ok(serde_json::to_value(user)?)
-            }
-            // Group tools
-            "list_groups" => {
-                let groups = self.client.get_groups().await?;
-                Ok(serde_json::to_value(groups)?)
-            }
-            "get_group" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    group_id: i64,
+            json!({
+                "name": "settlement_history",
+                "description": "Payments (settle-ups) between the user and a friend, or within a group, newest first, each with who paid whom, the amount and the balance right after it (positive when the user is owed). Also gives the latest payment the user made and received, so 'when did I last pay Ana and how much?' is one call",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "Payments with this friend"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Payments within this group, between anyone; balances are the user's in the group"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only list payments on or after this date (YYYY-MM-DD); balances still count the whole history"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only list payments before this date (YYYY-MM-DD)"
+                        }
+                    },
+                    "required": []
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let group = self.client.get_group(args.group_id).await?;
-                Ok(serde_json::to_value(group)?)
-            }
-            "create_group" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    name: String,
-                    group_type: Option<String>,
-                    simplify_by_default: Option<bool>,
+            }),
+            json!({
+                "name": "analyze_recurring_costs",
+                "description": "Find expenses that come back at a steady cadence (weekly, fortnightly, monthly, quarterly or yearly) for a similar amount from the same merchant, but aren't set up as recurring in Splitwise: often forgotten shared subscriptions. Per series: the cadence, the typical amount and the user's share, what it costs per year, and whether it still seems active. Costliest per year first",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Look at expenses on or after this date (YYYY-MM-DD). Default: 12 months ago; yearly series need a few years"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "min_occurrences": {
+                            "type": "integer",
+                            "description": "How many charges make a series (default 3, at least 2)"
+                        },
+                        "tolerance_pct": {
+                            "type": "number",
+                            "description": "How far a charge may be from the series' typical amount, in percent (default 15)"
+                        }
+                    },
+                    "required": []
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let request = CreateGroupRequest {
-                    name: args.name,
-                    group_type: args.group_type,
-                    simplify_by_default: args.simplify_by_default,
-                    users: vec![], // Current user is added automatically
-                };
-                let group = self.client.create_group(request).await?;
-                Ok(serde_json::to_value(group)?)
-            }
-            // Expense tools
-            "list_expenses" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    group_id: Option<i64>,
-                    friend_id: Option<i64>,
-                    dated_after: Option<String>,
-                    dated_before: Option<String>,
-                    limit: Option<i32>,
-                    offset: Option<i32>,
-                    fields: Vec<String>,  // Now required
-                    search_text: Option<String>,
-                    search_fields: Option<Vec<String>>,
-                    category_ids: Option<Vec<i64>>,
-                    include_deleted: Option<String>,
+            }),
+            json!({
+                "name": "monthly_close",
+                "description": "End-of-month routine for a group in one call: summarizes the month's spending, lists expenses still left in the General category, proposes the settle-up payments from the group's current balances and, with record_settlements and confirm, records them. Returns the findings plus a Markdown report to share",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group to close"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "month": {
+                            "type": "string",
+                            "description": "Month to close as YYYY-MM. Default: last month"
+                        },
+                        "record_settlements": {
+                            "type": "boolean",
+                            "description": "Record the proposed settle-up payments in Splitwise (requires confirm: true)"
+                        },
+                        "confirm": {
+                            "type": "boolean",
+                            "description": "Confirm recording the payments, after the user has agreed"
+                        }
+                    },
+                    "required": []
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                
-                // Default to excluding deleted expenses
-                let include_deleted = args.include_deleted.as_deref().unwrap_or("exclude");
-                
-                let mut expenses = Vec::new();
-                
-                // If searching or filtering by category, fetch in batches until we have enough matches
-                if args.search_text.is_some() || args.category_ids.is_some() {
-                    let search_lower = args.search_text.as_ref().map(|s| s.to_lowercase());
-                    let search_fields = args.search_fields.clone().unwrap_or_else(|| {
-                        vec!["description".to_string(), "details".to_string(), "category".to_string()]
-                    });
-                    
-                    let desired_count = args.limit.map(|l| l as usize);
-                    let batch_size = 100;
-                    let mut current_offset = args.offset.unwrap_or(0);
-                    
-                    // Keep fetching batches until we have enough matches (if limit set) or run out of expenses
-                    loop {
-                        // If we have a limit and reached it, stop
-                        if let Some(limit) = desired_count {
-                            if expenses.len() >= limit {
-                                break;
-                            }
+            }),
+            json!({
+                "name": "audit_data_quality",
+                "description": "Check expenses for data problems, each with a suggested fix: missing_category (left in General), zero_cost, shares_mismatch (paid or owed shares don't add up to the cost), orphaned_friendship (a non-group expense with someone who is no longer a friend), duplicate (same description, amount, currency and date), and currency_mismatch (a currency other than the one the group mostly uses). Payments are only checked for zero cost and shares",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "checks": {
+                            "type": "array",
+                            "items": {"type": "string", "enum": ["missing_category", "zero_cost", "shares_mismatch", "orphaned_friendship", "duplicate", "currency_mismatch"]},
+                            "description": "Only run these checks (default: all)"
                         }
-                        let params = ListExpensesParams {
-                            group_id: args.group_id,
-                            friend_id: args.friend_id,
-                            dated_after: args.dated_after.clone(),
-                            dated_before: args.dated_before.clone(),
-                            updated_after: None,
-                            updated_before: None,
-                            limit: Some(batch_size),
-                            offset: Some(current_offset),
-                        };
-                        
-                        let mut batch = self.client.get_expenses(params.clone()).await
-                            .map_err(|e| anyhow::anyhow!("Failed to fetch batch at offset {}: {}", current_offset, e))?;
-                        
-                        // Store the original batch size to check if we've reached the end
-                        let batch_had_results = !batch.is_empty();
-                        
-                        // Filter this batch
-                        batch.retain(|expense| {
-                            // Handle deleted expense filtering
-                            match include_deleted {
-                                "exclude" => {
-                                    if expense.deleted_at.is_some() {
-                                        return false;
-                                    }
-                                },
-                                "only" => {
-                                    if expense.deleted_at.is_none() {
-                                        return false;
-                                    }
-                                },
-                                "include" => {
-                                    // Include all expenses regardless of deleted status
-                                },
-                                _ => {
-                                    // Default to exclude if somehow invalid value
-                                    if expense.deleted_at.is_some() {
-                                        return false;
-                                    }
-                                }
-                            }
-                            
-                            // Check category filter first
-                            if let Some(ref category_ids) = args.category_ids {
-                                if !category_ids.contains(&expense.category.id) {
-                                    return false;
-                                }
-                            }
-                            
-                            // Then check text search if present
-                            if let Some(ref search_lower) = search_lower {
-                                for field in &search_fields {
-                                    match field.as_str() {
-                                        "description" => {
-                                            if expense.description.to_lowercase().contains(search_lower) {
-                                                return true;
-                                            }
-                                        },
-                                        "details" => {
-                                            if expense.details.as_ref().map_or(false, |d| d.to_lowercase().contains(search_lower)) {
-                                                return true;
-                                            }
-                                        },
-                                        "category" => {
-                                            if expense.category.name.to_lowercase().contains(search_lower) {
-                                                return true;
-                                            }
-                                        },
-                                        _ => {}
-                                    }
-                                }
-                                // If search text was provided but no match found, exclude this expense
-                                return false;
-                            }
-                            
-                            // If no search text but category matched (or no filters), include it
-                            true
-                        });
-                        
-                        // Add matches to our results
-                        for expense in batch {
-                            expenses.push(expense);
-                            if let Some(limit) = desired_count {
-                                if expenses.len() >= limit {
-                                    break;
-                                }
-                            }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "check_group_currencies",
+                "description": "Find a group's expenses recorded in a currency other than the one it mostly uses (or the one given), such as a dinner entered in USD in a trip kept in ARS. With suggest_conversions, each one gets its amount converted at the rate of its own date and an update_expense call that would record it in the group's currency; pass the mismatches to apply_fixes to make the changes. Nothing is changed by this tool",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group to check"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "The currency the group should use (default: the one most of its expenses are in)"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "suggest_conversions": {
+                            "type": "boolean",
+                            "description": "Convert each mismatched expense into the group's currency and suggest the update (default false)"
+                        },
+                        "rate_date": {
+                            "type": "string",
+                            "description": "Which exchange rate conversions use: 'expense_date' (default, the historical rate on each expense's date) or 'latest' (today's rate)",
+                            "enum": ["expense_date", "latest"]
                         }
-                        
-                        // If the original batch was empty, we've reached the end
-                        if !batch_had_results {
-                            break;
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "apply_fixes",
+                "description": "Apply fixes from audit_data_quality in one batch. Pass the audit's issues (all or some) as they are; each runs its suggested update_expense or delete_expense call. Fixes that need a decision (a category, a friend) run as update_expense with the fields given in `set`. Dry run by default: shows what would change; call again with dry_run: false to apply. Reports the outcome of each item",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "fixes": {
+                            "type": "array",
+                            "items": {"type": "object"},
+                            "description": "Issues from audit_data_quality, or {expense_id, tool, arguments} items. Add `set` (e.g. {\"category_name\": \"Taxi\"}) to update the expense with those fields instead of the suggested call"
+                        },
+                        "dry_run": {
+                            "type": "boolean",
+                            "description": "Only show what would be done (default true)"
                         }
-                        
-                        current_offset += batch_size;
-                    }
-                    
-                    // Truncate to requested limit if there is one
-                    if let Some(limit) = desired_count {
-                        expenses.truncate(limit);
-                    }
-                } else {
-                    // No search or category filter, but still need to handle deleted filtering properly with limit
-                    
-                    // If we're filtering deleted expenses AND have a limit, we need to fetch in batches
-                    // to ensure we get enough non-deleted results
-                    if include_deleted != "include" && args.limit.is_some() {
-                        let desired_count = args.limit.map(|l| l as usize);
-                        let batch_size = 100;
-                        let mut current_offset = args.offset.unwrap_or(0);
-                        
-                        loop {
-                            // If we have a limit and reached it, stop
-                            if let Some(limit) = desired_count {
-                                if expenses.len() >= limit {
-                                    break;
-                                }
-                            }
-                            
-                            let params = ListExpensesParams {
-                                group_id: args.group_id,
-                                friend_id: args.friend_id,
-                                dated_after: args.dated_after.clone(),
-                                dated_before: args.dated_before.clone(),
-                                updated_after: None,
-                                updated_before: None,
-                                limit: Some(batch_size),
-                                offset: Some(current_offset),
-                            };
-                            
-                            let mut batch = self.client.get_expenses(params).await?;
-                            let batch_had_results = !batch.is_empty();
-                            
-                            // Apply deleted expense filtering
-                            match include_deleted {
-                                "exclude" => {
-                                    batch.retain(|expense| expense.deleted_at.is_none());
-                                },
-                                "only" => {
-                                    batch.retain(|expense| expense.deleted_at.is_some());
-                                },
-                                _ => {
-                                    // Default to exclude
-                                    batch.retain(|expense| expense.deleted_at.is_none());
-                                }
-                            }
-                            
-                            // Add filtered results
-                            for expense in batch {
-                                expenses.push(expense);
-                                if let Some(limit) = desired_count {
-                                    if expenses.len() >= limit {
-                                        break;
-                                    }
-                                }
-                            }
-                            
-                            // If the original batch was empty, we've reached the end
-                            if !batch_had_results {
-                                break;
-                            }
-                            
-                            current_offset += batch_size;
-                        }
-                        
-                        // Truncate to requested limit if there is one
-                        if let Some(limit) = desired_count {
-                            expenses.truncate(limit);
+                    },
+                    "required": ["fixes"]
+                }
+            }),
+            json!({
+                "name": "parse_statement",
+                "description": "Find shared expenses in pasted bank-statement or receipt text. The server asks your client's model to read the text (MCP sampling, so the client must support it and may ask the user to approve), then checks each candidate: a positive amount that appears in the text, a real date not in the future, a description and a currency. Returns the candidates; to add them, pass them back (edited if needed) as `candidates` with group_id or group_name, create: true and, once the user agrees, confirm: true. Only valid candidates marked shared are created, split equally in the group",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "text": {
+                            "type": "string",
+                            "description": "Statement or receipt text as pasted by the user"
+                        },
+                        "file": {
+                            "type": "string",
+                            "description": "Path of a text or CSV statement to read instead of text; it must be inside a directory the client shared (MCP roots), or over HTTP one the server allows"
+                        },
+                        "candidates": {
+                            "type": "array",
+                            "items": {"type": "object"},
+                            "description": "Candidates from an earlier call ({date, description, amount, currency_code, shared}), instead of text"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency of amounts that don't state one (default: your default currency)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group to create the expenses in"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "create": {
+                            "type": "boolean",
+                            "description": "Create the valid shared candidates (needs confirm: true)"
+                        },
+                        "confirm": {
+                            "type": "boolean",
+                            "description": "Set after the user agreed to the expenses being created"
                         }
-                    } else {
-                        // Simple case: include all deleted or no limit specified
-                        let params = ListExpensesParams {
-                            group_id: args.group_id,
-                            friend_id: args.friend_id,
-                            dated_after: args.dated_after,
-                            dated_before: args.dated_before,
-                            updated_after: None,
-                            updated_before: None,
-                            limit: args.limit,
-                            offset: args.offset,
-                        };
-                        expenses = self.client.get_expenses(params).await?;
-                        
-                        // Apply deleted expense filtering if not including all
-                        if include_deleted != "include" {
-                            match include_deleted {
-                                "exclude" => {
-                                    expenses.retain(|expense| expense.deleted_at.is_none());
-                                },
-                                "only" => {
-                                    expenses.retain(|expense| expense.deleted_at.is_some());
-                                },
-                                _ => {
-                                    // Default to exclude
-                                    expenses.retain(|expense| expense.deleted_at.is_none());
-                                }
-                            }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "create_expense_from_text",
+                "description": "Propose an expense from a pasted receipt or forwarded order email, read by fixed rules (totals, dates, currency symbols, the merchant) rather than a model, so it works with any client. Returns the proposed cost, currency, date and description with a confidence and the line each came from; nothing is created until you call again with confirm: true after the user checked it",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "text": {
+                            "type": "string",
+                            "description": "Receipt or email text as pasted by the user"
+                        },
+                        "file": {
+                            "type": "string",
+                            "description": "Path of a text or .eml file to read instead of text; it must be inside a directory the client shared (MCP roots), or over HTTP one the server allows"
+                        },
+                        "cost": {
+                            "type": "string",
+                            "description": "Correction: the cost to use instead of the total read from the text"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "Correction: the description to use instead of the merchant read from the text"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Correction: the date (YYYY-MM-DD) to use instead of the one read from the text"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Correction: the currency to use instead of the one read from the text (default when none is shown: your default currency)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group to create the expense in"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "create_arguments": {
+                            "type": "object",
+                            "description": "Other create_expense arguments for the expense, e.g. {\"category_name\": \"groceries\", \"split_among\": [\"me\", \"Ana\"]}"
+                        },
+                        "confirm": {
+                            "type": "boolean",
+                            "description": "Create the expense; set only after the user agreed to the proposal"
                         }
-                    }
+                    },
+                    "required": []
                 }
-                
-                // Filter to requested fields
-                let filtered: Vec<serde_json::Value> = expenses.into_iter().map(|exp| {
-                    let mut obj = serde_json::Map::new();
-                    for field in &args.fields {
-                        match field.as_str() {
-                            "id" => { obj.insert("id".to_string(), json!(exp.id)); },
-                            "description" => { obj.insert("description".to_string(), json!(exp.description)); },
-                            "cost" => { obj.insert("cost".to_string(), json!(exp.cost)); },
-                            "currency_code" => { obj.insert("currency_code".to_string(), json!(exp.currency_code)); },
-                            "date" => { obj.insert("date".to_string(), json!(exp.date)); },
-                            "category" => { 
-                                obj.insert("category".to_string(), json!({"id": exp.category.id, "name": exp.category.name}));
-                            },
-                            "payment" => { obj.insert("payment".to_string(), json!(exp.payment)); },
-                            "group_id" => { obj.insert("group_id".to_string(), json!(exp.group_id)); },
-                            "friendship_id" => { obj.insert("friendship_id".to_string(), json!(exp.friendship_id)); },
-                            "details" => { obj.insert("details".to_string(), json!(exp.details)); },
-                            "users" => { obj.insert("users".to_string(), json!(exp.users)); },
-                            "repayments" => { obj.insert("repayments".to_string(), json!(exp.repayments)); },
-                            "created_at" => { obj.insert("created_at".to_string(), json!(exp.created_at)); },
-                            "created_by" => { obj.insert("created_by".to_string(), json!(exp.created_by)); },
-                            "updated_at" => { obj.insert("updated_at".to_string(), json!(exp.updated_at)); },
-                            "updated_by" => { obj.insert("updated_by".to_string(), json!(exp.updated_by)); },
-                            "deleted_at" => { 
-                                if exp.deleted_at.is_some() {
-                                    obj.insert("deleted_at".to_string(), json!(exp.deleted_at));
-                                }
-                            },
-                            "deleted_by" => { 
-                                if exp.deleted_by.is_some() {
-                                    obj.insert("deleted_by".to_string(), json!(exp.deleted_by));
-                                }
-                            },
-                            "receipt" => { obj.insert("receipt".to_string(), json!(exp.receipt)); },
-                            "comments_count" => { obj.insert("comments_count".to_string(), json!(exp.comments_count)); },
-                            "transaction_confirmed" => { obj.insert("transaction_confirmed".to_string(), json!(exp.transaction_confirmed)); },
-                            "transaction_id" => { obj.insert("transaction_id".to_string(), json!(exp.transaction_id)); },
-                            "transaction_method" => { obj.insert("transaction_method".to_string(), json!(exp.transaction_method)); },
-                            "transaction_status" => { obj.insert("transaction_status".to_string(), json!(exp.transaction_status)); },
-                            "repeats" => { obj.insert("repeats".to_string(), json!(exp.repeats)); },
-                            "repeat_interval" => { obj.insert("repeat_interval".to_string(), json!(exp.repeat_interval)); },
-                            "next_repeat" => { obj.insert("next_repeat".to_string(), json!(exp.next_repeat)); },
-                            "email_reminder" => { obj.insert("email_reminder".to_string(), json!(exp.email_reminder)); },
-                            "email_reminder_in_advance" => { obj.insert("email_reminder_in_advance".to_string(), json!(exp.email_reminder_in_advance)); },
-                            "expense_bundle_id" => { obj.insert("expense_bundle_id".to_string(), json!(exp.expense_bundle_id)); },
-                            _ => {}
-                        }
-                    }
-                    serde_json::Value::Object(obj)
-                }).collect();
-                Ok(serde_json::Value::Array(filtered))
-            }
-            "get_expense" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    expense_id: i64,
-                    fields: Vec<String>,  // Now required
+            }),
+            json!({
+                "name": "match_transactions",
+                "description": "Reconcile a bank or card statement against Splitwise: pairs each transaction (date, amount, description) with an expense you paid whose amount is within the amount tolerance, whose date is within the date window and, if a minimum is given, whose description is alike enough, each expense used once. Reports the matches, the transactions with no expense (not in Splitwise yet, or personal) and the expenses you paid in the statement's period with no transaction (paid some other way). Amounts are compared with what you paid; their sign is ignored. explain_match shows why one transaction did or didn't match. For a shared card, create_unmatched adds the unmatched transactions to the group once confirmed",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "transactions": {
+                            "type": "array",
+                            "items": {"type": "object"},
+                            "description": "Statement lines: {date: YYYY-MM-DD, amount, description}, with shared: false on personal ones when creating the unmatched"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency of the statement; only expenses in it are matched (default: your default currency)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only match expenses in this group"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "date_window_days": {
+                            "type": "integer",
+                            "description": "How many days apart a transaction and its expense may be (default 3, at most 365)"
+                        },
+                        "amount_tolerance": {
+                            "type": "string",
+                            "description": "How much a transaction's amount may differ from what you paid (default 0.01)"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "First day of the statement period (YYYY-MM-DD; default: the earliest transaction)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Last day of the statement period (YYYY-MM-DD; default: the latest transaction)"
+                        },
+                        "min_description_similarity": {
+                            "type": "number",
+                            "description": "How alike descriptions must be to match, from 0 to 1: the share of the shorter one's words found in the other, ignoring card noise such as '*TRIP 1234' (default 0: descriptions only break ties)"
+                        },
+                        "explain_match": {
+                            "type": "integer",
+                            "description": "Index of a transaction to explain: the closest expenses, and why each did or didn't match it"
+                        },
+                        "create_unmatched": {
+                            "type": "boolean",
+                            "description": "Create the unmatched transactions as expenses in the group (those not marked shared: false), paid by you (needs group_id or group_name and confirm: true)"
+                        },
+                        "confirm": {
+                            "type": "boolean",
+                            "description": "Set after the user agreed to the expenses being created"
+                        }
+                    },
+                    "required": ["transactions"]
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let expense = self.client.get_expense(args.expense_id).await?;
-                
-                // Filter to requested fields
-                let mut obj = serde_json::Map::new();
-                for field in &args.fields {
-                    match field.as_str() {
-                            "id" => { obj.insert("id".to_string(), json!(expense.id)); },
-                            "description" => { obj.insert("description".to_string(), json!(expense.description)); },
-                            "cost" => { obj.insert("cost".to_string(), json!(expense.cost)); },
-                            "currency_code" => { obj.insert("currency_code".to_string(), json!(expense.currency_code)); },
-                            "date" => { obj.insert("date".to_string(), json!(expense.date)); },
-                            "category" => { 
-                                obj.insert("category".to_string(), json!({"id": expense.category.id, "name": expense.category.name}));
-                            },
-                            "payment" => { obj.insert("payment".to_string(), json!(expense.payment)); },
-                            "group_id" => { obj.insert("group_id".to_string(), json!(expense.group_id)); },
-                            "friendship_id" => { obj.insert("friendship_id".to_string(), json!(expense.friendship_id)); },
-                            "details" => { obj.insert("details".to_string(), json!(expense.details)); },
-                            "users" => { obj.insert("users".to_string(), json!(expense.users)); },
-                            "repayments" => { obj.insert("repayments".to_string(), json!(expense.repayments)); },
-                            "created_at" => { obj.insert("created_at".to_string(), json!(expense.created_at)); },
-                            "created_by" => { obj.insert("created_by".to_string(), json!(expense.created_by)); },
-                            "updated_at" => { obj.insert("updated_at".to_string(), json!(expense.updated_at)); },
-                            "updated_by" => { obj.insert("updated_by".to_string(), json!(expense.updated_by)); },
-                            "deleted_at" => { 
-                                if expense.deleted_at.is_some() {
-                                    obj.insert("deleted_at".to_string(), json!(expense.deleted_at));
-                                }
-                            },
-                            "deleted_by" => { 
-                                if expense.deleted_by.is_some() {
-                                    obj.insert("deleted_by".to_string(), json!(expense.deleted_by));
-                                }
-                            },
-                            "receipt" => { obj.insert("receipt".to_string(), json!(expense.receipt)); },
-                            "comments_count" => { obj.insert("comments_count".to_string(), json!(expense.comments_count)); },
-                            "transaction_confirmed" => { obj.insert("transaction_confirmed".to_string(), json!(expense.transaction_confirmed)); },
-                            "transaction_id" => { obj.insert("transaction_id".to_string(), json!(expense.transaction_id)); },
-                            "transaction_method" => { obj.insert("transaction_method".to_string(), json!(expense.transaction_method)); },
-                            "transaction_status" => { obj.insert("transaction_status".to_string(), json!(expense.transaction_status)); },
-                            "repeats" => { obj.insert("repeats".to_string(), json!(expense.repeats)); },
-                            "repeat_interval" => { obj.insert("repeat_interval".to_string(), json!(expense.repeat_interval)); },
-                            "next_repeat" => { obj.insert("next_repeat".to_string(), json!(expense.next_repeat)); },
-                            "email_reminder" => { obj.insert("email_reminder".to_string(), json!(expense.email_reminder)); },
-                            "email_reminder_in_advance" => { obj.insert("email_reminder_in_advance".to_string(), json!(expense.email_reminder_in_advance)); },
-                            "expense_bundle_id" => { obj.insert("expense_bundle_id".to_string(), json!(expense.expense_bundle_id)); },
-                            _ => {}
-                    }
-                }
-                Ok(serde_json::Value::Object(obj))
-            }
-            "create_expense" => {
-                #[derive(Deserialize)]
-                struct ShareInput {
-                    user_id: Option<i64>,
-                    email: Option<String>,
-                    first_name: Option<String>,
-                    last_name: Option<String>,
-                    paid_share: String,
-                    owed_share: String,
+            }),
+            json!({
+                "name": "mark_reimbursable",
+                "description": "Mark expenses as to be paid back outside Splitwise (e.g. by an employer). Records the payer and the amount expected (the user's share unless given) and tags them 'reimbursable'",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_ids": {
+                            "type": "array",
+                            "items": {"type": "integer"}
+                        },
+                        "payer": {
+                            "type": "string",
+                            "description": "Who will pay the money back, e.g. 'Acme Corp'"
+                        },
+                        "amount": {
+                            "type": "string",
+                            "description": "Amount expected back, when marking a single expense and it differs from the user's share"
+                        }
+                    },
+                    "required": ["expense_ids", "payer"]
                 }
-                
-                #[derive(Deserialize)]
-                struct Args {
-                    cost: String,
-                    description: String,
-                    currency_code: Option<String>,
-                    group_id: Option<i64>,
-                    split_equally: Option<bool>,
-                    split_by_shares: Option<Vec<ShareInput>>,
-                    date: Option<String>,
-                    category_id: Option<i64>,
-                    details: Option<String>,
+            }),
+            json!({
+                "name": "list_pending_reimbursements",
+                "description": "Expenses marked reimbursable and not yet paid back, with totals per payer and month",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "payer": {
+                            "type": "string",
+                            "description": "Only reimbursements expected from this payer"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        }
+                    },
+                    "required": []
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                
-                // Convert ShareInput to ExpenseShare
-                let split_by_shares = args.split_by_shares.map(|shares| {
-                    shares.into_iter().map(|s| ExpenseShare {
-                        user_id: s.user_id,
-                        email: s.email,
-                        first_name: s.first_name,
-                        last_name: s.last_name,
-                        paid_share: s.paid_share,
-                        owed_share: s.owed_share,
-                    }).collect()
-                });
-                
-                // If shares are provided, split_equally should be false
-                let split_equally = if split_by_shares.is_some() {
-                    Some(false)
-                } else {
-                    args.split_equally.or(Some(true))
-                };
-                
-                let request = CreateExpenseRequest {
-                    cost: args.cost,
-                    description: args.description,
-                    currency_code: args.currency_code,
-                    category_id: args.category_id,
-                    date: args.date,
-                    repeat_interval: None,
-                    details: args.details,
-                    payment: Some(false),
-                    group_id: args.group_id,
-                    split_equally,
-                    split_by_shares,
-                };
-                let expenses = self.client.create_expense(request).await?;
-                // Return simplified response with just essential info
-                let simplified = if let Some(expense) = expenses.first() {
-                    json!({
-                        "success": true,
-                        "id": expense.id,
-                        "description": expense.description,
-                        "cost": expense.cost,
-                        "created_at": expense.created_at,
-                        "split": expense.users.iter().map(|u| json!({
-                            "name": u.user.as_ref().map(|user| &user.first_name),
-                            "paid": u.paid_share,
-                            "owes": u.owed_share
-                        })).collect::<Vec<_>>()
-                    })
-                } else {
-                    json!({ "success": true })
-                };
-                Ok(simplified)
-            }
-            "update_expense" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    expense_id: i64,
-                    cost: Option<String>,
-                    description: Option<String>,
-                    currency_code: Option<String>,
-                    category_id: Option<i64>,
-                    date: Option<String>,
-                    split_equally: Option<bool>,
-                    split_by_shares: Option<Vec<ExpenseShare>>,
-                }
-                let args: Args = serde_json::from_value(arguments)?;
-                let request = UpdateExpenseRequest {
-                    cost: args.cost,
-                    description: args.description,
-                    currency_code: args.currency_code,
-                    category_id: args.category_id,
-                    date: args.date,
-                    details: None,
-                    payment: None,
-                    group_id: None,
-                    split_equally: args.split_equally,
-                    split_by_shares: args.split_by_shares,
-                };
-                let expenses = self.client.update_expense(args.expense_id, request).await?;
-                // Return simplified response with just essential info
-                let simplified = if let Some(expense) = expenses.first() {
-                    json!({
-                        "success": true,
-                        "id": expense.id,
-                        "description": expense.description,
-                        "cost": expense.cost,
-                        "updated_at": expense.updated_at,
-                        "split": expense.users.iter().map(|u| json!({
-                            "name": u.user.as_ref().map(|user| &user.first_name),
-                            "paid": u.paid_share,
-                            "owes": u.owed_share
-                        })).collect::<Vec<_>>()
-                    })
-                } else {
-                    json!({ "success": true })
-                };
-                Ok(simplified)
-            }
-            "delete_expense" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    expense_id: i64,
+            }),
+            json!({
+                "name": "mark_reimbursed",
+                "description": "Record that reimbursable expenses have been paid back; they move from the 'reimbursable' tag to 'reimbursed'",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_ids": {
+                            "type": "array",
+                            "items": {"type": "integer"}
+                        },
+                        "reimbursed_on": {
+                            "type": "string",
+                            "description": "Date the money arrived (YYYY-MM-DD, default: today)"
+                        }
+                    },
+                    "required": ["expense_ids"]
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let success = self.client.delete_expense(args.expense_id).await?;
-                Ok(json!({ "success": success }))
-            }
-            // Friend tools
-            "list_friends" => {
-                let friends = self.client.get_friends().await?;
-                Ok(serde_json::to_value(friends)?)
-            }
-            "get_friend" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    friend_id: i64,
+            }),
+            json!({
+                "name": "get_expense_history",
+                "description": "Who created, edited and deleted an expense and when, with the field-level changes of each edit (from Splitwise's own change log). Answers questions like 'who changed the rent expense and what did they change?'",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The ID of the expense"
+                        }
+                    },
+                    "required": ["expense_id"]
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let friend = self.client.get_friend(args.friend_id).await?;
-                Ok(serde_json::to_value(friend)?)
-            }
-            "add_friend" => {
-                #[derive(Deserialize)]
-                struct Args {
-                    email: String,
+            }),
+            json!({
+                "name": "recent_comments",
+                "description": "Comments people left on recently updated expenses, newest first, across all groups or one. Your own comments say whether anyone answered after them, and others' say whether they came after one of yours, so 'did anyone reply to my note about the electricity bill?' can be answered. Expenses without comments are skipped",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "days": {
+                            "type": "integer",
+                            "description": "Look at expenses updated in the last this many days (default 30, at most 3650)"
+                        },
+                        "search": {
+                            "type": "string",
+                            "description": "Only comments whose text or expense description contains this"
+                        },
+                        "include_system": {
+                            "type": "boolean",
+                            "description": "Also list Splitwise's own change-log comments (default false)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Most comments to return (default 20)"
+                        }
+                    },
+                    "required": []
                 }
-                let args: Args = serde_json::from_value(arguments)?;
-                let friends = self.client.create_friend(args.email).await?;
+            }),
+            json!({
+                "name": "list_recurring_expenses",
+                "description": "List recurring expenses (weekly, monthly, yearly...) with their next charge date. With mode 'forecast_recurring' it also projects the next occurrences and totals per month, showing upcoming committed shared costs",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only recurring expenses in this group"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "mode": {
+                            "type": "string",
+                            "description": "'list' (default) or 'forecast_recurring' to add projected occurrences and monthly totals",
+                            "enum": ["list", "forecast_recurring"]
+                        },
+                        "occurrences": {
+                            "type": "integer",
+                            "description": "How many upcoming occurrences of each expense to project in forecast mode. Default: 3"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "export_all_history",
+                "description": "Start exporting the entire expense history (deleted expenses included) as a background job, for accounts too large to list in one call. It checkpoints to disk after every batch of 100 and resumes from the checkpoint after a restart: call again to resume an interrupted or failed export. Poll get_export_status, then read pages with get_export_result",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "restart": {
+                            "type": "boolean",
+                            "description": "Discard the previous export and start over (default false; a finished export is otherwise kept)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_export_status",
+                "description": "Progress of the history export: running, interrupted (by a restart; call export_all_history to resume), failed (with the error) or done, with expenses fetched so far",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_export_result",
+                "description": "A page of exported expenses, oldest first and deduplicated, with complete=false while the export is still going. Returns {items, returned, scanned, has_more, next_offset, total, complete, data_file}. With CATEGORY_MAPPING_FILE set, each expense also has the account code of its category",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "offset": {
+                            "type": "integer",
+                            "description": "Where to start: the next_offset of the previous page (default 0)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Expenses per page (default 100, max 1000)"
+                        },
+                        "fields": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Only return these fields, as in list_expenses (e.g. [\"id\", \"date\", \"cost\", \"category.name\"]). Default: everything"
+                        },
+                        "save_to": {
+                            "type": "string",
+                            "description": "Write everything exported so far to this file instead of returning a page: CSV if it ends in .csv, otherwise JSON Lines. It must be inside a directory the client shared (MCP roots), or over HTTP one the server allows"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "export_ynab_csv",
+                "description": "Your share of expenses as a CSV in YNAB's import format (Date, Payee, Memo, Outflow, Inflow), so a budget kept in YNAB follows what you spent through Splitwise. Outflow is what you owe of each expense, whoever paid it; payments (settle-ups) and other currencies are left out. The memo is the category's code from CATEGORY_MAPPING_FILE (the Splitwise category when it has none) and the group",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses (default: all)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency of the YNAB budget (default: your default currency)"
+                        },
+                        "save_to": {
+                            "type": "string",
+                            "description": "Write the CSV to this file instead of returning it. It must be inside a directory the client shared (MCP roots), or over HTTP one the server allows"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "generate_statement_pdf",
+                "description": "A PDF statement of a group for a period, to share with a landlord, flatmates or a trip: every expense with who paid it, totals and what each member paid and consumed in the period, the settle-ups made, current balances and who should pay whom to settle up. Returned as base64, or written to a file",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group to report on"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "save_to": {
+                            "type": "string",
+                            "description": "Write the PDF to this file instead of returning it. It must be inside a directory the client shared (MCP roots), or over HTTP one the server allows"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "sync_now",
+                "description": "Catch up with what changed in Splitwise since the last sync: fetches only the expenses updated after each group's checkpoint and reports which were created, updated, deleted or restored, then moves the checkpoint. The first sync of a group reads all of it; deleted expense IDs are kept as tombstones. Returns {synced_at, groups: [{group_id, group_name, complete, first_sync, changes, changed_ids, updated_after}]}",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only sync this group (default: every group)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_sync_status",
+                "description": "Where sync_now stands, without calling Splitwise for changes: each group's checkpoint (the latest update seen), when it last synced and how many deleted expenses it remembers, and the groups never synced. Given a group, also lists its tombstones (deleted expense ID -> when)",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group whose tombstones to list"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "list_pending_writes",
+                "description": "Expense writes (create, update, delete) waiting to be sent because Splitwise couldn't be reached when they were made (with QUEUE_OFFLINE_WRITES on), oldest first, with the last error and any conflict holding one back",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "flush_pending_writes",
+                "description": "Send the queued expense writes now, in the order they were made. Creates are retried with their idempotency key, so none is made twice. An update or delete of an expense changed in Splitwise after it was queued is held back as a conflict unless force is set. Stops at the first write Splitwise still can't be reached for",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "keys": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Only send these writes (their keys from list_pending_writes; default: all)"
+                        },
+                        "force": {
+                            "type": "boolean",
+                            "description": "Send conflicting updates and deletes anyway, overwriting the newer change in Splitwise"
+                        },
+                        "discard": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Keys of writes to drop from the queue without sending them"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "create_expense",
+                "description": "Create a new expense. IMPORTANT: Always call get_categories first to choose the most appropriate category/subcategory ID for the expense type. Categories determine the icon shown in Splitwise.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "cost": {
+                            "type": "string",
+                            "description": "Total cost of the expense (e.g., '25.00')"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "Description of the expense"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency code (e.g., 'USD', 'EUR'). When left out, a currency the description names (\"€40 taxi\", \"ARS 5000\") is used, else your default currency"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group ID to add expense to"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "split_equally": {
+                            "type": "boolean",
+                            "description": "Whether to split equally among all group members. Default: true. Set to false when using split_by_shares."
+                        },
+                        "split_by_shares": {
+                            "type": "array",
+                            "description": "Custom split amounts. Each entry specifies a user and their paid/owed amounts. Use this for unequal splits or when multiple people pay. Without any split argument, a group with a split profile (set_group_split_profile) is split by its profile; pass split_equally: true to split it equally instead",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "user_id": {
+                                        "type": "integer",
+                                        "description": "User ID (get from list_friends or get_group)"
+                                    },
+                                    "user_name": {
+                                        "type": "string",
+                                        "description": "User name as an alternative to user_id; matched loosely ('me' is the current user)"
+                                    },
+                                    "email": {
+                                        "type": "string",
+                                        "description": "User email (alternative to user_id). Someone who is not a friend yet is added and invited by Splitwise"
+                                    },
+                                    "first_name": {
+                                        "type": "string",
+                                        "description": "First name for an invited email (default: taken from the email address)"
+                                    },
+                                    "last_name": {
+                                        "type": "string",
+                                        "description": "Last name for an invited email"
+                                    },
+                                    "paid_share": {
+                                        "type": "string",
+                                        "description": "Amount this user paid (e.g., '50.00')"
+                                    },
+                                    "owed_share": {
+                                        "type": "string",
+                                        "description": "Amount this user owes (e.g., '25.00')"
+                                    }
+                                },
+                                "required": ["paid_share", "owed_share"]
+                            }
+                        },
+                        "split_among": {
+                            "type": "array",
+                            "description": "Split equally among exactly these people, whether or not they are in the group. Each entry is a user ID, an email or a name ('me' is the current user). Cannot be combined with split_by_shares",
+                            "items": {"type": ["integer", "string"]}
+                        },
+                        "paid_by": {
+                            "type": ["integer", "string", "object"],
+                            "description": "Who paid: one person (user ID, email or name) who paid the whole cost, or an object mapping people to the amounts they paid, e.g. {\"me\": \"120\", \"Ana\": \"80\"}; the amounts must add up to the cost. Owed shares come from split_among, prorate_by_attendance or an equal split of the group. Default: the current user. Not used with split_by_shares"
+                        },
+                        "prorate_by_attendance": {
+                            "type": "object",
+                            "description": "Split by nights present instead: each member's owed share is proportional to the nights between their arrival and departure (for trip accommodation and shared costs). Cannot be combined with split_by_shares",
+                            "properties": {
+                                "attendance": {
+                                    "type": "array",
+                                    "items": {
+                                        "type": "object",
+                                        "properties": {
+                                            "user_id": {"type": "integer"},
+                                            "user_name": {"type": "string", "description": "Name as an alternative to user_id ('me' is the current user)"},
+                                            "arrival": {"type": "string", "description": "Arrival date (YYYY-MM-DD)"},
+                                            "departure": {"type": "string", "description": "Departure date (YYYY-MM-DD)"}
+                                        },
+                                        "required": ["arrival", "departure"]
+                                    }
+                                },
+                                "paid_by": {
+                                    "type": "string",
+                                    "description": "Name of who paid (default: the current user)"
+                                }
+                            },
+                            "required": ["attendance"]
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Date of the expense (YYYY-MM-DD)"
+                        },
+                        "category_id": {
+                            "type": "integer",
+                            "description": "Category or subcategory ID from get_categories. Use the most specific subcategory when possible (e.g., 13 for Restaurants instead of 25 for Food). Required for proper icon display."
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name as an alternative to category_id; matched loosely (e.g. 'restaurants', 'groceries')"
+                        },
+                        "tags": {
+                            "type": "array",
+                            "description": "Tags to attach (e.g. 'wedding', 'reimbursable'). Stored as a metadata line at the end of the expense notes",
+                            "items": {"type": "string"}
+                        },
+                        "external_id": {
+                            "type": "string",
+                            "description": "Reference in another system (invoice number, bank transaction ID), stored in the notes metadata"
+                        },
+                        "import_source": {
+                            "type": "string",
+                            "description": "Where the expense came from (e.g. 'bank-import'), stored in the notes metadata"
+                        },
+                        "idempotency_key": {
+                            "type": "string",
+                            "description": "Makes retrying safe: when an expense was created with this key in the last 7 days, it is returned instead of creating another. Stored in the notes metadata"
+                        },
+                        "details": {
+                            "type": "string",
+                            "description": "Additional details about the expense"
+                        }
+                    },
+                    "required": ["cost", "description"]
+                }
+            }),
+            json!({
+                "name": "set_group_split_profile",
+                "description": "Save how a group's expenses are usually split, e.g. Casa 60/40 between two members, optionally per category (groceries 50/50 but rent 60/40). create_expense then uses it for that group whenever no split is given, and reports the rule it applied as split_profile. Stored on this server, not in Splitwise",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {"type": "integer"},
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "category_id": {
+                            "type": "integer",
+                            "description": "Set the split of this category only (e.g. Groceries 50/50 while the group default is 60/40). A parent category covers all of its subcategories; a subcategory's own split wins over its parent's"
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name as an alternative to category_id; matched loosely, parents included (e.g. 'home')"
+                        },
+                        "shares": {
+                            "type": "array",
+                            "description": "Each member who owes part of the group's expenses and their percent; the percents must add up to 100. Members left out owe nothing",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "user_id": {"type": "integer"},
+                                    "user_name": {"type": "string", "description": "Name as an alternative to user_id ('me' is the current user)"},
+                                    "percent": {"type": "number", "description": "Percent of each expense this member owes (e.g. 60)"}
+                                },
+                                "required": ["percent"]
+                            }
+                        },
+                        "non_paying": {
+                            "type": "array",
+                            "description": "Members who never owe (kids, guests), as user IDs or names: equal splits of this group leave them out, and create_expense warns when a split gives them a share. Replaces the current list; [] clears it",
+                            "items": {"type": ["integer", "string"]}
+                        },
+                        "clear": {
+                            "type": "boolean",
+                            "description": "Remove the group's profile, so its expenses are split equally again; with a category, remove only that category's split"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_group_split_profile",
+                "description": "Show the split profile create_expense uses for a group, or every group's profile when no group is given",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {"type": "integer"},
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "set_member_alias",
+                "description": "Link user IDs that are the same person, such as the placeholder someone was invited as and the account they later signed up with, so contribution reports and statements count them as one. Aliases are kept locally across sessions; nothing changes in Splitwise",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "user_id": {
+                            "type": "integer",
+                            "description": "The account the aliases stand for"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Name of that account as an alternative to user_id"
+                        },
+                        "aliases": {
+                            "type": "array",
+                            "items": {"type": ["integer", "string"]},
+                            "description": "The other user IDs (or names) of the same person"
+                        },
+                        "remove": {
+                            "type": "boolean",
+                            "description": "Unlink the given aliases instead (default false)"
+                        }
+                    },
+                    "required": ["aliases"]
+                }
+            }),
+            json!({
+                "name": "list_member_aliases",
+                "description": "Show the user IDs linked with set_member_alias and the account each stands for",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "calculate_household_split",
+                "description": "Split shared bills (rent, utilities) between housemates by weights such as room size, income or days present, and return the exact owed shares per bill and per person. Pure calculation unless create is true, in which case each bill is created as an expense",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "members": {
+                            "type": "array",
+                            "description": "Housemates with their weights, e.g. {\"user_name\": \"Ana\", \"weights\": {\"room_size\": 14, \"income\": 3000}}",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "user_id": {"type": "integer"},
+                                    "user_name": {"type": "string", "description": "Name as an alternative to user_id; 'me' is the current user"},
+                                    "weights": {
+                                        "type": "object",
+                                        "description": "Weight per factor name; any names may be used as long as the bills refer to them",
+                                        "additionalProperties": {"type": "number"}
+                                    }
+                                }
+                            }
+                        },
+                        "bills": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "description": {"type": "string"},
+                                    "cost": {"type": ["string", "number"]},
+                                    "currency_code": {"type": "string"},
+                                    "split_by": {"type": "string", "description": "Weight factor for this bill, or 'equal' (default: the top-level split_by)"},
+                                    "paid_by": {"type": "string", "description": "Name of who paid (default: the current user)"},
+                                    "category_name": {"type": "string"},
+                                    "date": {"type": "string"}
+                                },
+                                "required": ["description", "cost"]
+                            }
+                        },
+                        "split_by": {
+                            "type": "string",
+                            "description": "Default weight factor for bills that don't name one (default: equal)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Household group the expenses belong to"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "create": {
+                            "type": "boolean",
+                            "description": "Create one expense per bill with the calculated shares (default: false)"
+                        }
+                    },
+                    "required": ["members", "bills"]
+                }
+            }),
+            json!({
+                "name": "quick_add_expense",
+                "description": "Create an expense from a plain sentence such as 'I paid 30 for pizza with Ana' using Splitwise's own parser. Lighter alternative to create_expense; with autosave false (default) it only returns the parsed draft for review",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "input": {
+                            "type": "string",
+                            "description": "The sentence describing the expense"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group the expense belongs to (helps the parser resolve names)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "Friend the expense is shared with (for non-group expenses)"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        },
+                        "autosave": {
+                            "type": "boolean",
+                            "description": "Save the expense if the parse is valid. Default: false (preview only)"
+                        }
+                    },
+                    "required": ["input"]
+                }
+            }),
+            json!({
+                "name": "split_with_friend",
+                "description": "Create a non-group expense shared with one friend, without working out the shares: give the friend, the cost and how it splits (even by default) and the server builds the two shares and creates the expense. The friend can be a name, a friend ID or an email; someone not on Splitwise yet is invited",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "The friend to split with"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id"
+                        },
+                        "email": {
+                            "type": "string",
+                            "description": "Friend's email as an alternative to friend_id"
+                        },
+                        "cost": {
+                            "type": "string",
+                            "description": "Total cost of the expense"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "What the expense was for"
+                        },
+                        "ratio": {
+                            "type": "string",
+                            "description": "Your part to the friend's, e.g. \"1:1\" (default), \"60:40\" or \"2:1\""
+                        },
+                        "paid_by": {
+                            "type": "string",
+                            "enum": ["me", "friend"],
+                            "description": "Who paid the whole cost (default: me)"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency code (default: your default currency)"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Date of the expense (YYYY-MM-DD, default today)"
+                        },
+                        "category_id": {
+                            "type": "integer",
+                            "description": "Category or subcategory ID from get_categories"
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name as an alternative to category_id"
+                        },
+                        "details": {
+                            "type": "string",
+                            "description": "Notes on the expense"
+                        }
+                    },
+                    "required": ["cost", "description"]
+                }
+            }),
+            json!({
+                "name": "iou",
+                "description": "Record money lent between you and a friend, e.g. \"I lent Juan 20\" (borrower: Juan) or \"Marcos covered my ticket, 15\" (lender: Marcos). Creates a non-group expense the lender paid in full and the borrower owes in full. Give whichever side is not you; the other side is you. A negative amount is read as its size, since the direction comes from lender and borrower",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "lender": {
+                            "type": ["integer", "string"],
+                            "description": "Who lent the money: user ID, email, name or \"me\" (default: me)"
+                        },
+                        "borrower": {
+                            "type": ["integer", "string"],
+                            "description": "Who received the money: user ID, email, name or \"me\" (default: me)"
+                        },
+                        "amount": {
+                            "type": ["number", "string"],
+                            "description": "How much was lent"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "What the money was for (default: Loan)"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency code (default: your default currency)"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Date of the expense (YYYY-MM-DD, default today)"
+                        }
+                    },
+                    "required": ["amount"]
+                }
+            }),
+            json!({
+                "name": "dinner_split",
+                "description": "Split a bill equally among only the people who were there, in one call: who paid, the total, the participants by name and an optional tip. The payer always gets a share, listed or not. Works in a group (only the participants are included) or outside one",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "total": {
+                            "type": ["number", "string"],
+                            "description": "The bill before the tip"
+                        },
+                        "participants": {
+                            "type": "array",
+                            "items": {"type": ["integer", "string"]},
+                            "description": "Everyone who shares the bill: user IDs, emails or names, \"me\" for you"
+                        },
+                        "paid_by": {
+                            "type": ["integer", "string"],
+                            "description": "Who paid the bill: user ID, email, name or \"me\" (default: me)"
+                        },
+                        "tip_percent": {
+                            "type": "number",
+                            "description": "Tip on top of the total, in percent (default 0)"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "What the bill was for (default: Dinner)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Group the expense belongs to (default: none)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency code (default: your default currency)"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Date of the expense (YYYY-MM-DD, default today)"
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name, e.g. Dining out"
+                        }
+                    },
+                    "required": ["total", "participants"]
+                }
+            }),
+            json!({
+                "name": "update_expense",
+                "description": "Update an existing expense including its split/division",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The ID of the expense to update"
+                        },
+                        "cost": {
+                            "type": "string",
+                            "description": "New total cost of the expense"
+                        },
+                        "description": {
+                            "type": "string",
+                            "description": "New description of the expense"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "New currency code"
+                        },
+                        "category_id": {
+                            "type": "integer",
+                            "description": "Category or subcategory ID from get_categories"
+                        },
+                        "category_name": {
+                            "type": "string",
+                            "description": "Category name as an alternative to category_id; matched loosely (e.g. 'restaurants', 'groceries')"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Move the expense to this group"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id; matched loosely (e.g. 'la casa')"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "New date (YYYY-MM-DD)"
+                        },
+                        "details": {
+                            "type": "string",
+                            "description": "New notes. Metadata (tags, external_id) already on the expense is kept"
+                        },
+                        "tags": {
+                            "type": "array",
+                            "description": "Replace the expense's tags",
+                            "items": {"type": "string"}
+                        },
+                        "external_id": {
+                            "type": "string",
+                            "description": "Set the external reference stored in the notes metadata"
+                        },
+                        "import_source": {
+                            "type": "string",
+                            "description": "Set the import source stored in the notes metadata"
+                        },
+                        "split_equally": {
+                            "type": "boolean",
+                            "description": "Whether to split equally among all group members. Set to false when using split_by_shares."
+                        },
+                        "split_by_shares": {
+                            "type": "array",
+                            "description": "Custom split amounts. Each entry specifies a user and their paid/owed amounts. Use this for unequal splits or when changing who pays.",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "user_id": {
+                                        "type": "integer",
+                                        "description": "User ID (get from list_friends or get_group)"
+                                    },
+                                    "user_name": {
+                                        "type": "string",
+                                        "description": "User name as an alternative to user_id; matched loosely ('me' is the current user)"
+                                    },
+                                    "email": {
+                                        "type": "string",
+                                        "description": "User email (alternative to user_id)"
+                                    },
+                                    "paid_share": {
+                                        "type": "string",
+                                        "description": "Amount this user paid (e.g., '50.00')"
+                                    },
+                                    "owed_share": {
+                                        "type": "string",
+                                        "description": "Amount this user owes (e.g., '25.00')"
+                                    }
+                                },
+                                "required": ["paid_share", "owed_share"]
+                            }
+                        }
+                    },
+                    "required": ["expense_id"]
+                }
+            }),
+            json!({
+                "name": "delete_expense",
+                "description": "Delete an expense",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "expense_id": {
+                            "type": "integer",
+                            "description": "The ID of the expense to delete"
+                        }
+                    },
+                    "required": ["expense_id"]
+                }
+            }),
+            // Friend tools
+            json!({
+                "name": "list_friends",
+                "description": "List all friends and their balances. Returns {items, returned, scanned, has_more, next_offset}",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "fields": {
+                            "type": "array",
+                            "description": "Only return these fields, e.g. [\"id\", \"first_name\", \"balance\"]. Dotted paths reach into nested objects and lists (\"groups.balance\"); braces pick several sub-fields (\"groups{group_id,balance}\"). Available: id, first_name, last_name, email, registration_status, picture, balance, groups (group_id, balance), updated_at. Default: everything",
+                            "items": {"type": "string"}
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_friend",
+                "description": "Get detailed information about a specific friend",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "The user ID of the friend"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        },
+                        "fields": {
+                            "type": "array",
+                            "description": "Only return these fields, e.g. [\"id\", \"first_name\", \"balance\"]. Dotted paths reach into nested objects and lists (\"groups.balance\"); braces pick several sub-fields (\"groups{group_id,balance}\"). Available: id, first_name, last_name, email, registration_status, picture, balance, groups (group_id, balance), updated_at. Default: everything",
+                            "items": {"type": "string"}
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "add_friend",
+                "description": "Add a new friend by email",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "email": {
+                            "type": "string",
+                            "description": "Email address of the friend to add"
+                        }
+                    },
+                    "required": ["email"]
+                }
+            }),
+            json!({
+                "name": "remove_friend",
+                "description": "Remove a friendship. Only possible when the balance with that friend is settled. Destructive: confirm with the user first",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "The user ID of the friend to remove"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id; matched loosely (e.g. 'Marcos N.')"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "find_stale_friendships",
+                "description": "List friends with a zero balance and no shared expense in the last N months. Can optionally remove them in one batch (requires confirm: true)",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "months": {
+                            "type": "integer",
+                            "description": "Inactivity window in months, 1 to 120 (default: 12)"
+                        },
+                        "remove": {
+                            "type": "boolean",
+                            "description": "Remove every stale friend found. Without confirm: true this only returns what would be removed"
+                        },
+                        "confirm": {
+                            "type": "boolean",
+                            "description": "Set to true (after the user agreed) to actually perform the removal"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "pending_invitations",
+                "description": "People the user shares expenses with who have not joined Splitwise yet (invited by email), with their balance and the expenses shared with them",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_group_invites",
+                "description": "How to bring people into a group: its invite link, which anyone can open to join, and the members who were invited by email but haven't joined Splitwise yet, with their balance in the group. Splitwise has no way to make a new link; this returns the group's own",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group to invite people to"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "resend_invite",
+                "description": "Invite a group member who hasn't joined Splitwise yet again: adds them to the group once more, which has Splitwise email them (Splitwise may hold back repeats sent close together), and returns a message with the group's invite link to send yourself as well",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "group_id": {
+                            "type": "integer",
+                            "description": "The group the member was invited to"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "user_id": {
+                            "type": "integer",
+                            "description": "The invited member"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Member's name as an alternative to user_id"
+                        },
+                        "email": {
+                            "type": "string",
+                            "description": "Member's email as an alternative to user_id"
+                        },
+                        "language": {
+                            "type": "string",
+                            "enum": ["en", "es"],
+                            "description": "Message language (default: en)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            // Utility tools
+            json!({
+                "name": "get_currencies",
+                "description": "Get list of supported currencies",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_common_currencies",
+                "description": "The few currencies worth offering when picking one, instead of all ~150 from get_currencies: your default currency, then the ones your recent expenses and open balances are in, topped up with widely used ones. Each says why it is listed",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "limit": {
+                            "type": "integer",
+                            "description": "How many currencies to return (default 10)"
+                        },
+                        "months": {
+                            "type": "integer",
+                            "description": "How many months of expenses to count, 1 to 120 (default 12)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "convert_currency",
+                "description": "Convert an amount (or a list of amounts in mixed currencies) into one target currency using live exchange rates. Useful to express mixed-currency balances as a single figure",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "amount": {
+                            "type": "string",
+                            "description": "Amount to convert (e.g., '125.50')"
+                        },
+                        "from": {
+                            "type": "string",
+                            "description": "Currency code of the amount (e.g., 'EUR')"
+                        },
+                        "to": {
+                            "type": "string",
+                            "description": "Target currency code. Defaults to the current user's default currency"
+                        },
+                        "date": {
+                            "type": "string",
+                            "description": "Use the historical rate of this date (YYYY-MM-DD) instead of the latest rate"
+                        },
+                        "amounts": {
+                            "type": "array",
+                            "description": "Several amounts to convert and total, as an alternative to amount/from",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "amount": {
+                                        "type": "string"
+                                    },
+                                    "currency_code": {
+                                        "type": "string"
+                                    }
+                                },
+                                "required": ["amount", "currency_code"]
+                            }
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_net_worth_of_balances",
+                "description": "Convert every friend and group balance into one currency (the user's default currency unless overridden) and return the single net figure plus the per-friend and per-group breakdown. Positive means others owe the user",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "currency": {
+                            "type": "string",
+                            "description": "Target currency code (default: the user's default currency)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "collections_list",
+                "description": "Who owes you, largest first: every friend with a positive balance toward you, with the amounts per currency and per group, when you last had activity together and a ready-to-send reminder. The \"whom should I chase\" view; amounts you owe are left out. Sorted by the total converted into one currency. Nothing is sent",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "currency": {
+                            "type": "string",
+                            "description": "Currency to rank and total the debts in (default: the user's default currency)"
+                        },
+                        "language": {
+                            "type": "string",
+                            "enum": ["en", "es"],
+                            "description": "Reminder language (default: en)"
+                        },
+                        "tone": {
+                            "type": "string",
+                            "enum": ["friendly", "neutral", "firm"],
+                            "description": "Reminder tone (default: friendly)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "payables_list",
+                "description": "What you owe, most pressing first: each debt to a friend per currency with how long it has been owed and its size converted into one currency. Debts over 60 days old come first, then 30-60, then newer ones, the largest first within each. With add_to_plan, every debt listed goes into your settlement plan, kept across sessions; debts that get settled drop out of the plan by themselves",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "currency": {
+                            "type": "string",
+                            "description": "Currency to rank and total the debts in (default: the user's default currency)"
+                        },
+                        "add_to_plan": {
+                            "type": "boolean",
+                            "description": "Add every debt listed to the settlement plan (default false)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "draft_reminder_message",
+                "description": "Compose a payment reminder for a friend or a group using live Splitwise balances, with the exact amounts per currency and how to settle up. Use this instead of writing amounts by hand. Returns the text only; nothing is sent",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "friend_id": {
+                            "type": "integer",
+                            "description": "Remind a friend of what they owe the user"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to friend_id"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Remind a group of every pending payment in it"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "language": {
+                            "type": "string",
+                            "enum": ["en", "es"],
+                            "description": "Message language (default: en)"
+                        },
+                        "tone": {
+                            "type": "string",
+                            "enum": ["friendly", "neutral", "firm"],
+                            "description": "Message tone (default: friendly)"
+                        },
+                        "settle_currency": {
+                            "type": "string",
+                            "description": "For a friend owing in several currencies, also offer the total converted into this currency"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "resolve_group",
+                "description": "Find the group meant by a loose name such as 'la casa' or 'bariloche'. Returns the confident match (if any) and ranked candidates with confidence scores",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "The group name as the user said it"
+                        }
+                    },
+                    "required": ["name"]
+                }
+            }),
+            json!({
+                "name": "resolve_user",
+                "description": "Find the friend or group member meant by a loose name such as 'Marcos N.' or 'ana'. Returns the confident match (if any) and ranked candidates with confidence scores",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "The person's name as the user said it"
+                        }
+                    },
+                    "required": ["name"]
+                }
+            }),
+            json!({
+                "name": "start_job",
+                "description": "Run another tool call as a background job, for long operations (full-history summaries, list_expenses over years, bulk changes such as find_stale_friendships with removal) that would otherwise time out. Returns a job_id at once; poll job_status and collect the answer with job_result",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tool": {
+                            "type": "string",
+                            "description": "Name of the tool to run, e.g. summarize_expenses"
+                        },
+                        "arguments": {
+                            "type": "object",
+                            "description": "Arguments for that tool, exactly as in a direct call"
+                        }
+                    },
+                    "required": ["tool"]
+                }
+            }),
+            json!({
+                "name": "job_status",
+                "description": "Status of a background job (running, done, failed with its error, or cancelled) with elapsed time. Without job_id, lists recent jobs",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "job_id": {
+                            "type": "string",
+                            "description": "ID returned by start_job, e.g. job-3"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "job_result",
+                "description": "Result of a finished background job, under `result`, as the tool itself would have returned it. Errors while the job is still running",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "job_id": {
+                            "type": "string",
+                            "description": "ID returned by start_job"
+                        }
+                    },
+                    "required": ["job_id"]
+                }
+            }),
+            json!({
+                "name": "cancel_job",
+                "description": "Stop a running background job. Changes a bulk job already made to Splitwise are not undone",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "job_id": {
+                            "type": "string",
+                            "description": "ID returned by start_job"
+                        }
+                    },
+                    "required": ["job_id"]
+                }
+            }),
+            json!({
+                "name": "server_status",
+                "description": "Diagnostics for this MCP server: uptime, transport, cache contents and ages, Splitwise calls made, rate-limit estimate, last upstream error and sync checkpoints. Use it to explain slow or stale answers",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "suggest_category",
+                "description": "Suggest a category for an expense description. Prefers categories the user chose before when correcting similar expenses, then known aliases, then category names",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "description": {
+                            "type": "string",
+                            "description": "The expense description, e.g. 'Expensas octubre'"
+                        }
+                    },
+                    "required": ["description"]
+                }
+            }),
+            json!({
+                "name": "find_category",
+                "description": "Find the category ID for a name or everyday word in English or Spanish (e.g. 'nafta' -> Gas/fuel, 'restaurants' -> Dining out). Without a query, returns every category flattened with its parent for easy selection",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Category name or alias to look for"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "render_chart",
+                "description": "Draw a chart as a PNG image, for clients that show images but can't chart: category_pie (spending per category), monthly_bar (spending per month) or balance_line (your balance at the end of each month; positive means you are owed). Spending is the group's total in a group, otherwise your share. Covers the last 12 months unless a period is given, in one currency. The data comes back too",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "chart": {
+                            "type": "string",
+                            "enum": ["category_pie", "monthly_bar", "balance_line"],
+                            "description": "Which chart to draw"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses (default: all)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "user_id": {
+                            "type": "integer",
+                            "description": "Only expenses with this friend; balance_line then shows the balance with them"
+                        },
+                        "user_name": {
+                            "type": "string",
+                            "description": "Friend's name as an alternative to user_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency to chart (default: your default currency)"
+                        },
+                        "title": {
+                            "type": "string",
+                            "description": "Title drawn on the chart (default: one describing it)"
+                        }
+                    },
+                    "required": ["chart"]
+                }
+            }),
+            json!({
+                "name": "spending_heatmap",
+                "description": "When spending happens: a matrix of weekdays (Monday first) against months or hours of the day, with each weekday's total and share, to spot patterns like \"we always overspend on Sundays\". Spending is the group's total in a group, otherwise your share; payments are left out. Covers the last 12 months unless a period is given, in one currency. Optionally also drawn as a PNG image",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "by": {
+                            "type": "string",
+                            "enum": ["month", "hour"],
+                            "description": "Columns of the matrix: month (default) or hour, from the time on each expense in the server's time zone (expenses entered with only a day all fall on one hour)"
+                        },
+                        "group_id": {
+                            "type": "integer",
+                            "description": "Only this group's expenses (default: all)"
+                        },
+                        "group_name": {
+                            "type": "string",
+                            "description": "Group name as an alternative to group_id"
+                        },
+                        "dated_after": {
+                            "type": "string",
+                            "description": "Only expenses on or after this date (YYYY-MM-DD)"
+                        },
+                        "dated_before": {
+                            "type": "string",
+                            "description": "Only expenses before this date (YYYY-MM-DD)"
+                        },
+                        "currency_code": {
+                            "type": "string",
+                            "description": "Currency to chart (default: your default currency)"
+                        },
+                        "image": {
+                            "type": "boolean",
+                            "description": "Also draw the matrix as a PNG heatmap (default false)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "validate_category_mapping",
+                "description": "Check the category mapping in CATEGORY_MAPPING_FILE, which gives each Splitwise category a code in another system (a YNAB category, a ledger account, a company expense code) for exports. Lists the categories left without a code and the entries that name no category, usually a typo",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "include_mapped": {
+                            "type": "boolean",
+                            "description": "Also list every category that has a code, with the code and the entry it came from (default false)"
+                        }
+                    },
+                    "required": []
+                }
+            }),
+            json!({
+                "name": "get_categories",
+                "description": "Get list of expense categories with their IDs. Each category has an associated icon in Splitwise (e.g., 25=Food has a restaurant icon, 31=Transportation has a car icon)",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }),
+        ];
+        // List and summary tools can answer as a text table or CSV (applied in handle_tool_call)
+        for tool in &mut tools {
+            if TABULAR_TOOLS.contains(&tool["name"].as_str().unwrap_or_default()) {
+                tool["inputSchema"]["properties"]["output_format"] = json!({
+                    "type": "string",
+                    "enum": ["json", "table", "csv"],
+                    "description": "json (default), table for an aligned text table, or csv. Nested values become dotted columns (category.name)"
+                });
+            }
+            // Scans are cut short at the deadline too (applied in handle_tool_call)
+            if SCANNING_TOOLS.contains(&tool["name"].as_str().unwrap_or_default()) {
+                tool["inputSchema"]["properties"]["timeout_ms"] = json!({
+                    "type": "integer",
+                    "description": "Stop scanning after this many milliseconds and return what was found, with a _budget notice and next_offset to continue from where the tool pages"
+                });
+            }
+        }
+        tools
+    }
+
+    // Candidate groups for a loosely written name, best first
+    async fn rank_groups(&self, name: &str) -> Result<Vec<Candidate>> {
+        let groups = self.cached_groups().await?;
+        // Group 0 is Splitwise's "Non-group expenses" pseudo-group
+        Ok(resolve::rank(
+            name,
+            groups.iter().filter(|g| g.id != 0).map(|g| (g.id, g.name.clone())),
+        ))
+    }
+
+    // Candidate people for a loosely written name: friends plus fellow group members
+    async fn rank_users(&self, name: &str) -> Result<Vec<Candidate>> {
+        let me = self.cached_me().await?;
+        let mut people = self.known_people().await?;
+        people.remove(&me.id);
+        Ok(resolve::rank(name, people))
+    }
+
+    // Everyone the user shares something with (friends and fellow group members), plus the user
+    async fn known_people(&self) -> Result<BTreeMap<i64, String>> {
+        let (me, friends, groups) = tokio::try_join!(
+            self.cached_me(),
+            self.cached_friends(),
+            self.cached_groups(),
+        )?;
+        let mut people: BTreeMap<i64, String> = friends
+            .iter()
+            .map(|f| (f.id, full_name(&f.first_name, f.last_name.as_deref())))
+            .collect();
+        for member in groups.iter().flat_map(|g| &g.members) {
+            people
+                .entry(member.id)
+                .or_insert_with(|| full_name(&member.first_name, member.last_name.as_deref()));
+        }
+        people.insert(me.id, full_name(&me.first_name, me.last_name.as_deref()));
+        Ok(people)
+    }
+
+    // An explicit ID wins; otherwise resolve the name, refusing weak or ambiguous matches
+    async fn group_id_from(&self, id: Option<i64>, name: Option<&str>) -> Result<Option<i64>> {
+        match (id, name) {
+            (Some(id), _) => Ok(Some(id)),
+            (None, Some(name)) => pick("group", name, &self.rank_groups(name).await?).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    async fn user_id_from(&self, id: Option<i64>, name: Option<&str>) -> Result<Option<i64>> {
+        match (id, name) {
+            (Some(id), _) => Ok(Some(id)),
+            (None, Some(name)) if ME.contains(&nl_query::normalize(name).trim()) => {
+                Ok(Some(self.cached_me().await?.id))
+            }
+            (None, Some(name)) => pick("user", name, &self.rank_users(name).await?).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // Candidate categories for a loosely written name. Parents are included only when
+    // filtering, where a parent stands for all of its subcategories; expenses themselves
+    // must be filed under a subcategory.
+    async fn rank_categories(&self, name: &str, include_parents: bool) -> Result<Vec<Candidate>> {
+        let categories = self.cached_categories().await?;
+        let name = self.category_alias(name).unwrap_or(name);
+        let mut options = Vec::new();
+        for parent in categories.iter() {
+            if include_parents {
+                options.push((parent.id, parent.name.clone()));
+            }
+            for sub in parent.subcategories.as_deref().unwrap_or(&[]) {
+                match sub.name.as_str() {
+                    // Every parent has an "Other"; the parent itself covers it when filtering
+                    "Other" if include_parents => {}
+                    "Other" => options.push((sub.id, format!("{} other", parent.name))),
+                    _ => options.push((sub.id, sub.name.clone())),
+                }
+            }
+        }
+        Ok(resolve::rank(name, options))
+    }
+
+    fn learn_category(&self, description: &str, category: &Category) -> Result<()> {
+        let key = nl_query::normalize(description).trim().to_string();
+        let mut learned = self.learned_categories.lock().unwrap();
+        let entry = learned.entry(key).or_insert_with(|| LearnedCategory {
+            category_id: category.id,
+            category_name: category.name.clone(),
+            times: 0,
+            last_learned: String::new(),
+        });
+        // The latest correction wins; `times` counts how often it was confirmed
+        if entry.category_id != category.id {
+            entry.times = 0;
+        }
+        entry.category_id = category.id;
+        entry.category_name = category.name.clone();
+        entry.times += 1;
+        entry.last_learned = chrono::Utc::now().to_rfc3339();
+        self.store.save(LEARNED_CATEGORIES, &*learned)
+    }
+
+    // Configured aliases win over the built-in ones
+    fn category_alias(&self, word: &str) -> Option<&str> {
+        self.category_aliases
+            .get(nl_query::normalize(word).trim())
+            .map(String::as_str)
+            .or_else(|| nl_query::category_alias(word))
+    }
+
+    async fn category_id_from(&self, id: Option<i64>, name: Option<&str>) -> Result<Option<i64>> {
+        match (id, name) {
+            (Some(id), _) => Ok(Some(id)),
+            (None, Some(name)) => pick("category", name, &self.rank_categories(name, false).await?).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // The parent of a subcategory; None for a parent or an unknown ID
+    async fn parent_category(&self, id: i64) -> Result<Option<i64>> {
+        let categories = self.cached_categories().await?;
+        Ok(categories
+            .iter()
+            .find(|parent| parent.subcategories.iter().flatten().any(|sub| sub.id == id))
+            .map(|parent| parent.id))
+    }
+
+    // Category IDs for a filter: explicit IDs plus resolved names, parents expanded to their subcategories
+    async fn category_filter(&self, ids: Option<Vec<i64>>, names: Option<Vec<String>>) -> Result<Option<Vec<i64>>> {
+        let Some(names) = names else {
+            return Ok(ids);
+        };
+        let categories = self.cached_categories().await?;
+        let mut resolved = ids.unwrap_or_default();
+        for name in &names {
+            let id = pick("category", name, &self.rank_categories(name, true).await?)?;
+            resolved.push(id);
+            if let Some(parent) = categories.iter().find(|c| c.id == id) {
+                resolved.extend(parent.subcategories.iter().flatten().map(|s| s.id));
+            }
+        }
+        Ok(Some(resolved))
+    }
+
+    // Split entries may name a user instead of giving an ID or email
+    async fn shares_from(&self, shares: Option<Vec<ShareInput>>) -> Result<Option<Vec<ExpenseShare>>> {
+        let Some(shares) = shares else {
+            return Ok(None);
+        };
+        let mut resolved = Vec::with_capacity(shares.len());
+        for share in shares {
+            let resolved_share = match share.email {
+                Some(email) => {
+                    // A friend's email is as good as their ID; anyone else is invited by Splitwise,
+                    // which then needs at least a first name for them
+                    let friends = self.cached_friends().await?;
+                    let friend = friends
+                        .iter()
+                        .find(|f| f.email.as_deref().is_some_and(|e| e.eq_ignore_ascii_case(&email)));
+                    match friend {
+                        Some(friend) => ExpenseShare {
+                            user_id: Some(friend.id),
+                            email: None,
+                            first_name: None,
+                            last_name: None,
+                            paid_share: share.paid_share,
+                            owed_share: share.owed_share,
+                        },
+                        None => {
+                            let (first_name, last_name) = match share.first_name {
+                                Some(first) => (first, share.last_name),
+                                None => name_from_email(&email),
+                            };
+                            ExpenseShare {
+                                user_id: share.user_id,
+                                email: Some(email),
+                                first_name: Some(first_name),
+                                last_name,
+                                paid_share: share.paid_share,
+                                owed_share: share.owed_share,
+                            }
+                        }
+                    }
+                }
+                None => ExpenseShare {
+                    user_id: self.user_id_from(share.user_id, share.user_name.as_deref()).await?,
+                    email: None,
+                    first_name: share.first_name,
+                    last_name: share.last_name,
+                    paid_share: share.paid_share,
+                    owed_share: share.owed_share,
+                },
+            };
+            resolved.push(resolved_share);
+        }
+        Ok(Some(resolved))
+    }
+
+    // Equal owed shares among exactly `people`; who paid is filled in by apply_payers
+    async fn split_among_shares(&self, cost: f64, people: &[Value]) -> Result<Vec<ExpenseShare>> {
+        let owed = split_by_weights(cost, &vec![1.0; people.len()]).context("split_among needs at least one person")?;
+        let inputs = people
+            .iter()
+            .zip(&owed)
+            .map(|(person, owed)| share_for(person, format_amount(0.0), format_amount(*owed)))
+            .collect::<Result<Vec<_>>>()?;
+        let shares = self.shares_from(Some(inputs)).await?.unwrap_or_default();
+        for (i, share) in shares.iter().enumerate() {
+            if shares[..i].iter().any(|other| same_person(share, other)) {
+                anyhow::bail!("split_among lists the same person twice");
+            }
+        }
+        Ok(shares)
+    }
+
+    // Equal owed shares among every member of a group but the `excluded` (non-paying) ones
+    async fn group_equal_shares(&self, cost: f64, group_id: i64, excluded: &[i64]) -> Result<Vec<ExpenseShare>> {
+        let group = self.client.get_group(group_id).await?;
+        let paying: Vec<i64> = group.members.iter().map(|m| m.id).filter(|id| !excluded.contains(id)).collect();
+        let owed = split_by_weights(cost, &vec![1.0; paying.len()]).context("The group has no paying members")?;
+        Ok(paying.into_iter().zip(owed).map(|(id, owed)| owed_only(id, owed)).collect())
+    }
+
+    // Owed shares proportional to nights present
+    async fn attendance_shares(&self, cost: f64, attendance: &[AttendanceInput]) -> Result<Vec<ExpenseShare>> {
+        let mut members = Vec::with_capacity(attendance.len());
+        let mut nights = Vec::with_capacity(attendance.len());
+        for stay in attendance {
+            let id = self
+                .user_id_from(stay.user_id, stay.user_name.as_deref())
+                .await?
+                .context("Each attendance entry needs a user_id or user_name")?;
+            let arrival = chrono::NaiveDate::parse_from_str(&stay.arrival, "%Y-%m-%d")
+                .with_context(|| format!("Invalid arrival date: {}", stay.arrival))?;
+            let departure = chrono::NaiveDate::parse_from_str(&stay.departure, "%Y-%m-%d")
+                .with_context(|| format!("Invalid departure date: {}", stay.departure))?;
+            if departure < arrival {
+                anyhow::bail!("Departure {} is before arrival {}", stay.departure, stay.arrival);
+            }
+            members.push(id);
+            nights.push((departure - arrival).num_days() as f64);
+        }
+
+        let owed = split_by_weights(cost, &nights).context("Nobody stayed a night; check the attendance dates")?;
+        Ok(members.into_iter().zip(owed).map(|(id, owed)| owed_only(id, owed)).collect())
+    }
+
+    // Record who paid: one person (the current user by default) paid everything, or `paid_by`
+    // maps people to the amounts they paid, which must add up to the cost. A payer who owes
+    // nothing is added with a zero owed share.
+    async fn apply_payers(&self, shares: &mut Vec<ExpenseShare>, cost: f64, paid_by: Option<&Value>) -> Result<()> {
+        let payers: Vec<(Value, f64)> = match paid_by {
+            None => vec![(json!(self.cached_me().await?.id), cost)],
+            Some(Value::Object(amounts)) => {
+                let payers = amounts
+                    .iter()
+                    .map(|(person, amount)| {
+                        // Map keys are strings, so numeric keys stand for user IDs
+                        let person = person.parse::<i64>().map_or_else(|_| json!(person), |id| json!(id));
+                        Ok((person, parse_amount(amount)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let paid: f64 = payers.iter().map(|(_, amount)| amount).sum();
+                if (paid - cost).abs() >= 0.005 {
+                    anyhow::bail!(
+                        "paid_by amounts add up to {} but the cost is {}",
+                        format_amount(paid),
+                        format_amount(cost)
+                    );
+                }
+                payers
+            }
+            Some(person) => vec![(person.clone(), cost)],
+        };
+
+        let mut resolved: Vec<ExpenseShare> = Vec::new();
+        for (person, amount) in payers {
+            let input = share_for(&person, format_amount(amount), format_amount(0.0))?;
+            let payer = self.shares_from(Some(vec![input])).await?.unwrap_or_default().remove(0);
+            // "me" and the user's own ID are the same payer; adding both would drop one amount
+            if resolved.iter().any(|other| same_person(other, &payer)) {
+                anyhow::bail!("paid_by names the same person twice ({})", person);
+            }
+            resolved.push(payer.clone());
+            match shares.iter_mut().find(|share| same_person(share, &payer)) {
+                Some(share) => share.paid_share = format_amount(amount),
+                None => shares.push(payer),
+            }
+        }
+        Ok(())
+    }
+
+    // Count each aliased user ID as the account it stands for, merging their shares of an
+    // expense; returns the aliases that were found
+    fn merge_aliases(&self, expenses: &mut [Expense]) -> Vec<Value> {
+        let aliases = self.member_aliases.lock().unwrap();
+        if aliases.is_empty() {
+            return Vec::new();
+        }
+        let mut found = BTreeSet::new();
+        for expense in expenses.iter_mut() {
+            let mut merged: Vec<ExpenseUser> = Vec::with_capacity(expense.users.len());
+            for mut share in std::mem::take(&mut expense.users) {
+                if let Some(alias) = aliases.get(&share.user_id) {
+                    found.insert(share.user_id);
+                    share.user_id = alias.user_id;
+                    share.user = None;
+                }
+                match merged.iter_mut().find(|m| m.user_id == share.user_id) {
+                    Some(same) => {
+                        let add = |a: &str, b: &str| format_amount(a.parse::<f64>().unwrap_or(0.0) + b.parse::<f64>().unwrap_or(0.0));
+                        same.paid_share = add(&same.paid_share, &share.paid_share);
+                        same.owed_share = add(&same.owed_share, &share.owed_share);
+                        same.net_balance = add(&same.net_balance, &share.net_balance);
+                    }
+                    None => merged.push(share),
+                }
+            }
+            expense.users = merged;
+            for repayment in expense.repayments.iter_mut() {
+                for id in [&mut repayment.from, &mut repayment.to] {
+                    if let Some(alias) = aliases.get(id) {
+                        *id = alias.user_id;
+                    }
+                }
+            }
+        }
+        found
+            .into_iter()
+            .map(|id| json!({ "alias_id": id, "alias_name": aliases[&id].alias_name, "counted_as": aliases[&id].name }))
+            .collect()
+    }
+
+    // Rewrite only the metadata line of an expense's notes, keeping the user's text
+    async fn edit_metadata(&self, expense_id: i64, change: impl FnOnce(&mut metadata::Metadata)) -> Result<Expense> {
+        let expense = self.client.get_expense(expense_id).await?;
+        let (notes, mut metadata) = metadata::split(expense.details.as_deref());
+        change(&mut metadata);
+        let request = UpdateExpenseRequest {
+            cost: None,
+            description: None,
+            currency_code: None,
+            category_id: None,
+            date: None,
+            details: Some(metadata::join(&notes, &metadata)),
+            payment: None,
+            group_id: None,
+            split_equally: None,
+            split_by_shares: None,
+        };
+        self.client
+            .update_expense(expense_id, request)
+            .await?
+            .into_iter()
+            .next()
+            .context("Splitwise returned no expense after the update")
+    }
+
+    /// Upstream API metrics in the Prometheus text format, for the HTTP transport's /metrics.
+    #[allow(dead_code)]
+    pub fn metrics(&self) -> String {
+        self.client.metrics()
+    }
+
+    /// The same for several servers' accounts at once (HTTP tenants), labelled by tenant.
+    #[allow(dead_code)]
+    pub fn metrics_of(tools: &[&SplitwiseTools]) -> String {
+        let clients: Vec<&SplitwiseClient> = tools.iter().map(|t| t.client.as_ref()).collect();
+        SplitwiseClient::metrics_of(&clients)
+    }
+
+    /// Fetch the current user's identity and groups ahead of the first tool call. Nearly every
+    /// answer about balances or shares needs them; the lookup cache refreshes them after its TTL.
+    pub async fn warm_up(&self) {
+        let (me, groups) = tokio::join!(self.cached_me(), self.cached_groups());
+        if let Err(e) = me {
+            warn!("Could not load the current user at startup: {}", e);
+        }
+        if let Err(e) = groups {
+            warn!("Could not load groups at startup: {}", e);
+        }
+    }
+
+    /// The account this server acts on and its mode, from the lookup cache only, so initialize
+    /// never waits on Splitwise; what warm_up hasn't loaded yet is null.
+    pub fn account_summary(&self) -> Value {
+        let me = self.lookups.me.peek();
+        let groups = self.lookups.groups.peek();
+        self.redact(json!({
+            "first_name": me.as_ref().map(|me| me.first_name.clone()),
+            "default_currency": me.as_ref().and_then(|me| me.default_currency.clone()),
+            "groups": groups.map(|groups| groups.iter().filter(|g| g.id != 0).count()),
+            "mode": if self.client.is_sandbox() { "sandbox" } else { "live" },
+            "read_only": self.policies.current().0.read_only,
+        }))
+    }
+
+    /// initialize's instructions: the account summary as a sentence, then how to use the tools.
+    pub fn instructions(&self) -> String {
+        let summary = self.account_summary();
+        let mut text = match summary["first_name"].as_str() {
+            Some(name) => {
+                let mut details = Vec::new();
+                if let Some(currency) = summary["default_currency"].as_str() {
+                    details.push(format!("default currency {}", currency));
+                }
+                if let Some(groups) = summary["groups"].as_u64() {
+                    details.push(format!("{} group{}", groups, if groups == 1 { "" } else { "s" }));
+                }
+                if details.is_empty() {
+                    format!("Acting on the Splitwise account of {}.", name)
+                } else {
+                    format!("Acting on the Splitwise account of {} ({}).", name, details.join(", "))
+                }
+            }
+            None => "The Splitwise account hasn't been loaded yet; get_current_user shows whose it is.".to_string(),
+        };
+        if self.client.is_sandbox() {
+            text.push_str(" Sandbox mode: the data is made up, and changes last until the server stops.");
+        }
+        text.push_str("\n\nHow to use these tools:");
+        for line in usage_guidance(&self.get_tools()) {
+            text.push_str("\n- ");
+            text.push_str(&line);
+        }
+        text
+    }
+
+    async fn cached_me(&self) -> Result<Arc<User>> {
+        self.lookups.me.get_or_fetch(|| self.client.get_current_user()).await
+    }
+
+    async fn cached_groups(&self) -> Result<Arc<Vec<Group>>> {
+        self.lookups.groups.get_or_fetch(|| self.client.get_groups()).await
+    }
+
+    // Directories file tools are confined to: None on stdio, where the client's roots are this
+    // machine's; over HTTP only the operator's HTTP_FILE_DIRS
+    fn file_dirs(&self) -> Option<&[PathBuf]> {
+        (self.config.transport != "stdio").then_some(self.config.http_file_dirs.as_slice())
+    }
+
+    async fn cached_friends(&self) -> Result<Arc<Vec<Friend>>> {
+        self.lookups.friends.get_or_fetch(|| self.client.get_friends()).await
+    }
+
+    async fn cached_categories(&self) -> Result<Arc<Vec<Category>>> {
+        self.lookups.categories.get_or_fetch(|| self.client.get_categories()).await
+    }
+
+    // Page through one scope's expenses until `wanted` of them pass `keep`, or history runs out
+    async fn scan_expenses(
+        &self,
+        params: ListExpensesParams,
+        wanted: Option<usize>,
+        keep: &(dyn Fn(&Expense) -> bool + Sync),
+    ) -> Result<Vec<Expense>> {
+        Ok(self.scan_expenses_counted(params, wanted, keep).await?.0)
+    }
+
+    // scan_expenses, also returning how many expenses were examined
+    async fn scan_expenses_counted(
+        &self,
+        mut params: ListExpensesParams,
+        wanted: Option<usize>,
+        keep: &(dyn Fn(&Expense) -> bool + Sync),
+    ) -> Result<(Vec<Expense>, usize)> {
+        const BATCH_SIZE: i32 = 100;
+        let mut found = Vec::new();
+        let mut offset = 0;
+        loop {
+            // The first batch is always fetched, so a spent budget still leaves something to refine
+            if !budget::next_page() && offset > 0 {
+                return Ok((found, offset as usize));
+            }
+            params.limit = Some(BATCH_SIZE);
+            params.offset = Some(offset);
+            // Unreadable items still take up their place in the page
+            let batch = self.client.get_expense_page(params.clone()).await?;
+            let last_batch = (batch.len() as i32) < BATCH_SIZE;
+            let examined = offset as usize + batch.len();
+            found.extend(batch.into_iter().flatten().filter(|e| keep(e)));
+            if last_batch || wanted.is_some_and(|w| found.len() >= w) {
+                return Ok((found, examined));
+            }
+            offset += BATCH_SIZE;
+        }
+    }
+
+    // Walk the history in Splitwise's order from `offset`, keeping matches (with the offset each
+    // was found at) until `limit` are found. Also returns how many expenses were examined and,
+    // unless the history ran out, the offset just past the last one examined, where the next
+    // page starts
+    async fn page_expenses(
+        &self,
+        mut params: ListExpensesParams,
+        offset: usize,
+        limit: Option<usize>,
+        keep: &(dyn Fn(&Expense) -> bool + Sync),
+    ) -> Result<(Vec<(usize, Expense)>, usize, Option<usize>)> {
+        const BATCH_SIZE: usize = 100;
+        let mut found = Vec::new();
+        let mut position = offset;
+        loop {
+            if !budget::next_page() && position > offset {
+                return Ok((found, position - offset, Some(position)));
+            }
+            params.limit = Some(BATCH_SIZE as i32);
+            params.offset = Some(position as i32);
+            let batch = self
+                .client
+                .get_expense_page(params.clone())
+                .await
+                .with_context(|| format!("Failed to fetch batch at offset {}", position))?;
+            let batch_end = position + batch.len();
+            let last_batch = batch.len() < BATCH_SIZE;
+            for expense in batch {
+                position += 1;
+                // An unreadable item is passed over but still counts towards the offset
+                let Some(expense) = expense else {
+                    continue;
+                };
+                if keep(&expense) {
+                    found.push((position - 1, expense));
+                }
+                if limit.is_some_and(|l| found.len() >= l) {
+                    let more = !last_batch || position < batch_end;
+                    return Ok((found, position - offset, more.then_some(position)));
+                }
+            }
+            if last_batch {
+                return Ok((found, position - offset, None));
+            }
+        }
+    }
+
+    // Sum a multi-currency balance list in a single target currency
+    async fn convert_balances(&self, balances: &[Balance], target: &str) -> Result<f64> {
+        let mut total = 0.0;
+        for balance in balances {
+            let amount: f64 = balance.amount.parse().unwrap_or(0.0);
+            if amount.abs() < 0.005 {
+                continue;
+            }
+            total += self.rates.convert(amount, &balance.currency_code, target).await?.converted;
+        }
+        Ok(total)
+    }
+
+    /// One page of tools/list. The cursor is the name of the first tool on the page, so it stays
+    /// valid however the list is cut into pages.
+    pub fn list_tools(&self, cursor: Option<&str>) -> Result<Value> {
+        let tools = self.localized_tools();
+        let start = match cursor {
+            Some(cursor) => tools
+                .iter()
+                .position(|t| t["name"] == cursor)
+                .ok_or_else(|| InvalidParams(format!("Invalid cursor '{}'", cursor)))?,
+            None => 0,
+        };
+        let size = match self.config.tools_page_size {
+            0 => tools.len(),
+            size => size,
+        };
+        let end = tools.len().min(start + size);
+        let mut page = json!({ "tools": tools[start..end] });
+        if let Some(next) = tools.get(end) {
+            page["nextCursor"] = next["name"].clone();
+        }
+        Ok(page)
+    }
+
+    /// The tools as clients see them: descriptions in TOOL_LOCALE's language, or else in the
+    /// one the client gave in initialize. get_tools stays in English for the server's own use.
+    pub fn localized_tools(&self) -> Vec<Value> {
+        let (policy, _) = self.policies.current();
+        let mut tools = self.get_tools();
+        tools.retain(|t| t["name"].as_str().is_some_and(|name| policy.permits(name, WRITE_TOOLS.contains(&name))));
+        if let Some(locale) = self.config.tool_locale.clone().or_else(peer::locale) {
+            i18n::localize(&mut tools, &locale);
+        }
+        tools
+    }
+
+    /// Whether the tools offered can change while the server runs (a TOOL_POLICY_FILE is set),
+    /// for the listChanged capability.
+    pub fn tool_list_may_change(&self) -> bool {
+        self.policies.is_dynamic()
+    }
+
+    /// notifications/tools/list_changed for the client being served, if the tool policy changed
+    /// since it last heard.
+    pub fn tools_changed_notice(&self) -> Option<Value> {
+        let (_, revision) = self.policies.current();
+        peer::tools_changed(revision).then(|| json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }))
+    }
+
+    // Calls to tools the policy doesn't offer are refused, as if the tool didn't exist
+    fn check_offered(&self, name: &str) -> Result<()> {
+        let (policy, _) = self.policies.current();
+        let writes = WRITE_TOOLS.contains(&name);
+        if !policy.permits(name, writes) {
+            return Err(InvalidParams(policy.refusal(name, writes)).into());
+        }
+        Ok(())
+    }
+
+    /// MCP resources: the dashboard snapshot, for clients that attach context up front.
+    pub fn get_resources(&self) -> Vec<Value> {
+        vec![json!({
+            "uri": CONTEXT_URI,
+            "name": "Splitwise context",
+            "description": "You, your balance per group, friends you have a balance with and the last 10 expenses (the get_dashboard snapshot). Refreshed once it is older than the lookup cache TTL or after a change made through this server",
+            "mimeType": "application/json",
+        })]
+    }
+
+    /// Contents of a resource, as the `result` of resources/read.
+    pub async fn read_resource(&self, uri: &str) -> Result<Value> {
+        if uri != CONTEXT_URI {
+            return Err(UnknownResource(uri.to_string()).into());
+        }
+        let snapshot = self.redact(self.dashboard_snapshot().await?.as_ref().clone());
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": snapshot.to_string(),
+            }]
+        }))
+    }
+
+    /// The get_dashboard snapshot, reused until the lookup cache TTL passes or something changes.
+    pub async fn dashboard_snapshot(&self) -> Result<Arc<Value>> {
+        self.lookups.dashboard.get_or_fetch(|| self.dashboard()).await
+    }
+
+    // An expense write made from inside another tool (a queued write, a fix, a convenience tool).
+    // The writes are methods of their own, so calling them here doesn't stack a second
+    // dispatch_tool frame, which is large in debug builds, on top of the calling tool's
+    async fn write_expense(&self, tool: &str, arguments: Value) -> Result<Value> {
+        match tool {
+            "create_expense" => self.create_expense(arguments).await,
+            "update_expense" => self.update_expense(arguments).await,
+            "delete_expense" => self.delete_expense(arguments).await,
+            _ => Box::pin(self.dispatch_tool(tool, arguments)).await,
+        }
+    }
+
+    async fn create_expense(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            cost: String,
+            description: String,
+            currency_code: Option<String>,
+            group_id: Option<i64>,
+            group_name: Option<String>,
+            split_equally: Option<bool>,
+            split_by_shares: Option<Vec<ShareInput>>,
+            prorate_by_attendance: Option<ProrateInput>,
+            split_among: Option<Vec<Value>>,
+            paid_by: Option<Value>,
+            date: Option<String>,
+            category_id: Option<i64>,
+            category_name: Option<String>,
+            details: Option<String>,
+            tags: Option<Vec<String>>,
+            external_id: Option<String>,
+            import_source: Option<String>,
+            idempotency_key: Option<String>,
+        }
+        let mut args: Args = parse_args(arguments)?;
+        // A retry of a create that already went through gets the expense it made. The lookup has
+        // its own page cap rather than the call's budget: a scan cut short by a spent budget would
+        // find nothing and create it again, so one cut short by the cap refuses instead
+        if let Some(key) = &args.idempotency_key {
+            let since = chrono::Utc::now() - chrono::Days::new(write_queue::IDEMPOTENCY_WINDOW_DAYS);
+            let params = ListExpensesParams {
+                updated_after: Some(since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+                ..Default::default()
+            };
+            let made_with_key = |e: &Expense| {
+                e.deleted_at.is_none() && metadata::split(e.details.as_deref()).1.idempotency_key.as_ref() == Some(key)
+            };
+            let lookup = self.scan_expenses(params, Some(1), &made_with_key);
+            let limits = budget::Limits { pages: write_queue::IDEMPOTENCY_LOOKUP_PAGES, ..Default::default() };
+            let (made, cut_short) = budget::scoped(limits, lookup).await;
+            let made = made?;
+            if made.is_empty() && cut_short.is_some() {
+                anyhow::bail!(
+                    "Couldn't check whether an expense with idempotency_key '{}' was already created: too many expenses changed in the last {} days. Nothing was created",
+                    key,
+                    write_queue::IDEMPOTENCY_WINDOW_DAYS
+                );
+            }
+            if let Some(expense) = made.first() {
+                return Ok(json!({
+                    "success": true,
+                    "id": expense.id,
+                    "description": expense.description,
+                    "cost": expense.cost,
+                    "created_at": expense.created_at,
+                    "already_created": true,
+                }));
+            }
+        }
+        let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+        let category_id = self.category_id_from(args.category_id, args.category_name.as_deref()).await?;
+        // Without a currency, one the description names beats the account default
+        let detected = match args.currency_code {
+            Some(_) => None,
+            None => receipt::currency_in_description(&args.description),
+        };
+        if let Some((ref code, _, _)) = detected {
+            args.currency_code = Some(code.clone());
+        }
+        
+        // Convert ShareInput to ExpenseShare, or derive owed shares from a strategy and add the payers
+        let strategies = [
+            args.split_by_shares.is_some(),
+            args.prorate_by_attendance.is_some(),
+            args.split_among.is_some(),
+        ];
+        if strategies.iter().filter(|given| **given).count() > 1 {
+            anyhow::bail!("Use only one of split_by_shares, prorate_by_attendance or split_among");
+        }
+        let inner_payer = args.prorate_by_attendance.as_ref().and_then(|p| p.paid_by.clone());
+        let paid_by = match (args.paid_by, inner_payer) {
+            (Some(_), Some(_)) => anyhow::bail!("Give paid_by once, not also inside prorate_by_attendance"),
+            (paid_by, inner) => paid_by.or(inner.map(Value::String)),
+        };
+        if paid_by.is_some() && args.split_by_shares.is_some() {
+            anyhow::bail!("split_by_shares already says who paid; drop paid_by or use another split");
+        }
+
+        // A group's profile stands in for the split when none is given: the expense
+        // category's split if the profile has one, otherwise the group default. What was
+        // applied is echoed back so the rule that fired can be checked.
+        let profile = group_id.and_then(|id| self.split_profiles.lock().unwrap().get(&id).cloned());
+        let applied = match profile {
+            Some(ref profile) if !strategies.contains(&true) && args.split_equally.is_none() => {
+                let category = match category_id {
+                    Some(id) if !profile.categories.is_empty() => Some((id, self.parent_category(id).await?)),
+                    _ => None,
+                };
+                profile.rule_for(category).map(|(split, shares)| {
+                    let echo = match split {
+                        Some((id, split)) => json!({
+                            "rule": "category",
+                            "category_id": id,
+                            "category_name": split.category_name,
+                            "shares": share_summary(shares),
+                        }),
+                        None => json!({
+                            "rule": "group",
+                            "group_name": profile.group_name,
+                            "shares": share_summary(shares),
+                        }),
+                    };
+                    (echo, shares.to_vec())
+                })
+            }
+            _ => None,
+        };
+        // Members the profile marks as never paying (kids, guests) are left out of equal splits
+        let non_paying: Vec<i64> = profile.iter().flat_map(|p| p.non_paying.iter().map(|m| m.user_id)).collect();
+
+        let cost = parse_amount(&json!(args.cost))?;
+        let owed = if let Some(ref prorate) = args.prorate_by_attendance {
+            Some(self.attendance_shares(cost, &prorate.attendance).await?)
+        } else if let Some(ref people) = args.split_among {
+            Some(self.split_among_shares(cost, people).await?)
+        } else if let Some((_, ref shares)) = applied {
+            let percents: Vec<f64> = shares.iter().map(|s| s.percent).collect();
+            let owed = split_by_weights(cost, &percents).context("The group's split profile has no shares")?;
+            Some(shares.iter().zip(owed).map(|(share, owed)| owed_only(share.user_id, owed)).collect())
+        } else if paid_by.is_some() || (!non_paying.is_empty() && args.split_by_shares.is_none()) {
+            // Someone other than the user paid, or some members don't pay, so the group's
+            // equal split has to be spelled out
+            let group_id = group_id.context("paid_by needs a group to split equally in, or split_among")?;
+            Some(self.group_equal_shares(cost, group_id, &non_paying).await?)
+        } else {
+            None
+        };
+        let split_by_shares = match owed {
+            Some(mut shares) => {
+                self.apply_payers(&mut shares, cost, paid_by.as_ref()).await?;
+                Some(shares)
+            }
+            None => self.shares_from(args.split_by_shares).await?,
+        };
+        
+        // Owing something anyway can only come from an explicit split; it is created as
+        // asked, but flagged in case it was a mistake
+        let mut warnings = Vec::new();
+        if let Some(ref profile) = profile {
+            for share in split_by_shares.iter().flatten() {
+                let Some(member) = profile.non_paying.iter().find(|m| Some(m.user_id) == share.user_id) else {
+                    continue;
+                };
+                if share.owed_share.parse::<f64>().is_ok_and(|owed| owed > 0.0) {
+                    warnings.push(format!(
+                        "{} is marked as non-paying in the {} split profile but was given an owed share of {}",
+                        member.name, profile.group_name, share.owed_share
+                    ));
+                }
+            }
+        }
+
+        // If shares are provided, split_equally should be false
+        let split_equally = if split_by_shares.is_some() {
+            Some(false)
+        } else {
+            args.split_equally.or(Some(true))
+        };
+
+        // Emails that aren't friends yet; Splitwise creates and invites these people
+        let invitations: Vec<Value> = split_by_shares
+            .iter()
+            .flatten()
+            .filter(|s| s.user_id.is_none())
+            .filter_map(|s| {
+                let email = s.email.as_ref()?;
+                Some(json!({
+                    "email": email,
+                    "name": full_name(s.first_name.as_deref().unwrap_or(""), s.last_name.as_deref()),
+                }))
+            })
+            .collect();
+
+        let mut metadata = metadata::Metadata::default();
+        metadata.add_tags(args.tags.iter().flatten());
+        metadata.external_id = args.external_id;
+        metadata.source = args.import_source;
+        metadata.idempotency_key = args.idempotency_key;
+        let details = match (args.details, metadata.is_empty()) {
+            (details, true) => details,
+            (details, false) => Some(metadata::join(details.as_deref().unwrap_or(""), &metadata)),
+        };
+        
+        let request = CreateExpenseRequest {
+            cost: args.cost,
+            description: args.description,
+            currency_code: args.currency_code,
+            category_id,
+            date: args.date,
+            repeat_interval: None,
+            details,
+            payment: Some(false),
+            group_id,
+            split_equally,
+            split_by_shares,
+        };
+        let expenses = self.client.create_expense(request).await?;
+        self.lookups.dashboard.invalidate();
+        if !invitations.is_empty() {
+            self.lookups.invalidate_people();
+        }
+        // Return simplified response with just essential info
+        let mut simplified = if let Some(expense) = expenses.first() {
+            json!({
+                "success": true,
+                "id": expense.id,
+                "description": expense.description,
+                "cost": expense.cost,
+                "created_at": expense.created_at,
+                "split": expense.users.iter().map(|u| json!({
+                    "name": u.user.as_ref().map(|user| &user.first_name),
+                    "paid": u.paid_share,
+                    "owes": u.owed_share
+                })).collect::<Vec<_>>()
+            })
+        } else {
+            json!({ "success": true })
+        };
+        if let Some((echo, _)) = applied {
+            simplified["split_profile"] = echo;
+        }
+        if let Some((code, rule, matched)) = detected {
+            simplified["currency_detected"] = json!({ "currency_code": code, "rule": rule, "matched": matched });
+        }
+        if !warnings.is_empty() {
+            simplified["warnings"] = json!(warnings);
+        }
+        if !invitations.is_empty() {
+            simplified["invited"] = json!(invitations);
+            simplified["invitation_note"] = json!(
+                "These people are not on Splitwise yet. Splitwise added them as friends and emails them an invitation; they already owe their share, and pending_invitations lists what is shared with them"
+            );
+        }
+        Ok(simplified)
+    }
+
+    // Currencies ranked by how much the user uses them, for a short picker
+    async fn common_currencies(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            limit: Option<usize>,
+            months: Option<u32>,
+        }
+        let args: Args = parse_args(arguments)?;
+        let limit = args.limit.unwrap_or(10).max(1);
+        let months = args.months.unwrap_or(12);
+        if !(1..=120).contains(&months) {
+            anyhow::bail!("months must be between 1 and 120");
+        }
+        let since = chrono::Local::now().date_naive() - chrono::Months::new(months);
+        let params = ListExpensesParams { dated_after: Some(since.to_string()), ..Default::default() };
+        let (currencies, me, friends, expenses) = tokio::try_join!(
+            self.client.get_currencies(),
+            self.cached_me(),
+            self.cached_friends(),
+            self.scan_expenses(params, None, &|e: &Expense| e.deleted_at.is_none()),
+        )?;
+
+        let mut used: HashMap<&str, usize> = HashMap::new();
+        for expense in &expenses {
+            *used.entry(&expense.currency_code).or_default() += 1;
+        }
+        let open: HashSet<&str> = friends
+            .iter()
+            .flat_map(|f| &f.balance)
+            .filter(|b| b.amount.parse::<f64>().is_ok_and(|a| a.abs() >= 0.005))
+            .map(|b| b.currency_code.as_str())
+            .collect();
+        let default = me.default_currency.as_deref();
+        // Default first, then by use, then open balances, then the curated order
+        let rank = |code: &str| {
+            (
+                Some(code) != default,
+                std::cmp::Reverse(used.get(code).copied().unwrap_or(0)),
+                !open.contains(code),
+                POPULAR_CURRENCIES.iter().position(|c| *c == code).unwrap_or(usize::MAX),
+            )
+        };
+        let mut ranked: Vec<&Currency> = currencies
+            .iter()
+            .filter(|c| Some(c.currency_code.as_str()) == default || used.contains_key(c.currency_code.as_str()) || open.contains(c.currency_code.as_str()) || POPULAR_CURRENCIES.contains(&c.currency_code.as_str()))
+            .collect();
+        ranked.sort_by_key(|c| rank(&c.currency_code));
+        ranked.truncate(limit);
+
+        let listed: Vec<Value> = ranked
+            .iter()
+            .map(|c| {
+                let code = c.currency_code.as_str();
+                let expenses = used.get(code).copied().unwrap_or(0);
+                let why = if Some(code) == default {
+                    "Your default currency".to_string()
+                } else if expenses > 0 {
+                    format!("Used in {} expense{} in the last {} months", expenses, if expenses == 1 { "" } else { "s" }, months)
+                } else if open.contains(code) {
+                    "You have an open balance in it".to_string()
+                } else {
+                    "Widely used".to_string()
+                };
+                json!({ "currency_code": code, "unit": c.unit, "expenses": expenses, "why": why })
+            })
+            .collect();
+        Ok(json!({
+            "count": listed.len(),
+            "available": currencies.len(),
+            "currencies": listed,
+        }))
+    }
+
+    // Comments across the expenses updated lately, with whether each of mine got an answer
+    async fn recent_comments(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            group_id: Option<i64>,
+            group_name: Option<String>,
+            days: Option<i64>,
+            search: Option<String>,
+            #[serde(default)]
+            include_system: bool,
+            limit: Option<usize>,
+        }
+        let args: Args = parse_args(arguments)?;
+        let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+        let days = args.days.unwrap_or(30).clamp(1, 3650);
+        let limit = args.limit.unwrap_or(20);
+        let since = chrono::Utc::now() - chrono::Duration::days(days);
+        let params = ListExpensesParams {
+            group_id,
+            updated_after: Some(since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            ..Default::default()
+        };
+        let keep = |e: &Expense| e.comments_count > 0;
+        let (me, expenses) = tokio::try_join!(self.cached_me(), self.scan_expenses(params, None, &keep))?;
+        // One request per expense that has comments, a few at a time and within the call's budget
+        const CONCURRENT_FETCHES: usize = 4;
+        let fetches: Vec<_> = expenses
+            .iter()
+            .map(|expense| async move {
+                if !budget::next_page() {
+                    return Ok(None);
+                }
+                Ok::<_, anyhow::Error>(Some((expense, self.client.get_comments(expense.id).await?)))
+            })
+            .collect();
+        let mut fetches = futures::stream::iter(fetches).buffer_unordered(CONCURRENT_FETCHES);
+        let mut threads = Vec::new();
+        while let Some(thread) = fetches.next().await {
+            threads.extend(thread?);
+        }
+
+        let search = args.search.as_deref().map(nl_query::normalize);
+        let mut listed = Vec::new();
+        for (expense, comments) in &threads {
+            let mut comments: Vec<&Comment> = comments
+                .iter()
+                .filter(|c| c.deleted_at.is_none() && (args.include_system || c.comment_type != "System"))
+                .collect();
+            comments.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+            let author = |c: &Comment| c.user.as_ref().map(|u| u.id);
+            for (i, comment) in comments.iter().enumerate() {
+                let text = format!("{} {}", comment.content, expense.description);
+                if search.as_ref().is_some_and(|s| !nl_query::normalize(&text).contains(s.as_str())) {
+                    continue;
+                }
+                let mine = author(comment) == Some(me.id);
+                let mut entry = json!({
+                    "comment_id": comment.id,
+                    "expense_id": expense.id,
+                    "expense": expense.description,
+                    "group_id": expense.group_id,
+                    "by": comment.user.as_ref().map(|u| full_name(&u.first_name, u.last_name.as_deref())),
+                    "mine": mine,
+                    "content": comment.content,
+                    "created_at": comment.created_at,
+                });
+                if mine {
+                    entry["answered"] = json!(comments[i + 1..].iter().any(|c| c.comment_type != "System" && author(c) != Some(me.id)));
+                } else if comment.comment_type != "System" {
+                    entry["reply_to_you"] = json!(comments[..i].iter().any(|c| c.comment_type != "System" && author(c) == Some(me.id)));
+                }
+                listed.push(entry);
+            }
+        }
+        listed.sort_by(|a, b| b["created_at"].as_str().cmp(&a["created_at"].as_str()));
+        let total = listed.len();
+        listed.truncate(limit);
+        Ok(json!({
+            "group_id": group_id,
+            "since": since.date_naive().to_string(),
+            "expenses_with_comments": threads.len(),
+            "total": total,
+            "count": listed.len(),
+            "comments": listed,
+        }))
+    }
+
+    async fn update_expense(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            expense_id: i64,
+            cost: Option<String>,
+            description: Option<String>,
+            currency_code: Option<String>,
+            category_id: Option<i64>,
+            category_name: Option<String>,
+            group_id: Option<i64>,
+            group_name: Option<String>,
+            date: Option<String>,
+            details: Option<String>,
+            tags: Option<Vec<String>>,
+            external_id: Option<String>,
+            import_source: Option<String>,
+            split_equally: Option<bool>,
+            split_by_shares: Option<Vec<ShareInput>>,
+        }
+        let args: Args = parse_args(arguments)?;
+
+        // Notes and metadata share the details field, so changing either keeps the other
+        let details = if args.details.is_some()
+            || args.tags.is_some()
+            || args.external_id.is_some()
+            || args.import_source.is_some()
+        {
+            let current = self.client.get_expense(args.expense_id).await?;
+            let (notes, mut metadata) = metadata::split(current.details.as_deref());
+            if let Some(ref tags) = args.tags {
+                metadata.set_tags(tags);
+            }
+            if args.external_id.is_some() {
+                metadata.external_id = args.external_id;
+            }
+            if args.import_source.is_some() {
+                metadata.source = args.import_source;
+            }
+            Some(metadata::join(args.details.as_deref().unwrap_or(&notes), &metadata))
+        } else {
+            None
+        };
+
+        let request = UpdateExpenseRequest {
+            cost: args.cost,
+            description: args.description,
+            currency_code: args.currency_code,
+            category_id: self.category_id_from(args.category_id, args.category_name.as_deref()).await?,
+            date: args.date,
+            details,
+            payment: None,
+            group_id: self.group_id_from(args.group_id, args.group_name.as_deref()).await?,
+            split_equally: args.split_equally,
+            split_by_shares: self.shares_from(args.split_by_shares).await?,
+        };
+        let category_changed = request.category_id.is_some();
+        let expenses = self.client.update_expense(args.expense_id, request).await?;
+        self.lookups.dashboard.invalidate();
+        // A category correction teaches suggest_category what this description means; the
+        // update already went through, so failing to remember it only costs the lesson
+        if let (true, Some(expense)) = (category_changed, expenses.first()) {
+            if let Err(e) = self.learn_category(&expense.description, &expense.category) {
+                // Logs reach the client too, where results are redacted; the ID gives nothing away
+                warn!("Could not remember the category of expense {}: {:#}", expense.id, e);
+            }
+        }
+        // Return simplified response with just essential info
+        let simplified = if let Some(expense) = expenses.first() {
+            json!({
+                "success": true,
+                "id": expense.id,
+                "description": expense.description,
+                "cost": expense.cost,
+                "updated_at": expense.updated_at,
+                "split": expense.users.iter().map(|u| json!({
+                    "name": u.user.as_ref().map(|user| &user.first_name),
+                    "paid": u.paid_share,
+                    "owes": u.owed_share
+                })).collect::<Vec<_>>()
+            })
+        } else {
+            json!({ "success": true })
+        };
+        Ok(simplified)
+    }
+
+    async fn delete_expense(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            expense_id: i64,
+        }
+        let args: Args = parse_args(arguments)?;
+        let success = self.client.delete_expense(args.expense_id).await?;
+        self.lookups.dashboard.invalidate();
+        Ok(json!({ "success": success }))
+    }
+
+    // Expenses of a group in a currency other than the one it uses, with conversions to fix them.
+    // Kept out of dispatch_tool, whose future is already large
+    async fn check_group_currencies(&self, arguments: Value) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Args {
+            group_id: Option<i64>,
+            group_name: Option<String>,
+            currency_code: Option<String>,
+            dated_after: Option<String>,
+            dated_before: Option<String>,
+            #[serde(default)]
+            suggest_conversions: bool,
+            rate_date: Option<String>,
+        }
+        let args: Args = parse_args(arguments)?;
+        let group_id = self
+            .group_id_from(args.group_id, args.group_name.as_deref())
+            .await?
+            .context("Provide group_id or group_name")?;
+        let use_latest = match args.rate_date.as_deref() {
+            None | Some("expense_date") => false,
+            Some("latest") => true,
+            Some(other) => anyhow::bail!("Unknown rate_date '{}'; use expense_date or latest", other),
+        };
+        let params = ListExpensesParams {
+            group_id: Some(group_id),
+            dated_after: args.dated_after.clone(),
+            dated_before: args.dated_before.clone(),
+            ..Default::default()
+        };
+        // Settle-ups are left alone: a payment in another currency is deliberate
+        let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment;
+        let (group, expenses) = tokio::try_join!(
+            self.client.get_group(group_id),
+            self.scan_expenses(params, None, &keep),
+        )?;
+
+        let mut by_currency: BTreeMap<&str, (usize, f64)> = BTreeMap::new();
+        for expense in &expenses {
+            let entry = by_currency.entry(&expense.currency_code).or_default();
+            entry.0 += 1;
+            entry.1 += expense.cost.parse::<f64>().unwrap_or(0.0);
+        }
+        let currency = match args.currency_code {
+            Some(code) => code.trim().to_uppercase(),
+            None => match by_currency.iter().max_by_key(|(_, (count, _))| *count) {
+                Some((code, _)) => code.to_string(),
+                None => {
+                    return Ok(json!({
+                        "group_id": group_id,
+                        "group_name": group.name,
+                        "expenses_checked": 0,
+                        "mismatch_count": 0,
+                        "mismatches": [],
+                    }))
+                }
+            },
+        };
+
+        let mut mismatches = Vec::new();
+        let mut converted_total = 0.0;
+        let mut unconverted = 0;
+        let mut ordered: Vec<&Expense> = expenses.iter().filter(|e| e.currency_code != currency).collect();
+        ordered.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+        for expense in ordered {
+            let mut mismatch = json!({
+                "check": "currency_mismatch",
+                "expense_id": expense.id,
+                "date": expense.date.get(..10).unwrap_or(&expense.date),
+                "description": expense.description,
+                "cost": expense.cost,
+                "currency_code": expense.currency_code,
+                "problem": format!("In {} while the group uses {}", expense.currency_code, currency),
+                // Without a conversion, only a currency entered wrong can be fixed
+                "suggested_fix": {
+                    "action": "Change the currency if it was entered wrong",
+                    "tool": "update_expense",
+                    "arguments": { "expense_id": expense.id, "currency_code": currency },
+                },
+            });
+            if args.suggest_conversions {
+                let cost: f64 = expense.cost.parse().unwrap_or(0.0);
+                let conversion = if use_latest {
+                    self.rates.convert(cost, &expense.currency_code, &currency).await
+                } else {
+                    self.rates.convert_on(cost, &expense.currency_code, &currency, &expense.date).await
+                };
+                match conversion {
+                    Ok(conversion) => {
+                        converted_total += conversion.converted;
+                        mismatch["conversion"] = json!({
+                            "amount": format_amount(conversion.converted),
+                            "currency": currency,
+                            "rate": conversion.rate,
+                            "rate_date": conversion.date,
+                        });
+                        mismatch["suggested_fix"] = json!({
+                            "action": format!("Record it as {} {}", format_amount(conversion.converted), currency),
+                            "tool": "update_expense",
+                            "arguments": converted_expense(expense, conversion.converted, &currency),
+                        });
+                    }
+                    Err(e) => {
+                        unconverted += 1;
+                        mismatch["conversion_error"] = json!(format!("{:#}", e));
+                    }
+                }
+            }
+            mismatches.push(mismatch);
+        }
+
+        let converted = args.suggest_conversions && !mismatches.is_empty() && unconverted == 0;
+        let mut result = json!({
+            "group_id": group_id,
+            "group_name": group.name,
+            "currency": currency,
+            "expenses_checked": expenses.len(),
+            "by_currency": by_currency
+                .iter()
+                .map(|(code, (count, total))| json!({ "currency": code, "count": count, "total": format_amount(*total) }))
+                .collect::<Vec<_>>(),
+            "mismatch_count": mismatches.len(),
+            "mismatches": mismatches,
+        });
+        // A total missing some expenses would mislead, so it's only given when all converted
+        if converted {
+            result["converted_total"] = json!(format_amount(converted_total));
+        }
+        Ok(result)
+    }
+
+    // Who I am, where I stand in each group and with each friend, and what happened lately
+    async fn dashboard(&self) -> Result<Value> {
+        let recent = ListExpensesParams {
+            // A few extra, since deleted expenses are left out
+            limit: Some(DASHBOARD_EXPENSES as i32 + 5),
+            ..Default::default()
+        };
+        let (me, groups, friends, expenses) = tokio::try_join!(
+            self.cached_me(),
+            self.client.get_groups(),
+            self.client.get_friends(),
+            self.client.get_expenses(recent),
+        )?;
+        let owing = |balances: &[Balance]| -> Vec<Value> {
+            balances
+                .iter()
+                .filter(|b| !is_zero_amount(&b.amount))
+                .map(|b| json!({ "amount": b.amount, "currency_code": b.currency_code }))
+                .collect()
+        };
+
+        let groups: Vec<Value> = groups
+            .iter()
+            .map(|group| {
+                let mine = group.members.iter().find(|m| m.id == me.id);
+                json!({
+                    "group_id": group.id,
+                    "name": group.name,
+                    "balance": mine.map(|m| owing(&m.balance)).unwrap_or_default(),
+                })
+            })
+            .collect();
+        let friends: Vec<Value> = friends
+            .iter()
+            .filter(|friend| friend.balance.iter().any(|b| !is_zero_amount(&b.amount)))
+            .map(|friend| {
+                json!({
+                    "friend_id": friend.id,
+                    "name": full_name(&friend.first_name, friend.last_name.as_deref()),
+                    "balance": owing(&friend.balance),
+                })
+            })
+            .collect();
+        let fields: Vec<String> = DASHBOARD_EXPENSE_FIELDS.iter().map(|f| f.to_string()).collect();
+        let expenses: Vec<Value> = expenses
+            .iter()
+            .filter(|e| e.deleted_at.is_none())
+            .take(DASHBOARD_EXPENSES)
+            .map(|e| project_expense(e, me.id, &fields))
+            .collect();
+
+        Ok(json!({
+            "me": {
+                "id": me.id,
+                "name": full_name(&me.first_name, me.last_name.as_deref()),
+                "default_currency": me.default_currency,
+            },
+            "groups": groups,
+            "friends_with_balance": friends,
+            "recent_expenses": expenses,
+            "as_of": chrono::Utc::now().to_rfc3339(),
+        }))
+    }
+
+    // Takes the Arc so that start_job can hand the tools to a task that outlives the request
+    pub async fn handle_tool_call(self: &Arc<Self>, name: &str, arguments: Option<Value>) -> Result<Value> {
+        let arguments = arguments.unwrap_or_else(|| json!({}));
+        let format = match arguments.get("output_format") {
+            Some(format) => OutputFormat::deserialize(format)
+                .map_err(|_| InvalidParams("output_format must be json, table or csv".to_string()))?,
+            None => OutputFormat::Json,
+        };
+
+        // Any tool accepts `trace: true` to get a timing breakdown of the upstream work
+        let traced = arguments.get("trace").and_then(|t| t.as_bool()) == Some(true);
+        debug!("Tool {} started", name);
+        let started = Instant::now();
+        let limits = budget::Limits {
+            splitwise_calls: self.config.tool_max_splitwise_calls,
+            pages: self.config.tool_max_pages,
+            response_bytes: self.config.tool_max_response_bytes,
+            timeout_ms: arguments
+                .get("timeout_ms")
+                .and_then(Value::as_u64)
+                .unwrap_or(self.config.tool_timeout_ms),
+        };
+        // The dispatch future is large; boxed, it doesn't sit on the stack of every scope around it
+        let ((result, skipped), spent) = budget::scoped(
+            limits,
+            warnings::collect(async {
+                if !traced {
+                    return Box::pin(self.call_tool(name, arguments)).await;
+                }
+                let (result, breakdown) = trace::traced(Box::pin(self.call_tool(name, arguments))).await;
+                Ok(attach(result?, "_trace", breakdown))
+            }),
+        )
+        .await;
+        let elapsed = started.elapsed().as_millis();
+        // Redacted before it is logged, since the log is forwarded to the client as well
+        let result = result.map_err(|e| self.redact_error(e));
+        match &result {
+            Ok(_) => info!("Tool {} finished in {} ms", name, elapsed),
+            Err(e) => warn!("Tool {} failed after {} ms: {:#}", name, elapsed, e),
+        }
+        let mut result = result?;
+        if !skipped.is_empty() {
+            result = attach(result, "_warnings", json!(skipped));
+        }
+        let (mut result, oversized) = budget::fit(self.redact(result), limits.response_bytes);
+        if let Some(notice) = spent.or(oversized) {
+            result = attach(result, "_budget", notice);
+        }
+        Ok(render::render(result, format))
+    }
+
+    // `value` with personal data redacted when PII_REDACTION is on. Everyone in the cached
+    // account data counts as known, so their full names are shortened wherever they appear
+    fn redact(&self, value: Value) -> Value {
+        let Some(redactor) = &self.redactor else {
+            return value;
+        };
+        let mut people = Vec::new();
+        if let Some(me) = self.lookups.me.peek() {
+            people.push((me.first_name.clone(), me.last_name.clone()));
+        }
+        for friend in self.lookups.friends.peek().iter().flat_map(|f| f.iter()) {
+            people.push((friend.first_name.clone(), friend.last_name.clone()));
+        }
+        for group in self.lookups.groups.peek().iter().flat_map(|g| g.iter()) {
+            people.extend(group.members.iter().map(|m| (m.first_name.clone(), m.last_name.clone())));
+        }
+        redactor.redact(value, &people)
+    }
+
+    // `error` with its message redacted like a result; errors name people too (the candidates
+    // for an ambiguous name, a member left out of a split)
+    fn redact_error(&self, error: anyhow::Error) -> anyhow::Error {
+        if self.redactor.is_none() {
+            return error;
+        }
+        let redacted = errors::Redacted::new(&error, |message| match self.redact(json!(message)) {
+            Value::String(message) => message,
+            _ => String::new(),
+        });
+        redacted.into()
+    }
+
+    // `data` redacted like a result, for output that result redaction can't see into (a PDF)
+    fn redact_data<T: Serialize + serde::de::DeserializeOwned>(&self, data: T) -> Result<T> {
+        if self.redactor.is_none() {
+            return Ok(data);
+        }
+        Ok(serde_json::from_value(self.redact(serde_json::to_value(data)?))?)
+    }
+
+    async fn call_tool(self: &Arc<Self>, name: &str, arguments: Value) -> Result<Value> {
+        self.check_offered(name)?;
+        if self.config.queue_offline_writes && write_queue::QUEUED_TOOLS.contains(&name) {
+            return self.write_or_queue(name, arguments).await;
+        }
+        if name != "start_job" {
+            return self.dispatch_tool(name, arguments).await;
+        }
+        #[derive(Deserialize)]
+        struct Args {
+            tool: String,
+            arguments: Option<Value>,
+        }
+        let args: Args = parse_args(arguments)?;
+        if JOB_TOOLS.contains(&args.tool.as_str()) {
+            anyhow::bail!("{} cannot run as a job", args.tool);
+        }
+        if !self.get_tools().iter().any(|t| t["name"] == args.tool) {
+            return Err(InvalidParams(format!("Unknown tool: {}", args.tool)).into());
+        }
+        self.check_offered(&args.tool)?;
+        let tools = Arc::clone(self);
+        let tool = args.tool.clone();
+        let arguments = args.arguments.unwrap_or_else(|| json!({}));
+        let job_id = self
+            .jobs
+            .start(&args.tool, async move { tools.dispatch_tool(&tool, arguments).await });
+        let mut status = self.jobs.status(&job_id)?;
+        status["note"] = json!("Poll job_status, then collect the answer with job_result");
+        Ok(status)
+    }
+
+    // An expense write, queued when Splitwise can't be reached. Writes queued earlier are sent
+    // first; those held back by a conflict or an error stay queued and don't block this one
+    async fn write_or_queue(&self, name: &str, arguments: Value) -> Result<Value> {
+        let waiting = !self.pending_writes.lock().unwrap().is_empty();
+        let backlog = if waiting { Some(Box::pin(self.flush_writes(None, false)).await?) } else { None };
+        // Writes held back by a conflict or an error don't keep new ones from going through
+        let still_offline = backlog.as_ref().is_some_and(|backlog| backlog.get("stopped").is_some());
+        let error = if !still_offline {
+            match Box::pin(self.dispatch_tool(name, arguments.clone())).await {
+                Err(e) if write_queue::is_unreachable(&e) => Some(format!("{:#}", e)),
+                Ok(result) => return Ok(match backlog {
+                    Some(backlog) => attach(result, "flushed_writes", backlog),
+                    None => result,
+                }),
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+        let write = PendingWrite::new(name, arguments, error);
+        let position = {
+            let mut pending = self.pending_writes.lock().unwrap();
+            pending.push(write.clone());
+            self.store.save(PENDING_WRITES, &*pending)?;
+            pending.len()
+        };
+        warn!("Splitwise unreachable; queued {} as write {}", name, write.key);
+        Ok(json!({
+            "queued": true,
+            "key": write.key,
+            "tool": name,
+            "position": position,
+            "note": "Splitwise can't be reached, so nothing was changed yet. The write is saved and will be sent before the next one, or by flush_pending_writes",
+        }))
+    }
+
+    // Send the queued writes (or those in `keys`) in order. Returns what happened to each
+    async fn flush_writes(&self, keys: Option<&[String]>, force: bool) -> Result<Value> {
+        let _flushing = self.flushing.lock().await;
+        let queued: Vec<PendingWrite> = self.pending_writes.lock().unwrap().clone();
+        let mut sent = Vec::new();
+        let mut held = Vec::new();
+        let mut unreachable = None;
+        // Expenses this flush already wrote to; a later write to them isn't a conflict
+        let mut written: HashSet<i64> = HashSet::new();
+        for mut write in queued {
+            if keys.is_some_and(|keys| !keys.contains(&write.key)) {
+                continue;
+            }
+            write.attempts += 1;
+            let outcome = async {
+                if let Some(expense_id) = write.expense_id().filter(|id| !force && !written.contains(id)) {
+                    let expense = self.client.get_expense(expense_id).await?;
+                    if write.is_stale_against(&expense.updated_at) {
+                        return Ok(None);
+                    }
+                }
+                Box::pin(self.write_expense(&write.tool, write.arguments.clone())).await.map(Some)
+            }
+            .await;
+            match outcome {
+                Ok(Some(result)) => {
+                    written.extend(write.expense_id().or(result["id"].as_i64()));
+                    sent.push(json!({ "key": write.key, "tool": write.tool, "result": result }));
+                    self.pending_writes.lock().unwrap().retain(|w| w.key != write.key);
+                    continue;
+                }
+                Ok(None) => {
+                    write.conflict = Some(format!(
+                        "Expense {} was changed in Splitwise after this write was queued; flush with force to overwrite it, or discard the write",
+                        write.expense_id().unwrap_or_default()
+                    ));
+                }
+                Err(e) if write_queue::is_unreachable(&e) => {
+                    write.last_error = Some(format!("{:#}", e));
+                    unreachable = Some(format!("{:#}", e));
+                }
+                Err(e) => write.last_error = Some(format!("{:#}", e)),
+            }
+            held.push(json!({ "key": write.key, "tool": write.tool, "conflict": write.conflict, "error": write.last_error }));
+            let mut pending = self.pending_writes.lock().unwrap();
+            if let Some(slot) = pending.iter_mut().find(|w| w.key == write.key) {
+                *slot = write;
+            }
+            if unreachable.is_some() {
+                break;
+            }
+        }
+        let pending = self.pending_writes.lock().unwrap();
+        self.store.save(PENDING_WRITES, &*pending)?;
+        let mut result = json!({
+            "sent": sent,
+            "held": held,
+            "remaining": pending.len(),
+        });
+        if let Some(error) = unreachable {
+            result["stopped"] = json!(format!("Splitwise still can't be reached: {}", error));
+        }
+        Ok(result)
+    }
+
+    async fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+
+        match name {
+            "get_dashboard" => self.dashboard().await,
+            // User tools
+            "get_current_user" => {
+                let user = self.client.get_current_user().await?;
+                Ok(serde_json::to_value(user)?)
+            }
+            "get_user" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let user_id = self
+                    .user_id_from(args.user_id, args.user_name.as_deref())
+                    .await?
+                    .context("Provide user_id or user_name")?;
+                let user = self.client.get_user(user_id).await?;
+                Ok(serde_json::to_value(user)?)
+            }
+            // Group tools
+            "list_groups" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    fields: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let groups = self.client.get_groups().await?;
+                // Splitwise returns all groups at once, so there is never another page
+                let count = groups.len();
+                let items = projection::project_if(serde_json::to_value(groups)?, args.fields.as_deref());
+                Ok(page_envelope(serde_json::from_value(items)?, count, None))
+            }
+            "get_group" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    fields: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let group = self.client.get_group(group_id).await?;
+                Ok(projection::project_if(serde_json::to_value(group)?, args.fields.as_deref()))
+            }
+            "create_group" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    name: String,
+                    group_type: Option<String>,
+                    simplify_by_default: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let request = CreateGroupRequest {
+                    name: args.name,
+                    group_type: args.group_type,
+                    simplify_by_default: args.simplify_by_default,
+                    users: vec![], // Current user is added automatically
+                };
+                let group = self.client.create_group(request).await?;
+                self.lookups.invalidate_people();
+                Ok(serde_json::to_value(group)?)
+            }
+            // Expense tools
+            "list_expenses" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    limit: Option<i32>,
+                    offset: Option<i32>,
+                    fields: Vec<String>,  // Now required
+                    search_text: Option<String>,
+                    search_mode: Option<String>,
+                    fuzzy_threshold: Option<f64>,
+                    search_fields: Option<Vec<String>>,
+                    category_ids: Option<Vec<i64>>,
+                    category_names: Option<Vec<String>>,
+                    tags: Option<Vec<String>>,
+                    group_ids: Option<Vec<i64>>,
+                    include_personal: Option<bool>,
+                    include_deleted: Option<String>,
+                    convert_to: Option<String>,
+                    rate_date: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let friend_id = self.user_id_from(args.friend_id, args.user_name.as_deref()).await?;
+                let category_ids = self.category_filter(args.category_ids, args.category_names).await?;
+                
+                // Default to excluding deleted expenses
+                let include_deleted = args.include_deleted.as_deref().unwrap_or("exclude");
+                
+                let matcher = args
+                    .search_text
+                    .as_deref()
+                    .map(|text| TextMatcher::new(text, args.search_mode.as_deref(), args.fuzzy_threshold))
+                    .transpose()?;
+                let search_fields = args.search_fields.clone().unwrap_or_else(|| {
+                    vec!["description".to_string(), "details".to_string(), "category".to_string()]
+                });
+                // Whether an expense passes the deleted, category and text filters
+                let matches = |expense: &Expense| -> bool {
+                    // Handle deleted expense filtering
+                    match include_deleted {
+                        "exclude" => {
+                            if expense.deleted_at.is_some() {
+                                return false;
+                            }
+                        },
+                        "only" => {
+                            if expense.deleted_at.is_none() {
+                                return false;
+                            }
+                        },
+                        "include" => {
+                            // Include all expenses regardless of deleted status
+                        },
+                        _ => {
+                            // Default to exclude if somehow invalid value
+                            if expense.deleted_at.is_some() {
+                                return false;
+                            }
+                        }
+                    }
+                    
+                    // Check category filter first
+                    if let Some(ref category_ids) = category_ids {
+                        if !category_ids.contains(&expense.category.id) {
+                            return false;
+                        }
+                    }
+                    
+                    if let Some(ref tags) = args.tags {
+                        let (_, metadata) = metadata::split(expense.details.as_deref());
+                        if !tags.iter().all(|t| metadata.has_tag(t)) {
+                            return false;
+                        }
+                    }
+                    
+                    // Then check text search if present
+                    if let Some(ref matcher) = matcher {
+                        for field in &search_fields {
+                            match field.as_str() {
+                                "description" => {
+                                    if matcher.is_match(&expense.description) {
+                                        return true;
+                                    }
+                                },
+                                "details" => {
+                                    if expense.details.as_ref().is_some_and(|d| matcher.is_match(d)) {
+                                        return true;
+                                    }
+                                },
+                                "category" => {
+                                    if matcher.is_match(&expense.category.name) {
+                                        return true;
+                                    }
+                                },
+                                _ => {}
+                            }
+                        }
+                        // If search text was provided but no match found, exclude this expense
+                        return false;
+                    }
+                    
+                    // If no search text but category matched (or no filters), include it
+                    true
+                };
+
+                let offset = args.offset.unwrap_or(0).max(0) as usize;
+                // Like Splitwise, a limit of 0 means no limit
+                let limit = args.limit.filter(|l| *l > 0).map(|l| l as usize);
+                let params = |scope: Option<i64>| ListExpensesParams {
+                    group_id: scope,
+                    friend_id,
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    updated_after: None,
+                    updated_before: None,
+                    limit: None,
+                    offset: None,
+                };
+                let mut expenses = Vec::new();
+                // The offset each expense was found at, where a page cut short continues from
+                let positions: Vec<usize>;
+                let mut scanned = 0;
+                let mut next_offset = None;
+
+                // Several groups and/or personal expenses: scan each scope concurrently, then merge
+                let mut scopes: Vec<i64> = args.group_ids.clone().unwrap_or_default();
+                scopes.extend(group_id.filter(|id| !scopes.contains(id)));
+                let include_personal = args.include_personal.unwrap_or(false);
+                if args.group_ids.is_some() || include_personal {
+                    // One match past the page tells whether another page follows
+                    let wanted = limit.map(|l| offset + l + 1);
+                    // Splitwise has no filter for non-group expenses, so scan everything and keep those
+                    let personal = |expense: &Expense| expense.group_id.unwrap_or(0) == 0 && matches(expense);
+
+                    let mut scans = Vec::new();
+                    for scope in &scopes {
+                        scans.push(self.scan_expenses_counted(params(Some(*scope)), wanted, &matches));
+                    }
+                    if include_personal {
+                        scans.push(self.scan_expenses_counted(params(None), wanted, &personal));
+                    }
+                    for scan in futures::future::join_all(scans).await {
+                        let (found, examined) = scan?;
+                        expenses.extend(found);
+                        scanned += examined;
+                    }
+
+                    // Newest first across scopes, as Splitwise orders a single scope. Here the
+                    // offset counts matches, since there is no single upstream order to resume in
+                    expenses.sort_by(|a, b| b.date.cmp(&a.date));
+                    expenses = expenses.into_iter().skip(offset).collect();
+                    if let Some(limit) = limit {
+                        if expenses.len() > limit {
+                            expenses.truncate(limit);
+                            next_offset = Some(offset + limit);
+                        }
+                    }
+                    positions = (offset..offset + expenses.len()).collect();
+                } else if args.search_text.is_some()
+                    || category_ids.is_some()
+                    || args.tags.is_some()
+                    || (include_deleted != "include" && limit.is_some())
+                {
+                    // Filtering locally: walk the history in batches until the page is full
+                    let params = params(group_id);
+                    let found;
+                    (found, scanned, next_offset) = self.page_expenses(params, offset, limit, &matches).await?;
+                    (positions, expenses) = found.into_iter().unzip();
+                } else {
+                    // Nothing to filter but (without a limit) deleted expenses: a single request.
+                    // Splitwise returns 20 expenses when no limit is given, and all of them for 0
+                    let page = match args.limit {
+                        Some(0) => None,
+                        _ => Some(limit.unwrap_or(20)),
+                    };
+                    let mut params = params(group_id);
+                    params.limit = Some(page.map_or(0, |p| p as i32 + 1));
+                    params.offset = Some(offset as i32);
+                    // Unreadable items still take up their place in the page
+                    let mut batch = self.client.get_expense_page(params).await?;
+                    if let Some(page) = page.filter(|p| batch.len() > *p) {
+                        batch.truncate(page);
+                        next_offset = Some(offset + page);
+                    }
+                    scanned = batch.len();
+                    (positions, expenses) = (offset..)
+                        .zip(batch)
+                        .filter_map(|(position, expense)| Some((position, expense.filter(|e| matches(e))?)))
+                        .unzip();
+                }
+                
+                // Convert costs before projecting, one rate lookup per expense (cached per date)
+                let mut converted_costs = Vec::new();
+                if let Some(ref target) = args.convert_to {
+                    let use_latest = args.rate_date.as_deref() == Some("latest");
+                    for exp in &expenses {
+                        let amount: f64 = exp.cost.parse().unwrap_or(0.0);
+                        let conversion = if use_latest {
+                            self.rates.convert(amount, &exp.currency_code, target).await?
+                        } else {
+                            self.rates.convert_on(amount, &exp.currency_code, target, &exp.date).await?
+                        };
+                        converted_costs.push(json!({
+                            "amount": format_amount(conversion.converted),
+                            "currency": target.to_uppercase(),
+                            "rate": conversion.rate,
+                            "rate_date": conversion.date,
+                        }));
+                    }
+                }
+
+                // Filter to requested fields
+                let me = self.cached_me().await?;
+                let filtered: Vec<serde_json::Value> = expenses.into_iter().enumerate().map(|(i, exp)| {
+                    let mut obj = project_expense(&exp, me.id, &args.fields);
+                    if let Some(converted) = converted_costs.get(i) {
+                        obj["converted_cost"] = converted.clone();
+                    }
+                    obj
+                }).collect();
+                let mut page = page_envelope(filtered, scanned, next_offset);
+                page[budget::POSITIONS] = json!(positions);
+                Ok(page)
+            }
+            "query_expenses_nl" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    question: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                let query = nl_query::parse(&args.question, chrono::Local::now().date_naive());
+                let question = nl_query::normalize(&args.question);
+
+                let (me, groups, friends, categories) = tokio::try_join!(
+                    self.cached_me(),
+                    self.cached_groups(),
+                    self.cached_friends(),
+                    self.cached_categories(),
+                )?;
+
+                // Group 0 is Splitwise's "Non-group expenses" pseudo-group
+                let real_groups = || groups.iter().filter(|g| g.id != 0);
+                let group = match query.group_phrase {
+                    Some(ref phrase) => {
+                        let ranked = resolve::rank(phrase, real_groups().map(|g| (g.id, g.name.clone())));
+                        let id = pick("group", phrase, &ranked)?;
+                        real_groups().find(|g| g.id == id)
+                    }
+                    // Without the word "group", accept any group name mentioned verbatim
+                    None => real_groups()
+                        .filter(|g| nl_query::contains_word(&question, &nl_query::normalize(&g.name)))
+                        .max_by_key(|g| g.name.len()),
+                };
+
+                let friend = match query.friend_phrase {
+                    Some(ref phrase) => {
+                        let ranked = resolve::rank(
+                            phrase,
+                            friends.iter().map(|f| (f.id, full_name(&f.first_name, f.last_name.as_deref()))),
+                        );
+                        let id = pick("friend", phrase, &ranked)?;
+                        friends.iter().find(|f| f.id == id)
+                    }
+                    None => None,
+                };
+
+                // A parent category covers all of its subcategories
+                let mut category_ids = Vec::new();
+                let mut category_names = Vec::new();
+                for name in &query.categories {
+                    for parent in categories.iter() {
+                        let subcategories = parent.subcategories.as_deref().unwrap_or(&[]);
+                        if parent.name.eq_ignore_ascii_case(name) {
+                            category_ids.push(parent.id);
+                            category_ids.extend(subcategories.iter().map(|s| s.id));
+                            category_names.push(parent.name.clone());
+                        } else if let Some(sub) = subcategories.iter().find(|s| s.name.eq_ignore_ascii_case(name)) {
+                            category_ids.push(sub.id);
+                            category_names.push(sub.name.clone());
+                        }
+                    }
+                }
+
+                let search = query.search_text.as_deref().map(nl_query::normalize);
+                let params = ListExpensesParams {
+                    group_id: group.map(|g| g.id),
+                    friend_id: friend.map(|f| f.id),
+                    dated_after: query.dated_after.clone(),
+                    dated_before: query.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| {
+                    e.deleted_at.is_none()
+                        && !e.payment
+                        && (query.categories.is_empty() || category_ids.contains(&e.category.id))
+                        && search
+                            .as_ref()
+                            .is_none_or(|s| nl_query::normalize(&e.description).contains(s.as_str()))
+                };
+                let matching = self.scan_expenses(params, None, &keep).await?;
+                // The scan stops early only when the call's budget runs out
+                let truncated = budget::exceeded();
+
+                // Per currency: the full cost and the current user's share of it
+                let mut totals: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+                let mut by_category: BTreeMap<(&str, &str), (usize, f64)> = BTreeMap::new();
+                for expense in &matching {
+                    let cost: f64 = expense.cost.parse().unwrap_or(0.0);
+                    let my_share: f64 = expense
+                        .users
+                        .iter()
+                        .find(|u| u.user_id == me.id)
+                        .and_then(|u| u.owed_share.parse().ok())
+                        .unwrap_or(0.0);
+                    let total = totals.entry(&expense.currency_code).or_default();
+                    total.0 += cost;
+                    total.1 += my_share;
+                    let category = by_category
+                        .entry((&expense.category.name, &expense.currency_code))
+                        .or_default();
+                    category.0 += 1;
+                    category.1 += my_share;
+                }
+
+                let summary = if totals.is_empty() {
+                    "No matching expenses".to_string()
+                } else {
+                    let parts: Vec<String> = totals
+                        .iter()
+                        .map(|(currency, (total, mine))| {
+                            format!("{} {} total, {} {} your share", format_amount(*total), currency, format_amount(*mine), currency)
+                        })
+                        .collect();
+                    format!("{} matching expense(s): {}", matching.len(), parts.join("; "))
+                };
+
+                let mut by_category: Vec<Value> = by_category
+                    .into_iter()
+                    .map(|((category, currency), (count, mine))| json!({
+                        "category": category,
+                        "currency": currency,
+                        "count": count,
+                        "my_share": format_amount(mine),
+                    }))
+                    .collect();
+                by_category.sort_by_key(|c| std::cmp::Reverse(c["count"].as_u64().unwrap_or(0)));
+
+                Ok(json!({
+                    "question": args.question,
+                    "filter": {
+                        "period": query.period,
+                        "dated_after": query.dated_after,
+                        "dated_before": query.dated_before,
+                        "group": group.map(|g| json!({ "id": g.id, "name": g.name })),
+                        "friend": friend.map(|f| json!({ "id": f.id, "name": full_name(&f.first_name, f.last_name.as_deref()) })),
+                        "categories": category_names,
+                        "category_ids": category_ids,
+                        "search_text": query.search_text,
+                    },
+                    "answer": {
+                        "summary": summary,
+                        "count": matching.len(),
+                        "totals": totals.iter().map(|(currency, (total, mine))| json!({
+                            "currency": currency,
+                            "total_cost": format_amount(*total),
+                            "my_share": format_amount(*mine),
+                        })).collect::<Vec<_>>(),
+                        "by_category": by_category,
+                    },
+                    "truncated": truncated,
+                }))
+            }
+            "explain_expense" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_id: i64,
+                }
+                let args: Args = parse_args(arguments)?;
+                let (expense, me, groups) = tokio::try_join!(
+                    self.client.get_expense(args.expense_id),
+                    self.cached_me(),
+                    self.cached_groups(),
+                )?;
+
+                let currency = &expense.currency_code;
+                let name_of = |id: i64| -> String {
+                    if id == me.id {
+                        return "You".to_string();
+                    }
+                    expense
+                        .users
+                        .iter()
+                        .find(|u| u.user_id == id)
+                        .and_then(|u| u.user.as_ref())
+                        .map(|u| full_name(&u.first_name, u.last_name.as_deref()))
+                        .unwrap_or_else(|| format!("User {}", id))
+                };
+                let money = |value: f64| format!("{} {}", format_amount(value), currency);
+
+                let members: Vec<Value> = expense
+                    .users
+                    .iter()
+                    .map(|u| {
+                        let paid: f64 = u.paid_share.parse().unwrap_or(0.0);
+                        let owed: f64 = u.owed_share.parse().unwrap_or(0.0);
+                        let net = paid - owed;
+                        let effect = if net > 0.005 {
+                            format!("is owed {}", money(net))
+                        } else if net < -0.005 {
+                            format!("owes {}", money(-net))
+                        } else {
+                            "comes out even".to_string()
+                        };
+                        json!({
+                            "user_id": u.user_id,
+                            "name": name_of(u.user_id),
+                            "paid": format_amount(paid),
+                            "share": format_amount(owed),
+                            "net": format_amount(net),
+                            "effect": effect,
+                        })
+                    })
+                    .collect();
+
+                let payers: Vec<String> = expense
+                    .users
+                    .iter()
+                    .filter(|u| !is_zero_amount(&u.paid_share))
+                    .map(|u| format!("{} paid {}", name_of(u.user_id), money(u.paid_share.parse().unwrap_or(0.0))))
+                    .collect();
+                let shares: Vec<&str> = expense
+                    .users
+                    .iter()
+                    .filter(|u| !is_zero_amount(&u.owed_share))
+                    .map(|u| u.owed_share.as_str())
+                    .collect();
+                let split = if shares.windows(2).all(|w| (w[0].parse::<f64>().unwrap_or(0.0) - w[1].parse::<f64>().unwrap_or(0.0)).abs() < 0.015) {
+                    format!("split equally {} ways", shares.len())
+                } else {
+                    "split unequally".to_string()
+                };
+
+                let repayments: Vec<Value> = expense
+                    .repayments
+                    .iter()
+                    .map(|r| {
+                        let amount: f64 = r.amount.parse().unwrap_or(0.0);
+                        let verb = if r.from == me.id { "owe" } else { "owes" };
+                        let to = if r.to == me.id { "you".to_string() } else { name_of(r.to) };
+                        json!({
+                            "from": r.from,
+                            "to": r.to,
+                            "amount": r.amount,
+                            "text": format!("{} {} {} {}", name_of(r.from), verb, to, money(amount)),
+                        })
+                    })
+                    .collect();
+
+                let you = expense.users.iter().find(|u| u.user_id == me.id).map(|u| {
+                    let net: f64 = u.net_balance.parse().unwrap_or(0.0);
+                    let effect = if net > 0.005 {
+                        format!("You are owed {} in total", money(net))
+                    } else if net < -0.005 {
+                        format!("You owe {} in total", money(-net))
+                    } else {
+                        "You come out even".to_string()
+                    };
+                    json!({ "paid": u.paid_share, "share": u.owed_share, "net": u.net_balance, "effect": effect })
+                });
+
+                let group = expense
+                    .group_id
+                    .and_then(|id| groups.iter().find(|g| g.id == id))
+                    .map(|g| g.name.as_str());
+                let summary = format!(
+                    "{}{}: {} ({}) on {}{}. {}, {}.",
+                    if expense.payment { "Payment " } else { "" },
+                    expense.description,
+                    money(expense.cost.parse().unwrap_or(0.0)),
+                    expense.category.name,
+                    expense.date.get(..10).unwrap_or(&expense.date),
+                    group.map(|g| format!(" in {}", g)).unwrap_or_default(),
+                    if payers.is_empty() { "Nobody paid".to_string() } else { payers.join(", ") },
+                    split,
+                );
+
+                Ok(json!({
+                    "expense_id": expense.id,
+                    "summary": summary,
+                    "deleted": expense.deleted_at.is_some(),
+                    "members": members,
+                    "you": you,
+                    "who_pays_whom": repayments,
+                }))
+            }
+            "tag_expense" | "untag_expense" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_id: i64,
+                    tags: Vec<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let expense = self
+                    .edit_metadata(args.expense_id, |metadata| {
+                        if name == "tag_expense" {
+                            metadata.add_tags(&args.tags);
+                        } else {
+                            metadata.remove_tags(&args.tags);
+                        }
+                    })
+                    .await?;
+                Ok(json!({
+                    "success": true,
+                    "id": expense.id,
+                    "description": expense.description,
+                    "tags": metadata::split(expense.details.as_deref()).1.tags,
+                }))
+            }
+            "list_expenses_by_tag" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    tag: String,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let params = ListExpensesParams {
+                    group_id,
+                    friend_id: None,
+                    dated_after: args.dated_after,
+                    dated_before: args.dated_before,
+                    updated_after: None,
+                    updated_before: None,
+                    limit: None,
+                    offset: None,
+                };
+                let tagged = |e: &Expense| e.deleted_at.is_none() && metadata::split(e.details.as_deref()).1.has_tag(&args.tag);
+                let expenses = self.scan_expenses(params, None, &tagged).await?;
+
+                let mut totals: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+                for expense in &expenses {
+                    let total = totals.entry(&expense.currency_code).or_default();
+                    total.0 += expense.cost.parse::<f64>().unwrap_or(0.0);
+                    total.1 += owed_share_of(expense, me.id);
+                }
+                Ok(json!({
+                    "tag": args.tag,
+                    "count": expenses.len(),
+                    "totals": totals.iter().map(|(currency, (total, mine))| json!({
+                        "currency": currency,
+                        "total_cost": format_amount(*total),
+                        "my_share": format_amount(*mine),
+                    })).collect::<Vec<_>>(),
+                    "expenses": expenses.iter().map(|e| json!({
+                        "id": e.id,
+                        "description": e.description,
+                        "date": e.date,
+                        "cost": e.cost,
+                        "currency_code": e.currency_code,
+                        "group_id": e.group_id,
+                        "my_share": format_amount(owed_share_of(e, me.id)),
+                        "tags": metadata::split(e.details.as_deref()).1.tags,
+                    })).collect::<Vec<_>>(),
+                }))
+            }
+            "summarize_expenses" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_by: Option<String>,
+                    tags: Option<Vec<String>>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_by = args.group_by.as_deref().unwrap_or("category");
+                if !["category", "month", "tag"].contains(&group_by) {
+                    anyhow::bail!("Unsupported group_by '{}'; use category, month or tag", group_by);
+                }
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let params = ListExpensesParams {
+                    group_id,
+                    friend_id: None,
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    updated_after: None,
+                    updated_before: None,
+                    limit: None,
+                    offset: None,
+                };
+                let wanted_tags = args.tags.clone().unwrap_or_default();
+                let keep = |e: &Expense| {
+                    e.deleted_at.is_none()
+                        && !e.payment
+                        && (wanted_tags.is_empty() || {
+                            let (_, metadata) = metadata::split(e.details.as_deref());
+                            wanted_tags.iter().all(|t| metadata.has_tag(t))
+                        })
+                };
+                let expenses = self.scan_expenses(params, None, &keep).await?;
+
+                // (key, currency) -> (count, total cost, what the user paid, what the user owes)
+                let mut groups: BTreeMap<(String, String), (usize, f64, f64, f64)> = BTreeMap::new();
+                for expense in &expenses {
+                    let keys = match group_by {
+                        "month" => vec![expense.date.chars().take(7).collect()],
+                        "tag" => {
+                            let tags = metadata::split(expense.details.as_deref()).1.tags;
+                            if tags.is_empty() {
+                                vec!["(untagged)".to_string()]
+                            } else {
+                                tags
+                            }
+                        }
+                        _ => vec![expense.category.name.clone()],
+                    };
+                    for key in keys {
+                        let entry = groups.entry((key, expense.currency_code.clone())).or_default();
+                        entry.0 += 1;
+                        entry.1 += expense.cost.parse::<f64>().unwrap_or(0.0);
+                        entry.2 += paid_share_of(expense, me.id);
+                        entry.3 += owed_share_of(expense, me.id);
+                    }
+                }
+
+                Ok(json!({
+                    "group_by": group_by,
+                    "filter": {
+                        "group_id": group_id,
+                        "dated_after": args.dated_after,
+                        "dated_before": args.dated_before,
+                        "tags": args.tags,
+                    },
+                    "expense_count": expenses.len(),
+                    "groups": groups.into_iter().map(|((key, currency), (count, total, paid, owed))| json!({
+                        group_by: key,
+                        "currency": currency,
+                        "count": count,
+                        "total_cost": format_amount(total),
+                        "my_share": format_amount(owed),
+                        "relative_to_me": {
+                            "paid_share": format_amount(paid),
+                            "owed_share": format_amount(owed),
+                            "net_balance": format_amount(paid - owed),
+                        },
+                    })).collect::<Vec<_>>(),
+                }))
+            }
+            "compare_periods" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    first: Value,
+                    second: Value,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let today = chrono::Local::now().date_naive();
+                let first = period_range(&args.first, today)?;
+                let second = period_range(&args.second, today)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+
+                let params = |period: &PeriodRange| ListExpensesParams {
+                    group_id,
+                    dated_after: period.dated_after.clone(),
+                    dated_before: period.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment;
+                let (first_expenses, second_expenses) = tokio::try_join!(
+                    self.scan_expenses(params(&first), None, &keep),
+                    self.scan_expenses(params(&second), None, &keep),
+                )?;
+
+                let mut by_category: BTreeMap<(String, String), PeriodSides> = BTreeMap::new();
+                for (side, expenses) in [&first_expenses, &second_expenses].into_iter().enumerate() {
+                    for expense in expenses {
+                        let key = (expense.category.name.clone(), expense.currency_code.clone());
+                        let entry = &mut by_category.entry(key).or_default()[side];
+                        entry.0 += 1;
+                        entry.1 += expense.cost.parse::<f64>().unwrap_or(0.0);
+                        entry.2 += owed_share_of(expense, me.id);
+                    }
+                }
+                let mut by_currency: BTreeMap<String, PeriodSides> = BTreeMap::new();
+                for ((_, currency), sides) in &by_category {
+                    let entry = by_currency.entry(currency.clone()).or_default();
+                    for (total, side) in entry.iter_mut().zip(sides) {
+                        total.0 += side.0;
+                        total.1 += side.1;
+                        total.2 += side.2;
+                    }
+                }
+
+                let mut categories: Vec<_> = by_category.into_iter().collect();
+                categories.sort_by(|(_, a), (_, b)| (b[1].1 - b[0].1).abs().total_cmp(&(a[1].1 - a[0].1).abs()));
+                Ok(json!({
+                    "first": first.describe(),
+                    "second": second.describe(),
+                    "group_id": group_id,
+                    "totals": by_currency.into_iter().map(|(currency, sides)| {
+                        let mut row = period_comparison(&sides);
+                        row["currency"] = json!(currency);
+                        row
+                    }).collect::<Vec<_>>(),
+                    "categories": categories.into_iter().map(|((category, currency), sides)| {
+                        let mut row = period_comparison(&sides);
+                        row["category"] = json!(category);
+                        row["currency"] = json!(currency);
+                        row
+                    }).collect::<Vec<_>>(),
+                }))
+            }
+            "contribution_report" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    period_by: Option<String>,
+                    include_payments: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let period_by = args.period_by.as_deref().unwrap_or("none");
+                let period_chars = match period_by {
+                    "none" => None,
+                    "month" => Some(7),
+                    "year" => Some(4),
+                    other => anyhow::bail!("Unsupported period_by '{}'; use none, month or year", other),
+                };
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let include_payments = args.include_payments.unwrap_or(false);
+                let params = ListExpensesParams {
+                    group_id: Some(group_id),
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && (include_payments || !e.payment);
+                let (group, mut expenses) = tokio::try_join!(
+                    self.client.get_group(group_id),
+                    self.scan_expenses(params, None, &keep),
+                )?;
+                let merged_aliases = self.merge_aliases(&mut expenses);
+
+                let mut names: HashMap<i64, String> = group
+                    .members
+                    .iter()
+                    .map(|m| (m.id, full_name(&m.first_name, m.last_name.as_deref())))
+                    .collect();
+                // (period, currency, user) -> (expenses paid for, paid, consumed)
+                let mut figures: BTreeMap<(String, String, i64), (usize, f64, f64)> = BTreeMap::new();
+                for expense in &expenses {
+                    let period = match period_chars {
+                        Some(n) => expense.date.chars().take(n).collect(),
+                        None => String::new(),
+                    };
+                    for share in &expense.users {
+                        if let Some(ref user) = share.user {
+                            names
+                                .entry(share.user_id)
+                                .or_insert_with(|| full_name(&user.first_name, user.last_name.as_deref()));
+                        }
+                        let paid = share.paid_share.parse::<f64>().unwrap_or(0.0);
+                        let owed = share.owed_share.parse::<f64>().unwrap_or(0.0);
+                        let keys = std::iter::once(String::new())
+                            .chain((!period.is_empty()).then(|| period.clone()));
+                        for key in keys {
+                            let entry = figures
+                                .entry((key, expense.currency_code.clone(), share.user_id))
+                                .or_default();
+                            entry.0 += usize::from(paid.abs() >= 0.005);
+                            entry.1 += paid;
+                            entry.2 += owed;
+                        }
+                    }
+                }
+
+                // Everything paid per (period, currency), for each member's percentage
+                let mut paid_totals: HashMap<(&str, &str), f64> = HashMap::new();
+                for ((period, currency, _), (_, paid, _)) in &figures {
+                    *paid_totals.entry((period, currency)).or_default() += paid;
+                }
+                let row = |(period, currency, user_id): &(String, String, i64), (count, paid, owed): &(usize, f64, f64)| {
+                    let total = paid_totals.get(&(period.as_str(), currency.as_str())).copied().unwrap_or(0.0);
+                    json!({
+                        "user_id": user_id,
+                        "name": names.get(user_id).cloned().unwrap_or_else(|| format!("User {}", user_id)),
+                        "currency": currency,
+                        "paid": format_amount(*paid),
+                        "consumed": format_amount(*owed),
+                        "net_contribution": format_amount(paid - owed),
+                        "paid_pct": (total.abs() >= 0.005).then(|| format!("{:.1}", paid / total * 100.0)),
+                        "expenses_paid_for": count,
+                    })
+                };
+                let mut overall: Vec<_> = figures.iter().filter(|((period, _, _), _)| period.is_empty()).collect();
+                // Biggest payers first within each currency
+                overall.sort_by(|(a_key, a), (b_key, b)| a_key.1.cmp(&b_key.1).then(b.1.total_cmp(&a.1)));
+
+                let mut result = json!({
+                    "group_id": group_id,
+                    "group_name": group.name,
+                    "filter": {
+                        "dated_after": args.dated_after,
+                        "dated_before": args.dated_before,
+                        "include_payments": include_payments,
+                    },
+                    "expense_count": expenses.len(),
+                    "members": overall.into_iter().map(|(key, value)| row(key, value)).collect::<Vec<_>>(),
+                });
+                if !merged_aliases.is_empty() {
+                    result["merged_aliases"] = json!(merged_aliases);
+                }
+                if period_chars.is_some() {
+                    result["by_period"] = figures
+                        .iter()
+                        .filter(|((period, _, _), _)| !period.is_empty())
+                        .map(|(key, value)| {
+                            let mut row = row(key, value);
+                            row["period"] = json!(key.0);
+                            row
+                        })
+                        .collect();
+                }
+                Ok(result)
+            }
+            "category_drilldown" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    category_id: Option<i64>,
+                    category_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    top: Option<usize>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let top = args.top.unwrap_or(5).clamp(1, 50);
+                let mut ids = self
+                    .category_filter(args.category_id.map(|id| vec![id]), args.category_name.map(|name| vec![name]))
+                    .await?
+                    .context("Provide category_id or category_name")?;
+                // A parent category given by ID covers its subcategories too
+                let categories = self.cached_categories().await?;
+                let parent = categories.iter().find(|c| ids.contains(&c.id));
+                if let Some(parent) = parent {
+                    ids.extend(parent.subcategories.iter().flatten().map(|s| s.id));
+                }
+                ids.sort_unstable();
+                ids.dedup();
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment && ids.contains(&e.category.id);
+                let mut expenses = self.scan_expenses(params, None, &keep).await?;
+
+                // currency -> (count, total cost, the user's share)
+                let mut totals: BTreeMap<String, (usize, f64, f64)> = BTreeMap::new();
+                // (month, currency) and (subcategory, currency) -> (count, total cost)
+                let mut monthly: BTreeMap<(String, String), (usize, f64)> = BTreeMap::new();
+                let mut by_subcategory: BTreeMap<(String, String), (usize, f64)> = BTreeMap::new();
+                let descriptions: Vec<&str> = expenses.iter().map(|e| e.description.as_str()).collect();
+                // (merchant, currency) -> (count, total cost)
+                let mut merchants: HashMap<(String, String), (usize, f64)> = HashMap::new();
+                for (expense, merchant) in expenses.iter().zip(self.merchants.group(&descriptions)) {
+                    let cost = expense.cost.parse::<f64>().unwrap_or(0.0);
+                    let currency = expense.currency_code.clone();
+                    let total = totals.entry(currency.clone()).or_default();
+                    total.0 += 1;
+                    total.1 += cost;
+                    total.2 += owed_share_of(expense, me.id);
+                    for (map, key) in [
+                        (&mut monthly, expense.date.chars().take(7).collect()),
+                        (&mut by_subcategory, expense.category.name.clone()),
+                    ] {
+                        let entry = map.entry((key, currency.clone())).or_default();
+                        entry.0 += 1;
+                        entry.1 += cost;
+                    }
+                    let merchant = merchants.entry((merchant, currency)).or_default();
+                    merchant.0 += 1;
+                    merchant.1 += cost;
+                }
+                let mut merchants: Vec<_> = merchants.into_iter().collect();
+                merchants.sort_by(|(_, (_, a)), (_, (_, b))| b.total_cmp(a));
+                expenses.sort_by(|a, b| {
+                    let cost = |e: &Expense| e.cost.parse::<f64>().unwrap_or(0.0);
+                    cost(b).total_cmp(&cost(a))
+                });
+                let fields: Vec<String> = ["id", "date", "description", "cost", "currency_code", "category.name", "group_id", "my_owed_share"]
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect();
+
+                let mut result = json!({
+                    "category": parent.map(|p| &p.name).or_else(|| {
+                        categories
+                            .iter()
+                            .flat_map(|c| c.subcategories.iter().flatten())
+                            .find(|s| ids.contains(&s.id))
+                            .map(|s| &s.name)
+                    }),
+                    "category_ids": ids,
+                    "filter": {
+                        "group_id": group_id,
+                        "dated_after": args.dated_after,
+                        "dated_before": args.dated_before,
+                    },
+                    "expense_count": expenses.len(),
+                    "totals": totals.into_iter().map(|(currency, (count, total, share))| json!({
+                        "currency": currency,
+                        "count": count,
+                        "total_cost": format_amount(total),
+                        "my_share": format_amount(share),
+                    })).collect::<Vec<_>>(),
+                    "monthly": monthly.into_iter().map(|((month, currency), (count, total))| json!({
+                        "month": month,
+                        "currency": currency,
+                        "count": count,
+                        "total_cost": format_amount(total),
+                    })).collect::<Vec<_>>(),
+                    "top_merchants": merchants.into_iter().take(top).map(|((name, currency), (count, total))| json!({
+                        "merchant": name,
+                        "currency": currency,
+                        "count": count,
+                        "total_cost": format_amount(total),
+                    })).collect::<Vec<_>>(),
+                    "largest": expenses.iter().take(top).map(|e| project_expense(e, me.id, &fields)).collect::<Vec<_>>(),
+                });
+                if parent.is_some() {
+                    result["subcategories"] = by_subcategory
+                        .into_iter()
+                        .map(|((name, currency), (count, total))| json!({
+                            "subcategory": name,
+                            "currency": currency,
+                            "count": count,
+                            "total_cost": format_amount(total),
+                        }))
+                        .collect();
+                }
+                Ok(result)
+            }
+            "top_merchants" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    category_names: Option<Vec<String>>,
+                    limit: Option<usize>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let limit = args.limit.unwrap_or(10).clamp(1, 100);
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let categories = self.category_filter(None, args.category_names.clone()).await?;
+                let me = self.cached_me().await?;
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| {
+                    e.deleted_at.is_none()
+                        && !e.payment
+                        && categories.as_ref().is_none_or(|ids| ids.contains(&e.category.id))
+                };
+                let expenses = self.scan_expenses(params, None, &keep).await?;
+
+                #[derive(Default)]
+                struct Merchant {
+                    count: usize,
+                    total: f64,
+                    share: f64,
+                    last_date: String,
+                    descriptions: Vec<String>,
+                }
+                let descriptions: Vec<&str> = expenses.iter().map(|e| e.description.as_str()).collect();
+                let mut merchants: HashMap<(String, String), Merchant> = HashMap::new();
+                for (expense, name) in expenses.iter().zip(self.merchants.group(&descriptions)) {
+                    let merchant = merchants.entry((name, expense.currency_code.clone())).or_default();
+                    merchant.count += 1;
+                    merchant.total += expense.cost.parse::<f64>().unwrap_or(0.0);
+                    merchant.share += owed_share_of(expense, me.id);
+                    if expense.date > merchant.last_date {
+                        merchant.last_date = expense.date.clone();
+                    }
+                    let description = expense.description.trim().to_string();
+                    if merchant.descriptions.len() < 3 && !merchant.descriptions.contains(&description) {
+                        merchant.descriptions.push(description);
+                    }
+                }
+                let mut merchants: Vec<_> = merchants.into_iter().collect();
+                merchants.sort_by(|(_, a), (_, b)| b.total.total_cmp(&a.total));
+
+                Ok(json!({
+                    "filter": {
+                        "group_id": group_id,
+                        "dated_after": args.dated_after,
+                        "dated_before": args.dated_before,
+                        "categories": args.category_names,
+                    },
+                    "expense_count": expenses.len(),
+                    "merchant_count": merchants.len(),
+                    "merchants": merchants.into_iter().take(limit).map(|((name, currency), m)| json!({
+                        "merchant": name,
+                        "currency": currency,
+                        "count": m.count,
+                        "total_cost": format_amount(m.total),
+                        "my_share": format_amount(m.share),
+                        "last_date": m.last_date.chars().take(10).collect::<String>(),
+                        "descriptions": m.descriptions,
+                    })).collect::<Vec<_>>(),
+                }))
+            }
+            "analyze_recurring_costs" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    dated_after: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    min_occurrences: Option<usize>,
+                    tolerance_pct: Option<f64>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let min_occurrences = args.min_occurrences.unwrap_or(3).max(2);
+                let tolerance = args.tolerance_pct.unwrap_or(15.0).abs() / 100.0;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let today = chrono::Local::now().date_naive();
+                let dated_after = args
+                    .dated_after
+                    .unwrap_or_else(|| (today - chrono::Months::new(12)).to_string());
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: Some(dated_after.clone()),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment;
+                let (me, expenses) = tokio::try_join!(self.cached_me(), self.scan_expenses(params, None, &keep))?;
+
+                let descriptions: Vec<&str> = expenses.iter().map(|e| e.description.as_str()).collect();
+                let mut clusters: BTreeMap<(String, String), Vec<&Expense>> = BTreeMap::new();
+                for (expense, name) in expenses.iter().zip(self.merchants.group(&descriptions)) {
+                    clusters.entry((name, expense.currency_code.clone())).or_default().push(expense);
+                }
+
+                let mut found = Vec::new();
+                for ((merchant, currency), mut charges) in clusters {
+                    // Splitwise already repeats these on its own
+                    if charges.len() < min_occurrences || charges.iter().any(|e| e.repeats) {
+                        continue;
+                    }
+                    let typical = median(charges.iter().map(|e| e.cost.parse().unwrap_or(0.0)).collect());
+                    charges.retain(|e| (e.cost.parse::<f64>().unwrap_or(0.0) - typical).abs() <= typical.abs() * tolerance);
+                    if charges.len() < min_occurrences {
+                        continue;
+                    }
+                    charges.sort_by(|a, b| a.date.cmp(&b.date));
+                    let dates: Vec<chrono::NaiveDate> = charges
+                        .iter()
+                        .filter_map(|e| chrono::NaiveDate::parse_from_str(e.date.get(..10)?, "%Y-%m-%d").ok())
+                        .collect();
+                    let gaps: Vec<i64> = dates.windows(2).map(|w| (w[1] - w[0]).num_days()).collect();
+                    let Some((cadence, per_year, gap)) = cadence_of(&gaps) else {
+                        continue;
+                    };
+                    let share = median(charges.iter().map(|e| owed_share_of(e, me.id)).collect());
+                    let last = *dates.last().context("Series without dates")?;
+                    let latest = charges.last().context("Series without charges")?;
+                    found.push(json!({
+                        "merchant": merchant,
+                        "currency": currency,
+                        "cadence": cadence,
+                        "occurrences": charges.len(),
+                        "typical_cost": format_amount(typical),
+                        "typical_my_share": format_amount(share),
+                        "annual_cost": format_amount(typical * per_year),
+                        "annual_my_share": format_amount(share * per_year),
+                        "first_date": dates.first().map(|d| d.to_string()),
+                        "last_date": last.to_string(),
+                        "next_expected": (last + chrono::Duration::days(gap)).to_string(),
+                        // A missed charge or two is normal; more suggests it was cancelled
+                        "still_active": (today - last).num_days() <= gap * 2,
+                        "category": latest.category.name,
+                        "group_id": latest.group_id,
+                        "expense_ids": charges.iter().map(|e| e.id).collect::<Vec<_>>(),
+                    }));
+                }
+                let annual = |v: &Value| v["annual_my_share"].as_str().and_then(|a| a.parse::<f64>().ok()).unwrap_or(0.0);
+                found.sort_by(|a, b| annual(b).total_cmp(&annual(a)));
+
+                let mut totals: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+                for series in found.iter().filter(|s| s["still_active"] == true) {
+                    let total = totals.entry(series["currency"].as_str().unwrap_or_default().to_string()).or_default();
+                    total.0 += series["annual_cost"].as_str().and_then(|a| a.parse::<f64>().ok()).unwrap_or(0.0);
+                    total.1 += annual(series);
+                }
+                Ok(json!({
+                    "dated_after": dated_after,
+                    "group_id": group_id,
+                    "count": found.len(),
+                    "annual_totals_active": totals.into_iter().map(|(currency, (cost, mine))| json!({
+                        "currency": currency,
+                        "annual_cost": format_amount(cost),
+                        "annual_my_share": format_amount(mine),
+                    })).collect::<Vec<_>>(),
+                    "series": found,
+                    "hint": "Set a series up as recurring in Splitwise, or cancel it if nobody uses it any more",
+                }))
+            }
+            "project_cashflow" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    months: Option<u32>,
+                    history_months: Option<u32>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let months = args.months.unwrap_or(3);
+                if !(1..=24).contains(&months) {
+                    anyhow::bail!("months must be between 1 and 24");
+                }
+                let history_months = args.history_months.unwrap_or(6);
+                if !(1..=36).contains(&history_months) {
+                    anyhow::bail!("history_months must be between 1 and 36");
+                }
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+
+                // History is the full months before this one; the projection starts next month
+                let today = chrono::Local::now().date_naive();
+                let this_month = today.with_day(1).context("Invalid date")?;
+                let history_start = this_month - chrono::Months::new(history_months);
+                let window_start = this_month + chrono::Months::new(1);
+                let window_end = window_start + chrono::Months::new(months);
+
+                let history_params = ListExpensesParams {
+                    group_id,
+                    dated_after: Some(history_start.to_string()),
+                    dated_before: Some(this_month.to_string()),
+                    ..Default::default()
+                };
+                let recurring_params = ListExpensesParams { group_id, ..Default::default() };
+                let is_spending = |e: &Expense| e.deleted_at.is_none() && !e.payment;
+                // Only the latest expense of a series carries repeats=true and the next date
+                let is_recurring = |e: &Expense| e.repeats && e.deleted_at.is_none() && !e.payment;
+                let (me, history, recurring) = tokio::try_join!(
+                    self.cached_me(),
+                    self.scan_expenses(history_params, None, &is_spending),
+                    self.scan_expenses(recurring_params, None, &is_recurring),
+                )?;
+
+                // Per (category, currency): my history total and what recurring expenses add per month
+                let mut categories: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+                for expense in &history {
+                    let key = (expense.category.name.clone(), expense.currency_code.clone());
+                    categories.entry(key).or_default().0 += owed_share_of(expense, me.id);
+                }
+                // Per (month, currency): my share of the recurring expenses due then
+                let mut due: BTreeMap<(String, String), f64> = BTreeMap::new();
+                let mut series = Vec::new();
+                for expense in &recurring {
+                    let interval = expense.repeat_interval.as_deref().unwrap_or("never");
+                    let share = owed_share_of(expense, me.id);
+                    let key = (expense.category.name.clone(), expense.currency_code.clone());
+                    categories.entry(key).or_default().1 += share * per_month(interval);
+
+                    let first = expense
+                        .next_repeat
+                        .as_deref()
+                        .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+                        .map(|d| d.date_naive())
+                        .or_else(|| {
+                            chrono::DateTime::parse_from_rfc3339(&expense.date)
+                                .ok()
+                                .and_then(|d| occurrence_after(d.date_naive(), interval, 1))
+                        });
+                    let mut dates = Vec::new();
+                    // Step from the first date each time so month ends don't drift
+                    for step in 0.. {
+                        let Some(date) = first.and_then(|d| occurrence_after(d, interval, step)) else {
+                            break;
+                        };
+                        if date >= window_end {
+                            break;
+                        }
+                        if date >= window_start {
+                            *due.entry((date.format("%Y-%m").to_string(), expense.currency_code.clone())).or_default() += share;
+                            dates.push(date.to_string());
+                        }
+                    }
+                    series.push(json!({
+                        "id": expense.id,
+                        "description": expense.description,
+                        "category": expense.category.name,
+                        "currency": expense.currency_code,
+                        "my_share": format_amount(share),
+                        "repeat_interval": interval,
+                        "dates": dates,
+                    }));
+                }
+
+                // Spending beyond what recurring expenses already account for, per month
+                let estimate = |(average, recurring): (f64, f64)| (average - recurring).max(0.0);
+                let mut estimated: BTreeMap<String, f64> = BTreeMap::new();
+                for ((_, currency), totals) in &categories {
+                    *estimated.entry(currency.clone()).or_default() += estimate((totals.0 / history_months as f64, totals.1));
+                }
+
+                let mut projected = Vec::new();
+                let mut totals: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+                for step in 0..months {
+                    let month = (window_start + chrono::Months::new(step)).format("%Y-%m").to_string();
+                    for (currency, other) in &estimated {
+                        let recurring = due.get(&(month.clone(), currency.clone())).copied().unwrap_or(0.0);
+                        if recurring.abs() < 0.005 && other.abs() < 0.005 {
+                            continue;
+                        }
+                        let total = totals.entry(currency.clone()).or_default();
+                        total.0 += recurring;
+                        total.1 += other;
+                        projected.push(json!({
+                            "month": month,
+                            "currency": currency,
+                            "recurring": format_amount(recurring),
+                            "estimated": format_amount(*other),
+                            "total": format_amount(recurring + other),
+                        }));
+                    }
+                }
+
+                let mut by_category: Vec<_> = categories
+                    .into_iter()
+                    .map(|((category, currency), (total, recurring))| (category, currency, total / history_months as f64, recurring))
+                    .collect();
+                by_category.sort_by(|a, b| a.1.cmp(&b.1).then(b.2.total_cmp(&a.2)).then(a.0.cmp(&b.0)));
+                Ok(json!({
+                    "window": { "from": window_start.to_string(), "to": window_end.to_string(), "months": months },
+                    "history": { "dated_after": history_start.to_string(), "dated_before": this_month.to_string(), "months": history_months },
+                    "group_id": group_id,
+                    "totals": totals.into_iter().map(|(currency, (recurring, other))| json!({
+                        "currency": currency,
+                        "recurring": format_amount(recurring),
+                        "estimated": format_amount(other),
+                        "total": format_amount(recurring + other),
+                    })).collect::<Vec<_>>(),
+                    "months": projected,
+                    "categories": by_category.into_iter().map(|(category, currency, average, recurring)| json!({
+                        "category": category,
+                        "currency": currency,
+                        "monthly_average": format_amount(average),
+                        "recurring_per_month": format_amount(recurring),
+                        "estimated_per_month": format_amount(estimate((average, recurring))),
+                    })).collect::<Vec<_>>(),
+                    "recurring_expenses": series,
+                }))
+            }
+            "debt_aging" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let only = self.user_id_from(args.friend_id, args.user_name.as_deref()).await?;
+                let (me, friends) = tokio::try_join!(self.cached_me(), self.client.get_friends())?;
+                let owing: Vec<Friend> = friends
+                    .into_iter()
+                    .filter(|f| only.is_none_or(|id| f.id == id))
+                    .filter(|f| f.balance.iter().any(|b| !is_zero_amount(&b.amount)))
+                    .collect();
+
+                let keep = |e: &Expense| e.deleted_at.is_none();
+                let histories = futures::future::try_join_all(owing.iter().map(|friend| {
+                    let params = ListExpensesParams { friend_id: Some(friend.id), ..Default::default() };
+                    self.scan_expenses(params, None, &keep)
+                }))
+                .await?;
+
+                let today = chrono::Local::now().date_naive();
+                let mut debts = Vec::new();
+                for (friend, expenses) in owing.iter().zip(histories) {
+                    let rebuilt = balance_since(&expenses, me.id, friend.id);
+                    for balance in friend.balance.iter().filter(|b| !is_zero_amount(&b.amount)) {
+                        let amount: f64 = balance.amount.parse().unwrap_or(0.0);
+                        let (running, since) = rebuilt.get(&balance.currency_code).cloned().unwrap_or_default();
+                        let since = since.and_then(|d| chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+                        let age = since.map(|d| (today - d).num_days());
+                        let mut debt = json!({
+                            "friend_id": friend.id,
+                            "name": full_name(&friend.first_name, friend.last_name.as_deref()),
+                            "currency": balance.currency_code,
+                            "amount": format_amount(amount.abs()),
+                            "direction": if amount > 0.0 { "owed_to_you" } else { "you_owe" },
+                            "since": since.map(|d| d.to_string()),
+                            "age_days": age,
+                            "bucket": age.map(age_bucket),
+                        });
+                        // Group debt simplification can move balances between people
+                        if (running - amount).abs() >= 0.01 {
+                            debt["history_balance"] = json!(format_amount(running));
+                            debt["note"] = json!("The expense history doesn't add up to this balance (group debts may have been simplified); the age is approximate");
+                        }
+                        debts.push(debt);
+                    }
+                }
+                debts.sort_by_key(|d| std::cmp::Reverse(d["age_days"].as_i64().unwrap_or(-1)));
+
+                // Per (bucket, currency): owed to me, owed by me, how many debts
+                let mut buckets: BTreeMap<(String, String), (f64, f64, usize)> = BTreeMap::new();
+                for debt in &debts {
+                    let bucket = debt["bucket"].as_str().unwrap_or("unknown").to_string();
+                    let entry = buckets.entry((bucket, debt["currency"].as_str().unwrap_or_default().to_string())).or_default();
+                    let amount: f64 = debt["amount"].as_str().unwrap_or_default().parse().unwrap_or(0.0);
+                    if debt["direction"] == "owed_to_you" {
+                        entry.0 += amount;
+                    } else {
+                        entry.1 += amount;
+                    }
+                    entry.2 += 1;
+                }
+                Ok(json!({
+                    "as_of": today.to_string(),
+                    "buckets": buckets.into_iter().map(|((bucket, currency), (owed_to_you, you_owe, count))| json!({
+                        "bucket": bucket,
+                        "currency": currency,
+                        "owed_to_you": format_amount(owed_to_you),
+                        "you_owe": format_amount(you_owe),
+                        "count": count,
+                    })).collect::<Vec<_>>(),
+                    "debts": debts,
+                }))
+            }
+            "settlement_history" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let friend_id = self.user_id_from(args.friend_id, args.user_name.as_deref()).await?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let params = match (friend_id, group_id) {
+                    (Some(friend_id), None) => ListExpensesParams { friend_id: Some(friend_id), ..Default::default() },
+                    (None, Some(group_id)) => ListExpensesParams { group_id: Some(group_id), ..Default::default() },
+                    (Some(_), Some(_)) => anyhow::bail!("Give either a friend or a group, not both"),
+                    (None, None) => anyhow::bail!("Provide friend_id, user_name, group_id or group_name"),
+                };
+                // The balance after each payment needs every expense, not just the payments
+                let keep = |e: &Expense| e.deleted_at.is_none();
+                let (me, mut expenses) = tokio::try_join!(self.cached_me(), self.scan_expenses(params, None, &keep))?;
+                expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+
+                let name_of = |share: &ExpenseUser| match (share.user_id == me.id, &share.user) {
+                    (true, _) => "you".to_string(),
+                    (false, Some(user)) => full_name(&user.first_name, user.last_name.as_deref()),
+                    (false, None) => format!("User {}", share.user_id),
+                };
+                let listed = |date: &str| {
+                    let date = &date[..date.len().min(10)];
+                    args.dated_after.as_deref().is_none_or(|after| date >= after)
+                        && args.dated_before.as_deref().is_none_or(|before| date < before)
+                };
+
+                let mut balances: HashMap<String, f64> = HashMap::new();
+                let mut payments = Vec::new();
+                for expense in &expenses {
+                    let balance = balances.entry(expense.currency_code.clone()).or_default();
+                    *balance += match friend_id {
+                        Some(friend_id) => pair_delta(expense, me.id, friend_id),
+                        None => expense
+                            .users
+                            .iter()
+                            .find(|u| u.user_id == me.id)
+                            .and_then(|u| u.net_balance.parse::<f64>().ok())
+                            .unwrap_or(0.0),
+                    };
+                    if !expense.payment || !listed(&expense.date) {
+                        continue;
+                    }
+                    let payer = expense.users.iter().find(|u| !is_zero_amount(&u.paid_share));
+                    let payee = expense.users.iter().find(|u| !is_zero_amount(&u.owed_share));
+                    payments.push(json!({
+                        "id": expense.id,
+                        "date": expense.date,
+                        "from": payer.map(name_of),
+                        "to": payee.map(name_of),
+                        "amount": expense.cost,
+                        "currency": expense.currency_code,
+                        "group_id": expense.group_id,
+                        "balance_after": format_amount(*balance),
+                        "you_paid": payer.is_some_and(|u| u.user_id == me.id),
+                        "you_received": payee.is_some_and(|u| u.user_id == me.id),
+                    }));
+                }
+                payments.reverse();
+
+                let latest = |key: &str| payments.iter().find(|p| p[key] == true).cloned();
+                Ok(json!({
+                    "friend_id": friend_id,
+                    "group_id": group_id,
+                    "count": payments.len(),
+                    "last_paid_by_you": latest("you_paid"),
+                    "last_paid_to_you": latest("you_received"),
+                    "balances": balances
+                        .into_iter()
+                        .filter(|(_, amount)| amount.abs() >= 0.005)
+                        .map(|(currency, amount)| json!({ "currency": currency, "amount": format_amount(amount) }))
+                        .collect::<Vec<_>>(),
+                    "payments": payments,
+                }))
+            }
+            "monthly_close" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    month: Option<String>,
+                    record_settlements: Option<bool>,
+                    confirm: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let this_month = chrono::Local::now().date_naive().with_day(1).context("Invalid date")?;
+                let start = match args.month.as_deref() {
+                    Some(month) => chrono::NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d")
+                        .with_context(|| format!("month must be YYYY-MM, not '{}'", month))?,
+                    None => this_month - chrono::Months::new(1),
+                };
+                let end = start + chrono::Months::new(1);
+                let month = start.format("%Y-%m").to_string();
+
+                let params = ListExpensesParams {
+                    group_id: Some(group_id),
+                    dated_after: Some(start.to_string()),
+                    dated_before: Some(end.to_string()),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none();
+                let (me, group, expenses) = tokio::try_join!(
+                    self.cached_me(),
+                    self.client.get_group(group_id),
+                    self.scan_expenses(params, None, &keep),
+                )?;
+                let name_of = |id: i64| {
+                    group
+                        .members
+                        .iter()
+                        .find(|m| m.id == id)
+                        .map_or_else(|| format!("user {}", id), |m| full_name(&m.first_name, m.last_name.as_deref()))
+                };
+
+                // 1. What the month cost, per currency and per category
+                let spending: Vec<&Expense> = expenses.iter().filter(|e| !e.payment).collect();
+                let mut totals: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+                let mut by_category: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+                for expense in &spending {
+                    let cost: f64 = expense.cost.parse().unwrap_or(0.0);
+                    let mine = owed_share_of(expense, me.id);
+                    for entry in [
+                        totals.entry(expense.currency_code.clone()).or_default(),
+                        by_category.entry((expense.category.name.clone(), expense.currency_code.clone())).or_default(),
+                    ] {
+                        entry.0 += cost;
+                        entry.1 += mine;
+                    }
+                }
+                let mut categories: Vec<_> = by_category.into_iter().collect();
+                categories.sort_by(|a, b| a.0 .1.cmp(&b.0 .1).then(b.1 .0.total_cmp(&a.1 .0)));
+
+                // 2. Anything left uncategorized
+                let uncategorized: Vec<&&Expense> = spending.iter().filter(|e| e.category.id == GENERAL_CATEGORY_ID).collect();
+
+                // 3. Who pays whom, following the group's own setting for which debts Splitwise shows
+                let debts = if group.simplify_by_default || group.original_debts.is_empty() {
+                    &group.simplified_debts
+                } else {
+                    &group.original_debts
+                };
+                let pending: Vec<&Debt> = debts.iter().filter(|d| !is_zero_amount(&d.amount)).collect();
+                let mut settlements: Vec<Value> = pending
+                    .iter()
+                    .map(|d| json!({
+                        "from_id": d.from,
+                        "from": name_of(d.from),
+                        "to_id": d.to,
+                        "to": name_of(d.to),
+                        "amount": d.amount,
+                        "currency_code": d.currency_code,
+                    }))
+                    .collect();
+
+                // 4. Record them when asked to and confirmed
+                let mut confirmation_required = None;
+                let mut record_error = None;
+                if args.record_settlements.unwrap_or(false) && !pending.is_empty() {
+                    if args.confirm.unwrap_or(false) {
+                        for (debt, settlement) in pending.iter().zip(settlements.iter_mut()) {
+                            let request = CreateExpenseRequest {
+                                cost: debt.amount.clone(),
+                                description: "Payment".to_string(),
+                                currency_code: Some(debt.currency_code.clone()),
+                                category_id: None,
+                                date: None,
+                                repeat_interval: None,
+                                details: Some(format!("Settle-up from the {} monthly close", month)),
+                                payment: Some(true),
+                                group_id: Some(group_id),
+                                split_equally: Some(false),
+                                split_by_shares: Some(vec![
+                                    ExpenseShare {
+                                        user_id: Some(debt.from),
+                                        email: None,
+                                        first_name: None,
+                                        last_name: None,
+                                        paid_share: debt.amount.clone(),
+                                        owed_share: format_amount(0.0),
+                                    },
+                                    owed_only(debt.to, debt.amount.parse().unwrap_or(0.0)),
+                                ]),
+                            };
+                            // One at a time, so a failure leaves a clear record of what was already paid
+                            match self.client.create_expense(request).await {
+                                Ok(created) => {
+                                    self.lookups.dashboard.invalidate();
+                                    settlement["recorded_payment_id"] = json!(created.first().map(|e| e.id));
+                                }
+                                Err(e) => {
+                                    record_error = Some(format!("Recording {} -> {} failed: {}", settlement["from"], settlement["to"], e));
+                                    break;
+                                }
+                            }
+                        }
+                    } else {
+                        confirmation_required = Some(format!(
+                            "{} payments would be recorded. Ask the user, then call again with record_settlements: true and confirm: true",
+                            pending.len()
+                        ));
+                    }
+                }
+
+                // 5. The Markdown report
+                let mut report = vec![format!("# {}: monthly close for {}", group.name, month), String::new(), "## Summary".to_string()];
+                report.push(format!("- {} expenses, {} payments", spending.len(), expenses.len() - spending.len()));
+                for (currency, (total, mine)) in &totals {
+                    report.push(format!("- {}: {} spent, your share {}", currency, format_amount(*total), format_amount(*mine)));
+                }
+                if !categories.is_empty() {
+                    report.extend([String::new(), "## By category".to_string(), String::new()]);
+                    report.push("| Category | Currency | Total | Your share |".to_string());
+                    report.push("|---|---|---:|---:|".to_string());
+                    for ((category, currency), (total, mine)) in &categories {
+                        report.push(format!("| {} | {} | {} | {} |", category, currency, format_amount(*total), format_amount(*mine)));
+                    }
+                }
+                report.extend([String::new(), "## Categorization".to_string()]);
+                if uncategorized.is_empty() {
+                    report.push("Every expense has a category.".to_string());
+                }
+                for expense in &uncategorized {
+                    report.push(format!(
+                        "- {} {} ({} {}), expense {}",
+                        expense.date.get(..10).unwrap_or(&expense.date),
+                        expense.description,
+                        expense.cost,
+                        expense.currency_code,
+                        expense.id
+                    ));
+                }
+                report.extend([String::new(), "## Settle up".to_string()]);
+                if settlements.is_empty() {
+                    report.push("Everyone is settled up.".to_string());
+                }
+                for settlement in &settlements {
+                    let recorded = match settlement.get("recorded_payment_id") {
+                        Some(id) => format!(" (recorded as payment {})", id),
+                        None => String::new(),
+                    };
+                    report.push(format!(
+                        "- {} pays {} {} {}{}",
+                        settlement["from"].as_str().unwrap_or_default(),
+                        settlement["to"].as_str().unwrap_or_default(),
+                        settlement["amount"].as_str().unwrap_or_default(),
+                        settlement["currency_code"].as_str().unwrap_or_default(),
+                        recorded
+                    ));
+                }
+
+                let mut result = json!({
+                    "group_id": group.id,
+                    "group_name": group.name,
+                    "month": month,
+                    "status": if uncategorized.is_empty() { "ready" } else { "needs_attention" },
+                    "expense_count": spending.len(),
+                    "totals": totals.iter().map(|(currency, (total, mine))| json!({
+                        "currency_code": currency,
+                        "total_cost": format_amount(*total),
+                        "my_share": format_amount(*mine),
+                    })).collect::<Vec<_>>(),
+                    "categories": categories.iter().map(|((category, currency), (total, mine))| json!({
+                        "category": category,
+                        "currency_code": currency,
+                        "total_cost": format_amount(*total),
+                        "my_share": format_amount(*mine),
+                    })).collect::<Vec<_>>(),
+                    "uncategorized": uncategorized.iter().map(|e| json!({
+                        "id": e.id,
+                        "date": e.date,
+                        "description": e.description,
+                        "cost": e.cost,
+                        "currency_code": e.currency_code,
+                    })).collect::<Vec<_>>(),
+                    "settlements": settlements,
+                    "report_markdown": report.join("\n"),
+                });
+                if !uncategorized.is_empty() {
+                    result["hint"] = json!("suggest_category proposes a category for each description; set it with update_expense");
+                }
+                if let Some(message) = confirmation_required {
+                    result["confirmation_required"] = json!(message);
+                }
+                if let Some(error) = record_error {
+                    result["error"] = json!(error);
+                }
+                Ok(result)
+            }
+            "audit_data_quality" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    checks: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                if let Some(unknown) = args.checks.iter().flatten().find(|c| !AUDIT_CHECKS.contains(&c.as_str())) {
+                    anyhow::bail!("Unknown check '{}'; use {}", unknown, AUDIT_CHECKS.join(", "));
+                }
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none();
+                let (me, friends, expenses) = tokio::try_join!(
+                    self.cached_me(),
+                    self.client.get_friends(),
+                    self.scan_expenses(params, None, &keep),
+                )?;
+                let friends: HashSet<i64> = friends.iter().map(|f| f.id).collect();
+
+                let mut issues = audit_expenses(&expenses, me.id, &friends);
+                if let Some(checks) = &args.checks {
+                    issues.retain(|issue| checks.iter().any(|c| issue["check"] == c.as_str()));
+                }
+                let mut by_check: BTreeMap<&str, usize> = BTreeMap::new();
+                for issue in &issues {
+                    *by_check.entry(issue["check"].as_str().unwrap_or_default()).or_default() += 1;
+                }
+                Ok(json!({
+                    "group_id": group_id,
+                    "expenses_checked": expenses.len(),
+                    "issue_count": issues.len(),
+                    "by_check": by_check,
+                    "issues": issues,
+                }))
+            }
+            "check_group_currencies" => self.check_group_currencies(arguments).await,
+            "apply_fixes" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    fixes: Vec<Value>,
+                    dry_run: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let dry_run = args.dry_run.unwrap_or(true);
+
+                let mut results = Vec::new();
+                for (index, fix) in args.fixes.iter().enumerate() {
+                    let mut outcome = match fix_call(fix) {
+                        Ok((tool, arguments)) => json!({ "tool": tool, "arguments": arguments }),
+                        Err(reason) => json!({ "status": "skipped", "reason": reason }),
+                    };
+                    outcome["index"] = json!(index);
+                    outcome["expense_id"] = fix.get("expense_id").cloned().unwrap_or(Value::Null);
+                    outcome["check"] = fix.get("check").cloned().unwrap_or(Value::Null);
+                    if outcome.get("status").is_none() {
+                        if dry_run {
+                            outcome["status"] = json!("would_apply");
+                        } else {
+                            let tool = outcome["tool"].as_str().unwrap_or_default().to_string();
+                            // One at a time; a failure is reported and the rest still run
+                            match Box::pin(self.write_expense(&tool, outcome["arguments"].clone())).await {
+                                Ok(result) => {
+                                    outcome["status"] = json!("applied");
+                                    outcome["result"] = result;
+                                }
+                                Err(e) => {
+                                    outcome["status"] = json!("failed");
+                                    outcome["error"] = json!(format!("{:#}", e));
+                                }
+                            }
+                        }
+                    }
+                    results.push(outcome);
+                }
+
+                let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+                for outcome in &results {
+                    *counts.entry(outcome["status"].as_str().unwrap_or_default()).or_default() += 1;
+                }
+                let mut result = json!({
+                    "dry_run": dry_run,
+                    "counts": counts,
+                    "results": results,
+                });
+                if dry_run && counts.contains_key("would_apply") {
+                    result["note"] = json!("Nothing was changed. Ask the user, then call again with dry_run: false");
+                }
+                Ok(result)
+            }
+            "parse_statement" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    text: Option<String>,
+                    file: Option<String>,
+                    candidates: Option<Vec<Value>>,
+                    currency_code: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    create: Option<bool>,
+                    confirm: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let create = args.create.unwrap_or(false);
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                if create && group_id.is_none() {
+                    anyhow::bail!("Creating the expenses needs group_id or group_name");
+                }
+                let currency = match args.currency_code {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => self.cached_me().await?.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let today = chrono::Local::now().date_naive();
+
+                let text = match (args.text, args.file) {
+                    (Some(_), Some(_)) => anyhow::bail!("Give text or file, not both"),
+                    (None, Some(file)) => Some(roots::read_to_string(&file, self.file_dirs()).await?),
+                    (text, None) => text,
+                };
+                let (found, source) = match (text, args.candidates) {
+                    (Some(text), None) => {
+                        let prompt = format!("Currency when none is shown: {}. Today is {}.\n\n{}", currency, today, text);
+                        let reply = sampling::create_message(STATEMENT_PROMPT, &prompt, 4000).await?;
+                        let items = match json_in(&reply) {
+                            Some(Value::Array(items)) => items,
+                            Some(Value::Object(mut reply)) => match reply.remove("expenses") {
+                                Some(Value::Array(items)) => items,
+                                _ => Vec::new(),
+                            },
+                            _ => anyhow::bail!(
+                                "The client's model didn't answer with a JSON list of expenses: {}",
+                                reply.chars().take(200).collect::<String>()
+                            ),
+                        };
+                        (items, Some(text))
+                    }
+                    (None, Some(candidates)) => (candidates, None),
+                    _ => anyhow::bail!("Give either text or a file to parse, or the candidates from an earlier call"),
+                };
+                let mut candidates: Vec<Value> = found
+                    .iter()
+                    .map(|item| statement_candidate(item, source.as_deref(), &currency, today))
+                    .collect();
+
+                let chosen: Vec<usize> = (0..candidates.len())
+                    .filter(|i| candidates[*i]["valid"] == true && candidates[*i]["shared"] == true)
+                    .collect();
+                let mut result = json!({
+                    "counts": {
+                        "found": candidates.len(),
+                        "valid": candidates.iter().filter(|c| c["valid"] == true).count(),
+                        "to_create": chosen.len(),
+                    },
+                });
+                if create && !chosen.is_empty() {
+                    if args.confirm.unwrap_or(false) {
+                        for i in &chosen {
+                            let candidate = &candidates[*i];
+                            let arguments = json!({
+                                "cost": candidate["amount"],
+                                "description": candidate["description"],
+                                "currency_code": candidate["currency_code"],
+                                "date": candidate["date"],
+                                "group_id": group_id,
+                                "import_source": "statement",
+                            });
+                            // One at a time; a failure is reported and the rest are still created
+                            match Box::pin(self.create_expense(arguments)).await {
+                                Ok(created) => candidates[*i]["created_expense_id"] = created["id"].clone(),
+                                Err(e) => candidates[*i]["error"] = json!(format!("{:#}", e)),
+                            }
+                        }
+                    } else {
+                        result["confirmation_required"] = json!(format!(
+                            "{} expenses would be created. Ask the user, then call again with the candidates, create: true and confirm: true",
+                            chosen.len()
+                        ));
+                    }
+                } else if !create && !chosen.is_empty() {
+                    result["note"] = json!("Nothing was created. Pass the candidates back (fix or drop any first) with a group and create: true");
+                }
+                result["candidates"] = json!(candidates);
+                Ok(result)
+            }
+            "create_expense_from_text" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    text: Option<String>,
+                    file: Option<String>,
+                    cost: Option<Value>,
+                    description: Option<String>,
+                    date: Option<String>,
+                    currency_code: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    create_arguments: Option<Value>,
+                    #[serde(default)]
+                    confirm: bool,
+                }
+                let args: Args = parse_args(arguments)?;
+                let text = match (args.text, args.file) {
+                    (Some(_), Some(_)) => anyhow::bail!("Give text or file, not both"),
+                    (None, Some(file)) => roots::read_to_string(&file, self.file_dirs()).await?,
+                    (Some(text), None) => text,
+                    (None, None) => anyhow::bail!("Give the receipt or email as text or file"),
+                };
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let default_currency = self.cached_me().await?.default_currency.clone().unwrap_or_else(|| "USD".to_string());
+                let today = chrono::Local::now().date_naive();
+
+                let mut proposal = receipt::extract(&text, &default_currency, today).to_json();
+                let corrections = [
+                    ("cost", args.cost.as_ref().map(parse_amount).transpose()?.map(format_amount)),
+                    ("description", args.description.map(|d| d.trim().to_string())),
+                    ("date", args.date),
+                    ("currency_code", args.currency_code.map(|c| c.trim().to_uppercase())),
+                ];
+                for (field, value) in corrections {
+                    if let Some(value) = value {
+                        if field == "date" && chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d").is_err() {
+                            anyhow::bail!("date must be YYYY-MM-DD, got {}", value);
+                        }
+                        proposal[field] = json!(value);
+                        proposal["confidence"][field] = json!(1.0);
+                        proposal["evidence"][field] = json!("given");
+                    }
+                }
+                let missing: Vec<&str> = ["cost", "description"].into_iter().filter(|f| proposal[*f].is_null()).collect();
+                let unsure: Vec<&str> = ["cost", "currency_code", "date", "description"]
+                    .into_iter()
+                    .filter(|f| !proposal[*f].is_null() && proposal["confidence"][*f].as_f64().unwrap_or(0.0) < 0.6)
+                    .collect();
+                let mut result = json!({ "proposal": proposal, "missing": missing, "check_with_user": unsure });
+
+                if !args.confirm {
+                    result["note"] = json!(
+                        "Nothing was created. Show the proposal to the user, especially the fields in check_with_user, then call again with the same text, confirm: true, a group (or a split in create_arguments) and any corrections"
+                    );
+                    return Ok(result);
+                }
+                if !missing.is_empty() {
+                    anyhow::bail!("The {} couldn't be read from the text; pass it as a correction", missing.join(" and "));
+                }
+                let mut arguments = match args.create_arguments {
+                    Some(Value::Object(arguments)) => Value::Object(arguments),
+                    Some(other) => anyhow::bail!("create_arguments must be an object, got {}", other),
+                    None => json!({}),
+                };
+                let split_given = ["split_among", "split_by_shares", "prorate_by_attendance"]
+                    .iter()
+                    .any(|key| arguments.get(key).is_some());
+                if group_id.is_none() && arguments.get("group_id").is_none() && arguments.get("group_name").is_none() && !split_given {
+                    anyhow::bail!("Say who shares the expense: group_id or group_name, or split_among in create_arguments");
+                }
+                let proposal = &result["proposal"];
+                for field in ["cost", "description", "currency_code", "date"] {
+                    if !proposal[field].is_null() {
+                        arguments[field] = proposal[field].clone();
+                    }
+                }
+                if let Some(group_id) = group_id {
+                    arguments["group_id"] = json!(group_id);
+                }
+                if arguments.get("import_source").is_none() {
+                    arguments["import_source"] = json!("receipt");
+                }
+                let created = Box::pin(self.create_expense(arguments)).await?;
+                result["created"] = created;
+                Ok(result)
+            }
+            "match_transactions" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    transactions: Vec<Value>,
+                    currency_code: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    date_window_days: Option<i64>,
+                    amount_tolerance: Option<Value>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    min_description_similarity: Option<f64>,
+                    explain_match: Option<usize>,
+                    create_unmatched: Option<bool>,
+                    confirm: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let rules = matching::Rules {
+                    date_window_days: args.date_window_days.unwrap_or(3).clamp(0, 365),
+                    amount_tolerance: match &args.amount_tolerance {
+                        Some(tolerance) => parse_amount(tolerance)?.abs(),
+                        None => 0.01,
+                    },
+                    min_similarity: args.min_description_similarity.unwrap_or(0.0).clamp(0.0, 1.0),
+                };
+                if args.explain_match.is_some_and(|i| i >= args.transactions.len()) {
+                    anyhow::bail!("explain_match must be the index of a transaction, below {}", args.transactions.len());
+                }
+                let parse_date = |value: &str| {
+                    chrono::NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%Y-%m-%d")
+                        .with_context(|| format!("Dates must be YYYY-MM-DD, not '{}'", value))
+                };
+                let lines = args
+                    .transactions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let date = parse_date(line["date"].as_str().unwrap_or_default())
+                            .with_context(|| format!("Transaction {}", i + 1))?;
+                        let amount = parse_amount(&line["amount"]).with_context(|| format!("Transaction {}", i + 1))?;
+                        let description = line["description"].as_str().unwrap_or_default().trim().to_string();
+                        Ok(matching::Entry { date, amount: amount.abs(), description })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let (Some(first), Some(last)) = (lines.iter().map(|l| l.date).min(), lines.iter().map(|l| l.date).max()) else {
+                    anyhow::bail!("Give at least one transaction");
+                };
+                let from = args.dated_after.as_deref().map(parse_date).transpose()?.unwrap_or(first);
+                let to = args.dated_before.as_deref().map(parse_date).transpose()?.unwrap_or(last);
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let create = args.create_unmatched.unwrap_or(false);
+                if create && group_id.is_none() {
+                    anyhow::bail!("Creating the unmatched transactions needs group_id or group_name");
+                }
+                let me = self.cached_me().await?;
+                let currency = match args.currency_code {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+
+                // Expenses a late-booked transaction could still belong to, on both sides of the period
+                let window = chrono::Days::new(rules.date_window_days as u64);
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: Some((from.min(first) - window).to_string()),
+                    dated_before: Some((to.max(last) + window + chrono::Days::new(1)).to_string()),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| {
+                    e.deleted_at.is_none()
+                        && e.currency_code == currency
+                        && paid_share_of(e, me.id) > 0.0
+                        && parse_date(&e.date).is_ok()
+                };
+                let expenses = self.scan_expenses(params, None, &keep).await?;
+                let entries: Vec<matching::Entry> = expenses
+                    .iter()
+                    .map(|e| matching::Entry {
+                        date: parse_date(&e.date).unwrap_or(first),
+                        amount: paid_share_of(e, me.id),
+                        description: e.description.clone(),
+                    })
+                    .collect();
+                let matches = matching::pair(&lines, &entries, rules);
+
+                let line_json = |i: usize| {
+                    json!({
+                        "index": i,
+                        "date": lines[i].date.to_string(),
+                        "amount": format_amount(lines[i].amount),
+                        "description": lines[i].description,
+                    })
+                };
+                let expense_json = |j: usize| {
+                    let expense = &expenses[j];
+                    json!({
+                        "id": expense.id,
+                        "date": entries[j].date.to_string(),
+                        "description": expense.description,
+                        "cost": expense.cost,
+                        "paid": format_amount(entries[j].amount),
+                        "group_id": expense.group_id,
+                        "payment": expense.payment,
+                    })
+                };
+                let comparison = |m: &matching::Match| {
+                    json!({
+                        "amount_difference": format_amount(m.amount_difference),
+                        "days_apart": m.days_apart,
+                        "description_similarity": (m.similarity * 100.0).round() / 100.0,
+                    })
+                };
+                // Which expense each line took, and which line took each expense
+                let mut line_match: Vec<Option<usize>> = vec![None; lines.len()];
+                let mut expense_match: Vec<Option<usize>> = vec![None; entries.len()];
+                let matched: Vec<Value> = matches
+                    .iter()
+                    .map(|m| {
+                        line_match[m.line] = Some(m.expense);
+                        expense_match[m.expense] = Some(m.line);
+                        let mut item = comparison(m);
+                        item["transaction"] = line_json(m.line);
+                        item["expense"] = expense_json(m.expense);
+                        item
+                    })
+                    .collect();
+                let unmatched: Vec<usize> = (0..lines.len()).filter(|i| line_match[*i].is_none()).collect();
+                let mut unmatched_transactions: Vec<Value> = unmatched.iter().map(|i| line_json(*i)).collect();
+                // Expenses outside the period were only fetched for transactions booked late
+                let unmatched_expenses: Vec<Value> = (0..entries.len())
+                    .filter(|j| expense_match[*j].is_none() && (from..=to).contains(&entries[*j].date))
+                    .map(expense_json)
+                    .collect();
+
+                // 5 closest expenses to the line asked about, with why each did or didn't pair with it
+                let explanation = args.explain_match.map(|i| {
+                    let mut compared: Vec<matching::Match> =
+                        (0..entries.len()).map(|j| matching::compare(&lines, &entries, i, j)).collect();
+                    compared.sort_by(matching::closer);
+                    let candidates: Vec<Value> = compared
+                        .iter()
+                        .take(5)
+                        .map(|m| {
+                            let reasons = rules.mismatches(m);
+                            let verdict = match (line_match[i], expense_match[m.expense]) {
+                                (Some(j), _) if j == m.expense => "matched".to_string(),
+                                _ if !reasons.is_empty() => format!("not a match: {}", reasons.join("; ")),
+                                (Some(j), _) => format!("could match, but expense {} was closer", expenses[j].id),
+                                (None, Some(k)) => format!("could match, but transaction {} was closer to it", k),
+                                (None, None) => "could match".to_string(),
+                            };
+                            let mut candidate = comparison(m);
+                            candidate["expense"] = expense_json(m.expense);
+                            candidate["verdict"] = json!(verdict);
+                            candidate
+                        })
+                        .collect();
+                    json!({
+                        "transaction": line_json(i),
+                        "matched_expense_id": line_match[i].map(|j| expenses[j].id),
+                        "closest_expenses": candidates,
+                    })
+                });
+
+                // On a shared card, whatever nobody recorded goes into the group as paid by me
+                let to_create: Vec<usize> = (0..unmatched.len())
+                    .filter(|n| args.transactions[unmatched[*n]]["shared"].as_bool() != Some(false))
+                    .collect();
+                let mut confirmation_required = None;
+                if create && !to_create.is_empty() {
+                    if args.confirm.unwrap_or(false) {
+                        for n in &to_create {
+                            let line = &lines[unmatched[*n]];
+                            let arguments = json!({
+                                "cost": format_amount(line.amount),
+                                "description": line.description,
+                                "currency_code": currency,
+                                "date": line.date.to_string(),
+                                "group_id": group_id,
+                                "import_source": "statement",
+                            });
+                            // One at a time; a failure is reported and the rest are still created
+                            match Box::pin(self.create_expense(arguments)).await {
+                                Ok(created) => unmatched_transactions[*n]["created_expense_id"] = created["id"].clone(),
+                                Err(e) => unmatched_transactions[*n]["error"] = json!(format!("{:#}", e)),
+                            }
+                        }
+                    } else {
+                        confirmation_required = Some(format!(
+                            "{} expenses would be created. Ask the user, then call again with create_unmatched: true and confirm: true",
+                            to_create.len()
+                        ));
+                    }
+                }
+
+                let mut result = json!({
+                    "currency_code": currency,
+                    "period": { "from": from.to_string(), "to": to.to_string() },
+                    "rules": {
+                        "date_window_days": rules.date_window_days,
+                        "amount_tolerance": format_amount(rules.amount_tolerance),
+                        "min_description_similarity": rules.min_similarity,
+                    },
+                    "counts": {
+                        "transactions": lines.len(),
+                        "matched": matched.len(),
+                        "unmatched_transactions": unmatched_transactions.len(),
+                        "unmatched_expenses": unmatched_expenses.len(),
+                    },
+                    "matched": matched,
+                    "unmatched_transactions": unmatched_transactions,
+                    "unmatched_expenses": unmatched_expenses,
+                });
+                if !create && !to_create.is_empty() {
+                    result["hint"] = json!("Shared transactions with no expense can be added with create_expense, or all at once with create_unmatched: true and a group (mark personal ones shared: false first)");
+                }
+                if let Some(explanation) = explanation {
+                    result["explanation"] = explanation;
+                }
+                if let Some(message) = confirmation_required {
+                    result["confirmation_required"] = json!(message);
+                }
+                Ok(result)
+            }
+            "mark_reimbursable" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_ids: Vec<i64>,
+                    payer: String,
+                    amount: Option<Value>,
+                }
+                let args: Args = parse_args(arguments)?;
+                if args.amount.is_some() && args.expense_ids.len() != 1 {
+                    anyhow::bail!("amount can only be given when marking a single expense");
+                }
+                let amount = args.amount.as_ref().map(parse_amount).transpose()?;
+                let me = self.cached_me().await?;
+
+                let mut marked = Vec::new();
+                for id in args.expense_ids {
+                    let expense = self.client.get_expense(id).await?;
+                    let reimbursement = metadata::Reimbursement {
+                        payer: args.payer.clone(),
+                        amount: format_amount(amount.unwrap_or_else(|| owed_share_of(&expense, me.id))),
+                        currency_code: expense.currency_code.clone(),
+                        reimbursed_on: None,
+                    };
+                    marked.push(json!({
+                        "id": id,
+                        "description": expense.description,
+                        "amount": reimbursement.amount,
+                        "currency_code": reimbursement.currency_code,
+                    }));
+                    self.edit_metadata(id, |metadata| {
+                        metadata.remove_tags(&[metadata::REIMBURSED.to_string()]);
+                        metadata.add_tags(&[metadata::REIMBURSABLE.to_string()]);
+                        metadata.reimbursement = Some(reimbursement);
+                    })
+                    .await?;
+                }
+                Ok(json!({ "success": true, "payer": args.payer, "marked": marked }))
+            }
+            "list_pending_reimbursements" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    payer: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let payer = args.payer.as_deref().map(nl_query::normalize);
+                let params = ListExpensesParams {
+                    group_id: None,
+                    friend_id: None,
+                    dated_after: args.dated_after,
+                    dated_before: args.dated_before,
+                    updated_after: None,
+                    updated_before: None,
+                    limit: None,
+                    offset: None,
+                };
+                let pending = |e: &Expense| {
+                    let (_, metadata) = metadata::split(e.details.as_deref());
+                    e.deleted_at.is_none()
+                        && metadata.has_tag(metadata::REIMBURSABLE)
+                        && metadata.reimbursement.is_some_and(|r| {
+                            payer.as_ref().is_none_or(|p| nl_query::normalize(&r.payer) == *p)
+                        })
+                };
+                let expenses = self.scan_expenses(params, None, &pending).await?;
+
+                // (payer, month, currency) -> (count, amount)
+                let mut totals: BTreeMap<(String, String, String), (usize, f64)> = BTreeMap::new();
+                let mut listed = Vec::new();
+                for expense in &expenses {
+                    let Some(reimbursement) = metadata::split(expense.details.as_deref()).1.reimbursement else {
+                        continue;
+                    };
+                    let month: String = expense.date.chars().take(7).collect();
+                    let total = totals
+                        .entry((reimbursement.payer.clone(), month, reimbursement.currency_code.clone()))
+                        .or_default();
+                    total.0 += 1;
+                    total.1 += reimbursement.amount.parse::<f64>().unwrap_or(0.0);
+                    listed.push(json!({
+                        "id": expense.id,
+                        "description": expense.description,
+                        "date": expense.date,
+                        "payer": reimbursement.payer,
+                        "amount": reimbursement.amount,
+                        "currency_code": reimbursement.currency_code,
+                    }));
+                }
+                Ok(json!({
+                    "count": listed.len(),
+                    "totals": totals.into_iter().map(|((payer, month, currency), (count, amount))| json!({
+                        "payer": payer,
+                        "month": month,
+                        "currency": currency,
+                        "count": count,
+                        "amount": format_amount(amount),
+                    })).collect::<Vec<_>>(),
+                    "expenses": listed,
+                }))
+            }
+            "mark_reimbursed" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_ids: Vec<i64>,
+                    reimbursed_on: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let reimbursed_on = args
+                    .reimbursed_on
+                    .unwrap_or_else(|| chrono::Local::now().date_naive().to_string());
+
+                let mut updated = Vec::new();
+                for id in args.expense_ids {
+                    let expense = self.client.get_expense(id).await?;
+                    if metadata::split(expense.details.as_deref()).1.reimbursement.is_none() {
+                        anyhow::bail!("Expense {} ({}) was never marked reimbursable", id, expense.description);
+                    }
+                    self.edit_metadata(id, |metadata| {
+                        metadata.remove_tags(&[metadata::REIMBURSABLE.to_string()]);
+                        metadata.add_tags(&[metadata::REIMBURSED.to_string()]);
+                        if let Some(ref mut reimbursement) = metadata.reimbursement {
+                            reimbursement.reimbursed_on = Some(reimbursed_on.clone());
+                        }
+                    })
+                    .await?;
+                    updated.push(json!({ "id": id, "description": expense.description }));
+                }
+                Ok(json!({ "success": true, "reimbursed_on": reimbursed_on, "updated": updated }))
+            }
+            "recent_comments" => self.recent_comments(arguments).await,
+            "get_expense_history" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_id: i64,
+                }
+                let args: Args = parse_args(arguments)?;
+                let (expense, comments) = tokio::try_join!(
+                    self.client.get_expense(args.expense_id),
+                    self.client.get_comments(args.expense_id),
+                )?;
+
+                let person = |r: &UserReference| json!({
+                    "id": r.id,
+                    "name": full_name(&r.first_name, r.last_name.as_deref()),
+                });
+
+                let mut timeline = vec![json!({
+                    "event": "created",
+                    "at": expense.created_at,
+                    "by": person(&expense.created_by),
+                })];
+                // System comments are Splitwise's own log of each edit, listing the changed fields
+                for comment in comments.iter().filter(|c| c.comment_type == "System") {
+                    let event = if comment.content.contains("deleted") {
+                        "deleted"
+                    } else if comment.content.contains("restored") {
+                        "restored"
+                    } else {
+                        "updated"
+                    };
+                    timeline.push(json!({
+                        "event": event,
+                        "at": comment.created_at,
+                        "by": comment.user.as_ref().map(person),
+                        "changes": parse_change_lines(&comment.content),
+                    }));
+                }
+
+                // Older expenses may predate the comment log; fall back to the expense's own stamps
+                let logged = |event: &str| timeline.iter().any(|t| t["event"] == event);
+                let mut missing = Vec::new();
+                if !logged("updated") && expense.updated_at != expense.created_at {
+                    missing.push(json!({
+                        "event": "updated",
+                        "at": expense.updated_at,
+                        "by": expense.updated_by.as_ref().map(person),
+                        "changes": [],
+                    }));
+                }
+                if let (false, Some(at)) = (logged("deleted"), &expense.deleted_at) {
+                    missing.push(json!({
+                        "event": "deleted",
+                        "at": at,
+                        "by": expense.deleted_by.as_ref().map(person),
+                    }));
+                }
+                timeline.extend(missing);
+                timeline.sort_by(|a, b| a["at"].as_str().cmp(&b["at"].as_str()));
+
+                Ok(json!({
+                    "expense_id": expense.id,
+                    "description": expense.description,
+                    "created": { "at": expense.created_at, "by": person(&expense.created_by) },
+                    "last_updated": { "at": expense.updated_at, "by": expense.updated_by.as_ref().map(person) },
+                    "deleted": expense.deleted_at.as_ref().map(|at| json!({
+                        "at": at,
+                        "by": expense.deleted_by.as_ref().map(person),
+                    })),
+                    "timeline": timeline,
+                }))
+            }
+            "get_expense" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    expense_id: i64,
+                    fields: Vec<String>,  // Now required
+                }
+                let args: Args = parse_args(arguments)?;
+                let (expense, me) = tokio::try_join!(self.client.get_expense(args.expense_id), self.cached_me())?;
+                Ok(project_expense(&expense, me.id, &args.fields))
+            }
+            "list_recurring_expenses" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    mode: Option<String>,
+                    occurrences: Option<u32>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let forecast = match args.mode.as_deref().unwrap_or("list") {
+                    "list" => false,
+                    "forecast_recurring" => true,
+                    other => anyhow::bail!("Unknown mode '{}'; use list or forecast_recurring", other),
+                };
+
+                let params = ListExpensesParams {
+                    group_id,
+                    friend_id: None,
+                    dated_after: None,
+                    dated_before: None,
+                    updated_after: None,
+                    updated_before: None,
+                    limit: None,
+                    offset: None,
+                };
+                // Only the latest expense of a series carries repeats=true and the next date
+                let is_recurring = |e: &Expense| e.repeats && e.deleted_at.is_none();
+                let (me, recurring) = tokio::try_join!(
+                    self.cached_me(),
+                    self.scan_expenses(params, None, &is_recurring),
+                )?;
+
+                let my_share = |e: &Expense| -> f64 {
+                    e.users
+                        .iter()
+                        .find(|u| u.user_id == me.id)
+                        .and_then(|u| u.owed_share.parse().ok())
+                        .unwrap_or(0.0)
+                };
+
+                let mut listed = Vec::new();
+                // Per (month, currency): total cost and my share
+                let mut by_month: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+                for expense in &recurring {
+                    let interval = expense.repeat_interval.as_deref().unwrap_or("never");
+                    let mut entry = json!({
+                        "id": expense.id,
+                        "description": expense.description,
+                        "cost": expense.cost,
+                        "currency_code": expense.currency_code,
+                        "my_share": format_amount(my_share(expense)),
+                        "repeat_interval": interval,
+                        "next_repeat": expense.next_repeat,
+                        "group_id": expense.group_id,
+                    });
+
+                    if forecast {
+                        let first = expense
+                            .next_repeat
+                            .as_deref()
+                            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+                            .map(|d| d.date_naive())
+                            .or_else(|| {
+                                chrono::DateTime::parse_from_rfc3339(&expense.date)
+                                    .ok()
+                                    .and_then(|d| occurrence_after(d.date_naive(), interval, 1))
+                            });
+                        let mut upcoming = Vec::new();
+                        for step in 0..args.occurrences.unwrap_or(3) {
+                            // Step from the first date each time so month ends don't drift
+                            let Some(current) = first.and_then(|d| occurrence_after(d, interval, step)) else {
+                                break;
+                            };
+                            upcoming.push(current.to_string());
+                            let totals = by_month
+                                .entry((current.format("%Y-%m").to_string(), expense.currency_code.clone()))
+                                .or_default();
+                            totals.0 += expense.cost.parse::<f64>().unwrap_or(0.0);
+                            totals.1 += my_share(expense);
+                        }
+                        entry["upcoming"] = json!(upcoming);
+                    }
+                    listed.push(entry);
+                }
+
+                let mut result = json!({
+                    "count": listed.len(),
+                    "recurring_expenses": listed,
+                });
+                if forecast {
+                    result["monthly_totals"] = json!(by_month
+                        .into_iter()
+                        .map(|((month, currency), (total, mine))| json!({
+                            "month": month,
+                            "currency": currency,
+                            "total_cost": format_amount(total),
+                            "my_share": format_amount(mine),
+                        }))
+                        .collect::<Vec<_>>());
+                }
+                Ok(result)
+            }
+            "export_all_history" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    restart: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                self.export.start(args.restart.unwrap_or(false))?;
+                let mut status = self.export.status().context("Export did not start")?;
+                status["note"] = json!("The export runs in the background; poll get_export_status, then read pages with get_export_result");
+                Ok(status)
+            }
+            "export_ynab_csv" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    currency_code: Option<String>,
+                    save_to: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let currency = match args.currency_code {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: args.dated_after,
+                    dated_before: args.dated_before,
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment && owed_share_of(e, me.id).abs() >= 0.005;
+                let mut expenses = self.scan_expenses(params, None, &keep).await?;
+                expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+                let (expenses, other_currencies): (Vec<Expense>, Vec<Expense>) =
+                    expenses.into_iter().partition(|e| e.currency_code == currency);
+
+                let categories = self.cached_categories().await?;
+                let group_names: HashMap<i64, String> =
+                    self.cached_groups().await?.iter().map(|g| (g.id, g.name.clone())).collect();
+                let mut unmapped = BTreeSet::new();
+                let rows: Vec<Vec<String>> = expenses
+                    .iter()
+                    .map(|expense| {
+                        let code = self
+                            .category_mapping
+                            .as_ref()
+                            .and_then(|mapping| mapping.code(&categories, expense.category.id, &expense.category.name));
+                        let category = match code {
+                            Some((code, _)) => code.to_string(),
+                            None => {
+                                if self.category_mapping.is_some() {
+                                    unmapped.insert(expense.category.name.clone());
+                                }
+                                expense.category.name.clone()
+                            }
+                        };
+                        let memo = match expense.group_id.and_then(|id| group_names.get(&id)) {
+                            Some(group) => format!("{} ({})", category, group),
+                            None => category,
+                        };
+                        let mine = owed_share_of(expense, me.id);
+                        let (outflow, inflow) = if mine > 0.0 {
+                            (format_amount(mine), String::new())
+                        } else {
+                            (String::new(), format_amount(-mine))
+                        };
+                        vec![expense.date.get(..10).unwrap_or(&expense.date).to_string(), expense.description.trim().to_string(), memo, outflow, inflow]
+                    })
+                    .collect();
+                let csv = render::csv_with_columns(&["Date", "Payee", "Memo", "Outflow", "Inflow"], &rows);
+
+                let mut result = json!({
+                    "currency": currency,
+                    "rows": rows.len(),
+                    "net_outflow": format_amount(expenses.iter().map(|e| owed_share_of(e, me.id)).sum()),
+                    "skipped_other_currencies": other_currencies.len(),
+                });
+                if !unmapped.is_empty() {
+                    result["unmapped_categories"] = json!(unmapped);
+                    result["note"] = json!("Some categories have no code in CATEGORY_MAPPING_FILE, so their memo is the Splitwise category; see validate_category_mapping");
+                }
+                match args.save_to {
+                    Some(ref path) => result["saved_to"] = json!(roots::write(path, &csv, self.file_dirs()).await?),
+                    None => result["csv"] = json!(csv),
+                }
+                Ok(result)
+            }
+            "generate_statement_pdf" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    save_to: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let group = self.client.get_group(group_id).await?;
+                let params = ListExpensesParams {
+                    group_id: Some(group_id),
+                    dated_after: args.dated_after.clone(),
+                    dated_before: args.dated_before.clone(),
+                    ..Default::default()
+                };
+                let expenses = self.scan_expenses(params, None, &|e: &Expense| e.deleted_at.is_none()).await?;
+                // The PDF is opaque to PII_REDACTION, so names and descriptions are redacted before it's drawn
+                let (group, mut expenses) = self.redact_data((group, expenses))?;
+                expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+                let merged_aliases = self.merge_aliases(&mut expenses);
+                let (payments, expenses): (Vec<Expense>, Vec<Expense>) = expenses.into_iter().partition(|e| e.payment);
+
+                let mut names: HashMap<i64, String> =
+                    group.members.iter().map(|m| (m.id, full_name(&m.first_name, m.last_name.as_deref()))).collect();
+                // Former members still show up on old expenses
+                for share in expenses.iter().chain(&payments).flat_map(|e| &e.users) {
+                    if let Some(ref user) = share.user {
+                        names
+                            .entry(share.user_id)
+                            .or_insert_with(|| full_name(&user.first_name, user.last_name.as_deref()));
+                    }
+                }
+                let name = |id: i64| names.get(&id).cloned().unwrap_or_else(|| format!("User {}", id));
+                let money = |amount: f64, currency: &str| format!("{} {}", format_amount(amount), currency);
+                let day = |date: &str| date.get(..10).unwrap_or(date).to_string();
+
+                let mut document = pdf::Document::new();
+                document.title(&group.name);
+                let period = match (&args.dated_after, &args.dated_before) {
+                    (Some(from), Some(to)) => format!("Statement from {} to before {}", day(from), day(to)),
+                    (Some(from), None) => format!("Statement from {}", day(from)),
+                    (None, Some(to)) => format!("Statement of everything before {}", day(to)),
+                    (None, None) => "Statement of all expenses".to_string(),
+                };
+                document.text(&period);
+                document.text(&format!("Generated {}", chrono::Utc::now().format("%Y-%m-%d")));
+
+                let left = |x: f64, width: f64| pdf::Column { x, width, align: pdf::Align::Left };
+                let right = |x: f64, width: f64| pdf::Column { x, width, align: pdf::Align::Right };
+                document.heading(&format!("Expenses ({})", expenses.len()));
+                let columns = [left(0.0, 65.0), left(65.0, 175.0), left(240.0, 85.0), left(325.0, 90.0), right(415.0, 80.0)];
+                document.row(&["Date", "Description", "Category", "Paid by", "Amount"], &columns, true);
+                // Per currency: the total, and per member what they paid and consumed
+                let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+                let mut members: BTreeMap<(String, i64), (f64, f64)> = BTreeMap::new();
+                for expense in &expenses {
+                    let cost: f64 = expense.cost.parse().unwrap_or(0.0);
+                    *totals.entry(expense.currency_code.clone()).or_default() += cost;
+                    let mut payers = Vec::new();
+                    for share in &expense.users {
+                        let paid: f64 = share.paid_share.parse().unwrap_or(0.0);
+                        let owed: f64 = share.owed_share.parse().unwrap_or(0.0);
+                        let member = members.entry((expense.currency_code.clone(), share.user_id)).or_default();
+                        member.0 += paid;
+                        member.1 += owed;
+                        if paid > 0.0 {
+                            payers.push(name(share.user_id));
+                        }
+                    }
+                    let cells = [
+                        day(&expense.date),
+                        expense.description.trim().to_string(),
+                        expense.category.name.clone(),
+                        payers.join(", "),
+                        money(cost, &expense.currency_code),
+                    ];
+                    document.row(&cells.each_ref().map(String::as_str), &columns, false);
+                }
+                if expenses.is_empty() {
+                    document.text("No expenses in this period.");
+                }
+                for (currency, total) in &totals {
+                    document.row(&["", "Total", "", "", &money(*total, currency)], &columns, true);
+                }
+
+                document.heading("Paid and consumed in the period");
+                let columns = [left(0.0, 185.0), right(185.0, 100.0), right(285.0, 100.0), right(385.0, 110.0)];
+                document.row(&["Member", "Paid", "Consumed", "Net"], &columns, true);
+                for ((currency, id), (paid, owed)) in &members {
+                    let sign = if paid - owed > 0.005 { "+" } else { "" };
+                    let net = format!("{}{}", sign, money(paid - owed, currency));
+                    document.row(&[&name(*id), &money(*paid, currency), &money(*owed, currency), &net], &columns, false);
+                }
+
+                if !payments.is_empty() {
+                    document.heading(&format!("Settle-ups ({})", payments.len()));
+                    let columns = [left(0.0, 65.0), left(65.0, 150.0), left(215.0, 150.0), right(365.0, 130.0)];
+                    document.row(&["Date", "From", "To", "Amount"], &columns, true);
+                    for payment in &payments {
+                        let who = |share: fn(&ExpenseUser) -> &str| {
+                            let users = payment.users.iter().filter(|u| share(u).parse::<f64>().unwrap_or(0.0) > 0.0);
+                            users.map(|u| name(u.user_id)).collect::<Vec<_>>().join(", ")
+                        };
+                        let from = who(|u| &u.paid_share);
+                        let to = who(|u| &u.owed_share);
+                        let amount = money(payment.cost.parse().unwrap_or(0.0), &payment.currency_code);
+                        document.row(&[&day(&payment.date), &from, &to, &amount], &columns, false);
+                    }
+                }
+
+                // Balances and debts cover the group's whole history, not only the period
+                document.heading("Balances today");
+                let columns = [left(0.0, 285.0), right(285.0, 210.0)];
+                let mut settled = true;
+                for member in &group.members {
+                    for balance in &member.balance {
+                        let amount: f64 = balance.amount.parse().unwrap_or(0.0);
+                        if amount.abs() < 0.005 {
+                            continue;
+                        }
+                        settled = false;
+                        let label = format!("{} {}", name(member.id), if amount > 0.0 { "is owed" } else { "owes" });
+                        document.row(&[&label, &money(amount.abs(), &balance.currency_code)], &columns, false);
+                    }
+                }
+                document.heading("To settle up");
+                let debts = if group.simplify_by_default { &group.simplified_debts } else { &group.original_debts };
+                for debt in debts {
+                    let amount = money(debt.amount.parse().unwrap_or(0.0), &debt.currency_code);
+                    document.text(&format!("{} pays {} {}", name(debt.from), name(debt.to), amount));
+                }
+                if settled || debts.is_empty() {
+                    document.text("Everyone is settled up.");
+                }
+
+                let pages = document.page_count();
+                let bytes = document.finish();
+                let mut result = json!({
+                    "group_id": group.id,
+                    "group_name": group.name,
+                    "expenses": expenses.len(),
+                    "settle_ups": payments.len(),
+                    "pages": pages,
+                    "bytes": bytes.len(),
+                });
+                if !merged_aliases.is_empty() {
+                    result["merged_aliases"] = json!(merged_aliases);
+                }
+                match args.save_to {
+                    Some(ref path) => result["saved_to"] = json!(roots::write(path, &bytes, self.file_dirs()).await?),
+                    None => result["pdf_base64"] = json!(BASE64.encode(&bytes)),
+                }
+                Ok(result)
+            }
+            "render_chart" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    chart: String,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    currency_code: Option<String>,
+                    title: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                if !["category_pie", "monthly_bar", "balance_line"].contains(&args.chart.as_str()) {
+                    anyhow::bail!("chart must be category_pie, monthly_bar or balance_line");
+                }
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let friend_id = self.user_id_from(args.user_id, args.user_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let currency = match args.currency_code {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let (from, to) = chart_period(args.dated_after.as_deref(), args.dated_before.as_deref())?;
+
+                // A balance adds up the whole history, so it is fetched from the start
+                let balance = args.chart == "balance_line";
+                let params = ListExpensesParams {
+                    group_id,
+                    friend_id,
+                    dated_after: (!balance).then(|| from.to_string()),
+                    dated_before: Some(to.to_string()),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && e.currency_code == currency && (balance || !e.payment);
+                let mut expenses = self.scan_expenses(params, None, &keep).await?;
+                expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+                let whole_group = group_id.is_some() && friend_id.is_none();
+                let spent = |e: &Expense| if whole_group { e.cost.parse().unwrap_or(0.0) } else { owed_share_of(e, me.id) };
+                let month_of = |e: &Expense| e.date.get(..7).unwrap_or(&e.date).to_string();
+                // Every month of the period, so quiet months still show
+                let mut months = Vec::new();
+                let mut month = from.with_day(1).context("Invalid date")?;
+                while month < to {
+                    months.push(month.format("%Y-%m").to_string());
+                    month = month + chrono::Months::new(1);
+                }
+
+                let scope = match (&args.group_name, group_id, &args.user_name, friend_id) {
+                    (Some(name), _, _, _) => format!(", {}", name),
+                    (None, Some(id), _, _) => format!(", group {}", id),
+                    (None, None, Some(name), _) => format!(", with {}", name),
+                    (None, None, None, Some(id)) => format!(", with user {}", id),
+                    _ => String::new(),
+                };
+                let last_day = to - chrono::Days::new(1);
+                let (data, default_title, image) = match args.chart.as_str() {
+                    "category_pie" => {
+                        let categories = self.cached_categories().await?;
+                        let parents: HashMap<i64, &str> = categories
+                            .iter()
+                            .flat_map(|parent| {
+                                let subs = parent.subcategories.as_deref().unwrap_or(&[]);
+                                subs.iter().map(|sub| (sub.id, parent.name.as_str())).chain([(parent.id, parent.name.as_str())])
+                            })
+                            .collect();
+                        let mut totals: HashMap<String, f64> = HashMap::new();
+                        for expense in &expenses {
+                            let name = parents.get(&expense.category.id).copied().unwrap_or(&expense.category.name);
+                            *totals.entry(name.to_string()).or_default() += spent(expense);
+                        }
+                        let mut slices: Vec<(String, f64)> = totals.into_iter().filter(|(_, total)| *total > 0.005).collect();
+                        slices.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                        // Seven slices and the rest as one, so the colors stay apart
+                        if slices.len() > 8 {
+                            let rest: f64 = slices.drain(7..).map(|(_, total)| total).sum();
+                            slices.push(("Everything else".to_string(), rest));
+                        }
+                        anyhow::ensure!(!slices.is_empty(), "No spending in {} between {} and {}", currency, from, last_day);
+                        let title = format!("Spending by category{} ({})", scope, currency);
+                        let image = chart::pie(args.title.as_deref().unwrap_or(&title), &slices)?;
+                        (slices, title, image)
+                    }
+                    "monthly_bar" => {
+                        let mut totals: HashMap<String, f64> = HashMap::new();
+                        for expense in &expenses {
+                            *totals.entry(month_of(expense)).or_default() += spent(expense);
+                        }
+                        let bars: Vec<(String, f64)> =
+                            months.iter().map(|m| (m.clone(), totals.get(m).copied().unwrap_or(0.0))).collect();
+                        let title = format!("Spending per month{} ({})", scope, currency);
+                        let image = chart::bars(args.title.as_deref().unwrap_or(&title), &bars)?;
+                        (bars, title, image)
+                    }
+                    _ => {
+                        // My balance once each month's expenses and payments are in
+                        let mut points = Vec::new();
+                        let mut balance = 0.0;
+                        let mut pending = expenses.iter().peekable();
+                        for month in &months {
+                            while let Some(expense) = pending.next_if(|e| month_of(e) <= *month) {
+                                balance += match friend_id {
+                                    Some(friend) => pair_delta(expense, me.id, friend),
+                                    None => expense
+                                        .users
+                                        .iter()
+                                        .find(|u| u.user_id == me.id)
+                                        .and_then(|u| u.net_balance.parse::<f64>().ok())
+                                        .unwrap_or(0.0),
+                                };
+                            }
+                            points.push((month.clone(), (balance * 100.0).round() / 100.0));
+                        }
+                        let title = format!("My balance at the end of each month{} ({})", scope, currency);
+                        let image = chart::line(args.title.as_deref().unwrap_or(&title), &points)?;
+                        (points, title, image)
+                    }
+                };
+                Ok(json!({
+                    "chart": args.chart,
+                    "title": args.title.unwrap_or(default_title),
+                    "currency": currency,
+                    "from": from.to_string(),
+                    "to": last_day.to_string(),
+                    "data": data.iter().map(|(label, value)| json!({ "label": label, "value": format_amount(*value) })).collect::<Vec<_>>(),
+                    "image": { "data": BASE64.encode(&image), "mimeType": "image/png" },
+                }))
+            }
+            "spending_heatmap" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    by: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    dated_after: Option<String>,
+                    dated_before: Option<String>,
+                    currency_code: Option<String>,
+                    image: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let by_hour = match args.by.as_deref().unwrap_or("month") {
+                    "month" => false,
+                    "hour" => true,
+                    other => anyhow::bail!("by must be month or hour, not '{}'", other),
+                };
+                let (from, to) = chart_period(args.dated_after.as_deref(), args.dated_before.as_deref())?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let me = self.cached_me().await?;
+                let currency = match args.currency_code {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let params = ListExpensesParams {
+                    group_id,
+                    dated_after: Some(from.to_string()),
+                    dated_before: Some(to.to_string()),
+                    ..Default::default()
+                };
+                let keep = |e: &Expense| e.deleted_at.is_none() && !e.payment && e.currency_code == currency;
+                let expenses = self.scan_expenses(params, None, &keep).await?;
+                let spent = |e: &Expense| if group_id.is_some() { e.cost.parse().unwrap_or(0.0) } else { owed_share_of(e, me.id) };
+
+                let columns: Vec<String> = if by_hour {
+                    (0..24).map(|hour| format!("{:02}", hour)).collect()
+                } else {
+                    let mut months = Vec::new();
+                    let mut month = from.with_day(1).context("Invalid date")?;
+                    while month < to {
+                        months.push(month.format("%Y-%m").to_string());
+                        month = month + chrono::Months::new(1);
+                    }
+                    months
+                };
+                let mut matrix = vec![vec![0.0; columns.len()]; 7];
+                let mut counts = [0usize; 7];
+                for expense in &expenses {
+                    // A date alone counts as that day; a time is read in the server's time zone
+                    let when = match chrono::DateTime::parse_from_rfc3339(&expense.date) {
+                        Ok(stamp) => stamp.with_timezone(&chrono::Local).naive_local(),
+                        Err(_) => match chrono::NaiveDate::parse_from_str(expense.date.get(..10).unwrap_or_default(), "%Y-%m-%d") {
+                            Ok(day) => day.and_time(chrono::NaiveTime::MIN),
+                            Err(_) => continue,
+                        },
+                    };
+                    let column = if by_hour {
+                        Some(when.hour() as usize)
+                    } else {
+                        columns.iter().position(|month| *month == when.format("%Y-%m").to_string())
+                    };
+                    let Some(column) = column else { continue };
+                    let weekday = when.weekday().num_days_from_monday() as usize;
+                    matrix[weekday][column] += spent(expense);
+                    counts[weekday] += 1;
+                }
+
+                const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+                let totals: Vec<f64> = matrix.iter().map(|row| row.iter().sum()).collect();
+                let total: f64 = totals.iter().sum();
+                let share = |amount: f64| if total > 0.0 { (amount / total * 1000.0).round() / 10.0 } else { 0.0 };
+                let weekdays: Vec<Value> = WEEKDAYS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weekday)| json!({
+                        "weekday": weekday,
+                        "total": format_amount(totals[i]),
+                        "expenses": counts[i],
+                        "share_percent": share(totals[i]),
+                    }))
+                    .collect();
+                let mut result = json!({
+                    "currency": currency,
+                    "from": from.to_string(),
+                    "to": (to - chrono::Days::new(1)).to_string(),
+                    "by": if by_hour { "hour" } else { "month" },
+                    "rows": WEEKDAYS,
+                    "columns": columns,
+                    // Whole units keep the matrix compact; the weekday totals are exact
+                    "matrix": matrix.iter().map(|row| row.iter().map(|v| v.round() as i64).collect::<Vec<_>>()).collect::<Vec<_>>(),
+                    "weekdays": weekdays,
+                    "total": format_amount(total),
+                });
+                if let Some(busiest) = (0..7).filter(|i| totals[*i] > 0.0).max_by(|a, b| totals[*a].total_cmp(&totals[*b])) {
+                    result["busiest"] = json!({
+                        "weekday": WEEKDAYS[busiest],
+                        "share_percent": share(totals[busiest]),
+                        // How many times an average weekday's spending
+                        "times_average": (totals[busiest] / (total / 7.0) * 100.0).round() / 100.0,
+                    });
+                }
+                if args.image.unwrap_or(false) {
+                    let scope = args.group_name.as_deref().map(|name| format!(", {}", name)).unwrap_or_default();
+                    let title = format!("Spending by weekday and {}{} ({})", if by_hour { "hour" } else { "month" }, scope, currency);
+                    let rows: Vec<&str> = WEEKDAYS.iter().map(|day| &day[..3]).collect();
+                    let labels: Vec<&str> = columns.iter().map(String::as_str).collect();
+                    let image = chart::heatmap(&title, &rows, &labels, &matrix)?;
+                    result["image"] = json!({ "data": BASE64.encode(&image), "mimeType": "image/png" });
+                }
+                Ok(result)
+            }
+            "validate_category_mapping" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    include_mapped: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let Some(ref mapping) = self.category_mapping else {
+                    anyhow::bail!("No category mapping is configured; set CATEGORY_MAPPING_FILE to a JSON object of category -> code");
+                };
+                let categories = self.cached_categories().await?;
+                let coverage = mapping.coverage(&categories);
+                let mut result = json!({
+                    "file": self.config.category_mapping_file,
+                    "categories": coverage.mapped.len() + coverage.unmapped.len(),
+                    "mapped": coverage.mapped.len(),
+                    "unmapped": coverage.unmapped.iter().map(|(id, path)| json!({ "id": id, "path": path })).collect::<Vec<_>>(),
+                    "unknown_entries": coverage.unknown_entries,
+                    "complete": coverage.unmapped.is_empty() && coverage.unknown_entries.is_empty(),
+                });
+                if args.include_mapped.unwrap_or(false) {
+                    result["mapping"] = json!(coverage
+                        .mapped
+                        .iter()
+                        .map(|(id, path, code, entry)| json!({ "id": id, "path": path, "code": code, "from": entry }))
+                        .collect::<Vec<_>>());
+                }
+                Ok(result)
+            }
+            "get_export_status" => self
+                .export
+                .status()
+                .context("No export has been started; use export_all_history"),
+            "get_export_result" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    offset: Option<usize>,
+                    limit: Option<usize>,
+                    fields: Option<Vec<String>>,
+                    save_to: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let (expenses, state) = self.export.expenses()?;
+                let me = self.cached_me().await?;
+                let accounts = match self.category_mapping {
+                    Some(ref mapping) => Some((mapping, self.cached_categories().await?)),
+                    None => None,
+                };
+                let with_account = args.fields.as_ref().is_none_or(|fields| fields.iter().any(|f| f == "account"));
+                let project = |expense: &Expense| -> serde_json::Result<Value> {
+                    let mut row = match args.fields {
+                        Some(ref fields) => project_expense(expense, me.id, fields),
+                        None => serde_json::to_value(expense)?,
+                    };
+                    if let (Some((mapping, categories)), true) = (&accounts, with_account) {
+                        let code = mapping.code(categories, expense.category.id, &expense.category.name);
+                        row["account"] = json!(code.map(|(code, _)| code));
+                    }
+                    Ok(row)
+                };
+
+                // Everything exported so far goes to the file, instead of a page
+                if let Some(ref path) = args.save_to {
+                    let rows = expenses.iter().map(project).collect::<serde_json::Result<Vec<Value>>>()?;
+                    let saved = rows.len();
+                    let contents = if path.to_lowercase().ends_with(".csv") {
+                        render::text_content(&render::render(Value::Array(rows), OutputFormat::Csv))
+                    } else {
+                        rows.iter().map(|row| format!("{}\n", row)).collect()
+                    };
+                    let written = roots::write(path, &contents, self.file_dirs()).await?;
+                    return Ok(json!({
+                        "saved_to": written,
+                        "saved": saved,
+                        "complete": state.status == ExportStatus::Done,
+                    }));
+                }
+                let offset = args.offset.unwrap_or(0);
+                let limit = args.limit.unwrap_or(100).clamp(1, 1000);
+                let total = expenses.len();
+                let items = expenses
+                    .iter()
+                    .skip(offset)
+                    .take(limit)
+                    .map(project)
+                    .collect::<serde_json::Result<Vec<Value>>>()?;
+                let next_offset = (offset + limit < total).then_some(offset + limit);
+                // Nothing is filtered, so every expense looked at is returned
+                let returned = items.len();
+                let mut page = page_envelope(items, returned, next_offset);
+                page[budget::POSITIONS] = json!((offset..offset + returned).collect::<Vec<_>>());
+                page["total"] = json!(total);
+                page["complete"] = json!(state.status == ExportStatus::Done);
+                page["data_file"] = json!(self.export.data_file());
+                Ok(page)
+            }
+            "sync_now" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let groups: Vec<(i64, String)> = match self.group_id_from(args.group_id, args.group_name.as_deref()).await? {
+                    Some(id) => {
+                        let group = self.client.get_group(id).await?;
+                        vec![(group.id, group.name)]
+                    }
+                    None => self.cached_groups().await?.iter().map(|g| (g.id, g.name.clone())).collect(),
+                };
+                let mut synced = Vec::new();
+                let mut incomplete = 0;
+                for (group_id, group_name) in groups {
+                    let checkpoint = self.sync_checkpoints.lock().unwrap().get(&group_id).cloned().unwrap_or_default();
+                    let params = ListExpensesParams {
+                        group_id: Some(group_id),
+                        updated_after: checkpoint.query_stamp(),
+                        ..Default::default()
+                    };
+                    let (found, _, next_offset) = self.page_expenses(params, 0, None, &|_| true).await?;
+                    let expenses: Vec<Expense> = found.into_iter().map(|(_, expense)| expense).collect();
+                    // Splitwise pages by date rather than by update, so only a whole fetch moves the checkpoint
+                    if next_offset.is_some() {
+                        incomplete += 1;
+                        synced.push(json!({
+                            "group_id": group_id,
+                            "group_name": group_name,
+                            "complete": false,
+                            "fetched": expenses.len(),
+                        }));
+                        continue;
+                    }
+                    let (delta, updated_after) = {
+                        let mut checkpoints = self.sync_checkpoints.lock().unwrap();
+                        let checkpoint = checkpoints.entry(group_id).or_default();
+                        checkpoint.group_name = group_name.clone();
+                        let delta = checkpoint.apply(&expenses, chrono::Utc::now());
+                        let updated_after = checkpoint.updated_after.clone();
+                        self.store.save(SYNC_CHECKPOINTS, &*checkpoints)?;
+                        (delta, updated_after)
+                    };
+                    let first_sync = checkpoint.updated_after.is_none();
+                    let mut group = json!({
+                        "group_id": group_id,
+                        "group_name": group_name,
+                        "complete": true,
+                        "first_sync": first_sync,
+                        "changes": {
+                            "created": delta.created.len(),
+                            "updated": delta.updated.len(),
+                            "deleted": delta.deleted.len(),
+                            "restored": delta.restored.len(),
+                        },
+                        "updated_after": updated_after,
+                    });
+                    // A first sync "creates" the whole history, which isn't worth listing
+                    if !first_sync {
+                        group["changed_ids"] = json!({
+                            "created": delta.created,
+                            "updated": delta.updated,
+                            "deleted": delta.deleted,
+                            "restored": delta.restored,
+                        });
+                    }
+                    synced.push(group);
+                }
+                let mut result = json!({
+                    "synced_at": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    "groups": synced,
+                });
+                if incomplete > 0 {
+                    result["note"] = json!(format!(
+                        "{} groups had more changes than one call may fetch and were left at their checkpoint; run sync_now with start_job to finish",
+                        incomplete
+                    ));
+                }
+                Ok(result)
+            }
+            "get_sync_status" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let groups = self.cached_groups().await?;
+                let checkpoints = self.sync_checkpoints.lock().unwrap().clone();
+                let mut result = json!({
+                    "groups": checkpoints.iter().map(|(id, checkpoint)| json!({
+                        "group_id": id,
+                        "group_name": checkpoint.group_name,
+                        "updated_after": checkpoint.updated_after,
+                        "synced_at": checkpoint.synced_at,
+                        "tombstones": checkpoint.tombstones.len(),
+                    })).collect::<Vec<_>>(),
+                    "never_synced": groups.iter().filter(|g| !checkpoints.contains_key(&g.id)).map(|g| json!({
+                        "group_id": g.id,
+                        "group_name": g.name,
+                    })).collect::<Vec<_>>(),
+                    "persisted": self.store.dir().is_some(),
+                });
+                if let Some(id) = group_id {
+                    let checkpoint = checkpoints.get(&id).with_context(|| format!("Group {} hasn't been synced yet; run sync_now", id))?;
+                    result["tombstones"] = json!(checkpoint.tombstones);
+                }
+                Ok(result)
+            }
+            "list_pending_writes" => {
+                let pending = self.pending_writes.lock().unwrap();
+                Ok(json!({
+                    "enabled": self.config.queue_offline_writes,
+                    "count": pending.len(),
+                    "writes": *pending,
+                }))
+            }
+            "flush_pending_writes" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    keys: Option<Vec<String>>,
+                    force: Option<bool>,
+                    discard: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let mut discarded = Vec::new();
+                if let Some(discard) = &args.discard {
+                    let mut pending = self.pending_writes.lock().unwrap();
+                    discarded = pending.iter().filter(|w| discard.contains(&w.key)).map(|w| w.key.clone()).collect();
+                    pending.retain(|w| !discard.contains(&w.key));
+                    self.store.save(PENDING_WRITES, &*pending)?;
+                }
+                let mut result = Box::pin(self.flush_writes(args.keys.as_deref(), args.force.unwrap_or(false))).await?;
+                result["discarded"] = json!(discarded);
+                Ok(result)
+            }
+            "create_expense" => self.create_expense(arguments).await,
+            "set_group_split_profile" => {
+                #[derive(Deserialize)]
+                struct Share {
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                    percent: f64,
+                }
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    category_id: Option<i64>,
+                    category_name: Option<String>,
+                    shares: Option<Vec<Share>>,
+                    non_paying: Option<Vec<Value>>,
+                    #[serde(default)]
+                    clear: bool,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("group_id or group_name is required")?;
+                // A parent category stands for all of its subcategories here
+                let category_id = match (args.category_id, args.category_name.as_deref()) {
+                    (Some(id), _) => Some(id),
+                    (None, Some(name)) => Some(pick("category", name, &self.rank_categories(name, true).await?)?),
+                    (None, None) => None,
+                };
+                let category = match category_id {
+                    Some(id) => {
+                        let categories = self.cached_categories().await?;
+                        let name = categories
+                            .iter()
+                            .find_map(|parent| {
+                                if parent.id == id {
+                                    return Some(parent.name.clone());
+                                }
+                                let sub = parent.subcategories.iter().flatten().find(|sub| sub.id == id)?;
+                                Some(if sub.name == "Other" { format!("{} other", parent.name) } else { sub.name.clone() })
+                            })
+                            .with_context(|| format!("Unknown category {}", id))?;
+                        Some((id, name))
+                    }
+                    None => None,
+                };
+
+                if args.clear {
+                    let mut profiles = self.split_profiles.lock().unwrap();
+                    let removed = match category {
+                        Some((category_id, _)) => {
+                            let removed = profiles
+                                .get_mut(&group_id)
+                                .is_some_and(|profile| profile.categories.remove(&category_id).is_some());
+                            // Nothing left to apply
+                            if profiles.get(&group_id).is_some_and(SplitProfile::is_empty) {
+                                profiles.remove(&group_id);
+                            }
+                            removed
+                        }
+                        None => profiles.remove(&group_id).is_some(),
+                    };
+                    self.store.save(SPLIT_PROFILES, &*profiles)?;
+                    return Ok(json!({
+                        "success": true,
+                        "group_id": group_id,
+                        "cleared": removed,
+                        "profile": profiles.get(&group_id).map(|profile| profile.summary(group_id)),
+                    }));
+                }
+                if args.shares.is_none() && args.non_paying.is_none() {
+                    anyhow::bail!("Give shares (each member's percent), non_paying, or clear: true to remove the profile");
+                }
+                if category.is_some() && args.shares.is_none() {
+                    anyhow::bail!("Give the shares to split this category by");
+                }
+
+                let group = self.client.get_group(group_id).await?;
+                let member_of = |user_id: i64| {
+                    group
+                        .members
+                        .iter()
+                        .find(|m| m.id == user_id)
+                        .with_context(|| format!("User {} is not a member of {}", user_id, group.name))
+                };
+                let mut shares: Option<Vec<ProfileShare>> = None;
+                if let Some(requested) = args.shares {
+                    if requested.is_empty() {
+                        anyhow::bail!("shares is empty; use clear: true to remove a split");
+                    }
+                    let mut resolved: Vec<ProfileShare> = Vec::with_capacity(requested.len());
+                    for share in requested {
+                        let user_id = self
+                            .user_id_from(share.user_id, share.user_name.as_deref())
+                            .await?
+                            .context("Each share needs a user_id or user_name")?;
+                        let member = member_of(user_id)?;
+                        if !share.percent.is_finite() || share.percent < 0.0 {
+                            anyhow::bail!("Invalid percent for {}: {}", member.first_name, share.percent);
+                        }
+                        if resolved.iter().any(|s| s.user_id == user_id) {
+                            anyhow::bail!("{} is listed twice", member.first_name);
+                        }
+                        resolved.push(ProfileShare {
+                            user_id,
+                            name: full_name(&member.first_name, member.last_name.as_deref()),
+                            percent: share.percent,
+                        });
+                    }
+                    let total: f64 = resolved.iter().map(|s| s.percent).sum();
+                    if (total - 100.0).abs() > 0.01 {
+                        anyhow::bail!("The percents add up to {}, not 100", total);
+                    }
+                    shares = Some(resolved);
+                }
+                let mut non_paying: Option<Vec<ProfileMember>> = None;
+                if let Some(people) = args.non_paying {
+                    let mut resolved: Vec<ProfileMember> = Vec::with_capacity(people.len());
+                    for person in people {
+                        let user_id = match person {
+                            Value::Number(ref id) => id.as_i64(),
+                            Value::String(ref name) => self.user_id_from(None, Some(name)).await?,
+                            _ => None,
+                        }
+                        .with_context(|| format!("non_paying entries are user IDs or names, got {}", person))?;
+                        let member = member_of(user_id)?;
+                        if !resolved.iter().any(|m| m.user_id == user_id) {
+                            resolved.push(ProfileMember {
+                                user_id,
+                                name: full_name(&member.first_name, member.last_name.as_deref()),
+                            });
+                        }
+                    }
+                    non_paying = Some(resolved);
+                }
+
+                let mut profiles = self.split_profiles.lock().unwrap();
+                let mut profile = profiles.get(&group_id).cloned().unwrap_or_else(|| SplitProfile {
+                    group_name: group.name.clone(),
+                    shares: Vec::new(),
+                    categories: BTreeMap::new(),
+                    non_paying: Vec::new(),
+                    updated_at: String::new(),
+                });
+                match (shares, category) {
+                    (Some(shares), Some((category_id, category_name))) => {
+                        profile.categories.insert(category_id, CategorySplit { category_name, shares });
+                    }
+                    (Some(shares), None) => profile.shares = shares,
+                    (None, _) => {}
+                }
+                if let Some(non_paying) = non_paying {
+                    profile.non_paying = non_paying;
+                }
+                if let Some((member, rule)) = profile.paying_conflict() {
+                    anyhow::bail!("{} is marked as non-paying but has a share in {}", member.name, rule);
+                }
+                profile.group_name = group.name.clone();
+                profile.updated_at = chrono::Utc::now().to_rfc3339();
+                let result = json!({ "success": true, "profile": profile.summary(group_id) });
+                if profile.is_empty() {
+                    profiles.remove(&group_id);
+                } else {
+                    profiles.insert(group_id, profile);
+                }
+                self.store.save(SPLIT_PROFILES, &*profiles)?;
+                Ok(result)
+            }
+            "get_group_split_profile" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let profiles = self.split_profiles.lock().unwrap();
+                match group_id {
+                    Some(id) => Ok(match profiles.get(&id) {
+                        Some(profile) => profile.summary(id),
+                        None => json!({
+                            "group_id": id,
+                            "shares": null,
+                            "categories": [],
+                            "note": "No split profile: expenses in this group are split equally unless create_expense is given a split",
+                        }),
+                    }),
+                    None => Ok(json!({
+                        "profiles": profiles.iter().map(|(id, profile)| profile.summary(*id)).collect::<Vec<_>>(),
+                    })),
+                }
+            }
+            "set_member_alias" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                    aliases: Vec<Value>,
+                    #[serde(default)]
+                    remove: bool,
+                }
+                let args: Args = parse_args(arguments)?;
+                let mut alias_ids = Vec::with_capacity(args.aliases.len());
+                for alias in &args.aliases {
+                    alias_ids.push(match alias {
+                        Value::Number(n) => n.as_i64().context("Invalid user ID")?,
+                        Value::String(name) => pick("user", name, &self.rank_users(name).await?)?,
+                        _ => anyhow::bail!("Expected a user ID or name, got {}", alias),
+                    });
+                }
+                anyhow::ensure!(!alias_ids.is_empty(), "Give at least one alias");
+
+                if args.remove {
+                    let mut aliases = self.member_aliases.lock().unwrap();
+                    let removed: Vec<i64> = alias_ids.iter().copied().filter(|id| aliases.remove(id).is_some()).collect();
+                    self.store.save(MEMBER_ALIASES, &*aliases)?;
+                    return Ok(json!({ "removed": removed, "count": aliases.len() }));
+                }
+
+                let user_id = self
+                    .user_id_from(args.user_id, args.user_name.as_deref())
+                    .await?
+                    .context("Provide user_id or user_name for the account the aliases stand for")?;
+                let people = self.known_people().await?;
+                let name_of = |id: i64| people.get(&id).cloned().unwrap_or_else(|| format!("User {}", id));
+                let mut aliases = self.member_aliases.lock().unwrap();
+                // One level only: an alias always points at a real account
+                if let Some(alias) = aliases.get(&user_id) {
+                    anyhow::bail!("{} is itself an alias of {}; link the aliases to {} instead", name_of(user_id), alias.name, alias.user_id);
+                }
+                let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+                for &id in &alias_ids {
+                    anyhow::ensure!(id != user_id, "{} can't be an alias of itself", name_of(id));
+                    if let Some(other) = aliases.values().find(|a| a.user_id == id) {
+                        anyhow::bail!("{} already has aliases of its own ({}); remove them first", name_of(id), other.alias_name);
+                    }
+                    aliases.insert(id, MemberAlias { user_id, name: name_of(user_id), alias_name: name_of(id), linked_at: now.clone() });
+                }
+                self.store.save(MEMBER_ALIASES, &*aliases)?;
+                Ok(json!({
+                    "user_id": user_id,
+                    "name": name_of(user_id),
+                    "aliases": aliases
+                        .iter()
+                        .filter(|(_, a)| a.user_id == user_id)
+                        .map(|(id, a)| json!({ "alias_id": id, "alias_name": a.alias_name }))
+                        .collect::<Vec<_>>(),
+                    "persisted": self.store.dir().is_some(),
+                }))
+            }
+            "list_member_aliases" => {
+                let aliases = self.member_aliases.lock().unwrap();
+                Ok(json!({
+                    "count": aliases.len(),
+                    "aliases": aliases.iter().map(|(id, a)| json!({
+                        "alias_id": id,
+                        "alias_name": a.alias_name,
+                        "user_id": a.user_id,
+                        "name": a.name,
+                        "linked_at": a.linked_at,
+                    })).collect::<Vec<_>>(),
+                }))
+            }
+            "calculate_household_split" => {
+                #[derive(Deserialize)]
+                struct Member {
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                    #[serde(default)]
+                    weights: HashMap<String, f64>,
+                }
+                #[derive(Deserialize)]
+                struct Bill {
+                    description: String,
+                    cost: Value,
+                    currency_code: Option<String>,
+                    split_by: Option<String>,
+                    paid_by: Option<String>,
+                    category_name: Option<String>,
+                    date: Option<String>,
+                }
+                #[derive(Deserialize)]
+                struct Args {
+                    members: Vec<Member>,
+                    bills: Vec<Bill>,
+                    split_by: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    create: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                if args.members.is_empty() || args.bills.is_empty() {
+                    anyhow::bail!("Provide at least one member and one bill");
+                }
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let (me, people) = tokio::try_join!(self.cached_me(), self.known_people())?;
+
+                let mut member_ids = Vec::with_capacity(args.members.len());
+                for member in &args.members {
+                    let id = self
+                        .user_id_from(member.user_id, member.user_name.as_deref())
+                        .await?
+                        .context("Each member needs a user_id or user_name")?;
+                    member_ids.push(id);
+                }
+                let name_of = |id: i64| people.get(&id).cloned().unwrap_or_else(|| format!("user {}", id));
+                // Splitwise rejects an expense with the same user twice, so catch it before any bill
+                let mut seen = HashSet::new();
+                if let Some(id) = member_ids.iter().find(|id| !seen.insert(**id)) {
+                    anyhow::bail!("{} is listed more than once among the members; list each member once with all their weights", name_of(*id));
+                }
+
+                let mut bills = Vec::new();
+                let mut requests = Vec::new();
+                let mut per_member: BTreeMap<(i64, String), f64> = BTreeMap::new();
+                for bill in &args.bills {
+                    let cost = parse_amount(&bill.cost)?;
+                    let factor = bill.split_by.as_deref().or(args.split_by.as_deref()).unwrap_or("equal");
+                    let weights = args
+                        .members
+                        .iter()
+                        .zip(&member_ids)
+                        .map(|(member, id)| match factor {
+                            "equal" => Ok(1.0),
+                            _ => member.weights.get(factor).copied().with_context(|| {
+                                format!("{} has no '{}' weight (needed by '{}')", name_of(*id), factor, bill.description)
+                            }),
+                        })
+                        .collect::<Result<Vec<f64>>>()?;
+                    let owed = split_by_weights(cost, &weights)
+                        .with_context(|| format!("The '{}' weights for '{}' add up to zero", factor, bill.description))?;
+                    let payer = match &bill.paid_by {
+                        Some(name) => self.user_id_from(None, Some(name)).await?.unwrap_or(me.id),
+                        None => me.id,
+                    };
+                    if !member_ids.contains(&payer) {
+                        anyhow::bail!("{} paid '{}' but is not one of the members", name_of(payer), bill.description);
+                    }
+
+                    let currency = bill.currency_code.clone().unwrap_or_default();
+                    let shares: Vec<ExpenseShare> = member_ids
+                        .iter()
+                        .zip(&owed)
+                        .map(|(id, owed)| {
+                            *per_member.entry((*id, currency.clone())).or_default() += owed;
+                            ExpenseShare {
+                                user_id: Some(*id),
+                                email: None,
+                                first_name: None,
+                                last_name: None,
+                                paid_share: format_amount(if *id == payer { cost } else { 0.0 }),
+                                owed_share: format_amount(*owed),
+                            }
+                        })
+                        .collect();
+                    bills.push(json!({
+                        "description": bill.description,
+                        "cost": format_amount(cost),
+                        "currency_code": bill.currency_code,
+                        "split_by": factor,
+                        "paid_by": name_of(payer),
+                        "shares": shares.iter().zip(&weights).map(|(share, weight)| json!({
+                            "user_id": share.user_id,
+                            "name": share.user_id.map(name_of),
+                            "weight": weight,
+                            "paid_share": share.paid_share,
+                            "owed_share": share.owed_share,
+                        })).collect::<Vec<_>>(),
+                    }));
+                    requests.push(CreateExpenseRequest {
+                        cost: format_amount(cost),
+                        description: bill.description.clone(),
+                        currency_code: bill.currency_code.clone(),
+                        category_id: self.category_id_from(None, bill.category_name.as_deref()).await?,
+                        date: bill.date.clone(),
+                        repeat_interval: None,
+                        details: None,
+                        payment: Some(false),
+                        group_id,
+                        split_equally: Some(false),
+                        split_by_shares: Some(shares),
+                    });
+                }
+
+                let mut result = json!({
+                    "bills": bills,
+                    "totals": per_member.iter().map(|((id, currency), owed)| json!({
+                        "user_id": id,
+                        "name": name_of(*id),
+                        "currency_code": (!currency.is_empty()).then_some(currency),
+                        "owed": format_amount(*owed),
+                    })).collect::<Vec<_>>(),
+                });
+                if args.create.unwrap_or(false) {
+                    // One at a time, so a failure leaves a clear record of what was already created
+                    let mut created = Vec::new();
+                    for request in requests {
+                        let description = request.description.clone();
+                        match self.client.create_expense(request).await {
+                            Ok(expenses) => {
+                                self.lookups.dashboard.invalidate();
+                                created.push(json!({
+                                    "description": description,
+                                    "id": expenses.first().map(|e| e.id),
+                                }))
+                            }
+                            Err(e) => {
+                                result["created"] = json!(created);
+                                result["error"] = json!(format!("Creating '{}' failed: {}", description, e));
+                                return Ok(result);
+                            }
+                        }
+                    }
+                    result["created"] = json!(created);
+                }
+                Ok(result)
+            }
+            "quick_add_expense" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    input: String,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    autosave: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let autosave = args.autosave.unwrap_or(false);
+                let group_id = self.group_id_from(args.group_id, args.group_name.as_deref()).await?;
+                let friend_id = self.user_id_from(args.friend_id, args.user_name.as_deref()).await?;
+                let parsed = self
+                    .client
+                    .parse_sentence(&args.input, group_id, friend_id, autosave)
+                    .await?;
+
+                let expense = parsed.expense.unwrap_or(Value::Null);
+                let split: Vec<Value> = expense["users"]
+                    .as_array()
+                    .map(|users| users.iter().map(|u| json!({
+                        "user_id": u["user_id"],
+                        "name": u["user"]["first_name"],
+                        "paid": u["paid_share"],
+                        "owes": u["owed_share"]
+                    })).collect())
+                    .unwrap_or_default();
+
+                Ok(json!({
+                    "valid": parsed.valid,
+                    "saved": autosave && parsed.valid,
+                    "confidence": parsed.confidence,
+                    "error": parsed.error,
+                    "expense": {
+                        "id": expense["id"],
+                        "description": expense["description"],
+                        "cost": expense["cost"],
+                        "currency_code": expense["currency_code"],
+                        "date": expense["date"],
+                        "group_id": expense["group_id"],
+                        "category": expense["category"]["name"],
+                        "split": split
+                    }
+                }))
+            }
+            "split_with_friend" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    email: Option<String>,
+                    cost: String,
+                    description: String,
+                    ratio: Option<String>,
+                    paid_by: Option<String>,
+                    currency_code: Option<String>,
+                    date: Option<String>,
+                    category_id: Option<i64>,
+                    category_name: Option<String>,
+                    details: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let me = self.cached_me().await?;
+                // An email that isn't a friend's is invited by create_expense
+                let (friend_key, friend) = match (self.user_id_from(args.friend_id, args.user_name.as_deref()).await?, args.email) {
+                    (Some(id), _) if id == me.id => anyhow::bail!("The friend to split with can't be you"),
+                    (Some(id), _) => ("user_id", json!(id)),
+                    (None, Some(email)) => ("email", json!(email.trim())),
+                    (None, None) => anyhow::bail!("Provide friend_id, user_name or email"),
+                };
+                let friend_paid = match args.paid_by.as_deref().unwrap_or("me") {
+                    "me" => false,
+                    "friend" => true,
+                    other => anyhow::bail!("paid_by must be me or friend, not '{}'", other),
+                };
+
+                // "60:40", "60/40" or "2:1", your part first
+                let ratio = args.ratio.as_deref().unwrap_or("1:1");
+                let parts: Vec<f64> = ratio
+                    .split([':', '/'])
+                    .map(|part| part.trim().trim_end_matches('%').parse::<f64>())
+                    .collect::<std::result::Result<_, _>>()
+                    .ok()
+                    .filter(|parts: &Vec<f64>| parts.len() == 2)
+                    .with_context(|| format!("ratio must be your part to the friend's, like 1:1 or 60:40, not '{}'", ratio))?;
+                let cost = parse_amount(&json!(args.cost))?;
+                let owed = split_by_weights(cost, &parts)
+                    .with_context(|| format!("ratio '{}' needs parts that are not negative and not both zero", ratio))?;
+                let paid = |friend_side: bool| format_amount(if friend_side == friend_paid { cost } else { 0.0 });
+                let mine = json!({ "user_id": me.id, "paid_share": paid(false), "owed_share": format_amount(owed[0]) });
+                let mut theirs = json!({ "paid_share": paid(true), "owed_share": format_amount(owed[1]) });
+                theirs[friend_key] = friend;
+
+                let mut arguments = json!({
+                    "cost": format_amount(cost),
+                    "description": args.description,
+                    "split_by_shares": [mine, theirs],
+                });
+                for (key, value) in [
+                    ("currency_code", args.currency_code.map(Value::String)),
+                    ("date", args.date.map(Value::String)),
+                    ("category_id", args.category_id.map(|id| json!(id))),
+                    ("category_name", args.category_name.map(Value::String)),
+                    ("details", args.details.map(Value::String)),
+                ] {
+                    if let Some(value) = value {
+                        arguments[key] = value;
+                    }
+                }
+                let mut created = Box::pin(self.create_expense(arguments)).await?;
+                if friend_paid {
+                    created["you_owe_friend"] = json!(format_amount(owed[0]));
+                } else {
+                    created["friend_owes_you"] = json!(format_amount(owed[1]));
+                }
+                Ok(created)
+            }
+            "iou" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    lender: Option<Value>,
+                    borrower: Option<Value>,
+                    amount: Value,
+                    description: Option<String>,
+                    currency_code: Option<String>,
+                    date: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let me = self.cached_me().await?;
+                let (lender, borrower) = match (args.lender, args.borrower) {
+                    (None, None) => anyhow::bail!("Give the lender or the borrower; the other side is you"),
+                    (lender, borrower) => (lender.unwrap_or(json!(me.id)), borrower.unwrap_or(json!(me.id))),
+                };
+                // The sign carries no meaning; lender and borrower say which way the money went
+                let amount = parse_amount(&args.amount)?;
+                anyhow::ensure!(amount.abs() >= 0.005, "The amount must not be zero");
+                let amount = format_amount(amount.abs());
+
+                let mut shares = Vec::with_capacity(2);
+                for (person, paid, owed) in [(&lender, amount.clone(), format_amount(0.0)), (&borrower, format_amount(0.0), amount.clone())] {
+                    let share = share_for(person, paid, owed)?;
+                    shares.extend(self.shares_from(Some(vec![share])).await?.unwrap_or_default());
+                }
+                anyhow::ensure!(!same_person(&shares[0], &shares[1]), "The lender and the borrower are the same person");
+                anyhow::ensure!(
+                    shares.iter().any(|share| share.user_id == Some(me.id)),
+                    "One side of an IOU must be you; for money between two friends, use create_expense"
+                );
+                let people = self.known_people().await?;
+                let name_of = |share: &ExpenseShare| match share.user_id {
+                    Some(id) if id == me.id => "you".to_string(),
+                    Some(id) => people.get(&id).cloned().unwrap_or_else(|| format!("user {}", id)),
+                    None => full_name(share.first_name.as_deref().unwrap_or_default(), share.last_name.as_deref()),
+                };
+                let (lender_name, borrower_name) = (name_of(&shares[0]), name_of(&shares[1]));
+
+                let mut arguments = json!({
+                    "cost": amount,
+                    "description": args.description.unwrap_or_else(|| "Loan".to_string()),
+                    "split_by_shares": shares,
+                });
+                if let Some(currency) = args.currency_code {
+                    arguments["currency_code"] = json!(currency);
+                }
+                if let Some(date) = args.date {
+                    arguments["date"] = json!(date);
+                }
+                let mut created = Box::pin(self.create_expense(arguments)).await?;
+                created["lender"] = json!(lender_name);
+                created["borrower"] = json!(borrower_name);
+                created["summary"] = json!(if borrower_name == "you" {
+                    format!("You owe {} {} more", lender_name, amount)
+                } else {
+                    format!("{} owes you {} more", borrower_name, amount)
+                });
+                Ok(created)
+            }
+            "dinner_split" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    total: Value,
+                    participants: Vec<Value>,
+                    paid_by: Option<Value>,
+                    tip_percent: Option<f64>,
+                    description: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    currency_code: Option<String>,
+                    date: Option<String>,
+                    category_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let subtotal = parse_amount(&args.total)?;
+                anyhow::ensure!(subtotal > 0.0, "The total must be more than zero");
+                let tip_percent = args.tip_percent.unwrap_or(0.0);
+                anyhow::ensure!((0.0..=100.0).contains(&tip_percent), "tip_percent must be between 0 and 100");
+                let tip = (subtotal * tip_percent).round() / 100.0;
+                let cost = subtotal + tip;
+
+                // Everyone resolved once, so the payer isn't counted twice when also listed
+                let me = self.cached_me().await?;
+                let payer = args.paid_by.unwrap_or(json!(me.id));
+                let mut people: Vec<ExpenseShare> = Vec::new();
+                for person in std::iter::once(&payer).chain(&args.participants) {
+                    let share = share_for(person, format_amount(0.0), format_amount(0.0))?;
+                    let resolved = self.shares_from(Some(vec![share])).await?.unwrap_or_default();
+                    for share in resolved {
+                        if !people.iter().any(|known| same_person(known, &share)) {
+                            people.push(share);
+                        }
+                    }
+                }
+                anyhow::ensure!(people.len() > 1, "A bill needs at least two people to split it");
+                let person = |share: &ExpenseShare| match (share.user_id, &share.email) {
+                    (Some(id), _) => json!(id),
+                    (None, email) => json!(email),
+                };
+
+                let mut arguments = json!({
+                    "cost": format_amount(cost),
+                    "description": args.description.unwrap_or_else(|| "Dinner".to_string()),
+                    "split_among": people.iter().map(person).collect::<Vec<_>>(),
+                    "paid_by": person(&people[0]),
+                });
+                for (key, value) in [
+                    ("group_id", args.group_id.map(|id| json!(id))),
+                    ("group_name", args.group_name.map(Value::String)),
+                    ("currency_code", args.currency_code.map(Value::String)),
+                    ("date", args.date.map(Value::String)),
+                    ("category_name", args.category_name.map(Value::String)),
+                ] {
+                    if let Some(value) = value {
+                        arguments[key] = value;
+                    }
+                }
+                let mut created = Box::pin(self.create_expense(arguments)).await?;
+                created["subtotal"] = json!(format_amount(subtotal));
+                created["tip"] = json!(format_amount(tip));
+                created["people"] = json!(people.len());
+                Ok(created)
+            }
+            "update_expense" => self.update_expense(arguments).await,
+            "delete_expense" => self.delete_expense(arguments).await,
+            // Friend tools
+            "list_friends" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    fields: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let friends = self.client.get_friends().await?;
+                // Splitwise returns all friends at once, so there is never another page
+                let count = friends.len();
+                let items = projection::project_if(serde_json::to_value(friends)?, args.fields.as_deref());
+                Ok(page_envelope(serde_json::from_value(items)?, count, None))
+            }
+            "get_friend" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    fields: Option<Vec<String>>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let friend_id = self
+                    .user_id_from(args.friend_id, args.user_name.as_deref())
+                    .await?
+                    .context("Provide friend_id or user_name")?;
+                let friend = self.client.get_friend(friend_id).await?;
+                Ok(projection::project_if(serde_json::to_value(friend)?, args.fields.as_deref()))
+            }
+            "add_friend" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    email: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                let friends = self.client.create_friend(args.email).await?;
+                self.lookups.invalidate_people();
                 Ok(serde_json::to_value(friends)?)
             }
-            // Utility tools
-            "get_currencies" => {
-                let currencies = self.client.get_currencies().await?;
-                Ok(serde_json::to_value(currencies)?)
+            "remove_friend" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let friend_id = self
+                    .user_id_from(args.friend_id, args.user_name.as_deref())
+                    .await?
+                    .context("Provide friend_id or user_name")?;
+                let friend = self.client.get_friend(friend_id).await?;
+                if let Some(open) = friend.balance.iter().find(|b| !is_zero_amount(&b.amount)) {
+                    anyhow::bail!(
+                        "The balance with {} isn't settled ({} {}); settle it before removing the friendship",
+                        friend.first_name,
+                        open.amount,
+                        open.currency_code
+                    );
+                }
+                let success = self.client.delete_friend(friend_id).await?;
+                self.lookups.invalidate_people();
+                Ok(json!({ "success": success }))
+            }
+            "find_stale_friendships" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    months: Option<u32>,
+                    remove: Option<bool>,
+                    confirm: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let months = args.months.unwrap_or(12);
+                if !(1..=120).contains(&months) {
+                    anyhow::bail!("months must be between 1 and 120");
+                }
+                let cutoff = chrono::Utc::now().date_naive() - chrono::Months::new(months);
+
+                // Only settled friendships are candidates; check recent activity for those concurrently
+                let friends = self.client.get_friends().await?;
+                let settled: Vec<Friend> = friends
+                    .into_iter()
+                    .filter(|f| f.balance.iter().all(|b| is_zero_amount(&b.amount)))
+                    .collect();
+
+                // Deleted expenses still come back from Splitwise, so look past them for a live one
+                let activity = futures::future::join_all(settled.iter().map(|friend| {
+                    let params = ListExpensesParams {
+                        friend_id: Some(friend.id),
+                        dated_after: Some(cutoff.to_string()),
+                        ..Default::default()
+                    };
+                    self.scan_expenses(params, Some(1), &|e: &Expense| e.deleted_at.is_none())
+                }))
+                .await;
+
+                let mut stale = Vec::new();
+                for (friend, recent) in settled.iter().zip(activity) {
+                    if recent?.is_empty() {
+                        stale.push(friend);
+                    }
+                }
+
+                let listed: Vec<Value> = stale.iter().map(|f| json!({
+                    "id": f.id,
+                    "name": format!("{} {}", f.first_name, f.last_name.as_deref().unwrap_or("")).trim(),
+                    "email": f.email,
+                    "updated_at": f.updated_at,
+                })).collect();
+
+                let mut result = json!({
+                    "months": months,
+                    "inactive_since": cutoff.to_string(),
+                    "count": listed.len(),
+                    "stale_friends": listed,
+                });
+
+                if args.remove.unwrap_or(false) {
+                    if args.confirm.unwrap_or(false) {
+                        let mut removed = Vec::new();
+                        for friend in &stale {
+                            removed.push(match self.client.delete_friend(friend.id).await {
+                                Ok(success) => json!({ "id": friend.id, "success": success }),
+                                Err(e) => json!({ "id": friend.id, "success": false, "error": e.to_string() }),
+                            });
+                        }
+                        self.lookups.invalidate_people();
+                        result["removed"] = json!(removed);
+                    } else {
+                        result["confirmation_required"] = json!(format!(
+                            "{} friends would be removed. Ask the user, then call again with remove: true and confirm: true",
+                            stale.len()
+                        ));
+                    }
+                }
+
+                Ok(result)
+            }
+            "pending_invitations" => {
+                let friends = self.client.get_friends().await?;
+                let invited: Vec<Friend> = friends
+                    .into_iter()
+                    .filter(|f| f.registration_status.as_deref().is_some_and(|s| s != "confirmed"))
+                    .collect();
+
+                let shared = futures::future::join_all(invited.iter().map(|friend| {
+                    let params = ListExpensesParams {
+                        friend_id: Some(friend.id),
+                        ..Default::default()
+                    };
+                    self.scan_expenses(params, None, &|e: &Expense| e.deleted_at.is_none())
+                }))
+                .await;
+
+                let mut listed = Vec::new();
+                for (friend, expenses) in invited.iter().zip(shared) {
+                    let expenses = expenses?;
+                    listed.push(json!({
+                        "id": friend.id,
+                        "name": full_name(&friend.first_name, friend.last_name.as_deref()),
+                        "email": friend.email,
+                        "registration_status": friend.registration_status,
+                        "balance": friend.balance,
+                        "expense_count": expenses.len(),
+                        "expenses": expenses.iter().map(|e| json!({
+                            "id": e.id,
+                            "description": e.description,
+                            "date": e.date,
+                            "cost": e.cost,
+                            "currency_code": e.currency_code,
+                            "their_share": format_amount(owed_share_of(e, friend.id)),
+                        })).collect::<Vec<_>>(),
+                    }));
+                }
+                Ok(json!({
+                    "count": listed.len(),
+                    "invited": listed,
+                    "note": "Invited people can already owe and be owed; Splitwise emails them about new expenses and their balance carries over once they sign up",
+                }))
+            }
+            "get_group_invites" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let group = self.client.get_group(group_id).await?;
+                let pending: Vec<Value> = group
+                    .members
+                    .iter()
+                    .filter(|m| m.registration_status.as_deref().is_some_and(|s| s != "confirmed"))
+                    .map(|m| json!({
+                        "user_id": m.id,
+                        "name": full_name(&m.first_name, m.last_name.as_deref()),
+                        "email": m.email,
+                        "registration_status": m.registration_status,
+                        "balance": m.balance,
+                    }))
+                    .collect();
+                let mut result = json!({
+                    "group_id": group.id,
+                    "group_name": group.name,
+                    "invite_link": group.invite_link,
+                    "pending_count": pending.len(),
+                    "pending": pending,
+                });
+                if group.invite_link.is_none() {
+                    result["note"] = json!("Splitwise didn't return an invite link for this group; people can still be invited by email from the Splitwise app");
+                }
+                Ok(result)
+            }
+            "resend_invite" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    user_id: Option<i64>,
+                    user_name: Option<String>,
+                    email: Option<String>,
+                    language: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let language = args.language.as_deref().unwrap_or("en");
+                anyhow::ensure!(["en", "es"].contains(&language), "Unsupported language '{}'; use en or es", language);
+                let group_id = self
+                    .group_id_from(args.group_id, args.group_name.as_deref())
+                    .await?
+                    .context("Provide group_id or group_name")?;
+                let user_id = self.user_id_from(args.user_id, args.user_name.as_deref()).await?;
+                let group = self.client.get_group(group_id).await?;
+                let member = match (user_id, args.email.as_deref()) {
+                    (Some(id), _) => group.members.iter().find(|m| m.id == id),
+                    (None, Some(email)) => group
+                        .members
+                        .iter()
+                        .find(|m| m.email.as_deref().is_some_and(|e| e.eq_ignore_ascii_case(email.trim()))),
+                    (None, None) => anyhow::bail!("Provide user_id, user_name or email"),
+                }
+                .with_context(|| format!("That person isn't a member of {}", group.name))?;
+                let name = full_name(&member.first_name, member.last_name.as_deref());
+                if member.registration_status.as_deref().is_none_or(|s| s == "confirmed") {
+                    anyhow::bail!("{} has already joined Splitwise; there is no invitation to resend", name);
+                }
+
+                // Adding a member again is what has Splitwise send its invitation email
+                let input = GroupUserInput { user_id: Some(member.id), first_name: None, last_name: None, email: None };
+                self.client.add_user_to_group(group_id, input).await?;
+
+                let first = &member.first_name;
+                let message = match (language, &group.invite_link) {
+                    ("es", Some(link)) => format!("¡Hola {}! Te sumé a {} en Splitwise, donde llevamos los gastos compartidos. Entra con este enlace: {}", first, group.name, link),
+                    ("es", None) => format!("¡Hola {}! Te sumé a {} en Splitwise, donde llevamos los gastos compartidos. Regístrate en https://www.splitwise.com con este email para verlo.", first, group.name),
+                    (_, Some(link)) => format!("Hi {}! I added you to {} on Splitwise, where we keep track of shared expenses. Join with this link: {}", first, group.name, link),
+                    _ => format!("Hi {}! I added you to {} on Splitwise, where we keep track of shared expenses. Sign up at https://www.splitwise.com with this email to see it.", first, group.name),
+                };
+                Ok(json!({
+                    "group_id": group.id,
+                    "group_name": group.name,
+                    "user_id": member.id,
+                    "name": name,
+                    "email": member.email,
+                    "resent": true,
+                    "invite_link": group.invite_link,
+                    "message": message,
+                }))
+            }
+            // Utility tools
+            "get_currencies" => {
+                let currencies = self.client.get_currencies().await?;
+                Ok(serde_json::to_value(currencies)?)
+            }
+            "get_common_currencies" => self.common_currencies(arguments).await,
+            "suggest_category" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    description: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                let key = nl_query::normalize(&args.description).trim().to_string();
+
+                // 1. What the user chose before for this or a very similar description
+                let learned = {
+                    let learned = self.learned_categories.lock().unwrap();
+                    learned
+                        .get(&key)
+                        .map(|l| (l.clone(), 1.0))
+                        .or_else(|| {
+                            learned
+                                .iter()
+                                .map(|(known, l)| (l, strsim::normalized_levenshtein(known, &key)))
+                                .filter(|(_, similarity)| *similarity >= 0.8)
+                                .max_by(|a, b| a.1.total_cmp(&b.1))
+                                .map(|(l, similarity)| (l.clone(), similarity))
+                        })
+                };
+                if let Some((learned, similarity)) = learned {
+                    return Ok(json!({
+                        "description": args.description,
+                        "suggestion": {
+                            "id": learned.category_id,
+                            "name": learned.category_name,
+                            "source": "learned",
+                            "confidence": (similarity * 100.0).round() / 100.0,
+                            "times_confirmed": learned.times,
+                        },
+                    }));
+                }
+
+                // 2. A word in the description that is a known alias ("nafta", "super")
+                let alias = key
+                    .split(|c: char| !c.is_alphanumeric())
+                    .find_map(|word| self.category_alias(word));
+                let (ranked, source) = match alias {
+                    Some(category) => (self.rank_categories(category, false).await?, "alias"),
+                    // 3. The description itself against category names
+                    None => (self.rank_categories(&args.description, false).await?, "name_match"),
+                };
+
+                Ok(json!({
+                    "description": args.description,
+                    "suggestion": resolve::best(&ranked).map(|c| json!({
+                        "id": c.id,
+                        "name": c.name,
+                        "source": source,
+                        "confidence": c.confidence,
+                    })),
+                    "alternatives": ranked.iter().take(3).collect::<Vec<_>>(),
+                }))
+            }
+            "find_category" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    query: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let categories = self.cached_categories().await?;
+
+                // One entry per category or subcategory, each knowing its parent
+                let mut flat = BTreeMap::new();
+                for parent in categories.iter() {
+                    flat.insert(parent.id, json!({
+                        "id": parent.id,
+                        "name": parent.name,
+                        "parent_id": null,
+                        "parent_name": null,
+                        "path": parent.name,
+                    }));
+                    for sub in parent.subcategories.as_deref().unwrap_or(&[]) {
+                        flat.insert(sub.id, json!({
+                            "id": sub.id,
+                            "name": sub.name,
+                            "parent_id": parent.id,
+                            "parent_name": parent.name,
+                            "path": format!("{} > {}", parent.name, sub.name),
+                        }));
+                    }
+                }
+
+                let Some(query) = args.query else {
+                    return Ok(json!({ "categories": flat.into_values().collect::<Vec<_>>() }));
+                };
+                let ranked = self.rank_categories(&query, true).await?;
+                let with_parent = |c: &Candidate| {
+                    let mut entry = flat.get(&c.id).cloned().unwrap_or_else(|| json!({ "id": c.id, "name": c.name }));
+                    entry["confidence"] = json!(c.confidence);
+                    entry
+                };
+                Ok(json!({
+                    "query": query,
+                    "alias_for": self.category_alias(&query),
+                    "match": resolve::best(&ranked).map(with_parent),
+                    "candidates": ranked.iter().take(5).map(with_parent).collect::<Vec<_>>(),
+                }))
+            }
+            "get_categories" => {
+                let categories = self.client.get_categories().await?;
+                Ok(serde_json::to_value(categories)?)
+            }
+            "get_net_worth_of_balances" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    currency: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+
+                let (me, friends, groups) = tokio::try_join!(
+                    self.cached_me(),
+                    self.client.get_friends(),
+                    self.client.get_groups(),
+                )?;
+                let target = match args.currency {
+                    Some(currency) => currency.to_uppercase(),
+                    None => me
+                        .default_currency
+                        .clone()
+                        .context("The user has no default currency; pass 'currency' explicitly")?,
+                };
+
+                // Friend balances already include group debts, so they alone make up the net figure
+                let mut net = 0.0;
+                let mut by_friend = Vec::new();
+                for friend in &friends {
+                    let converted = self.convert_balances(&friend.balance, &target).await?;
+                    if converted.abs() < 0.005 {
+                        continue;
+                    }
+                    net += converted;
+                    by_friend.push(json!({
+                        "friend_id": friend.id,
+                        "name": format!("{} {}", friend.first_name, friend.last_name.as_deref().unwrap_or("")).trim(),
+                        "balances": friend.balance,
+                        "converted": format_amount(converted),
+                    }));
+                }
+
+                let mut by_group = Vec::new();
+                for group in &groups {
+                    let Some(member) = group.members.iter().find(|m| m.id == me.id) else {
+                        continue;
+                    };
+                    let converted = self.convert_balances(&member.balance, &target).await?;
+                    if converted.abs() < 0.005 {
+                        continue;
+                    }
+                    by_group.push(json!({
+                        "group_id": group.id,
+                        "name": group.name,
+                        "balances": member.balance,
+                        "converted": format_amount(converted),
+                    }));
+                }
+
+                Ok(json!({
+                    "currency": target,
+                    "net": format_amount(net),
+                    "provider": self.rates.provider().name(),
+                    "by_friend": by_friend,
+                    "by_group": by_group,
+                    "note": "by_group is the same money viewed per group; it is already included in the per-friend totals",
+                }))
+            }
+            "collections_list" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    currency: Option<String>,
+                    language: Option<String>,
+                    tone: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let language = args.language.as_deref().unwrap_or("en");
+                let tone = args.tone.as_deref().unwrap_or("friendly");
+                anyhow::ensure!(["en", "es"].contains(&language), "Unsupported language '{}'; use en or es", language);
+                anyhow::ensure!(
+                    ["friendly", "neutral", "firm"].contains(&tone),
+                    "Unsupported tone '{}'; use friendly, neutral or firm",
+                    tone
+                );
+                let (me, friends, groups) = tokio::try_join!(self.cached_me(), self.client.get_friends(), self.cached_groups())?;
+                let target = match args.currency {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let owed_only = |balances: &[Balance]| -> Vec<Balance> {
+                    balances.iter().filter(|b| b.amount.parse::<f64>().is_ok_and(|a| a >= 0.005)).cloned().collect()
+                };
+
+                let today = chrono::Local::now().date_naive();
+                let mut entries = Vec::new();
+                let mut unconverted = BTreeSet::new();
+                for friend in &friends {
+                    let owed = owed_only(&friend.balance);
+                    if owed.is_empty() {
+                        continue;
+                    }
+                    // A currency without a rate is left out of the ranking rather than failing the list
+                    let mut converted = 0.0;
+                    for balance in &owed {
+                        let amount: f64 = balance.amount.parse().unwrap_or(0.0);
+                        match self.rates.convert(amount, &balance.currency_code, &target).await {
+                            Ok(conversion) => converted += conversion.converted,
+                            Err(_) => {
+                                unconverted.insert(balance.currency_code.clone());
+                            }
+                        }
+                    }
+                    // Splitwise files debts outside any group under group 0
+                    let by_group: Vec<Value> = friend
+                        .groups
+                        .iter()
+                        .filter_map(|g| {
+                            let owed = owed_only(&g.balance);
+                            (!owed.is_empty()).then(|| json!({
+                                "group_id": (g.group_id != 0).then_some(g.group_id),
+                                "group_name": match g.group_id {
+                                    0 => "Non-group expenses".to_string(),
+                                    id => groups.iter().find(|group| group.id == id).map_or_else(|| format!("Group {}", id), |group| group.name.clone()),
+                                },
+                                "owed": owed,
+                            }))
+                        })
+                        .collect();
+                    let last_activity = chrono::DateTime::parse_from_rfc3339(&friend.updated_at)
+                        .ok()
+                        .map(|stamp| stamp.with_timezone(&chrono::Local).date_naive());
+                    let amounts: Vec<String> = owed.iter().map(|b| format!("{} {}", b.amount, b.currency_code)).collect();
+                    let amounts = amounts.join(if language == "es" { " y " } else { " and " });
+                    entries.push((converted, json!({
+                        "friend_id": friend.id,
+                        "name": full_name(&friend.first_name, friend.last_name.as_deref()),
+                        "owed": owed,
+                        "converted": format_amount(converted),
+                        "groups": by_group,
+                        "last_activity": last_activity.map(|d| d.to_string()),
+                        "days_since_activity": last_activity.map(|d| (today - d).num_days()),
+                        "reminder": friend_reminder(language, tone, &friend.first_name, &amounts),
+                    })));
+                }
+                entries.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+                let mut result = json!({
+                    "currency": target,
+                    "total": format_amount(entries.iter().fold(0.0, |total, (converted, _)| total + converted)),
+                    "count": entries.len(),
+                    "owed_to_you": entries.into_iter().map(|(_, entry)| entry).collect::<Vec<_>>(),
+                });
+                if !unconverted.is_empty() {
+                    result["note"] = json!(format!(
+                        "No exchange rate from {} to {}; those amounts are listed but left out of the ranking and the total",
+                        unconverted.into_iter().collect::<Vec<_>>().join(", "),
+                        target
+                    ));
+                }
+                Ok(result)
+            }
+            "payables_list" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    currency: Option<String>,
+                    add_to_plan: Option<bool>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let (me, friends) = tokio::try_join!(self.cached_me(), self.client.get_friends())?;
+                let target = match args.currency {
+                    Some(currency) => currency.trim().to_uppercase(),
+                    None => me.default_currency.clone().unwrap_or_else(|| "USD".to_string()),
+                };
+                let owing: Vec<&Friend> = friends
+                    .iter()
+                    .filter(|f| f.balance.iter().any(|b| b.amount.parse::<f64>().is_ok_and(|a| a <= -0.005)))
+                    .collect();
+
+                // How long each debt has been owed, from the history with that friend
+                let keep = |e: &Expense| e.deleted_at.is_none();
+                let histories = futures::future::try_join_all(owing.iter().map(|friend| {
+                    let params = ListExpensesParams { friend_id: Some(friend.id), ..Default::default() };
+                    self.scan_expenses(params, None, &keep)
+                }))
+                .await?;
+
+                let today = chrono::Local::now().date_naive();
+                let mut debts = Vec::new();
+                let mut unconverted = BTreeSet::new();
+                for (friend, expenses) in owing.iter().zip(histories) {
+                    let since_by_currency = balance_since(&expenses, me.id, friend.id);
+                    for balance in &friend.balance {
+                        let amount = -balance.amount.parse::<f64>().unwrap_or(0.0);
+                        if amount < 0.005 {
+                            continue;
+                        }
+                        // A currency without a rate ranks by its size alone, last in its age bucket
+                        let converted = match self.rates.convert(amount, &balance.currency_code, &target).await {
+                            Ok(conversion) => Some(conversion.converted),
+                            Err(_) => {
+                                unconverted.insert(balance.currency_code.clone());
+                                None
+                            }
+                        };
+                        let since = since_by_currency
+                            .get(&balance.currency_code)
+                            .and_then(|(_, since)| since.as_deref())
+                            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+                        let age = since.map(|d| (today - d).num_days());
+                        debts.push((age.map(age_bucket), converted, friend, balance.currency_code.clone(), amount, since, age));
+                    }
+                }
+                // Oldest bucket first, unknown ages last; the largest first within a bucket
+                let rank = |bucket: Option<&str>| match bucket {
+                    Some("60+") => 0,
+                    Some("30-60") => 1,
+                    Some(_) => 2,
+                    None => 3,
+                };
+                debts.sort_by(|a, b| {
+                    rank(a.0).cmp(&rank(b.0)).then(b.1.unwrap_or(f64::MIN).total_cmp(&a.1.unwrap_or(f64::MIN)))
+                });
+
+                // The plan keeps only debts that are still owed
+                let mut plan = self.settlement_plan.lock().unwrap();
+                let before = plan.len();
+                plan.retain(|key, _| debts.iter().any(|d| *key == format!("{}:{}", d.2.id, d.3)));
+                let mut added = 0;
+                if args.add_to_plan.unwrap_or(false) {
+                    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+                    for (_, _, friend, currency, amount, _, _) in &debts {
+                        let planned = PlannedPayment {
+                            friend_id: friend.id,
+                            name: full_name(&friend.first_name, friend.last_name.as_deref()),
+                            currency: currency.clone(),
+                            amount: format_amount(*amount),
+                            planned_at: now.clone(),
+                        };
+                        if plan.insert(format!("{}:{}", friend.id, currency), planned).is_none() {
+                            added += 1;
+                        }
+                    }
+                }
+                if plan.len() != before || added > 0 {
+                    self.store.save(SETTLEMENT_PLAN, &*plan)?;
+                }
+
+                let listed: Vec<Value> = debts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (bucket, converted, friend, currency, amount, since, age))| json!({
+                        "priority": i + 1,
+                        "friend_id": friend.id,
+                        "name": full_name(&friend.first_name, friend.last_name.as_deref()),
+                        "currency": currency,
+                        "amount": format_amount(*amount),
+                        "converted": converted.map(format_amount),
+                        "since": since.map(|d| d.to_string()),
+                        "age_days": age,
+                        "bucket": bucket,
+                        "planned_at": plan.get(&format!("{}:{}", friend.id, currency)).map(|p| p.planned_at.clone()),
+                    }))
+                    .collect();
+                let mut result = json!({
+                    "as_of": today.to_string(),
+                    "currency": target,
+                    "total": format_amount(debts.iter().filter_map(|d| d.1).fold(0.0, |total, converted| total + converted)),
+                    "count": listed.len(),
+                    "you_owe": listed,
+                    "plan": {
+                        "count": plan.len(),
+                        "added": added,
+                        "persisted": self.store.dir().is_some(),
+                        "payments": plan.values().collect::<Vec<_>>(),
+                    },
+                });
+                if !unconverted.is_empty() {
+                    result["note"] = json!(format!(
+                        "No exchange rate from {} to {}; those debts are left out of the total",
+                        unconverted.into_iter().collect::<Vec<_>>().join(", "),
+                        target
+                    ));
+                }
+                Ok(result)
+            }
+            "draft_reminder_message" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    friend_id: Option<i64>,
+                    user_name: Option<String>,
+                    group_id: Option<i64>,
+                    group_name: Option<String>,
+                    language: Option<String>,
+                    tone: Option<String>,
+                    settle_currency: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                let language = args.language.as_deref().unwrap_or("en");
+                let tone = args.tone.as_deref().unwrap_or("friendly");
+                if !["en", "es"].contains(&language) {
+                    anyhow::bail!("Unsupported language '{}'; use en or es", language);
+                }
+                if !["friendly", "neutral", "firm"].contains(&tone) {
+                    anyhow::bail!("Unsupported tone '{}'; use friendly, neutral or firm", tone);
+                }
+                let spanish = language == "es";
+
+                if let Some(group_id) = self.group_id_from(args.group_id, args.group_name.as_deref()).await? {
+                    let (me, group) = tokio::try_join!(self.cached_me(), self.client.get_group(group_id))?;
+                    // Follow the group's own setting for which debts Splitwise shows
+                    let debts = if group.simplify_by_default || group.original_debts.is_empty() {
+                        &group.simplified_debts
+                    } else {
+                        &group.original_debts
+                    };
+                    let name_of = |id: i64| {
+                        group
+                            .members
+                            .iter()
+                            .find(|m| m.id == id)
+                            .map_or_else(|| format!("user {}", id), |m| full_name(&m.first_name, m.last_name.as_deref()))
+                    };
+                    let pending: Vec<&Debt> = debts.iter().filter(|d| !is_zero_amount(&d.amount)).collect();
+                    let lines: Vec<String> = pending
+                        .iter()
+                        .map(|d| {
+                            let amount = format!("{} {}", d.amount, d.currency_code);
+                            let (from, to) = (name_of(d.from), name_of(d.to));
+                            match (spanish, d.from == me.id, d.to == me.id) {
+                                (false, true, _) => format!("- I owe {} {}", to, amount),
+                                (false, _, true) => format!("- {} owes me {}", from, amount),
+                                (false, _, _) => format!("- {} owes {} {}", from, to, amount),
+                                (true, true, _) => format!("- Le debo {} a {}", amount, to),
+                                (true, _, true) => format!("- {} me debe {}", from, amount),
+                                (true, _, _) => format!("- {} le debe {} a {}", from, amount, to),
+                            }
+                        })
+                        .collect();
+
+                    let message = (!lines.is_empty()).then(|| {
+                        let lines = lines.join("\n");
+                        match (language, tone) {
+                            ("es", "friendly") => format!("¡Hola a todos! Esto es lo que queda pendiente en {} en Splitwise:\n{}\nPueden saldarlo desde el grupo en Splitwise. ¡Gracias!", group.name, lines),
+                            ("es", "neutral") => format!("Saldos pendientes en {} en Splitwise:\n{}\nPor favor registren los pagos en Splitwise una vez hechos.", group.name, lines),
+                            ("es", _) => format!("Hola a todos, estos saldos de {} siguen pendientes:\n{}\nPor favor salden cuanto antes y registren el pago en Splitwise.", group.name, lines),
+                            (_, "friendly") => format!("Hi everyone! Here's what's still pending in {} on Splitwise:\n{}\nYou can settle up from the group in Splitwise. Thanks!", group.name, lines),
+                            (_, "neutral") => format!("Pending balances in {} on Splitwise:\n{}\nPlease record payments in Splitwise once they are made.", group.name, lines),
+                            _ => format!("Hi all, these balances in {} are still pending:\n{}\nPlease settle up as soon as possible and record the payment in Splitwise.", group.name, lines),
+                        }
+                    });
+
+                    return Ok(json!({
+                        "group_id": group.id,
+                        "group_name": group.name,
+                        "language": language,
+                        "tone": tone,
+                        "settle_up": pending.iter().map(|d| json!({
+                            "from": name_of(d.from),
+                            "to": name_of(d.to),
+                            "amount": d.amount,
+                            "currency_code": d.currency_code,
+                        })).collect::<Vec<_>>(),
+                        "message": message,
+                        "note": message.is_none().then_some("Everyone in this group is settled up"),
+                    }));
+                }
+
+                let friend_id = self
+                    .user_id_from(args.friend_id, args.user_name.as_deref())
+                    .await?
+                    .context("Provide a friend (friend_id or user_name) or a group (group_id or group_name)")?;
+                let friend = self.client.get_friend(friend_id).await?;
+                let name = full_name(&friend.first_name, friend.last_name.as_deref());
+
+                // Positive friend balances are money the friend owes the user
+                let owed: Vec<Balance> = friend
+                    .balance
+                    .iter()
+                    .filter(|b| b.amount.parse::<f64>().is_ok_and(|a| a >= 0.005))
+                    .cloned()
+                    .collect();
+                let you_owe: Vec<&Balance> = friend
+                    .balance
+                    .iter()
+                    .filter(|b| b.amount.parse::<f64>().is_ok_and(|a| a <= -0.005))
+                    .collect();
+                if owed.is_empty() {
+                    return Ok(json!({
+                        "friend_id": friend.id,
+                        "name": name,
+                        "message": null,
+                        "note": if you_owe.is_empty() {
+                            format!("{} and you are settled up", name)
+                        } else {
+                            format!("{} does not owe you anything; you owe them", name)
+                        },
+                        "you_owe": you_owe,
+                    }));
+                }
+
+                let amounts: Vec<String> = owed.iter().map(|b| format!("{} {}", b.amount, b.currency_code)).collect();
+                let mut amounts_text = amounts.join(if spanish { " y " } else { " and " });
+
+                // Offering a single converted payment is best effort; rates may be unavailable
+                let mut single_payment = None;
+                if let (Some(currency), true) = (args.settle_currency.as_deref(), owed.len() > 1) {
+                    let currency = currency.to_uppercase();
+                    if let Ok(total) = self.convert_balances(&owed, &currency).await {
+                        let total = format!("{} {}", format_amount(total), currency);
+                        amounts_text = if spanish {
+                            format!("{} (o {} en un solo pago)", amounts_text, total)
+                        } else {
+                            format!("{} (or {} in a single payment)", amounts_text, total)
+                        };
+                        single_payment = Some(total);
+                    }
+                }
+
+                let message = friend_reminder(language, tone, &friend.first_name, &amounts_text);
+
+                Ok(json!({
+                    "friend_id": friend.id,
+                    "name": name,
+                    "language": language,
+                    "tone": tone,
+                    "owed": owed,
+                    "single_payment": single_payment,
+                    "you_owe": you_owe,
+                    "message": message,
+                }))
+            }
+            "resolve_group" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    name: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                let ranked = self.rank_groups(&args.name).await?;
+                Ok(json!({
+                    "query": args.name,
+                    "match": resolve::best(&ranked),
+                    "candidates": ranked.iter().take(5).collect::<Vec<_>>(),
+                }))
+            }
+            "resolve_user" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    name: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                let ranked = self.rank_users(&args.name).await?;
+                Ok(json!({
+                    "query": args.name,
+                    "match": resolve::best(&ranked),
+                    "candidates": ranked.iter().take(5).collect::<Vec<_>>(),
+                }))
+            }
+            "job_status" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    job_id: Option<String>,
+                }
+                let args: Args = parse_args(arguments)?;
+                match args.job_id {
+                    Some(id) => self.jobs.status(&id),
+                    None => Ok(json!({ "jobs": self.jobs.list() })),
+                }
+            }
+            "job_result" | "cancel_job" => {
+                #[derive(Deserialize)]
+                struct Args {
+                    job_id: String,
+                }
+                let args: Args = parse_args(arguments)?;
+                if name == "job_result" {
+                    self.jobs.result(&args.job_id)
+                } else {
+                    self.jobs.cancel(&args.job_id)
+                }
+            }
+            "server_status" => {
+                let uptime = self.started_at.elapsed().as_secs();
+                Ok(json!({
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "transport": self.config.transport,
+                    "sandbox": self.client.is_sandbox(),
+                    "uptime_secs": uptime,
+                    "splitwise_api": self.client.stats(),
+                    "caches": {
+                        "exchange_rates": self.rates.cache_status(),
+                        "lookups": self.lookups.status(),
+                    },
+                    "local_store": {
+                        "dir": self.store.dir().map(|d| d.display().to_string()),
+                        "learned_categories": self.learned_categories.lock().unwrap().len(),
+                    },
+                    // sync_now's checkpoints; sync_status has the details
+                    "sync": {
+                        "checkpoints": self.sync_checkpoints.lock().unwrap().iter().map(|(id, checkpoint)| json!({
+                            "group_id": id,
+                            "updated_after": checkpoint.updated_after,
+                            "synced_at": checkpoint.synced_at,
+                        })).collect::<Vec<_>>(),
+                    },
+                }))
+            }
+            "convert_currency" => {
+                #[derive(Deserialize)]
+                struct AmountInput {
+                    amount: Value,
+                    currency_code: String,
+                }
+
+                #[derive(Deserialize)]
+                struct Args {
+                    amount: Option<Value>,
+                    from: Option<String>,
+                    to: Option<String>,
+                    date: Option<String>,
+                    amounts: Option<Vec<AmountInput>>,
+                }
+                let args: Args = parse_args(arguments)?;
+
+                let mut inputs = Vec::new();
+                if let Some(amount) = args.amount {
+                    let from = args.from.context("'from' is required when 'amount' is given")?;
+                    inputs.push((parse_amount(&amount)?, from));
+                }
+                for item in args.amounts.unwrap_or_default() {
+                    inputs.push((parse_amount(&item.amount)?, item.currency_code));
+                }
+                if inputs.is_empty() {
+                    anyhow::bail!("Provide either amount and from, or a non-empty amounts list");
+                }
+
+                let to = match args.to {
+                    Some(to) => to.to_uppercase(),
+                    None => self
+                        .cached_me()
+                        .await?
+                        .default_currency
+                        .clone()
+                        .context("No target currency given and the user has no default currency")?,
+                };
+
+                let mut total = 0.0;
+                let mut conversions = Vec::new();
+                for (amount, from) in inputs {
+                    let conversion = match args.date {
+                        Some(ref date) => self.rates.convert_on(amount, &from, &to, date).await?,
+                        None => self.rates.convert(amount, &from, &to).await?,
+                    };
+                    total += conversion.converted;
+                    conversions.push(json!({
+                        "amount": format_amount(amount),
+                        "from": from.to_uppercase(),
+                        "rate": conversion.rate,
+                        "rate_date": conversion.date,
+                        "converted": format_amount(conversion.converted),
+                    }));
+                }
+
+                Ok(json!({
+                    "to": to,
+                    "provider": self.rates.provider().name(),
+                    "conversions": conversions,
+                    "total": format_amount(total),
+                }))
+            }
+            _ => Err(InvalidParams(format!("Unknown tool: {}", name)).into()),
+        }
+    }
+}
+// search_text matching for list_expenses
+enum TextMatcher {
+    Substring(String),
+    Regex(Regex),
+    // Normalized query words and the minimum similarity to accept
+    Fuzzy(Vec<String>, f64),
+}
+
+impl TextMatcher {
+    fn new(text: &str, mode: Option<&str>, threshold: Option<f64>) -> Result<Self> {
+        match mode.unwrap_or("substring") {
+            "substring" => Ok(TextMatcher::Substring(text.to_lowercase())),
+            "regex" => Ok(TextMatcher::Regex(
+                RegexBuilder::new(text)
+                    .case_insensitive(true)
+                    .build()
+                    .with_context(|| format!("Invalid regex in search_text: {}", text))?,
+            )),
+            "fuzzy" => Ok(TextMatcher::Fuzzy(
+                nl_query::normalize(text).split_whitespace().map(str::to_string).collect(),
+                threshold.unwrap_or(0.75).clamp(0.0, 1.0),
+            )),
+            other => anyhow::bail!("Unknown search_mode '{}'; use substring, regex or fuzzy", other),
+        }
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        match self {
+            TextMatcher::Substring(needle) => haystack.to_lowercase().contains(needle),
+            TextMatcher::Regex(re) => re.is_match(haystack),
+            TextMatcher::Fuzzy(needle, threshold) => {
+                // Compare against every run of as many words as the query has
+                let words: Vec<String> = nl_query::normalize(haystack)
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|w| !w.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if needle.is_empty() || words.len() < needle.len() {
+                    return false;
+                }
+                let needle = needle.join(" ");
+                words
+                    .windows(needle.split(' ').count())
+                    .any(|window| strsim::normalized_levenshtein(&window.join(" "), &needle) >= *threshold)
+            }
+        }
+    }
+}
+
+// Store document holding learned description -> category mappings
+const LEARNED_CATEGORIES: &str = "learned_categories";
+// Store document holding each group's default split
+const SPLIT_PROFILES: &str = "split_profiles";
+// Store document holding each group's sync checkpoint and tombstones
+const SYNC_CHECKPOINTS: &str = "sync_checkpoints";
+// Store document holding the expense writes queued while Splitwise was unreachable
+const PENDING_WRITES: &str = "pending_writes";
+// Store document holding the debts the user planned to pay
+const SETTLEMENT_PLAN: &str = "settlement_plan";
+// Store document holding the user IDs that stand for another account
+const MEMBER_ALIASES: &str = "member_aliases";
+
+// Advice on using the tools, worked out from their schemas so it names only tools that exist and
+// follows their parameters as they change
+fn usage_guidance(tools: &[Value]) -> Vec<String> {
+    let names = |keep: &dyn Fn(&str, &Value) -> bool| -> Vec<String> {
+        tools
+            .iter()
+            .filter_map(|tool| Some((tool["name"].as_str()?, tool)))
+            .filter(|(name, tool)| keep(name, tool))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+    let takes = |tool: &Value, param: &str| tool["inputSchema"]["properties"].get(param).is_some();
+    let exists = |name: &str| !names(&|n, _| n == name).is_empty();
+
+    let mut guidance = Vec::new();
+    if exists("get_dashboard") {
+        guidance.push("Start with get_dashboard: it shows who the user is, their balances and recent expenses in one call.".to_string());
+    }
+    let categorized = names(&|name, tool| {
+        (name.starts_with("create_") || name.starts_with("update_")) && takes(tool, "category_id")
+    });
+    if exists("get_categories") && !categorized.is_empty() {
+        guidance.push(format!(
+            "Call get_categories before {} to pick the category; it decides the icon shown in Splitwise.",
+            categorized.join(" or ")
+        ));
+    }
+    let by_name = names(&|_, tool| takes(tool, "group_name") || takes(tool, "user_name"));
+    if !by_name.is_empty() {
+        guidance.push(format!(
+            "Groups and people can be given by name (group_name, user_name) in {} tools; IDs are only needed when a name is ambiguous.",
+            by_name.len()
+        ));
+    }
+    let projected = names(&|_, tool| takes(tool, "fields"));
+    if !projected.is_empty() {
+        guidance.push(format!("Pass fields to {} to get only the fields you need.", projected.join(", ")));
+    }
+    let confirmed = names(&|_, tool| takes(tool, "confirm"));
+    if !confirmed.is_empty() {
+        guidance.push(format!(
+            "{} only preview what they would do until called with confirm: true; show the user the preview and ask first.",
+            confirmed.join(", ")
+        ));
+    }
+    let destructive = names(&|name, tool| {
+        name.starts_with("delete_")
+            || name.starts_with("remove_")
+            || tool["description"].as_str().is_some_and(|d| d.contains("Destructive"))
+    });
+    if !destructive.is_empty() {
+        guidance.push(format!("{} remove data: confirm with the user before calling them.", destructive.join(", ")));
+    }
+    guidance
+}
+
+// Envelope for a page of list results, so callers can tell a full answer from a truncated one
+fn page_envelope(items: Vec<Value>, scanned: usize, next_offset: Option<usize>) -> Value {
+    json!({
+        "returned": items.len(),
+        "items": items,
+        "scanned": scanned,
+        "has_more": next_offset.is_some(),
+        "next_offset": next_offset,
+    })
+}
+
+// Add a side channel (`_trace`, `_warnings`) to a result, wrapping results that aren't objects
+fn attach(result: Value, key: &str, extra: Value) -> Value {
+    match result {
+        Value::Object(mut obj) => {
+            obj.insert(key.to_string(), extra);
+            Value::Object(obj)
+        }
+        other => json!({ "result": other, key: extra }),
+    }
+}
+
+const CONTEXT_URI: &str = "splitwise://context";
+
+// Recent expenses in get_dashboard, and the fields shown for each
+const DASHBOARD_EXPENSES: usize = 10;
+const DASHBOARD_EXPENSE_FIELDS: [&str; 7] =
+    ["id", "date", "description", "cost", "currency_code", "group_id", "my_net_balance"];
+
+// Tools that can change Splitwise data, some only when asked to (create, confirm, autosave);
+// read-only mode hides them all
+const WRITE_TOOLS: [&str; 23] = [
+    "create_group",
+    "tag_expense",
+    "untag_expense",
+    "monthly_close",
+    "apply_fixes",
+    "parse_statement",
+    "create_expense_from_text",
+    "match_transactions",
+    "mark_reimbursable",
+    "mark_reimbursed",
+    "flush_pending_writes",
+    "create_expense",
+    "calculate_household_split",
+    "quick_add_expense",
+    "split_with_friend",
+    "iou",
+    "dinner_split",
+    "update_expense",
+    "delete_expense",
+    "add_friend",
+    "remove_friend",
+    "find_stale_friendships",
+    "resend_invite",
+];
+
+// The job tools themselves, which make no sense inside a job
+const JOB_TOOLS: [&str; 4] = ["start_job", "job_status", "job_result", "cancel_job"];
+
+// Splitwise's catch-all category, where expenses nobody categorized end up
+const GENERAL_CATEGORY_ID: i64 = 18;
+
+// Tools apply_fixes may call
+const FIX_TOOLS: [&str; 2] = ["update_expense", "delete_expense"];
+// Currencies get_common_currencies tops its list up with, most used worldwide first
+const POPULAR_CURRENCIES: [&str; 12] = ["USD", "EUR", "GBP", "CAD", "AUD", "JPY", "CHF", "MXN", "BRL", "ARS", "INR", "CNY"];
+
+// How parse_statement asks the client's model to read a statement
+const STATEMENT_PROMPT: &str = "You extract expenses from bank statements and receipts for a bill-splitting app. \
+Answer with only a JSON array, one object per charge: {\"date\": \"YYYY-MM-DD\", \"description\": merchant or item, \
+\"amount\": positive number as printed, \"currency_code\": ISO code if shown, \"shared\": true if it looks like \
+a cost people usually split (groceries, restaurants, rent, utilities, travel), false for personal or financial lines \
+(salary, transfers, fees, card payments), \"reason\": a few words on why}. Leave out refunds, credits and balances. \
+For a receipt, give one object for the total, not one per item. Don't invent dates or amounts; answer [] if there are none.";
+
+// Checks audit_data_quality runs
+const AUDIT_CHECKS: [&str; 6] = [
+    "missing_category",
+    "zero_cost",
+    "shares_mismatch",
+    "orphaned_friendship",
+    "duplicate",
+    "currency_mismatch",
+];
+
+// Tools that page through the expense history; they advertise `timeout_ms`
+const SCANNING_TOOLS: [&str; 22] = [
+    "list_expenses",
+    "list_expenses_by_tag",
+    "summarize_expenses",
+    "compare_periods",
+    "contribution_report",
+    "category_drilldown",
+    "top_merchants",
+    "analyze_recurring_costs",
+    "project_cashflow",
+    "debt_aging",
+    "settlement_history",
+    "monthly_close",
+    "audit_data_quality",
+    "match_transactions",
+    "list_pending_reimbursements",
+    "list_recurring_expenses",
+    "pending_invitations",
+    "sync_now",
+    "export_ynab_csv",
+    "render_chart",
+    "spending_heatmap",
+    "payables_list",
+];
+
+// Tools whose results read well as rows; they advertise `output_format`
+const TABULAR_TOOLS: [&str; 27] = [
+    "get_dashboard",
+    "list_groups",
+    "list_expenses",
+    "list_expenses_by_tag",
+    "summarize_expenses",
+    "compare_periods",
+    "contribution_report",
+    "category_drilldown",
+    "top_merchants",
+    "analyze_recurring_costs",
+    "project_cashflow",
+    "debt_aging",
+    "audit_data_quality",
+    "settlement_history",
+    "list_pending_reimbursements",
+    "list_recurring_expenses",
+    "list_friends",
+    "find_stale_friendships",
+    "pending_invitations",
+    "get_currencies",
+    "get_categories",
+    "find_category",
+    "get_net_worth_of_balances",
+    "collections_list",
+    "payables_list",
+    "query_expenses_nl",
+    "get_export_result",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemberAlias {
+    // The account the alias stands for
+    user_id: i64,
+    name: String,
+    alias_name: String,
+    linked_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlannedPayment {
+    friend_id: i64,
+    name: String,
+    currency: String,
+    // What was owed when the debt was planned
+    amount: String,
+    planned_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LearnedCategory {
+    category_id: i64,
+    category_name: String,
+    times: u32,
+    last_learned: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SplitProfile {
+    group_name: String,
+    // The group's default split; empty when only some categories have one
+    shares: Vec<ProfileShare>,
+    // Category ID -> split of expenses in that category, or in any subcategory of a parent
+    #[serde(default)]
+    categories: BTreeMap<i64, CategorySplit>,
+    // Members who never owe (kids, guests), left out of the group's equal splits
+    #[serde(default)]
+    non_paying: Vec<ProfileMember>,
+    updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileMember {
+    user_id: i64,
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CategorySplit {
+    category_name: String,
+    shares: Vec<ProfileShare>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileShare {
+    user_id: i64,
+    name: String,
+    // Percent of each expense this member owes; a profile's shares add up to 100
+    percent: f64,
+}
+
+impl SplitProfile {
+    fn summary(&self, group_id: i64) -> Value {
+        json!({
+            "group_id": group_id,
+            "group_name": self.group_name,
+            "shares": (!self.shares.is_empty()).then(|| share_summary(&self.shares)),
+            "categories": self.categories.iter().map(|(id, split)| json!({
+                "category_id": id,
+                "category_name": split.category_name,
+                "shares": share_summary(&split.shares),
+            })).collect::<Vec<_>>(),
+            "non_paying": self.non_paying.iter().map(|m| json!({ "user_id": m.user_id, "name": m.name })).collect::<Vec<_>>(),
+            "updated_at": self.updated_at,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.shares.is_empty() && self.categories.is_empty() && self.non_paying.is_empty()
+    }
+
+    // A share of a non-paying member, in the default or any category's split
+    fn paying_conflict(&self) -> Option<(&ProfileMember, &str)> {
+        let splits = std::iter::once(("the group default", &self.shares))
+            .chain(self.categories.values().map(|split| (split.category_name.as_str(), &split.shares)));
+        for (rule, shares) in splits {
+            if let Some(member) = self
+                .non_paying
+                .iter()
+                .find(|m| shares.iter().any(|s| s.user_id == m.user_id && s.percent > 0.0))
+            {
+                return Some((member, rule));
+            }
+        }
+        None
+    }
+
+    // The rule that splits an expense in `category`: its own override, its parent's, or the
+    // group default; None when none applies
+    fn rule_for(&self, category: Option<(i64, Option<i64>)>) -> Option<ProfileRule<'_>> {
+        let (id, parent) = category.unzip();
+        let overridden = [id, parent.flatten()]
+            .into_iter()
+            .flatten()
+            .find_map(|id| self.categories.get(&id).map(|split| (id, split)));
+        match overridden {
+            Some((id, split)) => Some((Some((id, split)), &split.shares)),
+            None if !self.shares.is_empty() => Some((None, &self.shares)),
+            None => None,
+        }
+    }
+}
+
+// The category split that applied (None for the group default) and its shares
+type ProfileRule<'a> = (Option<(i64, &'a CategorySplit)>, &'a [ProfileShare]);
+
+fn share_summary(shares: &[ProfileShare]) -> Vec<Value> {
+    shares
+        .iter()
+        .map(|s| json!({ "user_id": s.user_id, "name": s.name, "percent": s.percent }))
+        .collect()
+}
+
+// A date range for compare_periods; the end is exclusive
+struct PeriodRange {
+    dated_after: Option<String>,
+    dated_before: Option<String>,
+    label: String,
+}
+
+impl PeriodRange {
+    fn describe(&self) -> Value {
+        json!({
+            "period": self.label,
+            "dated_after": self.dated_after,
+            "dated_before": self.dated_before,
+        })
+    }
+}
+
+// A period given as text ("last month", "2025-03") or as {dated_after, dated_before}
+fn period_range(value: &Value, today: chrono::NaiveDate) -> Result<PeriodRange> {
+    if let Some(text) = value.as_str() {
+        let query = nl_query::parse(text, today);
+        let label = query.period.with_context(|| format!("Couldn't read a period from '{}'", text))?;
+        return Ok(PeriodRange {
+            dated_after: query.dated_after,
+            dated_before: query.dated_before,
+            label,
+        });
+    }
+    let field = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let (dated_after, dated_before) = (field("dated_after"), field("dated_before"));
+    if dated_after.is_none() && dated_before.is_none() {
+        anyhow::bail!("A period is text such as 'last month' or an object with dated_after and/or dated_before");
+    }
+    let label = format!(
+        "{} to {}",
+        dated_after.as_deref().unwrap_or("the beginning"),
+        dated_before.as_deref().unwrap_or("now")
+    );
+    Ok(PeriodRange { dated_after, dated_before, label })
+}
+
+// (count, total cost, the user's share) in the first and the second period
+type PeriodSides = [(usize, f64, f64); 2];
+
+// Both periods side by side, with the change from the first
+fn period_comparison(sides: &PeriodSides) -> Value {
+    let [(first_count, first_total, first_share), (second_count, second_total, second_share)] = *sides;
+    // Percent change is undefined when the first period had nothing
+    let percent = |from: f64, to: f64| (from.abs() >= 0.005).then(|| format!("{:.1}", (to - from) / from * 100.0));
+    json!({
+        "first": { "count": first_count, "total_cost": format_amount(first_total), "my_share": format_amount(first_share) },
+        "second": { "count": second_count, "total_cost": format_amount(second_total), "my_share": format_amount(second_share) },
+        "change": format_amount(second_total - first_total),
+        "change_pct": percent(first_total, second_total),
+        "my_share_change": format_amount(second_share - first_share),
+        "my_share_change_pct": percent(first_share, second_share),
+    })
+}
+
+// Aliases file: a JSON object mapping words to Splitwise category names
+fn load_category_aliases(path: &str) -> Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read CATEGORY_ALIASES_FILE {}", path))?;
+    let aliases: HashMap<String, String> = serde_json::from_str(&text)
+        .with_context(|| format!("CATEGORY_ALIASES_FILE {} must be a JSON object of alias -> category name", path))?;
+    Ok(aliases
+        .into_iter()
+        .map(|(alias, category)| (nl_query::normalize(&alias).trim().to_string(), category))
+        .collect())
+}
+
+// The days charts cover, the last one excluded: the period given, or else the last 12 months
+// with this one
+fn chart_period(dated_after: Option<&str>, dated_before: Option<&str>) -> Result<(chrono::NaiveDate, chrono::NaiveDate)> {
+    let parse_date = |value: &str| {
+        chrono::NaiveDate::parse_from_str(value.get(..10).unwrap_or(value), "%Y-%m-%d")
+            .with_context(|| format!("Dates must be YYYY-MM-DD, not '{}'", value))
+    };
+    let today = chrono::Local::now().date_naive();
+    let from = match dated_after {
+        Some(date) => parse_date(date)?,
+        None => today.with_day(1).context("Invalid date")? - chrono::Months::new(11),
+    };
+    let to = match dated_before {
+        Some(date) => parse_date(date)?,
+        None => today + chrono::Days::new(1),
+    };
+    anyhow::ensure!(from < to, "dated_after must come before dated_before");
+    Ok((from, to))
+}
+
+// Date `steps` repetitions after `date`, or None for intervals that don't repeat
+fn occurrence_after(date: chrono::NaiveDate, interval: &str, steps: u32) -> Option<chrono::NaiveDate> {
+    match interval {
+        "weekly" => date.checked_add_days(chrono::Days::new(7 * steps as u64)),
+        "fortnightly" => date.checked_add_days(chrono::Days::new(14 * steps as u64)),
+        "monthly" => date.checked_add_months(chrono::Months::new(steps)),
+        "yearly" => date.checked_add_months(chrono::Months::new(steps.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+// Balance with a friend per currency (positive: they owe me) as the expense history adds up,
+// and the date it last crossed zero
+fn balance_since(expenses: &[Expense], me: i64, friend: i64) -> HashMap<String, (f64, Option<String>)> {
+    let mut ordered: Vec<&Expense> = expenses.iter().collect();
+    ordered.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+    let mut balances: HashMap<String, (f64, Option<String>)> = HashMap::new();
+    for expense in ordered {
+        let delta = pair_delta(expense, me, friend);
+        if delta.abs() < 0.005 {
+            continue;
+        }
+        let (balance, since) = balances.entry(expense.currency_code.clone()).or_default();
+        let before = *balance;
+        *balance += delta;
+        if balance.abs() < 0.005 {
+            *since = None;
+        } else if before.abs() < 0.005 || before.signum() != balance.signum() {
+            *since = Some(expense.date.chars().take(10).collect());
+        }
+    }
+    balances
+}
+
+// A reminder to a friend of what they owe, `amounts` already written out ("500.00 USD and 20.00 EUR")
+fn friend_reminder(language: &str, tone: &str, first: &str, amounts: &str) -> String {
+    let settle = if language == "es" {
+        "Cuando me pagues, regístralo como pago en Splitwise para que el saldo quede en cero."
+    } else {
+        "When you pay, record it as a payment in Splitwise so our balance goes back to zero."
+    };
+    match (language, tone) {
+        ("es", "friendly") => format!("¡Hola {}! Te recuerdo que en Splitwise me debes {}. {} ¡Gracias!", first, amounts, settle),
+        ("es", "neutral") => format!("Hola {}, según Splitwise me debes {}. {}", first, amounts, settle),
+        ("es", _) => format!("Hola {}, todavía me debes {} en Splitwise. Por favor salda la deuda cuanto antes. {}", first, amounts, settle),
+        (_, "friendly") => format!("Hi {}! Just a friendly reminder that you owe me {} on Splitwise. {} Thanks!", first, amounts, settle),
+        (_, "neutral") => format!("Hi {}, according to Splitwise you owe me {}. {}", first, amounts, settle),
+        _ => format!("Hi {}, you still owe me {} on Splitwise. Please settle up as soon as possible. {}", first, amounts, settle),
+    }
+}
+
+// Cadence of charges from the days between them: its name, how often it comes per year and the
+// typical gap. Most gaps must be near the typical one, so a stray extra charge doesn't hide a series.
+fn cadence_of(gaps: &[i64]) -> Option<(&'static str, f64, i64)> {
+    if gaps.is_empty() {
+        return None;
+    }
+    let gap = median(gaps.iter().map(|g| *g as f64).collect());
+    let steady = gaps.iter().filter(|g| (**g as f64 - gap).abs() <= gap * 0.3).count();
+    if steady * 4 < gaps.len() * 3 {
+        return None;
+    }
+    let (name, per_year) = match gap.round() as i64 {
+        5..=9 => ("weekly", 52.0),
+        12..=17 => ("fortnightly", 26.0),
+        25..=36 => ("monthly", 12.0),
+        85..=97 => ("quarterly", 4.0),
+        355..=375 => ("yearly", 1.0),
+        _ => return None,
+    };
+    Some((name, per_year, gap.round() as i64))
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+// Data problems in a set of expenses, each with the tool call that would fix it
+fn audit_expenses(expenses: &[Expense], me: i64, friends: &HashSet<i64>) -> Vec<Value> {
+    let mut issues = Vec::new();
+    let mut issue = |expense: &Expense, check: &str, problem: String, fix: &str, tool: &str, arguments: Value| {
+        issues.push(json!({
+            "check": check,
+            "expense_id": expense.id,
+            "date": expense.date.get(..10).unwrap_or(&expense.date),
+            "description": expense.description,
+            "cost": expense.cost,
+            "currency_code": expense.currency_code,
+            "group_id": expense.group_id,
+            "problem": problem,
+            "suggested_fix": { "action": fix, "tool": tool, "arguments": arguments },
+        }));
+    };
+
+    // The currency each group uses most is taken as its default
+    let mut currencies: HashMap<i64, BTreeMap<&str, usize>> = HashMap::new();
+    for expense in expenses.iter().filter(|e| !e.payment) {
+        if let Some(group_id) = expense.group_id.filter(|id| *id != 0) {
+            *currencies.entry(group_id).or_default().entry(&expense.currency_code).or_default() += 1;
+        }
+    }
+    let defaults: HashMap<i64, &str> = currencies
+        .iter()
+        .filter_map(|(group_id, counts)| Some((*group_id, *counts.iter().max_by_key(|(_, n)| **n)?.0)))
+        .collect();
+
+    let mut seen: HashMap<(String, String, String, String, Option<i64>), i64> = HashMap::new();
+    let mut ordered: Vec<&Expense> = expenses.iter().collect();
+    ordered.sort_by_key(|e| e.id);
+    for expense in ordered {
+        let id = json!({ "expense_id": expense.id });
+        let cost: f64 = expense.cost.parse().unwrap_or(0.0);
+        if cost.abs() < 0.005 {
+            issue(expense, "zero_cost", "The expense is for 0.00".to_string(), "Set the real cost, or delete it if it was a mistake", "delete_expense", id.clone());
+        }
+        let paid: f64 = expense.users.iter().map(|u| u.paid_share.parse::<f64>().unwrap_or(0.0)).sum();
+        let owed: f64 = expense.users.iter().map(|u| u.owed_share.parse::<f64>().unwrap_or(0.0)).sum();
+        // Each share may be up to half a cent off from rounding
+        let slack = 0.005 * expense.users.len() as f64 + 0.001;
+        if (paid - cost).abs() > slack || (owed - cost).abs() > slack {
+            issue(
+                expense,
+                "shares_mismatch",
+                format!("Paid shares add up to {} and owed shares to {}, but the cost is {}", format_amount(paid), format_amount(owed), expense.cost),
+                "Split it again equally, or give shares that add up to the cost",
+                "update_expense",
+                json!({ "expense_id": expense.id, "split_equally": true }),
+            );
+        }
+        if expense.payment {
+            continue;
+        }
+        if expense.category.id == GENERAL_CATEGORY_ID || expense.category.name.is_empty() {
+            issue(expense, "missing_category", "The expense has no category (General)".to_string(), "Pick a category with suggest_category, then set it with update_expense", "suggest_category", json!({ "description": expense.description }));
+        }
+        let group_id = expense.group_id.filter(|id| *id != 0);
+        if group_id.is_none() {
+            let strangers: Vec<String> = expense
+                .users
+                .iter()
+                .filter(|u| u.user_id != me && !friends.contains(&u.user_id))
+                .map(|u| u.user.as_ref().map_or_else(|| format!("User {}", u.user_id), |user| full_name(&user.first_name, user.last_name.as_deref())))
+                .collect();
+            if !strangers.is_empty() {
+                issue(expense, "orphaned_friendship", format!("Shared outside a group with {}, who is no longer a friend", strangers.join(", ")), "Add them back as a friend, or move the expense into a group you share", "add_friend", json!({ "user_name": strangers[0] }));
+            }
+        }
+        let key = (
+            nl_query::normalize(&expense.description).trim().to_string(),
+            expense.cost.clone(),
+            expense.currency_code.clone(),
+            expense.date.chars().take(10).collect(),
+            group_id,
+        );
+        match seen.get(&key) {
+            Some(original) => issue(expense, "duplicate", format!("Same description, amount, currency and date as expense {}", original), "Delete it if it was entered twice", "delete_expense", id.clone()),
+            None => {
+                seen.insert(key, expense.id);
+            }
+        }
+        if let Some(default) = group_id.and_then(|g| defaults.get(&g)).filter(|c| **c != expense.currency_code) {
+            issue(expense, "currency_mismatch", format!("In {} while the group mostly uses {}", expense.currency_code, default), "Change the currency if it was entered wrong; convert_currency gives the amount", "update_expense", json!({ "expense_id": expense.id, "currency_code": default }));
+        }
+    }
+    issues
+}
+
+// update_expense arguments recording `expense` as `cost` in `currency`, with every share scaled
+// to match so the split stays the same
+fn converted_expense(expense: &Expense, cost: f64, currency: &str) -> Value {
+    let weights = |pick: fn(&ExpenseUser) -> &str| -> Vec<f64> { expense.users.iter().map(|u| pick(u).parse().unwrap_or(0.0)).collect() };
+    let scaled = |weights: Vec<f64>| split_by_weights(cost, &weights).unwrap_or_else(|| vec![0.0; weights.len()]);
+    let paid = scaled(weights(|u| &u.paid_share));
+    let owed = scaled(weights(|u| &u.owed_share));
+    let shares: Vec<Value> = expense
+        .users
+        .iter()
+        .zip(paid.iter().zip(&owed))
+        .map(|(user, (paid, owed))| json!({ "user_id": user.user_id, "paid_share": format_amount(*paid), "owed_share": format_amount(*owed) }))
+        .collect();
+    json!({
+        "expense_id": expense.id,
+        "cost": format_amount(cost),
+        "currency_code": currency,
+        "split_by_shares": shares,
+    })
+}
+
+// The tool call behind one apply_fixes item, or why it can't run without a decision
+fn fix_call(fix: &Value) -> std::result::Result<(String, Value), String> {
+    let suggested = fix.get("suggested_fix").unwrap_or(fix);
+    let expense_id = fix
+        .get("expense_id")
+        .or_else(|| suggested.get("arguments").and_then(|a| a.get("expense_id")))
+        .and_then(Value::as_i64)
+        .ok_or("No expense_id")?;
+    if let Some(set) = fix.get("set") {
+        let mut arguments = set.as_object().cloned().ok_or("`set` must be an object of update_expense fields")?;
+        arguments.insert("expense_id".to_string(), json!(expense_id));
+        return Ok(("update_expense".to_string(), Value::Object(arguments)));
+    }
+    let tool = suggested.get("tool").and_then(Value::as_str).unwrap_or_default();
+    if !FIX_TOOLS.contains(&tool) {
+        let action = suggested.get("action").and_then(Value::as_str).unwrap_or("This fix needs a decision");
+        return Err(format!("{}. Give `set` with the fields to update, e.g. {{\"category_name\": \"...\"}}", action));
+    }
+    let mut arguments = suggested.get("arguments").cloned().unwrap_or_else(|| json!({}));
+    if arguments.get("expense_id").and_then(Value::as_i64).is_some_and(|id| id != expense_id) {
+        return Err(format!("The arguments are for another expense than {}", expense_id));
+    }
+    arguments["expense_id"] = json!(expense_id);
+    Ok((tool.to_string(), arguments))
+}
+
+// The JSON in a model's reply, which may be wrapped in prose or a code fence
+fn json_in(reply: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(reply.trim()) {
+        return Some(value);
+    }
+    ['[', '{'].iter().zip([']', '}']).find_map(|(open, close)| {
+        let start = reply.find(*open)?;
+        let end = reply.rfind(close)?;
+        serde_json::from_str(reply.get(start..=end)?).ok()
+    })
+}
+
+// Every number written in a text, read with either decimal separator ("1.234,50" and "1,234.50")
+fn amounts_in(text: &str) -> Vec<f64> {
+    let numbers = Regex::new(r"\d[\d.,]*").unwrap();
+    numbers
+        .find_iter(text)
+        .flat_map(|m| {
+            let token = m.as_str().trim_end_matches(['.', ',']);
+            [token.replace(',', ""), token.replace('.', "").replace(',', ".")]
+        })
+        .filter_map(|n| n.parse().ok())
+        .collect()
+}
+
+// One expense the model found in a statement, with what's wrong with it. `source` is the text
+// it was read from, where its amount must appear
+fn statement_candidate(item: &Value, source: Option<&str>, currency: &str, today: chrono::NaiveDate) -> Value {
+    let mut problems = Vec::new();
+    let description = item["description"].as_str().unwrap_or_default().trim();
+    if description.is_empty() {
+        problems.push("No description".to_string());
+    }
+    let amount = parse_amount(&item["amount"]).ok().filter(|a| a.is_finite());
+    match amount {
+        Some(amount) if amount <= 0.0 => problems.push("The amount isn't positive".to_string()),
+        Some(amount) if source.is_some_and(|text| !amounts_in(text).iter().any(|a| (a - amount).abs() < 0.005)) => {
+            problems.push(format!("{} doesn't appear in the text", format_amount(amount)))
+        }
+        Some(_) => {}
+        None => problems.push("No amount".to_string()),
+    }
+    let date = item["date"].as_str().and_then(|d| chrono::NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok());
+    match date {
+        Some(date) if date > today => problems.push(format!("{} is in the future", date)),
+        Some(_) => {}
+        None => problems.push("No date, or not a YYYY-MM-DD one".to_string()),
+    }
+    let currency_code = match item["currency_code"].as_str().map(|c| c.trim().to_uppercase()) {
+        Some(code) if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) => code,
+        _ => currency.to_string(),
+    };
+    json!({
+        "date": date.map(|d| d.to_string()),
+        "description": description,
+        "amount": amount.map(format_amount),
+        "currency_code": currency_code,
+        "shared": item["shared"].as_bool().unwrap_or(true),
+        "reason": item.get("reason"),
+        "valid": problems.is_empty(),
+        "problems": problems,
+    })
+}
+
+// How much an expense moves what `friend` owes `me` (negative: what I owe them)
+fn pair_delta(expense: &Expense, me: i64, friend: i64) -> f64 {
+    expense
+        .repayments
+        .iter()
+        .map(|r| match (r.from, r.to) {
+            (from, to) if from == friend && to == me => r.amount.parse().unwrap_or(0.0),
+            (from, to) if from == me && to == friend => -r.amount.parse::<f64>().unwrap_or(0.0),
+            _ => 0.0,
+        })
+        .sum()
+}
+
+fn age_bucket(days: i64) -> &'static str {
+    match days {
+        ..=30 => "0-30",
+        31..=60 => "30-60",
+        _ => "60+",
+    }
+}
+
+// How many times a month an interval comes round on average
+fn per_month(interval: &str) -> f64 {
+    match interval {
+        "weekly" => 52.0 / 12.0,
+        "fortnightly" => 26.0 / 12.0,
+        "monthly" => 1.0,
+        "yearly" => 1.0 / 12.0,
+        _ => 0.0,
+    }
+}
+
+// Field changes from a Splitwise system comment such as
+// "Ana G. updated this transaction:\n- cost changed from “1450.00” to “1500.00”"
+fn parse_change_lines(content: &str) -> Vec<Value> {
+    let quoted = Regex::new(r"^\s*-?\s*(.+?) changed from “(.*)” to “(.*)”\s*$").unwrap();
+    let plain = Regex::new(r"^\s*-?\s*(.+?) changed from (.*) to (.*?)\s*$").unwrap();
+    content
+        .lines()
+        .filter_map(|line| quoted.captures(line).or_else(|| plain.captures(line)))
+        .map(|c| json!({ "field": &c[1], "from": &c[2], "to": &c[3] }))
+        .collect()
+}
+
+// Split entry as tools accept it: a user is given by ID, email, name, or first/last name
+#[derive(Deserialize)]
+struct ShareInput {
+    user_id: Option<i64>,
+    user_name: Option<String>,
+    email: Option<String>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    paid_share: String,
+    owed_share: String,
+}
+
+fn owed_only(user_id: i64, owed: f64) -> ExpenseShare {
+    ExpenseShare {
+        user_id: Some(user_id),
+        email: None,
+        first_name: None,
+        last_name: None,
+        paid_share: format_amount(0.0),
+        owed_share: format_amount(owed),
+    }
+}
+
+// A person given as a user ID, an email address or a name
+fn share_for(person: &Value, paid_share: String, owed_share: String) -> Result<ShareInput> {
+    let mut share = ShareInput {
+        user_id: None,
+        user_name: None,
+        email: None,
+        first_name: None,
+        last_name: None,
+        paid_share,
+        owed_share,
+    };
+    match person {
+        Value::Number(n) => share.user_id = Some(n.as_i64().context("Invalid user ID")?),
+        Value::String(s) if s.contains('@') => share.email = Some(s.trim().to_string()),
+        Value::String(s) => share.user_name = Some(s.clone()),
+        _ => anyhow::bail!("Expected a user ID, email or name, got {}", person),
+    }
+    Ok(share)
+}
+
+fn same_person(a: &ExpenseShare, b: &ExpenseShare) -> bool {
+    match (a.user_id, b.user_id, &a.email, &b.email) {
+        (Some(a), Some(b), _, _) => a == b,
+        (None, None, Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
+
+// Who stayed when, for prorate_by_attendance
+#[derive(Deserialize)]
+struct ProrateInput {
+    attendance: Vec<AttendanceInput>,
+    paid_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AttendanceInput {
+    user_id: Option<i64>,
+    user_name: Option<String>,
+    arrival: String,
+    departure: String,
+}
+
+// "jane.doe@example.com" -> ("Jane", Some("Doe")), for inviting someone by email alone
+fn name_from_email(email: &str) -> (String, Option<String>) {
+    let local = email.split('@').next().unwrap_or(email);
+    let mut parts = local
+        .split(['.', '_', '-', '+'])
+        .filter(|p| !p.is_empty() && !p.chars().all(|c| c.is_ascii_digit()))
+        .map(|p| {
+            let mut chars = p.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        });
+    let first = parts.next().unwrap_or_else(|| local.to_string());
+    let rest: Vec<String> = parts.collect();
+    (first, (!rest.is_empty()).then(|| rest.join(" ")))
+}
+
+// Names that refer to the current user when resolving user_name
+const ME: [&str; 4] = ["me", "i", "myself", "yo"];
+
+fn full_name(first_name: &str, last_name: Option<&str>) -> String {
+    format!("{} {}", first_name, last_name.unwrap_or("")).trim().to_string()
+}
+
+// Turn ranked candidates into a single ID, or explain why the name is not specific enough
+fn pick(kind: &str, name: &str, ranked: &[Candidate]) -> Result<i64> {
+    if let Some(found) = resolve::best(ranked) {
+        return Ok(found.id);
+    }
+    let options: Vec<String> = ranked
+        .iter()
+        .take(5)
+        .map(|c| format!("{} (id {}, confidence {:.2})", c.name, c.id, c.confidence))
+        .collect();
+    if options.is_empty() {
+        anyhow::bail!("No {} matches '{}'", kind, name)
+    }
+    anyhow::bail!("'{}' does not identify a single {}. Candidates: {}", name, kind, options.join(", "))
+}
+
+// Split `total` in proportion to `weights`, rounded to cents with the leftover cents going to
+// the largest remainders so the shares always add up exactly. A negative total (a refund) is
+// split like its positive amount. None if the weights sum to zero or anything is out of range.
+fn split_by_weights(total: f64, weights: &[f64]) -> Option<Vec<f64>> {
+    let sum: f64 = weights.iter().sum();
+    if !total.is_finite() || total.abs() > MAX_AMOUNT || !sum.is_finite() || sum <= 0.0 || weights.iter().any(|w| *w < 0.0) {
+        return None;
+    }
+    let total_cents = (total * 100.0).round() as i64;
+    let sign = total_cents.signum();
+    let magnitude = total_cents.abs();
+    let exact: Vec<f64> = weights.iter().map(|w| magnitude as f64 * w / sum).collect();
+    let mut cents: Vec<i64> = exact.iter().map(|e| e.floor() as i64).collect();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by(|a, b| (exact[*b] - exact[*b].floor()).total_cmp(&(exact[*a] - exact[*a].floor())));
+    // Flooring leaves fewer than one cent per share; the clamp only guards against rounding surprises
+    let leftover = (magnitude - cents.iter().sum::<i64>()).clamp(0, weights.len() as i64);
+    for i in order.into_iter().cycle().take(leftover as usize) {
+        cents[i] += 1;
+    }
+    Some(cents.into_iter().map(|c| (sign * c) as f64 / 100.0).collect())
+}
+
+// An expense reduced to the requested fields. `category` is just its id and name, `deleted_at`
+// and `deleted_by` only appear on deleted expenses, and `tags`/`metadata` come from the notes.
+fn project_expense(expense: &Expense, me: i64, fields: &[String]) -> Value {
+    let mut value = serde_json::to_value(expense).unwrap_or_default();
+    let metadata = metadata::split(expense.details.as_deref()).1;
+    value["category"] = json!({ "id": expense.category.id, "name": expense.category.name });
+    value["tags"] = json!(metadata.tags);
+    value["metadata"] = json!(metadata);
+    let mine = relative_to_me(expense, me);
+    // Flat versions for the most common question, "what did I pay / owe"
+    value["my_paid_share"] = mine["paid_share"].clone();
+    value["my_owed_share"] = mine["owed_share"].clone();
+    value["my_net_balance"] = mine["net_balance"].clone();
+    value["relative_to_me"] = mine;
+    value["participants"] = json!(participants(expense, me));
+    let mut projected = projection::project(&value, fields);
+    if let Value::Object(ref mut obj) = projected {
+        obj.retain(|key, value| !(key.starts_with("deleted_") && value.is_null()));
+    }
+    projected
+}
+
+// The current user's side of an expense, so callers need not find themselves in `users`
+fn relative_to_me(expense: &Expense, me: i64) -> Value {
+    match expense.users.iter().find(|u| u.user_id == me) {
+        Some(share) => json!({
+            "involved": true,
+            "paid_share": share.paid_share,
+            "owed_share": share.owed_share,
+            "net_balance": share.net_balance,
+        }),
+        None => json!({
+            "involved": false,
+            "paid_share": "0.00",
+            "owed_share": "0.00",
+            "net_balance": "0.00",
+        }),
+    }
+}
+
+// "Marcos paid 30.00, owes 10.00; Ana owes 10.00", naming people as Splitwise sends them
+fn participants(expense: &Expense, me: i64) -> String {
+    expense
+        .users
+        .iter()
+        .filter_map(|share| {
+            let name = match share.user {
+                Some(ref user) => user.first_name.clone(),
+                None => format!("User {}", share.user_id),
+            };
+            let name = if share.user_id == me { format!("{} (you)", name) } else { name };
+            let paid = (!is_zero_amount(&share.paid_share)).then(|| format!("paid {}", share.paid_share));
+            let owes = (!is_zero_amount(&share.owed_share)).then(|| format!("owes {}", share.owed_share));
+            let parts: Vec<String> = paid.into_iter().chain(owes).collect();
+            (!parts.is_empty()).then(|| format!("{} {}", name, parts.join(", ")))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+// What `user_id` owes of an expense; 0 when they are not part of it
+fn owed_share_of(expense: &Expense, user_id: i64) -> f64 {
+    expense
+        .users
+        .iter()
+        .find(|u| u.user_id == user_id)
+        .and_then(|u| u.owed_share.parse().ok())
+        .unwrap_or(0.0)
+}
+
+// What `user_id` paid of an expense; 0 when they are not part of it
+fn paid_share_of(expense: &Expense, user_id: i64) -> f64 {
+    expense
+        .users
+        .iter()
+        .find(|u| u.user_id == user_id)
+        .and_then(|u| u.paid_share.parse().ok())
+        .unwrap_or(0.0)
+}
+
+// Splitwise amounts are decimal strings; anything under half a cent counts as settled
+fn is_zero_amount(amount: &str) -> bool {
+    amount.parse::<f64>().is_ok_and(|a| a.abs() < 0.005)
+}
+
+// A tool's arguments; anything missing or of the wrong type is the caller's mistake
+fn parse_args<T: serde::de::DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| InvalidParams(format!("Invalid arguments: {}", e)).into())
+}
+
+// Largest amount a tool accepts: far beyond any real expense, and small enough to count in cents
+const MAX_AMOUNT: f64 = 1e12;
+
+// Tool arguments may carry amounts either as JSON numbers or as decimal strings
+fn parse_amount(value: &Value) -> Result<f64> {
+    let amount = match value {
+        Value::Number(n) => n.as_f64().context("Invalid amount")?,
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .with_context(|| format!("Invalid amount: {}", s))?,
+        _ => anyhow::bail!("Invalid amount: {}", value),
+    };
+    if !amount.is_finite() || amount.abs() > MAX_AMOUNT {
+        anyhow::bail!("Invalid amount: {} is not a number between -{} and {}", value, MAX_AMOUNT, MAX_AMOUNT);
+    }
+    Ok(amount)
+}
+
+fn format_amount(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer;
+
+    fn sandbox_tools() -> SplitwiseTools {
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        SplitwiseTools::new(Arc::new(SplitwiseClient::sandbox()), config).unwrap()
+    }
+
+    // Field names listed in a tool's `fields` description, without the parenthesized notes
+    fn documented_fields(tools: &SplitwiseTools, tool: &str, marker: &str) -> Vec<String> {
+        let schema = tools
+            .get_tools()
+            .into_iter()
+            .find(|t| t["name"] == tool)
+            .unwrap();
+        let description = schema["inputSchema"]["properties"]["fields"]["description"].as_str().unwrap();
+        let listed = description.split(marker).nth(1).unwrap();
+        let listed = listed.split(". ").next().unwrap();
+        Regex::new(r"\s*\([^)]*\)")
+            .unwrap()
+            .replace_all(listed, "")
+            .split(',')
+            .map(|f| f.trim().to_string())
+            .collect()
+    }
+
+    // A deleted expense, so that deleted_at and deleted_by are present too
+    async fn deleted_expense(tools: &SplitwiseTools) -> Expense {
+        tools
+            .client
+            .get_expenses(ListExpensesParams::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|e| e.deleted_at.is_some())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_documented_get_expense_field_is_returned() {
+        let tools = sandbox_tools();
+        let expense = deleted_expense(&tools).await;
+        let fields = documented_fields(&tools, "get_expense", "Available: ");
+        assert!(fields.len() > 30, "parsed {:?}", fields);
+
+        let result = tools
+            .dispatch_tool("get_expense", json!({ "expense_id": expense.id, "fields": fields }))
+            .await
+            .unwrap();
+        for field in &fields {
+            assert!(result.get(field).is_some(), "get_expense did not return '{}'", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn every_documented_list_expenses_field_is_returned() {
+        let tools = sandbox_tools();
+        let expense = deleted_expense(&tools).await;
+        let fields = documented_fields(&tools, "list_expenses", "All available: ");
+        assert!(fields.len() > 30, "parsed {:?}", fields);
+
+        let result = tools
+            .dispatch_tool("list_expenses", json!({ "include_deleted": "only", "fields": fields }))
+            .await
+            .unwrap();
+        let listed = result["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["id"] == expense.id)
+            .expect("deleted expense listed");
+        for field in &fields {
+            assert!(listed.get(field).is_some(), "list_expenses did not return '{}'", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn list_expenses_pages_follow_next_offset_to_the_end() {
+        let tools = sandbox_tools();
+        let all = tools
+            .dispatch_tool("list_expenses", json!({ "limit": 0, "include_deleted": "include", "fields": ["id"] }))
+            .await
+            .unwrap();
+        assert_eq!(all["has_more"], false);
+        let total = all["returned"].as_u64().unwrap();
+
+        // Deleted expenses are filtered locally, so pages are assembled from batches
+        let mut seen = Vec::new();
+        let mut offset = json!(0);
+        loop {
+            let page = tools
+                .dispatch_tool("list_expenses", json!({ "limit": 4, "offset": offset, "fields": ["id", "deleted_at"] }))
+                .await
+                .unwrap();
+            let items = page["items"].as_array().unwrap();
+            assert_eq!(page["returned"], items.len());
+            assert!(items.iter().all(|e| e.get("deleted_at").is_none()));
+            seen.extend(items.iter().map(|e| e["id"].clone()));
+            if page["has_more"] == false {
+                assert!(page["next_offset"].is_null());
+                break;
+            }
+            assert_eq!(items.len(), 4);
+            offset = page["next_offset"].clone();
+        }
+        let unique: std::collections::HashSet<_> = seen.iter().map(|id| id.as_i64()).collect();
+        assert_eq!(unique.len(), seen.len());
+        assert!(seen.len() < total as usize, "the sandbox has a deleted expense");
+    }
+
+    #[tokio::test]
+    async fn several_groups_and_personal_expenses_are_listed_together() {
+        let tools = sandbox_tools();
+        let ids = |page: &Value| page["items"].as_array().unwrap().iter().map(|e| e["id"].as_i64().unwrap()).collect::<Vec<_>>();
+        let mut expected = Vec::new();
+        for group_id in [2001, 2002] {
+            let page = tools.dispatch_tool("list_expenses", json!({ "group_id": group_id, "limit": 0, "fields": ["id"] })).await.unwrap();
+            expected.extend(ids(&page));
+        }
+        let everything = tools.dispatch_tool("list_expenses", json!({ "limit": 0, "fields": ["id", "group_id"] })).await.unwrap();
+        let personal: Vec<i64> = everything["items"].as_array().unwrap().iter().filter(|e| e["group_id"].is_null()).map(|e| e["id"].as_i64().unwrap()).collect();
+        assert!(!personal.is_empty());
+
+        let arguments = json!({ "group_ids": [2001, 2002], "include_personal": true, "limit": 0, "fields": ["id", "group_id", "date"] });
+        let merged = tools.dispatch_tool("list_expenses", arguments).await.unwrap();
+        let items = merged["items"].as_array().unwrap();
+        let dates: Vec<&str> = items.iter().map(|e| e["date"].as_str().unwrap()).collect();
+        assert!(dates.windows(2).all(|pair| pair[0] >= pair[1]), "newest first across groups");
+        let mut found = ids(&merged);
+        found.sort();
+        expected.extend(&personal);
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // Without include_personal only the groups are listed, and pages follow next_offset
+        let mut paged = Vec::new();
+        let mut offset = json!(0);
+        loop {
+            let arguments = json!({ "group_ids": [2001, 2002], "limit": 7, "offset": offset, "fields": ["id"] });
+            let page = tools.dispatch_tool("list_expenses", arguments).await.unwrap();
+            paged.extend(ids(&page));
+            if page["has_more"] == false {
+                break;
+            }
+            offset = page["next_offset"].clone();
+        }
+        paged.sort();
+        assert_eq!(paged, expected.into_iter().filter(|id| !personal.contains(id)).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn explain_expense_says_who_paid_and_who_owes_whom() {
+        let tools = sandbox_tools();
+        let all = tools.dispatch_tool("list_expenses", json!({ "limit": 0, "fields": ["id", "description"] })).await.unwrap();
+        let id_of = |description: &str| all["items"].as_array().unwrap().iter().find(|e| e["description"] == description).unwrap()["id"].clone();
+
+        // Ana paid for the cleaning supplies and split them with me
+        let explained = tools.dispatch_tool("explain_expense", json!({ "expense_id": id_of("Cleaning supplies") })).await.unwrap();
+        let summary = explained["summary"].as_str().unwrap();
+        assert!(summary.starts_with("Cleaning supplies: 23.40 USD"), "{}", summary);
+        assert!(summary.contains(" in Casa. Ana García paid 23.40 USD, split equally 2 ways."), "{}", summary);
+        assert_eq!(explained["deleted"], false);
+        let ana = explained["members"].as_array().unwrap().iter().find(|m| m["user_id"] == 101).unwrap();
+        assert_eq!(ana["effect"], "is owed 11.70 USD");
+        assert_eq!(explained["you"]["effect"], "You owe 11.70 USD in total");
+        assert_eq!(explained["who_pays_whom"][0]["text"], "You owe Ana García 11.70 USD");
+
+        // The other way round for the concert tickets I paid for Jamie
+        let explained = tools.dispatch_tool("explain_expense", json!({ "expense_id": id_of("Concert tickets") })).await.unwrap();
+        assert!(explained["summary"].as_str().unwrap().contains("You paid 180.00 USD"));
+        assert_eq!(explained["you"]["effect"], "You are owed 90.00 USD in total");
+        assert_eq!(explained["who_pays_whom"][0]["text"], "Jamie Lee owes you 90.00 USD");
+    }
+
+    #[tokio::test]
+    async fn find_category_resolves_everyday_words_and_lists_the_tree() {
+        let tools = sandbox_tools();
+        let found = tools.dispatch_tool("find_category", json!({ "query": "nafta" })).await.unwrap();
+        assert_eq!(found["alias_for"], "Gas/fuel");
+        assert_eq!(found["match"]["id"], 33);
+        assert_eq!(found["match"]["path"], "Transportation > Gas/fuel");
+        assert_eq!(found["match"]["parent_id"], 31);
+
+        let found = tools.dispatch_tool("find_category", json!({ "query": "groceries" })).await.unwrap();
+        assert_eq!(found["match"]["id"], 12);
+        assert_eq!(found["match"]["parent_name"], "Food and drink");
+        assert!(found["candidates"].as_array().unwrap().len() <= 5);
+
+        // Without a query, every category comes back once with its parent
+        let all = tools.dispatch_tool("find_category", json!({})).await.unwrap();
+        let categories = all["categories"].as_array().unwrap();
+        let rent = categories.iter().find(|c| c["id"] == 3).unwrap();
+        assert_eq!(rent["path"], "Home > Rent");
+        let home = categories.iter().find(|c| c["id"] == 27).unwrap();
+        assert!(home["parent_id"].is_null());
+        assert_eq!(categories.iter().filter(|c| c["name"] == "Other").count(), 6);
+    }
+
+    #[tokio::test]
+    async fn reminders_quote_live_balances_in_the_chosen_language() {
+        let tools = sandbox_tools();
+        let jamie = tools.client.get_friend(103).await.unwrap();
+        let owed: Vec<&Balance> = jamie.balance.iter().filter(|b| b.amount.parse::<f64>().unwrap() > 0.0).collect();
+        assert_eq!(owed.len(), 2, "Jamie owes in USD and ARS from the trip");
+        let amounts: Vec<String> = owed.iter().map(|b| format!("{} {}", b.amount, b.currency_code)).collect();
+
+        let draft = tools.dispatch_tool("draft_reminder_message", json!({ "user_name": "Jamie" })).await.unwrap();
+        assert_eq!(draft["friend_id"], 103);
+        assert_eq!(draft["owed"].as_array().unwrap().len(), 2);
+        let message = draft["message"].as_str().unwrap();
+        assert!(message.contains("Jamie") && message.contains(&amounts.join(" and ")), "{}", message);
+
+        let draft = tools.dispatch_tool("draft_reminder_message", json!({ "friend_id": 103, "language": "es", "tone": "firm" })).await.unwrap();
+        let spanish = draft["message"].as_str().unwrap();
+        assert_ne!(spanish, message);
+        assert!(spanish.contains(&amounts.join(" y ")), "{}", spanish);
+
+        // A group reminder lists every pending debt, naming me in the first person
+        let draft = tools.dispatch_tool("draft_reminder_message", json!({ "group_name": "Casa" })).await.unwrap();
+        let settle_up = draft["settle_up"].as_array().unwrap();
+        assert!(!settle_up.is_empty());
+        let message = draft["message"].as_str().unwrap();
+        assert!(message.starts_with("Hi everyone! Here's what's still pending in Casa"), "{}", message);
+        assert_eq!(message.lines().filter(|l| l.starts_with("- ")).count(), settle_up.len());
+        assert!(message.contains("owes me") || message.contains("I owe"), "{}", message);
+
+        let error = tools.dispatch_tool("draft_reminder_message", json!({ "friend_id": 103, "tone": "angry" })).await.unwrap_err();
+        assert!(error.to_string().contains("Unsupported tone 'angry'"));
+    }
+
+    #[tokio::test]
+    async fn group_and_friend_tools_return_only_the_requested_fields() {
+        let tools = sandbox_tools();
+        let keys = |value: &Value| value.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+
+        let groups = tools.dispatch_tool("list_groups", json!({ "fields": ["id", "name", "members.first_name"] })).await.unwrap();
+        let casa = groups["items"].as_array().unwrap().iter().find(|g| g["id"] == 2001).unwrap();
+        assert_eq!(keys(casa), ["id", "members", "name"]);
+        assert_eq!(casa["name"], "Casa");
+        let members = casa["members"].as_array().unwrap();
+        assert_eq!(members.len(), 3);
+        assert!(members.iter().all(|m| keys(m) == ["first_name"]));
+
+        let group = tools.dispatch_tool("get_group", json!({ "group_name": "Bariloche", "fields": ["name", "simplified_debts.amount"] })).await.unwrap();
+        assert_eq!(keys(&group), ["name", "simplified_debts"]);
+        assert!(group["simplified_debts"].as_array().unwrap().iter().all(|d| keys(d) == ["amount"]));
+
+        let friends = tools.dispatch_tool("list_friends", json!({ "fields": ["id", "balance.currency_code", "nickname"] })).await.unwrap();
+        let items = friends["items"].as_array().unwrap();
+        assert_eq!(friends["returned"], items.len());
+        assert!(items.iter().all(|f| !f.as_object().unwrap().contains_key("nickname") && !f.as_object().unwrap().contains_key("first_name")));
+
+        let friend = tools.dispatch_tool("get_friend", json!({ "user_name": "Sam", "fields": ["first_name", "balance"] })).await.unwrap();
+        assert_eq!(keys(&friend), ["balance", "first_name"]);
+        assert_eq!(friend["first_name"], "Sam");
+
+        // Without fields, the whole friend comes back
+        let friend = tools.dispatch_tool("get_friend", json!({ "friend_id": 105 })).await.unwrap();
+        assert!(friend.get("last_name").is_some() && friend.get("id").is_some());
+    }
+
+    #[test]
+    fn expense_projection_trims_category_and_hides_unset_deletion() {
+        let expense: Expense = serde_json::from_value(json!({
+            "id": 1, "group_id": null, "friendship_id": null, "expense_bundle_id": null,
+            "description": "Rent", "repeats": false, "repeat_interval": null,
+            "email_reminder": null, "email_reminder_in_advance": null, "next_repeat": null,
+            "details": "paid by transfer\n\n[mcp] {\"tags\":[\"home\"]}", "comments_count": 0,
+            "payment": false, "creation_method": null, "transaction_method": null,
+            "transaction_confirmed": false, "transaction_id": null, "transaction_status": null,
+            "cost": "100.00", "currency_code": "USD", "repayments": [], "date": "2025-01-01",
+            "created_at": "2025-01-01", "created_by": { "id": 1, "first_name": "A", "last_name": null, "picture": null },
+            "updated_at": "2025-01-01", "updated_by": null, "deleted_at": null, "deleted_by": null,
+            "category": { "id": 3, "name": "Rent", "icon": "rent.png", "subcategories": null },
+            "receipt": { "original": null, "large": null },
+            "users": [{ "user_id": 1, "user": null, "paid_share": "100.00", "owed_share": "50.00", "net_balance": "50.00" }],
+        }))
+        .unwrap();
+        let fields: Vec<String> = ["category", "deleted_at", "tags", "users.owed_share", "relative_to_me.net_balance"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            project_expense(&expense, 1, &fields),
+            json!({
+                "category": { "id": 3, "name": "Rent" },
+                "tags": ["home"],
+                "users": [{ "owed_share": "50.00" }],
+                "relative_to_me": { "net_balance": "50.00" },
+            })
+        );
+        assert_eq!(project_expense(&expense, 2, &fields)["relative_to_me"]["net_balance"], "0.00");
+
+        let mine: Vec<String> = ["my_paid_share", "my_owed_share", "my_net_balance"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            project_expense(&expense, 1, &mine),
+            json!({ "my_paid_share": "100.00", "my_owed_share": "50.00", "my_net_balance": "50.00" })
+        );
+    }
+
+    #[tokio::test]
+    async fn dashboard_shows_balances_and_recent_expenses() {
+        let tools = Arc::new(sandbox_tools());
+        let dashboard = tools.handle_tool_call("get_dashboard", None).await.unwrap();
+        let me = tools.cached_me().await.unwrap();
+        assert_eq!(dashboard["me"]["id"], me.id);
+
+        let groups = dashboard["groups"].as_array().unwrap();
+        assert!(!groups.is_empty());
+        assert!(groups.iter().all(|g| g["balance"].is_array() && g.get("members").is_none()));
+        let friends = dashboard["friends_with_balance"].as_array().unwrap();
+        assert!(friends.iter().all(|f| !f["balance"].as_array().unwrap().is_empty()));
+
+        let expenses = dashboard["recent_expenses"].as_array().unwrap();
+        assert!(!expenses.is_empty() && expenses.len() <= DASHBOARD_EXPENSES);
+        assert!(expenses.iter().all(|e| e.get("my_net_balance").is_some() && e.get("users").is_none()));
+    }
+
+    #[tokio::test]
+    async fn server_status_reports_calls_caches_errors_and_checkpoints() {
+        let tools = sandbox_tools();
+        let status = tools.dispatch_tool("server_status", json!({})).await.unwrap();
+        assert_eq!((&status["sandbox"], &status["splitwise_api"]["calls"]), (&json!(true), &json!(0)));
+        assert_eq!(status["caches"]["lookups"]["me"]["cached"], false);
+        assert!(status["splitwise_api"]["last_error"].is_null());
+
+        tools.cached_me().await.unwrap();
+        tools.dispatch_tool("sync_now", json!({ "group_id": 2001 })).await.unwrap();
+        let sandbox = tools.client.sandbox_state().unwrap();
+        sandbox.unavailable.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(tools.client.get_expense(1).await.is_err());
+        sandbox.unavailable.store(false, std::sync::atomic::Ordering::SeqCst);
+
+        let status = tools.dispatch_tool("server_status", json!({})).await.unwrap();
+        let api = &status["splitwise_api"];
+        assert!(api["calls"].as_u64().unwrap() >= 3 && api["errors"].as_u64().unwrap() >= 1, "{}", api);
+        assert!(api["last_error"]["message"].as_str().unwrap().contains("503"), "{}", api);
+        assert_eq!(status["caches"]["lookups"]["me"]["cached"], true);
+        let checkpoint = &status["sync"]["checkpoints"][0];
+        assert_eq!(checkpoint["group_id"], 2001);
+        assert!(checkpoint["synced_at"].is_string() && checkpoint["updated_after"].is_string());
+    }
+
+    #[tokio::test]
+    async fn context_resource_is_cached_until_an_expense_changes() {
+        let tools = sandbox_tools();
+        assert_eq!(tools.get_resources()[0]["uri"], CONTEXT_URI);
+        assert!(tools.read_resource("splitwise://nope").await.is_err());
+
+        let read = tools.read_resource(CONTEXT_URI).await.unwrap();
+        let snapshot: Value = serde_json::from_str(read["contents"][0]["text"].as_str().unwrap()).unwrap();
+        assert!(snapshot["recent_expenses"].is_array());
+        assert_eq!(tools.lookups.status()["dashboard"]["cached"], true);
+
+        let id = snapshot["recent_expenses"][0]["id"].clone();
+        tools.dispatch_tool("delete_expense", json!({ "expense_id": id })).await.unwrap();
+        assert_eq!(tools.lookups.status()["dashboard"]["cached"], false);
+    }
+
+    #[tokio::test]
+    async fn compare_periods_reports_changes_per_category() {
+        let tools = sandbox_tools();
+        let everything = json!({ "dated_after": "2000-01-01" });
+        let result = tools
+            .dispatch_tool("compare_periods", json!({ "first": { "dated_before": "2000-01-01" }, "second": everything }))
+            .await
+            .unwrap();
+        let totals = result["totals"].as_array().unwrap();
+        assert!(!totals.is_empty());
+        // Nothing before 2000, so every change is the whole second period and has no percentage
+        for row in totals.iter().chain(result["categories"].as_array().unwrap()) {
+            assert_eq!(row["first"]["count"], 0);
+            assert_eq!(row["change"], row["second"]["total_cost"]);
+            assert!(row["change_pct"].is_null());
+        }
+        let changes: Vec<f64> = result["categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["change"].as_str().unwrap().parse::<f64>().unwrap().abs())
+            .collect();
+        assert!(changes.windows(2).all(|w| w[0] >= w[1]));
+
+        let months = period_range(&json!("2025-03"), chrono::NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()).unwrap();
+        assert_eq!((months.dated_after.as_deref(), months.dated_before.as_deref()), (Some("2025-03-01"), Some("2025-04-01")));
+        assert!(period_range(&json!("whenever"), chrono::NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn contributions_balance_out_across_members() {
+        let tools = sandbox_tools();
+        let group = &tools.client.get_groups().await.unwrap()[0];
+        let result = tools
+            .dispatch_tool("contribution_report", json!({ "group_id": group.id, "period_by": "month" }))
+            .await
+            .unwrap();
+        let members = result["members"].as_array().unwrap();
+        assert!(!members.is_empty());
+        let amount = |row: &Value, key: &str| row[key].as_str().unwrap().parse::<f64>().unwrap();
+        // What everyone paid is what everyone consumed, so net contributions sum to zero, give
+        // or take a cent per expense where the fixture shares don't add up exactly
+        let net: f64 = members.iter().map(|m| amount(m, "net_contribution")).sum();
+        let slack = 0.01 * result["expense_count"].as_f64().unwrap() + 0.005;
+        assert!(net.abs() <= slack, "net contributions sum to {}", net);
+        assert!(members.windows(2).all(|w| w[0]["currency"] != w[1]["currency"] || amount(&w[0], "paid") >= amount(&w[1], "paid")));
+        assert!(result["by_period"].as_array().unwrap().iter().all(|r| r["period"].as_str().unwrap().len() == 7));
+    }
+
+    #[tokio::test]
+    async fn drilldown_of_a_parent_category_covers_its_subcategories() {
+        let tools = sandbox_tools();
+        let categories = tools.cached_categories().await.unwrap();
+        let food = categories.iter().find(|c| c.name == "Food and drink").unwrap();
+        let result = tools
+            .dispatch_tool("category_drilldown", json!({ "category_id": food.id, "top": 2 }))
+            .await
+            .unwrap();
+        assert_eq!(result["category"], "Food and drink");
+        let subcategories: Vec<&str> = result["subcategories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["subcategory"].as_str().unwrap())
+            .collect();
+        assert!(subcategories.contains(&"Groceries") && subcategories.contains(&"Dining out"));
+
+        let largest = result["largest"].as_array().unwrap();
+        assert_eq!(largest.len(), 2);
+        let cost = |e: &Value| e["cost"].as_str().unwrap().parse::<f64>().unwrap();
+        assert!(cost(&largest[0]) >= cost(&largest[1]));
+        assert!(result["top_merchants"].as_array().unwrap().len() <= 2);
+        let monthly: usize = result["monthly"].as_array().unwrap().iter().map(|m| m["count"].as_u64().unwrap() as usize).sum();
+        assert_eq!(monthly, result["expense_count"].as_u64().unwrap() as usize);
+    }
+
+    #[tokio::test]
+    async fn top_merchants_groups_spellings_of_the_same_store() {
+        let tools = sandbox_tools();
+        let result = tools
+            .dispatch_tool("top_merchants", json!({ "category_names": ["Groceries"] }))
+            .await
+            .unwrap();
+        let merchants = result["merchants"].as_array().unwrap();
+        let dia = merchants.iter().find(|m| m["merchant"] == "Supermercado Día").unwrap();
+        assert_eq!(dia["count"], 2);
+        assert_eq!(dia["descriptions"], json!(["Supermercado Día"]));
+        let totals: Vec<f64> = merchants.iter().map(|m| m["total_cost"].as_str().unwrap().parse().unwrap()).collect();
+        assert!(totals.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[tokio::test]
+    async fn cashflow_projects_recurring_charges_and_category_averages() {
+        let tools = sandbox_tools();
+        let result = tools
+            .dispatch_tool("project_cashflow", json!({ "months": 2, "group_name": "casa" }))
+            .await
+            .unwrap();
+        let rent = result["recurring_expenses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["description"] == "Rent")
+            .unwrap();
+        assert_eq!(rent["dates"].as_array().unwrap().len(), 2);
+
+        // Rent is covered by the recurring series, so nothing more is estimated for it
+        let categories = result["categories"].as_array().unwrap();
+        let rent_category = categories.iter().find(|c| c["category"] == rent["category"]).unwrap();
+        assert_eq!(rent_category["estimated_per_month"], "0.00");
+
+        let months = result["months"].as_array().unwrap();
+        assert!(!months.is_empty());
+        for month in months {
+            let part = |key: &str| month[key].as_str().unwrap().parse::<f64>().unwrap();
+            assert!((part("recurring") + part("estimated") - part("total")).abs() < 0.01);
+            assert!(part("recurring") >= rent["my_share"].as_str().unwrap().parse::<f64>().unwrap());
+        }
+        assert!(tools.dispatch_tool("project_cashflow", json!({ "months": 0 })).await.is_err());
+    }
+
+    #[test]
+    fn debt_age_starts_when_the_balance_last_crossed_zero() {
+        let expense = |id: i64, date: &str, from: i64, to: i64, amount: &str| -> Expense {
+            serde_json::from_value(json!({
+                "id": id, "date": date, "currency_code": "USD",
+                "repayments": [{ "from": from, "to": to, "amount": amount }],
+            }))
+            .unwrap()
+        };
+        let history = [
+            expense(1, "2025-01-05T00:00:00Z", 2, 1, "20.00"),
+            // Settled, then the friend came to owe me again
+            expense(2, "2025-02-01T00:00:00Z", 1, 2, "20.00"),
+            expense(3, "2025-03-10T00:00:00Z", 2, 1, "15.00"),
+            expense(4, "2025-04-02T00:00:00Z", 2, 1, "5.00"),
+            // Something between other people leaves us alone
+            expense(5, "2025-04-03T00:00:00Z", 3, 2, "9.00"),
+        ];
+        let balances = balance_since(&history, 1, 2);
+        let (balance, since) = &balances["USD"];
+        assert!((balance - 20.0).abs() < 0.001);
+        assert_eq!(since.as_deref(), Some("2025-03-10"));
+
+        // Swinging to the other side restarts the clock
+        let balances = balance_since(&[history[2].clone(), expense(6, "2025-05-01T00:00:00Z", 1, 2, "40.00")], 1, 2);
+        assert_eq!(balances["USD"].1.as_deref(), Some("2025-05-01"));
+        assert_eq!([age_bucket(0), age_bucket(45), age_bucket(61)], ["0-30", "30-60", "60+"]);
+    }
+
+    #[tokio::test]
+    async fn settlement_history_lists_payments_with_the_balance_after() {
+        let tools = sandbox_tools();
+        let result = tools
+            .dispatch_tool("settlement_history", json!({ "user_name": "Ana" }))
+            .await
+            .unwrap();
+        let last = &result["last_paid_to_you"];
+        assert_eq!(last["from"], "Ana García");
+        assert_eq!(last["amount"], "300.00");
+        assert!(result["last_paid_by_you"].is_null());
+        assert_eq!(result["payments"].as_array().unwrap().len(), result["count"].as_u64().unwrap() as usize);
+
+        // The running balance ends where Splitwise's balance with the friend is
+        let friends = tools.client.get_friends().await.unwrap();
+        let ana = friends.iter().find(|f| f.first_name == "Ana").unwrap();
+        let usd = ana.balance.iter().find(|b| b.currency_code == "USD").unwrap();
+        let now = result["balances"].as_array().unwrap().iter().find(|b| b["currency"] == "USD").unwrap();
+        let amount = |text: &str| text.parse::<f64>().unwrap();
+        assert!((amount(now["amount"].as_str().unwrap()) - amount(&usd.amount)).abs() < 0.01);
+
+        assert!(tools.dispatch_tool("settlement_history", json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unmarked_monthly_charges_are_found_but_recurring_ones_are_not() {
+        let tools = sandbox_tools();
+        let result = tools.dispatch_tool("analyze_recurring_costs", json!({})).await.unwrap();
+        let series = result["series"].as_array().unwrap();
+        let netflix = series.iter().find(|s| s["merchant"] == "Netflix").unwrap();
+        assert_eq!(netflix["cadence"], "monthly");
+        assert_eq!(netflix["occurrences"], 4);
+        assert_eq!(netflix["annual_cost"], "185.88");
+        // Rent is already a recurring expense in Splitwise
+        assert!(series.iter().all(|s| s["merchant"] != "Rent"));
+
+        assert_eq!(cadence_of(&[7, 7, 8, 6]).map(|c| c.0), Some("weekly"));
+        assert_eq!(cadence_of(&[30, 31, 2, 29, 30]).map(|c| c.0), Some("monthly"));
+        assert_eq!(cadence_of(&[3, 40, 90, 12]), None);
+        assert_eq!(cadence_of(&[365, 366, 358]), Some(("yearly", 1.0, 365)));
+        assert_eq!(cadence_of(&[200, 365]), None);
+    }
+
+    #[tokio::test]
+    async fn yearly_charges_are_found_when_the_window_covers_them() {
+        let tools = sandbox_tools();
+        let today = chrono::Utc::now().date_naive();
+        for days_ago in [1100, 735, 368, 3] {
+            let date = (today - chrono::Days::new(days_ago)).to_string();
+            let arguments = json!({ "cost": "240.00", "description": "Home insurance", "date": date, "group_id": 2001 });
+            tools.dispatch_tool("create_expense", arguments).await.unwrap();
+        }
+
+        // The default year only holds one gap, too few for a series
+        let result = tools.dispatch_tool("analyze_recurring_costs", json!({})).await.unwrap();
+        assert!(result["series"].as_array().unwrap().iter().all(|s| s["merchant"] != "Home insurance"));
+
+        let dated_after = (today - chrono::Days::new(1200)).to_string();
+        let result = tools.dispatch_tool("analyze_recurring_costs", json!({ "dated_after": dated_after })).await.unwrap();
+        let insurance = result["series"].as_array().unwrap().iter().find(|s| s["merchant"] == "Home insurance").unwrap();
+        assert_eq!(insurance["cadence"], "yearly");
+        assert_eq!(insurance["occurrences"], 4);
+        assert_eq!(insurance["annual_cost"], "240.00");
+        assert_eq!(insurance["still_active"], true);
+    }
+
+    #[tokio::test]
+    async fn monthly_close_proposes_then_records_settlements() {
+        let tools = sandbox_tools();
+        let month = (chrono::Local::now().date_naive() - chrono::Months::new(1)).format("%Y-%m").to_string();
+        let args = json!({ "group_name": "casa", "month": month, "record_settlements": true });
+        let proposed = tools.dispatch_tool("monthly_close", args.clone()).await.unwrap();
+        assert!(proposed["confirmation_required"].is_string());
+        assert!(!proposed["settlements"].as_array().unwrap().is_empty());
+        let report = proposed["report_markdown"].as_str().unwrap();
+        assert!(report.starts_with(&format!("# Casa: monthly close for {}", month)));
+        assert!(report.contains("## Settle up"));
+
+        let mut confirmed = args;
+        confirmed["confirm"] = json!(true);
+        let closed = tools.dispatch_tool("monthly_close", confirmed).await.unwrap();
+        assert!(closed["error"].is_null());
+        assert!(closed["settlements"].as_array().unwrap().iter().all(|s| s["recorded_payment_id"].is_i64()));
+
+        // Once paid, there is nothing left to settle
+        let after = tools.dispatch_tool("monthly_close", json!({ "group_name": "casa", "month": month })).await.unwrap();
+        assert_eq!(after["settlements"], json!([]));
+    }
+
+    #[test]
+    fn audit_flags_each_kind_of_problem() {
+        let expense = |id: i64, description: &str, cost: &str, currency: &str, group_id: Option<i64>, category: i64, owed: &[(i64, &str)]| -> Expense {
+            serde_json::from_value(json!({
+                "id": id, "description": description, "cost": cost, "currency_code": currency,
+                "group_id": group_id, "date": "2025-03-01T10:00:00Z",
+                "category": { "id": category, "name": if category == 18 { "General" } else { "Groceries" } },
+                "users": owed.iter().enumerate().map(|(i, (user_id, share))| json!({
+                    "user_id": user_id,
+                    "paid_share": if i == 0 { cost } else { "0.00" },
+                    "owed_share": share,
+                })).collect::<Vec<_>>(),
+            }))
+            .unwrap()
+        };
+        let expenses = [
+            expense(1, "Groceries", "20.00", "USD", Some(7), 12, &[(1, "10.00"), (2, "10.00")]),
+            expense(2, "groceries ", "20.00", "USD", Some(7), 12, &[(1, "10.00"), (2, "10.00")]),
+            expense(3, "Taxi", "15.00", "USD", Some(7), 18, &[(1, "5.00"), (2, "5.00")]),
+            expense(4, "Dinner", "12.00", "EUR", Some(7), 12, &[(1, "6.00"), (2, "6.00")]),
+            expense(5, "Lunch", "0.00", "USD", None, 12, &[(1, "0.00"), (9, "0.00")]),
+        ];
+        let issues = audit_expenses(&expenses, 1, &HashSet::from([2]));
+        let found: Vec<(i64, &str)> = issues
+            .iter()
+            .map(|i| (i["expense_id"].as_i64().unwrap(), i["check"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, "duplicate"),
+                (3, "shares_mismatch"),
+                (3, "missing_category"),
+                (4, "currency_mismatch"),
+                (5, "zero_cost"),
+                (5, "orphaned_friendship"),
+            ]
+        );
+        assert_eq!(issues[3]["suggested_fix"]["arguments"]["currency_code"], "USD");
+    }
+
+    #[tokio::test]
+    async fn group_split_profile_is_used_when_no_split_is_given() {
+        let tools = sandbox_tools();
+        let shares = json!([{ "user_name": "me", "percent": 60 }, { "user_name": "Ana", "percent": 40 }]);
+        tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_name": "Casa", "shares": shares }))
+            .await
+            .unwrap();
+        let profile = tools.dispatch_tool("get_group_split_profile", json!({ "group_id": 2001 })).await.unwrap();
+        assert_eq!(profile["shares"][1]["percent"], 40.0);
+        // Groceries are halved, and anything under Home (Rent included) is 70/30
+        let halves = json!([{ "user_name": "me", "percent": 50 }, { "user_name": "Ana", "percent": 50 }]);
+        tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "category_name": "groceries", "shares": halves }))
+            .await
+            .unwrap();
+        let home = json!([{ "user_id": 100, "percent": 70 }, { "user_id": 101, "percent": 30 }]);
+        let set = tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "category_id": 27, "shares": home }))
+            .await
+            .unwrap();
+        assert_eq!(set["profile"]["categories"].as_array().unwrap().len(), 2);
+        assert_eq!(set["profile"]["shares"][0]["percent"], 60.0);
+
+        let owes = |created: &Value| {
+            created["split"].as_array().unwrap().iter().map(|u| u["owes"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        let groceries = json!({ "cost": "50.00", "description": "Groceries", "group_id": 2001 });
+        let created = tools.dispatch_tool("create_expense", groceries.clone()).await.unwrap();
+        assert_eq!(owes(&created), ["30.00", "20.00"]);
+        assert_eq!(created["split_profile"]["rule"], "group");
+        let mut categorized = groceries.clone();
+        categorized["category_id"] = json!(12);
+        let created = tools.dispatch_tool("create_expense", categorized).await.unwrap();
+        assert_eq!(owes(&created), ["25.00", "25.00"]);
+        assert_eq!(created["split_profile"]["category_name"], "Groceries");
+        let rent = json!({ "cost": "1000", "description": "Rent", "group_id": 2001, "category_name": "rent" });
+        let created = tools.dispatch_tool("create_expense", rent).await.unwrap();
+        assert_eq!(owes(&created), ["700.00", "300.00"]);
+        assert_eq!(created["split_profile"]["category_id"], 27);
+        let mut equal = groceries.clone();
+        equal["split_equally"] = json!(true);
+        let created = tools.dispatch_tool("create_expense", equal).await.unwrap();
+        assert_eq!(owes(&created).len(), 3);
+        assert!(created.get("split_profile").is_none());
+
+        let uneven = json!({ "group_id": 2001, "shares": [{ "user_id": 100, "percent": 50 }, { "user_id": 101, "percent": 40 }] });
+        let error = tools.dispatch_tool("set_group_split_profile", uneven).await.unwrap_err();
+        assert!(error.to_string().contains("add up to 90"), "{}", error);
+        let outsider = json!({ "group_id": 2001, "shares": [{ "user_id": 100, "percent": 50 }, { "user_id": 103, "percent": 50 }] });
+        assert!(tools.dispatch_tool("set_group_split_profile", outsider).await.is_err());
+
+        let cleared = tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "category_id": 12, "clear": true }))
+            .await
+            .unwrap();
+        assert_eq!(cleared["profile"]["categories"][0]["category_name"], "Home");
+        tools.dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "clear": true })).await.unwrap();
+        let all = tools.dispatch_tool("get_group_split_profile", json!({})).await.unwrap();
+        assert_eq!(all["profiles"], json!([]));
+        let created = tools.dispatch_tool("create_expense", groceries).await.unwrap();
+        assert_eq!(owes(&created).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn category_split_wins_over_the_group_rule_until_cleared() {
+        let tools = sandbox_tools();
+        let default = json!([{ "user_name": "me", "percent": 60 }, { "user_name": "Ana", "percent": 40 }]);
+        tools.dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "shares": default })).await.unwrap();
+        let halves = json!([{ "user_id": 100, "percent": 50 }, { "user_id": 101, "percent": 50 }]);
+        let arguments = json!({ "group_id": 2001, "category_id": 12, "shares": halves });
+        tools.dispatch_tool("set_group_split_profile", arguments).await.unwrap();
+
+        let owes = |created: &Value| {
+            created["split"].as_array().unwrap().iter().map(|u| u["owes"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        let groceries = json!({ "cost": "80.00", "description": "Groceries", "group_id": 2001, "category_id": 12 });
+        let created = tools.dispatch_tool("create_expense", groceries.clone()).await.unwrap();
+        assert_eq!(owes(&created), ["40.00", "40.00"]);
+        assert_eq!((&created["split_profile"]["rule"], &created["split_profile"]["category_id"]), (&json!("category"), &json!(12)));
+
+        let arguments = json!({ "group_id": 2001, "category_id": 12, "clear": true });
+        let cleared = tools.dispatch_tool("set_group_split_profile", arguments).await.unwrap();
+        assert_eq!((&cleared["cleared"], &cleared["profile"]["categories"]), (&json!(true), &json!([])));
+        let created = tools.dispatch_tool("create_expense", groceries).await.unwrap();
+        assert_eq!(owes(&created), ["48.00", "32.00"]);
+        assert_eq!(created["split_profile"]["rule"], "group");
+    }
+
+    #[tokio::test]
+    async fn non_paying_members_are_left_out_of_equal_splits() {
+        let tools = sandbox_tools();
+        tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_name": "Casa", "non_paying": ["Marcos"] }))
+            .await
+            .unwrap();
+        let owed = |created: &Value| {
+            created["split"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|u| u["owes"] != "0.00")
+                .map(|u| u["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let pizza = json!({ "cost": "30.00", "description": "Pizza", "group_id": 2001, "split_equally": true });
+        let created = tools.dispatch_tool("create_expense", pizza).await.unwrap();
+        assert_eq!(created["split"][0]["owes"], "15.00");
+        assert!(!owed(&created).contains(&"Marcos".to_string()));
+        assert!(created.get("warnings").is_none());
+
+        let among = json!({ "cost": "30.00", "description": "Cinema", "group_id": 2001, "split_among": ["me", "Marcos"] });
+        let created = tools.dispatch_tool("create_expense", among).await.unwrap();
+        assert!(owed(&created).contains(&"Marcos".to_string()));
+        assert!(created["warnings"][0].as_str().unwrap().starts_with("Marcos Núñez is marked as non-paying"));
+
+        let shares = json!([{ "user_id": 100, "percent": 50 }, { "user_id": 102, "percent": 50 }]);
+        let error = tools
+            .dispatch_tool("set_group_split_profile", json!({ "group_id": 2001, "shares": shares }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("non-paying"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn receipt_text_is_proposed_then_created_on_confirm() {
+        let tools = sandbox_tools();
+        let text = "From: Uber Receipts <noreply@uber.com>\nDate: Sat, May 3, 2025 at 11:02 PM\n\nTrip fare  €18.40\nBooking fee €2.10\nTotal €20.50";
+        let proposed = tools.dispatch_tool("create_expense_from_text", json!({ "text": text })).await.unwrap();
+        assert_eq!(proposed["proposal"]["cost"], "20.50");
+        assert_eq!(proposed["proposal"]["currency_code"], "EUR");
+        assert_eq!(proposed["proposal"]["description"], "Uber");
+        assert_eq!(proposed["proposal"]["evidence"]["cost"], "Total €20.50");
+        assert!(proposed.get("created").is_none());
+
+        let error = tools
+            .dispatch_tool("create_expense_from_text", json!({ "text": text, "confirm": true }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("who shares"), "{}", error);
+        let confirmed = json!({ "text": text, "confirm": true, "group_name": "Casa", "description": "Uber to the airport" });
+        let created = tools.dispatch_tool("create_expense_from_text", confirmed).await.unwrap();
+        assert_eq!(created["proposal"]["confidence"]["description"], 1.0);
+        let expense = tools.client.get_expense(created["created"]["id"].as_i64().unwrap()).await.unwrap();
+        assert_eq!(expense.cost, "20.50");
+        assert_eq!(expense.currency_code, "EUR");
+        assert_eq!(expense.description, "Uber to the airport");
+        assert!(expense.date.starts_with("2025-05-03"));
+    }
+
+    #[tokio::test]
+    async fn bank_lines_are_matched_to_what_i_paid() {
+        let tools = sandbox_tools();
+        let mut ids = Vec::new();
+        for (date, cost, description) in [("2024-03-02", "42.10", "Groceries Carrefour"), ("2024-03-10", "60.00", "Dinner")] {
+            let arguments = json!({ "cost": cost, "description": description, "date": date, "currency_code": "CHF", "group_id": 2001 });
+            ids.push(tools.dispatch_tool("create_expense", arguments).await.unwrap()["id"].clone());
+        }
+        let transactions = json!([
+            { "date": "2024-03-04", "amount": "-42.10", "description": "CARREFOUR 22 GENEVE" },
+            { "date": "2024-03-07", "amount": 15, "description": "PHARMACY" },
+        ]);
+        let result = tools
+            .dispatch_tool("match_transactions", json!({ "transactions": transactions, "currency_code": "chf" }))
+            .await
+            .unwrap();
+        assert_eq!(result["counts"]["matched"], 1);
+        assert_eq!(result["matched"][0]["expense"]["id"], ids[0]);
+        assert_eq!(result["matched"][0]["days_apart"], 2);
+        assert_eq!(result["unmatched_transactions"][0]["description"], "PHARMACY");
+        // Dinner falls after the last transaction
+        assert_eq!(result["counts"]["unmatched_expenses"], 0);
+
+        let arguments = json!({ "transactions": transactions, "currency_code": "CHF", "dated_before": "2024-03-31", "date_window_days": 1 });
+        let result = tools.dispatch_tool("match_transactions", arguments).await.unwrap();
+        assert_eq!(result["counts"]["matched"], 0);
+        let unmatched: Vec<&Value> = result["unmatched_expenses"].as_array().unwrap().iter().map(|e| &e["id"]).collect();
+        assert_eq!(unmatched, vec![&ids[1]]);
+
+        // A window too wide to add to a date is capped instead
+        let arguments = json!({ "transactions": transactions, "currency_code": "CHF", "date_window_days": i64::MAX });
+        let result = tools.dispatch_tool("match_transactions", arguments).await.unwrap();
+        assert_eq!(result["rules"]["date_window_days"], 365);
+    }
+
+    #[tokio::test]
+    async fn reconciliation_explains_lines_and_creates_the_unmatched() {
+        let tools = sandbox_tools();
+        let arguments = json!({ "cost": "42.10", "description": "Groceries Carrefour", "date": "2024-03-02", "currency_code": "CHF", "group_id": 2001 });
+        let groceries = tools.dispatch_tool("create_expense", arguments).await.unwrap()["id"].clone();
+        let transactions = json!([
+            { "date": "2024-03-03", "amount": "42.10", "description": "NETFLIX.COM" },
+            { "date": "2024-03-07", "amount": "15.00", "description": "PHARMACY", "shared": false },
+            { "date": "2024-03-06", "amount": "25.50", "description": "COOP PRONTO" },
+        ]);
+        let arguments = json!({ "transactions": transactions, "currency_code": "CHF", "min_description_similarity": 0.5, "explain_match": 0 });
+        let strict = tools.dispatch_tool("match_transactions", arguments).await.unwrap();
+        assert_eq!(strict["counts"]["matched"], 0);
+        let closest = &strict["explanation"]["closest_expenses"][0];
+        assert_eq!(closest["expense"]["id"], groceries);
+        assert!(closest["verdict"].as_str().unwrap().starts_with("not a match: descriptions 0.00 alike"));
+
+        let arguments = json!({ "transactions": transactions, "currency_code": "CHF", "group_name": "Casa", "create_unmatched": true, "explain_match": 0 });
+        let proposed = tools.dispatch_tool("match_transactions", arguments.clone()).await.unwrap();
+        assert_eq!(proposed["explanation"]["matched_expense_id"], groceries);
+        assert_eq!(proposed["explanation"]["closest_expenses"][0]["verdict"], "matched");
+        assert!(proposed["confirmation_required"].as_str().unwrap().starts_with("1 expenses would be created"));
+
+        let mut confirmed = arguments;
+        confirmed["confirm"] = json!(true);
+        let created = tools.dispatch_tool("match_transactions", confirmed).await.unwrap();
+        let coop = &created["unmatched_transactions"][1];
+        assert_eq!(coop["description"], "COOP PRONTO");
+        let expense = tools.client.get_expense(coop["created_expense_id"].as_i64().unwrap()).await.unwrap();
+        assert_eq!((expense.cost.as_str(), expense.group_id), ("25.50", Some(2001)));
+        assert!(created["unmatched_transactions"][0].get("created_expense_id").is_none());
+
+        let again = tools.dispatch_tool("match_transactions", json!({ "transactions": transactions, "currency_code": "CHF" })).await.unwrap();
+        assert_eq!(again["counts"]["matched"], 2);
+    }
+
+    #[tokio::test]
+    async fn sync_reports_what_changed_since_the_checkpoint() {
+        let tools = sandbox_tools();
+        let first = tools.dispatch_tool("sync_now", json!({ "group_name": "Casa" })).await.unwrap();
+        let casa = &first["groups"][0];
+        assert_eq!((casa["complete"].clone(), casa["first_sync"].clone()), (json!(true), json!(true)));
+        assert!(casa["changes"]["created"].as_u64().unwrap() > 0);
+        assert!(casa.get("changed_ids").is_none());
+
+        let quiet = tools.dispatch_tool("sync_now", json!({ "group_id": 2001 })).await.unwrap();
+        assert_eq!(quiet["groups"][0]["changes"], json!({ "created": 0, "updated": 0, "deleted": 0, "restored": 0 }));
+
+        let arguments = json!({ "cost": "18.00", "description": "Bread", "group_id": 2001 });
+        let bread = tools.dispatch_tool("create_expense", arguments).await.unwrap()["id"].clone();
+        let params = ListExpensesParams { group_id: Some(2001), ..Default::default() };
+        let expenses = tools.client.get_expenses(params).await.unwrap();
+        let rent = json!(expenses.iter().find(|e| e.description == "Rent" && e.deleted_at.is_none()).unwrap().id);
+        tools.dispatch_tool("delete_expense", json!({ "expense_id": rent })).await.unwrap();
+        let changed = tools.dispatch_tool("sync_now", json!({ "group_id": 2001 })).await.unwrap();
+        let changed_ids = &changed["groups"][0]["changed_ids"];
+        assert_eq!(changed_ids["created"], json!([bread]));
+        assert_eq!(changed_ids["deleted"], json!([rent]));
+
+        let status = tools.dispatch_tool("get_sync_status", json!({ "group_name": "casa" })).await.unwrap();
+        assert_eq!(status["groups"].as_array().unwrap().len(), 1);
+        assert!(status["tombstones"].get(rent.to_string()).is_some());
+        assert!(status["never_synced"].as_array().unwrap().iter().any(|g| g["group_name"] == "Bariloche Trip"));
+    }
+
+    #[tokio::test]
+    async fn writes_made_offline_are_queued_then_sent_once() {
+        let mut tools = sandbox_tools();
+        tools.config.queue_offline_writes = true;
+        let tools = Arc::new(tools);
+        let sandbox = tools.client.sandbox_state().unwrap();
+        let params = ListExpensesParams { group_id: Some(2001), ..Default::default() };
+        let rent = tools.client.get_expenses(params).await.unwrap().into_iter().find(|e| e.description == "Rent").unwrap();
+
+        // Online, a create goes straight through: no key in its notes and no lookup for one
+        let listings = || {
+            let stats = tools.client.stats();
+            let endpoints = stats["endpoints"].as_object().unwrap();
+            endpoints.iter().filter(|(key, _)| key.contains("get_expenses")).map(|(_, e)| e["calls"].as_u64().unwrap()).sum::<u64>()
+        };
+        let before = listings();
+        let lunch = json!({ "cost": "20.00", "description": "Lunch", "group_id": 2001, "details": "At the corner" });
+        let lunch = tools.handle_tool_call("create_expense", Some(lunch)).await.unwrap()["id"].as_i64().unwrap();
+        assert_eq!(listings(), before);
+        assert_eq!(tools.client.get_expense(lunch).await.unwrap().details.as_deref(), Some("At the corner"));
+
+        sandbox.unavailable.store(true, std::sync::atomic::Ordering::SeqCst);
+        let taxi = json!({ "cost": "30.00", "description": "Taxi from the airport", "group_id": 2001 });
+        let queued = tools.handle_tool_call("create_expense", Some(taxi)).await.unwrap();
+        assert_eq!((queued["queued"].clone(), queued["position"].clone()), (json!(true), json!(1)));
+        let update = json!({ "expense_id": rent.id, "description": "Rent (October)" });
+        assert_eq!(tools.handle_tool_call("update_expense", Some(update)).await.unwrap()["position"], 2);
+        let pending = tools.dispatch_tool("list_pending_writes", json!({})).await.unwrap();
+        assert_eq!(pending["count"], 2);
+        let key = pending["writes"][0]["key"].as_str().unwrap().to_string();
+        assert_eq!(pending["writes"][0]["arguments"]["idempotency_key"], key.as_str());
+        let still = tools.dispatch_tool("flush_pending_writes", json!({})).await.unwrap();
+        assert!(still["stopped"].as_str().unwrap().contains("503"));
+        assert_eq!(still["remaining"], 2);
+
+        // Back online: someone renames the rent first, so the queued rename is a conflict
+        sandbox.unavailable.store(false, std::sync::atomic::Ordering::SeqCst);
+        let newer = json!({ "expense_id": rent.id, "description": "Rent, paid by Ana" });
+        tools.dispatch_tool("update_expense", newer).await.unwrap();
+        let flushed = tools.dispatch_tool("flush_pending_writes", json!({})).await.unwrap();
+        assert_eq!(flushed["sent"][0]["key"], key.as_str());
+        let taxi_id = flushed["sent"][0]["result"]["id"].as_i64().unwrap();
+        assert!(flushed["held"][0]["conflict"].as_str().unwrap().contains("changed in Splitwise"));
+        assert_eq!(tools.client.get_expense(rent.id).await.unwrap().description, "Rent, paid by Ana");
+
+        // The create's answer may have been lost; retrying it with its key doesn't make another
+        let retry = json!({ "cost": "30.00", "description": "Taxi from the airport", "group_id": 2001, "idempotency_key": key });
+        let again = tools.dispatch_tool("create_expense", retry).await.unwrap();
+        assert_eq!((again["id"].as_i64(), again["already_created"].clone()), (Some(taxi_id), json!(true)));
+
+        let update_key = flushed["held"][0]["key"].clone();
+        let forced = tools.dispatch_tool("flush_pending_writes", json!({ "force": true })).await.unwrap();
+        assert_eq!((forced["sent"][0]["key"].clone(), forced["remaining"].clone()), (update_key, json!(0)));
+        assert_eq!(tools.client.get_expense(rent.id).await.unwrap().description, "Rent (October)");
+
+        // Nor when the call's budget is spent before the lookup reaches the expense, which an
+        // older date puts behind a full batch of newer ones
+        let old = json!({ "cost": "12.00", "description": "Museum", "group_id": 2001, "date": "2020-01-01", "idempotency_key": "old" });
+        let old_id = tools.dispatch_tool("create_expense", old.clone()).await.unwrap()["id"].as_i64();
+        for i in 0..100 {
+            let newer = json!({ "cost": "1.00", "description": format!("Coffee {}", i), "group_id": 2001 });
+            tools.dispatch_tool("create_expense", newer).await.unwrap();
+        }
+        let limits = budget::Limits { splitwise_calls: 0, pages: 1, response_bytes: 0, timeout_ms: 0 };
+        let (again, _) = budget::scoped(limits, async {
+            assert!(budget::next_page() && !budget::next_page());
+            tools.dispatch_tool("create_expense", old).await.unwrap()
+        })
+        .await;
+        assert_eq!((again["id"].as_i64(), again["already_created"].clone()), (old_id, json!(true)));
+    }
+
+    #[tokio::test]
+    async fn an_idempotency_lookup_that_runs_out_of_pages_creates_nothing() {
+        let tools = sandbox_tools();
+        let old = json!({ "cost": "12.00", "description": "Museum", "group_id": 2001, "date": "2020-01-01", "idempotency_key": "museum" });
+        tools.dispatch_tool("create_expense", old.clone()).await.unwrap();
+        // More recent changes than the lookup reads hide the expense made with the key
+        for i in 0..write_queue::IDEMPOTENCY_LOOKUP_PAGES * 100 {
+            let newer = json!({ "cost": "1.00", "description": format!("Coffee {}", i), "group_id": 2001 });
+            tools.dispatch_tool("create_expense", newer).await.unwrap();
+        }
+        let error = tools.dispatch_tool("create_expense", old).await.unwrap_err();
+        assert!(error.to_string().contains("Nothing was created"), "{}", error);
+        let params = ListExpensesParams { group_id: Some(2001), limit: Some(0), ..Default::default() };
+        let museums = tools.client.get_expenses(params).await.unwrap().into_iter().filter(|e| e.description == "Museum").count();
+        assert_eq!(museums, 1);
+    }
+
+    #[tokio::test]
+    async fn ynab_export_has_my_share_with_the_mapped_category() {
+        let mut tools = sandbox_tools();
+        let entries = [("Groceries", "Food:Groceries")];
+        tools.category_mapping = Some(CategoryMapping::new(entries.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()));
+        let export = tools.dispatch_tool("export_ynab_csv", json!({ "group_name": "Casa" })).await.unwrap();
+        let csv = export["csv"].as_str().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Date,Payee,Memo,Outflow,Inflow");
+        assert_eq!(lines.len(), export["rows"].as_u64().unwrap() as usize + 1);
+        // Rent is 1500 split three ways
+        assert!(lines.iter().any(|l| l.ends_with(",Rent,Rent (Casa),500.00,")));
+        assert!(lines.iter().any(|l| l.ends_with(",Carrefour,Food:Groceries (Casa),37.58,")));
+        assert!(export["unmapped_categories"].as_array().unwrap().contains(&json!("Rent")));
+    }
+
+    #[tokio::test]
+    async fn statement_pdf_covers_the_period_of_one_group() {
+        let tools = sandbox_tools();
+        let arguments = json!({ "cost": "1500.00", "description": "Rent (September)", "group_name": "Casa", "date": "2024-09-01" });
+        tools.dispatch_tool("create_expense", arguments).await.unwrap();
+        let arguments = json!({ "group_name": "Casa", "dated_after": "2024-09-01", "dated_before": "2024-10-01" });
+        let statement = tools.dispatch_tool("generate_statement_pdf", arguments.clone()).await.unwrap();
+        assert_eq!((statement["expenses"].clone(), statement["pages"].clone()), (json!(1), json!(1)));
+        let pdf = BASE64.decode(statement["pdf_base64"].as_str().unwrap()).unwrap();
+        assert_eq!(pdf.len() as u64, statement["bytes"].as_u64().unwrap());
+        let contains = |needle: &str| pdf.windows(needle.len()).any(|w| w == needle.as_bytes());
+        assert!(contains("(Rent \\(September\\)) Tj"));
+        assert!(contains("(1500.00 USD) Tj"));
+        assert!(contains("(Statement from 2024-09-01 to before 2024-10-01) Tj"));
+        assert!(contains("(Everyone is settled up.) Tj") || contains(" pays "));
+
+        // Redaction reaches into the PDF too
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        config.pii_redaction = "amounts_only".to_string();
+        let tools = SplitwiseTools::new(tools.client.clone(), config).unwrap();
+        let statement = tools.dispatch_tool("generate_statement_pdf", arguments).await.unwrap();
+        let pdf = BASE64.decode(statement["pdf_base64"].as_str().unwrap()).unwrap();
+        let contains = |needle: &str| pdf.windows(needle.len()).any(|w| w == needle.as_bytes());
+        assert!(!contains("September") && !contains("Alex Sandbox"));
+        assert!(contains("A. S."));
+    }
+
+    #[tokio::test]
+    async fn errors_are_redacted_like_results() {
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        config.pii_redaction = "initials".to_string();
+        let tools = Arc::new(SplitwiseTools::new(Arc::new(SplitwiseClient::sandbox()), config).unwrap());
+        let arguments = json!({ "user_name": "Ana Núñez", "fields": ["id"] });
+        let error = tools.handle_tool_call("list_expenses", Some(arguments)).await.unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("does not identify a single user"), "{}", message);
+        assert!(message.contains("A. G. (id 101") && message.contains("M. N. (id 102"), "{}", message);
+        assert!(!message.contains("García") && !message.contains("Marcos"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn amounts_only_keeps_amounts_and_categories_but_not_what_or_who() {
+        let plain = Arc::new(sandbox_tools());
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        config.pii_redaction = "amounts_only".to_string();
+        let redacted = Arc::new(SplitwiseTools::new(plain.client.clone(), config).unwrap());
+        let arguments = json!({ "group_name": "Casa", "fields": ["id", "description", "cost", "date", "category", "details", "created_by", "participants"] });
+        let before = plain.handle_tool_call("list_expenses", Some(arguments.clone())).await.unwrap();
+        let after = redacted.handle_tool_call("list_expenses", Some(arguments)).await.unwrap();
+
+        let rent = before["items"].as_array().unwrap().iter().position(|e| e["description"] == "Rent").unwrap();
+        let (before, after) = (&before["items"][rent], &after["items"][rent]);
+        assert_eq!(after["id"], before["id"]);
+        assert_eq!((&after["cost"], &after["date"], &after["category"]), (&before["cost"], &before["date"], &before["category"]));
+        assert_eq!(after["description"], before["category"]["name"]);
+        assert!(after.get("details").is_none());
+        assert_eq!(before["created_by"]["first_name"], "Alex");
+        assert_eq!((&after["created_by"]["first_name"], &after["created_by"]["last_name"]), (&json!("A."), &json!("S.")));
+        let participants = after["participants"].as_str().unwrap();
+        assert!(!participants.contains("Alex") && !participants.contains("Ana") && !participants.contains("Marcos"));
+        assert!(participants.contains("1500.00"), "{}", participants);
+    }
+
+    #[tokio::test]
+    async fn charts_come_back_as_png_images_with_their_data() {
+        let tools = sandbox_tools();
+        let pie = tools.dispatch_tool("render_chart", json!({ "chart": "category_pie", "group_name": "Casa" })).await.unwrap();
+        // The group's whole rent counts, not only my share
+        assert_eq!(pie["data"][0]["label"], "Home");
+        let home: f64 = pie["data"][0]["value"].as_str().unwrap().parse().unwrap();
+        assert!(home >= 1500.0);
+        let png = BASE64.decode(pie["image"]["data"].as_str().unwrap()).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let bars = tools.dispatch_tool("render_chart", json!({ "chart": "monthly_bar" })).await.unwrap();
+        assert_eq!(bars["data"].as_array().unwrap().len(), 12);
+        let arguments = json!({ "chart": "balance_line", "user_name": "Ana", "dated_after": "2024-01-01", "dated_before": "2024-03-01" });
+        let line = tools.dispatch_tool("render_chart", arguments).await.unwrap();
+        let months: Vec<&Value> = line["data"].as_array().unwrap().iter().map(|point| &point["label"]).collect();
+        assert_eq!(months, [&json!("2024-01"), &json!("2024-02")]);
+
+        // Clients get the image as an image block
+        let content = render::content(&line);
+        assert_eq!((content[0]["type"].clone(), content[0]["mimeType"].clone()), (json!("image"), json!("image/png")));
+        assert!(!content[1]["text"].as_str().unwrap().contains("image/png"));
+        let wrong = tools.dispatch_tool("render_chart", json!({ "chart": "radar" })).await;
+        assert!(wrong.unwrap_err().to_string().contains("category_pie"));
+    }
+
+    #[tokio::test]
+    async fn heatmap_adds_spending_up_by_weekday() {
+        let tools = sandbox_tools();
+        for date in ["2024-03-03", "2024-03-10", "2024-03-06"] {
+            let arguments = json!({ "cost": "30.00", "description": "Brunch", "group_name": "Casa", "date": date });
+            tools.dispatch_tool("create_expense", arguments).await.unwrap();
+        }
+        let arguments = json!({ "group_name": "Casa", "dated_after": "2024-03-01", "dated_before": "2024-04-01", "image": true });
+        let heatmap = tools.dispatch_tool("spending_heatmap", arguments).await.unwrap();
+        assert_eq!(heatmap["columns"], json!(["2024-03"]));
+        // March 3rd and 10th were Sundays, the 6th a Wednesday
+        assert_eq!(heatmap["matrix"], json!([[0], [0], [30], [0], [0], [0], [60]]));
+        assert_eq!(heatmap["weekdays"][6]["expenses"], 2);
+        assert_eq!(heatmap["busiest"]["weekday"], "Sunday");
+        assert_eq!(heatmap["busiest"]["share_percent"], 66.7);
+        let image = BASE64.decode(heatmap["image"]["data"].as_str().unwrap()).unwrap();
+        assert!(image.starts_with(b"\x89PNG"));
+
+        let hours = tools.dispatch_tool("spending_heatmap", json!({ "by": "hour" })).await.unwrap();
+        assert_eq!(hours["rows"].as_array().unwrap().len(), 7);
+        assert_eq!(hours["columns"].as_array().unwrap().len(), 24);
+        assert!(hours.get("image").is_none());
+    }
+
+    #[tokio::test]
+    async fn net_worth_converts_every_currency_into_one_figure() {
+        let tools = sandbox_tools();
+        tools.rates.seed("ARS", None, &[("USD", 0.001), ("EUR", 0.0009)]);
+        tools.rates.seed("USD", None, &[("EUR", 0.9)]);
+        tools.rates.seed("EUR", None, &[("USD", 1.1)]);
+        let friends = tools.client.get_friends().await.unwrap();
+        let currencies: std::collections::BTreeSet<&str> =
+            friends.iter().flat_map(|f| &f.balance).map(|b| b.currency_code.as_str()).collect();
+        assert!(currencies.contains("ARS") && currencies.contains("USD"), "{:?}", currencies);
+
+        // What the balances come to at the seeded rates
+        let convert = |balances: &[Balance], rates: &[(&str, f64)]| -> f64 {
+            let rate = |code: &str| rates.iter().find(|(c, _)| *c == code).map_or(1.0, |(_, r)| *r);
+            balances.iter().map(|b| b.amount.parse::<f64>().unwrap() * rate(&b.currency_code)).sum()
+        };
+        let to_usd = [("ARS", 0.001), ("EUR", 1.1)];
+        let worth = tools.dispatch_tool("get_net_worth_of_balances", json!({})).await.unwrap();
+        assert_eq!(worth["currency"], "USD");
+        let expected: f64 = friends.iter().map(|f| convert(&f.balance, &to_usd)).sum();
+        assert_eq!(worth["net"], format_amount(expected));
+        // Each friend keeps their balances as they are, next to the converted figure
+        let marcos = friends.iter().find(|f| f.id == 102).unwrap();
+        let row = worth["by_friend"].as_array().unwrap().iter().find(|f| f["friend_id"] == 102).unwrap();
+        assert_eq!(row["balances"], json!(marcos.balance));
+        assert_eq!(row["converted"], format_amount(convert(&marcos.balance, &to_usd)));
+        let trip = worth["by_group"].as_array().unwrap().iter().find(|g| g["group_id"] == 2002).unwrap();
+        assert!(trip["balances"].as_array().unwrap().iter().any(|b| b["currency_code"] == "ARS"));
+
+        let to_eur = [("ARS", 0.0009), ("USD", 0.9)];
+        let in_euros = tools.dispatch_tool("get_net_worth_of_balances", json!({ "currency": "eur" })).await.unwrap();
+        assert_eq!(in_euros["currency"], "EUR");
+        let expected: f64 = friends.iter().map(|f| convert(&f.balance, &to_eur)).sum();
+        assert_eq!(in_euros["net"], format_amount(expected));
+    }
+
+    #[tokio::test]
+    async fn expenses_are_converted_at_the_rate_of_their_own_date() {
+        let tools = sandbox_tools();
+        for date in ["2024-03-01", "2024-06-03"] {
+            let gallery = json!({ "cost": "100.00", "currency_code": "EUR", "description": "Gallery", "group_id": 2001, "date": date });
+            tools.dispatch_tool("create_expense", gallery).await.unwrap();
+        }
+        tools.rates.seed("EUR", Some("2024-03-01"), &[("USD", 1.08)]);
+        tools.rates.seed("EUR", Some("2024-06-03"), &[("USD", 1.12)]);
+        tools.rates.seed("EUR", None, &[("USD", 1.2)]);
+
+        async fn converted(tools: &SplitwiseTools, rate_date: &str) -> Result<Vec<Value>> {
+            let arguments = json!({ "search_text": "Gallery", "convert_to": "usd", "rate_date": rate_date, "fields": ["date"] });
+            let page = tools.dispatch_tool("list_expenses", arguments).await?;
+            Ok(page["items"].as_array().unwrap().iter().map(|e| e["converted_cost"].clone()).collect())
+        }
+        let by_date = converted(&tools, "expense_date").await.unwrap();
+        assert_eq!(
+            by_date,
+            [
+                json!({ "amount": "112.00", "currency": "USD", "rate": 1.12, "rate_date": "2024-06-03" }),
+                json!({ "amount": "108.00", "currency": "USD", "rate": 1.08, "rate_date": "2024-03-01" }),
+            ]
+        );
+        let latest = converted(&tools, "latest").await.unwrap();
+        assert!(latest.iter().all(|c| c["amount"] == "120.00" && c["rate"] == 1.2));
+
+        // A day whose published rates don't include the currency is reported, not guessed
+        let gallery = json!({ "cost": "100.00", "currency_code": "EUR", "description": "Gallery", "group_id": 2001, "date": "2024-07-01" });
+        tools.dispatch_tool("create_expense", gallery).await.unwrap();
+        tools.rates.seed("EUR", Some("2024-07-01"), &[("GBP", 0.85)]);
+        let error = converted(&tools, "expense_date").await.unwrap_err();
+        assert!(error.to_string().contains("EUR -> USD on 2024-07-01 not available"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn tags_come_and_go_without_touching_the_notes() {
+        let tools = sandbox_tools();
+        let notes = "Paid by transfer\nAsk Ana for the receipt";
+        let gift = json!({ "cost": "80.00", "description": "Wedding gift", "group_id": 2001, "details": notes });
+        let id = tools.dispatch_tool("create_expense", gift).await.unwrap()["id"].as_i64().unwrap();
+
+        let tagged = tools.dispatch_tool("tag_expense", json!({ "expense_id": id, "tags": ["Wedding", "gifts", "wedding"] })).await.unwrap();
+        assert_eq!(tagged["tags"], json!(["wedding", "gifts"]));
+        let details = tools.client.get_expense(id).await.unwrap().details.unwrap();
+        assert!(details.starts_with(notes) && details.ends_with(r#"[mcp] {"tags":["wedding","gifts"]}"#), "{}", details);
+        let by_tag = tools.dispatch_tool("list_expenses_by_tag", json!({ "tag": "WEDDING" })).await.unwrap();
+        assert_eq!((&by_tag["count"], &by_tag["expenses"][0]["id"]), (&json!(1), &json!(id)));
+
+        let untagged = tools.dispatch_tool("untag_expense", json!({ "expense_id": id, "tags": ["wedding"] })).await.unwrap();
+        assert_eq!(untagged["tags"], json!(["gifts"]));
+        assert_eq!(tools.dispatch_tool("list_expenses_by_tag", json!({ "tag": "wedding" })).await.unwrap()["count"], 0);
+        // With the last tag gone the metadata line goes too, and the notes are as they were
+        tools.dispatch_tool("untag_expense", json!({ "expense_id": id, "tags": ["gifts"] })).await.unwrap();
+        assert_eq!(tools.client.get_expense(id).await.unwrap().details.as_deref(), Some(notes));
+    }
+
+    #[tokio::test]
+    async fn reimbursements_are_totalled_per_payer_and_month_until_paid_back() {
+        let tools = sandbox_tools();
+        let mut ids = Vec::new();
+        for (date, description) in [("2025-03-10", "Client dinner"), ("2025-03-20", "Train to Rosario"), ("2025-04-02", "Hotel")] {
+            let expense = json!({ "cost": "90.00", "description": description, "group_id": 2001, "date": date });
+            ids.push(tools.dispatch_tool("create_expense", expense).await.unwrap()["id"].as_i64().unwrap());
+        }
+        // My share by default, or the amount given
+        let marked = tools.dispatch_tool("mark_reimbursable", json!({ "expense_ids": [ids[0], ids[2]], "payer": "Acme" })).await.unwrap();
+        assert_eq!(marked["marked"][0]["amount"], "30.00");
+        let arguments = json!({ "expense_ids": [ids[1]], "payer": "Acme", "amount": "12.50" });
+        tools.dispatch_tool("mark_reimbursable", arguments).await.unwrap();
+        let both = json!({ "expense_ids": [ids[0], ids[1]], "payer": "Acme", "amount": "5" });
+        assert!(tools.dispatch_tool("mark_reimbursable", both).await.is_err());
+
+        let pending = tools.dispatch_tool("list_pending_reimbursements", json!({ "payer": "acme" })).await.unwrap();
+        assert_eq!(pending["count"], 3);
+        assert_eq!(
+            pending["totals"],
+            json!([
+                { "payer": "Acme", "month": "2025-03", "currency": "USD", "count": 2, "amount": "42.50" },
+                { "payer": "Acme", "month": "2025-04", "currency": "USD", "count": 1, "amount": "30.00" },
+            ])
+        );
+
+        let arguments = json!({ "expense_ids": [ids[0]], "reimbursed_on": "2025-04-15" });
+        tools.dispatch_tool("mark_reimbursed", arguments).await.unwrap();
+        let (_, metadata) = metadata::split(tools.client.get_expense(ids[0]).await.unwrap().details.as_deref());
+        assert!(metadata.has_tag(metadata::REIMBURSED) && !metadata.has_tag(metadata::REIMBURSABLE));
+        assert_eq!(metadata.reimbursement.unwrap().reimbursed_on.as_deref(), Some("2025-04-15"));
+        let pending = tools.dispatch_tool("list_pending_reimbursements", json!({})).await.unwrap();
+        assert_eq!(pending["count"], 2);
+        assert_eq!(pending["totals"][0]["amount"], "12.50");
+
+        let rent = tools.dispatch_tool("list_expenses", json!({ "search_text": "Rent", "limit": 1, "fields": ["id"] })).await.unwrap();
+        let never = json!({ "expense_ids": [rent["items"][0]["id"]] });
+        let error = tools.dispatch_tool("mark_reimbursed", never).await.unwrap_err();
+        assert!(error.to_string().contains("never marked reimbursable"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn collections_list_has_only_what_friends_owe_me() {
+        let tools = sandbox_tools();
+        let list = tools.dispatch_tool("collections_list", json!({ "language": "es" })).await.unwrap();
+        let owed = list["owed_to_you"].as_array().unwrap();
+        let converted: Vec<f64> = owed.iter().map(|e| e["converted"].as_str().unwrap().parse().unwrap()).collect();
+        assert!(converted.windows(2).all(|w| w[0] >= w[1]));
+        // Sam is settled up; every amount listed is owed to me
+        assert!(owed.iter().all(|e| e["friend_id"] != 105));
+        assert!(owed.iter().flat_map(|e| e["owed"].as_array().unwrap()).all(|b| !b["amount"].as_str().unwrap().starts_with('-')));
+
+        let pat = owed.iter().find(|e| e["friend_id"] == 104).unwrap();
+        assert_eq!(pat["groups"], json!([{ "group_id": null, "group_name": "Non-group expenses", "owed": [{ "amount": "21.00", "currency_code": "USD" }] }]));
+        assert_eq!(pat["days_since_activity"], 200);
+        assert!(pat["reminder"].as_str().unwrap().starts_with("¡Hola Pat! Te recuerdo que en Splitwise me debes 21.00 USD."));
+    }
+
+    #[tokio::test]
+    async fn payables_put_old_debts_first_and_go_into_the_plan() {
+        let tools = sandbox_tools();
+        let list = tools.dispatch_tool("payables_list", json!({})).await.unwrap();
+        assert_eq!((list["count"].as_u64(), list["total"].as_str()), (Some(0), Some("0.00")));
+
+        // Sam's museum trip three months ago, Pat's dinner this week
+        let long_ago = (chrono::Local::now().date_naive() - chrono::Days::new(90)).to_string();
+        let museum = json!({ "cost": "100.00", "description": "Museum", "currency_code": "EUR", "paid_by": "Sam", "split_among": ["me", "Sam"], "date": long_ago });
+        let dinner = json!({ "cost": "100.00", "description": "Dinner", "paid_by": "Pat", "split_among": ["me", "Pat"] });
+        for arguments in [museum, dinner] {
+            tools.dispatch_tool("create_expense", arguments).await.unwrap();
+        }
+        let list = tools.dispatch_tool("payables_list", json!({ "add_to_plan": true })).await.unwrap();
+        let owed = list["you_owe"].as_array().unwrap();
+        assert_eq!(owed.len(), 2);
+        assert_eq!((&owed[0]["name"], &owed[0]["amount"], &owed[0]["bucket"]), (&json!("Sam Oldfriend"), &json!("50.00"), &json!("60+")));
+        // Pat's 21.00 from pizza night is netted off
+        assert_eq!((&owed[1]["name"], &owed[1]["amount"], &owed[1]["age_days"]), (&json!("Pat Rivera"), &json!("29.00"), &json!(0)));
+        assert_eq!(list["plan"]["added"], 2);
+        assert!(owed.iter().all(|debt| debt["planned_at"].is_string()));
+
+        // Paying Pat takes the debt out of the plan
+        let payment = json!({ "cost": "29.00", "description": "Payment", "paid_by": "me", "split_among": ["Pat"] });
+        tools.dispatch_tool("create_expense", payment).await.unwrap();
+        let list = tools.dispatch_tool("payables_list", json!({})).await.unwrap();
+        assert_eq!(list["plan"]["count"], 1);
+        assert_eq!(list["plan"]["payments"][0]["name"], "Sam Oldfriend");
+    }
+
+    #[tokio::test]
+    async fn quick_add_reads_the_amount_currency_and_payer() {
+        let tools = sandbox_tools();
+        let owes = |parsed: &Value| {
+            let split = parsed["expense"]["split"].as_array().unwrap();
+            split.iter().map(|u| (u["user_id"].as_i64().unwrap(), u["paid"].clone(), u["owes"].clone())).collect::<Vec<_>>()
+        };
+
+        // A preview: parsed, but not saved
+        let arguments = json!({ "input": "Pizza 24.50 EUR", "group_name": "Casa" });
+        let preview = tools.dispatch_tool("quick_add_expense", arguments).await.unwrap();
+        assert_eq!((&preview["valid"], &preview["saved"]), (&json!(true), &json!(false)));
+        let expense = &preview["expense"];
+        assert_eq!((&expense["cost"], &expense["currency_code"], &expense["description"]), (&json!("24.50"), &json!("EUR"), &json!("Pizza")));
+        assert_eq!(expense["group_id"], 2001);
+        assert_eq!(owes(&preview).len(), 3);
+        assert!(tools.client.get_expense(expense["id"].as_i64().unwrap()).await.is_err());
+
+        let arguments = json!({ "input": "Ana paid 30 for groceries", "group_id": 2001, "autosave": true });
+        let saved = tools.dispatch_tool("quick_add_expense", arguments).await.unwrap();
+        assert_eq!(saved["saved"], true);
+        assert_eq!(saved["expense"]["description"], "for groceries");
+        assert_eq!(
+            owes(&saved),
+            [(100, json!("0.00"), json!("10.00")), (101, json!("30.00"), json!("10.00")), (102, json!("0.00"), json!("10.00"))]
+        );
+        assert_eq!(tools.client.get_expense(saved["expense"]["id"].as_i64().unwrap()).await.unwrap().cost, "30.00");
+
+        let arguments = json!({ "input": "€12 museum", "user_name": "Sam" });
+        let with_sam = tools.dispatch_tool("quick_add_expense", arguments).await.unwrap();
+        assert_eq!(with_sam["expense"]["currency_code"], "EUR");
+        assert_eq!(owes(&with_sam), [(100, json!("12.00"), json!("6.00")), (105, json!("0.00"), json!("6.00"))]);
+
+        let arguments = json!({ "input": "dinner with everyone", "group_id": 2001, "autosave": true });
+        let unparsed = tools.dispatch_tool("quick_add_expense", arguments).await.unwrap();
+        assert_eq!((&unparsed["valid"], &unparsed["saved"]), (&json!(false), &json!(false)));
+        assert!(unparsed["error"].as_str().unwrap().contains("amount"));
+        assert!(unparsed["expense"]["id"].is_null());
+    }
+
+    #[tokio::test]
+    async fn split_with_friend_builds_both_shares() {
+        let tools = sandbox_tools();
+        let arguments = json!({ "user_name": "Jamie", "cost": "100", "description": "Groceries", "ratio": "60:40", "paid_by": "friend" });
+        let created = tools.dispatch_tool("split_with_friend", arguments).await.unwrap();
+        assert_eq!(created["you_owe_friend"], "60.00");
+        let expense = tools.dispatch_tool("get_expense", json!({ "expense_id": created["id"], "fields": ["group_id", "users{user_id,paid_share,owed_share}"] })).await.unwrap();
+        assert_eq!(expense["group_id"], Value::Null);
+        assert_eq!(
+            expense["users"],
+            json!([
+                { "user_id": 100, "paid_share": "0.00", "owed_share": "60.00" },
+                { "user_id": 103, "paid_share": "100.00", "owed_share": "40.00" },
+            ])
+        );
+
+        // Odd cents go to one side; the shares always add up to the cost
+        let arguments = json!({ "friend_id": 101, "cost": "10.00", "description": "Coffee", "ratio": "2:1" });
+        let created = tools.dispatch_tool("split_with_friend", arguments).await.unwrap();
+        assert_eq!(created["friend_owes_you"], "3.33");
+        assert!(tools.dispatch_tool("split_with_friend", json!({ "friend_id": 101, "cost": "10", "description": "x", "ratio": "half" })).await.is_err());
+        assert!(tools.dispatch_tool("split_with_friend", json!({ "user_name": "me", "cost": "10", "description": "x" })).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn iou_direction_comes_from_lender_and_borrower() {
+        let tools = sandbox_tools();
+        let lent = tools.dispatch_tool("iou", json!({ "borrower": "Ana", "amount": 20 })).await.unwrap();
+        assert_eq!(lent["summary"], "Ana García owes you 20.00 more");
+        let expense = tools.dispatch_tool("get_expense", json!({ "expense_id": lent["id"], "fields": ["description", "users{user_id,paid_share,owed_share}"] })).await.unwrap();
+        assert_eq!(expense["description"], "Loan");
+        assert_eq!(
+            expense["users"],
+            json!([
+                { "user_id": 100, "paid_share": "20.00", "owed_share": "0.00" },
+                { "user_id": 101, "paid_share": "0.00", "owed_share": "20.00" },
+            ])
+        );
+
+        // A negative amount still goes from lender to borrower
+        let borrowed = tools.dispatch_tool("iou", json!({ "lender": "Marcos", "borrower": "me", "amount": "-15", "description": "Bus ticket" })).await.unwrap();
+        assert_eq!((&borrowed["lender"], &borrowed["borrower"]), (&json!("Marcos Núñez"), &json!("you")));
+        assert_eq!(borrowed["summary"], "You owe Marcos Núñez 15.00 more");
+
+        for arguments in [json!({ "amount": 5 }), json!({ "lender": "Ana", "borrower": "Jamie", "amount": 5 }), json!({ "lender": "me", "amount": 5 })] {
+            assert!(tools.dispatch_tool("iou", arguments).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn dinner_split_shares_the_bill_and_tip_among_the_participants() {
+        let tools = sandbox_tools();
+        // Marcos paid and isn't listed; Ana is left out of the Casa dinner
+        let arguments = json!({ "total": "90", "tip_percent": 10, "paid_by": "Marcos", "participants": ["me", "Marcos N."], "group_name": "Casa" });
+        let created = tools.dispatch_tool("dinner_split", arguments).await.unwrap();
+        assert_eq!((&created["cost"], &created["tip"], &created["people"]), (&json!("99.00"), &json!("9.00"), &json!(2)));
+        let expense = tools.dispatch_tool("get_expense", json!({ "expense_id": created["id"], "fields": ["group_id", "users{user_id,paid_share,owed_share}"] })).await.unwrap();
+        assert_eq!(expense["group_id"], 2001);
+        assert_eq!(
+            expense["users"],
+            json!([
+                { "user_id": 102, "paid_share": "99.00", "owed_share": "49.50" },
+                { "user_id": 100, "paid_share": "0.00", "owed_share": "49.50" },
+            ])
+        );
+
+        let alone = json!({ "total": 30, "participants": ["me"] });
+        assert!(tools.dispatch_tool("dinner_split", alone).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn attendance_prorates_by_nights_and_adds_up_to_the_cost() {
+        let tools = sandbox_tools();
+        // Jamie came for the last night only; Marcos paid the cabin
+        let attendance = json!([
+            { "user_name": "me", "arrival": "2025-01-10", "departure": "2025-01-13" },
+            { "user_name": "Marcos", "arrival": "2025-01-10", "departure": "2025-01-13" },
+            { "user_id": 103, "arrival": "2025-01-12", "departure": "2025-01-13" },
+        ]);
+        let arguments = json!({
+            "cost": "100.00",
+            "description": "Cabin",
+            "group_name": "Bariloche",
+            "prorate_by_attendance": { "attendance": attendance, "paid_by": "Marcos" },
+        });
+        let created = tools.dispatch_tool("create_expense", arguments).await.unwrap();
+        let fields = json!(["users{user_id,paid_share,owed_share}"]);
+        let expense = tools.dispatch_tool("get_expense", json!({ "expense_id": created["id"], "fields": fields })).await.unwrap();
+        let owed: BTreeMap<i64, String> = expense["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| (u["user_id"].as_i64().unwrap(), u["owed_share"].as_str().unwrap().to_string()))
+            .collect();
+        // 3, 3 and 1 nights: the leftover cents go to the largest remainders
+        assert_eq!(owed, BTreeMap::from([(100, "42.86".to_string()), (102, "42.86".to_string()), (103, "14.28".to_string())]));
+        let total: f64 = owed.values().map(|o| o.parse::<f64>().unwrap()).sum();
+        assert_eq!(format_amount(total), "100.00");
+        let payer = expense["users"].as_array().unwrap().iter().find(|u| u["user_id"] == 102).unwrap();
+        assert_eq!(payer["paid_share"], "100.00");
+
+        let backwards = json!({
+            "cost": "50.00",
+            "description": "Cabin",
+            "group_id": 2002,
+            "prorate_by_attendance": { "attendance": [{ "user_name": "me", "arrival": "2025-01-13", "departure": "2025-01-10" }] },
+        });
+        let error = tools.dispatch_tool("create_expense", backwards).await.unwrap_err();
+        assert!(error.to_string().contains("Departure 2025-01-10 is before arrival 2025-01-13"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn group_invites_list_who_hasnt_joined_and_resend() {
+        let tools = sandbox_tools();
+        let pat = GroupUserInput { user_id: None, first_name: Some("Pat".to_string()), last_name: None, email: Some("pat@example.com".to_string()) };
+        tools.client.add_user_to_group(2001, pat).await.unwrap();
+
+        let invites = tools.dispatch_tool("get_group_invites", json!({ "group_name": "Casa" })).await.unwrap();
+        let link = invites["invite_link"].as_str().unwrap();
+        assert!(link.starts_with("https://www.splitwise.com/join/"));
+        assert_eq!(invites["pending_count"], 1);
+        assert_eq!((&invites["pending"][0]["user_id"], &invites["pending"][0]["email"]), (&json!(104), &json!("pat@example.com")));
+
+        let resent = tools.dispatch_tool("resend_invite", json!({ "group_name": "Casa", "email": "PAT@example.com", "language": "es" })).await.unwrap();
+        assert_eq!(resent["user_id"], 104);
+        assert!(resent["message"].as_str().unwrap().starts_with("¡Hola Pat! Te sumé a Casa"));
+        assert!(resent["message"].as_str().unwrap().ends_with(link));
+
+        // Ana already has an account, and Jamie isn't in Casa
+        assert!(tools.dispatch_tool("resend_invite", json!({ "group_name": "Casa", "user_name": "Ana" })).await.is_err());
+        assert!(tools.dispatch_tool("resend_invite", json!({ "group_name": "Casa", "user_name": "Jamie" })).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn aliases_count_as_the_account_they_stand_for() {
+        let tools = sandbox_tools();
+        // Pat stands in for an old placeholder of Ana's
+        let pat = GroupUserInput { user_id: None, first_name: Some("Pat".to_string()), last_name: None, email: Some("pat@example.com".to_string()) };
+        tools.client.add_user_to_group(2001, pat).await.unwrap();
+        let dinner = json!({ "group_id": 2001, "cost": "90.00", "description": "Dinner", "paid_by": "Pat", "split_among": ["me", "Ana", "Pat"] });
+        tools.dispatch_tool("create_expense", dinner).await.unwrap();
+        let report = json!({ "group_id": 2001 });
+        let row = |result: &Value, id: i64| result["members"].as_array().unwrap().iter().find(|m| m["user_id"] == id && m["currency"] == "USD").cloned();
+        let before = tools.dispatch_tool("contribution_report", report.clone()).await.unwrap();
+        assert!(row(&before, 104).is_some());
+        assert!(before.get("merged_aliases").is_none());
+
+        let linked = tools.dispatch_tool("set_member_alias", json!({ "user_name": "Ana", "aliases": [104] })).await.unwrap();
+        assert_eq!(linked["aliases"], json!([{ "alias_id": 104, "alias_name": "Pat Rivera" }]));
+        let after = tools.dispatch_tool("contribution_report", report.clone()).await.unwrap();
+        assert!(row(&after, 104).is_none());
+        let paid = |result: &Value| row(result, 101).map_or(0.0, |m| m["paid"].as_str().unwrap().parse::<f64>().unwrap());
+        assert!((paid(&after) - paid(&before) - 90.0).abs() < 0.005);
+        assert_eq!(after["merged_aliases"][0]["counted_as"], "Ana García");
+
+        // Aliases don't chain, and nobody is an alias of themselves
+        assert!(tools.dispatch_tool("set_member_alias", json!({ "user_id": 104, "aliases": [102] })).await.is_err());
+        assert!(tools.dispatch_tool("set_member_alias", json!({ "user_id": 102, "aliases": [101] })).await.is_err());
+        assert!(tools.dispatch_tool("set_member_alias", json!({ "user_id": 101, "aliases": [101] })).await.is_err());
+        assert_eq!(tools.dispatch_tool("list_member_aliases", json!({})).await.unwrap()["count"], 1);
+
+        let removed = tools.dispatch_tool("set_member_alias", json!({ "aliases": ["Pat"], "remove": true })).await.unwrap();
+        assert_eq!(removed["removed"], json!([104]));
+        assert!(row(&tools.dispatch_tool("contribution_report", report).await.unwrap(), 104).is_some());
+    }
+
+    #[tokio::test]
+    async fn group_currencies_flag_what_the_group_doesnt_use() {
+        let tools = sandbox_tools();
+        let trip = tools.dispatch_tool("check_group_currencies", json!({ "group_name": "Bariloche" })).await.unwrap();
+        assert_eq!((&trip["currency"], &trip["mismatch_count"]), (&json!("ARS"), &json!(1)));
+        let uber = &trip["mismatches"][0];
+        assert_eq!((&uber["description"], &uber["currency_code"]), (&json!("Uber to the airport"), &json!("USD")));
+        assert_eq!(uber["suggested_fix"]["arguments"]["currency_code"], "ARS");
+        assert!(uber.get("conversion").is_none());
+
+        // Asked to use USD instead, the three ARS expenses stand out; rates can't be fetched offline
+        let usd = json!({ "group_name": "Bariloche", "currency_code": "usd", "suggest_conversions": true });
+        let trip = tools.dispatch_tool("check_group_currencies", usd).await.unwrap();
+        assert_eq!(trip["mismatch_count"], 3);
+        assert!(trip["mismatches"].as_array().unwrap().iter().all(|m| m["conversion_error"].is_string()));
+        assert!(trip.get("converted_total").is_none());
+
+        // A conversion scales every share, keeping them adding up to the new cost
+        let expenses = tools.client.get_expenses(ListExpensesParams { group_id: Some(2002), ..Default::default() }).await.unwrap();
+        let hotel = expenses.iter().find(|e| e.description == "Hotel Llao Llao").unwrap();
+        let arguments = converted_expense(hotel, 500.0, "USD");
+        let shares = arguments["split_by_shares"].as_array().unwrap();
+        let sum = |key: &str| shares.iter().map(|s| s[key].as_str().unwrap().parse::<f64>().unwrap()).sum::<f64>();
+        assert_eq!((sum("paid_share"), sum("owed_share")), (500.0, 500.0));
+        assert_eq!(shares[0]["owed_share"], "166.67");
+        assert_eq!((&arguments["cost"], &arguments["currency_code"]), (&json!("500.00"), &json!("USD")));
+    }
+
+    #[tokio::test]
+    async fn create_expense_takes_the_currency_the_description_names() {
+        let tools = sandbox_tools();
+        let taxi = json!({ "cost": "40", "description": "€40 taxi", "group_id": 2001 });
+        let created = tools.dispatch_tool("create_expense", taxi).await.unwrap();
+        assert_eq!(created["currency_detected"], json!({ "currency_code": "EUR", "rule": "symbol", "matched": "€40" }));
+        let expense = tools.client.get_expense(created["id"].as_i64().unwrap()).await.unwrap();
+        assert_eq!(expense.currency_code, "EUR");
+
+        // A currency given outright, or a description naming none, is left alone
+        let given = json!({ "cost": "40", "description": "€40 taxi", "currency_code": "USD", "group_id": 2001 });
+        assert!(tools.dispatch_tool("create_expense", given).await.unwrap().get("currency_detected").is_none());
+        let plain = json!({ "cost": "40", "description": "Taxi", "group_id": 2001 });
+        assert!(tools.dispatch_tool("create_expense", plain).await.unwrap().get("currency_detected").is_none());
+    }
+
+    #[tokio::test]
+    async fn common_currencies_put_mine_first() {
+        let tools = sandbox_tools();
+        let common = tools.dispatch_tool("get_common_currencies", json!({ "limit": 4 })).await.unwrap();
+        let codes: Vec<&str> = common["currencies"].as_array().unwrap().iter().map(|c| c["currency_code"].as_str().unwrap()).collect();
+        // USD is the default and ARS comes from the trip; the museum in EUR is too long ago to
+        // count, so EUR and GBP only top up the list
+        assert_eq!(codes, ["USD", "ARS", "EUR", "GBP"]);
+        assert_eq!(common["currencies"][0]["why"], "Your default currency");
+        assert!(common["currencies"][1]["why"].as_str().unwrap().starts_with("Used in 3 expenses"));
+        assert_eq!((&common["currencies"][2]["why"], &common["currencies"][3]["why"]), (&json!("Widely used"), &json!("Widely used")));
+        assert_eq!(common["available"], 8);
+        let error = tools.dispatch_tool("get_common_currencies", json!({ "months": u32::MAX })).await.unwrap_err();
+        assert_eq!(error.to_string(), "months must be between 1 and 120");
+    }
+
+    #[tokio::test]
+    async fn recent_comments_say_whether_my_note_was_answered() {
+        let tools = sandbox_tools();
+        let feed = tools.dispatch_tool("recent_comments", json!({ "group_name": "Casa", "search": "electricity" })).await.unwrap();
+        let comments = feed["comments"].as_array().unwrap();
+        assert_eq!(comments.len(), 2);
+        // Newest first: Ana's answer, then my question
+        assert_eq!((&comments[0]["by"], &comments[0]["reply_to_you"]), (&json!("Ana García"), &json!(true)));
+        assert_eq!((&comments[1]["mine"], &comments[1]["answered"]), (&json!(true), &json!(true)));
+        assert_eq!(comments[1]["expense"], "Electricity bill");
+
+        // The change log stays out unless asked for
+        let everything = tools.dispatch_tool("recent_comments", json!({})).await.unwrap();
+        assert!(everything["comments"].as_array().unwrap().iter().all(|c| c.get("answered").is_some() || c.get("reply_to_you").is_some()));
+        let with_log = tools.dispatch_tool("recent_comments", json!({ "include_system": true, "days": 365 })).await.unwrap();
+        assert!(with_log["total"].as_u64().unwrap() > everything["total"].as_u64().unwrap());
+
+        // Far-reaching periods are capped, and threads are only fetched while the budget lasts
+        let all_time = tools.dispatch_tool("recent_comments", json!({ "days": i64::MAX })).await.unwrap();
+        assert_eq!(all_time["total"], everything["total"]);
+        let limits = budget::Limits { splitwise_calls: 0, pages: 1, response_bytes: 0, timeout_ms: 0 };
+        let (feed, notice) = budget::scoped(limits, tools.dispatch_tool("recent_comments", json!({}))).await;
+        assert_eq!(feed.unwrap()["expenses_with_comments"], 0);
+        assert_eq!(notice.unwrap()["exceeded"], "pages");
+    }
+
+    #[tokio::test]
+    async fn stale_friendships_look_past_deleted_expenses() {
+        let tools = sandbox_tools();
+        let added = tools.dispatch_tool("add_friend", json!({ "email": "quinn@example.com" })).await.unwrap();
+        let quinn = added[0]["id"].as_i64().unwrap();
+        let split = json!({ "friend_id": quinn, "cost": "10.00", "description": "Coffee" });
+        let made = tools.dispatch_tool("split_with_friend", split).await.unwrap();
+        let ids = |report: &Value| -> Vec<i64> {
+            report["stale_friends"].as_array().unwrap().iter().map(|f| f["id"].as_i64().unwrap()).collect()
+        };
+        // An open balance keeps them out, so the removal is refused as well
+        let report = tools.dispatch_tool("find_stale_friendships", json!({})).await.unwrap();
+        assert!(!ids(&report).contains(&quinn));
+        let refused = tools.dispatch_tool("remove_friend", json!({ "friend_id": quinn })).await.unwrap_err();
+        assert!(refused.to_string().contains("isn't settled"), "{}", refused);
+
+        tools.dispatch_tool("delete_expense", json!({ "expense_id": made["id"] })).await.unwrap();
+        let report = tools.dispatch_tool("find_stale_friendships", json!({})).await.unwrap();
+        assert!(ids(&report).contains(&quinn));
+        assert!(tools.dispatch_tool("find_stale_friendships", json!({ "months": 100000 })).await.is_err());
+        let removed = tools.dispatch_tool("remove_friend", json!({ "friend_id": quinn })).await.unwrap();
+        assert_eq!(removed["success"], json!(true));
+    }
+
+    #[test]
+    fn search_modes_match_as_documented() {
+        let substring = TextMatcher::new("uber", None, None).unwrap();
+        assert!(substring.is_match("UBER to the airport"));
+        let regex = TextMatcher::new("^uber", Some("regex"), None).unwrap();
+        assert!(regex.is_match("Uber home") && !regex.is_match("Shared Uber"));
+        assert!(TextMatcher::new("(", Some("regex"), None).is_err());
+        assert!(TextMatcher::new("uber", Some("exact"), None).is_err());
+
+        let fuzzy = TextMatcher::new("azado", Some("fuzzy"), None).unwrap();
+        assert!(fuzzy.is_match("Asado del domingo"));
+        assert!(!fuzzy.is_match("Groceries"));
+        // Multi-word queries are compared against runs of as many words
+        let fuzzy = TextMatcher::new("electricty bil", Some("fuzzy"), None).unwrap();
+        assert!(fuzzy.is_match("Electricity bill - March"));
+        assert!(!fuzzy.is_match("Electricity"));
+        let strict = TextMatcher::new("azado", Some("fuzzy"), Some(0.95)).unwrap();
+        assert!(!strict.is_match("Asado"));
+    }
+
+    #[test]
+    fn weighted_splits_always_add_up() {
+        assert_eq!(split_by_weights(100.0, &[1.0, 1.0, 1.0]), Some(vec![33.34, 33.33, 33.33]));
+        assert_eq!(split_by_weights(10.0, &[1.0, 2.0, 3.0]), Some(vec![1.67, 3.33, 5.0]));
+        // A refund is split like the charge it undoes
+        assert_eq!(split_by_weights(-100.0, &[1.0, 1.0, 1.0]), Some(vec![-33.34, -33.33, -33.33]));
+        assert_eq!(split_by_weights(-0.05, &[1.0, 1.0]), Some(vec![-0.03, -0.02]));
+        let shares = split_by_weights(-1234.57, &[0.5, 0.3, 0.2]).unwrap();
+        assert_eq!((shares.iter().sum::<f64>() * 100.0).round(), -123457.0);
+        assert_eq!(split_by_weights(10.0, &[0.0, 0.0]), None);
+        assert_eq!(split_by_weights(10.0, &[1.0, -1.0]), None);
+        assert_eq!(split_by_weights(1e30, &[1.0, 1.0]), None);
+        assert_eq!(split_by_weights(f64::INFINITY, &[1.0, 1.0]), None);
+        assert_eq!(split_by_weights(f64::NAN, &[1.0, 1.0]), None);
+        assert_eq!(split_by_weights(10.0, &[1.0, f64::INFINITY]), None);
+        assert_eq!(split_by_weights(10.0, &[f64::NAN, 1.0]), None);
+        let shares = split_by_weights(MAX_AMOUNT, &[1.0, 1.0, 1.0]).unwrap();
+        assert_eq!(shares.iter().sum::<f64>(), MAX_AMOUNT);
+    }
+
+    #[tokio::test]
+    async fn huge_or_infinite_costs_are_rejected_instead_of_split() {
+        let tools = sandbox_tools();
+        for cost in ["1e30", "inf", "NaN"] {
+            let error = tools.dispatch_tool("split_with_friend", json!({ "friend_id": 101, "cost": cost, "description": "Oops" })).await.unwrap_err();
+            assert!(error.to_string().starts_with("Invalid amount"), "{}", error);
+            let arguments = json!({ "cost": cost, "description": "Oops", "group_id": 2001, "split_among": ["me", "Ana"] });
+            assert!(tools.dispatch_tool("create_expense", arguments).await.is_err());
+            let bills = json!([{ "description": "Oops", "cost": cost }]);
+            let arguments = json!({ "members": [{ "user_name": "me" }, { "user_id": 101 }], "bills": bills });
+            assert!(tools.dispatch_tool("calculate_household_split", arguments).await.is_err());
+        }
+        let arguments = json!({ "friend_id": 101, "cost": "10", "description": "Oops", "ratio": "inf:1" });
+        assert!(tools.dispatch_tool("split_with_friend", arguments).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_member_listed_twice_is_rejected_before_anything_is_created() {
+        let tools = sandbox_tools();
+        let count = || async {
+            let all = tools.dispatch_tool("list_expenses", json!({ "limit": 0, "fields": ["id"] })).await.unwrap();
+            all["items"].as_array().unwrap().len()
+        };
+        let before = count().await;
+        let bills = json!([
+            { "description": "Electricity", "cost": "90.00" },
+            { "description": "Internet", "cost": "45.00" },
+        ]);
+
+        // The same friend once by ID and once by name
+        let members = json!([{ "user_name": "me" }, { "user_id": 101 }, { "user_name": "Ana" }]);
+        let arguments = json!({ "members": members, "bills": bills, "group_id": 2001, "create": true });
+        let error = tools.dispatch_tool("calculate_household_split", arguments).await.unwrap_err();
+        assert!(error.to_string().contains("Ana García is listed more than once"), "{}", error);
+        assert_eq!(count().await, before);
+
+        let members = json!([{ "user_name": "me" }, { "user_id": 101 }, { "user_name": "Marcos" }]);
+        let arguments = json!({ "members": members, "bills": bills, "group_id": 2001, "create": true });
+        let result = tools.dispatch_tool("calculate_household_split", arguments).await.unwrap();
+        assert_eq!(result["created"].as_array().unwrap().len(), 2);
+        assert_eq!(result["bills"][0]["shares"][0]["owed_share"], "30.00");
+        assert_eq!(count().await, before + 2);
+    }
+
+    #[tokio::test]
+    async fn several_payers_fill_in_their_paid_shares() {
+        let tools = sandbox_tools();
+        let owed = |user_id: i64, owed: &str| ExpenseShare {
+            user_id: Some(user_id),
+            email: None,
+            first_name: None,
+            last_name: None,
+            paid_share: "0.00".to_string(),
+            owed_share: owed.to_string(),
+        };
+        let paid = |shares: &[ExpenseShare]| -> Vec<(Option<i64>, String, String)> {
+            shares.iter().map(|s| (s.user_id, s.paid_share.clone(), s.owed_share.clone())).collect()
+        };
+
+        let mut shares = vec![owed(100, "15.00"), owed(101, "15.00")];
+        tools.apply_payers(&mut shares, 30.0, Some(&json!({ "me": "10", "Ana": "20" }))).await.unwrap();
+        assert_eq!(paid(&shares), vec![(Some(100), "10.00".into(), "15.00".into()), (Some(101), "20.00".into(), "15.00".into())]);
+
+        // A payer outside the split pays without owing
+        let mut shares = vec![owed(100, "15.00"), owed(101, "15.00")];
+        tools.apply_payers(&mut shares, 30.0, Some(&json!({ "101": "12.50", "Jamie": "17.50" }))).await.unwrap();
+        assert_eq!(
+            paid(&shares),
+            vec![
+                (Some(100), "0.00".into(), "15.00".into()),
+                (Some(101), "12.50".into(), "15.00".into()),
+                (Some(103), "17.50".into(), "0.00".into()),
+            ]
+        );
+
+        let mut shares = vec![owed(100, "15.00"), owed(101, "15.00")];
+        let short = tools.apply_payers(&mut shares, 30.0, Some(&json!({ "me": "10", "Ana": "10" }))).await.unwrap_err();
+        assert!(short.to_string().contains("add up to 20.00"), "{}", short);
+        let twice = tools.apply_payers(&mut shares, 30.0, Some(&json!({ "me": "10", "100": "20" }))).await.unwrap_err();
+        assert!(twice.to_string().contains("same person twice"), "{}", twice);
+    }
+
+    #[test]
+    fn amounts_under_half_a_cent_are_zero() {
+        assert!(is_zero_amount("0.0") && is_zero_amount("-0.004") && is_zero_amount("0.00"));
+        assert!(!is_zero_amount("0.01") && !is_zero_amount("-12.50"));
+        // Something that isn't a number isn't a settled balance
+        assert!(!is_zero_amount("") && !is_zero_amount("n/a"));
+    }
+
+    #[test]
+    fn amounts_are_read_from_numbers_or_strings() {
+        assert_eq!(parse_amount(&json!(12.5)).unwrap(), 12.5);
+        assert_eq!(parse_amount(&json!(" 30.10 ")).unwrap(), 30.1);
+        assert_eq!(parse_amount(&json!(-4)).unwrap(), -4.0);
+        assert!(parse_amount(&json!("ten")).is_err());
+        assert!(parse_amount(&json!(null)).is_err());
+        assert!(parse_amount(&json!("1e30")).is_err());
+        assert!(parse_amount(&json!(1e30)).is_err());
+        assert!(parse_amount(&json!("inf")).is_err());
+        assert!(parse_amount(&json!("-inf")).is_err());
+        assert!(parse_amount(&json!("NaN")).is_err());
+        assert_eq!(parse_amount(&json!("1e12")).unwrap(), 1e12);
+        assert_eq!(format_amount(1.005 + 2.0), "3.00");
+        assert_eq!(format_amount(-0.5), "-0.50");
+    }
+
+    #[test]
+    fn change_comments_become_field_changes() {
+        let content = "Alex P. updated this transaction:\n- cost changed from “30.00” to “45.00”\n- date changed from 2025-03-01 to 2025-03-02\nA note with no change";
+        assert_eq!(
+            parse_change_lines(content),
+            vec![
+                json!({ "field": "cost", "from": "30.00", "to": "45.00" }),
+                json!({ "field": "date", "from": "2025-03-01", "to": "2025-03-02" }),
+            ]
+        );
+        assert!(parse_change_lines("Alex P. deleted this expense.").is_empty());
+    }
+
+    #[test]
+    fn occurrences_step_by_the_interval() {
+        let date = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(occurrence_after(date("2025-01-31"), "monthly", 1), Some(date("2025-02-28")));
+        assert_eq!(occurrence_after(date("2025-01-01"), "fortnightly", 2), Some(date("2025-01-29")));
+        assert_eq!(occurrence_after(date("2024-02-29"), "yearly", 1), Some(date("2025-02-28")));
+        assert_eq!(occurrence_after(date("2025-01-01"), "weekly", 3), Some(date("2025-01-22")));
+        assert_eq!(occurrence_after(date("2025-01-01"), "daily", 1), None);
+        assert_eq!(occurrence_after(date("2025-01-01"), "weekly", u32::MAX), None);
+    }
+
+    #[test]
+    fn invited_emails_get_a_readable_name() {
+        assert_eq!(name_from_email("maria.lopez@example.com"), ("Maria".to_string(), Some("Lopez".to_string())));
+        assert_eq!(name_from_email("juan_de-la+splits@example.com"), ("Juan".to_string(), Some("De La Splits".to_string())));
+        assert_eq!(name_from_email("sam1984.2@example.com"), ("Sam1984".to_string(), None));
+        assert_eq!(name_from_email("12345@example.com"), ("12345".to_string(), None));
+    }
+
+    #[test]
+    fn people_are_told_apart_by_id_then_email() {
+        let share = |person: Value| share_for(&person, "0.00".to_string(), "5.00".to_string()).unwrap();
+        assert_eq!(share(json!(101)).user_id, Some(101));
+        assert_eq!(share(json!(" ana@example.com ")).email.as_deref(), Some("ana@example.com"));
+        assert_eq!(share(json!("Ana")).user_name.as_deref(), Some("Ana"));
+        assert!(share_for(&json!(["Ana"]), "0".to_string(), "0".to_string()).is_err());
+
+        let person = |user_id: Option<i64>, email: Option<&str>| ExpenseShare {
+            user_id,
+            email: email.map(str::to_string),
+            first_name: None,
+            last_name: None,
+            paid_share: "0.00".to_string(),
+            owed_share: "0.00".to_string(),
+        };
+        assert!(same_person(&person(Some(101), None), &person(Some(101), Some("x@example.com"))));
+        assert!(same_person(&person(None, Some("Ana@Example.com")), &person(None, Some("ana@example.com"))));
+        // An ID and an email can't be compared without looking the email up
+        assert!(!same_person(&person(Some(101), None), &person(None, Some("ana@example.com"))));
+    }
+
+    #[tokio::test]
+    async fn list_expenses_cut_by_the_response_limit_continue_where_they_stopped() {
+        let everything = sandbox_tools()
+            .dispatch_tool("list_expenses", json!({ "limit": 0, "fields": ["id"] }))
+            .await
+            .unwrap();
+        let everything: Vec<Value> = everything["items"].as_array().unwrap().iter().map(|e| e["id"].clone()).collect();
+
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        config.tool_max_response_bytes = 600;
+        let tools = Arc::new(SplitwiseTools::new(Arc::new(SplitwiseClient::sandbox()), config).unwrap());
+        // Deleted expenses included is a single request; excluded, pages are filtered locally
+        for include_deleted in ["include", "exclude"] {
+            let mut seen = Vec::new();
+            let mut offset = json!(0);
+            loop {
+                let page = tools
+                    .handle_tool_call(
+                        "list_expenses",
+                        Some(json!({
+                            "limit": 10,
+                            "offset": offset,
+                            "include_deleted": include_deleted,
+                            "fields": ["id", "description", "date"],
+                        })),
+                    )
+                    .await
+                    .unwrap();
+                assert!(page.get(budget::POSITIONS).is_none());
+                let items = page["items"].as_array().unwrap();
+                assert!(!items.is_empty());
+                seen.extend(items.iter().map(|e| e["id"].clone()));
+                if page["has_more"] == false {
+                    break;
+                }
+                offset = page["next_offset"].clone();
+            }
+            let unique: std::collections::HashSet<_> = seen.iter().map(|id| id.as_i64()).collect();
+            assert_eq!(unique.len(), seen.len(), "{}: no expense is listed twice", include_deleted);
+            if include_deleted == "exclude" {
+                assert_eq!(seen, everything, "every expense is reached");
             }
-            "get_categories" => {
-                let categories = self.client.get_categories().await?;
-                Ok(serde_json::to_value(categories)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn list_expenses_past_the_deadline_continue_from_next_offset() {
+        let tools = Arc::new(sandbox_tools());
+        // Enough expenses that listing them all takes more than one batch
+        for i in 0..120 {
+            let coffee = json!({ "cost": "2.50", "description": format!("Coffee {}", i), "group_id": 2001 });
+            tools.dispatch_tool("create_expense", coffee).await.unwrap();
+        }
+        let everything = tools.dispatch_tool("list_expenses", json!({ "limit": 0, "fields": ["id"] })).await.unwrap();
+        let everything: Vec<Value> = everything["items"].as_array().unwrap().iter().map(|e| e["id"].clone()).collect();
+        assert!(everything.len() > 100);
+
+        // Each batch takes longer than the whole call may, so only the first is fetched. Deleted
+        // expenses are filtered locally, in batches, when a limit is given
+        let sandbox = tools.client.sandbox_state().unwrap();
+        sandbox.latency_ms.store(20, std::sync::atomic::Ordering::SeqCst);
+        let arguments = json!({ "limit": 1000, "timeout_ms": 5, "fields": ["id"] });
+        let partial = tools.handle_tool_call("list_expenses", Some(arguments)).await.unwrap();
+        sandbox.latency_ms.store(0, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(partial["_budget"]["exceeded"], "deadline");
+        assert_eq!(partial["has_more"], true);
+        let mut seen: Vec<Value> = partial["items"].as_array().unwrap().iter().map(|e| e["id"].clone()).collect();
+        assert!(!seen.is_empty() && seen.len() < everything.len());
+
+        let arguments = json!({ "limit": 1000, "offset": partial["next_offset"], "fields": ["id"] });
+        let rest = tools.handle_tool_call("list_expenses", Some(arguments)).await.unwrap();
+        assert!(rest.get("_budget").is_none());
+        assert_eq!(rest["has_more"], false);
+        seen.extend(rest["items"].as_array().unwrap().iter().map(|e| e["id"].clone()));
+        assert_eq!(seen, everything, "nothing skipped or listed twice");
+    }
+
+
+    #[tokio::test]
+    async fn category_mapping_reports_what_it_leaves_out() {
+        let mut tools = sandbox_tools();
+        let entries = [("Food and drink", "5100"), ("Groceries", "5110"), ("Transportation", "6200"), ("Rnet", "7000")];
+        tools.category_mapping = Some(CategoryMapping::new(entries.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()));
+        let report = tools.dispatch_tool("validate_category_mapping", json!({ "include_mapped": true })).await.unwrap();
+        assert_eq!(report["mapped"], json!(13));
+        assert_eq!(report["unknown_entries"], json!(["Rnet"]));
+        assert_eq!(report["complete"], json!(false));
+        assert!(report["unmapped"].as_array().unwrap().iter().any(|c| c["path"] == "Home > Rent"));
+        let groceries = report["mapping"].as_array().unwrap().iter().find(|c| c["id"] == 12).unwrap();
+        assert_eq!((groceries["code"].clone(), groceries["from"].clone()), (json!("5110"), json!("Groceries")));
+
+        tools.category_mapping = None;
+        assert!(tools.dispatch_tool("validate_category_mapping", json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn apply_fixes_dry_runs_then_applies_each_item() {
+        let tools = sandbox_tools();
+        let audit = tools.dispatch_tool("audit_data_quality", json!({})).await.unwrap();
+        let mismatch = audit["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["check"] == "currency_mismatch")
+            .unwrap()
+            .clone();
+        let mut categorize = mismatch.clone();
+        categorize["check"] = json!("missing_category");
+        categorize["suggested_fix"] = json!({ "action": "Pick a category", "tool": "suggest_category", "arguments": {} });
+        let mut decided = categorize.clone();
+        decided["set"] = json!({ "category_name": "Taxi" });
+
+        let fixes = json!([mismatch, categorize, decided]);
+        let preview = tools.dispatch_tool("apply_fixes", json!({ "fixes": fixes })).await.unwrap();
+        let statuses = |result: &Value| result["results"].as_array().unwrap().iter().map(|r| r["status"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(statuses(&preview), ["would_apply", "skipped", "would_apply"]);
+        assert_eq!(preview["results"][2]["tool"], "update_expense");
+
+        let applied = tools.dispatch_tool("apply_fixes", json!({ "fixes": fixes, "dry_run": false })).await.unwrap();
+        assert_eq!(statuses(&applied), ["applied", "skipped", "applied"]);
+        let id = applied["results"][0]["expense_id"].as_i64().unwrap();
+        let expense = tools.client.get_expense(id).await.unwrap();
+        assert_eq!(expense.currency_code, "ARS");
+        assert_eq!(expense.category.name, "Taxi");
+    }
+
+    #[test]
+    fn tools_list_pages_follow_the_cursor() {
+        let mut tools = sandbox_tools();
+        let all: Vec<Value> = tools.get_tools();
+        assert!(tools.list_tools(None).unwrap().get("nextCursor").is_none());
+
+        tools.config.tools_page_size = 10;
+        let mut listed = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = tools.list_tools(cursor.as_deref()).unwrap();
+            let names = page["tools"].as_array().unwrap();
+            assert!(names.len() <= 10);
+            listed.extend(names.iter().map(|t| t["name"].clone()));
+            match page["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+        assert_eq!(listed, all.iter().map(|t| t["name"].clone()).collect::<Vec<_>>());
+        assert!(tools.list_tools(Some("no_such_tool")).is_err());
+    }
+
+    #[tokio::test]
+    async fn the_tool_policy_hides_tools_and_announces_each_change() {
+        let dir = std::env::temp_dir().join(format!("splitwise-mcp-tools-policy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("policy.json");
+        let write = |text: &str, seconds: u64| {
+            std::fs::write(&path, text).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+        };
+        write(r#"{"read_only": true}"#, 1);
+        let mut config = Config::from_env();
+        config.sandbox = true;
+        config.data_dir = None;
+        config.tool_policy_file = Some(path.to_string_lossy().into_owned());
+        let tools = Arc::new(SplitwiseTools::new(Arc::new(SplitwiseClient::sandbox()), config).unwrap());
+        assert!(tools.tool_list_may_change() && !sandbox_tools().tool_list_may_change());
+        let names: Vec<Value> = tools.get_tools().into_iter().map(|t| t["name"].clone()).collect();
+        assert!(WRITE_TOOLS.iter().all(|tool| names.contains(&json!(tool))));
+
+        let listed = |tools: &SplitwiseTools| -> Vec<String> {
+            let page = tools.list_tools(None).unwrap();
+            page["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap().to_string()).collect()
+        };
+        let offered = listed(&tools);
+        assert!(offered.contains(&"list_expenses".to_string()) && !offered.contains(&"create_expense".to_string()));
+        assert_eq!(offered.len(), names.len() - WRITE_TOOLS.len());
+        assert_eq!(tools.account_summary()["read_only"], true);
+        let create = json!({ "cost": "10.00", "description": "Taxi", "group_id": 2001 });
+        let error = tools.handle_tool_call("create_expense", Some(create.clone())).await.unwrap_err();
+        assert!(error.to_string().contains("read-only mode"), "{}", error);
+        let job = json!({ "tool": "create_expense", "arguments": create });
+        assert!(tools.handle_tool_call("start_job", Some(job)).await.is_err());
+
+        // The client being served hears of each change once, and the list follows the file
+        let peer = peer::Peer::new(Some("policy".to_string()), None);
+        let (sink, _) = tokio::sync::mpsc::unbounded_channel();
+        let notice = || peer::connect(peer.clone(), sink.clone(), async { tools.tools_changed_notice() });
+        assert_eq!(notice().await, None);
+        write(r#"{"allow": ["list_expenses", "create_expense"]}"#, 2);
+        assert_eq!(notice().await, Some(json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" })));
+        assert_eq!(notice().await, None);
+        assert_eq!(listed(&tools), ["list_expenses", "create_expense"]);
+        let create = json!({ "cost": "10.00", "description": "Taxi", "group_id": 2001 });
+        tools.handle_tool_call("create_expense", Some(create)).await.unwrap();
+        let error = tools.handle_tool_call("get_dashboard", None).await.unwrap_err();
+        assert!(error.to_string().contains("allowlist"), "{}", error);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn parse_statement_checks_what_the_model_found() {
+        let tools = sandbox_tools();
+        peer::initialize(Some(&json!({ "capabilities": { "sampling": {} } })));
+        let (sink, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        let client = tokio::spawn(async move {
+            let request: Value = requests.recv().await.unwrap();
+            let found = json!([
+                { "date": "2025-03-10", "description": "Supermercado Dia", "amount": 1234.5, "currency_code": "ars", "shared": true },
+                { "date": "2025-03-11", "description": "Salary", "amount": 2000, "shared": false },
+                { "date": "2025-03-12", "description": "Cafe", "amount": 7.25, "shared": true },
+            ]);
+            let text = format!("Here they are:\n```json\n{}\n```", found);
+            peer::resolve(&peer::Peer::default(), &json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": { "role": "assistant", "content": { "type": "text", "text": text } }
+            }));
+        });
+        let text = "10/03 SUPERMERCADO DIA 1.234,50\n11/03 SALARY ACME 2.000,00\n";
+        let parse = Box::pin(tools.dispatch_tool("parse_statement", json!({ "text": text, "currency_code": "EUR" })));
+        let parsed = peer::connect(peer::Peer::default(), sink, parse).await.unwrap();
+        client.await.unwrap();
+        assert_eq!(parsed["counts"], json!({ "found": 3, "valid": 2, "to_create": 1 }));
+        let candidates = parsed["candidates"].as_array().unwrap();
+        assert_eq!(candidates[0]["amount"], "1234.50");
+        assert_eq!(candidates[0]["currency_code"], "ARS");
+        assert_eq!(candidates[1]["currency_code"], "EUR");
+        assert_eq!(candidates[2]["problems"], json!(["7.25 doesn't appear in the text"]));
+
+        let args = json!({ "candidates": candidates, "group_name": "casa", "create": true });
+        let proposed = tools.dispatch_tool("parse_statement", args.clone()).await.unwrap();
+        assert!(proposed["confirmation_required"].is_string());
+        let mut confirmed = args;
+        confirmed["confirm"] = json!(true);
+        let created = tools.dispatch_tool("parse_statement", confirmed).await.unwrap();
+        let candidates = created["candidates"].as_array().unwrap();
+        assert!(candidates[0]["created_expense_id"].is_i64());
+        assert!(candidates[1].get("created_expense_id").is_none());
+    }
+
+    #[test]
+    fn spanish_covers_every_description() {
+        let mut tools = sandbox_tools();
+        tools.config.tool_locale = Some("es".to_string());
+        let english = tools.get_tools();
+        let spanish = tools.localized_tools();
+        assert_eq!(english.len(), spanish.len());
+
+        // A description edited in English without updating locales/es.json shows up here
+        fn descriptions<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+            match value {
+                Value::Object(obj) => obj.iter().for_each(|(key, value)| match value {
+                    Value::String(text) if key == "description" => found.push(text),
+                    _ => descriptions(value, found),
+                }),
+                Value::Array(items) => items.iter().for_each(|item| descriptions(item, found)),
+                _ => {}
             }
-            _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
         }
+        let (english, spanish) = (Value::Array(english), Value::Array(spanish));
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        descriptions(&english, &mut before);
+        descriptions(&spanish, &mut after);
+        let untranslated: Vec<_> = before.iter().zip(&after).filter(|(en, es)| en == es).map(|(en, _)| en).collect();
+        assert!(untranslated.is_empty(), "not in locales/es.json: {:?}", untranslated);
+    }
+
+    #[tokio::test]
+    async fn instructions_name_the_account_once_loaded() {
+        let tools = sandbox_tools();
+        assert!(tools.instructions().starts_with("The Splitwise account hasn't been loaded yet"));
+        assert_eq!(tools.account_summary()["mode"], "sandbox");
+
+        tools.warm_up().await;
+        let summary = tools.account_summary();
+        let me = tools.client.get_current_user().await.unwrap();
+        assert_eq!(summary["first_name"], me.first_name.as_str());
+        assert!(summary["groups"].as_u64().unwrap() > 0);
+        let instructions = tools.instructions();
+        assert!(instructions.starts_with(&format!("Acting on the Splitwise account of {} (", me.first_name)));
+        assert!(instructions.contains("changes last until the server stops."));
+    }
+
+    #[test]
+    fn usage_guidance_follows_the_tool_schemas() {
+        let tools = sandbox_tools();
+        let guidance = usage_guidance(&tools.get_tools()).join("\n");
+        assert!(guidance.contains("Call get_categories before create_expense or update_expense"));
+        assert!(guidance.contains("monthly_close, parse_statement, create_expense_from_text, match_transactions, find_stale_friendships only preview"));
+        assert!(guidance.contains("delete_expense, remove_friend remove data"));
+        assert!(guidance.contains("Pass fields to list_groups, get_group,"));
+
+        let registry = vec![
+            json!({ "name": "create_expense", "inputSchema": { "properties": { "category_id": {} } } }),
+            json!({ "name": "drop_group", "description": "Destructive", "inputSchema": { "properties": {} } }),
+        ];
+        assert_eq!(usage_guidance(&registry), vec!["drop_group remove data: confirm with the user before calling them."]);
+        assert!(tools.instructions().contains("\n\nHow to use these tools:\n- Start with get_dashboard"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn participants_name_who_paid_and_who_owes() {
+        let mut expense: Expense = serde_json::from_value(json!({
+            "id": 1, "group_id": 7, "friendship_id": null, "expense_bundle_id": null,
+            "description": "Dinner", "repeats": false, "repeat_interval": null,
+            "email_reminder": null, "email_reminder_in_advance": null, "next_repeat": null,
+            "details": null, "comments_count": 0,
+            "payment": false, "creation_method": null, "transaction_method": null,
+            "transaction_confirmed": false, "transaction_id": null, "transaction_status": null,
+            "cost": "30.00", "currency_code": "USD", "repayments": [], "date": "2025-01-01",
+            "created_at": "2025-01-01", "created_by": { "id": 2, "first_name": "Marcos", "last_name": null, "picture": null },
+            "updated_at": "2025-01-01", "updated_by": null, "deleted_at": null, "deleted_by": null,
+            "category": { "id": 13, "name": "Dining out", "icon": null, "subcategories": null },
+            "receipt": { "original": null, "large": null },
+            "users": [
+                { "user_id": 2, "user": { "id": 2, "first_name": "Marcos", "last_name": null, "picture": null },
+                  "paid_share": "30.00", "owed_share": "10.00", "net_balance": "20.00" },
+                { "user_id": 3, "user": { "id": 3, "first_name": "Ana", "last_name": null, "picture": null },
+                  "paid_share": "0.00", "owed_share": "10.00", "net_balance": "-10.00" },
+                { "user_id": 1, "user": null, "paid_share": "0.00", "owed_share": "10.00", "net_balance": "-10.00" },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(participants(&expense, 1), "Marcos paid 30.00, owes 10.00; Ana owes 10.00; User 1 (you) owes 10.00");
+
+        // Someone on the expense with nothing paid or owed is left out
+        expense.users[1].owed_share = "0.00".to_string();
+        assert_eq!(participants(&expense, 1), "Marcos paid 30.00, owes 10.00; User 1 (you) owes 10.00");
+    }
+}
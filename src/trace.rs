@@ -0,0 +1,65 @@
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Per-tool-call timing breakdown, collected only when a caller asks for `trace: true`.
+// Recording functions are no-ops outside of a traced scope, so the client and caches
+// can call them unconditionally.
+
+tokio::task_local! {
+    static CURRENT: Arc<Mutex<Trace>>;
+}
+
+#[derive(Default)]
+struct Trace {
+    calls: Vec<CallRecord>,
+    cache_hits: Vec<String>,
+}
+
+struct CallRecord {
+    method: String,
+    endpoint: String,
+    status: u16,
+    latency: Duration,
+}
+
+/// Run `future` while collecting a trace, returning its output and the breakdown.
+pub async fn traced<F: Future>(future: F) -> (F::Output, Value) {
+    let trace = Arc::new(Mutex::new(Trace::default()));
+    let started = Instant::now();
+    let output = CURRENT.scope(trace.clone(), future).await;
+    let total = started.elapsed();
+
+    let trace = trace.lock().unwrap();
+    let upstream: Duration = trace.calls.iter().map(|c| c.latency).sum();
+    let summary = json!({
+        "total_ms": total.as_millis() as u64,
+        "splitwise_calls": trace.calls.len(),
+        "splitwise_ms": upstream.as_millis() as u64,
+        "cache_hits": trace.cache_hits.len(),
+        "cache_hit_keys": trace.cache_hits,
+        "calls": trace.calls.iter().map(|c| json!({
+            "method": c.method,
+            "endpoint": c.endpoint,
+            "status": c.status,
+            "ms": c.latency.as_millis() as u64,
+        })).collect::<Vec<_>>(),
+    });
+    (output, summary)
+}
+
+pub fn record_call(method: &str, endpoint: &str, status: u16, latency: Duration) {
+    let _ = CURRENT.try_with(|trace| {
+        trace.lock().unwrap().calls.push(CallRecord {
+            method: method.to_string(),
+            endpoint: endpoint.to_string(),
+            status,
+            latency,
+        });
+    });
+}
+
+pub fn record_cache_hit(key: &str) {
+    let _ = CURRENT.try_with(|trace| trace.lock().unwrap().cache_hits.push(key.to_string()));
+}
@@ -1,169 +1,301 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Response types tolerate what Splitwise sends in practice: missing fields take their
+// defaults, explicit nulls too (`nullable`), and amounts may come as numbers as well as
+// strings (`amount`). Unknown fields are ignored. An `id` is the one thing an item can't do
+// without, so it stays required and an item missing it is skipped rather than read as id 0.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub email: String,
+    #[serde(default)]
     pub registration_status: Option<String>,
+    #[serde(default)]
     pub picture: Option<Picture>,
+    #[serde(default)]
     pub default_currency: Option<String>,
+    #[serde(default)]
     pub locale: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Picture {
     pub small: Option<String>,
     pub medium: Option<String>,
     pub large: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Group {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub name: String,
+    #[serde(default)]
     pub group_type: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub updated_at: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub simplify_by_default: bool,
+    #[serde(default, deserialize_with = "nullable")]
     pub members: Vec<GroupMember>,
+    #[serde(default, deserialize_with = "nullable")]
     pub original_debts: Vec<Debt>,
+    #[serde(default, deserialize_with = "nullable")]
     pub simplified_debts: Vec<Debt>,
+    #[serde(default)]
     pub whiteboard: Option<serde_json::Value>,
+    #[serde(default)]
     pub group_reminders: Option<serde_json::Value>,
+    // Link that lets anyone join the group; Splitwise leaves it out for some accounts
+    #[serde(default)]
+    pub invite_link: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GroupMember {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: Option<String>,
+    #[serde(default)]
     pub email: Option<String>,
+    #[serde(default)]
     pub registration_status: Option<String>,
+    #[serde(default)]
     pub picture: Option<Picture>,
+    #[serde(default, deserialize_with = "nullable")]
     pub balance: Vec<Balance>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Balance {
+    #[serde(deserialize_with = "nullable")]
     pub currency_code: String,
+    #[serde(deserialize_with = "amount")]
     pub amount: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Debt {
+    #[serde(deserialize_with = "nullable")]
     pub from: i64,
+    #[serde(deserialize_with = "nullable")]
     pub to: i64,
+    #[serde(deserialize_with = "amount")]
     pub amount: String,
+    #[serde(deserialize_with = "nullable")]
     pub currency_code: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Expense {
     pub id: i64,
+    #[serde(default)]
     pub group_id: Option<i64>,
+    #[serde(default)]
     pub friendship_id: Option<i64>,
+    #[serde(default)]
     pub expense_bundle_id: Option<i64>,
+    #[serde(default, deserialize_with = "nullable")]
     pub description: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub repeats: bool,
+    #[serde(default)]
     pub repeat_interval: Option<String>,
+    #[serde(default)]
     pub email_reminder: Option<bool>,
+    #[serde(default)]
     pub email_reminder_in_advance: Option<i32>,
+    #[serde(default)]
     pub next_repeat: Option<String>,
+    #[serde(default)]
     pub details: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub comments_count: i32,
+    #[serde(default, deserialize_with = "nullable")]
     pub payment: bool,
+    #[serde(default)]
     pub creation_method: Option<String>,
+    #[serde(default)]
     pub transaction_method: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub transaction_confirmed: bool,
+    #[serde(default)]
     pub transaction_id: Option<String>,
+    #[serde(default)]
     pub transaction_status: Option<String>,
+    #[serde(default, deserialize_with = "amount")]
     pub cost: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub currency_code: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub repayments: Vec<Repayment>,
+    #[serde(default, deserialize_with = "nullable")]
     pub date: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub created_at: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub created_by: UserReference,
+    #[serde(default, deserialize_with = "nullable")]
     pub updated_at: String,
+    #[serde(default)]
     pub updated_by: Option<UserReference>,
+    #[serde(default)]
     pub deleted_at: Option<String>,
+    #[serde(default)]
     pub deleted_by: Option<UserReference>,
+    #[serde(default, deserialize_with = "nullable")]
     pub category: Category,
+    #[serde(default, deserialize_with = "nullable")]
     pub receipt: Receipt,
+    #[serde(default, deserialize_with = "nullable")]
     pub users: Vec<ExpenseUser>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserReference {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: Option<String>,
+    #[serde(default)]
     pub picture: Option<Picture>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Category {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub name: String,
+    #[serde(default)]
     pub icon: Option<String>,
+    #[serde(default)]
     pub subcategories: Option<Vec<Subcategory>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Subcategory {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub name: String,
+    #[serde(default)]
     pub icon: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Receipt {
     pub original: Option<String>,
     pub large: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ExpenseUser {
+    #[serde(deserialize_with = "nullable")]
     pub user_id: i64,
     pub user: Option<UserReference>,
+    #[serde(deserialize_with = "amount")]
     pub paid_share: String,
+    #[serde(deserialize_with = "amount")]
     pub owed_share: String,
+    #[serde(deserialize_with = "amount")]
     pub net_balance: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Repayment {
+    #[serde(deserialize_with = "nullable")]
     pub from: i64,
+    #[serde(deserialize_with = "nullable")]
     pub to: i64,
+    #[serde(deserialize_with = "amount")]
     pub amount: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Friend {
     pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: Option<String>,
+    #[serde(default)]
     pub email: Option<String>,
+    #[serde(default)]
     pub registration_status: Option<String>,
+    #[serde(default)]
     pub picture: Option<Picture>,
+    #[serde(default, deserialize_with = "nullable")]
     pub balance: Vec<Balance>,
+    #[serde(default, deserialize_with = "nullable")]
     pub groups: Vec<FriendGroup>,
+    #[serde(default, deserialize_with = "nullable")]
     pub updated_at: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FriendGroup {
+    #[serde(deserialize_with = "nullable")]
     pub group_id: i64,
+    #[serde(deserialize_with = "nullable")]
     pub balance: Vec<Balance>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Currency {
+    #[serde(deserialize_with = "nullable")]
     pub currency_code: String,
+    #[serde(deserialize_with = "nullable")]
     pub unit: String,
 }
 
+// Comment on an expense. Splitwise writes "System" comments itself whenever an expense
+// is edited, deleted or restored, which makes them the change log of the expense
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: i64,
+    #[serde(default, deserialize_with = "nullable")]
+    pub content: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub comment_type: String,
+    #[serde(default)]
+    pub relation_type: Option<String>,
+    #[serde(default)]
+    pub relation_id: Option<i64>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub created_at: String,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    #[serde(default)]
+    pub user: Option<UserReference>,
+}
+
+// Result of Splitwise's natural-language parser. The expense is left untyped because
+// unsaved drafts come back without an id and other fields the Expense struct requires
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedSentence {
+    pub expense: Option<serde_json::Value>,
+    pub valid: bool,
+    pub confidence: Option<f64>,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateExpenseRequest {
     pub cost: String,
@@ -250,4 +382,21 @@ pub struct ApiError {
 pub struct SuccessResponse {
     pub success: bool,
     pub errors: Option<Vec<String>>,
-}
\ No newline at end of file
+}
+
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// Amounts are decimal strings, but accept a bare number (or null) without failing
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(s)) => s,
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    })
+}
@@ -0,0 +1,32 @@
+use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+// Problems a tool call worked around instead of failing on, such as a list item Splitwise
+// sent in a shape the typed parser can't read. They are returned beside the result as
+// `_warnings`, each with the raw JSON that was skipped. Recording is a no-op outside of a
+// collecting scope (background jobs), where the warning is only logged.
+
+tokio::task_local! {
+    static CURRENT: Arc<Mutex<Vec<Value>>>;
+}
+
+/// Run `future` while collecting warnings, returning its output and what was recorded.
+pub async fn collect<F: Future>(future: F) -> (F::Output, Vec<Value>) {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let output = CURRENT.scope(warnings.clone(), future).await;
+    let warnings = std::mem::take(&mut *warnings.lock().unwrap());
+    (output, warnings)
+}
+
+pub fn record(endpoint: &str, error: &str, raw: &Value) {
+    tracing::warn!("Skipped unreadable item from {}: {}", endpoint, error);
+    let _ = CURRENT.try_with(|warnings| {
+        warnings.lock().unwrap().push(json!({
+            "endpoint": endpoint,
+            "id": raw.get("id"),
+            "error": error,
+            "raw": raw,
+        }));
+    });
+}
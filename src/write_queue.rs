@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::splitwise::UpstreamError;
+
+// Expense writes that couldn't reach Splitwise, kept to be sent later (QUEUE_OFFLINE_WRITES=true),
+// so an expense dictated on a flight isn't lost. When create_expense, update_expense or
+// delete_expense fails because Splitwise can't be reached (no connection, a timeout, a 502-504),
+// the call is saved in the local store and answered with its place in the queue. Queued writes
+// are sent in the order they were made: before the next write, or by flush_pending_writes.
+// Each queued create gets an idempotency key that goes into the expense's metadata, so a create
+// whose answer was lost on the way back is found by its key instead of being made twice. An
+// update or delete of an expense that was changed in Splitwise after the write was queued is a
+// conflict, held back until flushed with force, so a stale edit can't overwrite a newer one.
+
+/// Tools whose calls are queued when Splitwise can't be reached.
+pub const QUEUED_TOOLS: [&str; 3] = ["create_expense", "update_expense", "delete_expense"];
+
+// How far back create_expense looks for an expense made with the same idempotency key
+pub const IDEMPOTENCY_WINDOW_DAYS: u64 = 7;
+
+// Pages of recently changed expenses the idempotency lookup may read before giving up
+pub const IDEMPOTENCY_LOOKUP_PAGES: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingWrite {
+    pub key: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub queued_at: String,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    // Why the write is held back, when its expense changed in Splitwise after it was queued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<String>,
+}
+
+impl PendingWrite {
+    pub fn new(tool: &str, mut arguments: Value, error: Option<String>) -> Self {
+        // A create's key is its idempotency key, given to it here unless the caller chose one;
+        // other writes get a key to be named by
+        let key = match arguments["idempotency_key"].as_str() {
+            Some(key) => key.to_string(),
+            None => new_key(),
+        };
+        if tool == "create_expense" {
+            arguments["idempotency_key"] = json!(key);
+        }
+        Self {
+            key,
+            tool: tool.to_string(),
+            arguments,
+            queued_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            attempts: u32::from(error.is_some()),
+            last_error: error,
+            conflict: None,
+        }
+    }
+
+    /// The expense an update or delete is for.
+    pub fn expense_id(&self) -> Option<i64> {
+        self.arguments["expense_id"].as_i64()
+    }
+
+    /// Whether an expense last updated at `updated_at` changed after this write was queued.
+    pub fn is_stale_against(&self, updated_at: &str) -> bool {
+        let parse = |stamp: &str| chrono::DateTime::parse_from_rfc3339(stamp).ok();
+        match (parse(&self.queued_at), parse(updated_at)) {
+            (Some(queued), Some(updated)) => updated > queued,
+            _ => false,
+        }
+    }
+}
+
+pub fn new_key() -> String {
+    format!("{:016x}{:016x}", rand::random::<u64>(), rand::random::<u64>())
+}
+
+/// Whether `error` means Splitwise couldn't be reached, so the write may work later.
+pub fn is_unreachable(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_connect() || e.is_timeout();
+        }
+        cause
+            .downcast_ref::<UpstreamError>()
+            .is_some_and(|e| matches!(e.status.as_u16(), 502..=504))
+    })
+}